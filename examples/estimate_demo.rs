@@ -51,7 +51,51 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         current_png = with_text;
     }
 
-    println!("\n=== All estimations are accurate! ===");
+    // 圧縮テキストと国際化テキスト（書き込み前の容量見積もり）
+    println!("\n## PNG zTXt / iTXt Chunk Estimation ##");
+    let description = "A long description that will be compressed. ".repeat(20);
+    println!(
+        "\nzTXt \"Description\" ({} bytes of text)",
+        description.len()
+    );
+    println!(
+        "  Upper bound:        {} bytes",
+        png::estimate_ztxt_chunk("Description", &description)
+    );
+    println!("\niTXt \"Title\": \"日本語のタイトル\"");
+    println!(
+        "  Estimated increase: {} bytes",
+        png::estimate_itxt_chunk("Title", "日本語のタイトル")
+    );
+
+    // ICCプロファイル
+    println!("\n## JPEG ICC Profile Estimation ##");
+    let icc_jpeg = fs::read("tests/test_data/jpeg/icc/icc_srgb.jpg")?;
+    let cleaned = jpeg::clean_metadata(&icc_jpeg)?;
+    let without_icc = jpeg::clean_metadata_with_options(
+        &icc_jpeg,
+        &jpeg::CleanOptions {
+            replace_srgb_icc: true,
+            ..Default::default()
+        },
+    )?;
+    let actual = cleaned.len() - without_icc.len();
+    let profile_len = actual - 18;
+    let estimated = jpeg::estimate_icc_profile(profile_len).ok_or("profile too large")?;
+
+    println!("\nsRGB profile ({} bytes)", profile_len);
+    println!("  Estimated increase: {} bytes", estimated);
+    println!("  Actual increase:    {} bytes", actual);
+    println!("  Match: {}", if estimated == actual { "✓" } else { "✗" });
+    if let Some(size) = jpeg::estimate_icc_profile(100 * 1024) {
+        println!("  A 100KB profile needs {size} bytes in multiple APP2 segments");
+    }
+    if jpeg::estimate_icc_profile(20 * 1024 * 1024).is_none() {
+        println!("  A 20MB profile needs more than 255 segments and cannot be embedded");
+    }
+
+    // zTXtは圧縮後のサイズが分からないため上限値、それ以外は正確な値
+    println!("\n=== Comment, tEXt, iTXt and ICC estimates are exact; the zTXt estimate is an upper bound ===");
 
     Ok(())
 }
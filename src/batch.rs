@@ -0,0 +1,278 @@
+//! 多数の画像をまとめて処理するための関数
+
+use crate::cancel::{self, CancellationToken};
+use crate::{jpeg, png, Error, Processor, ProgressCallback};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// 入力を順に処理し、IDと結果の組を返すイテレーターを作成します
+///
+/// # Arguments
+/// * `items` - IDと入力の組（ファイルパスと画像データなど）
+/// * `op` - 入力ごとに呼び出す処理
+///
+/// # Returns
+/// 入力と同じ順で`(ID, 処理結果)`を返すイテレーター
+///
+/// # Details
+/// - 処理は遅延評価で、イテレーターを進めたときに1件ずつ実行します
+/// - 失敗した入力があっても中断せず、エラーを結果として返して次の入力に進みます
+/// - 最初のエラーで止める場合は、呼び出し側で`take_while`などを使用してください
+pub fn process<Id, Input, Output, I, F>(
+    items: I,
+    mut op: F,
+) -> impl Iterator<Item = (Id, Result<Output, Error>)>
+where
+    I: IntoIterator<Item = (Id, Input)>,
+    F: FnMut(Input) -> Result<Output, Error>,
+{
+    items.into_iter().map(move |(id, input)| {
+        let result = op(input);
+        (id, result)
+    })
+}
+
+/// 中断を確認しながら入力を順に処理するイテレーターを作成します
+///
+/// # Arguments
+/// * `items` - IDと入力の組（ファイルパスと画像データなど）
+/// * `cancellation` - 処理を中断するためのトークン
+/// * `op` - 入力ごとに呼び出す処理
+///
+/// # Returns
+/// 入力と同じ順で`(ID, 処理結果)`を返すイテレーター
+///
+/// # Details
+/// - [`process`]と同じく遅延評価で、失敗した入力があっても次の入力に進みます
+/// - 各入力の処理の前にトークンを確認し、中断されていればその入力の結果として
+///   [`Error::Cancelled`]を返して終了します。`op`は呼び出しません
+/// - 1件の処理の途中で中断する場合は、同じトークンを`op`内のオプションにも設定してください
+pub fn process_with_cancellation<Id, Input, Output, I, F>(
+    items: I,
+    cancellation: CancellationToken,
+    mut op: F,
+) -> impl Iterator<Item = (Id, Result<Output, Error>)>
+where
+    I: IntoIterator<Item = (Id, Input)>,
+    F: FnMut(Input) -> Result<Output, Error>,
+{
+    let mut cancelled = false;
+    items.into_iter().map_while(move |(id, input)| {
+        if cancelled {
+            return None;
+        }
+        if cancellation.is_cancelled() {
+            cancelled = true;
+            return Some((id, Err(Error::Cancelled)));
+        }
+        Some((id, op(input)))
+    })
+}
+
+/// [`clean_tree`]のオプション
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    /// JPEGの軽量化のオプション
+    pub jpeg_options: jpeg::CleanOptions,
+    /// PNGの軽量化のオプション
+    pub png_options: png::CleanOptions,
+    /// ファイルを書き換えず、削減できるバイト数だけを集計する
+    pub dry_run: bool,
+    /// 処理を中断するためのトークン
+    ///
+    /// ファイルの処理の前に確認し、中断時は[`Error::Cancelled`]を返します。
+    /// `jpeg_options`・`png_options`にトークンがない場合はそれらにも設定し、
+    /// ファイルの処理の途中でも中断します。
+    pub cancellation: Option<CancellationToken>,
+    /// ファイルごとの軽量化の進捗を受け取るコールバック
+    ///
+    /// 処理中のファイルについて[`Processor::clean_with_progress`]と同じ内容を通知します。
+    /// 新しいファイルの処理は`ValidateInput`の`processed == 0`の通知から始まります。
+    pub progress: Option<ProgressCallback>,
+}
+
+/// [`clean_tree`]の集計レポート
+#[derive(Debug, Default)]
+pub struct TreeReport {
+    /// パターンに一致して処理したファイル数
+    pub files_processed: usize,
+    /// 書き換えたファイル数（`dry_run`の場合は書き換える対象のファイル数）
+    pub files_touched: usize,
+    /// 削減したバイト数の合計（小さくなったファイルのみ）
+    pub bytes_saved: u64,
+    /// 増加したバイト数の合計（EXIFの再構築などで大きくなったファイルのみ）
+    pub bytes_grown: u64,
+    /// 処理できなかったファイルとそのエラー
+    pub failures: Vec<(PathBuf, Error)>,
+}
+
+/// ディレクトリを再帰的にたどり、パターンに一致する画像を軽量化します
+///
+/// # Arguments
+/// * `root` - 起点のディレクトリ
+/// * `filter` - 対象とするファイルのパターン（`root`からの相対パスと照合）
+/// * `options` - 軽量化のオプション
+///
+/// # Returns
+/// * `Ok(TreeReport)` - 処理したファイル数、削減したバイト数、失敗したファイルの集計
+/// * `Err(Error)` - `root`を読み取れない場合や、処理が中断された場合
+///
+/// # Details
+/// - 形式はファイルの内容から判定します。JPEG・PNG以外のファイルは失敗として集計します
+/// - 内容が変わらないファイルは書き換えません
+/// - 同じディレクトリの一時ファイルに書き込んでから置き換えるため、途中で中断しても
+///   元のファイルが壊れることはありません。元のファイルのパーミッションを引き継ぎます
+/// - シンボリックリンクはたどりません。サブディレクトリを読み取れない場合も失敗として集計し、処理を続けます
+/// - 中断された場合は、それまでに置き換えたファイルはそのまま残ります
+pub fn clean_tree(
+    root: impl AsRef<Path>,
+    filter: &GlobSet,
+    options: &TreeOptions,
+) -> Result<TreeReport, Error> {
+    let root = root.as_ref();
+    let mut processor =
+        Processor::with_options(options.jpeg_options.clone(), options.png_options.clone());
+    processor.set_default_cancellation(options.cancellation.as_ref());
+    let mut report = TreeReport::default();
+
+    let mut pending = vec![root.to_path_buf()];
+    let mut first = true;
+    while let Some(dir) = pending.pop() {
+        let entries = match read_dir_sorted(&dir) {
+            Ok(entries) => entries,
+            // 起点のディレクトリを読み取れない場合はエラー
+            Err(err) if first => return Err(err),
+            Err(err) => {
+                report.failures.push((dir, err));
+                continue;
+            }
+        };
+        first = false;
+
+        // 名前順に処理するため、サブディレクトリは逆順に積む
+        let mut subdirs = Vec::new();
+        for (path, file_type) in entries {
+            if file_type.is_dir() {
+                subdirs.push(path);
+                continue;
+            }
+            if !file_type.is_file() {
+                continue;
+            }
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            if !filter.is_match(relative) {
+                continue;
+            }
+
+            cancel::check(options.cancellation.as_ref())?;
+            report.files_processed += 1;
+            match clean_file(&mut processor, &path, options) {
+                // 処理の途中で中断された場合は失敗として集計せずに終了する
+                Err(Error::Cancelled) => return Err(Error::Cancelled),
+                Ok(Some((before, after))) => {
+                    report.files_touched += 1;
+                    report.bytes_saved += before.saturating_sub(after) as u64;
+                    report.bytes_grown += after.saturating_sub(before) as u64;
+                }
+                Ok(None) => {}
+                Err(err) => report.failures.push((path, err)),
+            }
+        }
+        pending.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(report)
+}
+
+/// ディレクトリのエントリを名前順に読み取ります
+fn read_dir_sorted(dir: &Path) -> Result<Vec<(PathBuf, fs::FileType)>, Error> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| {
+            let entry = entry?;
+            Ok((entry.path(), entry.file_type()?))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
+
+/// 1つのファイルを軽量化し、書き換えた場合は前後のバイト数を返します
+fn clean_file(
+    processor: &mut Processor,
+    path: &Path,
+    options: &TreeOptions,
+) -> Result<Option<(usize, usize)>, Error> {
+    let data = fs::read(path)?;
+    let cleaned = processor.clean_with_progress(&data, |value| {
+        if let Some(progress) = &options.progress {
+            progress.report(value);
+        }
+    })?;
+    if cleaned == data.as_slice() {
+        return Ok(None);
+    }
+    let sizes = (data.len(), cleaned.len());
+    if !options.dry_run {
+        replace_file(path, cleaned)?;
+    }
+    Ok(Some(sizes))
+}
+
+/// 一時ファイル名が使用中の場合に名前を変えて作成を試みる回数
+const MAX_TEMP_FILE_ATTEMPTS: u32 = 100;
+
+/// 一時ファイルに書き込んでから名前を変更し、ファイルの内容を置き換えます
+fn replace_file(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidFormat(format!("Invalid path: {}", path.display())))?;
+    let permissions = fs::metadata(path)?.permissions();
+
+    // 既存のファイルやシンボリックリンクは開かず、名前が使用中なら別の名前で作り直す
+    let mut attempt = 0;
+    let (mut file, temp_path) = loop {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.{attempt}.tmp", std::process::id()));
+        let temp_path = path.with_file_name(temp_name);
+        match create_temp_file(&temp_path, &permissions) {
+            Ok(file) => break (file, temp_path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                attempt += 1;
+                if attempt == MAX_TEMP_FILE_ATTEMPTS {
+                    return Err(e.into());
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    let mut write = || -> Result<(), Error> {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::set_permissions(&temp_path, permissions.clone())?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+/// 一時ファイルを新規に作成します（UNIXでは作成時点で元のファイルの権限にする）
+fn create_temp_file(path: &Path, permissions: &fs::Permissions) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(permissions.mode());
+    }
+    #[cfg(not(unix))]
+    let _ = permissions;
+    options.open(path)
+}
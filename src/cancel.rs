@@ -0,0 +1,89 @@
+use crate::progress::PROGRESS_INTERVAL;
+use crate::Error;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// 処理を中断するためのトークン
+///
+/// クローンしたトークンは同じ状態を共有します。別のスレッドから[`cancel`](Self::cancel)を
+/// 呼び出すと、処理は次の確認位置で[`Error::Cancelled`]を返して終了します。
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// 中断されていないトークンを作成します
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 処理の中断を要求します
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// 中断が要求されているか
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 中断が要求されていればエラーを返します
+pub(crate) fn check(token: Option<&CancellationToken>) -> Result<(), Error> {
+    if token.is_some_and(CancellationToken::is_cancelled) {
+        return Err(Error::Cancelled);
+    }
+    Ok(())
+}
+
+/// 読み取りのたびに中断を確認し、読み取り位置を通知するリーダー
+///
+/// デコーダーは画像データを少しずつ読み取るため、デコードの途中でも中断できます。
+/// 読み取り位置は[`PROGRESS_INTERVAL`]バイトごとと末尾で通知します。
+pub(crate) struct CancellableReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    reported: usize,
+    cancellation: Option<&'a CancellationToken>,
+    progress: Option<&'a mut dyn FnMut(usize)>,
+}
+
+impl<'a> CancellableReader<'a> {
+    pub(crate) fn new(data: &'a [u8], cancellation: Option<&'a CancellationToken>) -> Self {
+        Self {
+            data,
+            position: 0,
+            reported: 0,
+            cancellation,
+            progress: None,
+        }
+    }
+
+    /// 読み取り位置を受け取るコールバックを設定します
+    pub(crate) fn with_progress(mut self, progress: &'a mut dyn FnMut(usize)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+impl Read for CancellableReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self
+            .cancellation
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            return Err(io::Error::other("cancelled"));
+        }
+        let read = (&self.data[self.position..]).read(buf)?;
+        self.position += read;
+        if let Some(progress) = self.progress.as_mut() {
+            if self.position - self.reported >= PROGRESS_INTERVAL
+                || (read > 0 && self.position == self.data.len())
+            {
+                self.reported = self.position;
+                progress(self.position);
+            }
+        }
+        Ok(read)
+    }
+}
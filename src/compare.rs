@@ -0,0 +1,66 @@
+use crate::etag::{self, EtagOptions};
+use crate::regions::{map_regions, RegionKind};
+use crate::{Error, ImageFormat};
+use sha2::{Digest, Sha256};
+
+/// 2つの画像の画像データが同じかをデコードせずに判定します
+///
+/// # Arguments
+/// * `a` - 比較する画像のバイトデータ（JPEGまたはPNG）
+/// * `b` - 比較する画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// 画像データが同じ場合は`true`、異なる場合や解析できない場合は`false`
+///
+/// # Details
+/// - [`etag_with_options`](crate::etag_with_options)の`pixels_only`と同じ範囲
+///   （JPEGのフレーム・テーブル・スキャン、PNGのIHDR・PLTE・tRNS・IDAT）を比較します
+/// - PNGのIDATは連結したデータとして比較するため、分割位置が異なっても同じと判定します
+/// - 符号化したバイト列の比較のため、再圧縮した画像は画素が同じでも`false`になります
+pub fn pixels_equal(a: &[u8], b: &[u8]) -> bool {
+    match (pixels_digest(a), pixels_digest(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// 2つの画像のメタデータが同じかを判定します
+///
+/// # Arguments
+/// * `a` - 比較する画像のバイトデータ（JPEGまたはPNG）
+/// * `b` - 比較する画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// メタデータが同じ場合は`true`、異なる場合や解析できない場合は`false`
+///
+/// # Details
+/// - [`map_regions`](crate::map_regions)でメタデータに分類される範囲
+///   （JPEGのAPP・COMセグメント、PNGの付随チャンク）を、ファイル内の順に比較します
+/// - 画像データとEOI・IEND以降の末尾データは比較しません
+/// - 形式が異なる画像は`false`になります
+pub fn metadata_equal(a: &[u8], b: &[u8]) -> bool {
+    if ImageFormat::detect(a) != ImageFormat::detect(b) {
+        return false;
+    }
+
+    match (metadata_items(a), metadata_items(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// 画像データのダイジェストを計算します
+fn pixels_digest(data: &[u8]) -> Result<[u8; 32], Error> {
+    let mut hasher = Sha256::new();
+    etag::update_content(data, &EtagOptions { pixels_only: true }, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// メタデータの範囲のバイト列をファイル内の順に返します
+fn metadata_items(data: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    Ok(map_regions(data)?
+        .into_iter()
+        .filter(|region| region.kind == RegionKind::Metadata)
+        .map(|region| &data[region.range])
+        .collect())
+}
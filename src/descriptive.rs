@@ -0,0 +1,438 @@
+use crate::exif::{self, ByteOrder, IfdKind, TiffBuilder, XpTag};
+use crate::iptc::{self, IptcCharset};
+use crate::{jpeg, png, xmp, Error, ImageFormat};
+
+/// EXIFのImageDescription
+const TAG_IMAGE_DESCRIPTION: u16 = 0x010E;
+/// EXIFのArtist
+const TAG_ARTIST: u16 = 0x013B;
+/// EXIFのCopyright
+const TAG_COPYRIGHT: u16 = 0x8298;
+
+/// EXIF、XMP、IPTCを統合した説明情報
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Descriptive {
+    /// タイトル（XMPの`dc:title`、IPTCのObjectName、EXIFのXPTitle）
+    pub title: Option<String>,
+    /// 説明（EXIFのImageDescription、XMPの`dc:description`、IPTCのCaption-Abstract、EXIFのXPComment）
+    pub description: Option<String>,
+    /// キーワード（XMPの`dc:subject`、IPTCのKeywords、EXIFのXPKeywords）
+    pub keywords: Vec<String>,
+    /// 作成者（EXIFのArtist、XMPの`dc:creator`、IPTCのBy-line、EXIFのXPAuthor）
+    pub creator: Vec<String>,
+    /// 著作権表示（EXIFのCopyright、XMPの`dc:rights`、IPTCのCopyrightNotice）
+    pub copyright: Option<String>,
+}
+
+/// 説明情報の読み取りのオプション
+#[derive(Debug, Clone, Default)]
+pub struct DescriptiveOptions {
+    /// IPTCのCodedCharacterSet（1:90）に宣言がなく、UTF-8として正しくない値に使う文字コード
+    ///
+    /// 日本語環境で作成された古いIPTCには[`IptcCharset::ShiftJis`]を指定します。
+    pub iptc_charset: IptcCharset,
+}
+
+/// 説明情報の書き込みのオプション
+#[derive(Debug, Clone, Default)]
+pub struct DescriptiveWriteOptions {
+    /// Photoshopの旧形式のキャプション（画像リソース0x03F0）にも説明を書き込む
+    ///
+    /// XMPを参照しない古い編集システム向けです。説明が`None`の場合は既存のリソースを削除します。
+    /// `false`の場合、既存のリソースは変更せずに保持します。
+    pub legacy_caption: bool,
+}
+
+/// EXIF、XMP、IPTCからタイトル・説明・キーワードなどを読み取ります
+///
+/// # Arguments
+/// * `data` - JPEGまたはPNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Descriptive)` - 統合した説明情報（どのメタデータにもない項目は空）
+/// * `Err(Error)` - 対応していない形式の場合など
+///
+/// # Details
+/// Metadata Working Group（MWG）のガイドラインに従い、項目ごとに次の順で採用します。
+/// 1. EXIF（タイトルとキーワードはEXIFに対応する項目がありません）
+/// 2. IPTC（Photoshopの画像リソースに記録されたダイジェストと内容が一致しない場合）
+/// 3. XMP
+/// 4. IPTC
+/// 5. EXIFのWindowsのXP系タグ（エクスプローラーで付けた説明はここにのみ記録されます）
+/// 6. JPEGのDucky（説明と著作権表示のみ。「Web用に保存」で書き出した古い画像はここにのみ記録されます）
+///
+/// ダイジェストの不一致は、XMP非対応のアプリケーションがIPTCのみを変更したことを示します。
+/// PNGではeXIfチャンクとiTXtチャンクのXMPを参照します（PNGにIPTCはありません）。
+///
+/// IPTCの文字列はCodedCharacterSet（1:90）の宣言に従って復号します。
+/// 宣言がない場合の扱いは[`read_descriptive_with_options`]で指定できます。
+pub fn read_descriptive(data: &[u8]) -> Result<Descriptive, Error> {
+    read_descriptive_with_options(data, &DescriptiveOptions::default())
+}
+
+/// オプションを指定して説明情報を読み取ります
+///
+/// # Arguments
+/// * `data` - JPEGまたはPNG画像のバイトデータ
+/// * `options` - 読み取りのオプション
+///
+/// # Returns
+/// * `Ok(Descriptive)` - 統合した説明情報（どのメタデータにもない項目は空）
+/// * `Err(Error)` - 対応していない形式の場合など
+pub fn read_descriptive_with_options(
+    data: &[u8],
+    options: &DescriptiveOptions,
+) -> Result<Descriptive, Error> {
+    let (packet, mut iptc) = read_sources(data)?;
+    if let Some(iptc) = &mut iptc {
+        iptc.fallback_charset = options.iptc_charset;
+    }
+    let exif_text = |tag: u16| exif::read_text(data, IfdKind::Ifd0, tag);
+
+    // IPTCが変更されている場合はXMPより優先する
+    let iptc_changed = iptc.as_ref().is_some_and(|iptc| iptc.digest_mismatch);
+    let iptc_string = |id| iptc.as_ref().and_then(|iptc| iptc.string(id));
+    let iptc_strings = |id| {
+        iptc.as_ref()
+            .map(|iptc| iptc.strings(id))
+            .filter(|values| !values.is_empty())
+    };
+    let pick = |exif, iptc, xmp| reconcile(exif, iptc, xmp, iptc_changed);
+    let pick_list = |exif, iptc, xmp| reconcile(exif, iptc, xmp, iptc_changed);
+
+    // EXIFのArtistやXP系のタグは複数の値をセミコロンで区切る
+    let non_empty = |values: Vec<String>| (!values.is_empty()).then_some(values);
+    let split = |value: Option<String>| value.map(|value| split_list(&value)).and_then(non_empty);
+    let xp_text = |tag: XpTag| exif::read_xp_text(data, tag);
+    let ducky = match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => jpeg::read_ducky(data)?.unwrap_or_default(),
+        _ => jpeg::Ducky::default(),
+    };
+
+    Ok(Descriptive {
+        title: pick(
+            None,
+            iptc_string(iptc::DATASET_OBJECT_NAME),
+            xmp::read_lang_alt(&packet, "dc", "title"),
+        )
+        .or(xp_text(XpTag::Title)?),
+        description: pick(
+            exif_text(TAG_IMAGE_DESCRIPTION)?,
+            iptc_string(iptc::DATASET_CAPTION),
+            xmp::read_lang_alt(&packet, "dc", "description"),
+        )
+        .or(xp_text(XpTag::Comment)?)
+        .or(ducky.comment),
+        keywords: pick_list(
+            None,
+            iptc_strings(iptc::DATASET_KEYWORDS),
+            non_empty(xmp::read_array(&packet, "dc", "subject")),
+        )
+        .or(split(xp_text(XpTag::Keywords)?))
+        .unwrap_or_default(),
+        creator: pick_list(
+            split(exif_text(TAG_ARTIST)?),
+            iptc_strings(iptc::DATASET_BY_LINE),
+            non_empty(xmp::read_array(&packet, "dc", "creator")),
+        )
+        .or(split(xp_text(XpTag::Author)?))
+        .unwrap_or_default(),
+        copyright: pick(
+            exif_text(TAG_COPYRIGHT)?,
+            iptc_string(iptc::DATASET_COPYRIGHT_NOTICE),
+            xmp::read_lang_alt(&packet, "dc", "rights"),
+        )
+        .or(ducky.copyright),
+    })
+}
+
+/// キーワードの書き込み方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeywordMode {
+    /// 既存のキーワードに追加する（重複は除きます）
+    #[default]
+    Merge,
+    /// 既存のキーワードを置き換える
+    Replace,
+}
+
+/// XMPの`dc:subject`とIPTCのKeywordsを統合したキーワードを読み取ります
+///
+/// # Arguments
+/// * `data` - JPEGまたはPNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<String>)` - XMPのキーワードに続けて、XMPにないIPTCのキーワードを並べたもの
+/// * `Err(Error)` - 対応していない形式の場合など
+///
+/// どちらか一方にしかないキーワードも失われないよう、優先順位ではなく和集合を返します。
+/// 前後の空白を除いて同じ文字列のキーワードは1つにまとめます。
+pub fn read_keywords(data: &[u8]) -> Result<Vec<String>, Error> {
+    let (packet, iptc) = read_sources(data)?;
+    let mut keywords = xmp::read_array(&packet, xmp::NS_DC.0, "subject");
+    if let Some(iptc) = iptc {
+        keywords.extend(iptc.strings(iptc::DATASET_KEYWORDS));
+    }
+    Ok(unique_keywords(keywords))
+}
+
+/// IPTCの文字列データセットの文字コードを判定します
+///
+/// # Arguments
+/// * `data` - JPEGまたはPNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some(IptcCharset))` - CodedCharacterSet（1:90）で宣言された文字コード。
+///   宣言がない場合は、値がすべてUTF-8として正しければ[`IptcCharset::Utf8`]、
+///   そうでなければ[`IptcCharset::Latin1`]
+/// * `Ok(None)` - IPTCがない場合（PNGを含む）
+/// * `Err(Error)` - 対応していない形式の場合など
+pub fn read_iptc_charset(data: &[u8]) -> Result<Option<IptcCharset>, Error> {
+    let (_, iptc) = read_sources(data)?;
+    Ok(iptc.map(|iptc| iptc.charset()))
+}
+
+/// XMPの`dc:subject`とIPTCのKeywordsの両方にキーワードを書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `keywords` - 書き込むキーワード
+/// * `mode` - 既存のキーワードに追加するか、置き換えるか
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - キーワードを書き込んだJPEG画像データ
+/// * `Err(Error)` - JPEGではない場合など
+///
+/// # Details
+/// - [`KeywordMode::Merge`]では[`read_keywords`]の結果に新しいキーワードを追加します
+/// - 両方のブロックに同じキーワードを同じ順序で書き込み、IPTCのダイジェストを更新します
+/// - XMPやIPTCがない場合は作成し、キーワード以外の値は変更しません
+pub fn write_keywords(
+    data: &[u8],
+    keywords: &[String],
+    mode: KeywordMode,
+) -> Result<Vec<u8>, Error> {
+    if ImageFormat::detect(data) != Some(ImageFormat::Jpeg) {
+        return Err(Error::InvalidFormat(
+            "Writing keywords requires a JPEG file".to_string(),
+        ));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    jpeg::validate_jpeg_decode(data, None)?;
+
+    let keywords = match mode {
+        KeywordMode::Merge => {
+            let mut merged = read_keywords(data)?;
+            merged.extend(keywords.iter().cloned());
+            unique_keywords(merged)
+        }
+        KeywordMode::Replace => unique_keywords(keywords.to_vec()),
+    };
+
+    let mut output = data.to_vec();
+    let packet = jpeg::read_xmp(data)?.unwrap_or_default();
+    if !packet.is_empty() || !keywords.is_empty() {
+        let packet = xmp::set_array(&packet, xmp::NS_DC, "subject", "rdf:Bag", &keywords)?;
+        output = jpeg::replace_xmp_segment(&output, &packet)?;
+    }
+    output = iptc::write_jpeg_iptc(&output, &[(iptc::DATASET_KEYWORDS, keywords)], None)?;
+
+    // 出力が有効なJPEGか検証
+    jpeg::validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// セミコロン区切りの値を分割します（前後の空白を除き、空の値は除きます）
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// 前後の空白を除き、空のキーワードと重複を取り除きます（最初の出現順を保持）
+fn unique_keywords(keywords: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(keywords.len());
+    for keyword in keywords {
+        let keyword = keyword.trim();
+        if !keyword.is_empty() && !unique.iter().any(|k| k == keyword) {
+            unique.push(keyword.to_string());
+        }
+    }
+    unique
+}
+
+/// XMPパケット（ない場合は空文字列）とIPTCを読み取ります
+fn read_sources(data: &[u8]) -> Result<(String, Option<iptc::Iptc>), Error> {
+    let (packet, iptc) = match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => (jpeg::read_xmp(data)?, iptc::read_jpeg_iptc(data)?),
+        Some(ImageFormat::Png) => (png::read_xmp(data)?, None),
+        None => {
+            return Err(Error::InvalidFormat(
+                "Unsupported format for descriptive metadata".to_string(),
+            ))
+        }
+    };
+    Ok((packet.unwrap_or_default(), iptc))
+}
+
+/// EXIF、XMP、IPTCのすべてに説明情報を書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `descriptive` - 書き込む説明情報
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 説明情報を書き込んだJPEG画像データ
+/// * `Err(Error)` - JPEGではない場合、XMPパケットが1つのセグメントに収まらない場合など
+///
+/// # Details
+/// - `descriptive`を書き込み後の状態として扱い、`None`や空の項目は3つのブロックすべてから削除します
+/// - EXIFにはImageDescription、Artist（作成者を`; `で連結）、Copyrightを書き込みます。
+///   古い値が読み取られないよう、対応するXP系のタグ（XPTitle、XPComment、XPKeywords、XPAuthor）は削除します
+/// - XMPには`dc:title`、`dc:description`、`dc:rights`（`x-default`）、`dc:subject`、`dc:creator`を書き込みます
+/// - IPTCにはObjectName、Caption-Abstract、Keywords、By-line、CopyrightNoticeを書き込み、
+///   ダイジェストを更新してXMPと同期した状態にします
+/// - 古い値が読み取られないよう、Ducky（APP12）のコメントと著作権表示は削除します（画質は保持します）
+/// - ブロックがない場合は必要に応じて作成し、その他のタグ・プロパティ・データセットは保持します
+pub fn write_descriptive(data: &[u8], descriptive: &Descriptive) -> Result<Vec<u8>, Error> {
+    write_descriptive_with_options(data, descriptive, &DescriptiveWriteOptions::default())
+}
+
+/// オプションを指定して説明情報を書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `descriptive` - 書き込む説明情報
+/// * `options` - 書き込みのオプション
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 説明情報を書き込んだJPEG画像データ
+/// * `Err(Error)` - JPEGではない場合、XMPパケットが1つのセグメントに収まらない場合など
+///
+/// # Details
+/// [`write_descriptive`]に加え、[`legacy_caption`](DescriptiveWriteOptions::legacy_caption)を
+/// 指定した場合はIPTCと同じAPP13の画像リソースに旧形式のキャプションを書き込みます。
+/// キャプションはWindows-1252（表せない文字を含む場合はUTF-8）のパスカル文字列で、
+/// 255バイトを超える部分は切り詰めます。IPTCのダイジェスト（0x0425）も更新します。
+pub fn write_descriptive_with_options(
+    data: &[u8],
+    descriptive: &Descriptive,
+    options: &DescriptiveWriteOptions,
+) -> Result<Vec<u8>, Error> {
+    if ImageFormat::detect(data) != Some(ImageFormat::Jpeg) {
+        return Err(Error::InvalidFormat(
+            "Writing descriptive metadata requires a JPEG file".to_string(),
+        ));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    jpeg::validate_jpeg_decode(data, None)?;
+
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+
+    // EXIF
+    let exif_texts = [
+        (TAG_IMAGE_DESCRIPTION, text(&descriptive.description)),
+        (TAG_ARTIST, descriptive.creator.join("; ")),
+        (TAG_COPYRIGHT, text(&descriptive.copyright)),
+    ];
+    let has_exif = !exif::entries_raw(data)?.is_empty();
+    let mut output = if has_exif || exif_texts.iter().any(|(_, value)| !value.is_empty()) {
+        jpeg::rewrite_exif_segment(data, |tiff| {
+            let (byte_order, mut builder) = match tiff {
+                Some(tiff) => exif::to_builder(tiff)
+                    .ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string()))?,
+                None => (ByteOrder::LittleEndian, TiffBuilder::default()),
+            };
+            for (tag, value) in &exif_texts {
+                exif::apply_text(&mut builder, IfdKind::Ifd0, *tag, value)?;
+            }
+            for tag in [XpTag::Title, XpTag::Comment, XpTag::Keywords, XpTag::Author] {
+                exif::apply_xp_text(&mut builder, tag, "")?;
+            }
+            Ok(builder.build(byte_order))
+        })?
+    } else {
+        data.to_vec()
+    };
+
+    // XMP
+    let packet = jpeg::read_xmp(&output)?.unwrap_or_default();
+    let is_empty = descriptive.title.is_none()
+        && descriptive.description.is_none()
+        && descriptive.copyright.is_none()
+        && descriptive.keywords.is_empty()
+        && descriptive.creator.is_empty();
+    if !packet.is_empty() || !is_empty {
+        let packet = xmp::set_lang_alt(&packet, xmp::NS_DC, "title", descriptive.title.as_deref())?;
+        let packet = xmp::set_lang_alt(
+            &packet,
+            xmp::NS_DC,
+            "description",
+            descriptive.description.as_deref(),
+        )?;
+        let packet = xmp::set_lang_alt(
+            &packet,
+            xmp::NS_DC,
+            "rights",
+            descriptive.copyright.as_deref(),
+        )?;
+        let packet = xmp::set_array(
+            &packet,
+            xmp::NS_DC,
+            "subject",
+            "rdf:Bag",
+            &descriptive.keywords,
+        )?;
+        let packet = xmp::set_array(
+            &packet,
+            xmp::NS_DC,
+            "creator",
+            "rdf:Seq",
+            &descriptive.creator,
+        )?;
+        output = jpeg::replace_xmp_segment(&output, &packet)?;
+    }
+
+    // IPTC
+    let single = |value: &Option<String>| value.iter().cloned().collect::<Vec<_>>();
+    output = iptc::write_jpeg_iptc(
+        &output,
+        &[
+            (iptc::DATASET_OBJECT_NAME, single(&descriptive.title)),
+            (iptc::DATASET_KEYWORDS, descriptive.keywords.clone()),
+            (iptc::DATASET_BY_LINE, descriptive.creator.clone()),
+            (
+                iptc::DATASET_COPYRIGHT_NOTICE,
+                single(&descriptive.copyright),
+            ),
+            (iptc::DATASET_CAPTION, single(&descriptive.description)),
+        ],
+        options
+            .legacy_caption
+            .then(|| descriptive.description.as_deref().unwrap_or_default()),
+    )?;
+
+    // Ducky
+    output = jpeg::remove_ducky_text(&output)?;
+
+    // 出力が有効なJPEGか検証
+    jpeg::validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// MWGの優先順位でEXIF、IPTC、XMPの値を選びます
+fn reconcile<T>(exif: Option<T>, iptc: Option<T>, xmp: Option<T>, iptc_changed: bool) -> Option<T> {
+    if exif.is_some() {
+        return exif;
+    }
+    if iptc_changed && iptc.is_some() {
+        return iptc;
+    }
+    xmp.or(iptc)
+}
@@ -0,0 +1,91 @@
+use crate::{exif, iptc, jpeg, png, Error, ImageFormat};
+
+/// 画像に埋め込まれたバイナリデータ
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmbeddedAssets {
+    /// ICCプロファイル（JPEGは分割されたAPP2を連結、PNGはiCCPを展開したもの）
+    pub icc: Option<Vec<u8>>,
+    /// EXIFのIFD1に格納されたサムネイル画像
+    pub exif_thumbnail: Option<Vec<u8>>,
+    /// 標準XMPパケット（拡張XMPは含みません）
+    pub xmp: Option<Vec<u8>>,
+    /// IPTC-IIMのデータセット列（JPEGのAPP13のみ）
+    pub iptc: Option<Vec<u8>>,
+    /// C2PAマニフェストストア（JUMBFスーパーボックス）
+    pub c2pa: Option<Vec<u8>>,
+    /// MPFが参照する副画像（JPEGのみ、代表画像を除く）
+    pub mpf_images: Vec<Vec<u8>>,
+}
+
+/// 画像に埋め込まれたバイナリデータを1回の解析でまとめて取り出します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// * `Ok(EmbeddedAssets)` - 取り出したデータ（存在しないものは`None`または空）
+/// * `Err(Error)` - 対応していない形式や構造が壊れている場合
+///
+/// # Details
+/// 個別の読み取り関数と異なり、画像のデコードによる検証は行いません。
+/// 壊れていて復元できないデータ（連番が矛盾するICCセグメントなど）は存在しないものとして扱います。
+/// PNGのC2PAマニフェストはcaBXチャンクから読み取ります。
+pub fn extract_embedded(data: &[u8]) -> Result<EmbeddedAssets, Error> {
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => extract_jpeg(data),
+        Some(ImageFormat::Png) => extract_png(data),
+        None => Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    }
+}
+
+/// JPEGのセグメントから取り出します
+fn extract_jpeg(data: &[u8]) -> Result<EmbeddedAssets, Error> {
+    let (segments, _) = jpeg::parse_segments(data)?;
+
+    let mut assets = EmbeddedAssets {
+        icc: jpeg::assemble_icc_profile(&segments).ok().flatten(),
+        iptc: iptc::read_jpeg_iim(&segments),
+        c2pa: jpeg::read_c2pa_from_segments(&segments),
+        mpf_images: jpeg::read_mpf_images(data, &segments),
+        ..Default::default()
+    };
+
+    for segment in segments.iter().filter(|segment| segment.marker == 0xE1) {
+        let payload = segment.payload();
+        if let Some(tiff) = payload.strip_prefix(b"Exif\0\0") {
+            if assets.exif_thumbnail.is_none() {
+                assets.exif_thumbnail = exif::thumbnail(tiff).map(<[u8]>::to_vec);
+            }
+        } else if let Some(packet) = payload.strip_prefix(b"http://ns.adobe.com/xap/1.0/\0") {
+            if assets.xmp.is_none() {
+                assets.xmp = Some(packet.to_vec());
+            }
+        }
+    }
+
+    Ok(assets)
+}
+
+/// PNGのチャンクから取り出します
+fn extract_png(data: &[u8]) -> Result<EmbeddedAssets, Error> {
+    let mut assets = EmbeddedAssets::default();
+
+    // 途切れたチャンク以降は読み取らない
+    for chunk in png::chunks(data)?.map_while(Result::ok) {
+        match &chunk.chunk_type {
+            b"iCCP" if assets.icc.is_none() => assets.icc = png::iccp_profile(chunk.data),
+            b"eXIf" if assets.exif_thumbnail.is_none() => {
+                assets.exif_thumbnail = exif::thumbnail(chunk.data).map(<[u8]>::to_vec);
+            }
+            b"iTXt" if assets.xmp.is_none() => {
+                assets.xmp = png::decode_text_chunk(&chunk)
+                    .filter(|text| text.keyword == png::XMP_KEYWORD)
+                    .map(|text| text.text.into_bytes());
+            }
+            b"caBX" if assets.c2pa.is_none() => assets.c2pa = Some(chunk.data.to_vec()),
+            _ => {}
+        }
+    }
+
+    Ok(assets)
+}
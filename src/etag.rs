@@ -0,0 +1,116 @@
+use crate::{jpeg, png, Error, ImageFormat};
+use sha2::{Digest, Sha256};
+
+/// ETag生成のオプション
+#[derive(Debug, Clone, Default)]
+pub struct EtagOptions {
+    /// 画素の復元に必要なデータのみを対象にする
+    ///
+    /// `true`の場合、EXIFやICCプロファイルなどのメタデータが変わってもETagは変化しません。
+    pub pixels_only: bool,
+}
+
+/// 画像の内容に基づくETagを生成します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// * `Ok(String)` - `W/"<32桁の16進数>"`形式の弱いETag
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// - コメント（COM）、テキストチャンク（tEXt/zTXt/iTXt）、タイムスタンプ（tIME）、
+///   EOI/IEND以降の末尾データは対象外
+/// - バイト列が異なっても同じ値になり得るため、弱いETagとして返します
+pub fn etag(data: &[u8]) -> Result<String, Error> {
+    etag_with_options(data, &EtagOptions::default())
+}
+
+/// オプションを指定して画像の内容に基づくETagを生成します
+pub fn etag_with_options(data: &[u8], options: &EtagOptions) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    hasher.update(if options.pixels_only {
+        b"pixels\0".as_slice()
+    } else {
+        b"normalized\0".as_slice()
+    });
+
+    update_content(data, options, &mut hasher)?;
+
+    let digest = hasher.finalize();
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+
+    Ok(format!("W/\"{hex}\""))
+}
+
+/// 画像の内容をハッシュに追加します
+pub(crate) fn update_content(
+    data: &[u8],
+    options: &EtagOptions,
+    hasher: &mut Sha256,
+) -> Result<(), Error> {
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => hash_jpeg(data, options, hasher),
+        Some(ImageFormat::Png) => hash_png(data, options, hasher),
+        None => Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    }
+}
+
+/// JPEGのハッシュ対象を追加します
+fn hash_jpeg(data: &[u8], options: &EtagOptions, hasher: &mut Sha256) -> Result<(), Error> {
+    let (segments, sos_pos) = jpeg::parse_segments(data)?;
+    let sos_pos = sos_pos.ok_or_else(|| Error::ParseError("SOS marker not found".to_string()))?;
+
+    for segment in &segments {
+        let include = if options.pixels_only {
+            // フレーム・テーブル・リスタート間隔とAdobe変換フラグ
+            matches!(segment.marker, 0xC0..=0xCF | 0xDB | 0xDD)
+                || (segment.marker == 0xEE && segment.payload().starts_with(b"Adobe"))
+        } else {
+            segment.marker != 0xFE
+        };
+
+        if include {
+            update_item(hasher, &[segment.marker], segment.payload());
+        }
+    }
+
+    // SOSからEOIまでのスキャンデータ（末尾データは除く）
+    let end = jpeg::walk_scans(data, sos_pos)
+        .eoi_end
+        .unwrap_or(data.len());
+    update_item(hasher, b"scan", &data[sos_pos..end]);
+
+    Ok(())
+}
+
+/// PNGのハッシュ対象を追加します
+fn hash_png(data: &[u8], options: &EtagOptions, hasher: &mut Sha256) -> Result<(), Error> {
+    let chunks = png::parse_chunks(data)?;
+
+    // IDATは分割位置に依存しないよう連結したデータとして扱う
+    let mut idat = Sha256::new();
+
+    for chunk in &chunks {
+        match &chunk.chunk_type {
+            b"IDAT" => idat.update(chunk.data),
+            b"IEND" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME" => {}
+            b"IHDR" | b"PLTE" | b"tRNS" => update_item(hasher, &chunk.chunk_type, chunk.data),
+            _ if !options.pixels_only => update_item(hasher, &chunk.chunk_type, chunk.data),
+            _ => {}
+        }
+    }
+
+    update_item(hasher, b"IDAT", &idat.finalize());
+
+    Ok(())
+}
+
+/// 種別と長さを付けてハッシュに追加します（連結による曖昧さを避けるため）
+pub(crate) fn update_item(hasher: &mut Sha256, kind: &[u8], bytes: &[u8]) {
+    hasher.update((kind.len() as u32).to_be_bytes());
+    hasher.update(kind);
+    hasher.update((bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
@@ -0,0 +1,1677 @@
+//! EXIF（TIFF構造）の解析と書き換え
+
+use crate::{jpeg, png, DecodedText, Error, ImageFormat, Utf8Policy};
+
+/// IFDへのポインタを表すタグ
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_INTEROP_IFD: u16 = 0xA005;
+/// オリエンテーション
+pub(crate) const TAG_ORIENTATION: u16 = 0x0112;
+/// 解像度のタグ
+pub(crate) const TAG_X_RESOLUTION: u16 = 0x011A;
+pub(crate) const TAG_Y_RESOLUTION: u16 = 0x011B;
+pub(crate) const TAG_RESOLUTION_UNIT: u16 = 0x0128;
+/// 色再現に関わるタグ
+pub(crate) const TAG_WHITE_POINT: u16 = 0x013E;
+pub(crate) const TAG_PRIMARY_CHROMATICITIES: u16 = 0x013F;
+pub(crate) const TAG_COLOR_SPACE: u16 = 0xA001;
+pub(crate) const TAG_GAMMA: u16 = 0xA500;
+/// EXIF 3.0で追加されたUTF-8文字列のデータ型
+const TYPE_UTF8: u16 = 129;
+
+/// 日時と時差のタグ
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_DATE_TIME_DIGITIZED: u16 = 0x9004;
+const TAG_OFFSET_TIME: u16 = 0x9010;
+const TAG_OFFSET_TIME_ORIGINAL: u16 = 0x9011;
+const TAG_OFFSET_TIME_DIGITIZED: u16 = 0x9012;
+/// GPS IFDのバージョンと緯度・経度・高度
+const TAG_GPS_VERSION_ID: u16 = 0x0000;
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+const TAG_GPS_ALTITUDE_REF: u16 = 0x0005;
+const TAG_GPS_ALTITUDE: u16 = 0x0006;
+const TAG_GPS_SPEED_REF: u16 = 0x000C;
+const TAG_GPS_SPEED: u16 = 0x000D;
+const TAG_GPS_IMG_DIRECTION_REF: u16 = 0x0010;
+const TAG_GPS_IMG_DIRECTION: u16 = 0x0011;
+/// IFD1の圧縮方式（6はJPEG圧縮のサムネイル）
+const TAG_COMPRESSION: u16 = 0x0103;
+const COMPRESSION_JPEG: u16 = 6;
+/// IFD1のサムネイル位置と長さ
+const TAG_THUMBNAIL_OFFSET: u16 = 0x0201;
+const TAG_THUMBNAIL_LENGTH: u16 = 0x0202;
+
+/// IFD0・Exif IFDのタグ名（ExifToolと同じ名前、タグ番号順）
+const TAG_NAMES: &[(u16, &str)] = &[
+    (0x00FE, "SubfileType"),
+    (0x0100, "ImageWidth"),
+    (0x0101, "ImageHeight"),
+    (0x0102, "BitsPerSample"),
+    (0x0103, "Compression"),
+    (0x0106, "PhotometricInterpretation"),
+    (0x010E, "ImageDescription"),
+    (0x010F, "Make"),
+    (0x0110, "Model"),
+    (0x0111, "StripOffsets"),
+    (0x0112, "Orientation"),
+    (0x0115, "SamplesPerPixel"),
+    (0x0116, "RowsPerStrip"),
+    (0x0117, "StripByteCounts"),
+    (0x011A, "XResolution"),
+    (0x011B, "YResolution"),
+    (0x011C, "PlanarConfiguration"),
+    (0x0128, "ResolutionUnit"),
+    (0x012D, "TransferFunction"),
+    (0x0131, "Software"),
+    (0x0132, "ModifyDate"),
+    (0x013B, "Artist"),
+    (0x013E, "WhitePoint"),
+    (0x013F, "PrimaryChromaticities"),
+    (0x0201, "ThumbnailOffset"),
+    (0x0202, "ThumbnailLength"),
+    (0x0211, "YCbCrCoefficients"),
+    (0x0212, "YCbCrSubSampling"),
+    (0x0213, "YCbCrPositioning"),
+    (0x0214, "ReferenceBlackWhite"),
+    (0x4746, "Rating"),
+    (0x4749, "RatingPercent"),
+    (0x8298, "Copyright"),
+    (0x829A, "ExposureTime"),
+    (0x829D, "FNumber"),
+    (0x8769, "ExifOffset"),
+    (0x8822, "ExposureProgram"),
+    (0x8824, "SpectralSensitivity"),
+    (0x8825, "GPSInfo"),
+    (0x8827, "ISO"),
+    (0x8828, "Opto-ElectricConvFactor"),
+    (0x8830, "SensitivityType"),
+    (0x8831, "StandardOutputSensitivity"),
+    (0x8832, "RecommendedExposureIndex"),
+    (0x8833, "ISOSpeed"),
+    (0x9000, "ExifVersion"),
+    (0x9003, "DateTimeOriginal"),
+    (0x9004, "CreateDate"),
+    (0x9010, "OffsetTime"),
+    (0x9011, "OffsetTimeOriginal"),
+    (0x9012, "OffsetTimeDigitized"),
+    (0x9101, "ComponentsConfiguration"),
+    (0x9102, "CompressedBitsPerPixel"),
+    (0x9201, "ShutterSpeedValue"),
+    (0x9202, "ApertureValue"),
+    (0x9203, "BrightnessValue"),
+    (0x9204, "ExposureCompensation"),
+    (0x9205, "MaxApertureValue"),
+    (0x9206, "SubjectDistance"),
+    (0x9207, "MeteringMode"),
+    (0x9208, "LightSource"),
+    (0x9209, "Flash"),
+    (0x920A, "FocalLength"),
+    (0x9214, "SubjectArea"),
+    (0x927C, "MakerNote"),
+    (0x9286, "UserComment"),
+    (0x9290, "SubSecTime"),
+    (0x9291, "SubSecTimeOriginal"),
+    (0x9292, "SubSecTimeDigitized"),
+    (0x9C9B, "XPTitle"),
+    (0x9C9C, "XPComment"),
+    (0x9C9D, "XPAuthor"),
+    (0x9C9E, "XPKeywords"),
+    (0x9C9F, "XPSubject"),
+    (0xA000, "FlashpixVersion"),
+    (0xA001, "ColorSpace"),
+    (0xA002, "ExifImageWidth"),
+    (0xA003, "ExifImageHeight"),
+    (0xA004, "RelatedSoundFile"),
+    (0xA005, "InteropOffset"),
+    (0xA20B, "FlashEnergy"),
+    (0xA20E, "FocalPlaneXResolution"),
+    (0xA20F, "FocalPlaneYResolution"),
+    (0xA210, "FocalPlaneResolutionUnit"),
+    (0xA214, "SubjectLocation"),
+    (0xA215, "ExposureIndex"),
+    (0xA217, "SensingMethod"),
+    (0xA300, "FileSource"),
+    (0xA301, "SceneType"),
+    (0xA302, "CFAPattern"),
+    (0xA401, "CustomRendered"),
+    (0xA402, "ExposureMode"),
+    (0xA403, "WhiteBalance"),
+    (0xA404, "DigitalZoomRatio"),
+    (0xA405, "FocalLengthIn35mmFormat"),
+    (0xA406, "SceneCaptureType"),
+    (0xA407, "GainControl"),
+    (0xA408, "Contrast"),
+    (0xA409, "Saturation"),
+    (0xA40A, "Sharpness"),
+    (0xA40B, "DeviceSettingDescription"),
+    (0xA40C, "SubjectDistanceRange"),
+    (0xA420, "ImageUniqueID"),
+    (0xA430, "OwnerName"),
+    (0xA431, "SerialNumber"),
+    (0xA432, "LensInfo"),
+    (0xA433, "LensMake"),
+    (0xA434, "LensModel"),
+    (0xA435, "LensSerialNumber"),
+    (0xA460, "CompositeImage"),
+    (0xA500, "Gamma"),
+    (0xC4A5, "PrintIM"),
+];
+
+/// GPS IFDのタグ名（ExifToolと同じ名前、タグ番号順）
+const GPS_TAG_NAMES: &[(u16, &str)] = &[
+    (0x0000, "GPSVersionID"),
+    (0x0001, "GPSLatitudeRef"),
+    (0x0002, "GPSLatitude"),
+    (0x0003, "GPSLongitudeRef"),
+    (0x0004, "GPSLongitude"),
+    (0x0005, "GPSAltitudeRef"),
+    (0x0006, "GPSAltitude"),
+    (0x0007, "GPSTimeStamp"),
+    (0x0008, "GPSSatellites"),
+    (0x0009, "GPSStatus"),
+    (0x000A, "GPSMeasureMode"),
+    (0x000B, "GPSDOP"),
+    (0x000C, "GPSSpeedRef"),
+    (0x000D, "GPSSpeed"),
+    (0x000E, "GPSTrackRef"),
+    (0x000F, "GPSTrack"),
+    (0x0010, "GPSImgDirectionRef"),
+    (0x0011, "GPSImgDirection"),
+    (0x0012, "GPSMapDatum"),
+    (0x0013, "GPSDestLatitudeRef"),
+    (0x0014, "GPSDestLatitude"),
+    (0x0015, "GPSDestLongitudeRef"),
+    (0x0016, "GPSDestLongitude"),
+    (0x0017, "GPSDestBearingRef"),
+    (0x0018, "GPSDestBearing"),
+    (0x0019, "GPSDestDistanceRef"),
+    (0x001A, "GPSDestDistance"),
+    (0x001B, "GPSProcessingMethod"),
+    (0x001C, "GPSAreaInformation"),
+    (0x001D, "GPSDateStamp"),
+    (0x001E, "GPSDifferential"),
+    (0x001F, "GPSHPositioningError"),
+];
+
+/// 互換性IFDのタグ名（ExifToolと同じ名前、タグ番号順）
+const INTEROP_TAG_NAMES: &[(u16, &str)] = &[
+    (0x0001, "InteropIndex"),
+    (0x0002, "InteropVersion"),
+    (0x1000, "RelatedImageFileFormat"),
+    (0x1001, "RelatedImageWidth"),
+    (0x1002, "RelatedImageHeight"),
+];
+
+/// TIFFのバイトオーダー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// リトルエンディアン（"II"）
+    LittleEndian,
+    /// ビッグエンディアン（"MM"）
+    BigEndian,
+}
+
+impl ByteOrder {
+    pub(crate) fn read_u16(self, data: &[u8], pos: usize) -> Option<u16> {
+        let bytes: [u8; 2] = data.get(pos..pos.checked_add(2)?)?.try_into().ok()?;
+        Some(match self {
+            ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+        })
+    }
+
+    pub(crate) fn read_u32(self, data: &[u8], pos: usize) -> Option<u32> {
+        let bytes: [u8; 4] = data.get(pos..pos.checked_add(4)?)?.try_into().ok()?;
+        Some(match self {
+            ByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u32::from_be_bytes(bytes),
+        })
+    }
+
+    pub(crate) fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+
+    pub(crate) fn u32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::LittleEndian => value.to_le_bytes(),
+            ByteOrder::BigEndian => value.to_be_bytes(),
+        }
+    }
+
+    /// TIFFヘッダーのバイトオーダー識別子
+    fn signature(self) -> &'static [u8; 2] {
+        match self {
+            ByteOrder::LittleEndian => b"II",
+            ByteOrder::BigEndian => b"MM",
+        }
+    }
+}
+
+/// IFDの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfdKind {
+    /// 主画像のIFD
+    Ifd0,
+    /// Exif IFD（0x8769）
+    Exif,
+    /// GPS IFD（0x8825）
+    Gps,
+    /// 互換性IFD（0xA005）
+    Interop,
+    /// サムネイルのIFD
+    Ifd1,
+}
+
+/// GPS IFDから復元した位置情報
+///
+/// 度分秒の有理数と方位の参照（N/S、E/W、海抜の上下）を解釈した値です。
+/// 項目が追加されても互換性を保てるよう、クレートの外では[`GpsCoordinates::new`]と
+/// `with_*`のメソッドで作成します。
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[non_exhaustive]
+pub struct GpsCoordinates {
+    /// 緯度（度、南緯は負）
+    pub lat: f64,
+    /// 経度（度、西経は負）
+    pub lon: f64,
+    /// 高度（メートル、海面下は負）
+    pub alt: Option<f64>,
+    /// 撮影方向（度、0以上360未満）
+    ///
+    /// 書き込みは真北（T）基準です。読み取りでは記録された基準（真北・磁北）の値をそのまま返します。
+    pub img_direction: Option<f64>,
+    /// 移動速度（km/h）
+    ///
+    /// 書き込みはkm/h（K）で記録します。読み取りではマイル毎時（M）とノット（N）もkm/hに換算します。
+    pub speed: Option<f64>,
+}
+
+impl GpsCoordinates {
+    /// 緯度・経度から作成します（その他の項目は`None`）
+    pub fn new(lat: f64, lon: f64) -> Self {
+        Self {
+            lat,
+            lon,
+            ..Default::default()
+        }
+    }
+
+    /// 高度（メートル）を設定します
+    pub fn with_alt(mut self, alt: Option<f64>) -> Self {
+        self.alt = alt;
+        self
+    }
+
+    /// 撮影方向（度）を設定します
+    pub fn with_img_direction(mut self, img_direction: Option<f64>) -> Self {
+        self.img_direction = img_direction;
+        self
+    }
+
+    /// 移動速度（km/h）を設定します
+    pub fn with_speed(mut self, speed: Option<f64>) -> Self {
+        self.speed = speed;
+        self
+    }
+}
+
+/// WindowsのエクスプローラーがIFD0に記録するXP系のタグ
+///
+/// 値はBYTE型の配列に、終端のNULを含むUCS-2（UTF-16リトルエンディアン）で記録されます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XpTag {
+    /// XPTitle（0x9C9B、エクスプローラーの「タイトル」）
+    Title,
+    /// XPComment（0x9C9C、「コメント」）
+    Comment,
+    /// XPAuthor（0x9C9D、「作成者」。複数の場合はセミコロン区切り）
+    Author,
+    /// XPKeywords（0x9C9E、「タグ」。セミコロン区切り）
+    Keywords,
+    /// XPSubject（0x9C9F、「件名」）
+    Subject,
+}
+
+impl XpTag {
+    /// タグ番号
+    pub fn tag(self) -> u16 {
+        match self {
+            XpTag::Title => 0x9C9B,
+            XpTag::Comment => 0x9C9C,
+            XpTag::Author => 0x9C9D,
+            XpTag::Keywords => 0x9C9E,
+            XpTag::Subject => 0x9C9F,
+        }
+    }
+}
+
+/// EXIFの日時と時差
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateTime {
+    /// 日時（`YYYY:MM:DD HH:MM:SS`、タイムゾーンなし）
+    pub value: String,
+    /// UTCからの時差（`+09:00`形式）
+    pub offset: Option<String>,
+}
+
+impl DateTime {
+    /// 日時と時差を指定して作成します
+    pub fn new(value: impl Into<String>, offset: Option<&str>) -> Self {
+        DateTime {
+            value: value.into(),
+            offset: offset.map(str::to_string),
+        }
+    }
+
+    /// 時差を分単位で返します（時差がない場合や形式が不正な場合は`None`）
+    pub fn offset_minutes(&self) -> Option<i32> {
+        parse_offset(self.offset.as_deref()?)
+    }
+
+    /// RFC 3339形式（`2024-05-01T10:00:00+09:00`）に変換します
+    ///
+    /// 時差がない場合はタイムゾーンを付けずに返します。日時の形式が不正な場合は`None`です。
+    pub fn to_rfc3339(&self) -> Option<String> {
+        let (year, month, day, hour, minute, second) = parse_date_time(&self.value)?;
+        let mut output = format!("{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}");
+        if let Some(offset) = self.offset_minutes() {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.abs();
+            output.push_str(&format!("{sign}{:02}:{:02}", offset / 60, offset % 60));
+        }
+        Some(output)
+    }
+
+    /// UNIX時間（秒）に変換します
+    ///
+    /// 時差がないと時刻が確定しないため、時差がない場合は`None`を返します。
+    /// 撮影地の異なる画像を時系列に並べる場合に使用します。
+    pub fn unix_timestamp(&self) -> Option<i64> {
+        let (year, month, day, hour, minute, second) = parse_date_time(&self.value)?;
+        let offset = self.offset_minutes()?;
+
+        // グレゴリオ暦の日付から1970-01-01までの日数を求める
+        let (year, month) = (year as i64, month as i64);
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        Some(
+            days * 86_400 + hour as i64 * 3600 + minute as i64 * 60 + second as i64
+                - offset as i64 * 60,
+        )
+    }
+}
+
+/// EXIFに記録された3種類の日時
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateTimes {
+    /// ファイルの変更日時（IFD0のDateTimeとOffsetTime）
+    pub modified: Option<DateTime>,
+    /// 撮影日時（DateTimeOriginalとOffsetTimeOriginal）
+    pub original: Option<DateTime>,
+    /// デジタル化日時（DateTimeDigitizedとOffsetTimeDigitized）
+    pub digitized: Option<DateTime>,
+}
+
+/// IFDエントリ
+///
+/// [`entries_raw`]が返す位置は入力データ先頭からの絶対位置です。
+/// クレート内部の走査ではTIFFヘッダー先頭からの相対位置として扱います。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IfdEntry {
+    /// エントリが属するIFD
+    pub ifd: IfdKind,
+    /// タグ番号
+    pub tag: u16,
+    /// データ型
+    pub field_type: u16,
+    /// 値の個数
+    pub count: u32,
+    /// 12バイトのエントリの位置
+    pub entry_offset: usize,
+    /// 値の位置（4バイト以下の値はエントリ内）
+    pub value_offset: usize,
+    /// 値のバイト数（未知の型は0）
+    pub value_len: usize,
+}
+
+impl IfdEntry {
+    /// エントリが属するIFDに応じたタグ名を返します
+    ///
+    /// 互換性IFDのタグ（0x0001のInteropIndexなど）はGPS IFDのタグと番号が重なるため、
+    /// [`tag_name`]ではなくこのメソッドで引きます。その他のIFDは[`tag_name`]と同じです。
+    pub fn tag_name(&self) -> Option<&'static str> {
+        match self.ifd {
+            IfdKind::Interop => INTEROP_TAG_NAMES
+                .binary_search_by_key(&self.tag, |(id, _)| *id)
+                .ok()
+                .map(|index| INTEROP_TAG_NAMES[index].1),
+            _ => tag_name(self.tag),
+        }
+    }
+}
+
+/// IFDテーブルの位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IfdTable {
+    /// IFDの種類
+    pub(crate) kind: IfdKind,
+    /// エントリ数フィールドの位置
+    pub(crate) offset: usize,
+    /// 次のIFDへのオフセットを含むテーブルの終端
+    pub(crate) end: usize,
+}
+
+/// TIFF構造を走査した結果
+#[derive(Debug, Clone)]
+pub(crate) struct Walk {
+    /// バイトオーダー
+    pub(crate) byte_order: ByteOrder,
+    /// 走査したIFDテーブル
+    pub(crate) tables: Vec<IfdTable>,
+    /// すべてのエントリ（出現順）
+    pub(crate) entries: Vec<IfdEntry>,
+}
+
+impl Walk {
+    /// 指定したIFDのタグを探します
+    pub(crate) fn find(&self, ifd: IfdKind, tag: u16) -> Option<&IfdEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.ifd == ifd && entry.tag == tag)
+    }
+
+    /// SHORTまたはLONGの値を1つ読み取ります
+    pub(crate) fn read_uint(&self, tiff: &[u8], entry: &IfdEntry) -> Option<u32> {
+        match entry.field_type {
+            3 => self
+                .byte_order
+                .read_u16(tiff, entry.value_offset)
+                .map(u32::from),
+            4 => self.byte_order.read_u32(tiff, entry.value_offset),
+            _ => None,
+        }
+    }
+
+    /// RATIONALの値をすべて読み取ります
+    pub(crate) fn read_rationals(&self, tiff: &[u8], entry: &IfdEntry) -> Option<Vec<f64>> {
+        if entry.field_type != 5 {
+            return None;
+        }
+        (0..entry.count as usize)
+            .map(|index| {
+                let pos = entry.value_offset.checked_add(index * 8)?;
+                let numerator = self.byte_order.read_u32(tiff, pos)?;
+                let denominator = self.byte_order.read_u32(tiff, pos + 4)?;
+                (denominator != 0).then(|| numerator as f64 / denominator as f64)
+            })
+            .collect()
+    }
+
+    /// ASCIIまたはUTF-8（EXIF 3.0の型129）の値を最初のNULまで読み取ります
+    ///
+    /// 前後の空白は除きます。ASCII型に格納された非ASCIIのバイトもUTF-8として解釈します。
+    fn read_text(&self, tiff: &[u8], entry: &IfdEntry) -> Option<String> {
+        let text = String::from_utf8_lossy(self.read_text_bytes(tiff, entry)?)
+            .trim()
+            .to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// ASCIIまたはUTF-8（EXIF 3.0の型129）の値の、最初のNULまでのバイト列を返します
+    fn read_text_bytes<'a>(&self, tiff: &'a [u8], entry: &IfdEntry) -> Option<&'a [u8]> {
+        if !matches!(entry.field_type, 2 | TYPE_UTF8) {
+            return None;
+        }
+        let value = tiff.get(entry.value_offset..entry.value_offset + entry.value_len)?;
+        let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+        Some(&value[..end])
+    }
+
+    /// BYTE型に記録されたUCS-2の文字列を読み取ります（前後の空白を除き、空の場合は`None`）
+    fn read_xp_text(&self, tiff: &[u8], entry: &IfdEntry) -> Option<String> {
+        if entry.field_type != 1 {
+            return None;
+        }
+        let value = tiff.get(entry.value_offset..entry.value_offset + entry.value_len)?;
+        let units: Vec<u16> = value
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        let text = String::from_utf16_lossy(&units).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// ASCIIまたはBYTEの値の先頭1バイトを読み取ります
+    fn read_first_byte(&self, tiff: &[u8], entry: &IfdEntry) -> Option<u8> {
+        match entry.field_type {
+            1 | 2 | 7 | TYPE_UTF8 if entry.count > 0 => tiff.get(entry.value_offset).copied(),
+            _ => None,
+        }
+    }
+}
+
+/// 書き込み用のIFDエントリ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Field {
+    /// タグ番号
+    pub(crate) tag: u16,
+    /// データ型
+    pub(crate) field_type: u16,
+    /// 値の個数
+    pub(crate) count: u32,
+    /// 値のバイト列
+    pub(crate) value: Vec<u8>,
+    /// 値のバイトオーダー
+    pub(crate) byte_order: ByteOrder,
+}
+
+impl Field {
+    /// SHORT型の値を1つ持つエントリを作成します
+    pub(crate) fn short(tag: u16, value: u16) -> Self {
+        Field {
+            tag,
+            field_type: 3,
+            count: 1,
+            value: value.to_le_bytes().to_vec(),
+            byte_order: ByteOrder::LittleEndian,
+        }
+    }
+
+    /// BYTE型の値を持つエントリを作成します
+    pub(crate) fn bytes(tag: u16, value: &[u8]) -> Self {
+        Field {
+            tag,
+            field_type: 1,
+            count: value.len() as u32,
+            value: value.to_vec(),
+            byte_order: ByteOrder::LittleEndian,
+        }
+    }
+
+    /// 文字列の値（終端のNULを含む）を持つエントリを作成します
+    ///
+    /// ASCIIのみの文字列はASCII型、それ以外はEXIF 3.0のUTF-8型で記録します。
+    pub(crate) fn text(tag: u16, value: &str) -> Self {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        Field {
+            tag,
+            field_type: if value.is_ascii() { 2 } else { TYPE_UTF8 },
+            count: bytes.len() as u32,
+            value: bytes,
+            byte_order: ByteOrder::LittleEndian,
+        }
+    }
+
+    /// RATIONAL型の値（分子, 分母）を持つエントリを作成します
+    pub(crate) fn rationals(tag: u16, values: &[(u32, u32)]) -> Self {
+        Field {
+            tag,
+            field_type: 5,
+            count: values.len() as u32,
+            value: values
+                .iter()
+                .flat_map(|(numerator, denominator)| {
+                    [numerator.to_le_bytes(), denominator.to_le_bytes()].concat()
+                })
+                .collect(),
+            byte_order: ByteOrder::LittleEndian,
+        }
+    }
+
+    /// LONG型の値を1つ持つエントリを作成します
+    pub(crate) fn long(tag: u16, value: u32) -> Self {
+        Field {
+            tag,
+            field_type: 4,
+            count: 1,
+            value: value.to_le_bytes().to_vec(),
+            byte_order: ByteOrder::LittleEndian,
+        }
+    }
+
+    /// 走査したエントリの値をコピーします
+    ///
+    /// 未知の型や範囲外を指す値の場合は`None`を返します。
+    pub(crate) fn from_entry(tiff: &[u8], walk: &Walk, entry: &IfdEntry) -> Option<Self> {
+        if type_size(entry.field_type) == 0 {
+            return None;
+        }
+        let value =
+            tiff.get(entry.value_offset..entry.value_offset.checked_add(entry.value_len)?)?;
+
+        Some(Field {
+            tag: entry.tag,
+            field_type: entry.field_type,
+            count: entry.count,
+            value: value.to_vec(),
+            byte_order: walk.byte_order,
+        })
+    }
+
+    /// 指定したバイトオーダーに変換した値を返します
+    fn value_in(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let unit = match self.field_type {
+            3 | 8 => 2,
+            4 | 5 | 9 | 10 | 11 | 13 => 4,
+            12 => 8,
+            _ => 1,
+        };
+
+        if self.byte_order == byte_order || unit == 1 {
+            return self.value.clone();
+        }
+
+        self.value
+            .chunks(unit)
+            .flat_map(|chunk| chunk.iter().rev().copied())
+            .collect()
+    }
+}
+
+/// サブIFDへのポインタを表すタグか
+pub(crate) fn is_pointer_tag(tag: u16) -> bool {
+    matches!(tag, TAG_EXIF_IFD | TAG_GPS_IFD | TAG_INTEROP_IFD)
+}
+
+/// データ型ごとの1要素のバイト数
+fn type_size(field_type: u16) -> usize {
+    match field_type {
+        1 | 2 | 6 | 7 | TYPE_UTF8 => 1,
+        3 | 8 => 2,
+        4 | 9 | 11 | 13 => 4,
+        5 | 10 | 12 => 8,
+        _ => 0,
+    }
+}
+
+/// EXIFのIFDエントリを列挙します
+///
+/// IFD0、Exif IFD、GPS IFD、互換性IFD、IFD1（サムネイル）を走査し、各エントリの
+/// タグ、データ型、個数、入力データ先頭からの絶対位置を返します。
+///
+/// # Arguments
+/// * `data` - JPEG画像、PNG画像（eXIfチャンク）、またはTIFFヘッダーから始まるEXIFデータ
+///
+/// # Returns
+/// * `Ok(Vec<IfdEntry>)` - IFDの走査順に並んだエントリ（EXIFがない場合は空）
+/// * `Err(Error)` - エラー
+pub fn entries_raw(data: &[u8]) -> Result<Vec<IfdEntry>, Error> {
+    let Some(base) = locate_tiff(data)? else {
+        return Ok(Vec::new());
+    };
+
+    let walk =
+        walk(&data[base..]).ok_or_else(|| Error::ParseError("Invalid TIFF header".to_string()))?;
+
+    Ok(walk
+        .entries
+        .into_iter()
+        .map(|entry| IfdEntry {
+            entry_offset: base + entry.entry_offset,
+            value_offset: base + entry.value_offset,
+            ..entry
+        })
+        .collect())
+}
+
+/// タグ番号からタグ名を返します
+///
+/// IFD0、Exif IFD、GPS IFDのタグを対象とし、名前はExifToolと互換です
+/// （例：0x0112は`Orientation`、0x9003は`DateTimeOriginal`、0x0002は`GPSLatitude`）。
+/// 0x0000〜0x001FはGPS IFDのタグとして引きます。この範囲は互換性IFDのタグ
+/// （0x0001のInteropIndexなど）と重なるため、互換性IFDのエントリは
+/// [`IfdEntry::tag_name`]で引いてください。
+///
+/// # Returns
+/// * `Some(&str)` - タグ名
+/// * `None` - 登録されていないタグ
+pub fn tag_name(tag: u16) -> Option<&'static str> {
+    let table = if tag <= 0x001F {
+        GPS_TAG_NAMES
+    } else {
+        TAG_NAMES
+    };
+    table
+        .binary_search_by_key(&tag, |(id, _)| *id)
+        .ok()
+        .map(|index| table[index].1)
+}
+
+/// タグ名からタグ番号を返します（[`tag_name`]の逆引き）
+///
+/// 大文字・小文字は区別しません。
+pub fn tag_id(name: &str) -> Option<u16> {
+    TAG_NAMES
+        .iter()
+        .chain(GPS_TAG_NAMES)
+        .find(|(_, tag_name)| tag_name.eq_ignore_ascii_case(name))
+        .map(|(id, _)| *id)
+}
+
+/// EXIFのGPS IFDから位置情報を読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像、PNG画像（eXIfチャンク）、またはTIFFヘッダーから始まるEXIFデータ
+///
+/// # Returns
+/// * `Ok(Some(GpsCoordinates))` - 緯度と経度（高度は記録されている場合のみ）
+/// * `Ok(None)` - EXIFや緯度・経度がない場合、値が不正な場合
+/// * `Err(Error)` - エラー
+pub fn read_gps_coordinates(data: &[u8]) -> Result<Option<GpsCoordinates>, Error> {
+    let Some(base) = locate_tiff(data)? else {
+        return Ok(None);
+    };
+    Ok(gps_coordinates(&data[base..]))
+}
+
+/// TIFFデータのGPS IFDから位置情報を復元します
+pub(crate) fn gps_coordinates(tiff: &[u8]) -> Option<GpsCoordinates> {
+    let walk = walk(tiff)?;
+
+    // 度・分・秒の有理数を度に変換し、参照が負の方向なら符号を反転する
+    let coordinate = |tag: u16, ref_tag: u16, negative: u8| -> Option<f64> {
+        let values = walk.read_rationals(tiff, walk.find(IfdKind::Gps, tag)?)?;
+        let degrees = values
+            .iter()
+            .zip([1.0, 60.0, 3600.0])
+            .map(|(value, divisor)| value / divisor)
+            .sum::<f64>();
+        let reference = walk
+            .find(IfdKind::Gps, ref_tag)
+            .and_then(|entry| walk.read_first_byte(tiff, entry));
+        let sign = if reference.map(|r| r.to_ascii_uppercase()) == Some(negative) {
+            -1.0
+        } else {
+            1.0
+        };
+        (!values.is_empty()).then_some(sign * degrees)
+    };
+
+    let lat = coordinate(TAG_GPS_LATITUDE, TAG_GPS_LATITUDE_REF, b'S')?;
+    let lon = coordinate(TAG_GPS_LONGITUDE, TAG_GPS_LONGITUDE_REF, b'W')?;
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return None;
+    }
+
+    let alt = walk
+        .find(IfdKind::Gps, TAG_GPS_ALTITUDE)
+        .and_then(|entry| walk.read_rationals(tiff, entry))
+        .and_then(|values| values.first().copied())
+        .map(|altitude| {
+            // 参照が1の場合は海面下
+            let below_sea_level = walk
+                .find(IfdKind::Gps, TAG_GPS_ALTITUDE_REF)
+                .and_then(|entry| walk.read_first_byte(tiff, entry))
+                == Some(1);
+            if below_sea_level {
+                -altitude
+            } else {
+                altitude
+            }
+        });
+
+    let img_direction = walk
+        .find(IfdKind::Gps, TAG_GPS_IMG_DIRECTION)
+        .and_then(|entry| walk.read_rationals(tiff, entry))
+        .and_then(|values| values.first().copied());
+
+    let speed = walk
+        .find(IfdKind::Gps, TAG_GPS_SPEED)
+        .and_then(|entry| walk.read_rationals(tiff, entry))
+        .and_then(|values| values.first().copied())
+        .map(|speed| {
+            let reference = walk
+                .find(IfdKind::Gps, TAG_GPS_SPEED_REF)
+                .and_then(|entry| walk.read_first_byte(tiff, entry));
+            match reference.map(|r| r.to_ascii_uppercase()) {
+                Some(b'M') => speed * 1.609344,
+                Some(b'N') => speed * 1.852,
+                _ => speed,
+            }
+        });
+
+    Some(GpsCoordinates {
+        lat,
+        lon,
+        alt,
+        img_direction,
+        speed,
+    })
+}
+
+/// EXIFの文字列タグを読み取ります
+///
+/// ASCII型とEXIF 3.0のUTF-8型（129）のどちらにも対応します。
+///
+/// # Arguments
+/// * `data` - JPEG画像、PNG画像（eXIfチャンク）、またはTIFFヘッダーから始まるEXIFデータ
+/// * `ifd` - タグが属するIFD（ImageDescriptionやArtistは[`IfdKind::Ifd0`]）
+/// * `tag` - タグ番号
+///
+/// # Returns
+/// * `Ok(Some(String))` - 最初のNULまでの文字列（前後の空白は除きます）
+/// * `Ok(None)` - EXIFやタグがない場合、文字列型ではない場合、空の場合
+/// * `Err(Error)` - エラー
+pub fn read_text(data: &[u8], ifd: IfdKind, tag: u16) -> Result<Option<String>, Error> {
+    let Some(base) = locate_tiff(data)? else {
+        return Ok(None);
+    };
+    let tiff = &data[base..];
+    Ok(walk(tiff).and_then(|walk| walk.read_text(tiff, walk.find(ifd, tag)?)))
+}
+
+/// EXIFの文字列タグを読み取り、UTF-8として不正な場合は指定したポリシーで修復します
+///
+/// # Arguments
+/// * `data` - JPEG画像、PNG画像（eXIfチャンク）、またはTIFFヘッダーから始まるEXIFデータ
+/// * `ifd` - タグが属するIFD
+/// * `tag` - タグ番号
+/// * `policy` - UTF-8として不正な場合の扱い
+///
+/// # Returns
+/// * `Ok(Some(DecodedText))` - 最初のNULまでの文字列（前後の空白は除きます）と、適用した修復
+/// * `Ok(None)` - EXIFやタグがない場合、文字列型ではない場合、空の場合
+/// * `Err(Error)` - エラー（[`Utf8Policy::Reject`]でUTF-8として不正な場合を含む）
+pub fn read_text_with_policy(
+    data: &[u8],
+    ifd: IfdKind,
+    tag: u16,
+    policy: Utf8Policy,
+) -> Result<Option<DecodedText>, Error> {
+    let Some(base) = locate_tiff(data)? else {
+        return Ok(None);
+    };
+    let tiff = &data[base..];
+    let Some(bytes) = walk(tiff).and_then(|walk| walk.read_text_bytes(tiff, walk.find(ifd, tag)?))
+    else {
+        return Ok(None);
+    };
+
+    let mut decoded = policy.decode(bytes, &format!("EXIF tag 0x{tag:04X}"))?;
+    decoded.text = decoded.text.trim().to_string();
+    Ok((!decoded.text.is_empty()).then_some(decoded))
+}
+
+/// WindowsのXP系のタグ（XPTitleなど）の文字列を読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像、PNG画像（eXIfチャンク）、またはTIFFヘッダーから始まるEXIFデータ
+/// * `tag` - 読み取るタグ
+///
+/// # Returns
+/// * `Ok(Some(String))` - UCS-2から変換した文字列（前後の空白は除きます）
+/// * `Ok(None)` - EXIFやタグがない場合、BYTE型ではない場合、空の場合
+/// * `Err(Error)` - エラー
+///
+/// XP系のタグはTIFFのバイトオーダーによらず、常にリトルエンディアンとして読み取ります。
+pub fn read_xp_text(data: &[u8], tag: XpTag) -> Result<Option<String>, Error> {
+    let Some(base) = locate_tiff(data)? else {
+        return Ok(None);
+    };
+    let tiff = &data[base..];
+    Ok(walk(tiff).and_then(|walk| walk.read_xp_text(tiff, walk.find(IfdKind::Ifd0, tag.tag())?)))
+}
+
+/// EXIFの日時と時差（OffsetTime系のタグ）を読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像、PNG画像（eXIfチャンク）、またはTIFFヘッダーから始まるEXIFデータ
+///
+/// # Returns
+/// * `Ok(Some(DateTimes))` - 記録されている日時（時差は記録されている場合のみ）
+/// * `Ok(None)` - EXIFがない場合
+/// * `Err(Error)` - エラー
+///
+/// 形式が不正な日時や未設定（空白やコロンのみ）の日時は`None`として扱います。
+pub fn read_date_times(data: &[u8]) -> Result<Option<DateTimes>, Error> {
+    let Some(base) = locate_tiff(data)? else {
+        return Ok(None);
+    };
+    let tiff = &data[base..];
+    let Some(walk) = walk(tiff) else {
+        return Ok(None);
+    };
+
+    let read = |ifd: IfdKind, tag: u16, offset_tag: u16| -> Option<DateTime> {
+        let value = walk
+            .read_text(tiff, walk.find(ifd, tag)?)
+            .filter(|value| parse_date_time(value).is_some())?;
+        let offset = walk
+            .find(IfdKind::Exif, offset_tag)
+            .and_then(|entry| walk.read_text(tiff, entry))
+            .filter(|offset| parse_offset(offset).is_some());
+        Some(DateTime { value, offset })
+    };
+
+    Ok(Some(DateTimes {
+        modified: read(IfdKind::Ifd0, TAG_DATE_TIME, TAG_OFFSET_TIME),
+        original: read(
+            IfdKind::Exif,
+            TAG_DATE_TIME_ORIGINAL,
+            TAG_OFFSET_TIME_ORIGINAL,
+        ),
+        digitized: read(
+            IfdKind::Exif,
+            TAG_DATE_TIME_DIGITIZED,
+            TAG_OFFSET_TIME_DIGITIZED,
+        ),
+    }))
+}
+
+/// `YYYY:MM:DD HH:MM:SS`形式の日時を解析します
+fn parse_date_time(value: &str) -> Option<(u16, u8, u8, u8, u8, u8)> {
+    let bytes = value.as_bytes();
+    let separators_valid =
+        bytes.len() == 19 && [4, 7, 13, 16].iter().all(|&i| bytes[i] == b':') && bytes[10] == b' ';
+    if !separators_valid {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<u16> {
+        let digits = &value[range];
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    let valid = (1..=12).contains(&month)
+        && (1..=31).contains(&day)
+        && hour < 24
+        && minute < 60
+        && second <= 60;
+    valid.then_some((
+        year,
+        month as u8,
+        day as u8,
+        hour as u8,
+        minute as u8,
+        second as u8,
+    ))
+}
+
+/// `+HH:MM`形式の時差を分単位で解析します
+fn parse_offset(offset: &str) -> Option<i32> {
+    let bytes = offset.as_bytes();
+    if bytes.len() != 6 || bytes[3] != b':' || !matches!(bytes[0], b'+' | b'-') {
+        return None;
+    }
+    let digits = [1, 2, 4, 5].map(|i| bytes[i]);
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let [h1, h2, m1, m2] = digits.map(|b| (b - b'0') as i32);
+    let (hours, minutes) = (h1 * 10 + h2, m1 * 10 + m2);
+    if hours > 14 || minutes >= 60 {
+        return None;
+    }
+    let minutes = hours * 60 + minutes;
+    Some(if bytes[0] == b'-' { -minutes } else { minutes })
+}
+
+/// 入力データ内のTIFFヘッダーの位置を探します
+pub(crate) fn locate_tiff(data: &[u8]) -> Result<Option<usize>, Error> {
+    if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        return Ok(Some(0));
+    }
+    if data.starts_with(b"Exif\0\0") {
+        return Ok(Some(6));
+    }
+
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => {
+            let (segments, _) = jpeg::parse_segments(data)?;
+            Ok(segments
+                .iter()
+                .find(|segment| {
+                    segment.marker == 0xE1 && segment.payload().starts_with(b"Exif\0\0")
+                })
+                .map(|segment| segment.offset + 4 + 6))
+        }
+        Some(ImageFormat::Png) => {
+            let chunks = png::parse_chunks(data)?;
+            Ok(chunks
+                .iter()
+                .find(|chunk| &chunk.chunk_type == b"eXIf")
+                .map(|chunk| {
+                    // "Exif\0\0"を付けて書き込むエンコーダーがある
+                    let prefix = if chunk.data.starts_with(b"Exif\0\0") {
+                        6
+                    } else {
+                        0
+                    };
+                    chunk.offset + 8 + prefix
+                }))
+        }
+        None => Err(Error::InvalidFormat(
+            "Unsupported format for EXIF".to_string(),
+        )),
+    }
+}
+
+/// TIFFヘッダーを検証し、バイトオーダーとIFD0の位置を返します
+pub(crate) fn parse_header(tiff: &[u8]) -> Option<(ByteOrder, usize)> {
+    let byte_order = match tiff.get(0..2)? {
+        b"II" => ByteOrder::LittleEndian,
+        b"MM" => ByteOrder::BigEndian,
+        _ => return None,
+    };
+
+    if byte_order.read_u16(tiff, 2)? != 42 {
+        return None;
+    }
+
+    let ifd0 = byte_order.read_u32(tiff, 4)? as usize;
+    Some((byte_order, ifd0))
+}
+
+/// IFD0からサブIFD（Exif、GPS、互換性）とIFD1までを走査します
+///
+/// 範囲外のIFDや循環参照は無視します。TIFFヘッダーや走査済みのIFDテーブルと重なるIFD、
+/// 2つ目以降の同じ種類のIFDも、同じエントリを別のIFDとして読み取らないよう無視します。
+pub(crate) fn walk(tiff: &[u8]) -> Option<Walk> {
+    let (byte_order, ifd0) = parse_header(tiff)?;
+
+    let mut walk = Walk {
+        byte_order,
+        tables: Vec::new(),
+        entries: Vec::new(),
+    };
+    let mut queue = vec![(IfdKind::Ifd0, ifd0)];
+
+    while !queue.is_empty() {
+        let (kind, offset) = queue.remove(0);
+        let Some(count) = byte_order.read_u16(tiff, offset) else {
+            continue;
+        };
+        let end = offset + 2 + count as usize * 12 + 4;
+        let overlaps = walk
+            .tables
+            .iter()
+            .any(|table| table.kind == kind || offset < table.end && table.offset < end);
+        if offset < 8 || end > tiff.len() || overlaps {
+            continue;
+        }
+        walk.tables.push(IfdTable { kind, offset, end });
+
+        for index in 0..count as usize {
+            let entry_offset = offset + 2 + index * 12;
+            let tag = byte_order.read_u16(tiff, entry_offset)?;
+            let field_type = byte_order.read_u16(tiff, entry_offset + 2)?;
+            let count = byte_order.read_u32(tiff, entry_offset + 4)?;
+
+            let value_len = type_size(field_type).saturating_mul(count as usize);
+            let value_offset = if value_len <= 4 {
+                entry_offset + 8
+            } else {
+                byte_order.read_u32(tiff, entry_offset + 8)? as usize
+            };
+
+            let entry = IfdEntry {
+                ifd: kind,
+                tag,
+                field_type,
+                count,
+                entry_offset,
+                value_offset,
+                value_len,
+            };
+
+            let child = match (kind, tag) {
+                (IfdKind::Ifd0, TAG_EXIF_IFD) => Some(IfdKind::Exif),
+                (IfdKind::Ifd0, TAG_GPS_IFD) => Some(IfdKind::Gps),
+                (IfdKind::Exif, TAG_INTEROP_IFD) => Some(IfdKind::Interop),
+                _ => None,
+            };
+            if let Some(child) = child {
+                if let Some(child_offset) = walk.read_uint(tiff, &entry) {
+                    queue.push((child, child_offset as usize));
+                }
+            }
+
+            walk.entries.push(entry);
+        }
+
+        if kind == IfdKind::Ifd0 {
+            match byte_order.read_u32(tiff, end - 4) {
+                Some(0) | None => {}
+                Some(next) => queue.push((IfdKind::Ifd1, next as usize)),
+            }
+        }
+    }
+
+    Some(walk)
+}
+
+/// IFD1のサムネイル画像のバイト数を返します
+pub(crate) fn thumbnail_length(tiff: &[u8]) -> Option<usize> {
+    let walk = walk(tiff)?;
+    let length = walk.find(IfdKind::Ifd1, TAG_THUMBNAIL_LENGTH)?;
+    walk.read_uint(tiff, length).map(|length| length as usize)
+}
+
+/// IFD1のサムネイル画像（JPEG）のバイト列を返します
+pub(crate) fn thumbnail(tiff: &[u8]) -> Option<&[u8]> {
+    let walk = walk(tiff)?;
+    let offset = walk.read_uint(tiff, walk.find(IfdKind::Ifd1, TAG_THUMBNAIL_OFFSET)?)? as usize;
+    let length = walk.read_uint(tiff, walk.find(IfdKind::Ifd1, TAG_THUMBNAIL_LENGTH)?)? as usize;
+    tiff.get(offset..offset.checked_add(length)?)
+}
+
+/// IFD1（サムネイル）への参照を取り除いたTIFFデータを返します
+///
+/// IFD1とサムネイル画像が他のデータより後ろに配置されている場合は切り詰めます。
+/// IFD1が存在しない場合は`None`を返します。
+pub(crate) fn remove_thumbnail(tiff: &[u8]) -> Option<Vec<u8>> {
+    let walk = walk(tiff)?;
+    let ifd0 = walk
+        .tables
+        .iter()
+        .find(|table| table.kind == IfdKind::Ifd0)?;
+    let ifd1 = walk
+        .tables
+        .iter()
+        .find(|table| table.kind == IfdKind::Ifd1)?;
+
+    // IFD1以外が使用している範囲の終端
+    let mut used_end = 8;
+    for table in walk.tables.iter().filter(|t| t.kind != IfdKind::Ifd1) {
+        used_end = used_end.max(table.end);
+    }
+    for entry in walk.entries.iter().filter(|e| e.ifd != IfdKind::Ifd1) {
+        let value_end = entry.value_offset.saturating_add(entry.value_len);
+        if value_end <= tiff.len() {
+            used_end = used_end.max(value_end);
+        }
+    }
+
+    // IFD1とサムネイルが占める範囲の先頭
+    let mut thumbnail_start = ifd1.offset;
+    for entry in walk.entries.iter().filter(|e| e.ifd == IfdKind::Ifd1) {
+        thumbnail_start = thumbnail_start.min(entry.value_offset);
+    }
+    if let (Some(offset), Some(_)) = (
+        walk.find(IfdKind::Ifd1, TAG_THUMBNAIL_OFFSET),
+        walk.find(IfdKind::Ifd1, TAG_THUMBNAIL_LENGTH),
+    ) {
+        if let Some(offset) = walk.read_uint(tiff, offset) {
+            thumbnail_start = thumbnail_start.min(offset as usize);
+        }
+    }
+
+    let mut output = tiff.to_vec();
+    let next_pos = ifd0.end - 4;
+    output[next_pos..ifd0.end].copy_from_slice(&walk.byte_order.u32_bytes(0));
+
+    if thumbnail_start >= used_end {
+        output.truncate(used_end);
+    }
+
+    Some(output)
+}
+
+/// TIFFデータの組み立て
+///
+/// エントリはタグ番号順に並べ替えられ、サブIFDへのポインタとサムネイルの位置は
+/// 自動で設定されます。
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TiffBuilder {
+    /// IFD0のエントリ
+    pub(crate) ifd0: Vec<Field>,
+    /// Exif IFDのエントリ
+    pub(crate) exif: Vec<Field>,
+    /// GPS IFDのエントリ
+    pub(crate) gps: Vec<Field>,
+    /// 互換性IFDのエントリ
+    pub(crate) interop: Vec<Field>,
+    /// IFD1のエントリ
+    pub(crate) ifd1: Vec<Field>,
+    /// IFD1のサムネイル画像
+    pub(crate) thumbnail: Option<Vec<u8>>,
+}
+
+impl TiffBuilder {
+    /// 指定したバイトオーダーでTIFFデータを作成します
+    pub(crate) fn build(&self, byte_order: ByteOrder) -> Vec<u8> {
+        let without = |fields: &[Field], tags: &[u16]| -> Vec<Field> {
+            fields
+                .iter()
+                .filter(|field| !tags.contains(&field.tag))
+                .cloned()
+                .collect()
+        };
+        let mut ifd0 = without(&self.ifd0, &[TAG_EXIF_IFD, TAG_GPS_IFD]);
+        let mut exif = without(&self.exif, &[TAG_INTEROP_IFD]);
+        let mut ifd1 = without(&self.ifd1, &[TAG_THUMBNAIL_OFFSET, TAG_THUMBNAIL_LENGTH]);
+        let has_exif = !exif.is_empty() || !self.interop.is_empty();
+        let has_ifd1 = !ifd1.is_empty() || self.thumbnail.is_some();
+
+        // IFDの配置: ヘッダー(8) → IFD0 → Exif IFD → 互換性IFD → GPS IFD → IFD1 → サムネイル
+        let pointer_size = |present: bool| if present { 12 } else { 0 };
+        let exif_offset =
+            8 + ifd_size(&ifd0) + pointer_size(has_exif) + pointer_size(!self.gps.is_empty());
+        let interop_offset = exif_offset
+            + if has_exif {
+                ifd_size(&exif) + pointer_size(!self.interop.is_empty())
+            } else {
+                0
+            };
+        let gps_offset = interop_offset
+            + if self.interop.is_empty() {
+                0
+            } else {
+                ifd_size(&self.interop)
+            };
+        let ifd1_offset = gps_offset
+            + if self.gps.is_empty() {
+                0
+            } else {
+                ifd_size(&self.gps)
+            };
+        let thumbnail_offset = ifd1_offset
+            + if has_ifd1 {
+                ifd_size(&ifd1) + pointer_size(self.thumbnail.is_some()) * 2
+            } else {
+                0
+            };
+
+        if has_exif {
+            ifd0.push(Field::long(TAG_EXIF_IFD, exif_offset as u32));
+        }
+        if !self.gps.is_empty() {
+            ifd0.push(Field::long(TAG_GPS_IFD, gps_offset as u32));
+        }
+        if !self.interop.is_empty() {
+            exif.push(Field::long(TAG_INTEROP_IFD, interop_offset as u32));
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            ifd1.push(Field::long(TAG_THUMBNAIL_OFFSET, thumbnail_offset as u32));
+            ifd1.push(Field::long(TAG_THUMBNAIL_LENGTH, thumbnail.len() as u32));
+        }
+
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(byte_order.signature());
+        tiff.extend_from_slice(&byte_order.u16_bytes(42));
+        tiff.extend_from_slice(&byte_order.u32_bytes(8));
+
+        let next = if has_ifd1 { ifd1_offset as u32 } else { 0 };
+        write_ifd(&mut tiff, byte_order, &ifd0, next);
+        if has_exif {
+            write_ifd(&mut tiff, byte_order, &exif, 0);
+        }
+        if !self.interop.is_empty() {
+            write_ifd(&mut tiff, byte_order, &self.interop, 0);
+        }
+        if !self.gps.is_empty() {
+            write_ifd(&mut tiff, byte_order, &self.gps, 0);
+        }
+        if has_ifd1 {
+            write_ifd(&mut tiff, byte_order, &ifd1, 0);
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            tiff.extend_from_slice(thumbnail);
+        }
+
+        tiff
+    }
+}
+
+/// TIFFデータを指定したバイトオーダーで組み立て直します
+///
+/// すべてのIFDとサムネイルを新しい配置に書き出し、オフセットを再計算します。
+/// MakerNoteは値としてそのままコピーされるため、内部に絶対オフセットを持つ
+/// 形式では参照がずれる場合があります。
+pub(crate) fn rebuild(tiff: &[u8], byte_order: ByteOrder) -> Option<Vec<u8>> {
+    let (_, builder) = to_builder(tiff)?;
+    Some(builder.build(byte_order))
+}
+
+/// TIFFデータのすべてのエントリとサムネイルを読み込みます
+///
+/// 戻り値の1番目は元のバイトオーダーです。
+pub(crate) fn to_builder(tiff: &[u8]) -> Option<(ByteOrder, TiffBuilder)> {
+    let walk = walk(tiff)?;
+    let mut builder = TiffBuilder::default();
+
+    for entry in &walk.entries {
+        if is_pointer_tag(entry.tag) {
+            continue;
+        }
+        let Some(field) = Field::from_entry(tiff, &walk, entry) else {
+            continue;
+        };
+        match entry.ifd {
+            IfdKind::Ifd0 => builder.ifd0.push(field),
+            IfdKind::Exif => builder.exif.push(field),
+            IfdKind::Gps => builder.gps.push(field),
+            IfdKind::Interop => builder.interop.push(field),
+            IfdKind::Ifd1 => builder.ifd1.push(field),
+        }
+    }
+
+    if let (Some(offset), Some(length)) = (
+        walk.find(IfdKind::Ifd1, TAG_THUMBNAIL_OFFSET),
+        walk.find(IfdKind::Ifd1, TAG_THUMBNAIL_LENGTH),
+    ) {
+        let offset = walk.read_uint(tiff, offset)? as usize;
+        let length = walk.read_uint(tiff, length)? as usize;
+        builder.thumbnail = Some(tiff.get(offset..offset.checked_add(length)?)?.to_vec());
+    }
+
+    Some((walk.byte_order, builder))
+}
+
+/// IFD0のオリエンテーションを書き換えたTIFFデータを返します
+///
+/// タグが既にある場合は値のみを上書きし（範囲外の値も修復されます）、
+/// ない場合はタグを追加して組み立て直します。
+pub(crate) fn set_orientation(tiff: &[u8], value: u16) -> Option<Vec<u8>> {
+    let walk = walk(tiff)?;
+
+    if let Some(entry) = walk
+        .find(IfdKind::Ifd0, TAG_ORIENTATION)
+        .filter(|entry| entry.field_type == 3 && entry.count == 1)
+    {
+        let mut output = tiff.to_vec();
+        output[entry.value_offset..entry.value_offset + 2]
+            .copy_from_slice(&walk.byte_order.u16_bytes(value));
+        return Some(output);
+    }
+
+    let (byte_order, mut builder) = to_builder(tiff)?;
+    builder.ifd0.retain(|field| field.tag != TAG_ORIENTATION);
+    builder.ifd0.push(Field::short(TAG_ORIENTATION, value));
+    Some(builder.build(byte_order))
+}
+
+/// 位置情報をGPS IFDのエントリに変換します
+///
+/// 度分秒の秒は100万分の1秒、高度・速度・撮影方向は小数点以下3桁の精度で記録します。
+pub(crate) fn gps_fields(gps: &GpsCoordinates) -> Result<Vec<Field>, Error> {
+    if !gps.lat.is_finite() || !(-90.0..=90.0).contains(&gps.lat) {
+        return Err(Error::InvalidFormat(format!(
+            "Invalid latitude: {}",
+            gps.lat
+        )));
+    }
+    if !gps.lon.is_finite() || !(-180.0..=180.0).contains(&gps.lon) {
+        return Err(Error::InvalidFormat(format!(
+            "Invalid longitude: {}",
+            gps.lon
+        )));
+    }
+
+    // 度を（度, 分, 秒）の有理数に変換する
+    let dms = |degrees: f64| -> [(u32, u32); 3] {
+        const MICRO: u64 = 1_000_000;
+        let total = (degrees.abs() * 3600.0 * MICRO as f64).round() as u64;
+        [
+            ((total / (3600 * MICRO)) as u32, 1),
+            ((total / (60 * MICRO) % 60) as u32, 1),
+            ((total % (60 * MICRO)) as u32, MICRO as u32),
+        ]
+    };
+
+    let mut fields = vec![
+        Field::bytes(TAG_GPS_VERSION_ID, &[2, 3, 0, 0]),
+        Field::text(TAG_GPS_LATITUDE_REF, if gps.lat < 0.0 { "S" } else { "N" }),
+        Field::rationals(TAG_GPS_LATITUDE, &dms(gps.lat)),
+        Field::text(TAG_GPS_LONGITUDE_REF, if gps.lon < 0.0 { "W" } else { "E" }),
+        Field::rationals(TAG_GPS_LONGITUDE, &dms(gps.lon)),
+    ];
+
+    // 千分の一の単位の有理数に変換する（範囲外はNone）
+    let milli = |value: f64| -> Option<(u32, u32)> {
+        let scaled = (value * 1000.0).round();
+        (scaled.is_finite() && (0.0..=u32::MAX as f64).contains(&scaled))
+            .then_some((scaled as u32, 1000))
+    };
+
+    if let Some(alt) = gps.alt {
+        let altitude = milli(alt.abs())
+            .ok_or_else(|| Error::InvalidFormat(format!("Invalid altitude: {alt}")))?;
+        fields.push(Field::bytes(TAG_GPS_ALTITUDE_REF, &[(alt < 0.0) as u8]));
+        fields.push(Field::rationals(TAG_GPS_ALTITUDE, &[altitude]));
+    }
+
+    if let Some(speed) = gps.speed {
+        let value =
+            milli(speed).ok_or_else(|| Error::InvalidFormat(format!("Invalid speed: {speed}")))?;
+        fields.push(Field::text(TAG_GPS_SPEED_REF, "K"));
+        fields.push(Field::rationals(TAG_GPS_SPEED, &[value]));
+    }
+
+    if let Some(direction) = gps.img_direction {
+        let value = milli(direction)
+            .filter(|_| direction < 360.0)
+            .ok_or_else(|| Error::InvalidFormat(format!("Invalid image direction: {direction}")))?;
+        fields.push(Field::text(TAG_GPS_IMG_DIRECTION_REF, "T"));
+        fields.push(Field::rationals(TAG_GPS_IMG_DIRECTION, &[value]));
+    }
+
+    Ok(fields)
+}
+
+/// GPS IFDの位置情報を書き換えたTIFFデータを返します
+///
+/// 緯度・経度・高度とその参照、バージョンを置き換え、その他のGPSタグ（日時など）は残します。
+/// 高度を指定しない場合は既存の高度を削除します。速度・撮影方向は指定した場合のみ置き換えます。
+pub(crate) fn set_gps(tiff: &[u8], gps: &GpsCoordinates) -> Result<Vec<u8>, Error> {
+    let fields = gps_fields(gps)?;
+    let (byte_order, mut builder) =
+        to_builder(tiff).ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string()))?;
+
+    builder.gps.retain(|field| match field.tag {
+        TAG_GPS_VERSION_ID..=TAG_GPS_ALTITUDE => false,
+        TAG_GPS_SPEED_REF | TAG_GPS_SPEED => gps.speed.is_none(),
+        TAG_GPS_IMG_DIRECTION_REF | TAG_GPS_IMG_DIRECTION => gps.img_direction.is_none(),
+        _ => true,
+    });
+    builder.gps.extend(fields);
+    Ok(builder.build(byte_order))
+}
+
+/// 文字列タグを設定します
+///
+/// 空文字列の場合はタグを削除します。
+pub(crate) fn apply_text(
+    builder: &mut TiffBuilder,
+    ifd: IfdKind,
+    tag: u16,
+    value: &str,
+) -> Result<(), Error> {
+    if is_pointer_tag(tag) || tag == TAG_THUMBNAIL_OFFSET || tag == TAG_THUMBNAIL_LENGTH {
+        return Err(Error::InvalidFormat(format!(
+            "Tag 0x{tag:04X} cannot hold a string"
+        )));
+    }
+    if value.contains('\0') {
+        return Err(Error::InvalidFormat(
+            "EXIF strings cannot contain NUL characters".to_string(),
+        ));
+    }
+
+    let fields = match ifd {
+        IfdKind::Ifd0 => &mut builder.ifd0,
+        IfdKind::Exif => &mut builder.exif,
+        IfdKind::Gps => &mut builder.gps,
+        IfdKind::Interop => &mut builder.interop,
+        IfdKind::Ifd1 => &mut builder.ifd1,
+    };
+    fields.retain(|field| field.tag != tag);
+    if !value.is_empty() {
+        fields.push(Field::text(tag, value));
+    }
+    Ok(())
+}
+
+/// WindowsのXP系のタグを設定します
+///
+/// 空文字列の場合はタグを削除します。
+pub(crate) fn apply_xp_text(
+    builder: &mut TiffBuilder,
+    tag: XpTag,
+    value: &str,
+) -> Result<(), Error> {
+    if value.contains('\0') {
+        return Err(Error::InvalidFormat(
+            "EXIF strings cannot contain NUL characters".to_string(),
+        ));
+    }
+
+    let tag = tag.tag();
+    builder.ifd0.retain(|field| field.tag != tag);
+    if !value.is_empty() {
+        let mut bytes: Vec<u8> = value.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        bytes.extend_from_slice(&[0, 0]);
+        builder.ifd0.push(Field::bytes(tag, &bytes));
+    }
+    Ok(())
+}
+
+/// IFD1のサムネイル画像を差し替えます
+///
+/// 圧縮方式（Compression）はJPEGに設定し、位置と長さは[`TiffBuilder::build`]で設定されます。
+pub(crate) fn apply_thumbnail(builder: &mut TiffBuilder, thumbnail: &[u8]) {
+    builder.ifd1.retain(|field| field.tag != TAG_COMPRESSION);
+    builder
+        .ifd1
+        .push(Field::short(TAG_COMPRESSION, COMPRESSION_JPEG));
+    builder.thumbnail = Some(thumbnail.to_vec());
+}
+
+/// 文字列タグを書き換えたTIFFデータを返します
+pub(crate) fn set_text(tiff: &[u8], ifd: IfdKind, tag: u16, value: &str) -> Result<Vec<u8>, Error> {
+    let (byte_order, mut builder) =
+        to_builder(tiff).ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string()))?;
+    apply_text(&mut builder, ifd, tag, value)?;
+    Ok(builder.build(byte_order))
+}
+
+/// 指定された日時と時差をIFDのエントリに反映します
+///
+/// `None`の日時は変更しません。日時を指定して時差が`None`の場合は既存の時差を削除します。
+pub(crate) fn apply_date_times(
+    builder: &mut TiffBuilder,
+    date_times: &DateTimes,
+) -> Result<(), Error> {
+    let targets = [
+        (&date_times.modified, TAG_DATE_TIME, TAG_OFFSET_TIME),
+        (
+            &date_times.original,
+            TAG_DATE_TIME_ORIGINAL,
+            TAG_OFFSET_TIME_ORIGINAL,
+        ),
+        (
+            &date_times.digitized,
+            TAG_DATE_TIME_DIGITIZED,
+            TAG_OFFSET_TIME_DIGITIZED,
+        ),
+    ];
+
+    for (date_time, tag, offset_tag) in targets {
+        let Some(date_time) = date_time else {
+            continue;
+        };
+        if parse_date_time(&date_time.value).is_none() {
+            return Err(Error::InvalidFormat(format!(
+                "Invalid date/time: {}",
+                date_time.value
+            )));
+        }
+        if let Some(offset) = &date_time.offset {
+            if parse_offset(offset).is_none() {
+                return Err(Error::InvalidFormat(format!(
+                    "Invalid time zone offset: {offset}"
+                )));
+            }
+        }
+
+        // DateTimeはIFD0、それ以外の日時と時差はExif IFDに記録する
+        let fields = if tag == TAG_DATE_TIME {
+            &mut builder.ifd0
+        } else {
+            &mut builder.exif
+        };
+        fields.retain(|field| field.tag != tag);
+        fields.push(Field::text(tag, &date_time.value));
+
+        builder.exif.retain(|field| field.tag != offset_tag);
+        if let Some(offset) = &date_time.offset {
+            builder.exif.push(Field::text(offset_tag, offset));
+        }
+    }
+
+    Ok(())
+}
+
+/// 日時と時差を書き換えたTIFFデータを返します
+pub(crate) fn set_date_times(tiff: &[u8], date_times: &DateTimes) -> Result<Vec<u8>, Error> {
+    let (byte_order, mut builder) =
+        to_builder(tiff).ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string()))?;
+    apply_date_times(&mut builder, date_times)?;
+    Ok(builder.build(byte_order))
+}
+
+/// IFDテーブルと4バイトを超える値の合計サイズ
+fn ifd_size(fields: &[Field]) -> usize {
+    2 + fields.len() * 12
+        + 4
+        + fields
+            .iter()
+            .filter(|field| field.value.len() > 4)
+            .map(|field| field.value.len() + field.value.len() % 2)
+            .sum::<usize>()
+}
+
+/// IFDテーブルと値を書き込みます
+fn write_ifd(tiff: &mut Vec<u8>, byte_order: ByteOrder, fields: &[Field], next: u32) {
+    let mut fields: Vec<&Field> = fields.iter().collect();
+    fields.sort_by_key(|field| field.tag);
+
+    let mut value_offset = tiff.len() + 2 + fields.len() * 12 + 4;
+    let mut values = Vec::new();
+
+    tiff.extend_from_slice(&byte_order.u16_bytes(fields.len() as u16));
+    for field in fields {
+        let value = field.value_in(byte_order);
+        tiff.extend_from_slice(&byte_order.u16_bytes(field.tag));
+        tiff.extend_from_slice(&byte_order.u16_bytes(field.field_type));
+        tiff.extend_from_slice(&byte_order.u32_bytes(field.count));
+
+        if value.len() <= 4 {
+            let mut inline = [0u8; 4];
+            inline[..value.len()].copy_from_slice(&value);
+            tiff.extend_from_slice(&inline);
+        } else {
+            tiff.extend_from_slice(&byte_order.u32_bytes(value_offset as u32));
+            value_offset += value.len() + value.len() % 2;
+            values.extend_from_slice(&value);
+            // 値はワード境界に揃える
+            if value.len() % 2 == 1 {
+                values.push(0);
+            }
+        }
+    }
+
+    // 次のIFDへのオフセット
+    tiff.extend_from_slice(&byte_order.u32_bytes(next));
+    tiff.extend_from_slice(&values);
+}
@@ -0,0 +1,80 @@
+use crate::png::PNG_SIGNATURE;
+
+/// JPEGのSOIマーカーと、続くマーカーの先頭バイト
+const JPEG_SIGNATURE: [u8; 3] = [0xFF, 0xD8, 0xFF];
+
+/// 対応する画像形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// JPEG
+    Jpeg,
+    /// PNG
+    Png,
+}
+
+impl ImageFormat {
+    /// 先頭のマジックバイトから画像形式を判定します
+    ///
+    /// 判定はシグネチャのみで行い、メモリ確保やデコードは行いません。
+    pub const fn detect(data: &[u8]) -> Option<Self> {
+        if starts_with(data, &PNG_SIGNATURE) {
+            Some(ImageFormat::Png)
+        } else if starts_with(data, &JPEG_SIGNATURE) {
+            Some(ImageFormat::Jpeg)
+        } else {
+            None
+        }
+    }
+
+    /// MIMEタイプ
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+        }
+    }
+}
+
+/// 先頭のマジックバイトからMIMEタイプを判定します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（先頭の数バイトがあれば十分です）
+///
+/// # Returns
+/// * `Some("image/jpeg")`、`Some("image/png")` - 対応している形式
+/// * `None` - このクレートが扱えない形式
+pub fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    ImageFormat::detect(data).map(ImageFormat::mime_type)
+}
+
+/// 先頭の16バイトから画像形式を判定します
+///
+/// # Arguments
+/// * `header` - データの先頭16バイト
+///
+/// # Returns
+/// * `Some(ImageFormat)` - 対応している形式
+/// * `None` - このクレートが扱えない形式
+///
+/// # Details
+/// `const fn`で、メモリ確保や標準ライブラリに依存する処理を行いません。
+/// 本体をバッファリングする前に、ソケットから読んだ先頭のバイトで振り分ける用途に利用できます。
+/// 判定結果は[`ImageFormat::detect`]と同じです。
+pub const fn sniff(header: &[u8; 16]) -> Option<ImageFormat> {
+    ImageFormat::detect(header)
+}
+
+/// `const fn`で使用できる[`slice::starts_with`]
+const fn starts_with(data: &[u8], prefix: &[u8]) -> bool {
+    if data.len() < prefix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < prefix.len() {
+        if data[i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
@@ -0,0 +1,724 @@
+use crate::isobmff::{parse_boxes, write_box, IsoBox};
+use crate::Error;
+use std::ops::Range;
+
+/// HEIF系（AVIF、HEIC）のファイルタイプを示すブランド
+const HEIF_BRANDS: &[&[u8; 4]] = &[
+    b"avif", b"avis", b"heic", b"heix", b"heim", b"heis", b"hevc", b"mif1", b"msf1",
+];
+
+/// colrボックスに記録されたnclx形式の色空間情報
+///
+/// 値はITU-T H.273のコードポイントです（例：BT.709は1、Display P3は12）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Nclx {
+    /// 色域（colour_primaries）
+    pub colour_primaries: u16,
+    /// 伝達特性（transfer_characteristics）
+    pub transfer_characteristics: u16,
+    /// 行列係数（matrix_coefficients）
+    pub matrix_coefficients: u16,
+    /// フルレンジかどうか
+    pub full_range: bool,
+}
+
+/// メタデータ軽量化のオプション
+///
+/// 色空間情報（`colr`）を含むアイテムプロパティは常に保持します。
+#[derive(Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// EXIFアイテムを保持する
+    pub preserve_exif: bool,
+    /// XMPアイテム（`application/rdf+xml`）を保持する
+    pub preserve_xmp: bool,
+}
+
+/// AVIF/HEIF画像からICCプロファイルを読み取ります
+///
+/// プライマリアイテムに関連付けられた`colr`ボックス（`prof`または`rICC`）を探します。
+///
+/// # Returns
+/// * `Ok(Some(Vec<u8>))` - ICCプロファイル
+/// * `Ok(None)` - ICCプロファイルがない場合（nclxのみの場合を含む）
+/// * `Err(Error)` - AVIF/HEIFではない場合
+pub fn read_icc_profile(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    Ok(find_colr(data, &[b"prof", b"rICC"])?.map(|colr| colr[4..].to_vec()))
+}
+
+/// AVIF/HEIF画像からnclx形式の色空間情報を読み取ります
+///
+/// # Returns
+/// * `Ok(Some(Nclx))` - プライマリアイテムの色空間情報
+/// * `Ok(None)` - nclx形式の`colr`ボックスがない場合
+/// * `Err(Error)` - AVIF/HEIFではない場合
+pub fn read_nclx(data: &[u8]) -> Result<Option<Nclx>, Error> {
+    let Some(colr) = find_colr(data, &[b"nclx"])? else {
+        return Ok(None);
+    };
+    if colr.len() < 11 {
+        return Err(Error::ParseError("nclx colour box too short".to_string()));
+    }
+
+    let read_u16 = |pos: usize| u16::from_be_bytes([colr[pos], colr[pos + 1]]);
+    Ok(Some(Nclx {
+        colour_primaries: read_u16(4),
+        transfer_characteristics: read_u16(6),
+        matrix_coefficients: read_u16(8),
+        full_range: colr[10] & 0x80 != 0,
+    }))
+}
+
+/// AVIF/HEIF画像からEXIF・XMPのアイテムを削除します
+///
+/// [`CleanOptions`]のデフォルト値で[`clean_metadata_with_options`]を実行します。
+pub fn clean_metadata(data: &[u8]) -> Result<Vec<u8>, Error> {
+    clean_metadata_with_options(data, &CleanOptions::default())
+}
+
+/// オプションを指定してAVIF/HEIF画像からEXIF・XMPのアイテムを削除します
+///
+/// # Arguments
+/// * `data` - AVIF/HEIF画像のバイトデータ
+/// * `options` - 保持するメタデータの指定
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - メタデータを削除した画像データ（削除するアイテムがない場合は元のデータのコピー）
+/// * `Err(Error)` - AVIF/HEIFではない場合や、アイテムの配置を解釈できない場合
+///
+/// # Details
+/// - アイテム情報（iinf）・配置（iloc）・参照（iref）・プロパティの関連付け（ipma）から
+///   削除するアイテムを取り除き、mdat内のデータも削除して残りのアイテムの位置を詰め直します
+/// - アイテムプロパティ（ipco）は変更せずにコピーするため、`colr`ボックス（ICCプロファイル・nclx）は
+///   そのまま残ります。出力のプライマリアイテムの`colr`が入力と一致しない場合はエラーになります
+/// - idat内のデータは位置を変えずに0で埋めます
+pub fn clean_metadata_with_options(data: &[u8], options: &CleanOptions) -> Result<Vec<u8>, Error> {
+    let top = parse_heif(data)?;
+    let Some(meta) = top.iter().find(|b| &b.box_type == b"meta") else {
+        return Ok(data.to_vec());
+    };
+    let meta_content = meta
+        .data
+        .get(4..)
+        .ok_or_else(|| Error::ParseError("meta box too short".to_string()))?;
+    let children = parse_boxes(meta_content)?;
+
+    let removed = metadata_items(&children, options)?;
+    if removed.is_empty() {
+        return Ok(data.to_vec());
+    }
+
+    let iloc = children
+        .iter()
+        .find(|b| &b.box_type == b"iloc")
+        .ok_or_else(|| Error::InvalidFormat("iloc box not found".to_string()))?;
+    let iloc = Iloc::parse(iloc.data)?;
+
+    // 削除するアイテムのデータの範囲（ファイル内とidat内）
+    let mut file_ranges: Vec<Range<usize>> = Vec::new();
+    let mut idat_ranges: Vec<Range<usize>> = Vec::new();
+    for item in iloc.items.iter().filter(|item| removed.contains(&item.id)) {
+        for extent in &item.extents {
+            let start = usize::try_from(item.extent_start(extent)?)
+                .map_err(|_| Error::InvalidFormat("Item extent out of range".to_string()))?;
+            let length = usize::try_from(extent.length)
+                .ok()
+                .filter(|&length| length > 0)
+                .ok_or_else(|| Error::InvalidFormat("Unsupported item extent".to_string()))?;
+            let end = start
+                .checked_add(length)
+                .ok_or_else(|| Error::InvalidFormat("Item extent out of range".to_string()))?;
+            match item.construction_method {
+                0 => file_ranges.push(start..end),
+                1 => idat_ranges.push(start..end),
+                _ => {}
+            }
+        }
+    }
+    file_ranges.sort_by_key(|range| range.start);
+
+    // ファイル内の範囲はmdatの内容に収まっている必要がある
+    for range in &file_ranges {
+        let inside = top.iter().any(|b| {
+            &b.box_type == b"mdat"
+                && range.start >= b.data_offset
+                && range.end <= b.data_offset + b.data.len()
+        });
+        if !inside {
+            return Err(Error::InvalidFormat(
+                "Metadata item is not stored in mdat".to_string(),
+            ));
+        }
+    }
+
+    // metaの大きさは位置の値に依存しないため、まず大きさを求めてから位置を詰め直す
+    let meta_end = meta.data_offset + meta.data.len();
+    let old_meta_len = meta_end - meta.offset;
+    let placeholder = build_meta(
+        meta.data,
+        &children,
+        &removed,
+        &iloc,
+        &idat_ranges,
+        &|pos| Some(pos),
+    )?;
+    let shrink = old_meta_len
+        .checked_sub(placeholder.len())
+        .ok_or_else(|| Error::InvalidFormat("meta box grew while cleaning".to_string()))?;
+    let map = |pos: usize| -> Option<usize> {
+        if file_ranges.iter().any(|range| range.contains(&pos)) {
+            return None;
+        }
+        let removed_before: usize = file_ranges
+            .iter()
+            .filter(|range| range.end <= pos)
+            .map(|range| range.len())
+            .sum();
+        let meta_shift = if pos >= meta_end { shrink } else { 0 };
+        Some(pos - removed_before - meta_shift)
+    };
+    let new_meta = build_meta(meta.data, &children, &removed, &iloc, &idat_ranges, &map)?;
+
+    let mut output = Vec::with_capacity(data.len());
+    for b in &top {
+        let end = b.data_offset + b.data.len();
+        match &b.box_type {
+            b"meta" if b.offset == meta.offset => output.extend_from_slice(&new_meta),
+            b"mdat" => {
+                let mut content = Vec::with_capacity(b.data.len());
+                let mut pos = b.data_offset;
+                for range in file_ranges
+                    .iter()
+                    .filter(|range| range.start >= b.data_offset && range.end <= end)
+                {
+                    content.extend_from_slice(&data[pos..range.start.max(pos)]);
+                    pos = pos.max(range.end);
+                }
+                content.extend_from_slice(&data[pos..end]);
+                write_box_like(&mut output, data, b, &content)?;
+            }
+            _ => output.extend_from_slice(&data[b.offset..end]),
+        }
+    }
+
+    // 色空間情報が変わっていないか検証
+    for colour_types in [&[b"prof", b"rICC"][..], &[b"nclx"][..]] {
+        if find_colr(&output, colour_types)? != find_colr(data, colour_types)? {
+            return Err(Error::InvalidFormat(
+                "Colour information changed while cleaning".to_string(),
+            ));
+        }
+    }
+
+    Ok(output)
+}
+
+/// 削除するメタデータのアイテムIDを返します
+fn metadata_items(children: &[IsoBox], options: &CleanOptions) -> Result<Vec<u32>, Error> {
+    let Some(iinf) = children.iter().find(|b| &b.box_type == b"iinf") else {
+        return Ok(Vec::new());
+    };
+    let mut removed = Vec::new();
+    for infe in parse_boxes(iinf_entries(iinf.data)?)? {
+        let Some((id, item_type, content_type)) = parse_infe(infe.data) else {
+            continue;
+        };
+        let remove = match &item_type {
+            b"Exif" => !options.preserve_exif,
+            b"mime" => !options.preserve_xmp && content_type.starts_with(b"application/rdf+xml"),
+            _ => false,
+        };
+        if remove {
+            removed.push(id);
+        }
+    }
+    Ok(removed)
+}
+
+/// iinfのエントリ（infeボックスの並び）を返します
+fn iinf_entries(iinf: &[u8]) -> Result<&[u8], Error> {
+    let header = if iinf.first() == Some(&0) { 6 } else { 8 };
+    iinf.get(header..)
+        .ok_or_else(|| Error::ParseError("iinf box too short".to_string()))
+}
+
+/// infe（バージョン2以降）からアイテムID・アイテムタイプ・コンテンツタイプを読み取ります
+fn parse_infe(infe: &[u8]) -> Option<(u32, [u8; 4], &[u8])> {
+    let (id, pos) = match infe.first()? {
+        2 => (
+            u16::from_be_bytes(infe.get(4..6)?.try_into().ok()?) as u32,
+            6,
+        ),
+        3 => (u32::from_be_bytes(infe.get(4..8)?.try_into().ok()?), 8),
+        _ => return None,
+    };
+    // item_protection_index(2) + item_type(4) + item_name（null終端）+ content_type（mimeのみ）
+    let item_type: [u8; 4] = infe.get(pos + 2..pos + 6)?.try_into().ok()?;
+    let rest = infe.get(pos + 6..)?;
+    let name_end = rest
+        .iter()
+        .position(|&b| b == 0)
+        .map_or(rest.len(), |p| p + 1);
+    let content = &rest[name_end..];
+    let content_type = &content[..content
+        .iter()
+        .position(|&b| b == 0)
+        .unwrap_or(content.len())];
+    Some((id, item_type, content_type))
+}
+
+/// 削除するアイテムを取り除いたmetaボックスを組み立てます
+fn build_meta(
+    meta: &[u8],
+    children: &[IsoBox],
+    removed: &[u32],
+    iloc: &Iloc,
+    idat_ranges: &[Range<usize>],
+    map: &dyn Fn(usize) -> Option<usize>,
+) -> Result<Vec<u8>, Error> {
+    // metaはFullBox（バージョン・フラグ4バイト）
+    let mut content = meta[..4].to_vec();
+    for child in children {
+        match &child.box_type {
+            b"iinf" => {
+                let entries = iinf_entries(child.data)?;
+                let mut kept = Vec::new();
+                let mut count = 0u32;
+                for infe in parse_boxes(entries)? {
+                    if parse_infe(infe.data).is_some_and(|(id, _, _)| removed.contains(&id)) {
+                        continue;
+                    }
+                    write_box(&mut kept, b"infe", infe.data)?;
+                    count += 1;
+                }
+                let mut iinf = child.data[..4].to_vec();
+                if child.data[0] == 0 {
+                    let count = u16::try_from(count)
+                        .map_err(|_| Error::InvalidFormat("Too many items".to_string()))?;
+                    iinf.extend_from_slice(&count.to_be_bytes());
+                } else {
+                    iinf.extend_from_slice(&count.to_be_bytes());
+                }
+                iinf.extend_from_slice(&kept);
+                write_box(&mut content, b"iinf", &iinf)?;
+            }
+            b"iloc" => write_box(&mut content, b"iloc", &iloc.write(removed, map)?)?,
+            b"iref" => write_box(&mut content, b"iref", &rewrite_iref(child.data, removed)?)?,
+            b"iprp" => {
+                let mut iprp = Vec::with_capacity(child.data.len());
+                for property in parse_boxes(child.data)? {
+                    if &property.box_type == b"ipma" {
+                        write_box(&mut iprp, b"ipma", &rewrite_ipma(property.data, removed)?)?;
+                    } else {
+                        // ipco（colrを含む）はそのままコピーする
+                        write_box(&mut iprp, &property.box_type, property.data)?;
+                    }
+                }
+                write_box(&mut content, b"iprp", &iprp)?;
+            }
+            b"idat" => {
+                let mut idat = child.data.to_vec();
+                for range in idat_ranges {
+                    if let Some(bytes) = idat.get_mut(range.clone()) {
+                        bytes.fill(0);
+                    }
+                }
+                write_box(&mut content, b"idat", &idat)?;
+            }
+            _ => write_box(&mut content, &child.box_type, child.data)?,
+        }
+    }
+
+    let mut output = Vec::with_capacity(content.len() + 8);
+    write_box(&mut output, b"meta", &content)?;
+    Ok(output)
+}
+
+/// 削除するアイテムへの参照を取り除いたirefの内容を返します
+fn rewrite_iref(iref: &[u8], removed: &[u32]) -> Result<Vec<u8>, Error> {
+    let id_size = if iref.first() == Some(&0) { 2 } else { 4 };
+    let mut output = iref
+        .get(..4)
+        .ok_or_else(|| Error::ParseError("iref box too short".to_string()))?
+        .to_vec();
+    for reference in parse_boxes(&iref[4..])? {
+        let ids: Vec<u32> = reference
+            .data
+            .chunks_exact(id_size)
+            .map(|id| id.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32))
+            .collect();
+        // from_item_ID + reference_count(2) + to_item_ID
+        let (Some(&from), Some(count)) = (ids.first(), reference.data.get(id_size..id_size + 2))
+        else {
+            continue;
+        };
+        let count = u16::from_be_bytes([count[0], count[1]]) as usize;
+        if removed.contains(&from) {
+            continue;
+        }
+        let to: Vec<u32> = reference.data[id_size + 2..]
+            .chunks_exact(id_size)
+            .take(count)
+            .map(|id| id.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32))
+            .filter(|id| !removed.contains(id))
+            .collect();
+        if to.is_empty() {
+            continue;
+        }
+
+        let mut content = Vec::new();
+        push_uint(&mut content, from as u64, id_size)?;
+        content.extend_from_slice(&(to.len() as u16).to_be_bytes());
+        for id in to {
+            push_uint(&mut content, id as u64, id_size)?;
+        }
+        write_box(&mut output, &reference.box_type, &content)?;
+    }
+    Ok(output)
+}
+
+/// 削除するアイテムの関連付けを取り除いたipmaの内容を返します
+fn rewrite_ipma(ipma: &[u8], removed: &[u32]) -> Result<Vec<u8>, Error> {
+    let truncated = || Error::ParseError("ipma box truncated".to_string());
+    let version = *ipma.first().ok_or_else(truncated)?;
+    let large_index = ipma.get(3).is_some_and(|flags| flags & 1 != 0);
+    let entry_count = u32::from_be_bytes(ipma.get(4..8).ok_or_else(truncated)?.try_into().unwrap());
+    let id_size = if version < 1 { 2 } else { 4 };
+    let index_size = if large_index { 2 } else { 1 };
+
+    let mut entries = Vec::new();
+    let mut kept = 0u32;
+    let mut pos = 8;
+    for _ in 0..entry_count {
+        let id = ipma.get(pos..pos + id_size).ok_or_else(truncated)?;
+        let id = id.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        let count = *ipma.get(pos + id_size).ok_or_else(truncated)? as usize;
+        let end = pos + id_size + 1 + count * index_size;
+        let entry = ipma.get(pos..end).ok_or_else(truncated)?;
+        if !removed.contains(&id) {
+            entries.extend_from_slice(entry);
+            kept += 1;
+        }
+        pos = end;
+    }
+
+    let mut output = ipma[..4].to_vec();
+    output.extend_from_slice(&kept.to_be_bytes());
+    output.extend_from_slice(&entries);
+    Ok(output)
+}
+
+/// ilocのアイテムの配置
+struct Iloc {
+    version: u8,
+    flags: [u8; 3],
+    offset_size: usize,
+    length_size: usize,
+    base_offset_size: usize,
+    index_size: usize,
+    items: Vec<IlocItem>,
+}
+
+struct IlocItem {
+    id: u32,
+    construction_method: u8,
+    data_reference_index: u16,
+    base_offset: u64,
+    extents: Vec<IlocExtent>,
+}
+
+struct IlocExtent {
+    index: u64,
+    offset: u64,
+    length: u64,
+}
+
+impl IlocItem {
+    /// エクステントの開始位置（ベースオフセットからの相対位置を加えた値）
+    fn extent_start(&self, extent: &IlocExtent) -> Result<u64, Error> {
+        self.base_offset
+            .checked_add(extent.offset)
+            .ok_or_else(|| Error::InvalidFormat("Item extent out of range".to_string()))
+    }
+}
+
+impl Iloc {
+    fn parse(iloc: &[u8]) -> Result<Self, Error> {
+        let truncated = || Error::ParseError("iloc box truncated".to_string());
+        let mut pos = 0;
+        let mut read = |size: usize| -> Result<u64, Error> {
+            let bytes = iloc.get(pos..pos + size).ok_or_else(truncated)?;
+            pos += size;
+            Ok(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64))
+        };
+
+        let version = read(1)? as u8;
+        let flags = (read(3)? as u32).to_be_bytes();
+        let sizes = read(2)?;
+        let offset_size = (sizes >> 12) as usize;
+        let length_size = ((sizes >> 8) & 0xF) as usize;
+        let base_offset_size = ((sizes >> 4) & 0xF) as usize;
+        let index_size = if version >= 1 {
+            (sizes & 0xF) as usize
+        } else {
+            0
+        };
+        for size in [offset_size, length_size, base_offset_size, index_size] {
+            if ![0, 4, 8].contains(&size) {
+                return Err(Error::InvalidFormat(format!(
+                    "Unsupported iloc field size: {size}"
+                )));
+            }
+        }
+
+        let id_size = if version < 2 { 2 } else { 4 };
+        let item_count = read(id_size)?;
+        let mut items = Vec::new();
+        for _ in 0..item_count {
+            let id = read(id_size)? as u32;
+            let construction_method = if version >= 1 {
+                (read(2)? & 0xF) as u8
+            } else {
+                0
+            };
+            let data_reference_index = read(2)? as u16;
+            let base_offset = read(base_offset_size)?;
+            let extent_count = read(2)?;
+            let mut extents = Vec::new();
+            for _ in 0..extent_count {
+                extents.push(IlocExtent {
+                    index: read(index_size)?,
+                    offset: read(offset_size)?,
+                    length: read(length_size)?,
+                });
+            }
+            items.push(IlocItem {
+                id,
+                construction_method,
+                data_reference_index,
+                base_offset,
+                extents,
+            });
+        }
+
+        Ok(Self {
+            version,
+            flags: [flags[1], flags[2], flags[3]],
+            offset_size,
+            length_size,
+            base_offset_size,
+            index_size,
+            items,
+        })
+    }
+
+    /// 削除するアイテムを除き、ファイル内の位置を`map`で変換して書き込みます
+    fn write(
+        &self,
+        removed: &[u32],
+        map: &dyn Fn(usize) -> Option<usize>,
+    ) -> Result<Vec<u8>, Error> {
+        let out_of_range = || Error::InvalidFormat("Item extent out of range".to_string());
+        let mut output = vec![self.version];
+        output.extend_from_slice(&self.flags);
+        output.push(((self.offset_size << 4) | self.length_size) as u8);
+        output.push(((self.base_offset_size << 4) | self.index_size) as u8);
+
+        let id_size = if self.version < 2 { 2 } else { 4 };
+        let items: Vec<&IlocItem> = self
+            .items
+            .iter()
+            .filter(|item| !removed.contains(&item.id))
+            .collect();
+        push_uint(&mut output, items.len() as u64, id_size)?;
+
+        for item in items {
+            push_uint(&mut output, item.id as u64, id_size)?;
+            if self.version >= 1 {
+                output.extend_from_slice(&(item.construction_method as u16).to_be_bytes());
+            }
+            output.extend_from_slice(&item.data_reference_index.to_be_bytes());
+
+            // ファイル内のデータ（外部参照でないもの）のみ位置を変換する
+            let in_file = item.construction_method == 0 && item.data_reference_index == 0;
+            let convert = |pos: u64| -> Result<u64, Error> {
+                if !in_file {
+                    return Ok(pos);
+                }
+                let pos = usize::try_from(pos).map_err(|_| out_of_range())?;
+                map(pos).map(|pos| pos as u64).ok_or_else(out_of_range)
+            };
+            let base_offset = convert(item.base_offset)?;
+            push_uint(&mut output, base_offset, self.base_offset_size)?;
+            output.extend_from_slice(&(item.extents.len() as u16).to_be_bytes());
+            for extent in &item.extents {
+                let offset = convert(item.extent_start(extent)?)?
+                    .checked_sub(base_offset)
+                    .ok_or_else(out_of_range)?;
+                push_uint(&mut output, extent.index, self.index_size)?;
+                push_uint(&mut output, offset, self.offset_size)?;
+                push_uint(&mut output, extent.length, self.length_size)?;
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// 指定したバイト数（0・2・4・8）のビッグエンディアンの整数を書き込みます
+fn push_uint(output: &mut Vec<u8>, value: u64, size: usize) -> Result<(), Error> {
+    if size < 8 && value >> (size * 8) != 0 {
+        return Err(Error::InvalidFormat(format!(
+            "Value {value} does not fit in {size} bytes"
+        )));
+    }
+    output.extend_from_slice(&value.to_be_bytes()[8 - size..]);
+    Ok(())
+}
+
+/// 元のボックスのヘッダー形式（サイズ0・64ビットサイズ）を保って書き込みます
+fn write_box_like(
+    output: &mut Vec<u8>,
+    data: &[u8],
+    b: &IsoBox,
+    content: &[u8],
+) -> Result<(), Error> {
+    let size = &data[b.offset..b.offset + 4];
+    if size == [0, 0, 0, 0] {
+        output.extend_from_slice(&[0, 0, 0, 0]);
+        output.extend_from_slice(&b.box_type);
+    } else if size == [0, 0, 0, 1] {
+        output.extend_from_slice(&[0, 0, 0, 1]);
+        output.extend_from_slice(&b.box_type);
+        output.extend_from_slice(&(content.len() as u64 + 16).to_be_bytes());
+    } else {
+        return write_box(output, &b.box_type, content);
+    }
+    output.extend_from_slice(content);
+    Ok(())
+}
+
+/// ファイルタイプを確認し、トップレベルのボックスを列挙します
+fn parse_heif(data: &[u8]) -> Result<Vec<IsoBox<'_>>, Error> {
+    let top = parse_boxes(data)?;
+
+    let ftyp = top
+        .first()
+        .filter(|b| &b.box_type == b"ftyp" && b.data.len() >= 8)
+        .ok_or_else(|| Error::InvalidFormat("Not a valid HEIF file".to_string()))?;
+    let is_heif = ftyp
+        .data
+        .chunks_exact(4)
+        .enumerate()
+        // major_brand(4) + minor_version(4) + compatible_brands
+        .filter(|(index, _)| *index != 1)
+        .any(|(_, brand)| HEIF_BRANDS.iter().any(|heif| &heif[..] == brand));
+    if !is_heif {
+        return Err(Error::InvalidFormat("Not a valid HEIF file".to_string()));
+    }
+
+    Ok(top)
+}
+
+/// 指定した形式の`colr`ボックスの内容を探します
+///
+/// プライマリアイテムに関連付けられたものを優先し、関連付けがない場合は最初のものを返します。
+fn find_colr<'a>(data: &'a [u8], colour_types: &[&[u8; 4]]) -> Result<Option<&'a [u8]>, Error> {
+    let top = parse_heif(data)?;
+
+    let Some(meta) = top.iter().find(|b| &b.box_type == b"meta") else {
+        return Ok(None);
+    };
+    // metaはFullBox（バージョン・フラグ4バイト）
+    let meta = parse_boxes(meta.data.get(4..).unwrap_or_default())?;
+
+    let primary = meta
+        .iter()
+        .find(|b| &b.box_type == b"pitm")
+        .and_then(|pitm| read_item_id(pitm.data, 4));
+
+    let Some(iprp) = meta.iter().find(|b| &b.box_type == b"iprp") else {
+        return Ok(None);
+    };
+    let iprp = parse_boxes(iprp.data)?;
+    let Some(ipco) = iprp.iter().find(|b| &b.box_type == b"ipco") else {
+        return Ok(None);
+    };
+    let properties = parse_boxes(ipco.data)?;
+
+    let matches = |property: &IsoBox| {
+        &property.box_type == b"colr"
+            && property.data.len() >= 4
+            && colour_types.iter().any(|t| t[..] == property.data[..4])
+    };
+
+    // プライマリアイテムに関連付けられたプロパティ
+    if let (Some(primary), Some(ipma)) = (primary, iprp.iter().find(|b| &b.box_type == b"ipma")) {
+        for index in associated_properties(ipma.data, primary) {
+            if let Some(property) = index.checked_sub(1).and_then(|i| properties.get(i)) {
+                if matches(property) {
+                    return Ok(Some(property.data));
+                }
+            }
+        }
+    }
+
+    Ok(properties
+        .iter()
+        .find(|property| matches(property))
+        .map(|property| property.data))
+}
+
+/// FullBoxのバージョンに応じたアイテムIDを読み取ります（pitm）
+fn read_item_id(data: &[u8], pos: usize) -> Option<u32> {
+    match data.first()? {
+        0 => Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?) as u32),
+        _ => Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?)),
+    }
+}
+
+/// ipmaボックスから指定したアイテムに関連付けられたプロパティ番号（1始まり）を返します
+fn associated_properties(ipma: &[u8], item_id: u32) -> Vec<usize> {
+    let Some(&version) = ipma.first() else {
+        return Vec::new();
+    };
+    let large_index = ipma.get(3).is_some_and(|flags| flags & 1 != 0);
+    let Some(entry_count) = ipma.get(4..8) else {
+        return Vec::new();
+    };
+    let entry_count = u32::from_be_bytes(entry_count.try_into().unwrap());
+
+    let mut pos = 8;
+    for _ in 0..entry_count {
+        let id_size = if version < 1 { 2 } else { 4 };
+        let Some(id) = ipma.get(pos..pos + id_size) else {
+            break;
+        };
+        let id = id.iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        pos += id_size;
+
+        let Some(&count) = ipma.get(pos) else {
+            break;
+        };
+        pos += 1;
+
+        let index_size = if large_index { 2 } else { 1 };
+        let Some(associations) = ipma.get(pos..pos + count as usize * index_size) else {
+            break;
+        };
+        pos += associations.len();
+
+        if id == item_id {
+            // 先頭ビットはessentialフラグ
+            return associations
+                .chunks_exact(index_size)
+                .map(|index| match index {
+                    [high, low] => (u16::from_be_bytes([*high, *low]) & 0x7FFF) as usize,
+                    [index] => (index & 0x7F) as usize,
+                    _ => 0,
+                })
+                .collect();
+        }
+    }
+
+    Vec::new()
+}
@@ -0,0 +1,727 @@
+use crate::jpeg::FrameHeader;
+use crate::Error;
+
+/// ハフマンテーブルのスロット数（DC・ACの各クラスに4つ）
+const TABLE_SLOTS: usize = 8;
+/// 符号の最大長
+const MAX_CODE_LENGTH: usize = 16;
+
+/// 復号用のハフマンテーブル
+#[derive(Debug, Clone)]
+struct DecodeTable {
+    /// 符号長ごとの最大の符号（該当する符号がない場合は-1）
+    max_code: [i32; MAX_CODE_LENGTH + 1],
+    /// 符号長ごとの、符号から`values`のインデックスへの差分
+    offset: [i32; MAX_CODE_LENGTH + 1],
+    values: Vec<u8>,
+}
+
+impl DecodeTable {
+    /// DHTの符号長ごとの個数と値から作成します
+    fn new(counts: &[u8], values: &[u8]) -> Result<Self, Error> {
+        let mut max_code = [-1; MAX_CODE_LENGTH + 1];
+        let mut offset = [0; MAX_CODE_LENGTH + 1];
+        let mut code = 0i32;
+        let mut index = 0i32;
+        for (length, &count) in (1..=MAX_CODE_LENGTH).zip(counts) {
+            let count = i32::from(count);
+            if count > 0 {
+                offset[length] = index - code;
+                index += count;
+                code += count;
+                if code > 1 << length {
+                    return Err(Error::InvalidFormat(
+                        "Invalid Huffman table code lengths".to_string(),
+                    ));
+                }
+                max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+
+        Ok(Self {
+            max_code,
+            offset,
+            values: values.to_vec(),
+        })
+    }
+
+    /// 符号を1つ読み取り、対応する値を返します
+    fn decode(&self, reader: &mut BitReader) -> Result<u8, Error> {
+        let mut code = 0i32;
+        for length in 1..=MAX_CODE_LENGTH {
+            code = (code << 1) | i32::from(reader.bit()?);
+            if code <= self.max_code[length] {
+                let index = (code + self.offset[length]) as usize;
+                return Ok(self.values[index]);
+            }
+        }
+        Err(Error::InvalidFormat(
+            "Invalid Huffman code in entropy-coded data".to_string(),
+        ))
+    }
+}
+
+/// 符号化用のハフマンテーブル
+#[derive(Debug, Clone)]
+struct EncodeTable {
+    /// 値ごとの符号と符号長
+    codes: [(u16, u8); 256],
+}
+
+impl EncodeTable {
+    /// 符号長ごとの個数（1〜16ビット）と値から作成します
+    fn new(counts: &[u8; MAX_CODE_LENGTH], values: &[u8]) -> Self {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut values = values.iter();
+        for (length, &count) in (1..=MAX_CODE_LENGTH as u8).zip(counts) {
+            for value in values.by_ref().take(usize::from(count)) {
+                codes[usize::from(*value)] = (code, length);
+                code += 1;
+            }
+            code = code.wrapping_shl(1);
+        }
+        Self { codes }
+    }
+}
+
+/// フレームの成分
+#[derive(Debug, Clone, Copy)]
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+}
+
+/// SOFセグメントから読み取ったフレームの情報
+#[derive(Debug, Clone)]
+struct Frame {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+}
+
+impl Frame {
+    /// SOFセグメントのペイロードから作成します
+    ///
+    /// 高さをDNLマーカーで定義する画像では`None`を返します。
+    fn parse(payload: &[u8]) -> Result<Option<Self>, Error> {
+        let invalid = || Error::InvalidFormat("Invalid SOF segment".to_string());
+        let header = FrameHeader::parse(payload)?;
+        let height = usize::from(header.height);
+        let width = usize::from(header.width);
+        let count = usize::from(header.components);
+        let fields = payload.get(6..6 + count * 3).ok_or_else(invalid)?;
+        if count == 0 || width == 0 {
+            return Err(invalid());
+        }
+        if height == 0 {
+            return Ok(None);
+        }
+
+        let components = fields
+            .chunks_exact(3)
+            .map(|field| {
+                let (h, v) = (usize::from(field[1] >> 4), usize::from(field[1] & 0x0F));
+                if !(1..=4).contains(&h) || !(1..=4).contains(&v) {
+                    return Err(invalid());
+                }
+                Ok(Component { id: field[0], h, v })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(Self {
+            width,
+            height,
+            components,
+        }))
+    }
+}
+
+/// MCUを構成するブロックごとのDC・ACテーブルのスロット
+type Blocks = Vec<(usize, usize)>;
+
+/// 再符号化するスキャン
+struct Scan<'a> {
+    /// SOSセグメント（マーカーを含む）
+    header: &'a [u8],
+    /// エントロピー符号化データ（リスタートマーカーを含む）
+    data: &'a [u8],
+    /// MCUの数
+    mcus: usize,
+    blocks: Blocks,
+    /// リスタート間隔（MCU数、0は無効）
+    restart_interval: usize,
+    /// スキャンの時点で定義されているテーブル
+    tables: Vec<Option<DecodeTable>>,
+}
+
+/// 再符号化する画像の構成
+enum Part<'a> {
+    /// そのままコピーするバイト列
+    Bytes(&'a [u8]),
+    /// 再符号化するスキャン
+    Scan(Scan<'a>),
+}
+
+/// スキャンから読み取った符号
+#[derive(Debug, Clone, Copy)]
+enum Symbol {
+    /// ハフマン符号化された値と、それに続く付加ビット
+    Code {
+        slot: usize,
+        value: u8,
+        extra: u16,
+        extra_length: u8,
+    },
+    /// リスタートマーカー
+    Restart,
+}
+
+/// エントロピー符号化データを最適なハフマンテーブルで再符号化します
+///
+/// ハフマン符号化のシーケンシャルJPEG（SOF0・SOF1）以外では`None`を返します。
+/// DHTセグメントはすべて削除し、最適化したテーブルを1つのDHTセグメントとして最初のSOSの直前に配置します。
+pub(crate) fn optimize(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let Some(parts) = parse(data)? else {
+        return Ok(None);
+    };
+    let scans = || {
+        parts.iter().filter_map(|part| match part {
+            Part::Scan(scan) => Some(scan),
+            Part::Bytes(_) => None,
+        })
+    };
+    if scans().next().is_none() {
+        return Ok(None);
+    }
+
+    // 1回目の走査で値の出現頻度を数える
+    let mut frequencies = [[0u64; 256]; TABLE_SLOTS];
+    for scan in scans() {
+        decode_scan(scan, &mut |symbol| {
+            if let Symbol::Code { slot, value, .. } = symbol {
+                frequencies[slot][usize::from(value)] += 1;
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut dht = vec![0xFF, 0xC4, 0, 0];
+    let mut encoders: Vec<Option<EncodeTable>> = vec![None; TABLE_SLOTS];
+    for (slot, frequencies) in frequencies.iter().enumerate() {
+        if frequencies.iter().all(|&count| count == 0) {
+            continue;
+        }
+        let (counts, values) = optimal_table(frequencies);
+        dht.push((((slot / 4) << 4) | (slot % 4)) as u8);
+        dht.extend_from_slice(&counts);
+        dht.extend_from_slice(&values);
+        encoders[slot] = Some(EncodeTable::new(&counts, &values));
+    }
+    let length = (dht.len() - 2) as u16;
+    dht[2..4].copy_from_slice(&length.to_be_bytes());
+
+    // 2回目の走査で再符号化する
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&data[..2]);
+    let mut dht = Some(dht);
+    for part in &parts {
+        match part {
+            Part::Bytes(bytes) => output.extend_from_slice(bytes),
+            Part::Scan(scan) => {
+                if let Some(dht) = dht.take() {
+                    output.extend_from_slice(&dht);
+                }
+                output.extend_from_slice(scan.header);
+                encode_scan(scan, &encoders, &mut output)?;
+            }
+        }
+    }
+
+    Ok(Some(output))
+}
+
+/// SOIの後を走査し、コピーする部分と再符号化するスキャンに分けます
+fn parse(data: &[u8]) -> Result<Option<Vec<Part<'_>>>, Error> {
+    let truncated = || Error::InvalidFormat("Truncated JPEG data".to_string());
+    let mut parts = Vec::new();
+    let mut tables: Vec<Option<DecodeTable>> = vec![None; TABLE_SLOTS];
+    let mut frame: Option<Frame> = None;
+    let mut restart_interval = 0;
+    let mut copy_start = 2;
+    let mut pos = 2;
+
+    loop {
+        if pos + 2 > data.len() {
+            return Err(truncated());
+        }
+        if data[pos] != 0xFF {
+            return Err(Error::InvalidFormat(format!(
+                "Expected marker at offset {pos}"
+            )));
+        }
+        let marker = data[pos + 1];
+        match marker {
+            // 埋め草のバイト
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // EOI以降はそのままコピー
+            0xD9 => break,
+            // スタンドアロンマーカー
+            0x01 | 0xD0..=0xD8 => {
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        let length = data
+            .get(pos + 2..pos + 4)
+            .map(|bytes| usize::from(u16::from_be_bytes([bytes[0], bytes[1]])))
+            .ok_or_else(truncated)?;
+        let end = pos + 2 + length;
+        if length < 2 || end > data.len() {
+            return Err(truncated());
+        }
+        let payload = &data[pos + 4..end];
+
+        match marker {
+            // DHTは最適化したテーブルに置き換えるため削除
+            0xC4 => {
+                parse_dht(payload, &mut tables)?;
+                if copy_start < pos {
+                    parts.push(Part::Bytes(&data[copy_start..pos]));
+                }
+                copy_start = end;
+            }
+            0xDD if payload.len() >= 2 => {
+                restart_interval = usize::from(u16::from_be_bytes([payload[0], payload[1]]));
+            }
+            0xC0 | 0xC1 => match Frame::parse(payload)? {
+                Some(parsed) => frame = Some(parsed),
+                None => return Ok(None),
+            },
+            // プログレッシブ・ロスレス・算術符号化は対象外
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return Ok(None),
+            0xDA => {
+                let frame = frame
+                    .as_ref()
+                    .ok_or_else(|| Error::InvalidFormat("SOS before SOF".to_string()))?;
+                let Some((mcus, blocks)) = scan_layout(frame, payload)? else {
+                    return Ok(None);
+                };
+                if let Some(&(dc, ac)) = blocks
+                    .iter()
+                    .find(|&&(dc, ac)| tables[dc].is_none() || tables[ac].is_none())
+                {
+                    let slot = if tables[dc].is_none() { dc } else { ac };
+                    return Err(Error::InvalidFormat(format!(
+                        "Undefined Huffman table (class {}, id {})",
+                        slot / 4,
+                        slot % 4
+                    )));
+                }
+
+                if copy_start < pos {
+                    parts.push(Part::Bytes(&data[copy_start..pos]));
+                }
+                let data_end = entropy_data_end(data, end).ok_or_else(truncated)?;
+                parts.push(Part::Scan(Scan {
+                    header: &data[pos..end],
+                    data: &data[end..data_end],
+                    mcus,
+                    blocks,
+                    restart_interval,
+                    tables: tables.clone(),
+                }));
+                pos = data_end;
+                copy_start = data_end;
+                continue;
+            }
+            _ => {}
+        }
+        pos = end;
+    }
+
+    parts.push(Part::Bytes(&data[copy_start..]));
+    Ok(Some(parts))
+}
+
+/// DHTセグメントのペイロードを読み取り、テーブルを定義します
+fn parse_dht(payload: &[u8], tables: &mut [Option<DecodeTable>]) -> Result<(), Error> {
+    let invalid = || Error::InvalidFormat("Invalid DHT segment".to_string());
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let (class, id) = (usize::from(rest[0] >> 4), usize::from(rest[0] & 0x0F));
+        if class > 1 || id > 3 {
+            return Err(invalid());
+        }
+        let counts = rest.get(1..1 + MAX_CODE_LENGTH).ok_or_else(invalid)?;
+        let total: usize = counts.iter().map(|&count| usize::from(count)).sum();
+        let values = rest
+            .get(1 + MAX_CODE_LENGTH..1 + MAX_CODE_LENGTH + total)
+            .ok_or_else(invalid)?;
+        tables[class * 4 + id] = Some(DecodeTable::new(counts, values)?);
+        rest = &rest[1 + MAX_CODE_LENGTH + total..];
+    }
+    Ok(())
+}
+
+/// SOSセグメントのペイロードからMCUの数とMCUを構成するブロックを求めます
+///
+/// シーケンシャルのスキャンではない場合は`None`を返します。
+fn scan_layout(frame: &Frame, payload: &[u8]) -> Result<Option<(usize, Blocks)>, Error> {
+    let invalid = || Error::InvalidFormat("Invalid SOS segment".to_string());
+    let count = usize::from(*payload.first().ok_or_else(invalid)?);
+    let fields = payload.get(1..1 + count * 2).ok_or_else(invalid)?;
+    let spectral = payload
+        .get(1 + count * 2..4 + count * 2)
+        .ok_or_else(invalid)?;
+    if count == 0 {
+        return Err(invalid());
+    }
+    if spectral != [0, 63, 0] {
+        return Ok(None);
+    }
+
+    let h_max = frame.components.iter().map(|c| c.h).max().unwrap_or(1);
+    let v_max = frame.components.iter().map(|c| c.v).max().unwrap_or(1);
+    let mut components = Vec::with_capacity(count);
+    for field in fields.chunks_exact(2) {
+        let component = frame
+            .components
+            .iter()
+            .find(|c| c.id == field[0])
+            .ok_or_else(invalid)?;
+        let (dc, ac) = (usize::from(field[1] >> 4), usize::from(field[1] & 0x0F));
+        if dc > 3 || ac > 3 {
+            return Err(invalid());
+        }
+        components.push((component, dc, 4 + ac));
+    }
+
+    if let [(component, dc, ac)] = components[..] {
+        // 非インターリーブのスキャンは1ブロックが1MCU
+        let width = (frame.width * component.h).div_ceil(h_max);
+        let height = (frame.height * component.v).div_ceil(v_max);
+        let mcus = width.div_ceil(8) * height.div_ceil(8);
+        return Ok(Some((mcus, vec![(dc, ac)])));
+    }
+
+    let mcus = frame.width.div_ceil(8 * h_max) * frame.height.div_ceil(8 * v_max);
+    let blocks = components
+        .iter()
+        .flat_map(|&(component, dc, ac)| std::iter::repeat_n((dc, ac), component.h * component.v))
+        .collect();
+    Ok(Some((mcus, blocks)))
+}
+
+/// エントロピー符号化データの終端（次のマーカーの位置）を求めます
+fn entropy_data_end(data: &[u8], start: usize) -> Option<usize> {
+    let mut pos = start;
+    while pos + 1 < data.len() {
+        if data[pos] == 0xFF && !matches!(data[pos + 1], 0x00 | 0xD0..=0xD7) {
+            return Some(pos);
+        }
+        pos += if data[pos] == 0xFF { 2 } else { 1 };
+    }
+    None
+}
+
+/// スキャンを復号し、読み取った符号を順に`visit`に渡します
+fn decode_scan(
+    scan: &Scan,
+    visit: &mut dyn FnMut(Symbol) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let table = |slot: usize| {
+        scan.tables[slot]
+            .as_ref()
+            .ok_or_else(|| Error::InvalidFormat("Undefined Huffman table".to_string()))
+    };
+    let mut reader = BitReader::new(scan.data);
+
+    for mcu in 0..scan.mcus {
+        if scan.restart_interval > 0 && mcu > 0 && mcu % scan.restart_interval == 0 {
+            reader.restart()?;
+            visit(Symbol::Restart)?;
+        }
+
+        for &(dc, ac) in &scan.blocks {
+            let size = table(dc)?.decode(&mut reader)?;
+            if usize::from(size) > MAX_CODE_LENGTH {
+                return Err(Error::InvalidFormat(
+                    "Invalid DC coefficient size".to_string(),
+                ));
+            }
+            visit(Symbol::Code {
+                slot: dc,
+                value: size,
+                extra: reader.bits(size)?,
+                extra_length: size,
+            })?;
+
+            let ac_table = table(ac)?;
+            let mut index = 1;
+            while index < 64 {
+                let value = ac_table.decode(&mut reader)?;
+                let (run, size) = (usize::from(value >> 4), value & 0x0F);
+                visit(Symbol::Code {
+                    slot: ac,
+                    value,
+                    extra: reader.bits(size)?,
+                    extra_length: size,
+                })?;
+                match (run, size) {
+                    // EOB
+                    (0..=14, 0) => break,
+                    // ZRL
+                    (_, 0) => index += 16,
+                    _ => index += run + 1,
+                }
+            }
+            if index > 64 {
+                return Err(Error::InvalidFormat(
+                    "Too many AC coefficients in block".to_string(),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// スキャンを最適化したテーブルで再符号化し、`output`に書き込みます
+fn encode_scan(
+    scan: &Scan,
+    tables: &[Option<EncodeTable>],
+    output: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let mut writer = BitWriter::new(output);
+    let mut restart = 0u8;
+    decode_scan(scan, &mut |symbol| {
+        match symbol {
+            Symbol::Code {
+                slot,
+                value,
+                extra,
+                extra_length,
+            } => {
+                let table = tables[slot]
+                    .as_ref()
+                    .ok_or_else(|| Error::InvalidFormat("Undefined Huffman table".to_string()))?;
+                let (code, length) = table.codes[usize::from(value)];
+                writer.put(code, length);
+                writer.put(extra, extra_length);
+            }
+            Symbol::Restart => {
+                writer.flush();
+                writer.output.extend_from_slice(&[0xFF, 0xD0 + restart]);
+                restart = (restart + 1) % 8;
+            }
+        }
+        Ok(())
+    })?;
+    writer.flush();
+    Ok(())
+}
+
+/// 出現頻度から符号長を16ビット以下に制限した最適なハフマンテーブルを作成します
+///
+/// JPEG仕様（ITU-T T.81）のK.2の手順に従い、すべて1の符号が使われないよう
+/// 出現頻度1の予約の値を加えて符号長を求めます。
+fn optimal_table(frequencies: &[u64; 256]) -> ([u8; MAX_CODE_LENGTH], Vec<u8>) {
+    const RESERVED: usize = 256;
+    let mut frequencies: Vec<u64> = frequencies.to_vec();
+    frequencies.push(1);
+    let mut code_size = [0usize; RESERVED + 1];
+    let mut others: [Option<usize>; RESERVED + 1] = [None; RESERVED + 1];
+
+    // 出現頻度の最も小さい2つを繰り返し併合する
+    loop {
+        let smallest = |excluded: Option<usize>| {
+            let mut found: Option<usize> = None;
+            for (index, &frequency) in frequencies.iter().enumerate() {
+                if frequency > 0
+                    && Some(index) != excluded
+                    && found.is_none_or(|found| frequency <= frequencies[found])
+                {
+                    found = Some(index);
+                }
+            }
+            found
+        };
+        let Some(mut c1) = smallest(None) else {
+            break;
+        };
+        let Some(mut c2) = smallest(Some(c1)) else {
+            break;
+        };
+
+        frequencies[c1] += frequencies[c2];
+        frequencies[c2] = 0;
+
+        code_size[c1] += 1;
+        while let Some(next) = others[c1] {
+            c1 = next;
+            code_size[c1] += 1;
+        }
+        others[c1] = Some(c2);
+
+        code_size[c2] += 1;
+        while let Some(next) = others[c2] {
+            c2 = next;
+            code_size[c2] += 1;
+        }
+    }
+
+    let mut bits = [0usize; RESERVED + 2];
+    for &size in code_size.iter().filter(|&&size| size > 0) {
+        bits[size] += 1;
+    }
+
+    // 16ビットを超える符号を短くする
+    for length in (MAX_CODE_LENGTH + 1..bits.len()).rev() {
+        while bits[length] > 0 {
+            let mut shorter = length - 2;
+            while bits[shorter] == 0 {
+                shorter -= 1;
+            }
+            bits[length] -= 2;
+            bits[length - 1] += 1;
+            bits[shorter + 1] += 2;
+            bits[shorter] -= 1;
+        }
+    }
+
+    // 予約の値（最も長い符号）を除く
+    if let Some(length) = (1..=MAX_CODE_LENGTH).rev().find(|&length| bits[length] > 0) {
+        bits[length] -= 1;
+    }
+
+    let mut counts = [0u8; MAX_CODE_LENGTH];
+    for (count, &bits) in counts.iter_mut().zip(&bits[1..]) {
+        *count = bits as u8;
+    }
+    let max_size = code_size.iter().copied().max().unwrap_or(0);
+    let values = (1..=max_size)
+        .flat_map(|size| (0..RESERVED).filter(move |&value| code_size[value] == size))
+        .map(|value| value as u8)
+        .collect();
+
+    (counts, values)
+}
+
+/// バイトスタッフィングを解除しながらエントロピー符号化データをビット単位で読み取ります
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u8,
+    remaining: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buffer: 0,
+            remaining: 0,
+        }
+    }
+
+    fn bit(&mut self) -> Result<u16, Error> {
+        if self.remaining == 0 {
+            self.buffer = self.next_byte()?;
+            self.remaining = 8;
+        }
+        self.remaining -= 1;
+        Ok(u16::from((self.buffer >> self.remaining) & 1))
+    }
+
+    fn bits(&mut self, count: u8) -> Result<u16, Error> {
+        let mut value = 0u32;
+        for _ in 0..count {
+            value = (value << 1) | u32::from(self.bit()?);
+        }
+        Ok(value as u16)
+    }
+
+    fn next_byte(&mut self) -> Result<u8, Error> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| Error::InvalidFormat("Truncated entropy-coded data".to_string()))?;
+        if byte == 0xFF {
+            if self.data.get(self.pos + 1) != Some(&0x00) {
+                return Err(Error::InvalidFormat(
+                    "Unexpected marker in entropy-coded data".to_string(),
+                ));
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    /// 残りのビットを捨て、リスタートマーカーを読み飛ばします
+    fn restart(&mut self) -> Result<(), Error> {
+        self.remaining = 0;
+        while self.data.get(self.pos..self.pos + 2) == Some(&[0xFF, 0xFF]) {
+            self.pos += 1;
+        }
+        match self.data.get(self.pos..self.pos + 2) {
+            Some(&[0xFF, 0xD0..=0xD7]) => {
+                self.pos += 2;
+                Ok(())
+            }
+            _ => Err(Error::InvalidFormat(
+                "Missing restart marker in entropy-coded data".to_string(),
+            )),
+        }
+    }
+}
+
+/// バイトスタッフィングを行いながらエントロピー符号化データをビット単位で書き込みます
+struct BitWriter<'a> {
+    output: &'a mut Vec<u8>,
+    buffer: u32,
+    count: u8,
+}
+
+impl<'a> BitWriter<'a> {
+    fn new(output: &'a mut Vec<u8>) -> Self {
+        Self {
+            output,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn put(&mut self, value: u16, length: u8) {
+        if length == 0 {
+            return;
+        }
+        self.buffer = (self.buffer << length) | (u32::from(value) & ((1 << length) - 1));
+        self.count += length;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.buffer >> self.count) as u8;
+            self.output.push(byte);
+            if byte == 0xFF {
+                self.output.push(0x00);
+            }
+        }
+        self.buffer &= (1 << self.count) - 1;
+    }
+
+    /// 残りのビットを1で埋めてバイト境界に揃えます
+    fn flush(&mut self) {
+        if self.count > 0 {
+            let padding = 8 - self.count;
+            self.put((1 << padding) - 1, padding);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! ICCプロファイルの解析
+
+/// ICCプロファイルのヘッダーサイズ
+const ICC_HEADER_SIZE: usize = 128;
+
+/// ICCプロファイルがsRGBプロファイルかどうかを判定します
+///
+/// RGB色空間のプロファイルで、説明（desc）タグに"sRGB"を含むものをsRGBとみなします。
+/// descタグはICC v2の`desc`型とICC v4の`mluc`型（UTF-16BE）に対応します。
+pub(crate) fn is_srgb_profile(profile: &[u8]) -> bool {
+    if profile.len() < ICC_HEADER_SIZE + 4
+        || &profile[16..20] != b"RGB "
+        || &profile[36..40] != b"acsp"
+    {
+        return false;
+    }
+
+    let Some(description) = find_tag(profile, b"desc") else {
+        return false;
+    };
+
+    match description.get(0..4) {
+        Some(b"desc") => {
+            // 型(4) + 予約(4) + ASCII文字数(4) + ASCII文字列
+            let Some(length) = read_u32(description, 8) else {
+                return false;
+            };
+            description
+                .get(12..12usize.saturating_add(length as usize))
+                .is_some_and(|text| contains(text, b"sRGB"))
+        }
+        Some(b"mluc") => {
+            // UTF-16BEの"sRGB"
+            contains(description, &[0, b's', 0, b'R', 0, b'G', 0, b'B'])
+        }
+        _ => false,
+    }
+}
+
+/// PCSの白色点（D50）
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+/// 白色点の変換に使うBradford行列
+const BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+/// ガンマ値と色度から最小限のICC v2プロファイル（ディスプレイクラス）を作成します
+///
+/// # Arguments
+/// * `description` - プロファイルの説明（descタグ、ASCII）
+/// * `gamma` - 表示側のガンマ値（トーンカーブの指数）
+/// * `white` - 白色点の色度（x, y）
+/// * `primaries` - 赤・緑・青の原色の色度（`None`の場合はグレースケールのプロファイル）
+///
+/// # Returns
+/// 作成したプロファイル。色度から変換行列を計算できない場合や、
+/// ガンマ値がトーンカーブで表せない場合は`None`
+///
+/// # Details
+/// RGBのプロファイルはwtpt・rXYZ・gXYZ・bXYZ・rTRC・gTRC・bTRC、グレースケールは
+/// wtpt・kTRCを持ちます。原色はBradford変換でPCSの白色点（D50）に順応させます。
+pub(crate) fn synthesize_profile(
+    description: &str,
+    gamma: f64,
+    white: (f64, f64),
+    primaries: Option<[(f64, f64); 3]>,
+) -> Option<Vec<u8>> {
+    // トーンカーブの指数はu8Fixed8Number（0〜255.996）
+    if !(gamma.is_finite() && gamma > 0.0 && gamma < 256.0) {
+        return None;
+    }
+    let white_xyz = xy_to_xyz(white)?;
+
+    let mut tags: Vec<([u8; 4], Vec<u8>)> = vec![
+        (*b"desc", text_description(description)),
+        (*b"cprt", text(b"No copyright, use freely")),
+        (*b"wtpt", xyz(white_xyz)),
+    ];
+    let curve = curve(gamma);
+    let color_space = match primaries {
+        Some(primaries) => {
+            let colorants = rgb_colorants(white_xyz, primaries)?;
+            for (signature, colorant) in [*b"rXYZ", *b"gXYZ", *b"bXYZ"].into_iter().zip(colorants) {
+                tags.push((signature, xyz(colorant)));
+            }
+            for signature in [*b"rTRC", *b"gTRC", *b"bTRC"] {
+                tags.push((signature, curve.clone()));
+            }
+            b"RGB "
+        }
+        None => {
+            tags.push((*b"kTRC", curve));
+            b"GRAY"
+        }
+    };
+
+    // ヘッダー(128) + タグ数(4) + タグテーブル(12 * タグ数) + タグの内容（4バイト境界）
+    let mut body = Vec::new();
+    let mut table = Vec::new();
+    let mut offset = ICC_HEADER_SIZE + 4 + tags.len() * 12;
+    for (signature, content) in &tags {
+        table.extend_from_slice(signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        body.extend_from_slice(content);
+        while body.len() % 4 != 0 {
+            body.push(0);
+        }
+        offset = ICC_HEADER_SIZE + 4 + tags.len() * 12 + body.len();
+    }
+
+    let size = offset as u32;
+    let mut profile = Vec::with_capacity(offset);
+    profile.extend_from_slice(&size.to_be_bytes());
+    profile.extend_from_slice(&[0; 4]); // CMM
+    profile.extend_from_slice(&[0x02, 0x10, 0, 0]); // バージョン2.1
+    profile.extend_from_slice(b"mntr");
+    profile.extend_from_slice(color_space);
+    profile.extend_from_slice(b"XYZ ");
+    profile.extend_from_slice(&[0; 12]); // 作成日時
+    profile.extend_from_slice(b"acsp");
+    profile.extend_from_slice(&[0; 24]); // プラットフォーム・フラグ・製造元・モデル・属性
+    profile.extend_from_slice(&[0; 4]); // レンダリングインテント（知覚的）
+    for value in D50 {
+        profile.extend_from_slice(&s15_fixed16(value));
+    }
+    profile.resize(ICC_HEADER_SIZE, 0); // 作成者・プロファイルID・予約
+    profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&body);
+
+    Some(profile)
+}
+
+/// 色度（x, y）をY=1のXYZに変換します
+fn xy_to_xyz((x, y): (f64, f64)) -> Option<[f64; 3]> {
+    if !(x.is_finite() && y.is_finite() && x >= 0.0 && y > 0.0 && x + y <= 1.0) {
+        return None;
+    }
+    Some([x / y, 1.0, (1.0 - x - y) / y])
+}
+
+/// 原色の色度からD50に順応させた各原色のXYZを計算します
+fn rgb_colorants(white: [f64; 3], primaries: [(f64, f64); 3]) -> Option<[[f64; 3]; 3]> {
+    let [red, green, blue] = [
+        xy_to_xyz(primaries[0])?,
+        xy_to_xyz(primaries[1])?,
+        xy_to_xyz(primaries[2])?,
+    ];
+    // 白色（R=G=B=1）が白色点になるように原色の強さを求める
+    let matrix = [
+        [red[0], green[0], blue[0]],
+        [red[1], green[1], blue[1]],
+        [red[2], green[2], blue[2]],
+    ];
+    let scale = multiply(&invert(&matrix)?, white);
+
+    let cone_white = multiply(&BRADFORD, white);
+    let cone_d50 = multiply(&BRADFORD, D50);
+    let inverse_bradford = invert(&BRADFORD)?;
+
+    let mut colorants = [[0.0; 3]; 3];
+    for (index, colorant) in colorants.iter_mut().enumerate() {
+        let column = [
+            matrix[0][index] * scale[index],
+            matrix[1][index] * scale[index],
+            matrix[2][index] * scale[index],
+        ];
+        let mut cone = multiply(&BRADFORD, column);
+        for channel in 0..3 {
+            cone[channel] *= cone_d50[channel] / cone_white[channel];
+        }
+        *colorant = multiply(&inverse_bradford, cone);
+    }
+    Some(colorants)
+}
+
+/// 3x3行列とベクトルの積
+fn multiply(matrix: &[[f64; 3]; 3], vector: [f64; 3]) -> [f64; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+/// 3x3行列の逆行列（正則でない場合は`None`）
+fn invert(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2)
+        + m[0][2] * cofactor(1, 2, 0, 1);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    Some([
+        [
+            cofactor(1, 2, 1, 2) / det,
+            -cofactor(0, 2, 1, 2) / det,
+            cofactor(0, 1, 1, 2) / det,
+        ],
+        [
+            -cofactor(1, 2, 0, 2) / det,
+            cofactor(0, 2, 0, 2) / det,
+            -cofactor(0, 1, 0, 2) / det,
+        ],
+        [
+            cofactor(1, 2, 0, 1) / det,
+            -cofactor(0, 2, 0, 1) / det,
+            cofactor(0, 1, 0, 1) / det,
+        ],
+    ])
+}
+
+/// s15Fixed16Number
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+/// XYZType
+fn xyz(values: [f64; 3]) -> Vec<u8> {
+    let mut content = b"XYZ \0\0\0\0".to_vec();
+    for value in values {
+        content.extend_from_slice(&s15_fixed16(value));
+    }
+    content
+}
+
+/// 指数だけを持つcurveType
+fn curve(gamma: f64) -> Vec<u8> {
+    let mut content = b"curv\0\0\0\0".to_vec();
+    content.extend_from_slice(&1u32.to_be_bytes());
+    content.extend_from_slice(&((gamma * 256.0).round() as u16).to_be_bytes());
+    content
+}
+
+/// textType（ASCII、null終端）
+fn text(value: &[u8]) -> Vec<u8> {
+    let mut content = b"text\0\0\0\0".to_vec();
+    content.extend_from_slice(value);
+    content.push(0);
+    content
+}
+
+/// ICC v2のtextDescriptionType（ASCIIのみ、Unicode・ScriptCodeは空）
+fn text_description(description: &str) -> Vec<u8> {
+    let ascii: Vec<u8> = description
+        .bytes()
+        .map(|b| if b.is_ascii() { b } else { b'?' })
+        .collect();
+    let mut content = b"desc\0\0\0\0".to_vec();
+    content.extend_from_slice(&(ascii.len() as u32 + 1).to_be_bytes());
+    content.extend_from_slice(&ascii);
+    content.push(0);
+    content.extend_from_slice(&[0; 8]); // Unicodeの言語コードと文字数
+    content.extend_from_slice(&[0; 3]); // ScriptCodeのコードと文字数
+    content.extend_from_slice(&[0; 67]);
+    content
+}
+
+/// タグテーブルから指定したタグの内容を探します
+fn find_tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = read_u32(profile, ICC_HEADER_SIZE)? as usize;
+
+    for index in 0..count {
+        let entry = ICC_HEADER_SIZE + 4 + index * 12;
+        let tag = profile.get(entry..entry + 4)?;
+        if tag == signature {
+            let offset = read_u32(profile, entry + 4)? as usize;
+            let size = read_u32(profile, entry + 8)? as usize;
+            return profile.get(offset..offset.checked_add(size)?);
+        }
+    }
+
+    None
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes: [u8; 4] = data.get(pos..pos.checked_add(4)?)?.try_into().ok()?;
+    Some(u32::from_be_bytes(bytes))
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
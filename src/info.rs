@@ -0,0 +1,48 @@
+use crate::{jpeg, png, Error, ImageFormat};
+
+/// 形式によらない画素の形式
+///
+/// 変換先の形式が扱えるかの判定などに、形式ごとの列挙型で分岐せずに利用できます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PixelFormat {
+    /// 1画素のチャンネル数（アルファを含み、パレット画像ではインデックスの1）
+    pub channels: u8,
+    /// 1チャンネルのビット数（パレット画像ではインデックスのビット数）
+    pub bits_per_channel: u8,
+    /// アルファチャンネルまたは透明色を持つ
+    pub has_alpha: bool,
+    /// パレット（インデックスカラー）の画像
+    pub palette: bool,
+}
+
+/// デコードせずに読み取った画像の基本情報
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageInfo {
+    /// 画像形式
+    pub format: ImageFormat,
+    /// 幅（ピクセル）
+    pub width: u32,
+    /// 高さ（ピクセル）
+    pub height: u32,
+    /// 画素の形式
+    pub pixel_format: PixelFormat,
+}
+
+/// 形式を判定して画像の基本情報を読み取ります
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// * `Ok(ImageInfo)` - 寸法と画素の形式
+/// * `Err(Error)` - 対応していない形式や、ヘッダーが不正な場合
+///
+/// # Details
+/// [`jpeg::read_info`]と[`png::read_info`]を形式に応じて呼び出します。
+pub fn read_info(data: &[u8]) -> Result<ImageInfo, Error> {
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => jpeg::read_info(data),
+        Some(ImageFormat::Png) => png::read_info(data),
+        None => Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    }
+}
@@ -0,0 +1,483 @@
+use crate::{jpeg, Error};
+use md5::{Digest, Md5};
+
+/// Photoshop画像リソース（APP13）の識別子
+const PHOTOSHOP_SIGNATURE: &[u8] = b"Photoshop 3.0\0";
+/// IPTC-IIMを格納する画像リソースのID
+const RESOURCE_IPTC: u16 = 0x0404;
+/// IPTC-IIMのMD5ダイジェストを格納する画像リソースのID
+const RESOURCE_IPTC_DIGEST: u16 = 0x0425;
+/// 旧形式のキャプション（Photoshop 6以前のパスカル文字列）を格納する画像リソースのID
+const RESOURCE_CAPTION: u16 = 0x03F0;
+
+/// ObjectName（タイトル）
+pub(crate) const DATASET_OBJECT_NAME: (u8, u8) = (2, 5);
+/// Keywords（繰り返し）
+pub(crate) const DATASET_KEYWORDS: (u8, u8) = (2, 25);
+/// By-line（作成者、繰り返し）
+pub(crate) const DATASET_BY_LINE: (u8, u8) = (2, 80);
+/// CopyrightNotice
+pub(crate) const DATASET_COPYRIGHT_NOTICE: (u8, u8) = (2, 116);
+/// Caption-Abstract（説明）
+pub(crate) const DATASET_CAPTION: (u8, u8) = (2, 120);
+/// CodedCharacterSet
+const DATASET_CODED_CHARACTER_SET: (u8, u8) = (1, 90);
+/// RecordVersion
+const DATASET_RECORD_VERSION: (u8, u8) = (2, 0);
+/// CodedCharacterSetでUTF-8を表すエスケープシーケンス
+const CHARSET_UTF8: &[u8] = b"\x1B%G";
+
+/// IPTC-IIMの文字列データセットの文字コード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum IptcCharset {
+    /// UTF-8（CodedCharacterSetが`ESC % G`）
+    Utf8,
+    /// ISO 8859-1（CodedCharacterSetが`ESC . A`・`ESC - A`）
+    ///
+    /// Windows-1252として復号します（0x80〜0x9Fの記号も扱えます）。
+    #[default]
+    Latin1,
+    /// Shift_JIS
+    ///
+    /// CodedCharacterSetで宣言する方法がないため、宣言のないIPTCの既定として指定する場合のみ使用します。
+    ShiftJis,
+}
+
+impl IptcCharset {
+    /// CodedCharacterSetのエスケープシーケンスから判定します
+    fn from_escape(data: &[u8]) -> Option<Self> {
+        match data {
+            b"\x1B%G" | b"\x1B%/G" | b"\x1B%/I" => Some(IptcCharset::Utf8),
+            b"\x1B.A" | b"\x1B-A" => Some(IptcCharset::Latin1),
+            _ => None,
+        }
+    }
+
+    /// バイト列を文字列に変換します
+    fn decode(self, data: &[u8]) -> String {
+        match self {
+            IptcCharset::Utf8 => String::from_utf8_lossy(data).to_string(),
+            IptcCharset::Latin1 => encoding_rs::WINDOWS_1252.decode(data).0.to_string(),
+            IptcCharset::ShiftJis => encoding_rs::SHIFT_JIS.decode(data).0.to_string(),
+        }
+    }
+}
+
+/// IPTC-IIMのデータセット
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Dataset {
+    /// レコード番号とデータセット番号
+    pub(crate) id: (u8, u8),
+    /// 値のバイト列
+    pub(crate) data: Vec<u8>,
+}
+
+/// Photoshop画像リソースから読み取ったIPTC-IIM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Iptc {
+    /// 出現順のデータセット
+    pub(crate) datasets: Vec<Dataset>,
+    /// 記録されたダイジェストと内容が一致しない（XMP非対応のアプリケーションで変更された）
+    pub(crate) digest_mismatch: bool,
+    /// CodedCharacterSetの宣言がない場合に、UTF-8として正しくない値に使う文字コード
+    pub(crate) fallback_charset: IptcCharset,
+}
+
+impl Iptc {
+    /// CodedCharacterSet（1:90）で宣言された文字コード
+    pub(crate) fn declared_charset(&self) -> Option<IptcCharset> {
+        self.datasets
+            .iter()
+            .find(|dataset| dataset.id == DATASET_CODED_CHARACTER_SET)
+            .and_then(|dataset| IptcCharset::from_escape(&dataset.data))
+    }
+
+    /// 文字列の復号に使う文字コード
+    ///
+    /// 宣言がない場合、レコード2の値がすべてUTF-8として正しければUTF-8、
+    /// そうでなければ[`fallback_charset`](Self::fallback_charset)とみなします。
+    pub(crate) fn charset(&self) -> IptcCharset {
+        self.declared_charset().unwrap_or_else(|| {
+            let utf8 = self
+                .datasets
+                .iter()
+                .filter(|dataset| dataset.id.0 == 2 && dataset.id != DATASET_RECORD_VERSION)
+                .all(|dataset| std::str::from_utf8(&dataset.data).is_ok());
+            if utf8 {
+                IptcCharset::Utf8
+            } else {
+                self.fallback_charset
+            }
+        })
+    }
+
+    /// 指定したデータセットの値を文字列としてすべて返します（空の値は除きます）
+    pub(crate) fn strings(&self, id: (u8, u8)) -> Vec<String> {
+        let declared = self.declared_charset();
+
+        self.datasets
+            .iter()
+            .filter(|dataset| dataset.id == id)
+            .map(|dataset| decode_string(&dataset.data, declared, self.fallback_charset))
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+
+    /// 指定したデータセットの最初の値を文字列として返します
+    pub(crate) fn string(&self, id: (u8, u8)) -> Option<String> {
+        self.strings(id).into_iter().next()
+    }
+}
+
+/// JPEG画像のAPP13からIPTC-IIMを読み取ります
+///
+/// 複数のAPP13セグメントに分割された画像リソースは連結して扱います。
+pub(crate) fn read_jpeg_iptc(data: &[u8]) -> Result<Option<Iptc>, Error> {
+    let (segments, _) = jpeg::parse_segments(data)?;
+    let resources = photoshop_resources(&segments);
+
+    let mut iim = None;
+    let mut digest = None;
+    for resource in parse_resources(&resources) {
+        match resource.id {
+            RESOURCE_IPTC if iim.is_none() => iim = Some(resource.data),
+            RESOURCE_IPTC_DIGEST if resource.data.len() == 16 => digest = Some(resource.data),
+            _ => {}
+        }
+    }
+
+    let Some(iim) = iim else {
+        return Ok(None);
+    };
+    Ok(Some(Iptc {
+        datasets: parse_datasets(iim),
+        digest_mismatch: digest.is_some_and(|digest| Md5::digest(iim).as_slice() != digest),
+        fallback_charset: IptcCharset::default(),
+    }))
+}
+
+/// APP13の画像リソースからIPTC-IIMのバイト列をそのまま取り出します
+pub(crate) fn read_jpeg_iim(segments: &[jpeg::Segment]) -> Option<Vec<u8>> {
+    let resources = photoshop_resources(segments);
+    parse_resources(&resources)
+        .into_iter()
+        .find(|resource| resource.id == RESOURCE_IPTC)
+        .map(|resource| resource.data.to_vec())
+}
+
+/// IPTCのデータセットを置き換えたJPEG画像データを返します
+///
+/// `updates`に含まれるデータセットは既存の値をすべて削除して指定した値に置き換え、
+/// その他のデータセットと画像リソースは保持します。
+/// ダイジェスト（0x0425）は新しい内容から計算し直すため、XMPと同期した状態になります。
+/// 非ASCIIの値を書き込む場合はCodedCharacterSetにUTF-8を宣言し、既存の値もUTF-8に変換します。
+///
+/// `legacy_caption`を指定した場合は旧形式のキャプション（0x03F0）も置き換えます
+/// （空文字列の場合は削除）。`None`の場合は既存のリソースをそのまま保持します。
+pub(crate) fn write_jpeg_iptc(
+    data: &[u8],
+    updates: &[((u8, u8), Vec<String>)],
+    legacy_caption: Option<&str>,
+) -> Result<Vec<u8>, Error> {
+    let (segments, _) = jpeg::parse_segments(data)?;
+    let is_photoshop = |segment: &jpeg::Segment| {
+        segment.marker == 0xED && segment.payload().starts_with(PHOTOSHOP_SIGNATURE)
+    };
+    let has_photoshop = segments.iter().any(is_photoshop);
+    if !has_photoshop
+        && updates.iter().all(|(_, values)| values.is_empty())
+        && legacy_caption.is_none_or(str::is_empty)
+    {
+        return Ok(data.to_vec());
+    }
+
+    let resources = photoshop_resources(&segments);
+    let resources = parse_resources(&resources);
+    let existing = resources
+        .iter()
+        .find(|resource| resource.id == RESOURCE_IPTC)
+        .map(|resource| parse_datasets(resource.data))
+        .unwrap_or_default();
+    let iim = build_iim(existing, updates);
+
+    // IPTCとダイジェスト（と置き換える旧形式のキャプション）以外の画像リソースは保持する
+    let mut block = Vec::new();
+    for resource in &resources {
+        let replaced = match resource.id {
+            RESOURCE_IPTC | RESOURCE_IPTC_DIGEST => true,
+            RESOURCE_CAPTION => legacy_caption.is_some(),
+            _ => false,
+        };
+        if !replaced {
+            write_resource(&mut block, resource.id, resource.name, resource.data);
+        }
+    }
+    if let Some(caption) = legacy_caption.filter(|caption| !caption.is_empty()) {
+        write_resource(
+            &mut block,
+            RESOURCE_CAPTION,
+            &[0, 0],
+            &pascal_string(caption),
+        );
+    }
+    if !iim.is_empty() {
+        write_resource(&mut block, RESOURCE_IPTC, &[0, 0], &iim);
+        write_resource(
+            &mut block,
+            RESOURCE_IPTC_DIGEST,
+            &[0, 0],
+            &Md5::digest(&iim),
+        );
+    }
+
+    // 既存のAPP13の位置（ない場合はJFIF・EXIF・XMPの後）に書き込む
+    let position = match segments.iter().find(|segment| is_photoshop(segment)) {
+        Some(segment) => segment.offset,
+        None => segments
+            .iter()
+            .rev()
+            .find(|segment| matches!(segment.marker, 0xE0 | 0xE1))
+            .map_or(2, |segment| segment.offset + segment.bytes.len()),
+    };
+
+    let mut output = Vec::with_capacity(data.len() + block.len() + 32);
+    let mut copied = 0;
+    for segment in segments.iter().filter(|segment| is_photoshop(segment)) {
+        output.extend_from_slice(&data[copied..segment.offset]);
+        copied = segment.offset + segment.bytes.len();
+        if segment.offset == position {
+            write_photoshop_segments(&mut output, &block);
+        }
+    }
+    if !has_photoshop {
+        output.extend_from_slice(&data[copied..position]);
+        copied = position;
+        write_photoshop_segments(&mut output, &block);
+    }
+    output.extend_from_slice(&data[copied..]);
+
+    Ok(output)
+}
+
+/// APP13に格納されたPhotoshop画像リソースを連結して返します
+fn photoshop_resources(segments: &[jpeg::Segment]) -> Vec<u8> {
+    segments
+        .iter()
+        .filter(|segment| segment.marker == 0xED)
+        .filter_map(|segment| segment.payload().strip_prefix(PHOTOSHOP_SIGNATURE))
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// 画像リソースを1つ以上のAPP13セグメントとして書き込みます
+fn write_photoshop_segments(output: &mut Vec<u8>, block: &[u8]) {
+    const MAX_CHUNK: usize = 0xFFFF - 2 - PHOTOSHOP_SIGNATURE.len();
+    for chunk in block.chunks(MAX_CHUNK) {
+        output.extend_from_slice(&[0xFF, 0xED]);
+        output.extend_from_slice(
+            &((chunk.len() + PHOTOSHOP_SIGNATURE.len() + 2) as u16).to_be_bytes(),
+        );
+        output.extend_from_slice(PHOTOSHOP_SIGNATURE);
+        output.extend_from_slice(chunk);
+    }
+}
+
+/// 既存のデータセットに更新を反映したIPTC-IIMを組み立てます
+///
+/// レコード2に値がなくなった場合は空を返します。
+fn build_iim(existing: Vec<Dataset>, updates: &[((u8, u8), Vec<String>)]) -> Vec<u8> {
+    let mut datasets: Vec<Dataset> = existing
+        .into_iter()
+        .filter(|dataset| !updates.iter().any(|(id, _)| *id == dataset.id))
+        .collect();
+
+    let declared = datasets
+        .iter()
+        .find(|dataset| dataset.id == DATASET_CODED_CHARACTER_SET)
+        .and_then(|dataset| IptcCharset::from_escape(&dataset.data));
+    let needs_utf8 = updates
+        .iter()
+        .flat_map(|(_, values)| values)
+        .any(|value| !value.is_ascii());
+    if needs_utf8 && declared != Some(IptcCharset::Utf8) {
+        for dataset in &mut datasets {
+            if dataset.id.0 == 2 && dataset.id != DATASET_RECORD_VERSION {
+                let text = decode_string(&dataset.data, declared, IptcCharset::default());
+                dataset.data = text.into_bytes();
+            }
+        }
+        datasets.retain(|dataset| dataset.id != DATASET_CODED_CHARACTER_SET);
+        datasets.push(Dataset {
+            id: DATASET_CODED_CHARACTER_SET,
+            data: CHARSET_UTF8.to_vec(),
+        });
+    }
+
+    for (id, values) in updates {
+        datasets.extend(
+            values
+                .iter()
+                .filter(|value| !value.is_empty())
+                .map(|value| Dataset {
+                    id: *id,
+                    data: value.as_bytes().to_vec(),
+                }),
+        );
+    }
+
+    let has_content = datasets
+        .iter()
+        .any(|dataset| dataset.id.0 == 2 && dataset.id != DATASET_RECORD_VERSION);
+    if !has_content {
+        return Vec::new();
+    }
+    if !datasets
+        .iter()
+        .any(|dataset| dataset.id == DATASET_RECORD_VERSION)
+    {
+        datasets.push(Dataset {
+            id: DATASET_RECORD_VERSION,
+            data: vec![0, 4],
+        });
+    }
+
+    // データセットはレコード番号・データセット番号の順に並べる（繰り返しの順序は保持）
+    datasets.sort_by_key(|dataset| dataset.id);
+
+    let mut output = Vec::new();
+    for dataset in &datasets {
+        output.extend_from_slice(&[0x1C, dataset.id.0, dataset.id.1]);
+        if dataset.data.len() < 0x8000 {
+            output.extend_from_slice(&(dataset.data.len() as u16).to_be_bytes());
+        } else {
+            // 拡張データセット（4バイトで長さを表す）
+            output.extend_from_slice(&0x8004u16.to_be_bytes());
+            output.extend_from_slice(&(dataset.data.len() as u32).to_be_bytes());
+        }
+        output.extend_from_slice(&dataset.data);
+    }
+    output
+}
+
+/// Photoshop画像リソース
+struct Resource<'a> {
+    /// リソースID
+    id: u16,
+    /// 偶数バイトに揃えたパスカル文字列の名前
+    name: &'a [u8],
+    /// リソースの内容
+    data: &'a [u8],
+}
+
+/// 旧形式のキャプションのパスカル文字列を作成します
+///
+/// 古いアプリケーションが扱えるようWindows-1252で符号化し、表せない文字を含む場合はUTF-8とします。
+/// 255バイトを超える部分は文字の境界で切り詰めます。
+fn pascal_string(text: &str) -> Vec<u8> {
+    let (encoded, _, unmappable) = encoding_rs::WINDOWS_1252.encode(text);
+    let bytes: &[u8] = if unmappable {
+        text.as_bytes()
+    } else {
+        &encoded
+    };
+
+    let mut len = bytes.len().min(255);
+    if unmappable {
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+    }
+    let mut output = Vec::with_capacity(len + 1);
+    output.push(len as u8);
+    output.extend_from_slice(&bytes[..len]);
+    output
+}
+
+/// 画像リソースブロックを書き込みます
+fn write_resource(output: &mut Vec<u8>, id: u16, name: &[u8], data: &[u8]) {
+    output.extend_from_slice(b"8BIM");
+    output.extend_from_slice(&id.to_be_bytes());
+    output.extend_from_slice(name);
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+/// Photoshop画像リソースブロック（`8BIM`）を列挙します
+fn parse_resources(data: &[u8]) -> Vec<Resource<'_>> {
+    let mut resources = Vec::new();
+    let mut pos = 0;
+
+    while pos + 12 <= data.len() && &data[pos..pos + 4] == b"8BIM" {
+        let id = u16::from_be_bytes([data[pos + 4], data[pos + 5]]);
+        // 名前はパスカル文字列（長さを含めて偶数バイトに揃える）
+        let name_len = data[pos + 6] as usize;
+        let name_size = (name_len + 1 + 1) & !1;
+        let size_pos = pos + 6 + name_size;
+        let Some(size) = data.get(size_pos..size_pos + 4) else {
+            break;
+        };
+        let size = u32::from_be_bytes(size.try_into().unwrap()) as usize;
+        let Some(resource) = data.get(size_pos + 4..size_pos + 4 + size) else {
+            break;
+        };
+        resources.push(Resource {
+            id,
+            name: &data[pos + 6..size_pos],
+            data: resource,
+        });
+        pos = size_pos + 4 + size + size % 2;
+    }
+
+    resources
+}
+
+/// IPTC-IIMのデータセットを列挙します
+fn parse_datasets(data: &[u8]) -> Vec<Dataset> {
+    let mut datasets = Vec::new();
+    let mut pos = 0;
+
+    while pos + 5 <= data.len() && data[pos] == 0x1C {
+        let id = (data[pos + 1], data[pos + 2]);
+        let mut size = u16::from_be_bytes([data[pos + 3], data[pos + 4]]) as usize;
+        pos += 5;
+
+        // 最上位ビットが立っている場合は拡張データセット（続くバイト数で長さを表す）
+        if size & 0x8000 != 0 {
+            let length_size = size & 0x7FFF;
+            let Some(length) = data.get(pos..pos + length_size).filter(|l| l.len() <= 4) else {
+                break;
+            };
+            size = length.iter().fold(0, |acc, &b| (acc << 8) | b as usize);
+            pos += length_size;
+        }
+
+        let Some(value) = data.get(pos..pos + size) else {
+            break;
+        };
+        datasets.push(Dataset {
+            id,
+            data: value.to_vec(),
+        });
+        pos += size;
+    }
+
+    datasets
+}
+
+/// データセットの値を文字列に変換します
+///
+/// CodedCharacterSetで宣言された文字コードに従います。宣言がない場合は、
+/// UTF-8として正しければUTF-8として扱い、そうでなければ`fallback`として扱います。
+fn decode_string(data: &[u8], declared: Option<IptcCharset>, fallback: IptcCharset) -> String {
+    let text = match (declared, std::str::from_utf8(data)) {
+        (Some(charset), _) => charset.decode(data),
+        (None, Ok(text)) => text.to_string(),
+        (None, Err(_)) => fallback.decode(data),
+    };
+    text.trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
@@ -0,0 +1,69 @@
+//! ISOBMFF（HEIF）およびJPEG 2000で共通のボックス構造
+
+use crate::Error;
+
+/// ISOBMFFのボックス
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IsoBox<'a> {
+    pub(crate) box_type: [u8; 4],
+    /// 列挙したデータ内でのボックスの開始位置
+    pub(crate) offset: usize,
+    /// 列挙したデータ内での内容の開始位置
+    pub(crate) data_offset: usize,
+    /// ヘッダーを除いた内容
+    pub(crate) data: &'a [u8],
+}
+
+/// ボックスを列挙します
+pub(crate) fn parse_boxes(data: &[u8]) -> Result<Vec<IsoBox<'_>>, Error> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+
+    while pos + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as u64;
+        let box_type = [data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]];
+
+        let (header, size) = match size {
+            // ファイルの終端まで
+            0 => (8, (data.len() - pos) as u64),
+            // 64ビットのサイズ
+            1 => {
+                let large = data
+                    .get(pos + 8..pos + 16)
+                    .ok_or_else(|| Error::ParseError("Box header truncated".to_string()))?;
+                (16, u64::from_be_bytes(large.try_into().unwrap()))
+            }
+            size => (8, size),
+        };
+
+        let end = usize::try_from(size)
+            .ok()
+            .and_then(|size| pos.checked_add(size))
+            .filter(|&end| size >= header as u64 && end <= data.len())
+            .ok_or_else(|| Error::ParseError("Box extends beyond data".to_string()))?;
+
+        boxes.push(IsoBox {
+            box_type,
+            offset: pos,
+            data_offset: pos + header,
+            data: &data[pos + header..end],
+        });
+        pos = end;
+    }
+
+    Ok(boxes)
+}
+
+/// ボックスを書き込みます
+pub(crate) fn write_box(
+    output: &mut Vec<u8>,
+    box_type: &[u8; 4],
+    content: &[u8],
+) -> Result<(), Error> {
+    let size = u32::try_from(content.len() + 8)
+        .map_err(|_| Error::InvalidFormat("Box too large".to_string()))?;
+    output.extend_from_slice(&size.to_be_bytes());
+    output.extend_from_slice(box_type);
+    output.extend_from_slice(content);
+    Ok(())
+}
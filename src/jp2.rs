@@ -0,0 +1,219 @@
+use crate::isobmff::{parse_boxes, write_box, IsoBox};
+use crate::Error;
+
+/// JP2シグネチャボックス
+const JP2_SIGNATURE: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x0C, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+];
+
+/// EXIFのUUID（"JpgTiffExif->JP2"）
+const UUID_EXIF: [u8; 16] = *b"JpgTiffExif->JP2";
+/// XMPのUUID
+const UUID_XMP: [u8; 16] = [
+    0xBE, 0x7A, 0xCF, 0xCB, 0x97, 0xA9, 0x42, 0xE8, 0x9C, 0x71, 0x99, 0x94, 0x91, 0xE3, 0xAF, 0xAC,
+];
+/// IPTCのUUID
+const UUID_IPTC: [u8; 16] = [
+    0x33, 0xC7, 0xA4, 0xD2, 0xB8, 0x1D, 0x47, 0x23, 0xA0, 0xBA, 0xF1, 0xA3, 0xE0, 0x97, 0xAD, 0x38,
+];
+/// GeoJP2のUUID
+const UUID_GEOJP2: [u8; 16] = [
+    0xB1, 0x4B, 0xF8, 0xBD, 0x08, 0x3D, 0x4B, 0x43, 0xA5, 0xAE, 0x8C, 0xD7, 0xD5, 0xA6, 0xCE, 0x03,
+];
+
+/// メタデータボックスの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BoxKind {
+    /// XMLボックス（`xml `）
+    Xml,
+    /// EXIFを格納したUUIDボックス
+    Exif,
+    /// XMPを格納したUUIDボックス
+    Xmp,
+    /// IPTCを格納したUUIDボックス
+    Iptc,
+    /// GeoJP2（GeoTIFF）を格納したUUIDボックス
+    GeoJp2,
+    /// その他のUUIDボックス
+    Uuid,
+    /// 解像度ボックス（jp2h内の`res `）
+    Resolution,
+}
+
+/// JP2のメタデータボックス
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataBox {
+    /// ボックスの種類
+    pub kind: BoxKind,
+    /// ファイル先頭からのボックスの位置
+    pub offset: usize,
+    /// ボックスの内容（UUIDボックスはUUIDの16バイトを除く）
+    pub data: Vec<u8>,
+}
+
+/// メタデータ軽量化のオプション
+#[derive(Debug, Clone)]
+pub struct CleanOptions {
+    /// XMLボックスを保持する
+    pub preserve_xml: bool,
+    /// EXIFを保持する
+    pub preserve_exif: bool,
+    /// XMPを保持する
+    pub preserve_xmp: bool,
+    /// GeoJP2を保持する
+    pub preserve_geojp2: bool,
+    /// 解像度ボックスを保持する（デフォルトで有効）
+    pub preserve_resolution: bool,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        Self {
+            preserve_xml: false,
+            preserve_exif: false,
+            preserve_xmp: false,
+            preserve_geojp2: false,
+            preserve_resolution: true,
+        }
+    }
+}
+
+/// JP2画像のメタデータボックスを列挙します
+///
+/// トップレベルの`xml `・`uuid`ボックスと、jp2h内の`res `ボックスを返します。
+pub fn read_metadata_boxes(data: &[u8]) -> Result<Vec<MetadataBox>, Error> {
+    let boxes = parse_jp2(data)?;
+    let mut result = Vec::new();
+
+    for b in &boxes {
+        match &b.box_type {
+            b"jp2h" => {
+                for child in parse_boxes(b.data)? {
+                    if &child.box_type == b"res " {
+                        result.push(MetadataBox {
+                            kind: BoxKind::Resolution,
+                            offset: b.data_offset + child.offset,
+                            data: child.data.to_vec(),
+                        });
+                    }
+                }
+            }
+            b"xml " | b"uuid" => {
+                let kind = box_kind(b)?;
+                let content = if kind == BoxKind::Xml {
+                    b.data
+                } else {
+                    &b.data[16..]
+                };
+                result.push(MetadataBox {
+                    kind,
+                    offset: b.offset,
+                    data: content.to_vec(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(result)
+}
+
+/// JP2画像からXML・UUIDボックスなどのメタデータを削除します
+///
+/// 解像度ボックスはデフォルトで保持します。
+pub fn clean_metadata(data: &[u8]) -> Result<Vec<u8>, Error> {
+    clean_metadata_with_options(data, &CleanOptions::default())
+}
+
+/// オプションを指定してJP2画像からメタデータを削除します
+///
+/// # Arguments
+/// * `data` - JP2画像のバイトデータ
+/// * `options` - 保持するメタデータの指定
+///
+/// # Details
+/// `uinf`（UUID情報）と`jp2i`（知的財産権）ボックスも削除します。
+/// コードストリーム（jp2c）の内容は変更しません。
+pub fn clean_metadata_with_options(data: &[u8], options: &CleanOptions) -> Result<Vec<u8>, Error> {
+    let boxes = parse_jp2(data)?;
+
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&JP2_SIGNATURE);
+
+    for b in boxes.iter().skip(1) {
+        let keep = match &b.box_type {
+            b"jp2h" if !options.preserve_resolution => {
+                let mut header = Vec::with_capacity(b.data.len());
+                for child in parse_boxes(b.data)? {
+                    if &child.box_type != b"res " {
+                        write_box(&mut header, &child.box_type, child.data)?;
+                    }
+                }
+                write_box(&mut output, b"jp2h", &header)?;
+                continue;
+            }
+            b"xml " | b"uuid" => match box_kind(b)? {
+                BoxKind::Xml => options.preserve_xml,
+                BoxKind::Exif => options.preserve_exif,
+                BoxKind::Xmp => options.preserve_xmp,
+                BoxKind::GeoJp2 => options.preserve_geojp2,
+                _ => false,
+            },
+            b"uinf" | b"jp2i" => false,
+            _ => true,
+        };
+
+        if keep {
+            write_box(&mut output, &b.box_type, b.data)?;
+        }
+    }
+
+    // 出力が有効なJP2か検証
+    parse_jp2(&output)?;
+
+    Ok(output)
+}
+
+/// シグネチャと必須ボックスを確認し、トップレベルのボックスを列挙します
+fn parse_jp2(data: &[u8]) -> Result<Vec<IsoBox<'_>>, Error> {
+    if !data.starts_with(&JP2_SIGNATURE) {
+        return Err(Error::InvalidFormat("Not a valid JP2 file".to_string()));
+    }
+
+    let boxes = parse_boxes(data)?;
+    if boxes.get(1).map(|b| &b.box_type) != Some(b"ftyp") {
+        return Err(Error::InvalidFormat(
+            "File type box must follow the signature".to_string(),
+        ));
+    }
+    for required in [b"jp2h", b"jp2c"] {
+        if !boxes.iter().any(|b| &b.box_type == required) {
+            return Err(Error::InvalidFormat(format!(
+                "Required box {} not found",
+                String::from_utf8_lossy(required)
+            )));
+        }
+    }
+
+    Ok(boxes)
+}
+
+/// XML・UUIDボックスの種類を判定します
+fn box_kind(b: &IsoBox) -> Result<BoxKind, Error> {
+    if &b.box_type == b"xml " {
+        return Ok(BoxKind::Xml);
+    }
+
+    let uuid: [u8; 16] = b
+        .data
+        .get(..16)
+        .and_then(|uuid| uuid.try_into().ok())
+        .ok_or_else(|| Error::ParseError("UUID box too short".to_string()))?;
+    Ok(match uuid {
+        UUID_EXIF => BoxKind::Exif,
+        UUID_XMP => BoxKind::Xmp,
+        UUID_IPTC => BoxKind::Iptc,
+        UUID_GEOJP2 => BoxKind::GeoJp2,
+        _ => BoxKind::Uuid,
+    })
+}
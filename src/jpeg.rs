@@ -1,11 +1,333 @@
-use crate::Error;
+use crate::cancel::{self, CancellableReader};
+use crate::exif::{self, ByteOrder, Field, IfdKind, TiffBuilder};
+use crate::xmp;
+use crate::{
+    CancellationToken, DecodedText, Error, ImageFormat, ImageInfo, LintFinding, LintRule,
+    Orientation, PixelFormat, Progress, ProgressStage, StructureReport, TextOptions, Utf8Policy,
+};
 use jpeg_decoder::Decoder;
+use md5::{Digest, Md5};
+use std::borrow::Cow;
+use std::ops::Range;
 
 const JPEG_SOI: [u8; 2] = [0xFF, 0xD8];
 const MARKER_COM: u8 = 0xFE;
 const MARKER_APP1: u8 = 0xE1;
 const MARKER_APP2: u8 = 0xE2;
+const MARKER_APP11: u8 = 0xEB;
+const MARKER_APP12: u8 = 0xEC;
 const MARKER_APP14: u8 = 0xEE;
+/// XMPパケットの識別子
+const XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// 拡張XMPの識別子
+const EXTENDED_XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xmp/extension/\0";
+/// 拡張XMPの1セグメントに格納できるデータの最大サイズ
+/// （識別子・GUID(32)・全体の長さ(4)・オフセット(4)を除く）
+const MAX_EXTENDED_XMP_CHUNK: usize = 0xFFFF - 2 - 35 - 32 - 8;
+/// ICCプロファイルの識別子
+const ICC_SIGNATURE: &[u8] = b"ICC_PROFILE\0";
+/// 1つのAPP2セグメントに格納できるICCプロファイルのバイト数
+///
+/// サイズフィールド(2) + 識別子(12) + シーケンス番号(1) + 総数(1)を除いた長さです。
+const MAX_ICC_CHUNK: usize = 0xFFFF - 2 - 14;
+/// 1つのCOMセグメントに格納できるコメントのバイト数
+const MAX_COMMENT_CHUNK: usize = 0xFFFF - 2;
+
+/// [`AppKind::Unknown`]に含める識別子の最大バイト数
+const MAX_UNKNOWN_SIGNATURE: usize = 32;
+/// Duckyの識別子（null終端なし）
+const DUCKY_SIGNATURE: &[u8] = b"Ducky";
+/// Duckyの画質のタグ
+const DUCKY_QUALITY: u16 = 1;
+/// Duckyのコメントのタグ
+const DUCKY_COMMENT: u16 = 2;
+/// Duckyの著作権表示のタグ
+const DUCKY_COPYRIGHT: u16 = 3;
+
+/// APPセグメントの種類
+///
+/// [`classify_app_segment`]でマーカーとペイロードの識別子から判定します。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AppKind {
+    /// JFIF（APP0、"JFIF\0"）
+    Jfif,
+    /// JFIF拡張（APP0、"JFXX\0"）
+    Jfxx,
+    /// EXIF（APP1、"Exif\0"）
+    Exif,
+    /// 標準XMP（APP1）
+    Xmp,
+    /// 拡張XMP（APP1）
+    ExtendedXmp,
+    /// ICCプロファイル（APP2、"ICC_PROFILE\0"）
+    Icc,
+    /// マルチピクチャーフォーマット（APP2、"MPF\0"）
+    Mpf,
+    /// JUMBFボックス（APP11、"JP"）。C2PAマニフェストを格納します
+    Jumbf,
+    /// Ducky（APP12、"Ducky"）。Photoshopの「Web用に保存」の画質情報です
+    Ducky,
+    /// Photoshopの画像リソース（APP13、"Photoshop 3.0\0"）。IPTCを格納します
+    Photoshop,
+    /// Adobeの色変換情報（APP14、"Adobe"）
+    Adobe,
+    /// 識別子が既知のものと一致しない
+    ///
+    /// ペイロード先頭のnull終端までのバイト列（最大32バイト）を含みます。
+    Unknown(Vec<u8>),
+}
+
+/// JPEGコメントの文字エンコーディング
+///
+/// JPEGのCOMセグメントはエンコーディング情報を持たないため、
+/// 読み書きの際に呼び出し側が指定します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommentEncoding {
+    /// UTF-8
+    #[default]
+    Utf8,
+    /// ISO-8859-1 (Latin-1)
+    Latin1,
+    /// Shift_JIS
+    ShiftJis,
+}
+
+/// コメントセグメントの挿入位置
+///
+/// 既存のCOMセグメントを取り除いた後のセグメント列に対して位置を決定します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommentPlacement {
+    /// 最初のDQTの直前（DQTがない場合はSOSの直前）
+    #[default]
+    BeforeTables,
+    /// SOIの直後（JFIFのAPP0より前になる点に注意）
+    AfterSoi,
+    /// 最後のAPPセグメントの直後（APPセグメントがない場合はSOIの直後）
+    AfterAppSegments,
+    /// SOSの直前
+    BeforeSos,
+}
+
+/// 軽量化で出力するEXIFセグメントの配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExifPlacement {
+    /// JFIFのAPP0の直後（JFIFがない場合はSOIの直後）
+    #[default]
+    AfterJfif,
+    /// SOIの直後（JFIFのAPP0より前）
+    ///
+    /// iOSのQuick Lookや一部のCDNなど、EXIFが先頭にあることを前提とする環境向けです。
+    First,
+}
+
+/// コメント書き込みのオプション
+#[derive(Debug, Clone, Default)]
+pub struct CommentOptions {
+    /// コメントセグメントの挿入位置
+    pub placement: CommentPlacement,
+    /// コメントの文字エンコーディング
+    pub encoding: CommentEncoding,
+    /// 1つのCOMセグメントに収まらないコメントを連続したCOMセグメントに分割する
+    ///
+    /// 文字の途中では分割しません。[`read_comments`]の
+    /// [`join_segments`](ReadCommentOptions::join_segments)で1つのコメントとして読み取れます。
+    /// `false`の場合、65533バイトを超えるコメントは`splittable`が`true`の
+    /// [`Error::PayloadTooLarge`]になります。
+    pub split_long: bool,
+    /// 書き込む前のコメントの検査・修正
+    pub text: TextOptions,
+}
+
+/// コメント読み取りのオプション
+#[derive(Debug, Clone, Default)]
+pub struct ReadCommentOptions {
+    /// コメントの文字エンコーディング
+    pub encoding: CommentEncoding,
+    /// 連続したCOMセグメントを1つのコメントとして連結する
+    ///
+    /// [`CommentOptions::split_long`]で分割して書き込んだコメントを復元します。
+    /// 連結はデコード前のバイト列に対して行います。
+    pub join_segments: bool,
+}
+
+impl CommentEncoding {
+    /// バイト列をこのエンコーディングの文字列としてデコードします
+    ///
+    /// 不正なバイト列の場合はエラーを返します（置換文字への変換は行いません）。
+    pub fn decode(self, bytes: &[u8]) -> Result<String, Error> {
+        match self {
+            CommentEncoding::Utf8 => String::from_utf8(bytes.to_vec())
+                .map_err(|_| Error::ParseError("Comment is not valid UTF-8".to_string())),
+            CommentEncoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+            CommentEncoding::ShiftJis => encoding_rs::SHIFT_JIS
+                .decode_without_bom_handling_and_without_replacement(bytes)
+                .map(|text| text.into_owned())
+                .ok_or_else(|| Error::ParseError("Comment is not valid Shift_JIS".to_string())),
+        }
+    }
+
+    /// 文字列をこのエンコーディングのバイト列にエンコードします
+    ///
+    /// 表現できない文字が含まれる場合はエラーを返します。
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            CommentEncoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            CommentEncoding::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c as u32).ok())
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(|| {
+                    Error::InvalidFormat("Comment cannot be encoded as Latin-1".to_string())
+                }),
+            CommentEncoding::ShiftJis => {
+                let (bytes, _, had_errors) = encoding_rs::SHIFT_JIS.encode(text);
+                if had_errors {
+                    return Err(Error::InvalidFormat(
+                        "Comment cannot be encoded as Shift_JIS".to_string(),
+                    ));
+                }
+                Ok(bytes.into_owned())
+            }
+        }
+    }
+}
+
+/// メタデータ軽量化のオプション
+///
+/// よく使う組み合わせは[`crate::CleanPreset`]から取得できます。
+#[derive(Debug, Clone)]
+pub struct CleanOptions {
+    /// C2PA（APP11のJUMBF）コンテンツクレデンシャルを保持する
+    pub preserve_c2pa: bool,
+    /// EXIFのオリエンテーションを最小限のEXIFとして保持する（デフォルト: `true`）
+    pub preserve_orientation: bool,
+    /// 範囲外（0や9以上）のオリエンテーションを1（Normal）に修復する
+    ///
+    /// `false`の場合、無効な値は最小限のEXIFから除外され、保持するEXIFにはそのまま残ります。
+    pub repair_orientation: bool,
+    /// EXIFをそのまま保持する
+    pub preserve_exif: bool,
+    /// XMP（拡張XMPを含む）を保持する
+    pub preserve_xmp: bool,
+    /// コメント（COMセグメント）を保持する
+    ///
+    /// 著作者の表示などに必要なコメントを、削除後に書き直さずに残せます。
+    pub preserve_comments: bool,
+    /// Ducky（APP12）を保持する
+    ///
+    /// Photoshopの「Web用に保存」が書き込む画質・コメント・著作権表示です。
+    /// 詳細は[`read_ducky`]を参照してください。
+    pub preserve_ducky: bool,
+    /// 最小限のEXIFに色再現に関わるタグ（ColorSpace、Gamma、WhitePoint、PrimaryChromaticities）を含める
+    ///
+    /// ICCプロファイルのない画像でも、カラーマネジメント対応のビューアが色を正しく解釈できます。
+    pub preserve_color_tags: bool,
+    /// 最小限のEXIFに解像度のタグ（XResolution、YResolution、ResolutionUnit）を含める
+    ///
+    /// 印刷用のワークフローでDPIが失われないようにします。ColorSpaceは
+    /// [`preserve_color_tags`](Self::preserve_color_tags)で保持できます。
+    pub preserve_resolution: bool,
+    /// 最小限のEXIFに含めるタグ番号（IFD0、Exif IFD、GPS IFDのタグが対象）
+    ///
+    /// 例えばDateTimeOriginal（0x9003）やCopyright（0x8298）を指定すると、
+    /// それらのタグのみを含むEXIFを再構築します。
+    pub preserve_exif_tags: Vec<u16>,
+    /// 再構築するEXIFのバイトオーダー
+    ///
+    /// `None`の場合、最小限のEXIFはリトルエンディアンで作成し、保持するEXIFは元のまま残します。
+    /// 指定した場合は保持するEXIFもオフセットを再計算して変換します。
+    pub exif_byte_order: Option<ByteOrder>,
+    /// 最小限のEXIFを元のEXIFと同じバイトオーダー（MMまたはII）で作成する
+    ///
+    /// [`exif_byte_order`](Self::exif_byte_order)を指定した場合はそちらが優先されます。
+    pub preserve_byte_order: bool,
+    /// sRGBのICCプロファイルを削除する（Webではプロファイルがない画像はsRGBとして扱われます）
+    pub replace_srgb_icc: bool,
+    /// 保持したEXIFからサムネイル（IFD1）を削除する
+    pub remove_thumbnail: bool,
+    /// 重複したメタデータセグメントを削除する
+    pub remove_duplicates: bool,
+    /// 仕様にないマーカーや未知のAPPセグメントがある入力をエラーにする
+    ///
+    /// 詳細は[`check_conformance`]を参照してください。
+    pub strict: bool,
+    /// SOI・SOFマーカーの重複や、ICCプロファイル・JUMBFの同じ連番を主張する
+    /// 複数のセグメントがある入力をエラーにする
+    ///
+    /// パーサーごとに解釈が異なる構造で、パーサー差異を突いた攻撃に使われます。
+    pub reject_duplicate_critical: bool,
+    /// ICCプロファイルのAPP2セグメントを1組に正規化する
+    ///
+    /// 重複したセグメントや誤ったシーケンス番号を修正します。
+    /// 詳細は[`normalize_icc_segments`]を参照してください。
+    pub normalize_icc: bool,
+    /// 出力の画像データが入力とバイト単位で一致することを検証する
+    ///
+    /// 詳細は[`verify_passthrough`]を参照してください。
+    pub verify_passthrough: bool,
+    /// 入力と出力をデコードし、画素が一致することを検証する
+    ///
+    /// 詳細は[`verify_pixels`]を参照してください。
+    pub verify_pixels: bool,
+    /// エントロピー符号化データを最適なハフマンテーブルで再符号化する
+    ///
+    /// メタデータの軽量化と同じ出力で、画素を変えずにさらにサイズを削減します。
+    /// 詳細は[`optimize_huffman`]を参照してください。対象外の画像や小さくならない場合は
+    /// 元の符号化のまま出力します。[`verify_passthrough`](Self::verify_passthrough)は
+    /// 再符号化する前の出力に対して検証します。
+    pub optimize_huffman: bool,
+    /// 保持・再構築したEXIFセグメントの配置
+    pub exif_placement: ExifPlacement,
+    /// 処理を中断するためのトークン
+    ///
+    /// 検証のデコード中（[`verify_pixels`](Self::verify_pixels)の全体のデコードを含む）と
+    /// セグメントの書き込みごとに確認し、中断時は[`Error::Cancelled`]を返します。
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl Default for CleanOptions {
+    fn default() -> Self {
+        Self {
+            preserve_c2pa: false,
+            preserve_orientation: true,
+            repair_orientation: false,
+            preserve_exif: false,
+            preserve_xmp: false,
+            preserve_comments: false,
+            preserve_ducky: false,
+            preserve_color_tags: false,
+            preserve_resolution: false,
+            preserve_exif_tags: Vec::new(),
+            exif_byte_order: None,
+            preserve_byte_order: false,
+            replace_srgb_icc: false,
+            remove_thumbnail: false,
+            remove_duplicates: false,
+            strict: false,
+            reject_duplicate_critical: false,
+            normalize_icc: false,
+            verify_passthrough: false,
+            verify_pixels: false,
+            optimize_huffman: false,
+            exif_placement: ExifPlacement::AfterJfif,
+            cancellation: None,
+        }
+    }
+}
+
+/// メタデータ軽量化の結果レポート
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanReport {
+    /// 入力にC2PAマニフェストが含まれていた
+    pub c2pa_present: bool,
+    /// C2PAマニフェストが無効になった
+    ///
+    /// マニフェストを削除した場合に加え、保持した場合でもファイル内容が変化すると
+    /// マニフェストのハッシュ（c2pa.hash.data）が一致しなくなるため`true`になります。
+    pub c2pa_invalidated: bool,
+    /// ハフマンテーブルを最適化して再符号化した
+    pub huffman_optimized: bool,
+}
 
 /// JPEG画像のメタデータを軽量化します
 ///
@@ -21,244 +343,2731 @@ const MARKER_APP14: u8 = 0xEE;
 /// - その他のEXIF情報を削除
 /// - 基本的なメタデータとEXIF・ICC以外を削除
 pub fn clean_metadata(data: &[u8]) -> Result<Vec<u8>, Error> {
+    clean_metadata_with_options(data, &CleanOptions::default())
+}
+
+/// オプションを指定してJPEG画像のメタデータを軽量化します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `options` - 保持するメタデータの指定
+///
+/// # Details
+/// SOSマーカー以降のデータと、保持するセグメント（再構築するEXIFを除く）は
+/// バイト単位でそのままコピーされます（[`verify_passthrough`]で検証できます）。
+pub fn clean_metadata_with_options(data: &[u8], options: &CleanOptions) -> Result<Vec<u8>, Error> {
+    clean_metadata_with_report(data, options).map(|(output, _)| output)
+}
+
+/// オプションを指定してJPEG画像のメタデータを軽量化し、結果レポートを返します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `options` - 保持するメタデータの指定
+///
+/// # Returns
+/// * `Ok((Vec<u8>, CleanReport))` - 軽量化されたJPEG画像データと結果レポート
+/// * `Err(Error)` - エラー
+pub fn clean_metadata_with_report(
+    data: &[u8],
+    options: &CleanOptions,
+) -> Result<(Vec<u8>, CleanReport), Error> {
+    let mut output = Vec::new();
+    let report = clean_metadata_into(data, options, &mut |_| {}, &mut output)?;
+    Ok((output, report))
+}
+
+/// オプションを指定してJPEG画像のメタデータを軽量化し、進捗を通知します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `options` - 保持するメタデータの指定
+/// * `progress` - 進捗を受け取るコールバック
+///
+/// # Details
+/// 入力の検証、出力の書き込み、出力の検証の各段階で[`Progress`]を通知します。
+/// 検証の段階はデコーダーが読み取ったバイト数を64KiBごとに通知し（`verify_pixels`の
+/// デコードを含む）、書き込みの段階はセグメントごとに出力済みのバイト数を通知します。
+pub fn clean_metadata_with_progress(
+    data: &[u8],
+    options: &CleanOptions,
+    mut progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    clean_metadata_into(data, options, &mut progress, &mut output)?;
+    Ok(output)
+}
+
+/// 進捗を通知しながらJPEG画像のメタデータを軽量化し、`output`に書き込みます
+///
+/// `output`の内容は置き換えられ、確保済みの容量は再利用されます。
+pub(crate) fn clean_metadata_into(
+    data: &[u8],
+    options: &CleanOptions,
+    progress: &mut dyn FnMut(Progress),
+    output: &mut Vec<u8>,
+) -> Result<CleanReport, Error> {
+    output.clear();
     if data.len() < 4 || data[0..2] != JPEG_SOI {
         return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
     }
 
+    let cancellation = options.cancellation.as_ref();
+
     // JPEGが正常にデコードできるか検証
-    validate_jpeg_decode(data)?;
+    progress(Progress::new(ProgressStage::ValidateInput, 0, data.len()));
+    validate_jpeg_decode_with_progress(data, cancellation, &mut |read| {
+        progress(Progress::new(
+            ProgressStage::ValidateInput,
+            read,
+            data.len(),
+        ))
+    })?;
+
+    if options.strict {
+        check_conformance(data)?;
+    }
+    if options.reject_duplicate_critical {
+        crate::security::check_duplicate_critical(data)?;
+    }
 
-    let mut output = Vec::new();
+    let plan = plan_clean(data, options)?;
+    progress(Progress::new(
+        ProgressStage::ValidateInput,
+        data.len(),
+        data.len(),
+    ));
+
+    let total = plan.output_len();
+    output.reserve(total);
     output.extend_from_slice(&JPEG_SOI);
+    for part in &plan.parts {
+        cancel::check(cancellation)?;
+        output.extend_from_slice(part);
+        progress(Progress::new(ProgressStage::Write, output.len(), total));
+    }
 
-    let mut pos = 2;
-    let mut has_exif = false;
-    let mut orientation: Option<u16> = None;
+    let mut report = plan.report;
+    if options.verify_passthrough {
+        verify_passthrough(data, output)?;
+    }
+    if options.optimize_huffman {
+        cancel::check(cancellation)?;
+        // 再符号化できない場合は軽量化のみの出力を残す
+        if let Ok(Some(optimized)) = crate::huffman::optimize(output) {
+            if optimized.len() < output.len() {
+                output.clear();
+                output.extend_from_slice(&optimized);
+                report.huffman_optimized = true;
+            }
+        }
+    }
 
-    // JPEGマーカーを解析
-    while pos < data.len() - 1 {
-        if data[pos] != 0xFF {
-            return Err(Error::ParseError("Invalid JPEG marker".to_string()));
+    // 出力が有効なJPEGか検証
+    let output_len = output.len();
+    progress(Progress::new(ProgressStage::ValidateOutput, 0, output_len));
+    // 画素の比較では先頭から読み直すため、読み取り位置が進んだ場合のみ通知する
+    let mut reported = 0;
+    let mut report_output = |read: usize| {
+        if read > reported {
+            reported = read;
+            progress(Progress::new(
+                ProgressStage::ValidateOutput,
+                read,
+                output_len,
+            ));
         }
+    };
+    validate_jpeg_decode_with_progress(output, cancellation, &mut report_output)?;
+    if options.verify_pixels {
+        verify_pixels_with_cancellation(data, output, cancellation, &mut report_output)?;
+    }
 
-        let marker = data[pos + 1];
-        pos += 2;
+    progress(Progress::new(
+        ProgressStage::ValidateOutput,
+        output.len(),
+        output.len(),
+    ));
 
-        // SOSマーカー以降は画像データなのでそのままコピー
-        if marker == 0xDA {
-            output.extend_from_slice(&[0xFF, marker]);
-            output.extend_from_slice(&data[pos..]);
-            break;
-        }
+    report.c2pa_invalidated = report.c2pa_present && output.as_slice() != data;
 
-        // スタンドアロンマーカーの場合
-        if (0xD0..=0xD9).contains(&marker) {
-            output.extend_from_slice(&[0xFF, marker]);
-            continue;
-        }
+    Ok(report)
+}
 
-        // セグメントサイズを読み取る
-        if pos + 2 > data.len() {
-            return Err(Error::ParseError("Unexpected end of JPEG data".to_string()));
-        }
+/// 軽量化後の出力サイズを予測します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `options` - 保持するメタデータの指定
+///
+/// # Returns
+/// * `Ok(usize)` - [`clean_metadata_with_options`]の出力と同じバイト数
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// メタデータのみを走査し、画像のデコードや出力の組み立ては行いません。
+/// デコードできない画像では、軽量化自体はエラーになる場合があります。
+/// [`CleanOptions::optimize_huffman`]による再符号化は考慮しないため、その場合は上限の値になります。
+pub fn predict_clean_size(data: &[u8], options: &CleanOptions) -> Result<usize, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
 
-        let segment_size = ((data[pos] as u16) << 8) | (data[pos + 1] as u16);
-        if segment_size < 2 {
-            return Err(Error::ParseError("Invalid segment size".to_string()));
-        }
+    plan_clean(data, options).map(|plan| plan.output_len())
+}
 
-        let segment_end = pos + segment_size as usize;
-        if segment_end > data.len() {
-            return Err(Error::ParseError("Segment extends beyond file".to_string()));
-        }
+/// 軽量化後の出力の構成
+struct CleanPlan<'a> {
+    /// SOIの後に出力する内容（出力順）
+    parts: Vec<Cow<'a, [u8]>>,
+    report: CleanReport,
+}
+
+impl CleanPlan<'_> {
+    /// 出力全体のバイト数
+    fn output_len(&self) -> usize {
+        JPEG_SOI.len() + self.parts.iter().map(|part| part.len()).sum::<usize>()
+    }
+}
+
+/// 保持するセグメントを決定し、軽量化後の出力の構成を返します
+fn plan_clean<'a>(data: &'a [u8], options: &CleanOptions) -> Result<CleanPlan<'a>, Error> {
+    let (segments, sos_pos) = parse_segments(data)?;
+    let c2pa_instances = find_c2pa_instances(&segments);
+
+    let report = CleanReport {
+        c2pa_present: !c2pa_instances.is_empty(),
+        ..Default::default()
+    };
+
+    let drop_icc = options.replace_srgb_icc
+        && read_icc_profile_from_segments(&segments)
+            .is_some_and(|profile| crate::icc::is_srgb_profile(&profile));
+    // 正規化したICCセグメントは最初のICCセグメントの位置に出力する
+    let mut normalized_icc = if options.normalize_icc && !drop_icc {
+        assemble_icc_profile(&segments)?
+            .map(|profile| build_icc_segments(&profile))
+            .transpose()?
+    } else {
+        None
+    };
+    let mut icc_kept = false;
+
+    let mut kept: Vec<&Segment> = Vec::new();
+    let mut exif_segment: Option<Vec<u8>> = None;
+    let mut has_exif = false;
+    let mut source_exif: Option<&[u8]> = None;
+
+    for segment in &segments {
+        let payload = segment.payload();
 
         // 保持するマーカーを判定
-        let keep_segment = match marker {
+        let keep_segment = match segment.marker {
             // 基本的な構造に必要なマーカー
             0xC0..=0xC3 | 0xC5..=0xCF => true, // SOF markers
             0xC4 => true,                      // DHT (Huffman tables)
             0xDB => true,                      // DQT (Quantization tables)
             0xDD => true,                      // DRI (Restart interval)
+            // スタンドアロンマーカー
+            0xD0..=0xD9 => true,
             // APP0 (JFIF) は保持
             0xE0 => true,
             // APP1 (EXIF) はオリエンテーション情報を抽出
-            MARKER_APP1 => {
-                if !has_exif && segment_size > 8 && &data[pos + 2..pos + 6] == b"Exif" {
+            MARKER_APP1 if payload.len() > 6 && &payload[0..4] == b"Exif" => {
+                if !has_exif {
                     has_exif = true;
-                    // EXIFからオリエンテーションを抽出
-                    // EXIFデータを簡易的に解析してオリエンテーションを取得
-                    orientation = extract_orientation_from_exif(&data[pos + 8..segment_end]);
+                    if options.preserve_exif {
+                        exif_segment = Some(match rewrite_exif(&payload[6..], options) {
+                            Some(tiff) => build_exif_segment(&tiff)?,
+                            None => segment.bytes.to_vec(),
+                        });
+                    } else {
+                        // 最小限のEXIFの再構築に使用
+                        source_exif = Some(&payload[6..]);
+                    }
                 }
                 false
             }
-            // APP2 (ICC Profile) は保持
-            MARKER_APP2 => segment_size > 14 && &data[pos + 2..pos + 14] == b"ICC_PROFILE\0",
-            // APP14 (Adobe色空間情報) は保持
-            MARKER_APP14 => {
-                segment_size >= 14 && pos + 7 <= data.len() && &data[pos + 2..pos + 7] == b"Adobe"
+            // APP1 (XMP) はオプションに応じて保持
+            MARKER_APP1 => {
+                options.preserve_xmp
+                    && (payload.starts_with(XMP_SIGNATURE)
+                        || payload.starts_with(EXTENDED_XMP_SIGNATURE))
+            }
+            // APP2 (ICC Profile) は保持（正規化する場合は最初のセグメントのみ）
+            MARKER_APP2 if payload.len() > 12 && payload.starts_with(ICC_SIGNATURE) => {
+                let keep = !(drop_icc || normalized_icc.is_some() && icc_kept);
+                icc_kept = true;
+                keep
+            }
+            // APP11 (JUMBF) はC2PAのみオプションに応じて保持
+            MARKER_APP11 => {
+                options.preserve_c2pa && c2pa_instance_of(payload, &c2pa_instances).is_some()
             }
-            // その他のAPPマーカーは削除 (0xE0, 0xE2, 0xEEは既に処理済みなので除外)
-            0xE3..=0xED | 0xEF => false,
-            // コメントは削除
-            MARKER_COM => false,
+            // APP14 (Adobe色空間情報) は保持
+            MARKER_APP14 => payload.len() >= 12 && &payload[0..5] == b"Adobe",
+            // APP12 (Ducky) はオプションに応じて保持
+            MARKER_APP12 if payload.starts_with(DUCKY_SIGNATURE) => options.preserve_ducky,
+            // その他のAPPマーカーは削除
+            0xE3..=0xEA | 0xEC | 0xED | 0xEF => false,
+            // コメントはオプションに応じて保持
+            MARKER_COM => options.preserve_comments,
             _ => false,
         };
 
-        if keep_segment {
-            output.extend_from_slice(&[0xFF, marker]);
-            output.extend_from_slice(&data[pos..segment_end]);
-        }
-
-        pos = segment_end;
-    }
-
-    // オリエンテーション情報がある場合は最小限のEXIFを追加
-    if let Some(orientation_value) = orientation {
-        if (1..=8).contains(&orientation_value) {
-            let exif_data = create_minimal_exif(orientation_value)?;
-            // JFIFマーカーの直後に挿入
-            let mut final_output = Vec::new();
-            let mut inserted = false;
-            let mut i = 0;
-
-            while i < output.len() - 1 {
-                if output[i] == 0xFF && output[i + 1] == 0xE0 && !inserted {
-                    // JFIFマーカーを見つけた
-                    let marker_size = ((output[i + 2] as u16) << 8) | (output[i + 3] as u16);
-                    let marker_end = i + 2 + marker_size as usize;
-                    final_output.extend_from_slice(&output[i..marker_end]);
-                    final_output.extend_from_slice(&exif_data);
-                    inserted = true;
-                    i = marker_end;
-                } else {
-                    final_output.push(output[i]);
-                    i += 1;
-                }
-            }
-            if i < output.len() {
-                final_output.push(output[i]);
-            }
+        // 同じ内容のAPPセグメントは1つだけ残す
+        let duplicate = options.remove_duplicates
+            && (0xE0..=0xEF).contains(&segment.marker)
+            && kept.iter().any(|k| k.bytes == segment.bytes);
+
+        if keep_segment && !duplicate {
+            kept.push(segment);
+        }
+    }
+
+    // オリエンテーション情報などがある場合は最小限のEXIFを追加
+    if let Some(tiff) = source_exif {
+        exif_segment = create_minimal_exif(tiff, options)?;
+    }
+
+    // JFIFマーカーの直後（JFIFがない場合はSOIの直後）またはSOIの直後に挿入
+    let exif_index = match options.exif_placement {
+        ExifPlacement::AfterJfif => kept
+            .iter()
+            .position(|segment| segment.marker == 0xE0)
+            .map_or(0, |index| index + 1),
+        ExifPlacement::First => 0,
+    };
+
+    let mut parts: Vec<Cow<[u8]>> = Vec::with_capacity(kept.len() + 2);
+    for (index, segment) in kept.iter().enumerate() {
+        if index == exif_index {
+            if let Some(exif) = exif_segment.take() {
+                parts.push(Cow::Owned(exif));
+            }
+        }
+        if segment.marker == MARKER_APP2 && segment.payload().starts_with(ICC_SIGNATURE) {
+            if let Some(icc) = normalized_icc.take() {
+                parts.push(Cow::Owned(icc));
+                continue;
+            }
+        }
+        parts.push(Cow::Borrowed(segment.bytes));
+    }
+    if let Some(exif) = exif_segment {
+        parts.push(Cow::Owned(exif));
+    }
+
+    // SOSマーカー以降は画像データなのでそのままコピー
+    if let Some(sos_pos) = sos_pos {
+        parts.push(Cow::Borrowed(&data[sos_pos..]));
+    }
+
+    Ok(CleanPlan { parts, report })
+}
+
+/// 軽量化の前後で画像データがバイト単位で変更されていないことを検証します
+///
+/// # Arguments
+/// * `original` - 軽量化前のJPEG画像データ
+/// * `cleaned` - 軽量化後のJPEG画像データ
+///
+/// # Returns
+/// * `Ok(())` - 画像データが一致する場合
+/// * `Err(Error::InvalidFormat)` - 一致しない場合（最初に異なる箇所を含みます）
+///
+/// # Details
+/// 次の部分が順序を含めて完全に一致することを確認します。
+/// - SOF・DHT・DQT・DRIなどのフレームとテーブルのセグメント
+/// - SOSマーカー以降のすべてのデータ（エントロピー符号化データとEOI以降を含む）
+///
+/// 軽量化関数はこれらの部分をそのままコピーするため、画素が変化しないことの証跡になります。
+pub fn verify_passthrough(original: &[u8], cleaned: &[u8]) -> Result<(), Error> {
+    for data in [original, cleaned] {
+        if data.len() < 4 || data[0..2] != JPEG_SOI {
+            return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+        }
+    }
+
+    let (original_segments, original_sos) = parse_segments(original)?;
+    let (cleaned_segments, cleaned_sos) = parse_segments(cleaned)?;
+
+    let original_tables = image_segments(&original_segments);
+    let cleaned_tables = image_segments(&cleaned_segments);
+    if original_tables.len() != cleaned_tables.len() {
+        return Err(Error::InvalidFormat(
+            "Number of frame and table segments differs".to_string(),
+        ));
+    }
+    for (segment, cleaned_segment) in original_tables.iter().zip(&cleaned_tables) {
+        if segment.bytes != cleaned_segment.bytes {
+            return Err(Error::InvalidFormat(format!(
+                "Segment 0x{:02X} at offset {} differs",
+                segment.marker, segment.offset
+            )));
+        }
+    }
+
+    let (Some(original_sos), Some(cleaned_sos)) = (original_sos, cleaned_sos) else {
+        return Err(Error::InvalidFormat("SOS marker not found".to_string()));
+    };
+    let original_scan = &original[original_sos..];
+    let cleaned_scan = &cleaned[cleaned_sos..];
+    if original_scan != cleaned_scan {
+        let position = original_scan
+            .iter()
+            .zip(cleaned_scan)
+            .position(|(a, b)| a != b)
+            .unwrap_or(original_scan.len().min(cleaned_scan.len()));
+        return Err(Error::InvalidFormat(format!(
+            "Image data differs at offset {} after SOS",
+            position
+        )));
+    }
+
+    Ok(())
+}
+
+/// 軽量化の前後で、デコードした画素が一致することを検証します
+///
+/// # Arguments
+/// * `original` - 元のJPEG画像データ
+/// * `cleaned` - 軽量化後のJPEG画像データ
+///
+/// # Returns
+/// * `Ok(())` - 画素が一致する場合
+/// * `Err(Error::PixelMismatch)` - 寸法・画素形式・画素が一致しない場合（最初に異なる画素の位置を含みます）
+/// * `Err(Error)` - デコードできない場合
+///
+/// # Details
+/// [`verify_passthrough`]と異なり、両方の画像を実際にデコードして比較します。
+/// Adobe（APP14）セグメントの削除による色変換の変化など、画像データのバイト列が
+/// 同じでもデコード結果が変わる場合を検出できます。ICCプロファイルによる
+/// 色の解釈の違いは比較の対象外です。
+pub fn verify_pixels(original: &[u8], cleaned: &[u8]) -> Result<(), Error> {
+    verify_pixels_with_cancellation(original, cleaned, None, &mut |_| {})
+}
+
+/// デコードの途中で中断を確認しながら画素を比較します
+///
+/// `progress`には軽量化後の画像のデコードで読み取った位置を通知します。
+fn verify_pixels_with_cancellation(
+    original: &[u8],
+    cleaned: &[u8],
+    cancellation: Option<&CancellationToken>,
+    progress: &mut dyn FnMut(usize),
+) -> Result<(), Error> {
+    let mut original_decoder = Decoder::new(CancellableReader::new(original, cancellation));
+    let original_pixels = decode_cancellable(&mut original_decoder, cancellation)?;
+    let mut cleaned_decoder =
+        Decoder::new(CancellableReader::new(cleaned, cancellation).with_progress(progress));
+    let cleaned_pixels = decode_cancellable(&mut cleaned_decoder, cancellation)?;
+
+    let (Some(original_info), Some(cleaned_info)) =
+        (original_decoder.info(), cleaned_decoder.info())
+    else {
+        return Err(Error::InvalidFormat("Failed to get JPEG info".to_string()));
+    };
+    if (original_info.width, original_info.height) != (cleaned_info.width, cleaned_info.height) {
+        return Err(Error::PixelMismatch(format!(
+            "Dimensions differ: {}x{} and {}x{}",
+            original_info.width, original_info.height, cleaned_info.width, cleaned_info.height
+        )));
+    }
+    if original_info.pixel_format != cleaned_info.pixel_format {
+        return Err(Error::PixelMismatch(format!(
+            "Pixel format differs: {:?} and {:?}",
+            original_info.pixel_format, cleaned_info.pixel_format
+        )));
+    }
+
+    compare_pixels(
+        &original_pixels,
+        &cleaned_pixels,
+        original_info.width as usize,
+        original_info.pixel_format.pixel_bytes() * 8,
+    )
+}
+
+/// デコードした画素のバッファを比較します
+///
+/// 各行は`ceil(width * bits_per_pixel / 8)`バイトで、1バイト未満の画素（1・2・4ビット）も扱えます。
+/// 一致しない場合は最初に異なる画素の座標を含むエラーを返します。
+pub(crate) fn compare_pixels(
+    original: &[u8],
+    cleaned: &[u8],
+    width: usize,
+    bits_per_pixel: usize,
+) -> Result<(), Error> {
+    if original.len() != cleaned.len() {
+        return Err(Error::PixelMismatch(format!(
+            "Pixel buffer sizes differ: {} and {}",
+            original.len(),
+            cleaned.len()
+        )));
+    }
+    let Some(position) = original.iter().zip(cleaned).position(|(a, b)| a != b) else {
+        return Ok(());
+    };
+    let bits_per_pixel = bits_per_pixel.max(1);
+    let row_bytes = (width.max(1) * bits_per_pixel).div_ceil(8);
+    Err(Error::PixelMismatch(format!(
+        "Pixel at ({}, {}) differs",
+        position % row_bytes * 8 / bits_per_pixel,
+        position / row_bytes
+    )))
+}
+
+/// エントロピー符号化データを画像に最適なハフマンテーブルで再符号化します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 再符号化したJPEG画像データ（小さくならない場合は入力のまま）
+/// * `Err(Error)` - JPEGではない場合や、エントロピー符号化データが壊れている場合
+///
+/// # Details
+/// - DCT係数は変更しないため、デコードした画素は一致します（[`verify_pixels`]で検証できます）
+/// - 元のDHTセグメントは削除し、画像で使われる値の出現頻度から作成したテーブルを
+///   1つのDHTセグメントとして最初のSOSの直前に配置します
+/// - ハフマン符号化のシーケンシャルJPEG（SOF0・SOF1）が対象で、プログレッシブや
+///   算術符号化などの画像は入力のまま返します
+/// - メタデータのセグメントとEOI以降のデータはそのまま残します
+///
+/// メタデータの軽量化と同時に行う場合は[`CleanOptions::optimize_huffman`]を使用してください。
+pub fn optimize_huffman(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+    validate_jpeg_decode(data, None)?;
+
+    match crate::huffman::optimize(data)? {
+        Some(optimized) if optimized.len() < data.len() => {
+            validate_jpeg_decode(&optimized, None)?;
+            Ok(optimized)
+        }
+        _ => Ok(data.to_vec()),
+    }
+}
+
+/// フレームとテーブルのセグメント（SOF、DHT、DAC、DQT、DRI）を抽出します
+fn image_segments<'a, 'b>(segments: &'b [Segment<'a>]) -> Vec<&'b Segment<'a>> {
+    segments
+        .iter()
+        .filter(|segment| matches!(segment.marker, 0xC0..=0xCF | 0xDB | 0xDD))
+        .collect()
+}
+
+/// JPEG画像からC2PAマニフェストストア（JUMBFボックス）を読み取ります
+///
+/// # Returns
+/// * `Ok(Some(Vec<u8>))` - 複数のAPP11セグメントを連結して復元したJUMBFスーパーボックス
+/// * `Ok(None)` - C2PAマニフェストが存在しない
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// JPEGではJUMBFボックスがAPP11セグメント（"JP"識別子、ボックスインスタンス番号、
+/// パケット連番）に分割して格納されます。2番目以降のパケットで繰り返される
+/// ボックスヘッダーは取り除いて連結します。
+pub fn read_c2pa_raw(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let (segments, _) = parse_segments(data)?;
+    Ok(read_c2pa_from_segments(&segments))
+}
+
+/// APP11セグメントに分割されたC2PAのJUMBFスーパーボックスを連結します
+pub(crate) fn read_c2pa_from_segments(segments: &[Segment]) -> Option<Vec<u8>> {
+    let c2pa_instances = find_c2pa_instances(segments);
+    let &instance = c2pa_instances.first()?;
+
+    // 同じインスタンスのパケットを連番順に並べる
+    let mut packets: Vec<(u32, &[u8])> = segments
+        .iter()
+        .filter(|segment| segment.marker == MARKER_APP11)
+        .map(|segment| segment.payload())
+        .filter(|payload| c2pa_instance_of(payload, &c2pa_instances) == Some(instance))
+        .map(|payload| {
+            let sequence = u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]);
+            (sequence, &payload[8..])
+        })
+        .collect();
+    packets.sort_by_key(|(sequence, _)| *sequence);
+
+    let mut jumbf = Vec::new();
+    for (index, (_, packet)) in packets.iter().enumerate() {
+        if index == 0 {
+            jumbf.extend_from_slice(packet);
+        } else if packet.len() > 8 {
+            // 繰り返されるLBox/TBoxを除去
+            jumbf.extend_from_slice(&packet[8..]);
+        }
+    }
+
+    Some(jumbf)
+}
+
+/// MPF（マルチピクチャーフォーマット）が参照する副画像を切り出します
+///
+/// MPインデックスIFDのMPエントリ（0xB002）を読み、オフセットが0の代表画像を除いた
+/// 各画像をファイル内の位置から取り出します。オフセットはMPFセグメント内の
+/// TIFFヘッダーの位置を基準とします。範囲外を指すエントリは無視します。
+pub(crate) fn read_mpf_images(data: &[u8], segments: &[Segment]) -> Vec<Vec<u8>> {
+    const TAG_MP_ENTRY: u16 = 0xB002;
+
+    let Some(segment) = segments
+        .iter()
+        .find(|segment| segment.marker == MARKER_APP2 && segment.payload().starts_with(b"MPF\0"))
+    else {
+        return Vec::new();
+    };
+    let tiff = &segment.payload()[4..];
+    // マーカー(2) + 長さ(2) + "MPF\0"(4)
+    let base = segment.offset + 8;
+
+    let Some(walk) = exif::walk(tiff) else {
+        return Vec::new();
+    };
+    let Some(entry) = walk.find(IfdKind::Ifd0, TAG_MP_ENTRY) else {
+        return Vec::new();
+    };
+    let Some(entries) = tiff.get(entry.value_offset..entry.value_offset + entry.value_len) else {
+        return Vec::new();
+    };
+
+    // 属性(4) + サイズ(4) + オフセット(4) + 従属画像(2 + 2)
+    entries
+        .chunks_exact(16)
+        .filter_map(|entry| {
+            let size = walk.byte_order.read_u32(entry, 4)? as usize;
+            let offset = walk.byte_order.read_u32(entry, 8)? as usize;
+            if offset == 0 {
+                return None;
+            }
+            let start = base.checked_add(offset)?;
+            data.get(start..start.checked_add(size)?)
+                .map(<[u8]>::to_vec)
+        })
+        .collect()
+}
+
+/// C2PAマニフェストを含むJUMBFのボックスインスタンス番号を列挙します
+fn find_c2pa_instances(segments: &[Segment]) -> Vec<u16> {
+    let mut instances = Vec::new();
+
+    for segment in segments.iter().filter(|s| s.marker == MARKER_APP11) {
+        let payload = segment.payload();
+        // "JP" + En(2) + Z(4) + LBox(4) + "jumb" + LBox(4) + "jumd" + UUID("c2pa"...)
+        if payload.len() >= 28
+            && &payload[0..2] == b"JP"
+            && &payload[12..16] == b"jumb"
+            && &payload[20..24] == b"jumd"
+            && &payload[24..28] == b"c2pa"
+        {
+            let instance = u16::from_be_bytes([payload[2], payload[3]]);
+            if !instances.contains(&instance) {
+                instances.push(instance);
+            }
+        }
+    }
+
+    instances
+}
+
+/// APP11セグメントがC2PAのJUMBFパケットであればそのインスタンス番号を返します
+fn c2pa_instance_of(payload: &[u8], instances: &[u16]) -> Option<u16> {
+    if payload.len() < 8 || &payload[0..2] != b"JP" {
+        return None;
+    }
+    let instance = u16::from_be_bytes([payload[2], payload[3]]);
+    instances.contains(&instance).then_some(instance)
+}
+
+/// APP2セグメントに分割されたICCプロファイルを連番順に連結します
+fn read_icc_profile_from_segments(segments: &[Segment]) -> Option<Vec<u8>> {
+    let mut chunks: Vec<(u8, &[u8])> = segments
+        .iter()
+        .filter(|segment| segment.marker == MARKER_APP2)
+        .map(|segment| segment.payload())
+        .filter(|payload| payload.len() > 14 && &payload[0..12] == b"ICC_PROFILE\0")
+        .map(|payload| (payload[12], &payload[14..]))
+        .collect();
+
+    if chunks.is_empty() {
+        return None;
+    }
+    chunks.sort_by_key(|(sequence, _)| *sequence);
+
+    Some(
+        chunks
+            .iter()
+            .flat_map(|(_, chunk)| chunk.iter().copied())
+            .collect(),
+    )
+}
+
+/// 重複や誤った連番を含むICCセグメントから1つのプロファイルを復元します
+///
+/// 内容が同一のセグメントを除いた上で、シーケンス番号が1から総数まで揃っていれば
+/// 連番順に連結します。揃っていない場合はファイル内の順に連結し、
+/// プロファイルヘッダーのサイズに達した時点のものを採用します。
+pub(crate) fn assemble_icc_profile(segments: &[Segment]) -> Result<Option<Vec<u8>>, Error> {
+    // (シーケンス番号, 総数, プロファイルデータ)
+    let mut chunks: Vec<(u8, u8, &[u8])> = Vec::new();
+    for segment in segments.iter().filter(|s| s.marker == MARKER_APP2) {
+        let payload = segment.payload();
+        if payload.len() > 14 && payload.starts_with(ICC_SIGNATURE) {
+            let chunk = (payload[12], payload[13], &payload[14..]);
+            if !chunks.contains(&chunk) {
+                chunks.push(chunk);
+            }
+        }
+    }
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+
+    let count = chunks[0].1 as usize;
+    let mut sequences: Vec<u8> = chunks.iter().map(|(sequence, _, _)| *sequence).collect();
+    sequences.sort_unstable();
+    let well_formed = chunks.iter().all(|(_, c, _)| *c as usize == count)
+        && sequences.iter().copied().eq(1..=count as u8)
+        && sequences.len() == count;
+
+    if well_formed {
+        let mut ordered = chunks;
+        ordered.sort_by_key(|(sequence, _, _)| *sequence);
+        return Ok(Some(
+            ordered
+                .iter()
+                .flat_map(|(_, _, chunk)| chunk.iter().copied())
+                .collect(),
+        ));
+    }
+
+    // プロファイルヘッダーの先頭4バイトはプロファイル全体のサイズ
+    let mut profile = Vec::new();
+    for (_, _, chunk) in &chunks {
+        profile.extend_from_slice(chunk);
+        if profile.len() >= 4 {
+            let declared = u32::from_be_bytes([profile[0], profile[1], profile[2], profile[3]]);
+            if profile.len() == declared as usize {
+                return Ok(Some(profile));
+            }
+            if profile.len() > declared as usize {
+                break;
+            }
+        }
+    }
+
+    Err(Error::InvalidFormat(
+        "ICC profile segments are inconsistent".to_string(),
+    ))
+}
+
+/// ICCプロファイルを連番付きのAPP2セグメント列に分割します
+fn build_icc_segments(profile: &[u8]) -> Result<Vec<u8>, Error> {
+    let size = estimate_icc_profile(profile.len()).ok_or(Error::PayloadTooLarge {
+        limit: 255 * MAX_ICC_CHUNK,
+        actual: profile.len(),
+        splittable: false,
+    })?;
+
+    let count = profile.len().div_ceil(MAX_ICC_CHUNK);
+    let mut output = Vec::with_capacity(size);
+    for (index, chunk) in profile.chunks(MAX_ICC_CHUNK).enumerate() {
+        output.extend_from_slice(&[0xFF, MARKER_APP2]);
+        output.extend_from_slice(&((chunk.len() + 16) as u16).to_be_bytes());
+        output.extend_from_slice(ICC_SIGNATURE);
+        output.push(index as u8 + 1);
+        output.push(count as u8);
+        output.extend_from_slice(chunk);
+    }
+    Ok(output)
+}
+
+/// JPEG画像にICCプロファイルを書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `profile` - ICCプロファイル
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - ICCプロファイルを置き換えたJPEG画像データ
+/// * `Err(Error::PayloadTooLarge)` - 255セグメント（約16MB）に収まらない場合
+/// * `Err(Error)` - その他のエラー
+///
+/// # Details
+/// - 1つのAPP2セグメントに収まらないプロファイルは、正しい連番と総数を付けて
+///   最小のセグメント数に分割します（[`estimate_icc_profile`]を参照）
+/// - 既存のICCセグメントはすべて削除し、最初のICCセグメントの位置に書き込みます
+/// - ICCセグメントがない場合は先頭のAPP0・APP1セグメント（JFIF・EXIF・XMP）の直後に挿入します
+pub fn write_icc_profile(data: &[u8], profile: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+    if profile.is_empty() {
+        return Err(Error::InvalidFormat("ICC profile is empty".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let icc = build_icc_segments(profile)?;
+    let (segments, _) = parse_segments(data)?;
+    let is_icc = |segment: &Segment| {
+        segment.marker == MARKER_APP2 && segment.payload().starts_with(ICC_SIGNATURE)
+    };
+    let position = match segments.iter().find(|segment| is_icc(segment)) {
+        Some(segment) => segment.offset,
+        None => segments
+            .iter()
+            .take_while(|segment| matches!(segment.marker, 0xE0 | MARKER_APP1))
+            .last()
+            .map_or(2, |segment| segment.offset + segment.bytes.len()),
+    };
+
+    let mut output = Vec::with_capacity(data.len() + icc.len());
+    let mut pos = 0;
+    for segment in segments.iter().filter(|segment| is_icc(segment)) {
+        output.extend_from_slice(&data[pos..segment.offset]);
+        if segment.offset == position {
+            output.extend_from_slice(&icc);
+        }
+        pos = segment.offset + segment.bytes.len();
+    }
+    if pos <= position {
+        output.extend_from_slice(&data[pos..position]);
+        output.extend_from_slice(&icc);
+        pos = position;
+    }
+    output.extend_from_slice(&data[pos..]);
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// ICCプロファイルのAPP2セグメントを正規化します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - ICCセグメントを書き直したJPEG画像データ（ICCプロファイルがない場合は入力のまま）
+/// * `Err(Error)` - セグメントからプロファイルを復元できない場合など
+///
+/// # Details
+/// エンコーダーによっては同じプロファイルを二重に出力したり、シーケンス番号が
+/// 誤っていたりします。重複を取り除いたプロファイルを正しい連番と総数で分割し直し、
+/// 最初のICCセグメントの位置に書き込みます。その他のセグメントは変更しません。
+///
+/// 分割は1セグメントの上限（65519バイト）ごとに行うため、細かく断片化された
+/// プロファイルも最小のセグメント数にまとめられます。
+pub fn normalize_icc_segments(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let (segments, sos_pos) = parse_segments(data)?;
+    let Some(profile) = assemble_icc_profile(&segments)? else {
+        return Ok(data.to_vec());
+    };
+    let mut icc = Some(build_icc_segments(&profile)?);
+
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&JPEG_SOI);
+    for segment in &segments {
+        if segment.marker == MARKER_APP2 && segment.payload().starts_with(ICC_SIGNATURE) {
+            if let Some(icc) = icc.take() {
+                output.extend_from_slice(&icc);
+            }
+        } else {
+            output.extend_from_slice(segment.bytes);
+        }
+    }
+    if let Some(sos_pos) = sos_pos {
+        output.extend_from_slice(&data[sos_pos..]);
+    }
+
+    Ok(output)
+}
+
+/// SOSより前のセグメントを正規の順序に並べ替え、断片化したセグメントをまとめます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 正規化したJPEG画像データ
+/// * `Err(Error)` - JPEGではない場合や、セグメントの構造が壊れている場合、
+///   入力または出力をデコードできない場合
+///
+/// # Details
+/// 内容が同じでもセグメントの並びが異なる画像を、バイト単位で比較・キャッシュできるようにします。
+/// - SOSより前のセグメントを次の順に並べます（同じ種類のセグメントは元の順序を保ちます）。
+///   JFIF、その他のAPP0、EXIF、標準XMP、拡張XMP、ICCプロファイル、その他のAPPセグメント、
+///   COM、DQT、SOF、DHT・DAC、その他（DRIなど）
+/// - ICCプロファイルは[`normalize_icc_segments`]と同様に最小のセグメント数に分割し直します
+///   （セグメントからプロファイルを復元できない場合はそのまま残します）
+/// - DQT・DHTは1つのセグメントに収まる限り連結します
+/// - データを持たない（サイズフィールドが2の）セグメントを削除します
+/// - 最初のSOS以降（エントロピー符号化データとスキャン間のセグメント）は変更しません
+///
+/// 正規化済みの画像を再び正規化しても結果は変わりません。
+pub fn normalize(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let (mut segments, sos_pos) = parse_segments(data)?;
+    segments.retain(|segment| segment.bytes.len() != 4);
+    segments.sort_by_key(canonical_rank);
+
+    let icc = assemble_icc_profile(&segments)
+        .ok()
+        .flatten()
+        .map(|profile| build_icc_segments(&profile))
+        .transpose()?;
+    let mut icc_pending = icc.as_deref();
+
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&JPEG_SOI);
+    // 連結中のDQT・DHT（マーカーとペイロード）
+    let mut pending: Option<(u8, Vec<u8>)> = None;
+    for segment in &segments {
+        let payload = segment.payload();
+        if let Some((marker, tables)) = &mut pending {
+            if *marker == segment.marker && tables.len() + payload.len() <= 0xFFFF - 2 {
+                tables.extend_from_slice(payload);
+                continue;
+            }
+        }
+        if let Some((marker, tables)) = pending.take() {
+            write_segment(&mut output, marker, &tables);
+        }
+
+        match segment.marker {
+            0xDB | 0xC4 => pending = Some((segment.marker, payload.to_vec())),
+            MARKER_APP2 if icc.is_some() && payload.starts_with(ICC_SIGNATURE) => {
+                // 最初のICCセグメントの位置にまとめて書き込む
+                if let Some(icc) = icc_pending.take() {
+                    output.extend_from_slice(icc);
+                }
+            }
+            _ => output.extend_from_slice(segment.bytes),
+        }
+    }
+    if let Some((marker, tables)) = pending {
+        write_segment(&mut output, marker, &tables);
+    }
+    if let Some(sos_pos) = sos_pos {
+        output.extend_from_slice(&data[sos_pos..]);
+    }
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// [`normalize`]でのセグメントの並び順
+fn canonical_rank(segment: &Segment) -> u8 {
+    match segment.app_kind() {
+        Some(AppKind::Jfif) => 0,
+        Some(_) if segment.marker == 0xE0 => 1,
+        Some(AppKind::Exif) => 2,
+        Some(AppKind::Xmp) => 3,
+        Some(AppKind::ExtendedXmp) => 4,
+        Some(AppKind::Icc) => 5,
+        Some(_) => 6,
+        None => match segment.marker {
+            MARKER_COM => 7,
+            0xDB => 8,
+            marker if is_sof(marker) => 9,
+            0xC4 | 0xCC => 10,
+            _ => 11,
+        },
+    }
+}
+
+/// マーカーとペイロードからセグメントを書き込みます
+fn write_segment(output: &mut Vec<u8>, marker: u8, payload: &[u8]) {
+    output.extend_from_slice(&[0xFF, marker]);
+    output.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    output.extend_from_slice(payload);
+}
+
+/// TIFFデータからEXIFのAPP1セグメントを作成
+fn build_exif_segment(tiff: &[u8]) -> Result<Vec<u8>, Error> {
+    // マーカー以降のサイズ（サイズフィールド + Exif識別子 + TIFF）
+    let size = 2 + 6 + tiff.len();
+    if size > 0xFFFF {
+        return Err(Error::PayloadTooLarge {
+            limit: 0xFFFF - 2 - 6,
+            actual: tiff.len(),
+            splittable: false,
+        });
+    }
+
+    let mut segment = Vec::with_capacity(size + 2);
+    segment.extend_from_slice(&[0xFF, MARKER_APP1]);
+    segment.extend_from_slice(&(size as u16).to_be_bytes());
+    segment.extend_from_slice(b"Exif\0\0");
+    segment.extend_from_slice(tiff);
+
+    Ok(segment)
+}
+
+/// 保持するEXIFをオプションに応じて書き換えます
+///
+/// 変更が不要な場合は`None`を返します。
+fn rewrite_exif(tiff: &[u8], options: &CleanOptions) -> Option<Vec<u8>> {
+    let mut rewritten = None;
+
+    if options.remove_thumbnail {
+        rewritten = exif::remove_thumbnail(tiff);
+    }
+
+    if options.repair_orientation {
+        let current = rewritten.as_deref().unwrap_or(tiff);
+        let invalid = extract_orientation_from_exif(current)
+            .is_some_and(|value| Orientation::from_exif(value).is_none());
+        if invalid {
+            if let Some(repaired) = exif::set_orientation(current, Orientation::Normal.to_exif()) {
+                rewritten = Some(repaired);
+            }
+        }
+    }
+
+    if let Some(byte_order) = options.exif_byte_order {
+        let current = rewritten.as_deref().unwrap_or(tiff);
+        if exif::parse_header(current).map(|(order, _)| order) != Some(byte_order) {
+            if let Some(converted) = exif::rebuild(current, byte_order) {
+                rewritten = Some(converted);
+            }
+        }
+    }
+
+    rewritten
+}
+
+/// 最小限のEXIFで保持する色再現に関わるタグ
+const COLOR_TAGS: &[(IfdKind, u16)] = &[
+    (IfdKind::Ifd0, exif::TAG_WHITE_POINT),
+    (IfdKind::Ifd0, exif::TAG_PRIMARY_CHROMATICITIES),
+    (IfdKind::Exif, exif::TAG_COLOR_SPACE),
+    (IfdKind::Exif, exif::TAG_GAMMA),
+];
+
+/// 最小限のEXIFで保持する解像度のタグ
+const RESOLUTION_TAGS: &[(IfdKind, u16)] = &[
+    (IfdKind::Ifd0, exif::TAG_X_RESOLUTION),
+    (IfdKind::Ifd0, exif::TAG_Y_RESOLUTION),
+    (IfdKind::Ifd0, exif::TAG_RESOLUTION_UNIT),
+];
+
+/// 元のEXIFから最小限のEXIFセグメントを作成
+///
+/// 保持するタグのみを含むTIFF構造を作り直すため、値のオフセットは詰めて再計算されます。
+///
+/// 保持するタグが1つもない場合は`None`を返します。
+fn create_minimal_exif(tiff: &[u8], options: &CleanOptions) -> Result<Option<Vec<u8>>, Error> {
+    let mut builder = TiffBuilder::default();
+
+    if options.preserve_orientation {
+        if let Some(orientation) = extract_orientation_from_exif(tiff).and_then(|value| {
+            Orientation::from_exif(value)
+                .or_else(|| options.repair_orientation.then_some(Orientation::Normal))
+        }) {
+            builder
+                .ifd0
+                .push(Field::short(exif::TAG_ORIENTATION, orientation.to_exif()));
+        }
+    }
+
+    if let Some(walk) = exif::walk(tiff) {
+        for entry in &walk.entries {
+            let wanted = (options.preserve_color_tags
+                && COLOR_TAGS.contains(&(entry.ifd, entry.tag)))
+                || (options.preserve_resolution
+                    && RESOLUTION_TAGS.contains(&(entry.ifd, entry.tag)))
+                || options.preserve_exif_tags.contains(&entry.tag);
+
+            // オリエンテーションとサブIFDへのポインタは別に扱う
+            if !wanted
+                || exif::is_pointer_tag(entry.tag)
+                || (options.preserve_orientation && entry.tag == exif::TAG_ORIENTATION)
+            {
+                continue;
+            }
+
+            let Some(field) = Field::from_entry(tiff, &walk, entry) else {
+                continue;
+            };
+            match entry.ifd {
+                IfdKind::Ifd0 => builder.ifd0.push(field),
+                IfdKind::Exif => builder.exif.push(field),
+                IfdKind::Gps => builder.gps.push(field),
+                IfdKind::Interop | IfdKind::Ifd1 => {}
+            }
+        }
+    }
+
+    if builder.ifd0.is_empty() && builder.exif.is_empty() && builder.gps.is_empty() {
+        return Ok(None);
+    }
+
+    let source_byte_order = options
+        .preserve_byte_order
+        .then(|| exif::parse_header(tiff).map(|(byte_order, _)| byte_order))
+        .flatten();
+    let byte_order = options
+        .exif_byte_order
+        .or(source_byte_order)
+        .unwrap_or(ByteOrder::LittleEndian);
+    build_exif_segment(&builder.build(byte_order)).map(Some)
+}
+
+/// スキャン構造の情報
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanInfo {
+    /// 最初のスキャンに適用されるリスタート間隔（MCU数）
+    ///
+    /// DRIセグメントがない場合や、間隔が0（無効）の場合は`None`です。
+    pub restart_interval: Option<u16>,
+    /// スキャン（SOSセグメント）の数
+    pub scan_count: usize,
+    /// 画像データに含まれるリスタートマーカー（RST0〜RST7）の数
+    pub restart_markers: usize,
+    /// プログレッシブJPEGか
+    pub progressive: bool,
+}
+
+impl ScanInfo {
+    /// 画像データにリスタートマーカーが含まれるか
+    pub fn has_restart_markers(&self) -> bool {
+        self.restart_markers > 0
+    }
+}
+
+/// JPEG画像のリスタート間隔とスキャン構造をデコードせずに読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(ScanInfo)` - スキャン構造の情報
+/// * `Err(Error)` - JPEGではない場合やSOSより前で途切れている場合
+///
+/// # Details
+/// マーカーの走査のみを行うため、並列デコードの方針決定などに低コストで利用できます。
+/// 途中で途切れたファイルでは、読み取れた範囲の情報を返します。
+pub fn read_scan_info(data: &[u8]) -> Result<ScanInfo, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let (segments, sos_pos) = parse_segments(data)?;
+    let Some(sos_pos) = sos_pos else {
+        return Err(Error::ParseError("SOS marker not found".to_string()));
+    };
+
+    let mut info = ScanInfo::default();
+    for segment in &segments {
+        match segment.marker {
+            0xDD => {
+                let payload = segment.payload();
+                if payload.len() < 2 {
+                    return Err(Error::ParseError("Invalid DRI segment".to_string()));
+                }
+                info.restart_interval =
+                    Some(u16::from_be_bytes([payload[0], payload[1]])).filter(|&i| i > 0);
+            }
+            0xC2 | 0xC6 | 0xCA | 0xCE => info.progressive = true,
+            _ => {}
+        }
+    }
+
+    let walk = walk_scans(data, sos_pos);
+    // walk_scansは最初のSOSもマーカーとして記録する
+    info.scan_count = walk
+        .markers
+        .iter()
+        .filter(|(marker, _)| *marker == 0xDA)
+        .count();
+    info.restart_markers = walk.restart_markers;
+
+    Ok(info)
+}
+
+/// JPEG画像が透明度を持つか判定します
+///
+/// JPEGはアルファチャンネルを持たないため、有効なJPEGでは常に`false`を返します。
+/// [`crate::png::has_transparency`]と合わせて、形式によらず透明度を判定するために利用できます。
+///
+/// # Returns
+/// * `Ok(false)` - JPEG画像の場合
+/// * `Err(Error)` - JPEGではない場合
+pub fn has_alpha(data: &[u8]) -> Result<bool, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    Ok(false)
+}
+
+/// JPEG画像のコンポーネント数を読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(u8)` - フレーム（SOF）のコンポーネント数（1: グレースケール、3: YCbCr・RGB、4: CMYK・YCCK）
+/// * `Err(Error)` - JPEGではない場合やSOFがない場合
+///
+/// # Details
+/// SOFまでのセグメントを読むだけで、デコードは行いません。
+pub fn component_count(data: &[u8]) -> Result<u8, Error> {
+    read_frame_header(data).map(|frame| frame.components)
+}
+
+/// JPEG画像の寸法と画素の形式を読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(ImageInfo)` - 寸法と画素の形式
+/// * `Err(Error)` - JPEGではない場合やSOFがない場合、寸法が0の場合
+///
+/// # Details
+/// SOFのサンプルのビット数とコンポーネント数を[`PixelFormat`]に変換します。
+/// JPEGはアルファチャンネルとパレットを持たないため、`has_alpha`と`palette`は常に`false`です。
+pub fn read_info(data: &[u8]) -> Result<ImageInfo, Error> {
+    let frame = read_frame_header(data)?;
+    if frame.width == 0 || frame.height == 0 {
+        return Err(Error::InvalidFormat("Invalid image dimensions".to_string()));
+    }
+
+    Ok(ImageInfo {
+        format: ImageFormat::Jpeg,
+        width: u32::from(frame.width),
+        height: u32::from(frame.height),
+        pixel_format: PixelFormat {
+            channels: frame.components,
+            bits_per_channel: frame.precision,
+            has_alpha: false,
+            palette: false,
+        },
+    })
+}
+
+/// JPEG画像がグレースケール（1コンポーネント）か判定します
+///
+/// 色差に関する処理（クロマサブサンプリングの変更など）を省略する判断に利用できます。
+///
+/// # Returns
+/// * `Ok(bool)` - コンポーネント数が1の場合は`true`
+/// * `Err(Error)` - JPEGではない場合やSOFがない場合
+pub fn is_grayscale(data: &[u8]) -> Result<bool, Error> {
+    component_count(data).map(|components| components == 1)
+}
+
+/// SOFセグメントから読み取ったフレームのヘッダー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FrameHeader {
+    /// サンプルのビット数
+    pub(crate) precision: u8,
+    pub(crate) width: u16,
+    /// 高さ（DNLマーカーで定義する場合は0）
+    pub(crate) height: u16,
+    pub(crate) components: u8,
+}
+
+impl FrameHeader {
+    /// SOFセグメントのペイロードから読み取ります
+    pub(crate) fn parse(payload: &[u8]) -> Result<Self, Error> {
+        if payload.len() < 6 {
+            return Err(Error::ParseError("Invalid SOF segment".to_string()));
+        }
+        Ok(Self {
+            precision: payload[0],
+            height: u16::from_be_bytes([payload[1], payload[2]]),
+            width: u16::from_be_bytes([payload[3], payload[4]]),
+            components: payload[5],
+        })
+    }
+}
+
+/// SOFマーカーか判定します（DHT・JPG・DACを除く）
+pub(crate) fn is_sof(marker: u8) -> bool {
+    matches!(marker, 0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF)
+}
+
+/// 最初のSOFセグメントを探し、フレームのヘッダーを読み取ります
+pub(crate) fn read_frame_header(data: &[u8]) -> Result<FrameHeader, Error> {
+    for segment in segments(data)? {
+        let segment = segment?;
+        if is_sof(segment.marker) {
+            return FrameHeader::parse(segment.payload());
+        }
+    }
+    Err(Error::ParseError("SOF marker not found".to_string()))
+}
+
+/// 色空間に関する情報の矛盾
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorConflict {
+    /// ICCプロファイルの色空間がコンポーネント数と一致しない
+    IccColorSpaceMismatch {
+        /// フレームのコンポーネント数
+        components: u8,
+        /// プロファイルヘッダーの色空間（`RGB `、`CMYK`など）
+        color_space: [u8; 4],
+    },
+    /// JFIFがあるが、コンポーネント数が1（グレースケール）・3（YCbCr）以外
+    JfifComponentMismatch {
+        /// フレームのコンポーネント数
+        components: u8,
+    },
+    /// Adobeセグメントの変換方式がコンポーネント数と一致しない
+    AdobeTransformMismatch {
+        /// フレームのコンポーネント数
+        components: u8,
+        /// 変換方式（1: YCbCr、2: YCCK）
+        transform: u8,
+    },
+    /// JFIF（YCbCr）とAdobeセグメントのRGB指定（変換なし）が両方ある
+    JfifWithAdobeRgb,
+    /// 4コンポーネントだがAdobeセグメントがなく、CMYKかYCCKか・反転の有無が不明
+    CmykWithoutAdobe,
+}
+
+/// JPEG画像の色空間に関する情報の矛盾を検査します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<ColorConflict>)` - 見つかった矛盾（矛盾がない場合は空）
+/// * `Err(Error)` - JPEGではない場合やSOFがない場合
+///
+/// # Details
+/// フレーム（SOF）のコンポーネント数、Adobeセグメントの変換方式、JFIFの有無、
+/// ICCプロファイルの色空間を照合します。
+/// 矛盾のある画像はビューアーによって色の解釈が異なります。
+pub fn check_color_segments(data: &[u8]) -> Result<Vec<ColorConflict>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let components = read_frame_header(data)?.components;
+    let (segments, _) = parse_segments(data)?;
+
+    let has_jfif = segments
+        .iter()
+        .any(|segment| segment.marker == 0xE0 && segment.payload().starts_with(b"JFIF\0"));
+    // "Adobe" + バージョン(2) + フラグ(2) + フラグ(2) + 変換方式(1)
+    let transform = segments
+        .iter()
+        .filter(|segment| segment.marker == MARKER_APP14)
+        .map(|segment| segment.payload())
+        .find(|payload| payload.len() >= 12 && payload.starts_with(b"Adobe"))
+        .map(|payload| payload[11]);
+
+    let mut conflicts = Vec::new();
+
+    if let Some(profile) = read_icc_profile_from_segments(&segments) {
+        if let Some(color_space) = profile.get(16..20) {
+            let color_space: [u8; 4] = color_space.try_into().unwrap();
+            let expected: &[&[u8; 4]] = match components {
+                1 => &[b"GRAY"],
+                3 => &[b"RGB ", b"Lab "],
+                4 => &[b"CMYK"],
+                _ => &[],
+            };
+            if !expected.contains(&&color_space) {
+                conflicts.push(ColorConflict::IccColorSpaceMismatch {
+                    components,
+                    color_space,
+                });
+            }
+        }
+    }
+
+    if has_jfif && !matches!(components, 1 | 3) {
+        conflicts.push(ColorConflict::JfifComponentMismatch { components });
+    }
+
+    match transform {
+        Some(transform @ (1 | 2)) if components != transform + 2 => {
+            conflicts.push(ColorConflict::AdobeTransformMismatch {
+                components,
+                transform,
+            });
+        }
+        Some(0) if has_jfif && components == 3 => {
+            conflicts.push(ColorConflict::JfifWithAdobeRgb);
+        }
+        None if components == 4 => conflicts.push(ColorConflict::CmykWithoutAdobe),
+        _ => {}
+    }
+
+    Ok(conflicts)
+}
+
+/// リントのオプション
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    /// EXIFのサムネイル画像の上限サイズ（バイト）
+    pub max_thumbnail_size: usize,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            max_thumbnail_size: 32 * 1024,
+        }
+    }
+}
+
+/// JPEG画像の衛生状態を検査します
+///
+/// [`LintOptions`]のデフォルト値で[`lint_with_options`]を実行します。
+pub fn lint(data: &[u8]) -> Vec<LintFinding> {
+    lint_with_options(data, &LintOptions::default())
+}
+
+/// オプションを指定してJPEG画像の衛生状態を検査します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `options` - 検査の設定
+///
+/// # Returns
+/// 見つかった問題（出現順）。JPEGではない場合や構造が壊れている場合も
+/// [`LintRule::MalformedStructure`]として報告し、エラーにはなりません。
+///
+/// # Details
+/// - JFIF・EXIFセグメントの重複、内容が空のAPPセグメント
+/// - 画像データ（SOS）より後ろにあるAPP・COMセグメント（多くのデコーダーが無視します）
+/// - EOIマーカーの欠落
+/// - 上限サイズを超えるEXIFのサムネイル画像
+pub fn lint_with_options(data: &[u8], options: &LintOptions) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        findings.push(LintFinding::new(
+            LintRule::MalformedStructure,
+            Some(0),
+            "Not a valid JPEG file",
+        ));
+        return findings;
+    }
+
+    let (segments, sos_pos) = match parse_segments(data) {
+        Ok(result) => result,
+        Err(e) => {
+            findings.push(LintFinding::new(
+                LintRule::MalformedStructure,
+                None,
+                e.to_string(),
+            ));
+            return findings;
+        }
+    };
+
+    let mut jfif_count = 0;
+    let mut exif_count = 0;
+    for segment in &segments {
+        if !(0xE0..=0xEF).contains(&segment.marker) {
+            continue;
+        }
+        let offset = Some(segment.offset);
+        let payload = segment.payload();
+
+        if payload.is_empty() {
+            findings.push(LintFinding::new(
+                LintRule::EmptySegment,
+                offset,
+                format!(
+                    "empty APP{} at offset {}",
+                    segment.marker - 0xE0,
+                    segment.offset
+                ),
+            ));
+        } else if segment.marker == 0xE0 && payload.starts_with(b"JFIF\0") {
+            jfif_count += 1;
+            if jfif_count > 1 {
+                findings.push(LintFinding::new(
+                    LintRule::DuplicateSegment,
+                    offset,
+                    format!("additional JFIF at offset {}", segment.offset),
+                ));
+            }
+        } else if segment.marker == MARKER_APP1 && payload.starts_with(b"Exif\0") {
+            exif_count += 1;
+            if exif_count > 1 {
+                findings.push(LintFinding::new(
+                    LintRule::DuplicateSegment,
+                    offset,
+                    format!("additional EXIF at offset {}", segment.offset),
+                ));
+            } else if let Some(length) = payload
+                .get(6..)
+                .and_then(exif::thumbnail_length)
+                .filter(|&length| length > options.max_thumbnail_size)
+            {
+                findings.push(LintFinding::new(
+                    LintRule::OversizedThumbnail,
+                    offset,
+                    format!("EXIF thumbnail has {length} bytes"),
+                ));
+            }
+        }
+    }
+
+    let Some(sos_pos) = sos_pos else {
+        findings.push(LintFinding::new(
+            LintRule::MalformedStructure,
+            None,
+            "SOS marker not found",
+        ));
+        return findings;
+    };
+
+    let walk = walk_scans(data, sos_pos);
+    for &(marker, pos) in &walk.markers {
+        let name = match marker {
+            MARKER_APP1
+                if data
+                    .get(pos + 4..)
+                    .is_some_and(|p| p.starts_with(b"Exif\0")) =>
+            {
+                "EXIF".to_string()
+            }
+            0xE0..=0xEF => format!("APP{}", marker - 0xE0),
+            MARKER_COM => "COM".to_string(),
+            _ => continue,
+        };
+        findings.push(LintFinding::new(
+            LintRule::SegmentAfterScan,
+            Some(pos),
+            format!("{name} at offset {pos} follows SOS"),
+        ));
+    }
+    if walk.eoi_end.is_none() {
+        findings.push(LintFinding::new(
+            LintRule::MissingEoi,
+            None,
+            "EOI marker not found",
+        ));
+    }
+
+    findings
+}
+
+/// JPEGの完全性チェックの結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// SOSマーカー（画像データの開始）が存在する
+    pub has_sos: bool,
+    /// EOIマーカーが存在する
+    pub has_eoi: bool,
+    /// データがセグメントや画像データの途中で途切れている
+    pub truncated: bool,
+    /// EOI以降に続くデータのバイト数
+    pub trailing_bytes: usize,
+}
+
+impl IntegrityReport {
+    /// 画像データがEOIまで揃っているか
+    pub fn is_complete(&self) -> bool {
+        self.has_sos && self.has_eoi && !self.truncated
+    }
+}
+
+/// JPEG画像がEOIまで揃っているかをデコードせずに確認します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(IntegrityReport)` - チェック結果（途切れたファイルもエラーにはなりません）
+/// * `Err(Error)` - JPEGではない場合
+pub fn check_integrity(data: &[u8]) -> Result<IntegrityReport, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let mut report = IntegrityReport::default();
+
+    let sos_pos = match parse_segments(data) {
+        Ok((_, Some(sos_pos))) => sos_pos,
+        // SOSより前で途切れている
+        Ok((_, None)) | Err(_) => {
+            report.truncated = true;
+            return Ok(report);
+        }
+    };
+    report.has_sos = true;
+
+    let walk = walk_scans(data, sos_pos);
+    match walk.eoi_end {
+        Some(end) => {
+            report.has_eoi = true;
+            report.trailing_bytes = data.len() - end;
+        }
+        None => report.truncated = true,
+    }
+
+    Ok(report)
+}
+
+/// 途中で途切れたJPEG画像にEOIを追加し、表示できる状態に修復します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 修復したJPEG画像データ（完全な画像はそのまま返します）
+/// * `Err(Error)` - SOSより前で途切れているなど修復できない場合
+///
+/// # Details
+/// 途中で途切れたスキャン間のセグメントや末尾の不完全なマーカーを取り除いてから
+/// EOIを追加します。欠けた部分の画素は復元されません。
+pub fn repair_truncated(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let report = check_integrity(data)?;
+    if !report.has_sos {
+        return Err(Error::ParseError(
+            "Cannot repair JPEG truncated before image data".to_string(),
+        ));
+    }
+    if report.has_eoi {
+        return Ok(data.to_vec());
+    }
+
+    let (_, sos_pos) = parse_segments(data)?;
+    let sos_pos = sos_pos.ok_or_else(|| Error::ParseError("SOS marker not found".to_string()))?;
+    let walk = walk_scans(data, sos_pos);
+
+    // 途切れたセグメントは取り除く
+    let mut end = walk.incomplete_segment.unwrap_or(data.len());
+    if end == sos_pos {
+        return Err(Error::ParseError(
+            "Cannot repair JPEG truncated before image data".to_string(),
+        ));
+    }
+    // 末尾の不完全なマーカー
+    if data[end - 1] == 0xFF {
+        end -= 1;
+    }
+
+    let mut output = Vec::with_capacity(end + 2);
+    output.extend_from_slice(&data[..end]);
+    output.extend_from_slice(&[0xFF, 0xD9]);
+
+    Ok(output)
+}
+
+/// JPEG画像が仕様に準拠したセグメント構成か検査します
+///
+/// # Returns
+/// * `Ok(())` - 問題がない場合
+/// * `Err(Error::InvalidFormat)` - 問題の一覧（位置を含む）
+///
+/// # Details
+/// - 予約済み（JPGn、RESなど）のマーカー
+/// - 識別子が既知のもの（JFIF、Exif、XMP、ICC_PROFILE、MPF、JUMBF、Ducky、
+///   Photoshop、Adobe）と一致しないAPPセグメント
+/// - 先頭以外にあるJFIFセグメント、SOFより前のSOS
+/// - EOIがない、またはEOI以降のデータ
+pub fn check_conformance(data: &[u8]) -> Result<(), Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let (segments, sos_pos) = parse_segments(data)?;
+    let mut issues = Vec::new();
+    let mut seen_sof = false;
+
+    for (index, segment) in segments.iter().enumerate() {
+        let (marker, offset) = (segment.marker, segment.offset);
+        match marker {
+            0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => seen_sof = true,
+            0xE0..=0xEF => match classify_app_segment(marker, segment.payload()) {
+                AppKind::Unknown(_) => issues.push(format!(
+                    "APP{} segment with unknown signature at offset {offset}",
+                    marker - 0xE0
+                )),
+                AppKind::Jfif if index != 0 => {
+                    issues.push(format!("JFIF segment at offset {offset} is not first"));
+                }
+                _ => {}
+            },
+            0xC4 | 0xCC | 0xDB..=0xDF | MARKER_COM => {}
+            _ => issues.push(format!(
+                "non-standard marker 0x{marker:02X} at offset {offset}"
+            )),
+        }
+    }
+
+    match sos_pos {
+        Some(sos_pos) => {
+            if !seen_sof {
+                issues.push(format!("SOS at offset {sos_pos} precedes any SOF"));
+            }
+            let walk = walk_scans(data, sos_pos);
+            for &(marker, offset) in &walk.markers {
+                if !matches!(marker, 0xC4 | 0xCC | 0xDA..=0xDD | 0xE0..=0xEF | MARKER_COM) {
+                    issues.push(format!(
+                        "unexpected marker 0x{marker:02X} between scans at offset {offset}"
+                    ));
+                }
+            }
+            match walk.eoi_end {
+                Some(end) if end < data.len() => {
+                    issues.push(format!("{} bytes after EOI", data.len() - end));
+                }
+                Some(_) => {}
+                None => issues.push("EOI marker not found".to_string()),
+            }
+        }
+        None => issues.push("SOS marker not found".to_string()),
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidFormat(format!(
+            "Non-conforming JPEG: {}",
+            issues.join("; ")
+        )))
+    }
+}
+
+/// デコーダーを使わずにJPEG画像のセグメント構造を検証します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(StructureReport)` - 検証結果（問題があってもエラーにはなりません）
+/// * `Err(Error)` - JPEGではない場合
+///
+/// # Details
+/// マーカーとセグメントをEOIまで走査し、次の項目を検査します。
+/// - セグメントサイズ（データの終端を超えない、SOF・SOS・DRIの長さが内容と一致する）
+/// - SOFが1つだけあり、SOSより前にある
+/// - SOSより前の不正なマーカー（RST、SOI、EOI）
+/// - EOIの有無
+///
+/// 「デコードできるか」とは別の、依存の少ない軽量な健全性チェックです。
+pub fn validate_structure(data: &[u8]) -> Result<StructureReport, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let mut report = StructureReport::default();
+    let mut sof_count = 0;
+    let mut sos_pos = None;
+    let mut pos = 2;
+
+    while pos + 1 < data.len() {
+        if data[pos] != 0xFF {
+            report.push(pos, format!("expected a marker at offset {pos}"));
+            break;
+        }
+        let marker = data[pos + 1];
+        match marker {
+            // フィルバイト
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            0xDA => {
+                sos_pos = Some(pos);
+                break;
+            }
+            0x01 | 0xD0..=0xD9 => {
+                report.push(
+                    pos,
+                    format!("unexpected marker 0x{marker:02X} at offset {pos}"),
+                );
+                report.units += 1;
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some(payload) = read_segment_payload(data, pos, &mut report) else {
+            return Ok(report);
+        };
+        match marker {
+            0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                sof_count += 1;
+                if sof_count > 1 {
+                    report.push(pos, format!("additional SOF at offset {pos}"));
+                }
+                let components = payload.get(5).copied().unwrap_or(0) as usize;
+                if payload.len() < 6 || payload.len() != 6 + components * 3 {
+                    report.push(pos, format!("invalid SOF length at offset {pos}"));
+                } else if payload[3..5] == [0, 0] || components == 0 {
+                    report.push(
+                        pos,
+                        format!("SOF at offset {pos} has zero width or components"),
+                    );
+                }
+            }
+            0xDD if payload.len() != 2 => {
+                report.push(pos, format!("invalid DRI length at offset {pos}"));
+            }
+            _ => {}
+        }
+        report.units += 1;
+        pos += 4 + payload.len();
+    }
+
+    let Some(sos_pos) = sos_pos else {
+        if report.is_valid() {
+            report.push(pos, "SOS marker not found");
+        }
+        return Ok(report);
+    };
+    if sof_count == 0 {
+        report.push(sos_pos, format!("SOS at offset {sos_pos} precedes any SOF"));
+    }
+
+    let walk = walk_scans(data, sos_pos);
+    report.units += walk.markers.len();
+    for &(marker, offset) in &walk.markers {
+        if marker == 0xDA {
+            if let Some(payload) = data.get(offset + 4..offset + 5) {
+                let size = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+                if size != 6 + payload[0] as usize * 2 {
+                    report.push(offset, format!("invalid SOS length at offset {offset}"));
+                }
+            }
+        }
+    }
+    if let Some(offset) = walk.incomplete_segment {
+        report.push(offset, format!("segment at offset {offset} is truncated"));
+    }
+    match walk.eoi_end {
+        Some(end) => {
+            report.complete = true;
+            report.units += 1;
+            report.trailing_bytes = data.len() - end;
+        }
+        None => report.push(data.len(), "EOI marker not found"),
+    }
+
+    Ok(report)
+}
+
+/// 指定位置のセグメントの内容を返します
+///
+/// サイズが不正、またはデータの終端を超える場合は問題を記録して`None`を返します。
+fn read_segment_payload<'a>(
+    data: &'a [u8],
+    pos: usize,
+    report: &mut StructureReport,
+) -> Option<&'a [u8]> {
+    if pos + 4 > data.len() {
+        report.push(pos, format!("segment at offset {pos} is truncated"));
+        return None;
+    }
+    let size = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+    if size < 2 {
+        report.push(pos, format!("invalid segment size at offset {pos}"));
+        return None;
+    }
+    let Some(payload) = data.get(pos + 4..pos + 2 + size) else {
+        report.push(pos, format!("segment at offset {pos} is truncated"));
+        return None;
+    };
+    Some(payload)
+}
+
+/// JPEG画像からコメントを読み取ります
+///
+/// コメントはUTF-8として解釈され、不正なバイト列は置換文字に変換されます。
+/// 他のエンコーディングのコメントは[`read_comment_raw`]や
+/// [`read_comment_with_encoding`]を使用してください。
+pub fn read_comment(data: &[u8]) -> Result<Option<String>, Error> {
+    Ok(read_comment_raw(data)?.map(|bytes| String::from_utf8_lossy(&bytes).to_string()))
+}
+
+/// JPEG画像からコメントをバイト列のまま読み取ります
+///
+/// # Returns
+/// * `Ok(Some(Vec<u8>))` - 最初のCOMセグメントの内容（変換なし）
+/// * `Ok(None)` - コメントが存在しない
+/// * `Err(Error)` - エラー
+pub fn read_comment_raw(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let (segments, _) = parse_segments(data)?;
+
+    Ok(segments
+        .iter()
+        .find(|segment| segment.marker == MARKER_COM)
+        .map(|segment| segment.payload().to_vec()))
+}
+
+/// JPEG画像からコメントを指定したエンコーディングで読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `encoding` - コメントの文字エンコーディング
+///
+/// # Returns
+/// * `Ok(Some(String))` - デコードされたコメント
+/// * `Ok(None)` - コメントが存在しない
+/// * `Err(Error)` - エラー（指定したエンコーディングとして不正な場合を含む）
+pub fn read_comment_with_encoding(
+    data: &[u8],
+    encoding: CommentEncoding,
+) -> Result<Option<String>, Error> {
+    match read_comment_raw(data)? {
+        Some(bytes) => Ok(Some(encoding.decode(&bytes)?)),
+        None => Ok(None),
+    }
+}
+
+/// JPEG画像からコメントをUTF-8として読み取り、不正な場合は指定したポリシーで修復します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `policy` - UTF-8として不正な場合の扱い
+///
+/// # Returns
+/// * `Ok(Some(DecodedText))` - コメントと、適用した修復
+/// * `Ok(None)` - コメントが存在しない
+/// * `Err(Error)` - エラー（[`Utf8Policy::Reject`]でUTF-8として不正な場合を含む）
+///
+/// [`read_comment`]と異なり、修復が行われたかを[`DecodedText::repaired`]で確認できます。
+pub fn read_comment_with_policy(
+    data: &[u8],
+    policy: Utf8Policy,
+) -> Result<Option<DecodedText>, Error> {
+    read_comment_raw(data)?
+        .map(|bytes| policy.decode(&bytes, "Comment"))
+        .transpose()
+}
+
+/// JPEG画像のすべてのコメントを読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `options` - 文字エンコーディングと連結の指定
+///
+/// # Returns
+/// * `Ok(Vec<String>)` - COMセグメントの出現順のコメント（コメントがない場合は空）
+/// * `Err(Error)` - エラー（指定したエンコーディングとして不正な場合を含む）
+///
+/// # Details
+/// [`join_segments`](ReadCommentOptions::join_segments)を指定すると、間に他のセグメントを
+/// 挟まずに連続するCOMセグメントを1つのコメントとして返します。
+pub fn read_comments(data: &[u8], options: &ReadCommentOptions) -> Result<Vec<String>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let (segments, _) = parse_segments(data)?;
+
+    let mut comments: Vec<Vec<u8>> = Vec::new();
+    let mut previous_is_comment = false;
+    for segment in &segments {
+        let is_comment = segment.marker == MARKER_COM;
+        if is_comment {
+            match comments.last_mut() {
+                Some(last) if options.join_segments && previous_is_comment => {
+                    last.extend_from_slice(segment.payload());
+                }
+                _ => comments.push(segment.payload().to_vec()),
+            }
+        }
+        previous_is_comment = is_comment;
+    }
+
+    comments
+        .iter()
+        .map(|comment| options.encoding.decode(comment))
+        .collect()
+}
+
+/// Ducky（APP12）セグメントの内容
+///
+/// Photoshopの「Web用に保存」が書き込む画質・コメント・著作権表示です。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ducky {
+    /// 書き出し時の画質（0〜100）
+    pub quality: Option<u32>,
+    /// コメント
+    pub comment: Option<String>,
+    /// 著作権表示
+    pub copyright: Option<String>,
+}
+
+/// JPEG画像からDucky（APP12）セグメントを読み取ります
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some(Ducky))` - 最初のDuckyセグメントの内容
+/// * `Ok(None)` - Duckyセグメントが存在しない
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// Duckyはタグ（2バイト）+ 長さ（2バイト）+ 値の繰り返しで、タグ0で終わります。
+/// 画質（タグ1）は4バイトの整数、コメント（タグ2）と著作権表示（タグ3）は
+/// 文字数（4バイト）+ UTF-16BEの文字列です。途中で壊れている場合はそれまでの値を返します。
+pub fn read_ducky(data: &[u8]) -> Result<Option<Ducky>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let (segments, _) = parse_segments(data)?;
+    Ok(segments
+        .iter()
+        .find(|segment| segment.app_kind() == Some(AppKind::Ducky))
+        .map(|segment| {
+            let mut ducky = Ducky::default();
+            for (tag, value) in ducky_blocks(&segment.payload()[DUCKY_SIGNATURE.len()..]) {
+                match tag {
+                    DUCKY_QUALITY if value.len() == 4 => {
+                        ducky.quality = Some(u32::from_be_bytes(value.try_into().unwrap()));
+                    }
+                    DUCKY_COMMENT => ducky.comment = decode_ducky_text(value),
+                    DUCKY_COPYRIGHT => ducky.copyright = decode_ducky_text(value),
+                    _ => {}
+                }
+            }
+            ducky
+        }))
+}
+
+/// Duckyセグメントからコメントと著作権表示を削除します
+///
+/// 画質などの他の値は保持し、値が残らないセグメントは削除します。
+pub(crate) fn remove_ducky_text(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let (segments, _) = parse_segments(data)?;
+    let mut output = Vec::with_capacity(data.len());
+    let mut copied = 0;
+    for segment in &segments {
+        if segment.app_kind() != Some(AppKind::Ducky) {
+            continue;
+        }
+        let blocks = ducky_blocks(&segment.payload()[DUCKY_SIGNATURE.len()..]);
+        if !blocks
+            .iter()
+            .any(|(tag, _)| matches!(*tag, DUCKY_COMMENT | DUCKY_COPYRIGHT))
+        {
+            continue;
+        }
+
+        output.extend_from_slice(&data[copied..segment.offset]);
+        copied = segment.offset + segment.bytes.len();
+        let mut payload = DUCKY_SIGNATURE.to_vec();
+        for (tag, value) in blocks {
+            if !matches!(tag, DUCKY_COMMENT | DUCKY_COPYRIGHT) {
+                payload.extend_from_slice(&tag.to_be_bytes());
+                payload.extend_from_slice(&(value.len() as u16).to_be_bytes());
+                payload.extend_from_slice(value);
+            }
+        }
+        if payload.len() > DUCKY_SIGNATURE.len() {
+            payload.extend_from_slice(&[0, 0]);
+            write_segment(&mut output, MARKER_APP12, &payload);
+        }
+    }
+    output.extend_from_slice(&data[copied..]);
+    Ok(output)
+}
+
+/// Duckyのタグと値を列挙します（タグ0または壊れた位置で終了）
+fn ducky_blocks(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut blocks = Vec::new();
+    while data.len() >= 4 {
+        let tag = u16::from_be_bytes([data[0], data[1]]);
+        let length = u16::from_be_bytes([data[2], data[3]]) as usize;
+        if tag == 0 {
+            break;
+        }
+        let Some(value) = data.get(4..4 + length) else {
+            break;
+        };
+        blocks.push((tag, value));
+        data = &data[4 + length..];
+    }
+    blocks
+}
+
+/// Duckyの文字列（文字数 + UTF-16BE）を読み取ります（空の場合は`None`）
+fn decode_ducky_text(value: &[u8]) -> Option<String> {
+    let units: Vec<u16> = value
+        .get(4..)?
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect();
+    let text = String::from_utf16_lossy(&units);
+    let text = text.trim_end_matches('\0');
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// JPEG画像のEXIFからオリエンテーションを読み取ります
+///
+/// # Returns
+/// * `Ok(Some(Orientation))` - オリエンテーション
+/// * `Ok(None)` - EXIFまたはオリエンテーションが存在しない、もしくは値が範囲外
+/// * `Err(Error)` - エラー
+pub fn read_orientation(data: &[u8]) -> Result<Option<Orientation>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    let (segments, _) = parse_segments(data)?;
+
+    Ok(segments
+        .iter()
+        .filter(|segment| segment.marker == MARKER_APP1)
+        .map(|segment| segment.payload())
+        .find(|payload| payload.len() > 6 && &payload[0..4] == b"Exif")
+        .and_then(|payload| extract_orientation_from_exif(&payload[6..]))
+        .and_then(Orientation::from_exif))
+}
+
+/// JPEG画像のEXIFにオリエンテーションを書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `orientation` - 書き込むオリエンテーション
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - オリエンテーションを書き込んだJPEG画像データ
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// - 既存のオリエンテーションは範囲外の値も含めて上書きされます
+/// - EXIFにオリエンテーションがない場合はタグを追加してEXIFを組み立て直します
+/// - EXIFがない場合はオリエンテーションのみの最小限のEXIFを追加します
+pub fn set_orientation(data: &[u8], orientation: Orientation) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let output = rewrite_exif_segment(data, |tiff| match tiff {
+        Some(tiff) => exif::set_orientation(tiff, orientation.to_exif())
+            .ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string())),
+        None => {
+            let mut builder = TiffBuilder::default();
+            builder
+                .ifd0
+                .push(Field::short(exif::TAG_ORIENTATION, orientation.to_exif()));
+            Ok(builder.build(ByteOrder::LittleEndian))
+        }
+    })?;
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// JPEG画像のEXIFにGPSの位置情報を書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `lat` - 緯度（度、南緯は負）
+/// * `lon` - 経度（度、西経は負）
+/// * `alt` - 高度（メートル、海面下は負）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 位置情報を書き込んだJPEG画像データ
+/// * `Err(Error)` - 緯度・経度が範囲外の場合など
+///
+/// # Details
+/// - GPS IFDにバージョン（2.3.0.0）、緯度・経度・高度とその参照（N/S、E/W、海抜の上下）を書き込みます
+/// - 既存の緯度・経度・高度は置き換え、速度・撮影方向や日時などその他のGPSタグは残します
+/// - EXIFがない場合はGPS IFDのみの最小限のEXIFを追加します
+/// - 速度や撮影方向も書き込む場合は[`set_gps_coordinates`]を使用してください
+pub fn set_gps(data: &[u8], lat: f64, lon: f64, alt: Option<f64>) -> Result<Vec<u8>, Error> {
+    set_gps_coordinates(data, &exif::GpsCoordinates::new(lat, lon).with_alt(alt))
+}
+
+/// JPEG画像のEXIFに高度・速度・撮影方向を含むGPSの位置情報を書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `gps` - 書き込む位置情報（`None`の項目は書き込みません）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 位置情報を書き込んだJPEG画像データ
+/// * `Err(Error)` - 緯度・経度が範囲外の場合、速度が負の場合、撮影方向が0以上360未満でない場合など
+///
+/// # Details
+/// - [`set_gps`]の項目に加え、速度（GPSSpeedRef=K、km/h）と撮影方向（GPSImgDirectionRef=T、真北基準）を書き込みます
+/// - 高度・速度・撮影方向はそれぞれ1/1000単位の有理数で記録します
+/// - 既存の位置情報は置き換えます。高度が`None`の場合は既存の高度を削除し、
+///   速度・撮影方向が`None`の場合は既存の値を残します。日時などその他のGPSタグは残します
+/// - EXIFがない場合はGPS IFDのみの最小限のEXIFを追加します
+pub fn set_gps_coordinates(data: &[u8], gps: &exif::GpsCoordinates) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let output = rewrite_exif_segment(data, |tiff| match tiff {
+        Some(tiff) => exif::set_gps(tiff, gps),
+        None => {
+            let builder = TiffBuilder {
+                gps: exif::gps_fields(gps)?,
+                ..Default::default()
+            };
+            Ok(builder.build(ByteOrder::LittleEndian))
+        }
+    })?;
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// JPEG画像のEXIFに文字列タグを書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `ifd` - タグが属するIFD（ImageDescriptionやArtistは[`IfdKind::Ifd0`](exif::IfdKind::Ifd0)）
+/// * `tag` - タグ番号
+/// * `value` - 書き込む文字列（空文字列の場合はタグを削除）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 文字列を書き込んだJPEG画像データ
+/// * `Err(Error)` - IFDへのポインタなど文字列を格納できないタグの場合、NUL文字を含む場合など
+///
+/// # Details
+/// - ASCIIのみの文字列はASCII型、それ以外はEXIF 3.0のUTF-8型（129）で記録します
+/// - EXIFがない場合は最小限のEXIFを追加します
+pub fn set_exif_text(
+    data: &[u8],
+    ifd: exif::IfdKind,
+    tag: u16,
+    value: &str,
+) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let output = rewrite_exif_segment(data, |tiff| match tiff {
+        Some(tiff) => exif::set_text(tiff, ifd, tag, value),
+        None => {
+            let mut builder = TiffBuilder::default();
+            exif::apply_text(&mut builder, ifd, tag, value)?;
+            Ok(builder.build(ByteOrder::LittleEndian))
+        }
+    })?;
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// JPEG画像のEXIFにWindowsのXP系のタグ（XPTitleなど）を書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `tag` - 書き込むタグ
+/// * `value` - 書き込む文字列（空文字列の場合はタグを削除）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 文字列を書き込んだJPEG画像データ
+/// * `Err(Error)` - JPEGではない場合、NUL文字を含む場合など
+///
+/// # Details
+/// - IFD0にBYTE型の配列として、終端のNULを含むUCS-2（UTF-16リトルエンディアン）で記録します
+/// - EXIFがない場合は最小限のEXIFを追加します
+pub fn set_xp_text(data: &[u8], tag: exif::XpTag, value: &str) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let output = rewrite_exif_segment(data, |tiff| {
+        let (byte_order, mut builder) = match tiff {
+            Some(tiff) => exif::to_builder(tiff)
+                .ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string()))?,
+            None => (ByteOrder::LittleEndian, TiffBuilder::default()),
+        };
+        exif::apply_xp_text(&mut builder, tag, value)?;
+        Ok(builder.build(byte_order))
+    })?;
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// JPEG画像のEXIFのサムネイル（IFD1）を差し替えます
+///
+/// 画素を編集した後など、埋め込まれたサムネイルが本体と食い違う場合に、
+/// 呼び出し側で作成したサムネイルに置き換えるために使用します。
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `thumbnail` - 新しいサムネイル（JPEG画像のバイトデータ）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - サムネイルを差し替えたJPEG画像データ
+/// * `Err(Error)` - サムネイルがデコードできないJPEGの場合や、本体より大きい場合など
+///
+/// # Details
+/// - サムネイルの寸法が本体の寸法を超える場合は`Error::InvalidFormat`を返します
+/// - EXIFセグメントが64KBを超える場合は`Error::PayloadTooLarge`を返します
+/// - 既存のサムネイルは破棄され、IFD1の圧縮方式はJPEG（6）に設定されます
+/// - EXIFがない場合は最小限のEXIFを追加します
+pub fn set_thumbnail(data: &[u8], thumbnail: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
+    if thumbnail.len() < 4 || thumbnail[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat(
+            "Thumbnail is not a valid JPEG file".to_string(),
+        ));
+    }
+
+    // 本体とサムネイルが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+    validate_jpeg_decode(thumbnail, None)?;
+
+    let info = read_info(data)?;
+    let thumbnail_info = read_info(thumbnail)?;
+    if thumbnail_info.width > info.width || thumbnail_info.height > info.height {
+        return Err(Error::InvalidFormat(format!(
+            "Thumbnail ({}x{}) is larger than the image ({}x{})",
+            thumbnail_info.width, thumbnail_info.height, info.width, info.height
+        )));
+    }
+
+    let output = rewrite_exif_segment(data, |tiff| {
+        let (byte_order, mut builder) = match tiff {
+            Some(tiff) => exif::to_builder(tiff)
+                .ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string()))?,
+            None => (ByteOrder::LittleEndian, TiffBuilder::default()),
+        };
+        exif::apply_thumbnail(&mut builder, thumbnail);
+        Ok(builder.build(byte_order))
+    })?;
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
 
-            if !inserted {
-                // JFIFマーカーがない場合はSOIの直後に挿入
-                let mut temp = vec![0xFF, 0xD8];
-                temp.extend_from_slice(&exif_data);
-                temp.extend_from_slice(&output[2..]);
-                return Ok(temp);
-            }
+    Ok(output)
+}
 
-            return Ok(final_output);
-        }
+/// JPEG画像のEXIFに日時と時差（OffsetTime系のタグ）を書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `date_times` - 書き込む日時（`None`の項目は変更しません）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 日時を書き込んだJPEG画像データ
+/// * `Err(Error)` - 日時が`YYYY:MM:DD HH:MM:SS`、時差が`+HH:MM`形式ではない場合など
+///
+/// # Details
+/// - 変更日時はIFD0のDateTime（0x0132）、撮影日時・デジタル化日時はExif IFDに書き込みます
+/// - 時差はOffsetTime（0x9010）、OffsetTimeOriginal（0x9011）、OffsetTimeDigitized（0x9012）に書き込みます
+/// - 日時を指定して時差を`None`にした場合、既存の時差は削除されます
+/// - EXIFがない場合は最小限のEXIFを追加します
+pub fn set_date_times(data: &[u8], date_times: &exif::DateTimes) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
     }
 
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let output = rewrite_exif_segment(data, |tiff| match tiff {
+        Some(tiff) => exif::set_date_times(tiff, date_times),
+        None => {
+            let mut builder = TiffBuilder::default();
+            exif::apply_date_times(&mut builder, date_times)?;
+            Ok(builder.build(ByteOrder::LittleEndian))
+        }
+    })?;
+
     // 出力が有効なJPEGか検証
-    validate_jpeg_decode(&output)?;
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// EXIFのAPP1セグメントを書き換えます
+///
+/// `rewrite`には既存のEXIFのTIFFデータ（ない場合は`None`）が渡され、新しいTIFFデータを返します。
+/// EXIFがない場合はJFIFマーカーの直後（JFIFがない場合はSOIの直後）に挿入します。
+pub(crate) fn rewrite_exif_segment(
+    data: &[u8],
+    rewrite: impl FnOnce(Option<&[u8]>) -> Result<Vec<u8>, Error>,
+) -> Result<Vec<u8>, Error> {
+    let (segments, _) = parse_segments(data)?;
+    let exif = segments.iter().find(|segment| {
+        segment.marker == MARKER_APP1 && segment.payload().starts_with(b"Exif\0\0")
+    });
+
+    let (start, end) = match exif {
+        Some(segment) => (segment.offset, segment.offset + segment.bytes.len()),
+        None => {
+            let position = segments
+                .first()
+                .filter(|segment| segment.marker == 0xE0)
+                .map_or(2, |segment| segment.offset + segment.bytes.len());
+            (position, position)
+        }
+    };
+    let tiff = rewrite(exif.map(|segment| &segment.payload()[6..]))?;
 
+    let mut output = Vec::with_capacity(data.len() + 32);
+    output.extend_from_slice(&data[..start]);
+    output.extend_from_slice(&build_exif_segment(&tiff)?);
+    output.extend_from_slice(&data[end..]);
     Ok(output)
 }
 
-/// 最小限のEXIFデータを作成（オリエンテーションのみ）
-fn create_minimal_exif(orientation: u16) -> Result<Vec<u8>, Error> {
-    let mut exif = Vec::new();
+/// JPEG画像のEXIFから緯度と経度を読み取ります
+///
+/// # Returns
+/// * `Some((f64, f64))` - 度単位の（緯度, 経度）。南緯・西経は負の値
+/// * `None` - EXIFまたは緯度・経度が存在しない、値が不正、もしくはJPEGとして読めない
+///
+/// # Details
+/// 位置情報だけを手軽に取り出すためのもので、構造のエラーは区別しません。
+/// 高度も必要な場合やエラーを判別したい場合は[`exif::read_gps_coordinates`]を使用してください。
+pub fn read_gps(data: &[u8]) -> Option<(f64, f64)> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return None;
+    }
 
-    // APP1マーカー
-    exif.extend_from_slice(&[0xFF, MARKER_APP1]);
+    let (segments, _) = parse_segments(data).ok()?;
 
-    // サイズは後で設定
-    exif.extend_from_slice(&[0x00, 0x00]);
+    segments
+        .iter()
+        .filter(|segment| segment.marker == MARKER_APP1)
+        .map(|segment| segment.payload())
+        .find(|payload| payload.len() > 6 && payload.starts_with(b"Exif\0\0"))
+        .and_then(|payload| exif::gps_coordinates(&payload[6..]))
+        .map(|gps| (gps.lat, gps.lon))
+}
 
-    // Exif識別子
-    exif.extend_from_slice(b"Exif\0\0");
+/// JPEG画像からXMPパケットを読み取ります
+///
+/// # Returns
+/// * `Ok(Some(String))` - 標準XMPのAPP1セグメントの内容（不正なUTF-8は置換文字に変換）
+/// * `Ok(None)` - XMPが存在しない
+/// * `Err(Error)` - エラー
+///
+/// 拡張XMP（`http://ns.adobe.com/xmp/extension/`）の内容は含みません。
+/// 拡張XMPは[`read_extended_xmp`]で読み取れます。
+pub fn read_xmp(data: &[u8]) -> Result<Option<String>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
+    }
 
-    // TIFF header (Little Endian)
-    exif.extend_from_slice(&[0x49, 0x49]); // "II"
-    exif.extend_from_slice(&[0x2A, 0x00]); // 42
-    exif.extend_from_slice(&[0x08, 0x00, 0x00, 0x00]); // IFD0 offset
+    let (segments, _) = parse_segments(data)?;
+    Ok(segments
+        .iter()
+        .find(|segment| {
+            segment.marker == MARKER_APP1 && segment.payload().starts_with(XMP_SIGNATURE)
+        })
+        .map(|segment| {
+            String::from_utf8_lossy(&segment.payload()[XMP_SIGNATURE.len()..]).to_string()
+        }))
+}
 
-    // IFD0
-    exif.extend_from_slice(&[0x01, 0x00]); // 1 entry
+/// JPEG画像から拡張XMPを読み取ります
+///
+/// # Returns
+/// * `Ok(Some(String))` - 標準XMPの`xmpNote:HasExtendedXMP`と同じGUIDの拡張XMPセグメントを
+///   オフセット順に連結したXMPパケット
+/// * `Ok(None)` - 標準XMPがGUIDを持たない、または対応する拡張XMPセグメントがない
+/// * `Err(Error)` - セグメントが欠けているなど、拡張XMPを復元できない場合
+pub fn read_extended_xmp(data: &[u8]) -> Result<Option<String>, Error> {
+    let Some(guid) = read_xmp(data)?
+        .and_then(|packet| xmp::read_property(&packet, xmp::NS_XMP_NOTE.0, "HasExtendedXMP"))
+    else {
+        return Ok(None);
+    };
 
-    // Orientation tag
-    exif.extend_from_slice(&[0x12, 0x01]); // Tag 0x0112
-    exif.extend_from_slice(&[0x03, 0x00]); // Type: SHORT
-    exif.extend_from_slice(&[0x01, 0x00, 0x00, 0x00]); // Count: 1
-    exif.extend_from_slice(&[orientation as u8, (orientation >> 8) as u8, 0x00, 0x00]); // Value
+    // 識別子 + GUID(32) + 全体の長さ(4) + オフセット(4) + データ
+    let header = EXTENDED_XMP_SIGNATURE.len() + 40;
+    let (segments, _) = parse_segments(data)?;
+    let mut chunks = Vec::new();
+    for segment in &segments {
+        let payload = segment.payload();
+        if segment.marker != MARKER_APP1
+            || payload.len() < header
+            || !payload.starts_with(EXTENDED_XMP_SIGNATURE)
+            || &payload[EXTENDED_XMP_SIGNATURE.len()..][..32] != guid.as_bytes()
+        {
+            continue;
+        }
+        let fields = &payload[EXTENDED_XMP_SIGNATURE.len() + 32..header];
+        let total = u32::from_be_bytes(fields[0..4].try_into().unwrap()) as usize;
+        let offset = u32::from_be_bytes(fields[4..8].try_into().unwrap()) as usize;
+        chunks.push((total, offset, &payload[header..]));
+    }
+    let Some(&(total, _, _)) = chunks.first() else {
+        return Ok(None);
+    };
 
-    // Next IFD offset (none)
-    exif.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    let mut size = 0usize;
+    for &(chunk_total, offset, chunk) in &chunks {
+        if chunk_total != total
+            || offset
+                .checked_add(chunk.len())
+                .is_none_or(|end| end > total)
+        {
+            return Err(Error::ParseError(
+                "Extended XMP segments are inconsistent".to_string(),
+            ));
+        }
+        size += chunk.len();
+    }
+    // 確保する前に、宣言された全体の長さをセグメントのデータで満たせるか確認する
+    if size < total {
+        return Err(Error::ParseError("Extended XMP is incomplete".to_string()));
+    }
 
-    // サイズを設定
-    let size = (exif.len() - 2) as u16;
-    exif[2] = (size >> 8) as u8;
-    exif[3] = size as u8;
+    // 重複や重なりのあるセグメントで隙間が残らないよう、埋まった範囲を追跡する
+    let mut ranges: Vec<Range<usize>> = chunks
+        .iter()
+        .map(|&(_, offset, chunk)| offset..offset + chunk.len())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+    let mut covered = 0;
+    for range in &ranges {
+        if range.start > covered {
+            break;
+        }
+        covered = covered.max(range.end);
+    }
+    if covered != total {
+        return Err(Error::ParseError("Extended XMP is incomplete".to_string()));
+    }
 
-    Ok(exif)
+    let mut buffer = vec![0; total];
+    for &(_, offset, chunk) in &chunks {
+        buffer[offset..offset + chunk.len()].copy_from_slice(chunk);
+    }
+    Ok(Some(String::from_utf8_lossy(&buffer).to_string()))
 }
 
-/// JPEG画像からコメントを読み取ります
-pub fn read_comment(data: &[u8]) -> Result<Option<String>, Error> {
+/// JPEG画像のXMPパケットを置き換えます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `packet` - 新しいXMPパケット（空文字列の場合はXMPを削除）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - XMPを置き換えたJPEG画像データ
+/// * `Err(Error::PayloadTooLarge)` - パケットが拡張XMPの上限（4GB）を超える場合
+/// * `Err(Error)` - その他のエラー
+///
+/// # Details
+/// - 既存の標準XMPセグメントの位置に書き込みます
+/// - XMPがない場合はEXIF（EXIFがない場合はJFIF、どちらもない場合はSOI）の直後に挿入します
+/// - 1つのAPP1セグメントに収まる場合、拡張XMPのセグメントは変更しません
+/// - 収まらない場合はXMP仕様のPart 3に従い、標準XMPに収まるまで大きいプロパティから順に
+///   拡張XMPへ移し、標準XMPに`xmpNote:HasExtendedXMP`（拡張XMPのMD5）を書き込みます。
+///   プロパティを解釈できない場合はパケット全体を拡張XMPにします。既存の拡張XMPセグメントは削除します
+pub fn write_xmp(data: &[u8], packet: &str) -> Result<Vec<u8>, Error> {
     if data.len() < 4 || data[0..2] != JPEG_SOI {
         return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
     }
 
     // JPEGが正常にデコードできるか検証
-    validate_jpeg_decode(data)?;
+    validate_jpeg_decode(data, None)?;
 
-    let mut pos = 2;
+    let output = replace_xmp_segment(data, packet)?;
 
-    while pos < data.len() - 1 {
-        if data[pos] != 0xFF {
-            return Err(Error::ParseError("Invalid JPEG marker".to_string()));
-        }
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
 
-        let marker = data[pos + 1];
-        pos += 2;
+    Ok(output)
+}
 
-        // SOSマーカー以降は画像データ
-        if marker == 0xDA {
-            break;
-        }
+/// 標準XMPのセグメントを置き換えます（空のパケットの場合は削除）
+pub(crate) fn replace_xmp_segment(data: &[u8], packet: &str) -> Result<Vec<u8>, Error> {
+    let xmp_segments = build_xmp_segments(packet)?;
 
-        // スタンドアロンマーカーの場合
-        if (0xD0..=0xD9).contains(&marker) {
-            continue;
-        }
+    // 拡張XMPとして分割する場合は既存の拡張XMPを削除
+    let without_extended;
+    let data = if XMP_SIGNATURE.len() + packet.len() > 0xFFFF - 2 {
+        without_extended = remove_extended_xmp(data)?;
+        &without_extended
+    } else {
+        data
+    };
 
-        // セグメントサイズを読み取る
-        if pos + 2 > data.len() {
-            return Err(Error::ParseError("Unexpected end of JPEG data".to_string()));
+    let (segments, _) = parse_segments(data)?;
+    let is_xmp = |segment: &Segment| {
+        segment.marker == MARKER_APP1 && segment.payload().starts_with(XMP_SIGNATURE)
+    };
+    let (start, end) = match segments.iter().find(|segment| is_xmp(segment)) {
+        Some(segment) => (segment.offset, segment.offset + segment.bytes.len()),
+        None => {
+            let anchor = segments
+                .iter()
+                .find(|segment| {
+                    segment.marker == MARKER_APP1 && segment.payload().starts_with(b"Exif\0\0")
+                })
+                .or_else(|| segments.first().filter(|segment| segment.marker == 0xE0));
+            let position = anchor.map_or(2, |segment| segment.offset + segment.bytes.len());
+            (position, position)
         }
+    };
 
-        let segment_size = ((data[pos] as u16) << 8) | (data[pos + 1] as u16);
-        if segment_size < 2 {
-            return Err(Error::ParseError("Invalid segment size".to_string()));
-        }
+    let mut output = Vec::with_capacity(data.len() + xmp_segments.len());
+    output.extend_from_slice(&data[..start]);
+    output.extend_from_slice(&xmp_segments);
+    output.extend_from_slice(&data[end..]);
+    Ok(output)
+}
 
-        let segment_end = pos + segment_size as usize;
-        if segment_end > data.len() {
-            return Err(Error::ParseError("Segment extends beyond file".to_string()));
-        }
+/// XMPパケットのAPP1セグメント列を作成します（空のパケットの場合は空）
+///
+/// 1つのセグメントに収まらない場合はXMP仕様のPart 3に従い、大きいプロパティから順に
+/// 拡張XMPに移して、残りと`xmpNote:HasExtendedXMP`を持つ標準XMPを先頭に置きます。
+/// プロパティを解釈できない場合は、パケット全体を拡張XMPにします。
+fn build_xmp_segments(packet: &str) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    if packet.is_empty() {
+        return Ok(output);
+    }
+    if XMP_SIGNATURE.len() + packet.len() <= 0xFFFF - 2 {
+        write_app1(&mut output, &[XMP_SIGNATURE, packet.as_bytes()]);
+        return Ok(output);
+    }
 
-        // コメントマーカーの場合
-        if marker == MARKER_COM {
-            if segment_size > 2 {
-                let comment_data = &data[pos + 2..segment_end];
-                let comment = String::from_utf8_lossy(comment_data).to_string();
-                return Ok(Some(comment));
-            } else {
-                // 空のコメント（セグメントサイズが2の場合）
-                return Ok(Some(String::new()));
-            }
-        }
+    // 拡張XMP自体はHasExtendedXMPを持たない
+    let packet = match xmp::read_property(packet, xmp::NS_XMP_NOTE.0, "HasExtendedXMP") {
+        Some(_) => Cow::Owned(xmp::set_property(
+            packet,
+            xmp::NS_XMP_NOTE,
+            "HasExtendedXMP",
+            None,
+        )?),
+        None => Cow::Borrowed(packet),
+    };
+
+    // 大きいプロパティから拡張XMPに移し、収まらない場合はパケット全体を拡張XMPにする
+    let guid_placeholder = "0".repeat(32);
+    let fits = |standard: &str| {
+        xmp::set_property(
+            standard,
+            xmp::NS_XMP_NOTE,
+            "HasExtendedXMP",
+            Some(&guid_placeholder),
+        )
+        .is_ok_and(|standard| XMP_SIGNATURE.len() + standard.len() <= 0xFFFF - 2)
+    };
+    let (standard, extended) = match xmp::split_extended(&packet, fits) {
+        Some((standard, extended)) => (Cow::Owned(standard), Cow::Owned(extended)),
+        None => (Cow::Borrowed(""), packet),
+    };
+    let extended = extended.as_bytes();
+    let total = u32::try_from(extended.len()).map_err(|_| Error::PayloadTooLarge {
+        limit: u32::MAX as usize,
+        actual: extended.len(),
+        splittable: false,
+    })?;
+
+    // GUIDは拡張XMPのMD5（大文字の16進数）
+    let guid: String = Md5::digest(extended)
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect();
+    let standard = xmp::set_property(&standard, xmp::NS_XMP_NOTE, "HasExtendedXMP", Some(&guid))?;
+    write_app1(&mut output, &[XMP_SIGNATURE, standard.as_bytes()]);
+
+    for (index, chunk) in extended.chunks(MAX_EXTENDED_XMP_CHUNK).enumerate() {
+        let offset = (index * MAX_EXTENDED_XMP_CHUNK) as u32;
+        write_app1(
+            &mut output,
+            &[
+                EXTENDED_XMP_SIGNATURE,
+                guid.as_bytes(),
+                &total.to_be_bytes(),
+                &offset.to_be_bytes(),
+                chunk,
+            ],
+        );
+    }
+    Ok(output)
+}
 
-        pos = segment_end;
+/// 複数の部分からなるペイロードのAPP1セグメントを書き込みます
+fn write_app1(output: &mut Vec<u8>, parts: &[&[u8]]) {
+    let length: usize = parts.iter().map(|part| part.len()).sum();
+    output.extend_from_slice(&[0xFF, MARKER_APP1]);
+    output.extend_from_slice(&((length + 2) as u16).to_be_bytes());
+    for part in parts {
+        output.extend_from_slice(part);
     }
+}
 
-    Ok(None)
+/// 拡張XMPのセグメントを削除します
+fn remove_extended_xmp(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let (segments, _) = parse_segments(data)?;
+    let mut output = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    for segment in segments
+        .iter()
+        .filter(|segment| segment.app_kind() == Some(AppKind::ExtendedXmp))
+    {
+        output.extend_from_slice(&data[pos..segment.offset]);
+        pos = segment.offset + segment.bytes.len();
+    }
+    output.extend_from_slice(&data[pos..]);
+    Ok(output)
 }
 
 /// EXIFデータからオリエンテーション値を抽出する簡易実装
-fn extract_orientation_from_exif(exif_data: &[u8]) -> Option<u16> {
+pub(crate) fn extract_orientation_from_exif(exif_data: &[u8]) -> Option<u16> {
     // 最小限のEXIF解析
     if exif_data.len() < 8 {
         return None;
@@ -335,11 +3144,27 @@ fn extract_orientation_from_exif(exif_data: &[u8]) -> Option<u16> {
 }
 
 /// JPEGデータが正常にデコードできるか検証
-fn validate_jpeg_decode(data: &[u8]) -> Result<(), Error> {
-    let mut decoder = Decoder::new(data);
+pub(crate) fn validate_jpeg_decode(
+    data: &[u8],
+    cancellation: Option<&CancellationToken>,
+) -> Result<(), Error> {
+    validate_jpeg_decode_with_progress(data, cancellation, &mut |_| {})
+}
+
+/// JPEGデータが正常にデコードできるか検証し、読み取った位置を`progress`に通知します
+fn validate_jpeg_decode_with_progress(
+    data: &[u8],
+    cancellation: Option<&CancellationToken>,
+    progress: &mut dyn FnMut(usize),
+) -> Result<(), Error> {
+    cancel::check(cancellation)?;
+    let mut decoder =
+        Decoder::new(CancellableReader::new(data, cancellation).with_progress(progress));
 
     // ヘッダーを読み込んでデコード可能か確認
-    match decoder.read_info() {
+    let result = decoder.read_info();
+    cancel::check(cancellation)?;
+    match result {
         Ok(_) => {
             // 基本情報の取得を試みる
             let info = decoder.info();
@@ -359,6 +3184,16 @@ fn validate_jpeg_decode(data: &[u8]) -> Result<(), Error> {
     }
 }
 
+/// 画像をデコードし、中断によって失敗した場合は[`Error::Cancelled`]を返します
+fn decode_cancellable(
+    decoder: &mut Decoder<CancellableReader<'_>>,
+    cancellation: Option<&CancellationToken>,
+) -> Result<Vec<u8>, Error> {
+    let result = decoder.decode();
+    cancel::check(cancellation)?;
+    Ok(result?)
+}
+
 /// コメント追加によるファイルサイズの増加量を見積もります
 ///
 /// # Arguments
@@ -378,93 +3213,459 @@ pub fn estimate_text_comment(comment: &str) -> usize {
     2 + 2 + comment_bytes.len()
 }
 
+/// ICCプロファイルの埋め込みによるファイルサイズの増加量を見積もります
+///
+/// # Arguments
+/// * `profile_len` - ICCプロファイルのバイト数
+///
+/// # Returns
+/// * `Some(usize)` - 追加されるバイト数（全APP2セグメントの合計）
+/// * `None` - 255セグメントを超え、JPEGに埋め込めない場合
+///
+/// # Details
+/// 1つのAPP2セグメントに収まらないプロファイルは複数のセグメントに分割されます。
+/// 各セグメントの構造:
+/// - マーカー (0xFF 0xE2): 2バイト
+/// - セグメントサイズ: 2バイト
+/// - 識別子 ("ICC_PROFILE\0"): 12バイト
+/// - シーケンス番号と総数: 2バイト
+/// - プロファイルデータ: 最大65519バイト
+///
+/// セグメント数は255が上限のため、約16MBを超えるプロファイルはJPEGに埋め込めません。
+pub fn estimate_icc_profile(profile_len: usize) -> Option<usize> {
+    let segments = profile_len.div_ceil(MAX_ICC_CHUNK);
+    if segments > 255 {
+        return None;
+    }
+    // マーカー(2) + サイズフィールド(2) + 識別子(12) + シーケンス番号と総数(2)
+    Some(profile_len + segments * 18)
+}
+
 /// JPEG画像にコメントを書き込みます
+///
+/// 1つのCOMセグメントに収まらないコメントは、文字の境界で連続したCOMセグメントに分割します
+/// （[`CommentOptions::split_long`]を参照）。
 pub fn write_comment(data: &[u8], comment: &str) -> Result<Vec<u8>, Error> {
+    let options = CommentOptions {
+        split_long: true,
+        ..Default::default()
+    };
+    write_comment_with_options(data, comment, &options)
+}
+
+/// JPEG画像にバイト列のコメントを書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `comment` - コメントのバイト列（変換せずにそのまま書き込まれます）
+/// * `encoding` - コメントの文字エンコーディング
+///
+/// # Details
+/// - `comment`が`encoding`として正しくデコードできない場合はエラー
+/// - 65533バイトを超える場合は[`Error::PayloadTooLarge`]（分割して書き込むには
+///   [`write_comment_with_options`]の[`split_long`](CommentOptions::split_long)を使用します）
+/// - 既存のコメントは置換されます
+pub fn write_comment_raw(
+    data: &[u8],
+    comment: &[u8],
+    encoding: CommentEncoding,
+) -> Result<Vec<u8>, Error> {
     if data.len() < 4 || data[0..2] != JPEG_SOI {
         return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
     }
 
     // JPEGが正常にデコードできるか検証
-    validate_jpeg_decode(data)?;
+    validate_jpeg_decode(data, None)?;
+
+    if comment.len() > MAX_COMMENT_CHUNK {
+        return Err(Error::PayloadTooLarge {
+            limit: MAX_COMMENT_CHUNK,
+            actual: comment.len(),
+            splittable: true,
+        });
+    }
 
-    let comment_bytes = comment.as_bytes();
-    if comment_bytes.len() > 65533 {
-        return Err(Error::InvalidFormat("Comment too long".to_string()));
+    // 指定されたエンコーディングとして妥当か確認
+    encoding.decode(comment)?;
+
+    insert_comment_segment(data, &[comment.to_vec()], CommentPlacement::default())
+}
+
+/// オプションを指定してJPEG画像にコメントを書き込みます
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+/// * `comment` - コメント文字列
+/// * `options` - 挿入位置や文字エンコーディングの指定
+///
+/// # Details
+/// - コメントは`options.encoding`でエンコードされて書き込まれます
+/// - 既存のコメントは削除されてから挿入されるため、同じ内容を繰り返し書き込んでも
+///   出力は変化しません
+pub fn write_comment_with_options(
+    data: &[u8],
+    comment: &str,
+    options: &CommentOptions,
+) -> Result<Vec<u8>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
     }
 
-    let mut output = Vec::new();
-    output.extend_from_slice(&JPEG_SOI);
+    // JPEGが正常にデコードできるか検証
+    validate_jpeg_decode(data, None)?;
+
+    let comment = options.text.apply(comment)?;
+    let comment = comment.as_ref();
+    let comment_bytes = options.encoding.encode(comment)?;
+    let chunks = if comment_bytes.len() <= MAX_COMMENT_CHUNK {
+        vec![comment_bytes]
+    } else if options.split_long {
+        split_comment(comment, options.encoding)?
+    } else {
+        return Err(Error::PayloadTooLarge {
+            limit: MAX_COMMENT_CHUNK,
+            actual: comment_bytes.len(),
+            splittable: true,
+        });
+    };
+
+    insert_comment_segment(data, &chunks, options.placement)
+}
+
+/// コメントを1つのCOMセグメントに収まるよう文字の境界で分割してエンコードします
+fn split_comment(comment: &str, encoding: CommentEncoding) -> Result<Vec<Vec<u8>>, Error> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut chunk_len = 0;
+    let mut buf = [0u8; 4];
+
+    for (index, ch) in comment.char_indices() {
+        let len = encoding.encode(ch.encode_utf8(&mut buf))?.len();
+        if chunk_len + len > MAX_COMMENT_CHUNK {
+            chunks.push(encoding.encode(&comment[start..index])?);
+            start = index;
+            chunk_len = 0;
+        }
+        chunk_len += len;
+    }
+    chunks.push(encoding.encode(&comment[start..])?);
+
+    Ok(chunks)
+}
 
+/// 既存のコメントを削除し、指定位置に新しいCOMセグメントを挿入します
+///
+/// `chunks`はそれぞれ1つのCOMセグメントとして連続して書き込みます。
+fn insert_comment_segment(
+    data: &[u8],
+    chunks: &[Vec<u8>],
+    placement: CommentPlacement,
+) -> Result<Vec<u8>, Error> {
     // コメントセグメントを作成
     let mut comment_segment = Vec::new();
-    comment_segment.extend_from_slice(&[0xFF, MARKER_COM]);
-    let segment_size = (comment_bytes.len() + 2) as u16;
-    comment_segment.push((segment_size >> 8) as u8);
-    comment_segment.push(segment_size as u8);
-    comment_segment.extend_from_slice(comment_bytes);
+    for chunk in chunks {
+        comment_segment.extend_from_slice(&[0xFF, MARKER_COM]);
+        let segment_size = (chunk.len() + 2) as u16;
+        comment_segment.extend_from_slice(&segment_size.to_be_bytes());
+        comment_segment.extend_from_slice(chunk);
+    }
 
-    let mut pos = 2;
-    let mut comment_inserted = false;
+    let (segments, sos_pos) = parse_segments(data)?;
+
+    // 既存のコメントは削除
+    let kept: Vec<&Segment> = segments
+        .iter()
+        .filter(|segment| segment.marker != MARKER_COM)
+        .collect();
+
+    // 挿入位置（keptのインデックス）を決定
+    let insert_index = match placement {
+        CommentPlacement::AfterSoi => 0,
+        CommentPlacement::AfterAppSegments => kept
+            .iter()
+            .rposition(|segment| (0xE0..=0xEF).contains(&segment.marker))
+            .map_or(0, |index| index + 1),
+        CommentPlacement::BeforeTables => kept
+            .iter()
+            .position(|segment| segment.marker == 0xDB)
+            .unwrap_or(kept.len()),
+        CommentPlacement::BeforeSos => kept.len(),
+    };
 
-    // 既存のコメントを削除しつつ、適切な位置に新しいコメントを挿入
-    while pos < data.len() - 1 {
-        if data[pos] != 0xFF {
-            return Err(Error::ParseError("Invalid JPEG marker".to_string()));
+    let mut output = Vec::new();
+    output.extend_from_slice(&JPEG_SOI);
+
+    for segment in &kept[..insert_index] {
+        output.extend_from_slice(segment.bytes);
+    }
+    output.extend_from_slice(&comment_segment);
+    for segment in &kept[insert_index..] {
+        output.extend_from_slice(segment.bytes);
+    }
+
+    // SOSマーカー以降は画像データなのでそのままコピー
+    if let Some(sos_pos) = sos_pos {
+        output.extend_from_slice(&data[sos_pos..]);
+    }
+
+    // 出力が有効なJPEGか検証
+    validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// SOSより前のマーカーセグメント
+///
+/// 入力データを借用するため、列挙時にセグメントごとのコピーは発生しません。
+#[derive(Debug, Clone, Copy)]
+pub struct Segment<'a> {
+    /// マーカー種別（0xFFの次のバイト）
+    pub marker: u8,
+    /// マーカーの位置
+    pub offset: usize,
+    /// マーカーとサイズフィールドを含むセグメント全体
+    pub bytes: &'a [u8],
+}
+
+impl<'a> Segment<'a> {
+    /// サイズフィールド以降のセグメントデータ
+    pub fn payload(&self) -> &'a [u8] {
+        if self.bytes.len() > 4 {
+            &self.bytes[4..]
+        } else {
+            &[]
         }
+    }
 
-        let marker = data[pos + 1];
-        pos += 2;
+    /// APPセグメントの種類（APP以外のセグメントは`None`）
+    ///
+    /// 詳細は[`classify_app_segment`]を参照してください。
+    pub fn app_kind(&self) -> Option<AppKind> {
+        (0xE0..=0xEF)
+            .contains(&self.marker)
+            .then(|| classify_app_segment(self.marker, self.payload()))
+    }
+}
+
+/// APPセグメントの種類を判定します
+///
+/// # Arguments
+/// * `marker` - マーカー種別（0xE0〜0xEF）
+/// * `payload` - サイズフィールド以降のセグメントデータ
+///
+/// # Returns
+/// * 識別子から判定した種類。APP以外のマーカーや識別子が一致しない場合は[`AppKind::Unknown`]
+pub fn classify_app_segment(marker: u8, payload: &[u8]) -> AppKind {
+    match marker {
+        0xE0 if payload.starts_with(b"JFIF\0") => AppKind::Jfif,
+        0xE0 if payload.starts_with(b"JFXX\0") => AppKind::Jfxx,
+        MARKER_APP1 if payload.starts_with(b"Exif\0") => AppKind::Exif,
+        MARKER_APP1 if payload.starts_with(XMP_SIGNATURE) => AppKind::Xmp,
+        MARKER_APP1 if payload.starts_with(EXTENDED_XMP_SIGNATURE) => AppKind::ExtendedXmp,
+        MARKER_APP2 if payload.starts_with(ICC_SIGNATURE) => AppKind::Icc,
+        MARKER_APP2 if payload.starts_with(b"MPF\0") => AppKind::Mpf,
+        MARKER_APP11 if payload.starts_with(b"JP") => AppKind::Jumbf,
+        MARKER_APP12 if payload.starts_with(DUCKY_SIGNATURE) => AppKind::Ducky,
+        0xED if payload.starts_with(b"Photoshop 3.0\0") => AppKind::Photoshop,
+        MARKER_APP14 if payload.starts_with(b"Adobe") => AppKind::Adobe,
+        _ => {
+            let end = payload
+                .iter()
+                .take(MAX_UNKNOWN_SIGNATURE)
+                .position(|&b| b == 0)
+                .unwrap_or(payload.len().min(MAX_UNKNOWN_SIGNATURE));
+            AppKind::Unknown(payload[..end].to_vec())
+        }
+    }
+}
+
+/// SOSより前のセグメントを順に返すイテレータ
+///
+/// [`segments`]で作成します。構造が壊れている位置で`Err`を1度返し、以降は`None`を返します。
+#[derive(Debug, Clone)]
+pub struct Segments<'a> {
+    data: &'a [u8],
+    pos: usize,
+    sos_pos: Option<usize>,
+    done: bool,
+}
 
-        // APPマーカーの後、SOSマーカーの前にコメントを挿入
-        if !comment_inserted && (marker == 0xDA || marker == 0xDB) {
-            output.extend_from_slice(&comment_segment);
-            comment_inserted = true;
+impl<'a> Segments<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 2,
+            sos_pos: None,
+            done: false,
+        }
+    }
+
+    /// SOSマーカーの位置（列挙がSOSに到達するまでは`None`）
+    pub fn sos_position(&self) -> Option<usize> {
+        self.sos_pos
+    }
+
+    fn read_next(&mut self) -> Option<Result<Segment<'a>, Error>> {
+        let data = self.data;
+        if self.pos + 1 >= data.len() {
+            return None;
+        }
+        if data[self.pos] != 0xFF {
+            return Some(Err(Error::ParseError("Invalid JPEG marker".to_string())));
         }
 
-        // SOSマーカー以降は画像データなのでそのままコピー
+        let offset = self.pos;
+        let marker = data[offset + 1];
+        let pos = offset + 2;
+
+        // SOSマーカー以降は画像データ
         if marker == 0xDA {
-            output.extend_from_slice(&[0xFF, marker]);
-            output.extend_from_slice(&data[pos..]);
-            break;
+            self.sos_pos = Some(offset);
+            return None;
         }
 
         // スタンドアロンマーカーの場合
         if (0xD0..=0xD9).contains(&marker) {
-            output.extend_from_slice(&[0xFF, marker]);
-            continue;
+            self.pos = pos;
+            return Some(Ok(Segment {
+                marker,
+                offset,
+                bytes: &data[offset..pos],
+            }));
         }
 
         // セグメントサイズを読み取る
         if pos + 2 > data.len() {
-            return Err(Error::ParseError("Unexpected end of JPEG data".to_string()));
+            return Some(Err(Error::ParseError(
+                "Unexpected end of JPEG data".to_string(),
+            )));
         }
 
-        let segment_size = ((data[pos] as u16) << 8) | (data[pos + 1] as u16);
+        let segment_size = u16::from_be_bytes([data[pos], data[pos + 1]]);
         if segment_size < 2 {
-            return Err(Error::ParseError("Invalid segment size".to_string()));
+            return Some(Err(Error::ParseError("Invalid segment size".to_string())));
         }
 
         let segment_end = pos + segment_size as usize;
         if segment_end > data.len() {
-            return Err(Error::ParseError("Segment extends beyond file".to_string()));
+            return Some(Err(Error::ParseError(
+                "Segment extends beyond file".to_string(),
+            )));
         }
 
-        // 既存のコメントは削除
-        if marker != MARKER_COM {
-            output.extend_from_slice(&[0xFF, marker]);
-            output.extend_from_slice(&data[pos..segment_end]);
-        }
+        self.pos = segment_end;
+        Some(Ok(Segment {
+            marker,
+            offset,
+            bytes: &data[offset..segment_end],
+        }))
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Result<Segment<'a>, Error>;
 
-        pos = segment_end;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.read_next();
+        self.done = !matches!(item, Some(Ok(_)));
+        item
     }
+}
 
-    // コメントがまだ挿入されていない場合（画像データがない場合）
-    if !comment_inserted {
-        output.extend_from_slice(&comment_segment);
+/// SOIの直後からSOSの直前までのセグメントを、入力を借用したまま列挙します
+///
+/// # Arguments
+/// * `data` - JPEG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Segments)` - セグメントを順に返すイテレータ
+/// * `Err(Error)` - JPEGではない場合
+///
+/// # Details
+/// セグメントを読み進めるだけで、ベクタの確保やデコードによる検証は行いません。
+/// 必要なセグメントが見つかった時点で列挙を打ち切れるため、内容を調べるだけの処理に向いています。
+/// スタンドアロンマーカーはデータを持たないセグメントとして列挙されます。
+pub fn segments(data: &[u8]) -> Result<Segments<'_>, Error> {
+    if data.len() < 4 || data[0..2] != JPEG_SOI {
+        return Err(Error::InvalidFormat("Not a valid JPEG file".to_string()));
     }
+    Ok(Segments::new(data))
+}
 
-    // 出力が有効なJPEGか検証
-    validate_jpeg_decode(&output)?;
+/// SOIの直後からSOSの直前までのセグメントを列挙します
+///
+/// 戻り値の2番目はSOSマーカーの位置です（SOSがない場合は`None`）。
+/// スタンドアロンマーカーはデータを持たないセグメントとして列挙されます。
+pub(crate) fn parse_segments(data: &[u8]) -> Result<(Vec<Segment<'_>>, Option<usize>), Error> {
+    let mut iter = Segments::new(data);
+    let segments = iter.by_ref().collect::<Result<Vec<_>, _>>()?;
+    Ok((segments, iter.sos_position()))
+}
 
-    Ok(output)
+/// SOS以降を走査した結果
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ScanWalk {
+    /// SOS以降に現れたマーカー（RST・EOIを除く）とその位置
+    pub(crate) markers: Vec<(u8, usize)>,
+    /// EOIマーカーの直後の位置（EOIが見つからない場合は`None`）
+    pub(crate) eoi_end: Option<usize>,
+    /// データの終端で途切れたセグメントの位置
+    pub(crate) incomplete_segment: Option<usize>,
+    /// 現れたリスタートマーカーの数
+    pub(crate) restart_markers: usize,
+}
+
+/// SOSマーカー以降のエントロピー符号化データを走査し、EOIの位置を求めます
+///
+/// プログレッシブJPEGのスキャン間に現れるDHTやSOSなどのセグメントは
+/// サイズフィールドに従って読み飛ばします。
+pub(crate) fn walk_scans(data: &[u8], sos_pos: usize) -> ScanWalk {
+    let mut walk = ScanWalk::default();
+    let mut pos = sos_pos;
+
+    while pos + 1 < data.len() {
+        if data[pos] != 0xFF {
+            pos += 1;
+            continue;
+        }
+
+        let marker = data[pos + 1];
+        match marker {
+            // バイトスタッフィング
+            0x00 => pos += 2,
+            // フィルバイト
+            0xFF => pos += 1,
+            // リスタートマーカー
+            0xD0..=0xD7 => {
+                walk.restart_markers += 1;
+                pos += 2;
+            }
+            // EOI
+            0xD9 => {
+                walk.eoi_end = Some(pos + 2);
+                return walk;
+            }
+            _ => {
+                walk.markers.push((marker, pos));
+                if pos + 4 > data.len() {
+                    walk.incomplete_segment = Some(pos);
+                    break;
+                }
+                let segment_size = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+                if segment_size < 2 {
+                    break;
+                }
+                if pos + 2 + segment_size > data.len() {
+                    walk.incomplete_segment = Some(pos);
+                    break;
+                }
+                pos += 2 + segment_size;
+            }
+        }
+    }
+
+    walk
 }
@@ -1,5 +1,69 @@
+pub mod batch;
+mod cancel;
+mod compare;
+mod descriptive;
+mod embedded;
+mod etag;
+pub mod exif;
+mod format;
+pub mod heif;
+mod huffman;
+mod icc;
+mod info;
+mod iptc;
+mod isobmff;
+pub mod jp2;
 pub mod jpeg;
+mod lint;
+mod optimize;
+mod orientation;
+mod peek;
 pub mod png;
+mod preset;
+mod processor;
+mod progress;
+mod provenance;
+mod regions;
+mod resolution;
+mod sanitize;
+mod security;
+mod signature;
+#[cfg(feature = "tokio")]
+pub mod stream;
+mod structure;
+mod text;
+pub mod webp;
+pub mod xmp;
+
+pub use cancel::CancellationToken;
+pub use compare::{metadata_equal, pixels_equal};
+pub use descriptive::{
+    read_descriptive, read_descriptive_with_options, read_iptc_charset, read_keywords,
+    write_descriptive, write_descriptive_with_options, write_keywords, Descriptive,
+    DescriptiveOptions, DescriptiveWriteOptions, KeywordMode,
+};
+pub use embedded::{extract_embedded, EmbeddedAssets};
+pub use etag::{etag, etag_with_options, EtagOptions};
+pub use format::{sniff, sniff_mime, ImageFormat};
+pub use info::{read_info, ImageInfo, PixelFormat};
+pub use iptc::IptcCharset;
+pub use lint::{LintFinding, LintRule};
+pub use optimize::{optimize, OptimizeOptions, OptimizeReport, Trim};
+pub use orientation::Orientation;
+pub use peek::{PeekOptions, PeekResult, PeekStep, PeekTargets, RangePlanner};
+pub use preset::CleanPreset;
+pub use processor::Processor;
+pub use progress::{Progress, ProgressCallback, ProgressStage};
+pub use provenance::{read_provenance, stamp_provenance, ProvenanceInfo};
+pub use regions::{map_regions, size_breakdown, Region, RegionKind, SizeBreakdown};
+pub use resolution::{
+    read_resolution, set_dpi, transfer_resolution, write_resolution, Resolution, ResolutionUnit,
+};
+pub use sanitize::{sanitize, SanitizeOptions, SanitizeReport};
+pub use security::{scan_security, SecurityFinding, SecurityReport, OVERSIZED_METADATA_THRESHOLD};
+pub use signature::{verify_signature, write_signature, SignOptions, SignedMetadata};
+pub use structure::{StructureIssue, StructureReport};
+pub use text::{DecodedText, TextOptions, Utf8Policy};
 
 use std::error::Error as StdError;
 use std::fmt;
@@ -12,6 +76,21 @@ pub enum Error {
     Io(std::io::Error),
     /// パースエラー
     ParseError(String),
+    /// リソース制限の超過
+    LimitExceeded(String),
+    /// [`CancellationToken`]による処理の中断
+    Cancelled,
+    /// 軽量化の前後で画素が一致しない
+    PixelMismatch(String),
+    /// 書き込むデータが形式の上限（1つのセグメントやチャンクの最大長など）を超えている
+    PayloadTooLarge {
+        /// 書き込めるバイト数の上限
+        limit: usize,
+        /// 書き込もうとしたバイト数
+        actual: usize,
+        /// 形式が分割して書き込む方法を持つ（JPEGのコメントの`split_long`など、分割を指定すれば書き込める）
+        splittable: bool,
+    },
 }
 
 impl fmt::Display for Error {
@@ -20,6 +99,20 @@ impl fmt::Display for Error {
             Error::InvalidFormat(msg) => write!(f, "Invalid format: {msg}"),
             Error::Io(err) => write!(f, "IO error: {err}"),
             Error::ParseError(msg) => write!(f, "Parse error: {msg}"),
+            Error::LimitExceeded(msg) => write!(f, "Limit exceeded: {msg}"),
+            Error::Cancelled => write!(f, "Operation cancelled"),
+            Error::PixelMismatch(msg) => write!(f, "Pixel mismatch: {msg}"),
+            Error::PayloadTooLarge {
+                limit,
+                actual,
+                splittable,
+            } => {
+                write!(f, "Payload too large: {actual} bytes exceeds {limit} bytes")?;
+                if *splittable {
+                    write!(f, " (can be split)")?;
+                }
+                Ok(())
+            }
         }
     }
 }
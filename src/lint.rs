@@ -0,0 +1,51 @@
+/// リントの規則
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// 構造が壊れており、途中で検査を打ち切った
+    MalformedStructure,
+    /// チャンクの順序が仕様と異なる（PNG）
+    ChunkOrder,
+    /// 1つだけ置くべきチャンクが重複している（PNG）
+    DuplicateChunk,
+    /// チャンクのCRCが一致しない（PNG）
+    CrcMismatch,
+    /// テキストチャンクのキーワードが仕様に違反している（PNG）
+    InvalidKeyword,
+    /// テキストチャンクが上限サイズを超えている（PNG）
+    OversizedText,
+    /// 色空間情報（sRGB、gAMA、iCCP、cICP）がない（PNG）
+    MissingColorInfo,
+    /// 色空間情報が矛盾している（PNG）
+    ColorConflict,
+    /// 1つだけ置くべきセグメント（JFIF、EXIF）が重複している（JPEG）
+    DuplicateSegment,
+    /// 内容が空のAPPセグメント（JPEG）
+    EmptySegment,
+    /// 画像データ（SOS）より後ろにあるAPP・COMセグメント（JPEG）
+    SegmentAfterScan,
+    /// EOIマーカーがない（JPEG）
+    MissingEoi,
+    /// EXIFのサムネイル画像が上限サイズを超えている（JPEG）
+    OversizedThumbnail,
+}
+
+/// リントで見つかった問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintFinding {
+    /// 違反した規則
+    pub rule: LintRule,
+    /// 問題のあるチャンク・セグメントの位置（画像全体に関する問題は`None`）
+    pub offset: Option<usize>,
+    /// 問題の内容
+    pub message: String,
+}
+
+impl LintFinding {
+    pub(crate) fn new(rule: LintRule, offset: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            offset,
+            message: message.into(),
+        }
+    }
+}
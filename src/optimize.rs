@@ -0,0 +1,256 @@
+use crate::{exif, jpeg, png, CleanPreset, Error, ImageFormat};
+
+/// [`optimize`]のオプション
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    /// JPEGの軽量化のオプション
+    ///
+    /// 既定は[`CleanPreset::WebSafe`]に、ハフマンテーブルの最適化を加えたものです。
+    pub jpeg_options: jpeg::CleanOptions,
+    /// PNGの軽量化のオプション
+    ///
+    /// 既定は[`CleanPreset::WebSafe`]に、IDATの連結を加えたものです。
+    pub png_options: png::CleanOptions,
+    /// PNGのIDATを圧縮し直す場合のzlibの圧縮レベル（0〜9、`None`の場合は圧縮し直さない）
+    pub png_recompress_level: Option<u32>,
+    /// 出力の最大バイト数（`None`の場合は制限しない）
+    ///
+    /// 最適化した結果が超える場合は、[`Trim`]の順にメタデータを削除して収めます。
+    /// 削除したものは[`OptimizeReport::trimmed`]で確認できます。
+    pub max_output_size: Option<usize>,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            jpeg_options: jpeg::CleanOptions {
+                optimize_huffman: true,
+                ..CleanPreset::WebSafe.jpeg_options()
+            },
+            png_options: png::CleanOptions {
+                merge_idat: true,
+                ..CleanPreset::WebSafe.png_options()
+            },
+            png_recompress_level: None,
+            max_output_size: None,
+        }
+    }
+}
+
+/// 出力を最大バイト数に収めるために削除するメタデータ
+///
+/// 画像の表示への影響が小さいものから順に並んでおり、この順に削除を試みます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trim {
+    /// EXIFのサムネイル（IFD1。JPEGのAPP1、PNGのeXIfチャンクの両方が対象）
+    Thumbnail,
+    /// XMP（JPEGの拡張XMPを含む）
+    Xmp,
+    /// ICCプロファイル（JPEGでは削除し、PNGではsRGBチャンクに置き換えます）
+    ///
+    /// sRGB以外のプロファイルの場合は色が変わります。
+    IccToSrgb,
+    /// コメント（JPEGのCOMセグメント、PNGのテキストチャンク）
+    Comments,
+}
+
+impl Trim {
+    /// 削除を試みる順序
+    const ORDER: [Trim; 4] = [Trim::Thumbnail, Trim::Xmp, Trim::IccToSrgb, Trim::Comments];
+}
+
+/// [`optimize`]の結果レポート
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimizeReport {
+    /// 検出された画像形式
+    pub format: ImageFormat,
+    /// 入力のバイト数
+    pub input_size: usize,
+    /// 出力のバイト数
+    pub output_size: usize,
+    /// メタデータの軽量化（ハフマンテーブルの最適化・IDATの連結を含む）で削減したバイト数
+    pub clean_saved: usize,
+    /// IDATの再圧縮で削減したバイト数
+    pub recompress_saved: usize,
+    /// [`max_output_size`](OptimizeOptions::max_output_size)に収めるために削除したメタデータ（削除した順）
+    pub trimmed: Vec<Trim>,
+}
+
+impl OptimizeReport {
+    /// 削減したバイト数の合計
+    pub fn bytes_saved(&self) -> usize {
+        self.input_size.saturating_sub(self.output_size)
+    }
+}
+
+/// 形式を判定し、Web配信向けに画像を1回の呼び出しで最適化します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+/// * `options` - 最適化のオプション
+///
+/// # Returns
+/// * `Ok((Vec<u8>, OptimizeReport))` - 最適化した画像データと削減量のレポート
+/// * `Err(Error)` - 対応していない形式や、軽量化できない場合
+///
+/// # Details
+/// 既定のオプションでは次の処理を行います。いずれも画素を変更しません。
+/// 1. メタデータを削除（オリエンテーションとICCプロファイルは保持）
+/// 2. sRGBのICCプロファイルを削除（PNGではsRGBチャンクに置換）。
+///    それ以外のプロファイルは置き換えると色が変わるため保持します
+/// 3. JPEGのハフマンテーブルを最適化、PNGのIDATを連結
+/// 4. [`png_recompress_level`](OptimizeOptions::png_recompress_level)を指定した場合はIDATを圧縮し直す
+/// 5. [`max_output_size`](OptimizeOptions::max_output_size)を超える場合は、
+///    収まるまで[`Trim`]の順にメタデータを削除する（該当するメタデータがない段階は飛ばします）
+///
+/// メタデータをすべて削除しても収まらない場合は[`Error::LimitExceeded`]を返します。
+pub fn optimize(
+    data: &[u8],
+    options: &OptimizeOptions,
+) -> Result<(Vec<u8>, OptimizeReport), Error> {
+    let format = ImageFormat::detect(data)
+        .ok_or_else(|| Error::InvalidFormat("Unsupported image format".to_string()))?;
+
+    let cleaned = match format {
+        ImageFormat::Jpeg => jpeg::clean_metadata_with_options(data, &options.jpeg_options)?,
+        ImageFormat::Png => png::clean_chunks_with_options(data, &options.png_options)?,
+    };
+    let clean_saved = data.len().saturating_sub(cleaned.len());
+
+    let (mut output, recompress_saved) = match (format, options.png_recompress_level) {
+        (ImageFormat::Png, Some(level)) => {
+            let recompressed = png::recompress(&cleaned, level)?;
+            let saved = cleaned.len().saturating_sub(recompressed.len());
+            (recompressed, saved)
+        }
+        _ => (cleaned, 0),
+    };
+
+    let mut trimmed = Vec::new();
+    if let Some(max_output_size) = options.max_output_size {
+        for trim in Trim::ORDER {
+            if output.len() <= max_output_size {
+                break;
+            }
+            let result = match format {
+                ImageFormat::Jpeg => trim_jpeg(&output, trim)?,
+                ImageFormat::Png => trim_png(&output, trim)?,
+            };
+            if let Some(result) = result {
+                output = result;
+                trimmed.push(trim);
+            }
+        }
+        if output.len() > max_output_size {
+            return Err(Error::LimitExceeded(format!(
+                "Output of {} bytes does not fit in {} bytes",
+                output.len(),
+                max_output_size
+            )));
+        }
+    }
+
+    let report = OptimizeReport {
+        format,
+        input_size: data.len(),
+        output_size: output.len(),
+        clean_saved,
+        recompress_saved,
+        trimmed,
+    };
+    Ok((output, report))
+}
+
+/// JPEGからメタデータを削除します（該当するメタデータがない場合は`None`）
+fn trim_jpeg(data: &[u8], trim: Trim) -> Result<Option<Vec<u8>>, Error> {
+    if trim == Trim::Thumbnail {
+        let (segments, _) = jpeg::parse_segments(data)?;
+        let has_thumbnail = segments
+            .iter()
+            .filter(|segment| segment.app_kind() == Some(jpeg::AppKind::Exif))
+            .filter_map(|segment| segment.payload().get(6..))
+            .any(|tiff| exif::thumbnail_length(tiff).is_some());
+        if !has_thumbnail {
+            return Ok(None);
+        }
+        return jpeg::rewrite_exif_segment(data, |tiff| {
+            let tiff = tiff.unwrap_or_default();
+            Ok(exif::remove_thumbnail(tiff).unwrap_or_else(|| tiff.to_vec()))
+        })
+        .map(Some);
+    }
+
+    let (segments, sos_pos) = jpeg::parse_segments(data)?;
+    let drop = |segment: &jpeg::Segment| match trim {
+        Trim::Xmp => matches!(
+            segment.app_kind(),
+            Some(jpeg::AppKind::Xmp | jpeg::AppKind::ExtendedXmp)
+        ),
+        Trim::IccToSrgb => segment.app_kind() == Some(jpeg::AppKind::Icc),
+        Trim::Comments => segment.marker == 0xFE,
+        Trim::Thumbnail => false,
+    };
+    if !segments.iter().any(drop) {
+        return Ok(None);
+    }
+
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&data[..2]);
+    for segment in segments.iter().filter(|segment| !drop(segment)) {
+        output.extend_from_slice(segment.bytes);
+    }
+    if let Some(sos_pos) = sos_pos {
+        output.extend_from_slice(&data[sos_pos..]);
+    }
+    Ok(Some(output))
+}
+
+/// PNGからメタデータを削除します（該当するメタデータがない場合は`None`）
+fn trim_png(data: &[u8], trim: Trim) -> Result<Option<Vec<u8>>, Error> {
+    let chunks = png::parse_chunks(data)?;
+    if trim == Trim::Thumbnail {
+        // eXIfチャンクのIFD1を取り除いて書き直す
+        let mut trimmed = false;
+        let mut output = Vec::with_capacity(data.len());
+        output.extend_from_slice(&data[..8]);
+        for chunk in &chunks {
+            let tiff = (&chunk.chunk_type == b"eXIf")
+                .then(|| exif::remove_thumbnail(chunk.data))
+                .flatten();
+            match tiff {
+                Some(tiff) => {
+                    png::write_chunk(&mut output, b"eXIf", &tiff);
+                    trimmed = true;
+                }
+                None => output.extend_from_slice(chunk.bytes),
+            }
+        }
+        return Ok(trimmed.then_some(output));
+    }
+
+    let is_xmp = |chunk: &png::Chunk| {
+        &chunk.chunk_type == b"iTXt" && png::text_keyword(chunk) == Some(b"XML:com.adobe.xmp")
+    };
+    let drop = |chunk: &png::Chunk| match trim {
+        Trim::Xmp => is_xmp(chunk),
+        Trim::IccToSrgb => &chunk.chunk_type == b"iCCP",
+        Trim::Comments => matches!(&chunk.chunk_type, b"tEXt" | b"zTXt" | b"iTXt"),
+        Trim::Thumbnail => false,
+    };
+    if !chunks.iter().any(drop) {
+        return Ok(None);
+    }
+
+    let has_srgb = chunks.iter().any(|chunk| &chunk.chunk_type == b"sRGB");
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&data[..8]);
+    for chunk in &chunks {
+        if !drop(chunk) {
+            output.extend_from_slice(chunk.bytes);
+        } else if &chunk.chunk_type == b"iCCP" && !has_srgb {
+            // sRGBチャンクに置き換え（レンダリングインテントは知覚的）
+            png::write_chunk(&mut output, b"sRGB", &[0]);
+        }
+    }
+    Ok(Some(output))
+}
@@ -0,0 +1,104 @@
+use crate::Error;
+
+/// EXIFのオリエンテーション（タグ0x0112）
+///
+/// 各値は、保存されている画素を正しい向きで表示するために必要な変換を表します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Orientation {
+    /// 変換なし（1）
+    #[default]
+    Normal,
+    /// 左右反転（2）
+    FlipHorizontal,
+    /// 180度回転（3）
+    Rotate180,
+    /// 上下反転（4）
+    FlipVertical,
+    /// 左上と右下を結ぶ対角線で反転（5）
+    Transpose,
+    /// 時計回りに90度回転（6）
+    Rotate90,
+    /// 右上と左下を結ぶ対角線で反転（7）
+    Transverse,
+    /// 反時計回りに90度回転（8）
+    Rotate270,
+}
+
+impl Orientation {
+    /// EXIFの値（1〜8）から変換します
+    ///
+    /// 範囲外の値は`None`になります。
+    pub fn from_exif(value: u16) -> Option<Self> {
+        match value {
+            1 => Some(Orientation::Normal),
+            2 => Some(Orientation::FlipHorizontal),
+            3 => Some(Orientation::Rotate180),
+            4 => Some(Orientation::FlipVertical),
+            5 => Some(Orientation::Transpose),
+            6 => Some(Orientation::Rotate90),
+            7 => Some(Orientation::Transverse),
+            8 => Some(Orientation::Rotate270),
+            _ => None,
+        }
+    }
+
+    /// EXIFの値（1〜8）を返します
+    pub fn to_exif(self) -> u16 {
+        match self {
+            Orientation::Normal => 1,
+            Orientation::FlipHorizontal => 2,
+            Orientation::Rotate180 => 3,
+            Orientation::FlipVertical => 4,
+            Orientation::Transpose => 5,
+            Orientation::Rotate90 => 6,
+            Orientation::Transverse => 7,
+            Orientation::Rotate270 => 8,
+        }
+    }
+
+    /// 表示時に幅と高さが入れ替わるか
+    pub fn is_transposed(self) -> bool {
+        matches!(
+            self,
+            Orientation::Transpose
+                | Orientation::Rotate90
+                | Orientation::Transverse
+                | Orientation::Rotate270
+        )
+    }
+
+    /// 反転を含むか
+    pub fn is_mirrored(self) -> bool {
+        matches!(
+            self,
+            Orientation::FlipHorizontal
+                | Orientation::FlipVertical
+                | Orientation::Transpose
+                | Orientation::Transverse
+        )
+    }
+
+    /// 表示時の寸法を返します
+    pub fn display_dimensions(self, width: u32, height: u32) -> (u32, u32) {
+        if self.is_transposed() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+impl TryFrom<u16> for Orientation {
+    type Error = Error;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Orientation::from_exif(value)
+            .ok_or_else(|| Error::InvalidFormat(format!("Invalid orientation value: {value}")))
+    }
+}
+
+impl From<Orientation> for u16 {
+    fn from(orientation: Orientation) -> Self {
+        orientation.to_exif()
+    }
+}
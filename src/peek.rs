@@ -0,0 +1,372 @@
+use crate::exif;
+use crate::jpeg::is_sof;
+use crate::{Error, ImageFormat, Orientation};
+use std::ops::Range;
+
+/// [`RangePlanner`]で読み取る情報
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeekTargets {
+    /// 幅と高さ
+    pub dimensions: bool,
+    /// EXIFのオリエンテーション
+    pub orientation: bool,
+    /// ICCプロファイルの有無
+    pub icc: bool,
+}
+
+impl Default for PeekTargets {
+    fn default() -> Self {
+        Self {
+            dimensions: true,
+            orientation: true,
+            icc: true,
+        }
+    }
+}
+
+/// [`RangePlanner`]の取得サイズのオプション
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeekOptions {
+    /// 最初に取得する先頭のバイト数
+    pub initial_fetch_size: u64,
+    /// 2回目以降に取得する最小のバイト数（小さな範囲の往復を減らす）
+    pub min_fetch_size: u64,
+}
+
+impl Default for PeekOptions {
+    fn default() -> Self {
+        Self {
+            initial_fetch_size: 64 * 1024,
+            min_fetch_size: 4 * 1024,
+        }
+    }
+}
+
+/// 部分的なバイト列から読み取った情報
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeekResult {
+    /// 画像形式
+    pub format: ImageFormat,
+    /// 幅（ピクセル、対象外または見つからない場合は`None`）
+    pub width: Option<u32>,
+    /// 高さ（ピクセル、対象外または見つからない場合は`None`）
+    pub height: Option<u32>,
+    /// EXIFのオリエンテーション（対象外、EXIFがない、値が不正な場合は`None`）
+    pub orientation: Option<Orientation>,
+    /// ICCプロファイルを含む（対象外の場合は`false`）
+    pub has_icc: bool,
+}
+
+/// [`RangePlanner::next_step`]の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeekStep {
+    /// 次に取得するバイト範囲（HTTPのRangeリクエストの`bytes=start-(end-1)`）
+    Fetch(Range<u64>),
+    /// 必要な情報がそろった
+    Done(PeekResult),
+}
+
+/// リモートの画像から情報を読み取るために、取得すべきバイト範囲を計画します
+///
+/// 通信を行わない状態機械で、呼び出し側は[`next_step`](Self::next_step)が返す範囲を
+/// HTTPのRangeリクエストなどで取得し、[`feed`](Self::feed)で渡すことを
+/// [`PeekStep::Done`]になるまで繰り返します。
+///
+/// # Details
+/// - JPEGはSOSまで、PNGはIDATまでのセグメント・チャンクのヘッダーをたどり、必要な部分だけを取得します
+/// - EXIFはIFD0のエントリだけを取得するため、大きなサムネイルを含むAPP1セグメントも全体は取得しません
+/// - 取得した範囲より短いデータを渡すとファイルの終端とみなします。
+///   終端より先が必要な場合は[`Error::InvalidFormat`]を返します
+#[derive(Debug, Clone, Default)]
+pub struct RangePlanner {
+    targets: PeekTargets,
+    options: PeekOptions,
+    /// 取得済みのバイト列（開始位置順、重複なし）
+    chunks: Vec<(u64, Vec<u8>)>,
+    /// 最後に返した取得範囲
+    pending: Option<Range<u64>>,
+    /// ファイルの長さ
+    len: Option<u64>,
+}
+
+/// 解析の中断理由
+enum Stop {
+    /// 取得していないバイト範囲が必要
+    Need(Range<u64>),
+    Error(Error),
+}
+
+impl From<Error> for Stop {
+    fn from(err: Error) -> Self {
+        Stop::Error(err)
+    }
+}
+
+impl RangePlanner {
+    /// 読み取る情報を指定して作成します
+    pub fn new(targets: PeekTargets) -> Self {
+        Self::with_options(targets, PeekOptions::default())
+    }
+
+    /// 読み取る情報と取得サイズのオプションを指定して作成します
+    pub fn with_options(targets: PeekTargets, options: PeekOptions) -> Self {
+        Self {
+            targets,
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// ファイルの長さを設定します（HTTPのContent-Rangeなどで判明した場合）
+    ///
+    /// 設定すると、取得範囲がファイルの終端を超えないように調整されます。
+    pub fn set_len(&mut self, len: u64) {
+        self.len = Some(len);
+    }
+
+    /// 取得したバイト列を渡します
+    ///
+    /// # Arguments
+    /// * `offset` - `bytes`のファイル先頭からの位置
+    /// * `bytes` - 取得したバイト列
+    pub fn feed(&mut self, offset: u64, bytes: &[u8]) {
+        // 要求した範囲より短ければファイルの終端
+        if let Some(pending) = self.pending.take() {
+            if offset == pending.start && offset + (bytes.len() as u64) < pending.end {
+                self.len = Some(offset + bytes.len() as u64);
+            }
+        }
+        if bytes.is_empty() {
+            return;
+        }
+
+        self.chunks.push((offset, bytes.to_vec()));
+        self.chunks.sort_by_key(|(start, _)| *start);
+
+        // 重なる・隣接するバイト列を結合する
+        let mut merged: Vec<(u64, Vec<u8>)> = Vec::with_capacity(self.chunks.len());
+        for (start, bytes) in self.chunks.drain(..) {
+            match merged.last_mut() {
+                Some((last_start, last)) if start <= *last_start + last.len() as u64 => {
+                    let overlap = (*last_start + last.len() as u64 - start) as usize;
+                    if overlap < bytes.len() {
+                        last.extend_from_slice(&bytes[overlap..]);
+                    }
+                }
+                _ => merged.push((start, bytes)),
+            }
+        }
+        self.chunks = merged;
+    }
+
+    /// 次に取得するバイト範囲、または読み取った情報を返します
+    ///
+    /// # Returns
+    /// * `Ok(PeekStep::Fetch(range))` - 次に取得する範囲
+    /// * `Ok(PeekStep::Done(result))` - 読み取った情報
+    /// * `Err(Error)` - 対応していない形式、構造が壊れている、ファイルが途中で終わっている場合
+    pub fn next_step(&mut self) -> Result<PeekStep, Error> {
+        let need = match self.analyze() {
+            Ok(result) => return Ok(PeekStep::Done(result)),
+            Err(Stop::Error(err)) => return Err(err),
+            Err(Stop::Need(need)) => need,
+        };
+
+        if self.len.is_some_and(|len| need.end > len) {
+            return Err(Error::InvalidFormat("Unexpected end of file".to_string()));
+        }
+
+        // 取得済みの部分は除き、小さな範囲はまとめて取得する
+        let start = self
+            .chunks
+            .iter()
+            .find(|(start, bytes)| (*start..*start + bytes.len() as u64).contains(&need.start))
+            .map_or(need.start, |(start, bytes)| *start + bytes.len() as u64);
+        let end = if self.chunks.is_empty() {
+            need.end.max(start + self.options.initial_fetch_size)
+        } else {
+            need.end.max(start + self.options.min_fetch_size)
+        };
+        let end = self.len.map_or(end, |len| end.min(len));
+
+        self.pending = Some(start..end);
+        Ok(PeekStep::Fetch(start..end))
+    }
+
+    /// 取得済みのバイト列から範囲を取り出します
+    fn bytes(&self, start: u64, length: u64) -> Result<&[u8], Stop> {
+        let end = start + length;
+        self.chunks
+            .iter()
+            .find(|(chunk_start, bytes)| {
+                *chunk_start <= start && end <= *chunk_start + bytes.len() as u64
+            })
+            .map(|(chunk_start, bytes)| {
+                &bytes[(start - chunk_start) as usize..(end - chunk_start) as usize]
+            })
+            .ok_or(Stop::Need(start..end))
+    }
+
+    /// 取得済みのバイト列を解析します
+    fn analyze(&self) -> Result<PeekResult, Stop> {
+        let end = self.len.map_or(16, |len| len.min(16));
+        let header = self.bytes(0, end)?;
+        match ImageFormat::detect(header) {
+            Some(ImageFormat::Jpeg) => self.analyze_jpeg(),
+            Some(ImageFormat::Png) => self.analyze_png(),
+            None => Err(Error::InvalidFormat("Unsupported image format".to_string()).into()),
+        }
+    }
+
+    /// 必要な情報がそろったか
+    fn is_complete(&self, result: &PeekResult, exif_checked: bool) -> bool {
+        (!self.targets.dimensions || result.width.is_some())
+            && (!self.targets.orientation || exif_checked)
+            && (!self.targets.icc || result.has_icc)
+    }
+
+    /// JPEGのセグメントをSOSまでたどります
+    fn analyze_jpeg(&self) -> Result<PeekResult, Stop> {
+        let mut result = PeekResult {
+            format: ImageFormat::Jpeg,
+            width: None,
+            height: None,
+            orientation: None,
+            has_icc: false,
+        };
+        let mut exif_checked = false;
+
+        let mut pos = 2;
+        while !self.is_complete(&result, exif_checked) {
+            let marker = self.bytes(pos, 2)?;
+            if marker[0] != 0xFF {
+                return Err(Error::ParseError("Invalid JPEG marker".to_string()).into());
+            }
+            let marker = marker[1];
+            match marker {
+                // 詰め物
+                0xFF => {
+                    pos += 1;
+                    continue;
+                }
+                // SOS・EOIで終了
+                0xDA | 0xD9 => break,
+                // 長さを持たないマーカー
+                0x01 | 0xD0..=0xD7 => {
+                    pos += 2;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let length = self.bytes(pos + 2, 2)?;
+            let length = u16::from_be_bytes([length[0], length[1]]) as u64;
+            if length < 2 {
+                return Err(Error::ParseError("Invalid JPEG segment length".to_string()).into());
+            }
+            let payload = pos + 4;
+            let payload_length = length - 2;
+
+            if is_sof(marker) && self.targets.dimensions && result.width.is_none() {
+                if payload_length < 5 {
+                    return Err(Error::ParseError("Invalid SOF segment".to_string()).into());
+                }
+                let frame = self.bytes(payload, 5)?;
+                result.height = Some(u16::from_be_bytes([frame[1], frame[2]]) as u32);
+                result.width = Some(u16::from_be_bytes([frame[3], frame[4]]) as u32);
+            } else if marker == 0xE1
+                && self.targets.orientation
+                && !exif_checked
+                && payload_length > 14
+            {
+                if self.bytes(payload, 4)? == b"Exif" {
+                    exif_checked = true;
+                    result.orientation = self.read_orientation(payload + 6, payload_length - 6)?;
+                }
+            } else if marker == 0xE2 && self.targets.icc && !result.has_icc && payload_length >= 12
+            {
+                result.has_icc = self.bytes(payload, 12)? == b"ICC_PROFILE\0";
+            }
+
+            pos = payload + payload_length;
+        }
+
+        Ok(result)
+    }
+
+    /// PNGのチャンクをIDATまでたどります
+    fn analyze_png(&self) -> Result<PeekResult, Stop> {
+        let mut result = PeekResult {
+            format: ImageFormat::Png,
+            width: None,
+            height: None,
+            orientation: None,
+            has_icc: false,
+        };
+        let mut exif_checked = false;
+
+        let mut pos = 8;
+        while !self.is_complete(&result, exif_checked) {
+            let header = self.bytes(pos, 8)?;
+            let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+            let chunk_type: [u8; 4] = [header[4], header[5], header[6], header[7]];
+            let data = pos + 8;
+
+            match &chunk_type {
+                b"IDAT" | b"IEND" => break,
+                b"IHDR" if self.targets.dimensions => {
+                    if length < 8 {
+                        return Err(Error::ParseError("Invalid IHDR chunk".to_string()).into());
+                    }
+                    let size = self.bytes(data, 8)?;
+                    result.width = Some(u32::from_be_bytes([size[0], size[1], size[2], size[3]]));
+                    result.height = Some(u32::from_be_bytes([size[4], size[5], size[6], size[7]]));
+                }
+                b"iCCP" => result.has_icc = true,
+                b"eXIf" if self.targets.orientation && !exif_checked => {
+                    exif_checked = true;
+                    result.orientation = self.read_orientation(data, length)?;
+                }
+                _ => {}
+            }
+
+            // データとCRC
+            pos = data + length + 4;
+        }
+
+        // 対象外の項目は報告しない
+        if !self.targets.icc {
+            result.has_icc = false;
+        }
+        Ok(result)
+    }
+
+    /// TIFFデータのIFD0からオリエンテーションを読み取ります
+    ///
+    /// # Arguments
+    /// * `base` - TIFFヘッダーの位置
+    /// * `length` - TIFFデータの長さ
+    fn read_orientation(&self, base: u64, length: u64) -> Result<Option<Orientation>, Stop> {
+        if length < 8 {
+            return Ok(None);
+        }
+        let Some((byte_order, ifd0)) = exif::parse_header(self.bytes(base, 8)?) else {
+            return Ok(None);
+        };
+        let ifd0 = ifd0 as u64;
+        if ifd0 + 2 > length {
+            return Ok(None);
+        }
+        let count = self.bytes(base + ifd0, 2)?;
+        let count = byte_order.read_u16(count, 0).unwrap_or(0) as u64;
+        let count = count.min((length - ifd0 - 2) / 12);
+        let entries = self.bytes(base + ifd0 + 2, count * 12)?;
+
+        Ok(entries
+            .chunks_exact(12)
+            .find(|entry| byte_order.read_u16(entry, 0) == Some(exif::TAG_ORIENTATION))
+            .filter(|entry| byte_order.read_u16(entry, 2) == Some(3))
+            .and_then(|entry| byte_order.read_u16(entry, 8))
+            .and_then(Orientation::from_exif))
+    }
+}
@@ -1,8 +1,15 @@
-use crate::Error;
+use crate::cancel::{self, CancellableReader};
+use crate::exif::{self, ByteOrder, Field, TiffBuilder};
+use crate::{
+    CancellationToken, DecodedText, Error, ImageFormat, ImageInfo, LintFinding, LintRule,
+    Orientation, PixelFormat, Progress, ProgressStage, StructureReport, TextOptions, Utf8Policy,
+};
 use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use png::{ColorType, Decoder};
-use std::collections::HashSet;
-use std::io::{Cursor, Read};
+use std::borrow::Cow;
+use std::io::{Cursor, Read, Write};
 
 /// PNG tEXtチャンク
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,6 +18,20 @@ pub struct TextChunk {
     pub text: String,    // テキスト内容
 }
 
+/// [`Utf8Policy`]を適用して読み取ったテキストチャンク
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedTextChunk {
+    /// キーワード
+    pub keyword: String,
+    /// テキスト内容と、適用した修復
+    pub text: DecodedText,
+}
+
+/// PNGシグネチャ（形式判定やセキュリティスキャンでも共通で使用）
+pub(crate) const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+/// チャンクのデータの最大長（2^31-1）
+const MAX_CHUNK_LENGTH: usize = i32::MAX as usize;
+
 // 保持すべき重要なチャンクタイプ
 const CRITICAL_CHUNKS: &[&str] = &[
     // Core
@@ -20,202 +41,2093 @@ const CRITICAL_CHUNKS: &[&str] = &[
     "pHYs",
 ];
 
-/// PNG画像から重要なチャンク以外を削除します
-pub fn clean_chunks(data: &[u8]) -> Result<Vec<u8>, Error> {
-    // PNGシグネチャの確認
-    if data.len() < 8 || data[0..8] != [137, 80, 78, 71, 13, 10, 26, 10] {
-        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+/// PNG仕様（APNGと登録済みの拡張を含む）で定義されたチャンクタイプ
+const KNOWN_CHUNKS: &[&[u8; 4]] = &[
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP",
+    b"mDCV", b"cLLI", b"bKGD", b"hIST", b"tRNS", b"eXIf", b"pHYs", b"sPLT", b"tIME", b"iTXt",
+    b"tEXt", b"zTXt", b"acTL", b"fcTL", b"fdAT", b"oFFs", b"pCAL", b"sCAL", b"gIFg", b"gIFx",
+    b"sTER", b"dSIG",
+];
+
+/// PLTEとIDATより前に置く必要があるチャンク
+const BEFORE_PLTE_CHUNKS: &[&[u8; 4]] = &[
+    b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCV", b"cLLI",
+];
+
+/// IDATより前に置く必要があるチャンク
+const BEFORE_IDAT_CHUNKS: &[&[u8; 4]] = &[
+    b"PLTE", b"bKGD", b"hIST", b"tRNS", b"pHYs", b"sPLT", b"acTL", b"oFFs", b"pCAL", b"sCAL",
+    b"sTER",
+];
+
+/// 画素の復元に関わるチャンク
+const IMAGE_DATA_CHUNKS: &[&[u8; 4]] = &[b"IHDR", b"PLTE", b"tRNS", b"IDAT"];
+
+/// XMPを格納するiTXtチャンクのキーワード
+pub(crate) const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+/// iCCPを展開する際の上限サイズ
+const MAX_ICC_PROFILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// 1つだけ置くことができる付随チャンク
+const UNIQUE_CHUNKS: &[&[u8; 4]] = &[
+    b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCV", b"cLLI", b"bKGD", b"hIST",
+    b"tRNS", b"eXIf", b"pHYs", b"tIME", b"acTL", b"oFFs", b"pCAL", b"sCAL", b"sTER",
+];
+
+/// 電子署名チャンク（dSIG）の扱い
+///
+/// dSIGはIHDRの直後とIENDの直前に1つずつ置かれ、間のチャンクに対する署名を表します。
+/// 片方だけを残すと署名として解釈できなくなるため、2つをまとめて扱います。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DsigPolicy {
+    /// すべてのdSIGを削除する
+    #[default]
+    Strip,
+    /// IHDRの直後とIENDの直前のdSIGの組を保持する
+    ///
+    /// 組になっていないdSIGはすべて削除します。間のチャンクを削除・変更すると
+    /// 署名は検証できなくなるため、署名を有効なまま残すには他のチャンクも保持する必要があります。
+    PreservePair,
+}
+
+/// PNGデータの検証レベル
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Validation {
+    /// 検証しない
+    None,
+    /// ヘッダー（IHDR）のみ検証する
+    #[default]
+    Header,
+    /// IDATを展開し、圧縮データと展開後のサイズが正しいか検証する
+    ///
+    /// フィルタの復元や画素バッファの確保を行わないため、完全なデコードより軽量です。
+    Inflate,
+    /// 画像全体をデコードして検証する
+    Full,
+}
+
+/// チャンク軽量化のオプション
+///
+/// よく使う組み合わせは[`crate::CleanPreset`]から取得できます。
+#[derive(Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// テキストチャンク（tEXt、zTXt、iTXt）を保持する
+    pub preserve_text: bool,
+    /// EXIFチャンク（eXIf）を保持する
+    pub preserve_exif: bool,
+    /// eXIfのオリエンテーションを最小限のeXIfとして保持する
+    ///
+    /// [`preserve_exif`](Self::preserve_exif)が`false`の場合に、オリエンテーションのみの
+    /// eXIfを作り直して残します。範囲外（0や9以上）のオリエンテーションは残しません。
+    pub preserve_orientation: bool,
+    /// XMP（キーワード`XML:com.adobe.xmp`のテキストチャンク）を保持する
+    ///
+    /// [`preserve_text`](Self::preserve_text)が`false`でもXMPのチャンクのみ保持します。
+    pub preserve_xmp: bool,
+    /// 保持するテキストチャンクのキーワード（`"Copyright"`など）
+    ///
+    /// [`preserve_text`](Self::preserve_text)が`false`でも、キーワードが一致する
+    /// tEXt・zTXt・iTXtチャンクを元のまま（iTXtの言語タグなども含めて）保持します。
+    pub keep_text_keywords: Vec<String>,
+    /// 保持するtEXtチャンクを、小さくなる場合にzTXtへ圧縮する
+    ///
+    /// JSONなど圧縮しやすいテキストを保持する場合に有効です。圧縮しても小さくならない
+    /// チャンクはそのまま残します。
+    pub compress_text: bool,
+    /// ヒストグラム（hIST）を保持する
+    ///
+    /// 減色ツールなどがパレットの使用頻度として参照します。PLTEより後になく、
+    /// 項目数がPLTEと一致しないhISTは、保持する場合でも削除します。
+    pub preserve_hist: bool,
+    /// 推奨パレット（sPLT）を保持する
+    ///
+    /// サンプル深度（8または16）と項目の長さが正しくないsPLTは、保持する場合でも削除します。
+    pub preserve_splt: bool,
+    /// 画像の位置（oFFs）を保持する
+    ///
+    /// 構造が正しくないoFFsは、保持する場合でも削除します。詳細は[`read_offset`]を参照してください。
+    pub preserve_offs: bool,
+    /// 被写体の物理的な寸法（sCAL）を保持する
+    ///
+    /// 顕微鏡や天体などの科学画像で、1ピクセルあたりの長さや角度の記録に使われます。
+    /// 構造が正しくないsCALは、保持する場合でも削除します。詳細は[`read_scale`]を参照してください。
+    pub preserve_scal: bool,
+    /// ステレオ画像の配置（sTER）を保持する
+    ///
+    /// 値が0・1以外のsTERは、保持する場合でも削除します。
+    pub preserve_ster: bool,
+    /// sRGBのICCプロファイル（iCCP）をsRGBチャンクに置き換える
+    pub replace_srgb_icc: bool,
+    /// 矛盾する色空間チャンクを削除する
+    ///
+    /// iCCPとsRGBが両方ある場合、sRGBのプロファイルならsRGBチャンクを、
+    /// それ以外ならiCCPを残します。sRGBと異なるgAMA・cHRMはsRGBを残す場合に削除します。
+    /// 詳細は[`check_color_chunks`]を参照してください。
+    pub resolve_color_conflicts: bool,
+    /// 電子署名チャンク（dSIG）の扱い
+    ///
+    /// 詳細は[`DsigPolicy`]と[`find_dsig_pair`]を参照してください。
+    pub dsig: DsigPolicy,
+    /// 同じ内容の付随チャンクを1つだけ残す
+    ///
+    /// dSIGの組は内容が同じでも両方を残します。
+    pub remove_duplicates: bool,
+    /// 複数のIDATチャンクを1つに連結する
+    ///
+    /// 詳細は[`merge_idat`]を参照してください。[`verify_passthrough`](Self::verify_passthrough)は
+    /// IDATのチャンクの区切りではなく、連結した圧縮データを比較します。
+    pub merge_idat: bool,
+    /// 入力と出力の検証レベル
+    pub validation: Validation,
+    /// 仕様にないチャンクや順序の誤りがある入力をエラーにする
+    ///
+    /// 詳細は[`check_conformance`]を参照してください。
+    pub strict: bool,
+    /// IHDR・PLTE・IENDが重複している入力をエラーにする
+    ///
+    /// 最初のIENDの後に置かれた別の画像など、パーサーごとに解釈が異なる構造を拒否します。
+    pub reject_duplicate_critical: bool,
+    /// 出力の画像データが入力とバイト単位で一致することを検証する
+    ///
+    /// 詳細は[`verify_passthrough`]を参照してください。
+    pub verify_passthrough: bool,
+    /// 入力と出力をデコードし、画素が一致することを検証する
+    ///
+    /// 詳細は[`verify_pixels`]を参照してください。
+    pub verify_pixels: bool,
+    /// 処理を中断するためのトークン
+    ///
+    /// 検証中（[`Validation::Inflate`]・[`Validation::Full`]では展開・デコードの途中）と
+    /// チャンクの書き込みごとに確認し、中断時は[`Error::Cancelled`]を返します。
+    pub cancellation: Option<CancellationToken>,
+}
+
+/// リントのオプション
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    /// テキストチャンク（tEXt、zTXt、iTXt）のデータの上限サイズ（バイト）
+    pub max_text_size: usize,
+    /// 色空間情報（sRGB、gAMA、iCCP、cICP）がない画像を報告する
+    pub require_color_info: bool,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            max_text_size: 64 * 1024,
+            require_color_info: true,
+        }
+    }
+}
+
+/// チャンク追加時のオプション
+///
+/// CRCが一致しない付随チャンクはデコーダーが無視するため、書き込みは妨げられず
+/// そのまま出力されます。
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// 一致しない付随チャンクのCRCを再計算して修正する
+    ///
+    /// 重要なチャンク（IHDR、IDATなど）のCRC不一致はデータの破損とみなしエラーになります。
+    pub fix_crc: bool,
+    /// 書き込む前のテキストの検査・修正
+    pub text: TextOptions,
+}
+
+/// PNG画像から重要なチャンク以外を削除します
+pub fn clean_chunks(data: &[u8]) -> Result<Vec<u8>, Error> {
+    clean_chunks_with_options(data, &CleanOptions::default())
+}
+
+/// オプションを指定してPNG画像から重要なチャンク以外を削除します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `options` - 保持するチャンクの指定
+///
+/// # Details
+/// 保持するチャンクは長さ・CRCを含めてバイト単位でそのままコピーされます
+/// （[`verify_passthrough`]で検証できます）。
+pub fn clean_chunks_with_options(data: &[u8], options: &CleanOptions) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    clean_chunks_into(data, options, &mut |_| {}, &mut output)?;
+    Ok(output)
+}
+
+/// オプションを指定してPNG画像から重要なチャンク以外を削除し、進捗を通知します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `options` - 保持するチャンクの指定
+/// * `progress` - 進捗を受け取るコールバック
+///
+/// # Details
+/// 入力の検証、出力の書き込み、出力の検証の各段階で[`Progress`]を通知します。
+/// 検証の段階は[`Validation::Inflate`]では展開した64KiBごと、[`Validation::Full`]では
+/// デコーダーが読み取った64KiBごとに検証済みの位置を通知します（[`Validation::Header`]は
+/// 開始時と完了時のみ）。書き込みの段階はチャンクごとに出力済みのバイト数を通知します。
+pub fn clean_chunks_with_progress(
+    data: &[u8],
+    options: &CleanOptions,
+    mut progress: impl FnMut(Progress),
+) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    clean_chunks_into(data, options, &mut progress, &mut output)?;
+    Ok(output)
+}
+
+/// 進捗を通知しながらPNG画像から重要なチャンク以外を削除し、`output`に書き込みます
+///
+/// `output`の内容は置き換えられ、確保済みの容量は再利用されます。
+pub(crate) fn clean_chunks_into(
+    data: &[u8],
+    options: &CleanOptions,
+    progress: &mut dyn FnMut(Progress),
+    output: &mut Vec<u8>,
+) -> Result<(), Error> {
+    output.clear();
+    // PNGシグネチャの確認
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    let cancellation = options.cancellation.as_ref();
+
+    // PNGが正常にデコードできるか検証
+    progress(Progress::new(ProgressStage::ValidateInput, 0, data.len()));
+    validate_png(data, options.validation, cancellation, &mut |read| {
+        progress(Progress::new(
+            ProgressStage::ValidateInput,
+            read,
+            data.len(),
+        ))
+    })?;
+
+    if options.strict {
+        check_conformance(data)?;
+    }
+    if options.reject_duplicate_critical {
+        crate::security::check_duplicate_critical(data)?;
+    }
+
+    let parts = plan_clean(data, options)?;
+    progress(Progress::new(
+        ProgressStage::ValidateInput,
+        data.len(),
+        data.len(),
+    ));
+
+    let total = clean_output_len(&parts);
+    output.reserve(total);
+    // PNGシグネチャをコピー
+    output.extend_from_slice(&data[0..8]);
+    for part in &parts {
+        cancel::check(cancellation)?;
+        output.extend_from_slice(part);
+        progress(Progress::new(ProgressStage::Write, output.len(), total));
+    }
+
+    // 出力が有効なPNGか検証
+    let output_len = output.len();
+    progress(Progress::new(ProgressStage::ValidateOutput, 0, output_len));
+    validate_png(output, options.validation, cancellation, &mut |read| {
+        progress(Progress::new(
+            ProgressStage::ValidateOutput,
+            read,
+            output_len,
+        ))
+    })?;
+    if options.verify_passthrough {
+        compare_image_data(data, output, options.merge_idat)?;
+    }
+    if options.verify_pixels {
+        verify_pixels(data, output)?;
+    }
+    progress(Progress::new(
+        ProgressStage::ValidateOutput,
+        output.len(),
+        output.len(),
+    ));
+
+    Ok(())
+}
+
+/// 軽量化後の出力サイズを予測します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `options` - 保持するチャンクの指定
+///
+/// # Returns
+/// * `Ok(usize)` - [`clean_chunks_with_options`]の出力と同じバイト数
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// チャンクの構造のみを走査し、画像の検証や出力の組み立ては行いません。
+pub fn predict_clean_size(data: &[u8], options: &CleanOptions) -> Result<usize, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    plan_clean(data, options).map(|parts| clean_output_len(&parts))
+}
+
+/// 軽量化後にシグネチャの後へ出力するチャンクを順に列挙します
+fn plan_clean<'a>(data: &'a [u8], options: &CleanOptions) -> Result<Vec<Cow<'a, [u8]>>, Error> {
+    let chunks = parse_chunks(data)?;
+    let has_srgb = chunks.iter().any(|chunk| &chunk.chunk_type == b"sRGB");
+
+    // 色空間チャンクの矛盾を解消する場合に削除するチャンク
+    let mut drop_color: Vec<&[u8; 4]> = Vec::new();
+    if options.resolve_color_conflicts {
+        let conflicts = color_conflicts(&chunks);
+        let keep_icc = conflicts.contains(&ColorConflict::IccWithSrgb)
+            && !chunks
+                .iter()
+                .any(|chunk| &chunk.chunk_type == b"iCCP" && is_srgb_iccp(chunk.data));
+        if keep_icc {
+            drop_color.push(b"sRGB");
+        } else {
+            for conflict in &conflicts {
+                match conflict {
+                    ColorConflict::IccWithSrgb => drop_color.push(b"iCCP"),
+                    ColorConflict::GammaMismatch { .. } => drop_color.push(b"gAMA"),
+                    ColorConflict::ChromaticityMismatch => drop_color.push(b"cHRM"),
+                }
+            }
+        }
+    }
+
+    // 保持するdSIGの組の位置
+    let dsig_pair = match options.dsig {
+        DsigPolicy::Strip => None,
+        DsigPolicy::PreservePair => {
+            dsig_pair(&chunks).map(|[start, end]| [start.offset, end.offset])
+        }
+    };
+
+    let mut parts: Vec<Cow<[u8]>> = Vec::new();
+    let mut kept: Vec<&[u8]> = Vec::new();
+    let mut idat_merged = false;
+    // 直前までに現れたPLTEの項目数
+    let mut palette_entries = None;
+    // 直前までにeXIfが現れたか
+    let mut exif_seen = false;
+    let indexed = chunks
+        .first()
+        .is_some_and(|ihdr| &ihdr.chunk_type == b"IHDR" && ihdr.data.get(9) == Some(&3));
+
+    for chunk in &chunks {
+        if &chunk.chunk_type == b"PLTE" && palette_entries.is_none() {
+            palette_entries = Some(chunk.data.len() / 3);
+        }
+
+        let keep_chunk = match &chunk.chunk_type {
+            chunk_type if drop_color.contains(&chunk_type) => false,
+            b"hIST" => {
+                options.preserve_hist
+                    && palette_entries.is_some_and(|entries| chunk.data.len() == entries * 2)
+            }
+            b"sPLT" => options.preserve_splt && is_valid_splt(chunk.data),
+            // パレット画像のtRNSはPLTEより後にあり、項目数がPLTEを超えないものだけを残す
+            // （bKGDは常に削除するため、パレットの範囲の確認は不要）
+            b"tRNS" if indexed => palette_entries
+                .is_some_and(|entries| !chunk.data.is_empty() && chunk.data.len() <= entries),
+            b"oFFs" => options.preserve_offs && parse_offs(chunk.data).is_some(),
+            b"sCAL" => options.preserve_scal && parse_scal(chunk.data).is_some(),
+            b"sTER" => options.preserve_ster && parse_ster(chunk.data).is_some(),
+            b"tEXt" | b"zTXt" | b"iTXt" => {
+                let keyword = text_keyword(chunk);
+                options.preserve_text
+                    || options.preserve_xmp && keyword == Some(XMP_KEYWORD.as_bytes())
+                    || keyword.is_some_and(|keyword| {
+                        options
+                            .keep_text_keywords
+                            .iter()
+                            .any(|kept| kept.as_bytes() == keyword)
+                    })
+            }
+            b"dSIG" => dsig_pair.is_some_and(|pair| pair.contains(&chunk.offset)),
+            b"eXIf" if !options.preserve_exif && options.preserve_orientation => {
+                // デコーダーが参照する最初のeXIfのオリエンテーションのみを残す
+                if !exif_seen {
+                    if let Some(minimal) = minimal_orientation_exif(chunk.data) {
+                        let mut exif = Vec::with_capacity(minimal.len() + 12);
+                        write_chunk(&mut exif, b"eXIf", &minimal);
+                        parts.push(Cow::Owned(exif));
+                    }
+                }
+                false
+            }
+            b"eXIf" => options.preserve_exif,
+            b"IDAT" if options.merge_idat => {
+                if !idat_merged {
+                    parts.push(Cow::Owned(merged_idat(&chunks)));
+                    idat_merged = true;
+                }
+                false
+            }
+            b"iCCP" if options.replace_srgb_icc && is_srgb_iccp(chunk.data) => {
+                // sRGBチャンクに置き換え（レンダリングインテントは知覚的）
+                if !has_srgb {
+                    let mut srgb = Vec::with_capacity(13);
+                    write_chunk(&mut srgb, b"sRGB", &[0]);
+                    parts.push(Cow::Owned(srgb));
+                }
+                false
+            }
+            chunk_type => CRITICAL_CHUNKS
+                .iter()
+                .any(|critical| critical.as_bytes() == chunk_type),
+        };
+        exif_seen |= &chunk.chunk_type == b"eXIf";
+
+        // 同じ内容の付随チャンクは1つだけ残す
+        let duplicate = options.remove_duplicates
+            && chunk.chunk_type[0].is_ascii_lowercase()
+            && &chunk.chunk_type != b"dSIG"
+            && kept.contains(&chunk.bytes);
+
+        if keep_chunk && !duplicate {
+            let compressed = if options.compress_text && &chunk.chunk_type == b"tEXt" {
+                compress_text_chunk(chunk)?
+            } else {
+                None
+            };
+            parts.push(compressed.map_or(Cow::Borrowed(chunk.bytes), Cow::Owned));
+            kept.push(chunk.bytes);
+        }
+    }
+
+    Ok(parts)
+}
+
+/// eXIfのオリエンテーションのみを持つ最小限のTIFFデータを作成します
+///
+/// オリエンテーションがない場合や範囲外の場合は`None`を返します。
+fn minimal_orientation_exif(tiff: &[u8]) -> Option<Vec<u8>> {
+    let orientation =
+        crate::jpeg::extract_orientation_from_exif(tiff).and_then(Orientation::from_exif)?;
+    let mut builder = TiffBuilder::default();
+    builder
+        .ifd0
+        .push(Field::short(exif::TAG_ORIENTATION, orientation.to_exif()));
+    Some(builder.build(ByteOrder::LittleEndian))
+}
+
+/// sPLTチャンクの構造が正しいか判定します
+///
+/// パレット名（1〜79バイト）+ null + サンプル深度（8または16）+ 項目（6または10バイト）の繰り返し
+fn is_valid_splt(chunk_data: &[u8]) -> bool {
+    let Some(null_pos) = chunk_data.iter().position(|&b| b == 0) else {
+        return false;
+    };
+    let entry_size = match chunk_data.get(null_pos + 1) {
+        Some(8) => 6,
+        Some(16) => 10,
+        _ => return false,
+    };
+    (1..=79).contains(&null_pos) && (chunk_data.len() - null_pos - 2).is_multiple_of(entry_size)
+}
+
+/// tEXtチャンクをzTXtチャンクに圧縮します（小さくならない場合は`None`）
+fn compress_text_chunk(chunk: &Chunk) -> Result<Option<Vec<u8>>, Error> {
+    let Some(null_pos) = chunk.data.iter().position(|&b| b == 0) else {
+        return Ok(None);
+    };
+    let (keyword, text) = (&chunk.data[..null_pos], &chunk.data[null_pos + 1..]);
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(text)?;
+    let compressed = encoder.finish()?;
+
+    // zTXt: キーワード + null + 圧縮方式(0) + 圧縮したテキスト
+    if keyword.len() + 2 + compressed.len() >= chunk.data.len() {
+        return Ok(None);
+    }
+    let mut output = Vec::with_capacity(keyword.len() + 2 + compressed.len() + 12);
+    write_chunk_parts(&mut output, b"zTXt", &[keyword, &[0, 0], &compressed]);
+    Ok(Some(output))
+}
+
+/// シグネチャを含めた出力全体のバイト数
+fn clean_output_len(parts: &[Cow<[u8]>]) -> usize {
+    PNG_SIGNATURE.len() + parts.iter().map(|part| part.len()).sum::<usize>()
+}
+
+/// 複数のIDATチャンクを1つに連結します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - IDATを連結したPNG画像データ
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// - 8KBごとにIDATを出力するエンコーダーでは、チャンクごとに12バイトのヘッダーとCRCを削減できます
+/// - 圧縮データは変更しないため、画素は変化しません
+/// - 連結したIDATは最初のIDATの位置に書き込みます。PNGのチャンクの長さの上限
+///   （2^31-1バイト）を超える場合のみ複数のチャンクに分割します
+pub fn merge_idat(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    // PNGが正常にデコードできるか検証
+    validate_png_decode(data)?;
+
+    let chunks = parse_chunks(data)?;
+    let output = replace_idat(data, &chunks, &merged_idat(&chunks));
+
+    // 出力が有効なPNGか検証
+    validate_png_decode(&output)?;
+
+    Ok(output)
+}
+
+/// IDATの圧縮データを指定したレベルで圧縮し直します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `level` - zlibの圧縮レベル（0〜9、9が最大）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - IDATを圧縮し直したPNG画像データ（小さくならない場合は入力のまま）
+/// * `Err(Error)` - レベルが範囲外の場合や、IDATを展開できない場合
+///
+/// # Details
+/// - フィルタ済みの画像データを展開して圧縮し直すだけで、フィルタや画素は変更しません
+/// - 圧縮し直したIDATは[`merge_idat`]と同様に、最初のIDATの位置に1つのチャンクとして書き込みます
+/// - 展開するサイズはIHDRから求めたサイズを上限とします
+/// - APNGのフレーム（fdAT）は変更しません
+pub fn recompress(data: &[u8], level: u32) -> Result<Vec<u8>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+    if level > 9 {
+        return Err(Error::InvalidFormat(format!(
+            "Invalid compression level: {level}"
+        )));
+    }
+
+    // PNGが正常にデコードできるか検証
+    validate_png_decode(data)?;
+
+    let chunks = parse_chunks(data)?;
+    let expected = inflated_idat_size(&chunks)?;
+    let compressed: Vec<u8> = chunks
+        .iter()
+        .filter(|chunk| &chunk.chunk_type == b"IDAT")
+        .flat_map(|chunk| chunk.data.iter().copied())
+        .collect();
+
+    let mut decoder = ZlibDecoder::new(compressed.as_slice()).take(expected + 1);
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    let inflated = std::io::copy(&mut decoder, &mut encoder)
+        .map_err(|e| Error::InvalidFormat(format!("Invalid IDAT stream: {e}")))?;
+    if inflated != expected {
+        return Err(Error::InvalidFormat(format!(
+            "IDAT stream size mismatch: expected {expected} bytes, got {inflated}"
+        )));
+    }
+    let recompressed = encoder.finish()?;
+    if recompressed.len() >= compressed.len() {
+        return Ok(data.to_vec());
+    }
+
+    let output = replace_idat(data, &chunks, &idat_chunks([recompressed.as_slice()]));
+
+    // 出力が有効なPNGか検証
+    validate_png_decode(&output)?;
+
+    Ok(output)
+}
+
+/// すべてのIDATを、最初のIDATの位置に置いた`idat`（IDATチャンク列）に置き換えます
+fn replace_idat(data: &[u8], chunks: &[Chunk], idat: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&data[..8]);
+    let mut written = false;
+    for chunk in chunks {
+        if &chunk.chunk_type != b"IDAT" {
+            output.extend_from_slice(chunk.bytes);
+        } else if !written {
+            output.extend_from_slice(idat);
+            written = true;
+        }
+    }
+    // IEND以降のデータはそのまま残す
+    let end = chunks
+        .last()
+        .map_or(8, |chunk| chunk.offset + chunk.bytes.len());
+    output.extend_from_slice(&data[end..]);
+    output
+}
+
+/// すべてのIDATのデータを連結したIDATチャンクを作成します
+fn merged_idat(chunks: &[Chunk]) -> Vec<u8> {
+    idat_chunks(
+        chunks
+            .iter()
+            .filter(|chunk| &chunk.chunk_type == b"IDAT")
+            .map(|chunk| chunk.data),
+    )
+}
+
+/// 圧縮データをIDATチャンクに書き込みます（チャンクの長さの上限を超える場合のみ分割）
+fn idat_chunks<'a>(data: impl IntoIterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut group: Vec<&[u8]> = Vec::new();
+    let mut group_len = 0;
+    for part in data {
+        let mut rest = part;
+        while !rest.is_empty() {
+            let take = rest.len().min(MAX_CHUNK_LENGTH - group_len);
+            group.push(&rest[..take]);
+            group_len += take;
+            rest = &rest[take..];
+            if group_len == MAX_CHUNK_LENGTH {
+                write_chunk_parts(&mut output, b"IDAT", &group);
+                group.clear();
+                group_len = 0;
+            }
+        }
+    }
+    if !group.is_empty() || output.is_empty() {
+        write_chunk_parts(&mut output, b"IDAT", &group);
+    }
+    output
+}
+
+/// PNG画像の幅と高さをIHDRから読み取ります
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok((width, height))` - 画像の幅と高さ（ピクセル）
+/// * `Err(Error)` - PNGではない場合やIHDRが不正な場合
+///
+/// # Details
+/// 先頭のIHDRチャンクのみを読み取り、デコーダーの構築やIDATの検証は行いません。
+/// HTMLの`width`・`height`属性の生成などに低コストで利用できます。
+pub fn read_dimensions(data: &[u8]) -> Result<(u32, u32), Error> {
+    let ihdr = read_ihdr(data)?;
+    let width = u32::from_be_bytes([ihdr[0], ihdr[1], ihdr[2], ihdr[3]]);
+    let height = u32::from_be_bytes([ihdr[4], ihdr[5], ihdr[6], ihdr[7]]);
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidFormat("Invalid image dimensions".to_string()));
+    }
+
+    Ok((width, height))
+}
+
+/// PNG画像の寸法と画素の形式をIHDRから読み取ります
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(ImageInfo)` - 寸法と画素の形式
+/// * `Err(Error)` - PNGではない場合やIHDRが不正な場合
+///
+/// # Details
+/// - カラータイプからチャンネル数（グレースケール: 1、グレースケール+アルファ: 2、RGB: 3、RGBA: 4）を求めます
+/// - パレット画像はチャンネル数1で、ビット数はインデックスのビット深度です
+/// - `has_alpha`は[`has_transparency`]と同じ判定で、tRNSによる透明色も含みます
+pub fn read_info(data: &[u8]) -> Result<ImageInfo, Error> {
+    let (width, height) = read_dimensions(data)?;
+    let ihdr = read_ihdr(data)?;
+    let (bit_depth, color_type) = (ihdr[8], ihdr[9]);
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => {
+            return Err(Error::InvalidFormat(format!(
+                "Invalid color type {color_type}"
+            )))
+        }
+    };
+
+    Ok(ImageInfo {
+        format: ImageFormat::Png,
+        width,
+        height,
+        pixel_format: PixelFormat {
+            channels,
+            bits_per_channel: bit_depth,
+            has_alpha: has_transparency(data)?,
+            palette: color_type == 3,
+        },
+    })
+}
+
+/// PNG画像が透明度を持つか判定します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(true)` - アルファチャンネルを持つか、tRNSに不透明でないエントリがある場合
+/// * `Ok(false)` - 不透明な画像（JPEGに変換しても透明度が失われない）
+/// * `Err(Error)` - PNGではない場合やチャンク構造が不正な場合
+///
+/// # Details
+/// - カラータイプ4（グレースケール+アルファ）と6（RGBA）は、画素を展開せずに`true`を返します
+/// - パレット画像のtRNSは、255未満のエントリがある場合のみ`true`です
+/// - グレースケール・RGBのtRNSは透明色の指定のため`true`を返します
+pub fn has_transparency(data: &[u8]) -> Result<bool, Error> {
+    let color_type = read_ihdr(data)?[9];
+    if matches!(color_type, 4 | 6) {
+        return Ok(true);
+    }
+
+    let Some(trns) = parse_chunks(data)?
+        .into_iter()
+        .find(|chunk| &chunk.chunk_type == b"tRNS")
+    else {
+        return Ok(false);
+    };
+
+    Ok(match color_type {
+        // エントリがないパレットは不透明
+        3 => trns.data.iter().any(|&alpha| alpha < 255),
+        _ => !trns.data.is_empty(),
+    })
+}
+
+/// 先頭のIHDRチャンクのデータ（13バイト）を読み取ります
+fn read_ihdr(data: &[u8]) -> Result<&[u8], Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    // IHDRは必ず最初のチャンク
+    let chunk = read_chunk(data, 8)?;
+    if &chunk.chunk_type != b"IHDR" || chunk.data.len() != 13 {
+        return Err(Error::InvalidFormat("IHDR chunk not found".to_string()));
+    }
+
+    Ok(chunk.data)
+}
+
+/// PNG画像が仕様に準拠したチャンク構成か検査します
+///
+/// # Returns
+/// * `Ok(())` - 問題がない場合
+/// * `Err(Error::InvalidFormat)` - 問題の一覧（位置を含む）
+///
+/// # Details
+/// - 仕様（APNGと登録済みの拡張を含む）にないチャンクタイプ
+/// - IHDRが先頭にない、IENDが末尾にない
+/// - IDATが連続していない
+/// - PLTE・IDATとの前後関係が仕様と異なるチャンク
+pub fn check_conformance(data: &[u8]) -> Result<(), Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    let chunks = parse_chunks(data)?;
+    let mut issues = Vec::new();
+
+    if chunks.first().map(|chunk| &chunk.chunk_type) != Some(b"IHDR") {
+        issues.push("IHDR is not the first chunk".to_string());
+    }
+    match chunks.last() {
+        Some(chunk) if &chunk.chunk_type == b"IEND" => {
+            let end = chunk.offset + chunk.bytes.len();
+            if end < data.len() {
+                issues.push(format!("{} bytes after IEND", data.len() - end));
+            }
+        }
+        _ => issues.push("IEND chunk not found".to_string()),
+    }
+
+    let mut order = ChunkOrder::default();
+
+    for chunk in &chunks {
+        if !KNOWN_CHUNKS.contains(&&chunk.chunk_type) {
+            issues.push(format!(
+                "non-standard chunk {} at offset {}",
+                chunk.type_name(),
+                chunk.offset
+            ));
+            continue;
+        }
+        issues.extend(order.check(&chunk.chunk_type, chunk.offset));
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidFormat(format!(
+            "Non-conforming PNG: {}",
+            issues.join("; ")
+        )))
+    }
+}
+
+/// 軽量化の前後で画像データがバイト単位で変更されていないことを検証します
+///
+/// # Arguments
+/// * `original` - 軽量化前のPNG画像データ
+/// * `cleaned` - 軽量化後のPNG画像データ
+///
+/// # Returns
+/// * `Ok(())` - 画像データが一致する場合
+/// * `Err(Error::InvalidFormat)` - 一致しない場合（最初に異なるチャンクを含みます）
+///
+/// # Details
+/// IHDR・PLTE・tRNS・IDATの各チャンクが、長さ・CRCを含めて順序どおり完全に一致することを
+/// 確認します。軽量化関数は保持するチャンクをそのままコピーするため、
+/// 画素が変化しないことの証跡になります。
+pub fn verify_passthrough(original: &[u8], cleaned: &[u8]) -> Result<(), Error> {
+    compare_image_data(original, cleaned, false)
+}
+
+/// 画像データのチャンクを比較します
+///
+/// `merged_idat`の場合、IDATはチャンクの区切りを無視して連結した圧縮データを比較します。
+fn compare_image_data(original: &[u8], cleaned: &[u8], merged_idat: bool) -> Result<(), Error> {
+    let mut original_chunks = image_data_chunks(original)?;
+    let mut cleaned_chunks = image_data_chunks(cleaned)?;
+
+    if merged_idat {
+        let idat = |chunks: &[Chunk]| -> Vec<u8> {
+            chunks
+                .iter()
+                .filter(|chunk| &chunk.chunk_type == b"IDAT")
+                .flat_map(|chunk| chunk.data.iter().copied())
+                .collect()
+        };
+        if idat(&original_chunks) != idat(&cleaned_chunks) {
+            return Err(Error::InvalidFormat("IDAT data differs".to_string()));
+        }
+        original_chunks.retain(|chunk| &chunk.chunk_type != b"IDAT");
+        cleaned_chunks.retain(|chunk| &chunk.chunk_type != b"IDAT");
+    }
+
+    if original_chunks.len() != cleaned_chunks.len() {
+        return Err(Error::InvalidFormat(
+            "Number of image data chunks differs".to_string(),
+        ));
+    }
+    for (chunk, cleaned_chunk) in original_chunks.iter().zip(&cleaned_chunks) {
+        if chunk.bytes != cleaned_chunk.bytes {
+            return Err(Error::InvalidFormat(format!(
+                "{} at offset {} differs",
+                chunk.type_name(),
+                chunk.offset
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// 軽量化の前後で、デコードした画素が一致することを検証します
+///
+/// # Arguments
+/// * `original` - 元のPNG画像データ
+/// * `cleaned` - 軽量化後のPNG画像データ
+///
+/// # Returns
+/// * `Ok(())` - 画素が一致する場合
+/// * `Err(Error::PixelMismatch)` - 寸法・画素形式・画素が一致しない場合（最初に異なる画素の位置を含みます）
+/// * `Err(Error)` - デコードできない場合
+///
+/// # Details
+/// [`verify_passthrough`]と異なり、両方の画像を実際にデコードして比較します。
+/// パレットと透過（tRNS）を展開した値で比較するため、PLTE・tRNSの変化も検出できます。
+/// APNGは既定の画像（最初のフレーム）のみを比較し、gAMA・iCCPなどによる
+/// 色の解釈の違いは比較の対象外です。
+pub fn verify_pixels(original: &[u8], cleaned: &[u8]) -> Result<(), Error> {
+    let (original_info, original_pixels) = decode_pixels(original)?;
+    let (cleaned_info, cleaned_pixels) = decode_pixels(cleaned)?;
+
+    if (original_info.width, original_info.height) != (cleaned_info.width, cleaned_info.height) {
+        return Err(Error::PixelMismatch(format!(
+            "Dimensions differ: {}x{} and {}x{}",
+            original_info.width, original_info.height, cleaned_info.width, cleaned_info.height
+        )));
+    }
+    let original_format = (original_info.color_type, original_info.bit_depth);
+    let cleaned_format = (cleaned_info.color_type, cleaned_info.bit_depth);
+    if original_format != cleaned_format {
+        return Err(Error::PixelMismatch(format!(
+            "Pixel format differs: {:?} and {:?}",
+            original_format, cleaned_format
+        )));
+    }
+
+    crate::jpeg::compare_pixels(
+        &original_pixels,
+        &cleaned_pixels,
+        original_info.width as usize,
+        original_info.color_type.samples() * original_info.bit_depth as usize,
+    )
+}
+
+/// パレットと透過を展開して既定の画像をデコードします
+fn decode_pixels(data: &[u8]) -> Result<(png::OutputInfo, Vec<u8>), Error> {
+    let invalid = |e| Error::InvalidFormat(format!("Invalid PNG image data: {e}"));
+    let mut decoder = Decoder::new(Cursor::new(data));
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(invalid)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(invalid)?;
+    buffer.truncate(info.buffer_size());
+    Ok((info, buffer))
+}
+
+/// 画素の復元に関わるチャンクを出現順に抽出します
+fn image_data_chunks(data: &[u8]) -> Result<Vec<Chunk<'_>>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+    Ok(parse_chunks(data)?
+        .into_iter()
+        .filter(|chunk| IMAGE_DATA_CHUNKS.contains(&&chunk.chunk_type))
+        .collect())
+}
+
+/// PLTE・IDATとの前後関係の検査状態
+#[derive(Default)]
+struct ChunkOrder {
+    seen_plte: bool,
+    seen_idat: bool,
+    /// IDATの後に別のチャンクが現れた
+    idat_ended: bool,
+}
+
+impl ChunkOrder {
+    /// チャンクの位置が仕様どおりか検査し、問題があればその内容を返します
+    fn check(&mut self, chunk_type: &[u8; 4], offset: usize) -> Option<String> {
+        let name = String::from_utf8_lossy(chunk_type);
+
+        if chunk_type == b"IDAT" {
+            let consecutive = !self.idat_ended;
+            self.seen_idat = true;
+            return (!consecutive).then(|| format!("non-consecutive IDAT at offset {offset}"));
+        }
+        self.idat_ended |= self.seen_idat;
+
+        let issue =
+            if BEFORE_PLTE_CHUNKS.contains(&chunk_type) && (self.seen_plte || self.seen_idat) {
+                Some(format!(
+                    "{name} at offset {offset} must precede PLTE and IDAT"
+                ))
+            } else if BEFORE_IDAT_CHUNKS.contains(&chunk_type) && self.seen_idat {
+                Some(format!("{name} at offset {offset} must precede IDAT"))
+            } else {
+                None
+            };
+        if chunk_type == b"PLTE" {
+            self.seen_plte = true;
+        }
+        issue
+    }
+}
+
+/// デコーダーを使わずにPNG画像のチャンク構造を検証します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(StructureReport)` - 検証結果（問題があってもエラーにはなりません）
+/// * `Err(Error)` - PNGではない場合
+///
+/// # Details
+/// チャンクをIENDまで走査し、次の項目を検査します。
+/// - チャンクの長さ（データの終端を超えない、IHDRは13バイト、IENDは0バイト）とタイプ
+/// - すべてのチャンクのCRC
+/// - IHDRが先頭に1つだけある、IDATが連続している、PLTE・IDATとの前後関係
+/// - 未知の重要チャンク、パレット画像のPLTE、IDAT・IENDの有無
+///
+/// 「デコードできるか」とは別の、依存の少ない軽量な健全性チェックです。
+/// 仕様にない付随チャンクは問題として扱いません（[`check_conformance`]を参照）。
+pub fn validate_structure(data: &[u8]) -> Result<StructureReport, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    let mut report = StructureReport::default();
+    let mut order = ChunkOrder::default();
+    let mut palette_required = false;
+    let mut pos = 8;
+
+    while pos < data.len() {
+        if pos + 12 > data.len() {
+            report.push(pos, format!("chunk at offset {pos} is truncated"));
+            return Ok(report);
+        }
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let chunk_type: [u8; 4] = data[pos + 4..pos + 8].try_into().unwrap();
+        let name = String::from_utf8_lossy(&chunk_type).to_string();
+
+        if !chunk_type.iter().all(u8::is_ascii_alphabetic) {
+            report.push(pos, format!("invalid chunk type at offset {pos}"));
+            return Ok(report);
+        }
+        if length > i32::MAX as usize || pos + 12 + length > data.len() {
+            report.push(pos, format!("{name} at offset {pos} is truncated"));
+            return Ok(report);
+        }
+
+        let chunk_data = &data[pos + 8..pos + 8 + length];
+        let stored = u32::from_be_bytes(
+            data[pos + 8 + length..pos + 12 + length]
+                .try_into()
+                .unwrap(),
+        );
+        if stored != calculate_crc(&chunk_type, chunk_data) {
+            report.push(pos, format!("CRC mismatch in {name} at offset {pos}"));
+        }
+
+        if report.units == 0 && &chunk_type != b"IHDR" {
+            report.push(pos, "IHDR is not the first chunk");
+        }
+        match &chunk_type {
+            b"IHDR" if report.units > 0 => {
+                report.push(pos, format!("additional IHDR at offset {pos}"));
+            }
+            b"IHDR" if length != 13 => {
+                report.push(pos, format!("invalid IHDR length at offset {pos}"));
+            }
+            b"IHDR" => palette_required = chunk_data[9] == 3,
+            b"IEND" if length != 0 => {
+                report.push(pos, format!("IEND at offset {pos} is not empty"));
+            }
+            _ => {}
+        }
+        if chunk_type[0].is_ascii_uppercase() && !KNOWN_CHUNKS.contains(&&chunk_type) {
+            report.push(
+                pos,
+                format!("unknown critical chunk {name} at offset {pos}"),
+            );
+        }
+        if &chunk_type == b"IDAT" && palette_required && !order.seen_plte {
+            report.push(
+                pos,
+                format!("IDAT at offset {pos} precedes the required PLTE"),
+            );
+            palette_required = false;
+        }
+        if let Some(issue) = order.check(&chunk_type, pos) {
+            report.push(pos, issue);
+        }
+
+        report.units += 1;
+        pos += 12 + length;
+
+        if &chunk_type == b"IEND" {
+            if !order.seen_idat {
+                report.push(pos - 12 - length, "IDAT chunk not found");
+            }
+            report.complete = true;
+            report.trailing_bytes = data.len() - pos;
+            return Ok(report);
+        }
+    }
+
+    report.push(data.len(), "IEND chunk not found");
+    Ok(report)
+}
+
+/// 色空間チャンクの矛盾
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorConflict {
+    /// iCCPとsRGBが両方存在する
+    IccWithSrgb,
+    /// sRGBと、sRGBの値（45455）と異なるgAMAが存在する
+    GammaMismatch {
+        /// gAMAの値（100000倍した値）
+        gamma: u32,
+    },
+    /// sRGBと、sRGBの色度と異なるcHRMが存在する
+    ChromaticityMismatch,
+}
+
+/// sRGBのガンマ値（1/2.2を100000倍した値）
+const SRGB_GAMMA: u32 = 45455;
+/// sRGBの白色点と原色の色度（x, yを100000倍した値）
+const SRGB_CHROMATICITIES: [u32; 8] = [31270, 32900, 64000, 33000, 30000, 60000, 15000, 6000];
+/// sRGBの値とみなす誤差
+const SRGB_TOLERANCE: u32 = 100;
+/// ICCプロファイルの作成時に名前を付ける原色（白色点と原色の色度を100000倍した値）
+const KNOWN_PRIMARIES: &[(&str, [u32; 8])] = &[
+    ("sRGB", SRGB_CHROMATICITIES),
+    (
+        "Display P3",
+        [31270, 32900, 68000, 32000, 26500, 69000, 15000, 6000],
+    ),
+    (
+        "Adobe RGB (1998)",
+        [31270, 32900, 64000, 33000, 21000, 71000, 15000, 6000],
+    ),
+    (
+        "ITU-R BT.2020",
+        [31270, 32900, 70800, 29200, 17000, 79700, 13100, 4600],
+    ),
+];
+
+/// PNG画像の色空間チャンク（sRGB、iCCP、gAMA、cHRM）の矛盾を検査します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<ColorConflict>)` - 見つかった矛盾（矛盾がない場合は空）
+/// * `Err(Error)` - PNGではない場合やチャンク構造が不正な場合
+///
+/// # Details
+/// 矛盾する色空間チャンクはブラウザによって優先するチャンクが異なり、表示が一致しません。
+/// iCCPがある場合のgAMA・cHRMは代替情報として扱い、矛盾とはみなしません。
+/// [`CleanOptions::resolve_color_conflicts`]で軽量化と同時に解消できます。
+pub fn check_color_chunks(data: &[u8]) -> Result<Vec<ColorConflict>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    Ok(color_conflicts(&parse_chunks(data)?))
+}
+
+/// チャンクの一覧から色空間チャンクの矛盾を検出します
+fn color_conflicts(chunks: &[Chunk]) -> Vec<ColorConflict> {
+    let find = |chunk_type: &[u8; 4]| {
+        chunks
+            .iter()
+            .find(|chunk| &chunk.chunk_type == chunk_type)
+            .map(|chunk| chunk.data)
+    };
+    if find(b"sRGB").is_none() {
+        return Vec::new();
+    }
+
+    let mut conflicts = Vec::new();
+    if find(b"iCCP").is_some() {
+        conflicts.push(ColorConflict::IccWithSrgb);
+    }
+
+    let near_srgb = |value: u32, expected: u32| value.abs_diff(expected) <= SRGB_TOLERANCE;
+    if let Some(gama) = find(b"gAMA").filter(|gama| gama.len() == 4) {
+        let gamma = u32::from_be_bytes([gama[0], gama[1], gama[2], gama[3]]);
+        if !near_srgb(gamma, SRGB_GAMMA) {
+            conflicts.push(ColorConflict::GammaMismatch { gamma });
+        }
+    }
+    if let Some(chrm) = find(b"cHRM") {
+        let matches = chrm.len() == 32
+            && chrm
+                .chunks_exact(4)
+                .zip(SRGB_CHROMATICITIES)
+                .all(|(value, expected)| {
+                    near_srgb(u32::from_be_bytes(value.try_into().unwrap()), expected)
+                });
+        if !matches {
+            conflicts.push(ColorConflict::ChromaticityMismatch);
+        }
+    }
+
+    conflicts
+}
+
+/// gAMA・cHRMチャンクから最小限のICCプロファイルを作成します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some(Vec<u8>))` - 作成したICC v2プロファイル
+/// * `Ok(None)` - gAMAがない場合、iCCP・sRGBがすでにある場合、値が不正な場合
+/// * `Err(Error)` - PNGではない場合やチャンク構造が不正な場合
+///
+/// # Details
+/// - iCCPのみを解釈するビューアーで、ガンマ値と色度だけを持つ古いPNGを正しく表示するための機能です
+/// - トーンカーブの指数はgAMAの逆数（gAMAが45455なら2.2）です
+/// - cHRMが既知の原色（sRGB、Display P3、Adobe RGB (1998)、ITU-R BT.2020）に近い場合は
+///   規格の値を使用し、cHRMがない場合はsRGBの原色とみなします
+/// - グレースケールの画像では、トーンカーブのみのグレースケールのプロファイルを作成します
+pub fn synthesize_icc_profile(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    Ok(synthesize_icc(&parse_chunks(data)?).map(|(_, profile)| profile))
+}
+
+/// gAMA・cHRMチャンクから作成したICCプロファイルをiCCPチャンクとして追加します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - iCCPを追加したPNG画像データ（作成できない場合は元のデータのコピー）
+/// * `Err(Error)` - PNGではない場合やチャンク構造が不正な場合
+///
+/// # Details
+/// - プロファイルは[`synthesize_icc_profile`]と同じで、プロファイル名は`sRGB primaries, gamma 2.20`のような説明です
+/// - iCCPはPLTEまたは最初のIDATの直前に追加します
+/// - gAMA・cHRMは、iCCPを解釈しないデコーダーのための代替情報として残します
+pub fn embed_synthesized_icc(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+    let chunks = parse_chunks(data)?;
+    let Some((name, profile)) = synthesize_icc(&chunks) else {
+        return Ok(data.to_vec());
+    };
+
+    // iCCP: プロファイル名 + null + 圧縮方式(0) + 圧縮したプロファイル
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&profile)?;
+    let mut chunk_data = name.into_bytes();
+    chunk_data.extend_from_slice(&[0, 0]);
+    chunk_data.extend_from_slice(&encoder.finish()?);
+
+    let mut output = Vec::with_capacity(data.len() + chunk_data.len() + 12);
+    output.extend_from_slice(&data[..8]);
+    let mut written = false;
+    for chunk in &chunks {
+        if !written && matches!(&chunk.chunk_type, b"PLTE" | b"IDAT") {
+            write_chunk(&mut output, b"iCCP", &chunk_data);
+            written = true;
+        }
+        output.extend_from_slice(chunk.bytes);
+    }
+    if !written {
+        return Err(Error::InvalidFormat("IDAT chunk not found".to_string()));
+    }
+    // IEND以降のデータはそのまま残す
+    let end = chunks
+        .last()
+        .map_or(8, |chunk| chunk.offset + chunk.bytes.len());
+    output.extend_from_slice(&data[end..]);
+
+    // 出力が有効なPNGか検証
+    validate_png_decode(&output)?;
+
+    Ok(output)
+}
+
+/// gAMA・cHRMからプロファイル名とICCプロファイルを作成します
+fn synthesize_icc(chunks: &[Chunk]) -> Option<(String, Vec<u8>)> {
+    let find = |chunk_type: &[u8; 4]| {
+        chunks
+            .iter()
+            .find(|chunk| &chunk.chunk_type == chunk_type)
+            .map(|chunk| chunk.data)
+    };
+    if find(b"iCCP").is_some() || find(b"sRGB").is_some() {
+        return None;
+    }
+
+    let gama = find(b"gAMA").filter(|gama| gama.len() == 4)?;
+    let gama = u32::from_be_bytes([gama[0], gama[1], gama[2], gama[3]]);
+    if gama == 0 {
+        return None;
+    }
+    let gamma = 100000.0 / f64::from(gama);
+
+    // cHRMが既知の原色に近い場合は規格の値に置き換える
+    let chrm = find(b"cHRM").filter(|chrm| chrm.len() == 32).map(|chrm| {
+        let mut values = [0u32; 8];
+        for (value, bytes) in values.iter_mut().zip(chrm.chunks_exact(4)) {
+            *value = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+        values
+    });
+    let known = KNOWN_PRIMARIES.iter().find(|(_, primaries)| {
+        chrm.unwrap_or(SRGB_CHROMATICITIES)
+            .iter()
+            .zip(primaries)
+            .all(|(value, expected)| value.abs_diff(*expected) <= SRGB_TOLERANCE)
+    });
+    let (label, values) = match known {
+        Some((name, primaries)) => (*name, *primaries),
+        None => ("Custom", chrm?),
+    };
+    let xy = |index: usize| {
+        (
+            f64::from(values[index * 2]) / 100000.0,
+            f64::from(values[index * 2 + 1]) / 100000.0,
+        )
+    };
+
+    // カラータイプ0・4はグレースケール
+    let grayscale = find(b"IHDR")
+        .and_then(|ihdr| ihdr.get(9))
+        .is_some_and(|color_type| color_type & 2 == 0);
+    let (name, primaries) = if grayscale {
+        (format!("Grayscale, gamma {gamma:.2}"), None)
+    } else {
+        (
+            format!("{label} primaries, gamma {gamma:.2}"),
+            Some([xy(1), xy(2), xy(3)]),
+        )
+    };
+
+    let profile = crate::icc::synthesize_profile(&name, gamma, xy(0), primaries)?;
+    Some((name, profile))
+}
+
+/// PNG画像の衛生状態を検査します
+///
+/// [`LintOptions`]のデフォルト値で[`lint_with_options`]を実行します。
+pub fn lint(data: &[u8]) -> Vec<LintFinding> {
+    lint_with_options(data, &LintOptions::default())
+}
+
+/// オプションを指定してPNG画像の衛生状態を検査します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `options` - 検査の設定
+///
+/// # Returns
+/// 見つかった問題（出現順）。PNGではない場合や構造が壊れている場合も
+/// [`LintRule::MalformedStructure`]として報告し、エラーにはなりません。
+///
+/// # Details
+/// - チャンクの順序（IHDRが先頭、IDATが連続、PLTE・IDATとの前後関係）
+/// - 1つだけ置くべき付随チャンクの重複、CRCの不一致
+/// - テキストチャンクのキーワード（1〜79バイトのLatin-1、前後や連続する空白なし）と上限サイズ
+/// - 色空間情報の欠落と矛盾（[`check_color_chunks`]）
+pub fn lint_with_options(data: &[u8], options: &LintOptions) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        findings.push(LintFinding::new(
+            LintRule::MalformedStructure,
+            Some(0),
+            "Not a valid PNG file",
+        ));
+        return findings;
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos < data.len() {
+        let Ok(chunk) = read_chunk(data, pos) else {
+            findings.push(LintFinding::new(
+                LintRule::MalformedStructure,
+                Some(pos),
+                format!("chunk at offset {pos} is truncated"),
+            ));
+            break;
+        };
+        pos += chunk.bytes.len();
+        chunks.push(chunk);
+        if &chunk.chunk_type == b"IEND" {
+            break;
+        }
+    }
+    if chunks.last().map(|chunk| &chunk.chunk_type) != Some(b"IEND") {
+        findings.push(LintFinding::new(
+            LintRule::MalformedStructure,
+            None,
+            "IEND chunk not found",
+        ));
+    }
+
+    let mut order = ChunkOrder::default();
+    let mut seen: Vec<&[u8; 4]> = Vec::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let offset = Some(chunk.offset);
+        let name = chunk.type_name();
+
+        if index == 0 && &chunk.chunk_type != b"IHDR" {
+            findings.push(LintFinding::new(
+                LintRule::ChunkOrder,
+                offset,
+                "IHDR is not the first chunk",
+            ));
+        }
+        if let Some(issue) = order.check(&chunk.chunk_type, chunk.offset) {
+            findings.push(LintFinding::new(LintRule::ChunkOrder, offset, issue));
+        }
+
+        if UNIQUE_CHUNKS.contains(&&chunk.chunk_type) && seen.contains(&&chunk.chunk_type) {
+            findings.push(LintFinding::new(
+                LintRule::DuplicateChunk,
+                offset,
+                format!("duplicate {name} at offset {}", chunk.offset),
+            ));
+        }
+        seen.push(&chunk.chunk_type);
+
+        let stored = u32::from_be_bytes(chunk.bytes[chunk.bytes.len() - 4..].try_into().unwrap());
+        if stored != calculate_crc(&chunk.chunk_type, chunk.data) {
+            findings.push(LintFinding::new(
+                LintRule::CrcMismatch,
+                offset,
+                format!("CRC mismatch in {name} at offset {}", chunk.offset),
+            ));
+        }
+
+        if matches!(&chunk.chunk_type, b"tEXt" | b"zTXt" | b"iTXt") {
+            if let Some(issue) = keyword_issue(chunk.data) {
+                findings.push(LintFinding::new(
+                    LintRule::InvalidKeyword,
+                    offset,
+                    format!("{name} at offset {}: {issue}", chunk.offset),
+                ));
+            }
+            if chunk.data.len() > options.max_text_size {
+                findings.push(LintFinding::new(
+                    LintRule::OversizedText,
+                    offset,
+                    format!(
+                        "{name} at offset {} has {} bytes",
+                        chunk.offset,
+                        chunk.data.len()
+                    ),
+                ));
+            }
+        }
+    }
+
+    let has_color_info = chunks
+        .iter()
+        .any(|chunk| matches!(&chunk.chunk_type, b"sRGB" | b"gAMA" | b"iCCP" | b"cICP"));
+    if options.require_color_info && !has_color_info {
+        findings.push(LintFinding::new(
+            LintRule::MissingColorInfo,
+            None,
+            "no sRGB, gAMA, iCCP or cICP chunk",
+        ));
+    }
+    for conflict in color_conflicts(&chunks) {
+        let message = match conflict {
+            ColorConflict::IccWithSrgb => "iCCP and sRGB are both present".to_string(),
+            ColorConflict::GammaMismatch { gamma } => format!("gAMA {gamma} does not match sRGB"),
+            ColorConflict::ChromaticityMismatch => "cHRM does not match sRGB".to_string(),
+        };
+        findings.push(LintFinding::new(LintRule::ColorConflict, None, message));
+    }
+
+    findings
+}
+
+/// テキストチャンクのキーワードが仕様に違反していればその内容を返します
+fn keyword_issue(chunk_data: &[u8]) -> Option<&'static str> {
+    let Some(null_pos) = chunk_data.iter().position(|&b| b == 0) else {
+        return Some("keyword is not terminated");
+    };
+    let keyword = &chunk_data[..null_pos];
+
+    if keyword.is_empty() || keyword.len() > 79 {
+        Some("keyword must be 1-79 bytes")
+    } else if !keyword
+        .iter()
+        .all(|&b| matches!(b, 0x20..=0x7E | 0xA1..=0xFF))
+    {
+        Some("keyword contains non-printable characters")
+    } else if keyword.starts_with(b" ") || keyword.ends_with(b" ") {
+        Some("keyword has leading or trailing spaces")
+    } else if keyword.windows(2).any(|pair| pair == b"  ") {
+        Some("keyword has consecutive spaces")
+    } else {
+        None
+    }
+}
+
+/// iCCPチャンクのプロファイルがsRGBか判定します
+fn is_srgb_iccp(chunk_data: &[u8]) -> bool {
+    iccp_profile(chunk_data).is_some_and(|profile| crate::icc::is_srgb_profile(&profile))
+}
+
+/// iCCPチャンクのプロファイルを展開します
+pub(crate) fn iccp_profile(chunk_data: &[u8]) -> Option<Vec<u8>> {
+    // キーワード + null + 圧縮方式(1) + 圧縮データ
+    let null_pos = chunk_data.iter().position(|&b| b == 0)?;
+    let compressed = chunk_data.get(null_pos + 2..)?;
+
+    let mut profile = Vec::new();
+    let mut decoder = ZlibDecoder::new(compressed).take(MAX_ICC_PROFILE_SIZE);
+    decoder.read_to_end(&mut profile).ok()?;
+    Some(profile)
+}
+
+/// PNG画像から全てのテキストチャンク(tEXt、zTXt、iTXt)を読み取ります
+pub fn read_text_chunks(data: &[u8]) -> Result<Vec<TextChunk>, Error> {
+    // PNGシグネチャの確認
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    // PNGが正常にデコードできるか検証
+    validate_png_decode(data)?;
+
+    // 途切れたチャンク以降は読み取らない
+    Ok(chunks(data)?
+        .map_while(Result::ok)
+        .filter(|chunk| !chunk.data.is_empty())
+        .filter_map(|chunk| decode_text_chunk(&chunk))
+        .collect())
+}
+
+/// PNG画像の全てのテキストチャンクを読み取り、UTF-8として不正なテキストは指定したポリシーで修復します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `policy` - UTF-8として不正な場合の扱い
+///
+/// # Returns
+/// * `Ok(Vec<DecodedTextChunk>)` - チャンクごとのテキストと、適用した修復
+/// * `Err(Error)` - エラー（[`Utf8Policy::Reject`]でUTF-8として不正なテキストがある場合を含む）
+///
+/// # Details
+/// [`read_text_chunks`]と同じチャンクを読み取り、テキストの解釈だけが異なります。
+/// tEXt・zTXtも、これまでと同様にまずUTF-8として解釈します。
+pub fn read_text_chunks_with_policy(
+    data: &[u8],
+    policy: Utf8Policy,
+) -> Result<Vec<DecodedTextChunk>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    validate_png_decode(data)?;
+
+    chunks(data)?
+        .map_while(Result::ok)
+        .filter(|chunk| !chunk.data.is_empty())
+        .filter_map(|chunk| decode_text_chunk_raw(&chunk))
+        .map(|(keyword, text)| {
+            let text = policy.decode(&text, &format!("Text chunk \"{keyword}\""))?;
+            Ok(DecodedTextChunk { keyword, text })
+        })
+        .collect()
+}
+
+/// キーワードが一致する最初のテキストチャンク(tEXt、zTXt、iTXt)を読み取ります
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `keyword` - 探すキーワード
+///
+/// # Returns
+/// * `Some(TextChunk)` - 最初に見つかったチャンク
+/// * `None` - 見つからない場合やPNGではない場合
+///
+/// # Details
+/// 配信時に1つのキーワードだけを参照する用途向けに、[`read_text_chunks`]と異なり
+/// デコードによる検証を行わず、一致したチャンクで走査を打ち切ります。
+/// キーワードが一致しないチャンクは展開しません。
+pub fn find_text_chunk(data: &[u8], keyword: &str) -> Option<TextChunk> {
+    chunks(data)
+        .ok()?
+        .map_while(Result::ok)
+        .filter(|chunk| !chunk.data.is_empty() && text_keyword(chunk) == Some(keyword.as_bytes()))
+        .find_map(|chunk| decode_text_chunk(&chunk))
+}
+
+/// PNG画像からXMPパケットを読み取ります
+///
+/// # Returns
+/// * `Ok(Some(String))` - キーワード`XML:com.adobe.xmp`の最初のiTXtチャンクの内容
+/// * `Ok(None)` - XMPが存在しない
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// 仕様ではXMPは非圧縮のiTXtチャンクに格納されますが、圧縮されたiTXtや
+/// 同じキーワードのtEXt・zTXtチャンクも読み取ります。
+pub fn read_xmp(data: &[u8]) -> Result<Option<String>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    Ok(find_text_chunk(data, XMP_KEYWORD).map(|chunk| chunk.text))
+}
+
+/// PNG画像のXMPパケットを置き換えます
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `packet` - 新しいXMPパケット（空文字列の場合はXMPを削除）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - XMPを置き換えたPNG画像データ
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// - XMPは仕様どおり非圧縮・言語タグなしのiTXtチャンクとして書き込みます
+/// - 既存のXMPチャンクの位置に書き込み、2つ目以降のXMPチャンクは削除します
+/// - XMPがない場合は最初のIDATの直前に挿入します
+pub fn write_xmp(data: &[u8], packet: &str) -> Result<Vec<u8>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+
+    // PNGが正常にデコードできるか検証
+    validate_png_decode(data)?;
+
+    // キーワード + null + 圧縮フラグ(0) + 圧縮方式(0) + 言語タグ("") + null + 翻訳キーワード("") + null
+    let mut xmp = Vec::new();
+    if !packet.is_empty() {
+        write_chunk_parts(
+            &mut xmp,
+            b"iTXt",
+            &[XMP_KEYWORD.as_bytes(), &[0, 0, 0, 0, 0], packet.as_bytes()],
+        );
+    }
+
+    let chunks = parse_chunks(data)?;
+    let has_xmp = chunks
+        .iter()
+        .any(|chunk| text_keyword(chunk) == Some(XMP_KEYWORD.as_bytes()));
+
+    let mut output = Vec::with_capacity(data.len() + xmp.len());
+    output.extend_from_slice(&data[0..8]);
+    let mut written = false;
+    let mut end = 8;
+    for chunk in &chunks {
+        let is_xmp = text_keyword(chunk) == Some(XMP_KEYWORD.as_bytes());
+        if !written && (is_xmp || !has_xmp && &chunk.chunk_type == b"IDAT") {
+            output.extend_from_slice(&xmp);
+            written = true;
+        }
+        if !is_xmp {
+            output.extend_from_slice(chunk.bytes);
+        }
+        end = chunk.offset + chunk.bytes.len();
+    }
+    // IEND以降のデータはそのままコピー
+    output.extend_from_slice(&data[end..]);
+
+    // 出力が有効なPNGか検証
+    validate_png_decode(&output)?;
+
+    Ok(output)
+}
+
+/// 指定したキーワードのテキストチャンクを取り除きます
+pub(crate) fn remove_text_chunks(data: &[u8], keywords: &[&str]) -> Result<Vec<u8>, Error> {
+    let chunks = parse_chunks(data)?;
+    let mut output = data[0..8].to_vec();
+    let mut end = 8;
+    for chunk in &chunks {
+        let remove = text_keyword(chunk)
+            .is_some_and(|keyword| keywords.iter().any(|k| k.as_bytes() == keyword));
+        if !remove {
+            output.extend_from_slice(chunk.bytes);
+        }
+        end = chunk.offset + chunk.bytes.len();
     }
+    // IEND以降のデータはそのままコピー
+    output.extend_from_slice(&data[end..]);
+    Ok(output)
+}
 
-    // PNGが正常にデコードできるか検証
-    validate_png_decode(data)?;
+/// oFFsチャンクの位置の単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OffsetUnit {
+    /// ピクセル
+    Pixel,
+    /// マイクロメートル
+    Micrometer,
+}
 
-    let critical_set: HashSet<&str> = CRITICAL_CHUNKS.iter().cloned().collect();
-    let mut output = Vec::new();
+/// oFFsチャンクの画像の位置
+///
+/// ページなどの左上を原点とした、画像の左上の位置を表します。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageOffset {
+    /// 横方向の位置（右が正）
+    pub x: i32,
+    /// 縦方向の位置（下が正）
+    pub y: i32,
+    /// 単位
+    pub unit: OffsetUnit,
+}
 
-    // PNGシグネチャをコピー
-    output.extend_from_slice(&data[0..8]);
+/// sCALチャンクの寸法の単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScaleUnit {
+    /// メートル
+    Meter,
+    /// ラジアン
+    Radian,
+}
 
-    let mut pos = 8;
+/// sCALチャンクの被写体の物理的な寸法
+///
+/// 1ピクセルが表す幅と高さです。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalScale {
+    /// 1ピクセルの幅（正の値）
+    pub width: f64,
+    /// 1ピクセルの高さ（正の値）
+    pub height: f64,
+    /// 単位
+    pub unit: ScaleUnit,
+}
 
-    while pos < data.len() {
-        // チャンクの長さを読み取る
-        if pos + 4 > data.len() {
-            return Err(Error::ParseError("Unexpected end of PNG data".to_string()));
-        }
+impl PhysicalScale {
+    /// 幅と高さが有限の正の値か判定します
+    pub fn is_valid(&self) -> bool {
+        [self.width, self.height]
+            .iter()
+            .all(|value| value.is_finite() && *value > 0.0)
+    }
+}
 
-        let length =
-            u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+/// sTERチャンクのステレオ画像の配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StereoMode {
+    /// 交差法（左に右目用、右に左目用の画像を配置）
+    CrossFuse,
+    /// 平行法（左に左目用、右に右目用の画像を配置）
+    DivergingFuse,
+}
 
-        // チャンクタイプを読み取る
-        if pos + 8 > data.len() {
-            return Err(Error::ParseError("Unexpected end of PNG data".to_string()));
-        }
+/// PNG画像の位置（oFFsチャンク）を読み取ります
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some(ImageOffset))` - 画像の位置
+/// * `Ok(None)` - oFFsチャンクがない場合、または構造が正しくない場合
+/// * `Err(Error)` - PNGとして不正な場合
+///
+/// # Details
+/// oFFsは位置X（4バイト）+ 位置Y（4バイト）+ 単位（0: ピクセル、1: マイクロメートル）です。
+pub fn read_offset(data: &[u8]) -> Result<Option<ImageOffset>, Error> {
+    Ok(find_chunk_data(data, b"oFFs")?.and_then(parse_offs))
+}
 
-        let chunk_type = std::str::from_utf8(&data[pos + 4..pos + 8])
-            .map_err(|_| Error::ParseError("Invalid chunk type".to_string()))?;
+/// PNG画像の位置（oFFsチャンク）を書き込みます
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `offset` - 書き込む位置
+///
+/// # Details
+/// oFFsチャンクを置き換えます（ない場合は最初のIDATの直前に追加します）。
+pub fn write_offset(data: &[u8], offset: &ImageOffset) -> Result<Vec<u8>, Error> {
+    let mut chunk_data = Vec::with_capacity(9);
+    chunk_data.extend_from_slice(&offset.x.to_be_bytes());
+    chunk_data.extend_from_slice(&offset.y.to_be_bytes());
+    chunk_data.push(match offset.unit {
+        OffsetUnit::Pixel => 0,
+        OffsetUnit::Micrometer => 1,
+    });
+    replace_chunk(data, b"oFFs", &chunk_data)
+}
 
-        // チャンク全体のサイズ（長さ + タイプ + データ + CRC）
-        let chunk_size = 12 + length;
-        if pos + chunk_size > data.len() {
-            return Err(Error::ParseError("Chunk extends beyond file".to_string()));
-        }
+/// PNG画像の被写体の物理的な寸法（sCALチャンク）を読み取ります
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some(PhysicalScale))` - 1ピクセルの幅と高さ
+/// * `Ok(None)` - sCALチャンクがない場合、または構造が正しくない場合
+/// * `Err(Error)` - PNGとして不正な場合
+///
+/// # Details
+/// sCALは単位（1: メートル、2: ラジアン）+ 幅 + null + 高さで、幅と高さは
+/// `1.5e-6`のような正の浮動小数点数の文字列です。
+pub fn read_scale(data: &[u8]) -> Result<Option<PhysicalScale>, Error> {
+    Ok(find_chunk_data(data, b"sCAL")?.and_then(parse_scal))
+}
 
-        // 重要なチャンクのみコピー
-        if critical_set.contains(chunk_type) {
-            output.extend_from_slice(&data[pos..pos + chunk_size]);
-        }
+/// PNG画像の被写体の物理的な寸法（sCALチャンク）を書き込みます
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `scale` - 書き込む寸法
+///
+/// # Details
+/// - sCALチャンクを置き換えます（ない場合は最初のIDATの直前に追加します）
+/// - 幅と高さは値を再現できる最短の文字列で書き込みます
+/// - 幅と高さが有限の正の値でない場合は[`Error::InvalidFormat`]を返します
+pub fn write_scale(data: &[u8], scale: &PhysicalScale) -> Result<Vec<u8>, Error> {
+    if !scale.is_valid() {
+        return Err(Error::InvalidFormat(format!(
+            "Invalid physical scale: {} x {}",
+            scale.width, scale.height
+        )));
+    }
+    let unit = match scale.unit {
+        ScaleUnit::Meter => 1,
+        ScaleUnit::Radian => 2,
+    };
+    let chunk_data = format!("{}\0{}", scale.width, scale.height);
+    replace_chunk(data, b"sCAL", &[&[unit], chunk_data.as_bytes()].concat())
+}
 
-        pos += chunk_size;
+/// PNG画像のステレオ画像の配置（sTERチャンク）を読み取ります
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some(StereoMode))` - 左右の画像の配置
+/// * `Ok(None)` - sTERチャンクがない場合、または値が0・1以外の場合
+/// * `Err(Error)` - PNGとして不正な場合
+pub fn read_stereo(data: &[u8]) -> Result<Option<StereoMode>, Error> {
+    Ok(find_chunk_data(data, b"sTER")?.and_then(parse_ster))
+}
 
-        // IENDチャンクに到達したら終了
-        if chunk_type == "IEND" {
-            break;
-        }
+/// PNG画像のステレオ画像の配置（sTERチャンク）を書き込みます
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `mode` - 書き込む配置
+///
+/// # Details
+/// sTERチャンクを置き換えます（ない場合は最初のIDATの直前に追加します）。
+/// 左右の画像の幅や間隔は検証しません。
+pub fn write_stereo(data: &[u8], mode: StereoMode) -> Result<Vec<u8>, Error> {
+    let chunk_data = match mode {
+        StereoMode::CrossFuse => 0,
+        StereoMode::DivergingFuse => 1,
+    };
+    replace_chunk(data, b"sTER", &[chunk_data])
+}
+
+/// PNG画像のEXIF（eXIfチャンク）からオリエンテーションを読み取ります
+///
+/// # Returns
+/// * `Ok(Some(Orientation))` - オリエンテーション
+/// * `Ok(None)` - eXIfまたはオリエンテーションが存在しない、もしくは値が範囲外
+/// * `Err(Error)` - エラー
+///
+/// # Details
+/// [`crate::jpeg::read_orientation`]と同じく、IFD0のオリエンテーションタグを読み取ります。
+/// eXIfが複数ある場合は最初のチャンクを使用します。
+pub fn read_orientation(data: &[u8]) -> Result<Option<Orientation>, Error> {
+    Ok(find_chunk_data(data, b"eXIf")?
+        .and_then(crate::jpeg::extract_orientation_from_exif)
+        .and_then(Orientation::from_exif))
+}
+
+/// PNG画像の電子署名チャンク（dSIG）の組を探します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some([Chunk; 2]))` - IHDRの直後のdSIGと、IENDの直前のdSIG
+/// * `Ok(None)` - dSIGがない場合、または組になっていない場合
+/// * `Err(Error)` - PNGではない場合やチャンク構造が不正な場合
+///
+/// # Details
+/// dSIGがちょうど2つあり、1つ目がIHDRの直後、2つ目がIENDの直前にある場合のみ組とみなします。
+/// 署名の内容（PKCS#7）は検証しません。個々のdSIGは[`chunks`]と[`Chunk::is_dsig`]で列挙できます。
+pub fn find_dsig_pair(data: &[u8]) -> Result<Option<[Chunk<'_>; 2]>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
     }
 
-    // 出力が有効なPNGか検証
-    validate_png_decode(&output)?;
+    Ok(dsig_pair(&parse_chunks(data)?))
+}
 
-    Ok(output)
+/// チャンクの一覧からdSIGの組を探します
+fn dsig_pair<'a>(chunks: &[Chunk<'a>]) -> Option<[Chunk<'a>; 2]> {
+    let positions: Vec<usize> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| chunk.is_dsig())
+        .map(|(index, _)| index)
+        .collect();
+    let [start, end] = positions[..] else {
+        return None;
+    };
+
+    let after_ihdr = start == 1 && &chunks[0].chunk_type == b"IHDR";
+    let before_iend = chunks
+        .get(end + 1)
+        .is_some_and(|chunk| &chunk.chunk_type == b"IEND");
+    (after_ihdr && before_iend).then(|| [chunks[start], chunks[end]])
 }
 
-/// PNG画像から全てのテキストチャンク(tEXt、zTXt、iTXt)を読み取ります
-pub fn read_text_chunks(data: &[u8]) -> Result<Vec<TextChunk>, Error> {
-    // PNGシグネチャの確認
-    if data.len() < 8 || data[0..8] != [137, 80, 78, 71, 13, 10, 26, 10] {
+/// 指定した種類の最初のチャンクのデータを返します
+fn find_chunk_data<'a>(data: &'a [u8], chunk_type: &[u8; 4]) -> Result<Option<&'a [u8]>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
         return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
     }
+    Ok(parse_chunks(data)?
+        .into_iter()
+        .find(|chunk| &chunk.chunk_type == chunk_type)
+        .map(|chunk| chunk.data))
+}
 
-    // PNGが正常にデコードできるか検証
-    validate_png_decode(data)?;
-
-    let mut text_chunks = Vec::new();
-    let mut pos = 8;
+/// oFFsチャンクのデータを解釈します
+fn parse_offs(chunk_data: &[u8]) -> Option<ImageOffset> {
+    if chunk_data.len() != 9 {
+        return None;
+    }
+    let unit = match chunk_data[8] {
+        0 => OffsetUnit::Pixel,
+        1 => OffsetUnit::Micrometer,
+        _ => return None,
+    };
+    Some(ImageOffset {
+        x: i32::from_be_bytes(chunk_data[0..4].try_into().unwrap()),
+        y: i32::from_be_bytes(chunk_data[4..8].try_into().unwrap()),
+        unit,
+    })
+}
 
-    while pos < data.len() {
-        // チャンクの長さを読み取る
-        if pos + 4 > data.len() {
-            break;
+/// sCALチャンクのデータを解釈します
+fn parse_scal(chunk_data: &[u8]) -> Option<PhysicalScale> {
+    let (&unit, values) = chunk_data.split_first()?;
+    let unit = match unit {
+        1 => ScaleUnit::Meter,
+        2 => ScaleUnit::Radian,
+        _ => return None,
+    };
+    let null_pos = values.iter().position(|&b| b == 0)?;
+    let parse = |value: &[u8]| -> Option<f64> {
+        // 仕様で許される文字（符号・数字・小数点・指数）のみ受け付ける
+        if !value
+            .iter()
+            .all(|b| b.is_ascii_digit() || b"+-.eE".contains(b))
+        {
+            return None;
         }
+        std::str::from_utf8(value).ok()?.parse().ok()
+    };
+    let scale = PhysicalScale {
+        width: parse(&values[..null_pos])?,
+        height: parse(&values[null_pos + 1..])?,
+        unit,
+    };
+    scale.is_valid().then_some(scale)
+}
 
-        let length =
-            u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+/// sTERチャンクのデータを解釈します
+fn parse_ster(chunk_data: &[u8]) -> Option<StereoMode> {
+    match chunk_data {
+        [0] => Some(StereoMode::CrossFuse),
+        [1] => Some(StereoMode::DivergingFuse),
+        _ => None,
+    }
+}
 
-        // チャンクタイプを読み取る
-        if pos + 8 > data.len() {
-            break;
+/// 指定した種類のチャンクを置き換えます（ない場合は最初のIDATの直前に追加します）
+///
+/// 同じ種類のチャンクが複数ある場合は、すべて削除して1つだけ書き込みます。
+pub(crate) fn replace_chunk(
+    data: &[u8],
+    chunk_type: &[u8; 4],
+    chunk_data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+    let chunks = parse_chunks(data)?;
+
+    let mut output = Vec::with_capacity(data.len() + chunk_data.len() + 12);
+    output.extend_from_slice(&data[..8]);
+    let mut written = false;
+    for chunk in &chunks {
+        if &chunk.chunk_type == chunk_type {
+            continue;
         }
+        if !written && &chunk.chunk_type == b"IDAT" {
+            write_chunk(&mut output, chunk_type, chunk_data);
+            written = true;
+        }
+        output.extend_from_slice(chunk.bytes);
+    }
+    if !written {
+        return Err(Error::InvalidFormat("IDAT chunk not found".to_string()));
+    }
+    // IEND以降のデータはそのまま残す
+    let end = chunks
+        .last()
+        .map_or(8, |chunk| chunk.offset + chunk.bytes.len());
+    output.extend_from_slice(&data[end..]);
 
-        let chunk_type = &data[pos + 4..pos + 8];
+    // 出力が有効なPNGか検証
+    validate_png_decode(&output)?;
 
-        // チャンク全体のサイズ
-        let chunk_size = 12 + length;
-        if pos + chunk_size > data.len() {
-            break;
-        }
+    Ok(output)
+}
 
-        // テキストチャンクの場合
-        if (chunk_type == b"tEXt" || chunk_type == b"zTXt" || chunk_type == b"iTXt") && length > 0 {
-            let chunk_data = &data[pos + 8..pos + 8 + length];
-
-            match chunk_type {
-                b"tEXt" => {
-                    // null終端でキーワードとテキストを分離
-                    if let Some(null_pos) = chunk_data.iter().position(|&b| b == 0) {
-                        let keyword = String::from_utf8_lossy(&chunk_data[..null_pos]).to_string();
-                        let text = if null_pos + 1 < chunk_data.len() {
-                            String::from_utf8_lossy(&chunk_data[null_pos + 1..]).to_string()
-                        } else {
-                            String::new()
-                        };
-
-                        text_chunks.push(TextChunk { keyword, text });
-                    } else {
-                        // nullバイトがない場合、全体をテキストとして扱い、キーワードは空文字列
-                        let keyword = String::new();
-                        let text = String::from_utf8_lossy(chunk_data).to_string();
-                        text_chunks.push(TextChunk { keyword, text });
-                    }
-                }
-                b"zTXt" => {
-                    // zTXt: keyword + null + compression method + compressed text
-                    if let Some(null_pos) = chunk_data.iter().position(|&b| b == 0) {
-                        let keyword = String::from_utf8_lossy(&chunk_data[..null_pos]).to_string();
-
-                        if null_pos + 2 < chunk_data.len() {
-                            let compression_method = chunk_data[null_pos + 1];
-
-                            if compression_method == 0 {
-                                // deflate
-                                let compressed_data = &chunk_data[null_pos + 2..];
-
-                                // 圧縮されたデータを解凍
-                                let mut decoder = ZlibDecoder::new(compressed_data);
-                                let mut decompressed = Vec::new();
-
-                                if decoder.read_to_end(&mut decompressed).is_ok() {
-                                    let text = String::from_utf8_lossy(&decompressed).to_string();
-                                    text_chunks.push(TextChunk { keyword, text });
-                                }
-                            }
-                        }
-                    }
-                }
-                b"iTXt" => {
-                    // iTXt: keyword + null + compression flag + compression method + language tag + null + translated keyword + null + text
-                    if let Some(null_pos) = chunk_data.iter().position(|&b| b == 0) {
-                        let keyword = String::from_utf8_lossy(&chunk_data[..null_pos]).to_string();
-
-                        if null_pos + 3 < chunk_data.len() {
-                            let compression_flag = chunk_data[null_pos + 1];
-                            let _compression_method = chunk_data[null_pos + 2];
-
-                            // 言語タグの終了位置を探す
-                            let remaining = &chunk_data[null_pos + 3..];
-                            if let Some(lang_null_pos) = remaining.iter().position(|&b| b == 0) {
-                                // 翻訳済みキーワードの終了位置を探す
-                                let after_lang = &remaining[lang_null_pos + 1..];
-                                if let Some(trans_null_pos) =
-                                    after_lang.iter().position(|&b| b == 0)
-                                {
-                                    // テキスト部分
-                                    let text_data = &after_lang[trans_null_pos + 1..];
-
-                                    let text = if compression_flag == 1 {
-                                        // 圧縮されている場合
-                                        let mut decoder = ZlibDecoder::new(text_data);
-                                        let mut decompressed = Vec::new();
-
-                                        if decoder.read_to_end(&mut decompressed).is_ok() {
-                                            String::from_utf8_lossy(&decompressed).to_string()
-                                        } else {
-                                            continue;
-                                        }
-                                    } else {
-                                        // 圧縮されていない場合（UTF-8）
-                                        String::from_utf8_lossy(text_data).to_string()
-                                    };
-
-                                    text_chunks.push(TextChunk { keyword, text });
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
+/// テキストチャンクのキーワード部分を返します
+///
+/// nullバイトがないtEXtチャンクはキーワードを空文字列として扱います。
+pub(crate) fn text_keyword<'a>(chunk: &Chunk<'a>) -> Option<&'a [u8]> {
+    let null_pos = chunk.data.iter().position(|&b| b == 0);
+    match (&chunk.chunk_type, null_pos) {
+        (b"tEXt" | b"zTXt" | b"iTXt", Some(null_pos)) => Some(&chunk.data[..null_pos]),
+        (b"tEXt", None) => Some(&[]),
+        _ => None,
+    }
+}
 
-        pos += chunk_size;
+/// テキストチャンクを読み取ります（テキストチャンク以外や壊れている場合は`None`）
+pub(crate) fn decode_text_chunk(chunk: &Chunk) -> Option<TextChunk> {
+    let (keyword, text) = decode_text_chunk_raw(chunk)?;
+    Some(TextChunk {
+        keyword,
+        text: String::from_utf8_lossy(&text).to_string(),
+    })
+}
 
-        // IENDチャンクに到達したら終了
-        if chunk_type == b"IEND" {
-            break;
+/// テキストチャンクのキーワードと、展開したテキストのバイト列を返します
+fn decode_text_chunk_raw<'a>(chunk: &Chunk<'a>) -> Option<(String, Cow<'a, [u8]>)> {
+    let chunk_data = chunk.data;
+    let Some(null_pos) = chunk_data.iter().position(|&b| b == 0) else {
+        // nullバイトがないtEXtは全体をテキストとして扱い、キーワードは空文字列
+        return (&chunk.chunk_type == b"tEXt").then(|| (String::new(), Cow::Borrowed(chunk_data)));
+    };
+    let keyword = String::from_utf8_lossy(&chunk_data[..null_pos]).to_string();
+
+    match &chunk.chunk_type {
+        b"tEXt" => {
+            // null終端でキーワードとテキストを分離
+            Some((keyword, Cow::Borrowed(&chunk_data[null_pos + 1..])))
+        }
+        b"zTXt" => {
+            // zTXt: keyword + null + compression method + compressed text
+            if null_pos + 2 >= chunk_data.len() || chunk_data[null_pos + 1] != 0 {
+                return None;
+            }
+            // deflateで圧縮されたデータを解凍
+            let mut decoder = ZlibDecoder::new(&chunk_data[null_pos + 2..]);
+            let mut decompressed = Vec::new();
+            decoder.read_to_end(&mut decompressed).ok()?;
+            Some((keyword, Cow::Owned(decompressed)))
+        }
+        b"iTXt" => {
+            // iTXt: keyword + null + compression flag + compression method + language tag + null + translated keyword + null + text
+            if null_pos + 3 >= chunk_data.len() {
+                return None;
+            }
+            let compression_flag = chunk_data[null_pos + 1];
+
+            // 言語タグと翻訳済みキーワードの終了位置を探す
+            let remaining = &chunk_data[null_pos + 3..];
+            let lang_null_pos = remaining.iter().position(|&b| b == 0)?;
+            let after_lang = &remaining[lang_null_pos + 1..];
+            let trans_null_pos = after_lang.iter().position(|&b| b == 0)?;
+            let text_data = &after_lang[trans_null_pos + 1..];
+
+            let text = if compression_flag == 1 {
+                // 圧縮されている場合
+                let mut decoder = ZlibDecoder::new(text_data);
+                let mut decompressed = Vec::new();
+                decoder.read_to_end(&mut decompressed).ok()?;
+                Cow::Owned(decompressed)
+            } else {
+                // 圧縮されていない場合（UTF-8）
+                Cow::Borrowed(text_data)
+            };
+            Some((keyword, text))
         }
+        _ => None,
     }
-
-    Ok(text_chunks)
 }
 
 /// テキストチャンク追加によるファイルサイズの増加量を見積もります
@@ -242,13 +2154,93 @@ pub fn estimate_text_chunk(keyword: &str, text: &str) -> usize {
     4 + 4 + keyword_bytes.len() + 1 + text_bytes.len() + 4
 }
 
+/// zTXtチャンク追加によるファイルサイズの増加量の上限を見積もります
+///
+/// # Arguments
+/// * `keyword` - キーワード
+/// * `text` - テキスト内容
+///
+/// # Returns
+/// * 追加されるバイト数の上限
+///
+/// # Details
+/// zTXtチャンクの構造:
+/// - 長さ: 4バイト
+/// - タイプ ("zTXt"): 4バイト
+/// - キーワード: keyword.len()バイト
+/// - nullセパレータ: 1バイト
+/// - 圧縮方式: 1バイト
+/// - 圧縮テキスト: zlibの圧縮サイズの上限（compressBound）
+/// - CRC: 4バイト
+///
+/// 圧縮後のサイズは圧縮レベルや実装によって変わるため、圧縮できないデータでも
+/// 超えることのない上限を返します。
+pub fn estimate_ztxt_chunk(keyword: &str, text: &str) -> usize {
+    // 長さ(4) + タイプ(4) + キーワード + null(1) + 圧縮方式(1) + 圧縮テキスト + CRC(4)
+    4 + 4 + keyword.len() + 1 + 1 + zlib_compress_bound(text.len()) + 4
+}
+
+/// iTXtチャンク追加によるファイルサイズの増加量を見積もります
+///
+/// # Arguments
+/// * `keyword` - キーワード
+/// * `text` - テキスト内容（UTF-8）
+///
+/// # Returns
+/// * 追加されるバイト数
+///
+/// # Details
+/// 言語タグと翻訳キーワードが空で、テキストを圧縮しないiTXtチャンクのサイズです。
+/// - 長さ: 4バイト
+/// - タイプ ("iTXt"): 4バイト
+/// - キーワード: keyword.len()バイト
+/// - nullセパレータ: 1バイト
+/// - 圧縮フラグ・圧縮方式: 2バイト
+/// - 言語タグ・翻訳キーワードのnullセパレータ: 2バイト
+/// - テキストデータ: text.len()バイト
+/// - CRC: 4バイト
+pub fn estimate_itxt_chunk(keyword: &str, text: &str) -> usize {
+    // 長さ(4) + タイプ(4) + キーワード + null(1) + フラグ(2) + null(2) + テキスト + CRC(4)
+    4 + 4 + keyword.len() + 1 + 2 + 2 + text.len() + 4
+}
+
+/// zlibで圧縮した場合のサイズの上限（zlibのcompressBoundと同じ計算）
+fn zlib_compress_bound(len: usize) -> usize {
+    len + (len >> 12) + (len >> 14) + (len >> 25) + 13
+}
+
 /// PNG画像に新しいtEXtチャンクを追加します
 pub fn add_text_chunk(data: &[u8], keyword: &str, text: &str) -> Result<Vec<u8>, Error> {
+    add_text_chunk_with_options(data, keyword, text, &WriteOptions::default())
+}
+
+/// オプションを指定してPNG画像に新しいtEXtチャンクを追加します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+/// * `keyword` - キーワード（1〜79文字のラテン文字）
+/// * `text` - テキスト内容
+/// * `options` - CRCが壊れたチャンクの扱い
+pub fn add_text_chunk_with_options(
+    data: &[u8],
+    keyword: &str,
+    text: &str,
+    options: &WriteOptions,
+) -> Result<Vec<u8>, Error> {
     // PNGシグネチャの確認
-    if data.len() < 8 || data[0..8] != [137, 80, 78, 71, 13, 10, 26, 10] {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
         return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
     }
 
+    // 壊れた付随チャンクのCRCを修正
+    let repaired;
+    let data = if options.fix_crc {
+        repaired = fix_ancillary_crcs(data)?;
+        repaired.as_deref().unwrap_or(data)
+    } else {
+        data
+    };
+
     // PNGが正常にデコードできるか検証
     validate_png_decode(data)?;
 
@@ -269,7 +2261,10 @@ pub fn add_text_chunk(data: &[u8], keyword: &str, text: &str) -> Result<Vec<u8>,
         ));
     }
 
-    let mut output = Vec::new();
+    let text = options.text.apply(text)?;
+    let text = text.as_ref();
+
+    let mut output = Vec::with_capacity(data.len() + estimate_text_chunk(keyword, text));
     output.extend_from_slice(&data[0..8]); // PNGシグネチャ
 
     let mut pos = 8;
@@ -304,20 +2299,12 @@ pub fn add_text_chunk(data: &[u8], keyword: &str, text: &str) -> Result<Vec<u8>,
     // IENDチャンクの前までコピー
     output.extend_from_slice(&data[8..iend_start]);
 
-    // 新しいtEXtチャンクを作成
-    let mut chunk_data = Vec::new();
-    chunk_data.extend_from_slice(keyword.as_bytes());
-    chunk_data.push(0); // null separator
-    chunk_data.extend_from_slice(text.as_bytes());
-
-    // チャンクを書き込む
-    output.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes()); // 長さ
-    output.extend_from_slice(b"tEXt"); // タイプ
-    output.extend_from_slice(&chunk_data); // データ
-
-    // CRCを計算
-    let crc = calculate_crc(b"tEXt", &chunk_data);
-    output.extend_from_slice(&crc.to_be_bytes());
+    // 新しいtEXtチャンクを書き込む（キーワード + null + テキスト）
+    write_chunk_parts(
+        &mut output,
+        b"tEXt",
+        &[keyword.as_bytes(), &[0], text.as_bytes()],
+    );
 
     // IENDチャンク以降をコピー
     output.extend_from_slice(&data[iend_start..]);
@@ -328,6 +2315,175 @@ pub fn add_text_chunk(data: &[u8], keyword: &str, text: &str) -> Result<Vec<u8>,
     Ok(output)
 }
 
+/// PNGチャンク
+///
+/// 入力データを借用するため、列挙時にチャンクごとのコピーは発生しません。
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk<'a> {
+    /// チャンクタイプ
+    pub chunk_type: [u8; 4],
+    /// チャンク（長さフィールド）の開始位置
+    pub offset: usize,
+    /// チャンクデータ
+    pub data: &'a [u8],
+    /// 長さフィールドからCRCまでを含むチャンク全体
+    pub bytes: &'a [u8],
+}
+
+impl Chunk<'_> {
+    /// チャンクタイプを文字列として返します（不正な場合は置換文字を含みます）
+    pub fn type_name(&self) -> String {
+        String::from_utf8_lossy(&self.chunk_type).to_string()
+    }
+
+    /// 電子署名チャンク（dSIG）かどうかを返します
+    ///
+    /// 組になっているかは[`find_dsig_pair`]で確認できます。
+    pub fn is_dsig(&self) -> bool {
+        &self.chunk_type == b"dSIG"
+    }
+}
+
+/// 指定位置からチャンクを1つ読み取ります
+pub(crate) fn read_chunk(data: &[u8], pos: usize) -> Result<Chunk<'_>, Error> {
+    if pos + 8 > data.len() {
+        return Err(Error::ParseError("Unexpected end of PNG data".to_string()));
+    }
+
+    let length =
+        u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+    let chunk_type = [data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]];
+
+    // チャンク全体のサイズ（長さ + タイプ + データ + CRC）
+    let chunk_end = pos
+        .checked_add(12)
+        .and_then(|end| end.checked_add(length))
+        .filter(|&end| end <= data.len())
+        .ok_or_else(|| Error::ParseError("Chunk extends beyond file".to_string()))?;
+
+    Ok(Chunk {
+        chunk_type,
+        offset: pos,
+        data: &data[pos + 8..pos + 8 + length],
+        bytes: &data[pos..chunk_end],
+    })
+}
+
+/// チャンクを順に返すイテレータ
+///
+/// [`chunks`]で作成します。IENDの次で終了し、構造が壊れている位置で`Err`を1度返した後は
+/// `None`を返します。
+#[derive(Debug, Clone)]
+pub struct Chunks<'a> {
+    data: &'a [u8],
+    pos: usize,
+    done: bool,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<Chunk<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.pos >= self.data.len() {
+            return None;
+        }
+        let chunk = read_chunk(self.data, self.pos);
+        match &chunk {
+            Ok(chunk) => {
+                self.pos += chunk.bytes.len();
+                // IENDチャンクに到達したら終了
+                self.done = &chunk.chunk_type == b"IEND";
+            }
+            Err(_) => self.done = true,
+        }
+        Some(chunk)
+    }
+}
+
+/// シグネチャの直後からIENDまでのチャンクを、入力を借用したまま列挙します
+///
+/// # Arguments
+/// * `data` - PNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Chunks)` - チャンクを順に返すイテレータ
+/// * `Err(Error)` - PNGではない場合
+///
+/// # Details
+/// チャンクを読み進めるだけで、ベクタの確保やCRC・デコードによる検証は行いません。
+/// 必要なチャンクが見つかった時点で列挙を打ち切れるため、内容を調べるだけの処理に向いています。
+pub fn chunks(data: &[u8]) -> Result<Chunks<'_>, Error> {
+    if data.len() < 8 || data[0..8] != PNG_SIGNATURE {
+        return Err(Error::InvalidFormat("Not a valid PNG file".to_string()));
+    }
+    Ok(Chunks {
+        data,
+        pos: 8,
+        done: false,
+    })
+}
+
+/// シグネチャの直後からIENDまでのチャンクを列挙します
+pub(crate) fn parse_chunks(data: &[u8]) -> Result<Vec<Chunk<'_>>, Error> {
+    Chunks {
+        data,
+        pos: 8,
+        done: false,
+    }
+    .collect()
+}
+
+/// チャンクを書き込みます
+pub(crate) fn write_chunk(output: &mut Vec<u8>, chunk_type: &[u8; 4], chunk_data: &[u8]) {
+    write_chunk_parts(output, chunk_type, &[chunk_data]);
+}
+
+/// 複数の部分からなるデータのチャンクを書き込みます
+///
+/// データを連結した中間バッファを作らず、各部分をコピーしながらCRCを計算します。
+pub(crate) fn write_chunk_parts(output: &mut Vec<u8>, chunk_type: &[u8; 4], parts: &[&[u8]]) {
+    let length: usize = parts.iter().map(|part| part.len()).sum();
+    output.reserve(length + 12);
+    output.extend_from_slice(&(length as u32).to_be_bytes());
+    output.extend_from_slice(chunk_type);
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    for part in parts {
+        output.extend_from_slice(part);
+        hasher.update(part);
+    }
+    output.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+/// 付随チャンクのCRCを修正します
+///
+/// 修正が必要な場合は修正したデータを返します。
+/// 重要なチャンクのCRC不一致はエラーになります。
+fn fix_ancillary_crcs(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let mut repaired: Option<Vec<u8>> = None;
+
+    for chunk in parse_chunks(data)? {
+        let crc_pos = chunk.offset + chunk.bytes.len() - 4;
+        let stored = u32::from_be_bytes(data[crc_pos..crc_pos + 4].try_into().unwrap());
+        let actual = calculate_crc(&chunk.chunk_type, chunk.data);
+        if stored == actual {
+            continue;
+        }
+
+        if chunk.chunk_type[0].is_ascii_uppercase() {
+            return Err(Error::InvalidFormat(format!(
+                "CRC mismatch in critical chunk {}",
+                chunk.type_name()
+            )));
+        }
+        repaired.get_or_insert_with(|| data.to_vec())[crc_pos..crc_pos + 4]
+            .copy_from_slice(&actual.to_be_bytes());
+    }
+
+    Ok(repaired)
+}
+
 /// CRC-32を計算
 fn calculate_crc(chunk_type: &[u8], data: &[u8]) -> u32 {
     let mut hasher = crc32fast::Hasher::new();
@@ -336,8 +2492,147 @@ fn calculate_crc(chunk_type: &[u8], data: &[u8]) -> u32 {
     hasher.finalize()
 }
 
+/// 指定したレベルでPNGデータを検証
+///
+/// `progress`には検証済みの位置（`data`内のバイト数）を通知します。
+fn validate_png(
+    data: &[u8],
+    validation: Validation,
+    cancellation: Option<&CancellationToken>,
+    progress: &mut dyn FnMut(usize),
+) -> Result<(), Error> {
+    cancel::check(cancellation)?;
+    match validation {
+        Validation::None => Ok(()),
+        Validation::Header => validate_png_decode(data),
+        Validation::Inflate => {
+            validate_png_decode(data)?;
+            validate_idat_stream(data, cancellation, progress)
+        }
+        Validation::Full => {
+            validate_png_decode(data)?;
+            let invalid = |e| Error::InvalidFormat(format!("Invalid PNG image data: {e}"));
+            let reader = CancellableReader::new(data, cancellation).with_progress(progress);
+            let mut reader = Decoder::new(reader)
+                .read_info()
+                .map_err(|e| Error::InvalidFormat(format!("Invalid PNG: {e}")))?;
+            // 1行ずつデコードし、行ごとに中断を確認する
+            loop {
+                let row = reader.next_row();
+                cancel::check(cancellation)?;
+                if row.map_err(invalid)?.is_none() {
+                    break;
+                }
+            }
+            reader.finish().map_err(invalid)
+        }
+    }
+}
+
+/// IDATを展開し、展開後のサイズがIHDRから求めたサイズと一致するか検証
+///
+/// `progress`には展開済みの割合を`data`内の位置に換算して通知します。
+fn validate_idat_stream(
+    data: &[u8],
+    cancellation: Option<&CancellationToken>,
+    progress: &mut dyn FnMut(usize),
+) -> Result<(), Error> {
+    let chunks = parse_chunks(data)?;
+    let expected = inflated_idat_size(&chunks)?;
+
+    let mut compressed = Vec::new();
+    for chunk in chunks.iter().filter(|chunk| &chunk.chunk_type == b"IDAT") {
+        let stored = u32::from_be_bytes(chunk.bytes[chunk.bytes.len() - 4..].try_into().unwrap());
+        if stored != calculate_crc(b"IDAT", chunk.data) {
+            return Err(Error::InvalidFormat("IDAT CRC mismatch".to_string()));
+        }
+        compressed.extend_from_slice(chunk.data);
+    }
+    if compressed.is_empty() {
+        return Err(Error::InvalidFormat("IDAT chunk not found".to_string()));
+    }
+
+    // 画素バッファを確保せずに展開後のサイズを数える（余分なデータは上限で検出）
+    let mut decoder = ZlibDecoder::new(compressed.as_slice()).take(expected + 1);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut inflated = 0u64;
+    loop {
+        cancel::check(cancellation)?;
+        let read = decoder
+            .read(&mut buffer)
+            .map_err(|e| Error::InvalidFormat(format!("Invalid IDAT stream: {e}")))?;
+        if read == 0 {
+            break;
+        }
+        inflated += read as u64;
+        if expected > 0 {
+            let position = data.len() as u128 * inflated.min(expected) as u128 / expected as u128;
+            progress(position as usize);
+        }
+    }
+    if inflated != expected {
+        return Err(Error::InvalidFormat(format!(
+            "IDAT stream size mismatch: expected {expected} bytes, got {inflated}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// IHDRから展開後のIDATのサイズ（各行のフィルタタイプを含む）を求めます
+fn inflated_idat_size(chunks: &[Chunk]) -> Result<u64, Error> {
+    let ihdr = chunks
+        .first()
+        .filter(|chunk| &chunk.chunk_type == b"IHDR" && chunk.data.len() == 13)
+        .ok_or_else(|| Error::InvalidFormat("IHDR chunk not found".to_string()))?;
+    let width = u32::from_be_bytes(ihdr.data[0..4].try_into().unwrap()) as u64;
+    let height = u32::from_be_bytes(ihdr.data[4..8].try_into().unwrap()) as u64;
+    let bit_depth = ihdr.data[8] as u64;
+    let samples = match ihdr.data[9] {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(Error::InvalidFormat("Invalid color type".to_string())),
+    };
+    let interlaced = ihdr.data[12] == 1;
+
+    // フィルタタイプ(1) + 1行分のデータ。巨大な幅・高さでの桁あふれはエラーにする
+    let overflow = || Error::InvalidFormat("Image dimensions overflow".to_string());
+    let pass_size = |w: u64, h: u64| -> Result<u64, Error> {
+        if w == 0 || h == 0 {
+            return Ok(0);
+        }
+        (w * samples * bit_depth)
+            .div_ceil(8)
+            .checked_add(1)
+            .and_then(|row| row.checked_mul(h))
+            .ok_or_else(overflow)
+    };
+    if !interlaced {
+        return pass_size(width, height);
+    }
+    // Adam7の各パス（開始位置x, y, 間隔x, y）
+    const PASSES: [(u64, u64, u64, u64); 7] = [
+        (0, 0, 8, 8),
+        (4, 0, 8, 8),
+        (0, 4, 4, 8),
+        (2, 0, 4, 4),
+        (0, 2, 2, 4),
+        (1, 0, 2, 2),
+        (0, 1, 1, 2),
+    ];
+    PASSES.iter().try_fold(0u64, |total, &(x0, y0, dx, dy)| {
+        let size = pass_size(
+            width.saturating_sub(x0).div_ceil(dx),
+            height.saturating_sub(y0).div_ceil(dy),
+        )?;
+        total.checked_add(size).ok_or_else(overflow)
+    })
+}
+
 /// PNGデータが正常にデコードできるか検証
-fn validate_png_decode(data: &[u8]) -> Result<(), Error> {
+pub(crate) fn validate_png_decode(data: &[u8]) -> Result<(), Error> {
     let cursor = Cursor::new(data);
     let decoder = Decoder::new(cursor);
 
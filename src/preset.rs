@@ -0,0 +1,99 @@
+use crate::{jpeg, png};
+
+/// メタデータ軽量化のプリセット
+///
+/// 個々のオプションを指定する代わりに、用途に応じた方針を選択できます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CleanPreset {
+    /// 個人情報につながるメタデータをすべて削除します
+    ///
+    /// EXIFのオリエンテーションも削除するため、回転が必要な画像は
+    /// 事前に画素を回転させておく必要があります。
+    /// 表示に必要なICCプロファイルとAdobe色空間情報は保持します。
+    Privacy,
+    /// [`Privacy`](Self::Privacy)と同じく個人情報につながるメタデータを削除し、
+    /// C2PAマニフェスト（JPEGのAPP11のJUMBF）のみを保持します
+    ///
+    /// C2PAのハードバインディング（c2pa.hash.data）はマニフェスト以外のファイル全体を
+    /// 対象とするため、他のメタデータを削除するとマニフェストの署名は検証できなくなります。
+    /// 無効化されたかは[`jpeg::clean_metadata_with_report`]の
+    /// [`c2pa_invalidated`](jpeg::CleanReport::c2pa_invalidated)で確認でき、
+    /// 保持したマニフェストは再署名する際の来歴（ingredient）として利用できます。
+    /// PNGの軽量化はC2PAの保持に対応していないため、[`Privacy`](Self::Privacy)と同じです。
+    PrivacyWithProvenance,
+    /// Web配信向けの標準的な軽量化です
+    ///
+    /// デフォルトの動作（オリエンテーションとICCプロファイルを保持）に加えて、
+    /// sRGBのICCプロファイルを削除（PNGではsRGBチャンクに置換）します。
+    WebSafe,
+    /// 保存用にEXIF・XMPを保持し、サムネイルと重複したメタデータのみを削除します
+    Archival,
+}
+
+impl CleanPreset {
+    /// JPEG用のオプションを返します
+    pub fn jpeg_options(self) -> jpeg::CleanOptions {
+        let defaults = jpeg::CleanOptions::default();
+        match self {
+            CleanPreset::Privacy => jpeg::CleanOptions {
+                preserve_orientation: false,
+                ..defaults
+            },
+            CleanPreset::PrivacyWithProvenance => jpeg::CleanOptions {
+                preserve_c2pa: true,
+                preserve_orientation: false,
+                ..defaults
+            },
+            CleanPreset::WebSafe => jpeg::CleanOptions {
+                replace_srgb_icc: true,
+                ..defaults
+            },
+            CleanPreset::Archival => jpeg::CleanOptions {
+                preserve_c2pa: true,
+                preserve_exif: true,
+                preserve_xmp: true,
+                preserve_ducky: true,
+                remove_thumbnail: true,
+                remove_duplicates: true,
+                ..defaults
+            },
+        }
+    }
+
+    /// PNG用のオプションを返します
+    pub fn png_options(self) -> png::CleanOptions {
+        let defaults = png::CleanOptions::default();
+        match self {
+            CleanPreset::Privacy | CleanPreset::PrivacyWithProvenance => defaults,
+            CleanPreset::WebSafe => png::CleanOptions {
+                preserve_orientation: true,
+                replace_srgb_icc: true,
+                ..defaults
+            },
+            CleanPreset::Archival => png::CleanOptions {
+                preserve_text: true,
+                preserve_exif: true,
+                preserve_hist: true,
+                preserve_splt: true,
+                preserve_offs: true,
+                preserve_scal: true,
+                preserve_ster: true,
+                dsig: png::DsigPolicy::PreservePair,
+                remove_duplicates: true,
+                ..defaults
+            },
+        }
+    }
+}
+
+impl From<CleanPreset> for jpeg::CleanOptions {
+    fn from(preset: CleanPreset) -> Self {
+        preset.jpeg_options()
+    }
+}
+
+impl From<CleanPreset> for png::CleanOptions {
+    fn from(preset: CleanPreset) -> Self {
+        preset.png_options()
+    }
+}
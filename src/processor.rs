@@ -0,0 +1,117 @@
+use crate::{jpeg, png, CancellationToken, Error, ImageFormat, Progress};
+
+/// 多数の画像を同じオプションで軽量化するためのプロセッサー
+///
+/// 出力バッファを保持し、呼び出しごとに確保済みの容量を再利用します。
+/// 大量のファイルを順に処理するバッチで、出力のためのメモリ確保を抑えられます。
+/// 再利用するのは出力バッファのみで、セグメント・チャンクの一覧や検証でのデコードに
+/// 使うメモリは呼び出しごとに確保されます。
+/// スレッドごとに1つ作成して使用してください。
+#[derive(Debug, Clone, Default)]
+pub struct Processor {
+    /// JPEGの軽量化のオプション
+    pub jpeg_options: jpeg::CleanOptions,
+    /// PNGの軽量化のオプション
+    pub png_options: png::CleanOptions,
+    /// 再利用する出力バッファ
+    buffer: Vec<u8>,
+}
+
+impl Processor {
+    /// デフォルトのオプションでプロセッサーを作成します
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// オプションを指定してプロセッサーを作成します
+    pub fn with_options(jpeg_options: jpeg::CleanOptions, png_options: png::CleanOptions) -> Self {
+        Self {
+            jpeg_options,
+            png_options,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// JPEG画像のメタデータを軽量化します
+    ///
+    /// # Returns
+    /// * `Ok(&[u8])` - 軽量化されたJPEG画像データ（次の呼び出しまで有効）
+    /// * `Err(Error)` - [`jpeg::clean_metadata_with_options`]と同じエラー
+    pub fn clean_jpeg(&mut self, data: &[u8]) -> Result<&[u8], Error> {
+        jpeg::clean_metadata_into(data, &self.jpeg_options, &mut |_| {}, &mut self.buffer)?;
+        Ok(&self.buffer)
+    }
+
+    /// JPEG画像のメタデータを軽量化し、結果レポートを返します
+    pub fn clean_jpeg_with_report(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(&[u8], jpeg::CleanReport), Error> {
+        let report =
+            jpeg::clean_metadata_into(data, &self.jpeg_options, &mut |_| {}, &mut self.buffer)?;
+        Ok((&self.buffer, report))
+    }
+
+    /// PNG画像から重要なチャンク以外を削除します
+    ///
+    /// # Returns
+    /// * `Ok(&[u8])` - 軽量化されたPNG画像データ（次の呼び出しまで有効）
+    /// * `Err(Error)` - [`png::clean_chunks_with_options`]と同じエラー
+    pub fn clean_png(&mut self, data: &[u8]) -> Result<&[u8], Error> {
+        png::clean_chunks_into(data, &self.png_options, &mut |_| {}, &mut self.buffer)?;
+        Ok(&self.buffer)
+    }
+
+    /// 形式を判定して画像を軽量化します
+    ///
+    /// # Returns
+    /// * `Ok(&[u8])` - 軽量化された画像データ（次の呼び出しまで有効）
+    /// * `Err(Error)` - JPEG・PNG以外の形式や軽量化できない場合
+    pub fn clean(&mut self, data: &[u8]) -> Result<&[u8], Error> {
+        self.clean_with_progress(data, |_| {})
+    }
+
+    /// 形式を判定し、進捗を通知しながら画像を軽量化します
+    ///
+    /// 通知する内容は[`jpeg::clean_metadata_with_progress`]・
+    /// [`png::clean_chunks_with_progress`]と同じです。
+    pub fn clean_with_progress(
+        &mut self,
+        data: &[u8],
+        mut progress: impl FnMut(Progress),
+    ) -> Result<&[u8], Error> {
+        match ImageFormat::detect(data) {
+            Some(ImageFormat::Jpeg) => {
+                jpeg::clean_metadata_into(
+                    data,
+                    &self.jpeg_options,
+                    &mut progress,
+                    &mut self.buffer,
+                )?;
+            }
+            Some(ImageFormat::Png) => {
+                png::clean_chunks_into(data, &self.png_options, &mut progress, &mut self.buffer)?;
+            }
+            None => return Err(Error::InvalidFormat("Unsupported image format".to_string())),
+        }
+        Ok(&self.buffer)
+    }
+
+    /// JPEG・PNGのオプションにトークンがない場合に、指定したトークンを設定します
+    pub(crate) fn set_default_cancellation(&mut self, cancellation: Option<&CancellationToken>) {
+        if let Some(token) = cancellation {
+            self.jpeg_options
+                .cancellation
+                .get_or_insert_with(|| token.clone());
+            self.png_options
+                .cancellation
+                .get_or_insert_with(|| token.clone());
+        }
+    }
+
+    /// 出力バッファを取り出します（以降の呼び出しでは新たに確保します）
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn take_buffer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+}
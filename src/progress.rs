@@ -0,0 +1,72 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// 検証中に進捗を通知する間隔（バイト）
+pub(crate) const PROGRESS_INTERVAL: usize = 64 * 1024;
+
+/// 処理の段階
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProgressStage {
+    /// 入力の検証
+    ValidateInput,
+    /// 出力の書き込み
+    Write,
+    /// 出力の検証
+    ValidateOutput,
+}
+
+/// 処理の進捗
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// 現在の段階
+    pub stage: ProgressStage,
+    /// 段階内で処理済みのバイト数
+    pub processed: usize,
+    /// 段階内で処理するバイト数
+    pub total: usize,
+}
+
+impl Progress {
+    pub(crate) fn new(stage: ProgressStage, processed: usize, total: usize) -> Self {
+        Self {
+            stage,
+            processed,
+            total,
+        }
+    }
+
+    /// 段階内の進捗率（0.0〜1.0）
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.processed as f64 / self.total as f64
+        }
+    }
+}
+
+/// スレッド間で共有できる進捗のコールバック
+///
+/// 別スレッドで軽量化する`stream::CleanReader`や、オプションの構造体に保持する
+/// [`batch::clean_tree`](crate::batch::clean_tree)で使用します。
+/// クローンしたコールバックは同じ関数を呼び出します。
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    /// 関数からコールバックを作成します
+    pub fn new(callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    /// 進捗を通知します
+    pub fn report(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{jpeg, png, xmp, Error, ImageFormat};
+
+/// 来歴の各項目のXMPプロパティ名とPNGのtEXtチャンクのキーワード
+const FIELDS: [(&str, &str); 4] = [
+    ("SourceSystem", "Provenance Source System"),
+    ("AssetID", "Provenance Asset ID"),
+    ("PipelineVersion", "Provenance Pipeline Version"),
+    ("Timestamp", "Provenance Timestamp"),
+];
+
+/// 配信パイプラインでの画像の来歴
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvenanceInfo {
+    /// 画像を登録したシステム（CMSの名前など）
+    pub source_system: String,
+    /// 登録元のシステムでのアセットID
+    pub asset_id: String,
+    /// 画像を処理したパイプラインのバージョン
+    pub pipeline_version: String,
+    /// 処理した日時（RFC 3339形式を推奨）
+    pub timestamp: String,
+}
+
+impl ProvenanceInfo {
+    /// [`FIELDS`]と同じ順の値
+    fn values(&self) -> [&str; 4] {
+        [
+            &self.source_system,
+            &self.asset_id,
+            &self.pipeline_version,
+            &self.timestamp,
+        ]
+    }
+
+    /// [`FIELDS`]と同じ順の値から作成します（すべて空の場合は`None`）
+    fn from_values(values: [Option<String>; 4]) -> Option<Self> {
+        if values.iter().all(Option::is_none) {
+            return None;
+        }
+        let [source_system, asset_id, pipeline_version, timestamp] =
+            values.map(Option::unwrap_or_default);
+        Some(Self {
+            source_system,
+            asset_id,
+            pipeline_version,
+            timestamp,
+        })
+    }
+}
+
+/// 画像に来歴を書き込みます
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+/// * `info` - 書き込む来歴
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 来歴を書き込んだ画像データ
+/// * `Err(Error)` - 対応していない形式や、値をPNGのtEXtチャンクに書き込めない場合など
+///
+/// # Details
+/// - JPEGではXMPの`wim:SourceSystem`、`wim:AssetID`、`wim:PipelineVersion`、
+///   `wim:Timestamp`プロパティに書き込みます（利用者のコメントと競合しないようCOMは使用しません）
+/// - PNGでは`Provenance Source System`などのキーワードのtEXtチャンクとしてIENDの直前に書き込みます
+/// - 既存の来歴はすべて置き換え、空文字列の項目は削除します
+pub fn stamp_provenance(data: &[u8], info: &ProvenanceInfo) -> Result<Vec<u8>, Error> {
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => {
+            let mut packet = jpeg::read_xmp(data)?.unwrap_or_default();
+            for ((name, _), value) in FIELDS.iter().zip(info.values()) {
+                let value = Some(value).filter(|value| !value.is_empty());
+                packet = xmp::set_property(&packet, xmp::NS_WIM, name, value)?;
+            }
+            jpeg::write_xmp(data, &packet)
+        }
+        Some(ImageFormat::Png) => {
+            let keywords = FIELDS.map(|(_, keyword)| keyword);
+            let mut output = png::remove_text_chunks(data, &keywords)?;
+            for ((_, keyword), value) in FIELDS.iter().zip(info.values()) {
+                if !value.is_empty() {
+                    output = png::add_text_chunk(&output, keyword, value)?;
+                }
+            }
+            Ok(output)
+        }
+        None => Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    }
+}
+
+/// [`stamp_provenance`]で書き込んだ来歴を読み取ります
+///
+/// # Returns
+/// * `Ok(Some(ProvenanceInfo))` - 来歴（記録されていない項目は空文字列）
+/// * `Ok(None)` - 来歴が記録されていない
+/// * `Err(Error)` - 対応していない形式や構造が壊れている場合
+pub fn read_provenance(data: &[u8]) -> Result<Option<ProvenanceInfo>, Error> {
+    let values = match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => {
+            let packet = jpeg::read_xmp(data)?.unwrap_or_default();
+            FIELDS.map(|(name, _)| xmp::read_property(&packet, xmp::NS_WIM.0, name))
+        }
+        Some(ImageFormat::Png) => {
+            let chunks = png::read_text_chunks(data)?;
+            FIELDS.map(|(_, keyword)| {
+                chunks
+                    .iter()
+                    .find(|chunk| chunk.keyword == keyword)
+                    .map(|chunk| chunk.text.clone())
+            })
+        }
+        None => return Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    };
+
+    Ok(ProvenanceInfo::from_values(values))
+}
@@ -0,0 +1,212 @@
+use crate::jpeg::AppKind;
+use crate::{jpeg, png, Error, ImageFormat};
+use std::ops::Range;
+
+/// バイト範囲の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegionKind {
+    /// ファイルのシグネチャ（JPEGのSOI、PNGのシグネチャ）
+    Signature,
+    /// メタデータ（JPEGのAPP・COMセグメント、PNGの付随チャンク）
+    Metadata,
+    /// 画像データ（JPEGのテーブル・フレーム・スキャン、PNGの重要なチャンクとtRNS）
+    ImageData,
+    /// EOI・IEND以降の末尾データ
+    Trailing,
+}
+
+/// 種類を判定したバイト範囲
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// 範囲の種類
+    pub kind: RegionKind,
+    /// セグメント名（`"APP1 (EXIF)"`、`"DQT"`など）、チャンクタイプ、
+    /// `"Signature"`、スキャンデータの`"SOS"`、末尾データの`"Trailing"`のいずれか
+    pub name: String,
+    /// ファイル先頭からのバイト範囲
+    pub range: Range<usize>,
+}
+
+/// 画像のすべてのバイト範囲を種類ごとに分類します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// * `Ok(Vec<Region>)` - ファイル先頭から隙間なく並んだ範囲（セグメント・チャンク単位）
+/// * `Err(Error)` - 対応していない形式や構造が壊れている場合
+///
+/// # Details
+/// 16進ビューアーでの差分表示など、変更箇所の種類を判定する用途を想定しています。
+/// JPEGのスキャンデータはSOSからEOIまでを1つの範囲とし、スキャン間に置かれた
+/// APP・COMセグメントのみメタデータとして分割します。
+/// SOSより前のセグメント間にフィルバイト（`0xFF`の詰め物）がある場合は
+/// 構造が壊れているものとして`Err`を返し、スキャンデータ内のフィルバイトは
+/// `"SOS"`の範囲に含めるため、返す範囲に隙間は生じません。
+pub fn map_regions(data: &[u8]) -> Result<Vec<Region>, Error> {
+    let mut regions = match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => map_jpeg(data)?,
+        Some(ImageFormat::Png) => map_png(data)?,
+        None => return Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    };
+
+    let end = regions.last().map_or(0, |region| region.range.end);
+    if end < data.len() {
+        push(
+            &mut regions,
+            RegionKind::Trailing,
+            "Trailing",
+            end..data.len(),
+        );
+    }
+
+    Ok(regions)
+}
+
+/// セグメント・チャンクごとのバイト数の内訳
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    /// セグメント・チャンク単位の範囲（大きい順、同じ大きさはファイル内の順）
+    pub items: Vec<Region>,
+}
+
+impl SizeBreakdown {
+    /// 種類ごとのバイト数の合計
+    pub fn total(&self, kind: RegionKind) -> usize {
+        self.items
+            .iter()
+            .filter(|item| item.kind == kind)
+            .map(|item| item.range.len())
+            .sum()
+    }
+}
+
+/// 画像のバイト数をセグメント・チャンクごとに集計します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// * `Ok(SizeBreakdown)` - 大きい順に並べたセグメント・チャンク
+/// * `Err(Error)` - 対応していない形式や構造が壊れている場合
+///
+/// # Details
+/// [`map_regions`]の範囲を大きさの順に並べ替えます。ファイルサイズが大きい原因を
+/// 調べる用途を想定しており、種類ごとの合計は[`SizeBreakdown::total`]で取得できます。
+pub fn size_breakdown(data: &[u8]) -> Result<SizeBreakdown, Error> {
+    let mut items = map_regions(data)?;
+    items.sort_by_key(|item| std::cmp::Reverse(item.range.len()));
+    Ok(SizeBreakdown { items })
+}
+
+/// JPEGのセグメントを分類します
+fn map_jpeg(data: &[u8]) -> Result<Vec<Region>, Error> {
+    let (segments, sos_pos) = jpeg::parse_segments(data)?;
+    let sos_pos = sos_pos.ok_or_else(|| Error::ParseError("SOS marker not found".to_string()))?;
+
+    let mut regions = Vec::new();
+    push(&mut regions, RegionKind::Signature, "SOI", 0..2);
+    for segment in &segments {
+        let range = segment.offset..segment.offset + segment.bytes.len();
+        let name = jpeg_name(segment.marker, segment.payload());
+        push(&mut regions, jpeg_kind(segment.marker), name, range);
+    }
+
+    // スキャンデータ（途中のAPP・COMセグメントを除く）
+    let walk = jpeg::walk_scans(data, sos_pos);
+    let end = walk.eoi_end.unwrap_or(data.len());
+    let mut pos = sos_pos;
+    for &(marker, offset) in &walk.markers {
+        if jpeg_kind(marker) != RegionKind::Metadata || offset + 4 > data.len() {
+            continue;
+        }
+        let size = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        let segment_end = (offset + 2 + size).min(end);
+        let name = jpeg_name(
+            marker,
+            data.get(offset + 4..segment_end).unwrap_or_default(),
+        );
+        push(&mut regions, RegionKind::ImageData, "SOS", pos..offset);
+        push(
+            &mut regions,
+            RegionKind::Metadata,
+            name,
+            offset..segment_end,
+        );
+        pos = segment_end;
+    }
+    push(&mut regions, RegionKind::ImageData, "SOS", pos..end);
+
+    Ok(regions)
+}
+
+/// JPEGのマーカーの種類
+fn jpeg_kind(marker: u8) -> RegionKind {
+    match marker {
+        0xE0..=0xEF | 0xFE => RegionKind::Metadata,
+        _ => RegionKind::ImageData,
+    }
+}
+
+/// JPEGのセグメント名（APPセグメントは既知の種類を付加）
+fn jpeg_name(marker: u8, payload: &[u8]) -> String {
+    match marker {
+        0xC4 => "DHT".to_string(),
+        0xC8 => "JPG".to_string(),
+        0xCC => "DAC".to_string(),
+        0xC0..=0xCF => format!("SOF{}", marker - 0xC0),
+        0xDB => "DQT".to_string(),
+        0xDC => "DNL".to_string(),
+        0xDD => "DRI".to_string(),
+        0xDE => "DHP".to_string(),
+        0xDF => "EXP".to_string(),
+        0xE0..=0xEF => {
+            let kind = match jpeg::classify_app_segment(marker, payload) {
+                AppKind::Jfif => "JFIF",
+                AppKind::Jfxx => "JFXX",
+                AppKind::Exif => "EXIF",
+                AppKind::Xmp => "XMP",
+                AppKind::ExtendedXmp => "Extended XMP",
+                AppKind::Icc => "ICC",
+                AppKind::Mpf => "MPF",
+                AppKind::Jumbf => "JUMBF",
+                AppKind::Ducky => "Ducky",
+                AppKind::Photoshop => "Photoshop",
+                AppKind::Adobe => "Adobe",
+                AppKind::Unknown(_) => return format!("APP{}", marker - 0xE0),
+            };
+            format!("APP{} ({kind})", marker - 0xE0)
+        }
+        0xFE => "COM".to_string(),
+        _ => format!("0x{marker:02X}"),
+    }
+}
+
+/// PNGのチャンクを分類します
+fn map_png(data: &[u8]) -> Result<Vec<Region>, Error> {
+    let mut regions = Vec::new();
+    push(&mut regions, RegionKind::Signature, "Signature", 0..8);
+
+    for chunk in png::parse_chunks(data)? {
+        let kind = if chunk.chunk_type[0].is_ascii_uppercase() || &chunk.chunk_type == b"tRNS" {
+            RegionKind::ImageData
+        } else {
+            RegionKind::Metadata
+        };
+        let range = chunk.offset..chunk.offset + chunk.bytes.len();
+        push(&mut regions, kind, chunk.type_name(), range);
+    }
+
+    Ok(regions)
+}
+
+/// 空でない範囲を追加します
+fn push(regions: &mut Vec<Region>, kind: RegionKind, name: impl Into<String>, range: Range<usize>) {
+    if !range.is_empty() {
+        regions.push(Region {
+            kind,
+            name: name.into(),
+            range,
+        });
+    }
+}
@@ -0,0 +1,393 @@
+use crate::exif::{self, ByteOrder, Field, IfdKind, TiffBuilder};
+use crate::{jpeg, png, Error, ImageFormat};
+
+/// 1インチあたりのメートル
+const METERS_PER_INCH: f64 = 0.0254;
+
+/// EXIFのResolutionUnitの値
+const EXIF_UNIT_NONE: u32 = 1;
+const EXIF_UNIT_INCH: u32 = 2;
+const EXIF_UNIT_CENTIMETER: u32 = 3;
+
+/// 解像度の単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionUnit {
+    /// 単位なし（ピクセルの縦横比のみ）
+    AspectRatio,
+    /// インチ（JFIFの単位1、EXIFの単位2）
+    Inch,
+    /// センチメートル（JFIFの単位2、EXIFの単位3）
+    Centimeter,
+    /// メートル（PNGのpHYsの単位1）
+    Meter,
+}
+
+/// 画像の解像度（単位あたりのピクセル数）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Resolution {
+    /// 水平方向の解像度
+    pub x: f64,
+    /// 垂直方向の解像度
+    pub y: f64,
+    /// 単位
+    pub unit: ResolutionUnit,
+}
+
+impl Resolution {
+    /// DPI（1インチあたりのピクセル数）から作成します
+    pub fn from_dpi(x: f64, y: f64) -> Self {
+        Self {
+            x,
+            y,
+            unit: ResolutionUnit::Inch,
+        }
+    }
+
+    /// DPIに換算します（単位がない場合は`None`）
+    pub fn to_dpi(&self) -> Option<(f64, f64)> {
+        let scale = match self.unit {
+            ResolutionUnit::AspectRatio => return None,
+            ResolutionUnit::Inch => 1.0,
+            ResolutionUnit::Centimeter => 2.54,
+            ResolutionUnit::Meter => METERS_PER_INCH,
+        };
+        Some((self.x * scale, self.y * scale))
+    }
+
+    /// JFIFの密度から作成します
+    ///
+    /// # Arguments
+    /// * `units` - 0（縦横比のみ）、1（インチ）、2（センチメートル）
+    /// * `x` / `y` - 水平・垂直方向の密度
+    ///
+    /// 単位が不正な場合や密度が0の場合は`None`を返します。
+    pub fn from_jfif(units: u8, x: u16, y: u16) -> Option<Self> {
+        let unit = match units {
+            0 => ResolutionUnit::AspectRatio,
+            1 => ResolutionUnit::Inch,
+            2 => ResolutionUnit::Centimeter,
+            _ => return None,
+        };
+        (x > 0 && y > 0).then_some(Self {
+            x: x as f64,
+            y: y as f64,
+            unit,
+        })
+    }
+
+    /// JFIFの（単位, 水平密度, 垂直密度）に変換します
+    ///
+    /// メートル単位は、整数のDPI（pHYsの丸め誤差の範囲）に換算できる場合はインチに、
+    /// それ以外はセンチメートルに換算します。JFIFの密度は整数のため丸められます。
+    pub fn to_jfif(&self) -> (u8, u16, u16) {
+        let (units, x, y) = match self.unit {
+            ResolutionUnit::AspectRatio => (0, self.x, self.y),
+            ResolutionUnit::Inch => (1, self.x, self.y),
+            ResolutionUnit::Centimeter => (2, self.x, self.y),
+            ResolutionUnit::Meter => {
+                let (x, y) = (self.x * METERS_PER_INCH, self.y * METERS_PER_INCH);
+                // 96 DPIは3780ピクセル/メートルで記録され、換算すると96.012になる
+                let integral = |dpi: f64| (dpi - dpi.round()).abs() < 0.02;
+                if integral(x) && integral(y) {
+                    (1, x, y)
+                } else {
+                    (2, self.x / 100.0, self.y / 100.0)
+                }
+            }
+        };
+        let (x, y) = fit(x, y, u16::MAX as f64);
+        (units, x as u16, y as u16)
+    }
+
+    /// PNGのpHYsチャンクの値から作成します
+    ///
+    /// # Arguments
+    /// * `x` / `y` - 単位あたりのピクセル数
+    /// * `unit` - 0（縦横比のみ）、1（メートル）
+    ///
+    /// 単位が不正な場合や値が0の場合は`None`を返します。
+    pub fn from_phys(x: u32, y: u32, unit: u8) -> Option<Self> {
+        let unit = match unit {
+            0 => ResolutionUnit::AspectRatio,
+            1 => ResolutionUnit::Meter,
+            _ => return None,
+        };
+        (x > 0 && y > 0).then_some(Self {
+            x: x as f64,
+            y: y as f64,
+            unit,
+        })
+    }
+
+    /// PNGのpHYsチャンクの（水平, 垂直, 単位）に変換します
+    ///
+    /// インチ・センチメートル単位はメートルあたりのピクセル数に換算します
+    /// （72 DPIは2835ピクセル/メートル）。
+    pub fn to_phys(&self) -> (u32, u32, u8) {
+        let (unit, x, y) = match self.unit {
+            ResolutionUnit::AspectRatio => (0, self.x, self.y),
+            ResolutionUnit::Inch => (1, self.x / METERS_PER_INCH, self.y / METERS_PER_INCH),
+            ResolutionUnit::Centimeter => (1, self.x * 100.0, self.y * 100.0),
+            ResolutionUnit::Meter => (1, self.x, self.y),
+        };
+        let (x, y) = fit(x, y, i32::MAX as f64);
+        (x as u32, y as u32, unit)
+    }
+
+    /// 値が有限の正の数か
+    fn is_valid(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.x > 0.0 && self.y > 0.0
+    }
+}
+
+/// 縦横比を保ったまま1以上`max`以下の整数に丸めます
+fn fit(x: f64, y: f64, max: f64) -> (f64, f64) {
+    let scale = (max / x.max(y)).min(1.0);
+    let round = |value: f64| (value * scale).round().clamp(1.0, max);
+    (round(x), round(y))
+}
+
+/// 画像の解像度を読み取ります
+///
+/// # Arguments
+/// * `data` - JPEGまたはPNG画像のバイトデータ
+///
+/// # Returns
+/// * `Ok(Some(Resolution))` - 記録されている解像度
+/// * `Ok(None)` - 解像度が記録されていない場合
+/// * `Err(Error)` - 対応していない形式の場合など
+///
+/// # Details
+/// JPEGでは単位のあるJFIFの密度、EXIFのXResolution・YResolution・ResolutionUnit、
+/// 単位のないJFIFの縦横比（1:1以外）の順に採用します。PNGではpHYsチャンクを参照します。
+pub fn read_resolution(data: &[u8]) -> Result<Option<Resolution>, Error> {
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => {
+            let jfif = read_jfif(data)?;
+            if let Some(resolution) = jfif.filter(|r| r.unit != ResolutionUnit::AspectRatio) {
+                return Ok(Some(resolution));
+            }
+            if let Some(resolution) = read_exif(data)? {
+                return Ok(Some(resolution));
+            }
+            Ok(jfif.filter(|r| r.x != r.y))
+        }
+        Some(ImageFormat::Png) => Ok(png::parse_chunks(data)?
+            .iter()
+            .find(|chunk| &chunk.chunk_type == b"pHYs" && chunk.data.len() == 9)
+            .and_then(|chunk| {
+                let x = u32::from_be_bytes(chunk.data[0..4].try_into().unwrap());
+                let y = u32::from_be_bytes(chunk.data[4..8].try_into().unwrap());
+                Resolution::from_phys(x, y, chunk.data[8])
+            })),
+        None => Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    }
+}
+
+/// 画像に解像度を書き込みます
+///
+/// # Arguments
+/// * `data` - JPEGまたはPNG画像のバイトデータ
+/// * `resolution` - 書き込む解像度
+///
+/// # Details
+/// - JPEGではJFIFの密度を書き換え、EXIFがある場合（またはJFIFがない場合）は
+///   EXIFのXResolution・YResolution・ResolutionUnitも書き込みます
+/// - PNGではpHYsチャンクを置き換えます（ない場合は最初のIDATの直前に追加します）
+/// - 単位は書き込む形式に合わせて換算します
+pub fn write_resolution(data: &[u8], resolution: &Resolution) -> Result<Vec<u8>, Error> {
+    if !resolution.is_valid() {
+        return Err(Error::InvalidFormat(format!(
+            "Invalid resolution: {} x {}",
+            resolution.x, resolution.y
+        )));
+    }
+
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => write_jpeg(data, resolution),
+        Some(ImageFormat::Png) => write_png(data, resolution),
+        None => Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    }
+}
+
+/// 画像の解像度をDPIで設定します
+///
+/// # Arguments
+/// * `data` - JPEGまたはPNG画像のバイトデータ
+/// * `dpi` - 1インチあたりのピクセル数（水平・垂直共通）
+///
+/// # Details
+/// 形式を判定し、JPEGではJFIFの密度（インチ単位）とEXIFの解像度を、PNGではpHYs
+/// （メートル単位、72 DPIは2835）を書き込みます。単位の換算は[`write_resolution`]と同じです。
+pub fn set_dpi(data: &[u8], dpi: f64) -> Result<Vec<u8>, Error> {
+    write_resolution(data, &Resolution::from_dpi(dpi, dpi))
+}
+
+/// 画像の解像度を別の画像に引き継ぎます
+///
+/// # Arguments
+/// * `source` - 解像度を読み取る画像（JPEGまたはPNG）
+/// * `target` - 解像度を書き込む画像（JPEGまたはPNG）
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 解像度を書き込んだ`target`（`source`に解像度がない場合はそのままのコピー）
+/// * `Err(Error)` - 対応していない形式の場合など
+///
+/// # Details
+/// JPEGからPNGへの変換ではJFIF・EXIFの密度をpHYsに、PNGからJPEGではpHYsをJFIF・EXIFに換算します。
+pub fn transfer_resolution(source: &[u8], target: &[u8]) -> Result<Vec<u8>, Error> {
+    match read_resolution(source)? {
+        Some(resolution) => write_resolution(target, &resolution),
+        None => Ok(target.to_vec()),
+    }
+}
+
+/// JFIFのAPP0セグメントを探します
+fn find_jfif<'a>(segments: &[jpeg::Segment<'a>]) -> Option<jpeg::Segment<'a>> {
+    segments
+        .iter()
+        .find(|segment| {
+            segment.marker == 0xE0
+                && segment.payload().starts_with(b"JFIF\0")
+                && segment.payload().len() >= 12
+        })
+        .copied()
+}
+
+/// JFIFの密度を読み取ります
+fn read_jfif(data: &[u8]) -> Result<Option<Resolution>, Error> {
+    let (segments, _) = jpeg::parse_segments(data)?;
+    Ok(find_jfif(&segments).and_then(|segment| {
+        let payload = segment.payload();
+        Resolution::from_jfif(
+            payload[7],
+            u16::from_be_bytes([payload[8], payload[9]]),
+            u16::from_be_bytes([payload[10], payload[11]]),
+        )
+    }))
+}
+
+/// EXIFの解像度を読み取ります
+fn read_exif(data: &[u8]) -> Result<Option<Resolution>, Error> {
+    let Some(base) = exif::locate_tiff(data)? else {
+        return Ok(None);
+    };
+    let tiff = &data[base..];
+    let Some(walk) = exif::walk(tiff) else {
+        return Ok(None);
+    };
+
+    let rational = |tag| {
+        let entry = walk.find(IfdKind::Ifd0, tag)?;
+        walk.read_rationals(tiff, entry)?.first().copied()
+    };
+    let (Some(x), Some(y)) = (
+        rational(exif::TAG_X_RESOLUTION),
+        rational(exif::TAG_Y_RESOLUTION),
+    ) else {
+        return Ok(None);
+    };
+    // ResolutionUnitがない場合はインチ
+    let unit = walk
+        .find(IfdKind::Ifd0, exif::TAG_RESOLUTION_UNIT)
+        .and_then(|entry| walk.read_uint(tiff, entry))
+        .unwrap_or(EXIF_UNIT_INCH);
+    let unit = match unit {
+        EXIF_UNIT_NONE => ResolutionUnit::AspectRatio,
+        EXIF_UNIT_INCH => ResolutionUnit::Inch,
+        EXIF_UNIT_CENTIMETER => ResolutionUnit::Centimeter,
+        _ => return Ok(None),
+    };
+
+    let resolution = Resolution { x, y, unit };
+    Ok(resolution.is_valid().then_some(resolution))
+}
+
+/// JPEGのJFIFとEXIFに解像度を書き込みます
+fn write_jpeg(data: &[u8], resolution: &Resolution) -> Result<Vec<u8>, Error> {
+    // JPEGが正常にデコードできるか検証
+    jpeg::validate_jpeg_decode(data, None)?;
+
+    let (segments, _) = jpeg::parse_segments(data)?;
+    let mut output = data.to_vec();
+
+    // JFIF: 識別子(5) + バージョン(2) + 単位(1) + 水平密度(2) + 垂直密度(2)
+    let jfif = find_jfif(&segments);
+    if let Some(segment) = jfif {
+        let (units, x, y) = resolution.to_jfif();
+        let pos = segment.offset + 4 + 7;
+        output[pos] = units;
+        output[pos + 1..pos + 3].copy_from_slice(&x.to_be_bytes());
+        output[pos + 3..pos + 5].copy_from_slice(&y.to_be_bytes());
+    }
+
+    // EXIF
+    let has_exif = segments
+        .iter()
+        .any(|segment| segment.marker == 0xE1 && segment.payload().starts_with(b"Exif\0\0"));
+    if has_exif || jfif.is_none() {
+        output = jpeg::rewrite_exif_segment(&output, |tiff| {
+            let (byte_order, mut builder) = match tiff {
+                Some(tiff) => exif::to_builder(tiff)
+                    .ok_or_else(|| Error::ParseError("Invalid EXIF data".to_string()))?,
+                None => (ByteOrder::LittleEndian, TiffBuilder::default()),
+            };
+            apply_exif(&mut builder, resolution);
+            Ok(builder.build(byte_order))
+        })?;
+    }
+
+    // 出力が有効なJPEGか検証
+    jpeg::validate_jpeg_decode(&output, None)?;
+
+    Ok(output)
+}
+
+/// IFD0の解像度のタグを置き換えます
+fn apply_exif(builder: &mut TiffBuilder, resolution: &Resolution) {
+    let (unit, x, y) = match resolution.unit {
+        ResolutionUnit::AspectRatio => (EXIF_UNIT_NONE, resolution.x, resolution.y),
+        ResolutionUnit::Inch => (EXIF_UNIT_INCH, resolution.x, resolution.y),
+        ResolutionUnit::Centimeter => (EXIF_UNIT_CENTIMETER, resolution.x, resolution.y),
+        ResolutionUnit::Meter => (
+            EXIF_UNIT_CENTIMETER,
+            resolution.x / 100.0,
+            resolution.y / 100.0,
+        ),
+    };
+
+    builder.ifd0.retain(|field| {
+        !matches!(
+            field.tag,
+            exif::TAG_X_RESOLUTION | exif::TAG_Y_RESOLUTION | exif::TAG_RESOLUTION_UNIT
+        )
+    });
+    builder
+        .ifd0
+        .push(Field::rationals(exif::TAG_X_RESOLUTION, &[rational(x)]));
+    builder
+        .ifd0
+        .push(Field::rationals(exif::TAG_Y_RESOLUTION, &[rational(y)]));
+    builder
+        .ifd0
+        .push(Field::short(exif::TAG_RESOLUTION_UNIT, unit as u16));
+}
+
+/// 値を分母1または1000の有理数に変換します
+fn rational(value: f64) -> (u32, u32) {
+    if value.fract() == 0.0 && value <= u32::MAX as f64 {
+        (value as u32, 1)
+    } else {
+        ((value * 1000.0).round().min(u32::MAX as f64) as u32, 1000)
+    }
+}
+
+/// PNGのpHYsチャンクを書き込みます
+fn write_png(data: &[u8], resolution: &Resolution) -> Result<Vec<u8>, Error> {
+    let (x, y, unit) = resolution.to_phys();
+    let mut phys_data = Vec::with_capacity(9);
+    phys_data.extend_from_slice(&x.to_be_bytes());
+    phys_data.extend_from_slice(&y.to_be_bytes());
+    phys_data.push(unit);
+    png::replace_chunk(data, b"pHYs", &phys_data)
+}
@@ -0,0 +1,236 @@
+use crate::security::{scan_security, SecurityFinding};
+use crate::{jpeg, png, Error, ImageFormat};
+use std::ops::Range;
+
+/// 信頼できない入力を処理する際のオプション
+#[derive(Debug, Clone)]
+pub struct SanitizeOptions {
+    /// 入力ファイルの最大バイト数
+    pub max_file_size: usize,
+    /// 幅・高さの最大値（ピクセル）
+    pub max_dimension: u32,
+    /// 総ピクセル数の最大値
+    pub max_pixels: u64,
+    /// EOI/IEND以降の末尾データを切り詰める（`false`の場合はエラー）
+    pub truncate_trailing_data: bool,
+    /// メタデータを削除する（`jpeg::clean_metadata`/`png::clean_chunks`）
+    pub clean_metadata: bool,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: 50 * 1024 * 1024,
+            max_dimension: 16384,
+            max_pixels: 100_000_000,
+            truncate_trailing_data: true,
+            clean_metadata: true,
+        }
+    }
+}
+
+/// サニタイズの結果レポート
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SanitizeReport {
+    /// 検出された画像形式のMIMEタイプ
+    pub mime_type: &'static str,
+    /// 画像の幅
+    pub width: u32,
+    /// 画像の高さ
+    pub height: u32,
+    /// 入力のバイト数
+    pub input_size: usize,
+    /// 出力のバイト数
+    pub output_size: usize,
+    /// 切り詰めた末尾データのバイト数
+    pub trailing_bytes_removed: usize,
+    /// 入力に対するセキュリティスキャンの検出結果
+    pub findings: Vec<SecurityFinding>,
+}
+
+/// アップロードされた画像を検証・無害化します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+/// * `options` - リソース制限と処理内容の指定
+///
+/// # Returns
+/// * `Ok((Vec<u8>, SanitizeReport))` - 無害化された画像データとレポート
+/// * `Err(Error)` - 受け入れられない入力（`LimitExceeded`は制限超過）
+///
+/// # Details
+/// 1. ファイルサイズの制限を確認
+/// 2. デコードせずに構造を検査し、認識できない形式・壊れた構造・重複した
+///    クリティカルチャンクを拒否
+/// 3. EOI/IEND以降の末尾データを切り詰め
+/// 4. 画像サイズの制限を確認
+/// 5. メタデータを削除
+/// 6. 残したICCプロファイル・JFIFセグメントに他形式のシグネチャが含まれる場合は
+///    セグメント（チャンク）ごと削除
+/// 7. 出力を再検査し、問題が残っていれば拒否
+pub fn sanitize(
+    data: &[u8],
+    options: &SanitizeOptions,
+) -> Result<(Vec<u8>, SanitizeReport), Error> {
+    if data.len() > options.max_file_size {
+        return Err(Error::LimitExceeded(format!(
+            "File size {} exceeds {} bytes",
+            data.len(),
+            options.max_file_size
+        )));
+    }
+
+    let mut report = SanitizeReport {
+        input_size: data.len(),
+        findings: scan_security(data).findings,
+        ..Default::default()
+    };
+
+    // 構造上の問題は修復せずに拒否する
+    let mut trailing_offset = None;
+    for finding in &report.findings {
+        match finding {
+            SecurityFinding::UnrecognizedFormat
+            | SecurityFinding::MalformedStructure { .. }
+            | SecurityFinding::DuplicateCritical { .. }
+            | SecurityFinding::OverlappingSegments { .. } => {
+                return Err(Error::InvalidFormat(format!(
+                    "Rejected by structural validation: {finding:?}"
+                )));
+            }
+            SecurityFinding::TrailingData { offset, .. } => trailing_offset = Some(*offset),
+            _ => {}
+        }
+    }
+
+    let truncated = match trailing_offset {
+        Some(offset) if options.truncate_trailing_data => {
+            report.trailing_bytes_removed = data.len() - offset;
+            &data[..offset]
+        }
+        Some(_) => {
+            return Err(Error::InvalidFormat(
+                "Trailing data after end of image".to_string(),
+            ))
+        }
+        None => data,
+    };
+
+    let format = ImageFormat::detect(truncated)
+        .ok_or_else(|| Error::InvalidFormat("Unsupported image format".to_string()))?;
+    report.mime_type = format.mime_type();
+
+    let (width, height) = read_dimensions(truncated, format)?;
+    if width > options.max_dimension || height > options.max_dimension {
+        return Err(Error::LimitExceeded(format!(
+            "Image dimensions {width}x{height} exceed {}",
+            options.max_dimension
+        )));
+    }
+    if width as u64 * height as u64 > options.max_pixels {
+        return Err(Error::LimitExceeded(format!(
+            "Image has {} pixels, exceeding {}",
+            width as u64 * height as u64,
+            options.max_pixels
+        )));
+    }
+    report.width = width;
+    report.height = height;
+
+    let output = match (options.clean_metadata, format) {
+        (true, ImageFormat::Png) => png::clean_chunks(truncated)?,
+        (true, ImageFormat::Jpeg) => jpeg::clean_metadata(truncated)?,
+        (false, _) => truncated.to_vec(),
+    };
+
+    // 出力に問題が残っていないことを確認
+    let output = strip_signature_payloads(output, format)?;
+    let remaining = scan_security(&output).findings;
+    if !remaining.is_empty() {
+        return Err(Error::InvalidFormat(format!(
+            "Sanitized output still has findings: {remaining:?}"
+        )));
+    }
+
+    report.output_size = output.len();
+
+    Ok((output, report))
+}
+
+/// 他形式のシグネチャを含むICCプロファイル・JFIFのセグメント（チャンク）を削除します
+///
+/// 分割されたICCプロファイルは、いずれかのセグメントに含まれる場合にすべて削除します。
+/// Adobeセグメントなどその他の箇所のシグネチャは削除せず、再検査で拒否されます。
+fn strip_signature_payloads(data: Vec<u8>, format: ImageFormat) -> Result<Vec<u8>, Error> {
+    let offsets: Vec<usize> = scan_security(&data)
+        .findings
+        .iter()
+        .filter_map(|finding| match finding {
+            SecurityFinding::EmbeddedSignature { offset, .. } => Some(*offset),
+            _ => None,
+        })
+        .collect();
+    if offsets.is_empty() {
+        return Ok(data);
+    }
+    let contains_signature =
+        |range: &Range<usize>| offsets.iter().any(|offset| range.contains(offset));
+
+    let mut removed: Vec<Range<usize>> = Vec::new();
+    if format == ImageFormat::Png {
+        for chunk in png::parse_chunks(&data)? {
+            let range = chunk.offset..chunk.offset + chunk.bytes.len();
+            if &chunk.chunk_type == b"iCCP" && contains_signature(&range) {
+                removed.push(range);
+            }
+        }
+    } else {
+        let (segments, _) = jpeg::parse_segments(&data)?;
+        let range = |segment: &jpeg::Segment| segment.offset..segment.offset + segment.bytes.len();
+        let is_icc = |segment: &jpeg::Segment| {
+            segment.marker == 0xE2 && segment.payload().starts_with(b"ICC_PROFILE\0")
+        };
+        let icc_has_signature = segments
+            .iter()
+            .any(|segment| is_icc(segment) && contains_signature(&range(segment)));
+        for segment in &segments {
+            let jfif = segment.marker == 0xE0 && segment.payload().starts_with(b"JFIF\0");
+            if (icc_has_signature && is_icc(segment))
+                || (jfif && contains_signature(&range(segment)))
+            {
+                removed.push(range(segment));
+            }
+        }
+    }
+
+    let mut output = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    for range in removed {
+        output.extend_from_slice(&data[pos..range.start]);
+        pos = range.end;
+    }
+    output.extend_from_slice(&data[pos..]);
+    Ok(output)
+}
+
+/// デコーダーを使わずに画像の幅と高さを読み取ります
+fn read_dimensions(data: &[u8], format: ImageFormat) -> Result<(u32, u32), Error> {
+    if format == ImageFormat::Png {
+        return png::read_dimensions(data);
+    }
+
+    let (segments, _) = jpeg::parse_segments(data)?;
+    segments
+        .iter()
+        .find(|segment| {
+            matches!(segment.marker, 0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF)
+        })
+        .map(|segment| segment.payload())
+        .filter(|payload| payload.len() >= 5)
+        .map(|payload| {
+            let height = u16::from_be_bytes([payload[1], payload[2]]) as u32;
+            let width = u16::from_be_bytes([payload[3], payload[4]]) as u32;
+            (width, height)
+        })
+        .ok_or_else(|| Error::InvalidFormat("SOF marker not found".to_string()))
+}
@@ -0,0 +1,335 @@
+use crate::{jpeg, png, Error, ImageFormat};
+
+/// 付随メタデータを過大とみなすサイズ（バイト）
+///
+/// PNGでは個々の付随チャンク、JPEGでは同じ種類のAPPセグメントの合計に適用します。
+pub const OVERSIZED_METADATA_THRESHOLD: usize = 1024 * 1024;
+
+/// メタデータや末尾データ内で検出する埋め込みシグネチャ
+const EMBEDDED_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"PK\x03\x04", "ZIP"),
+    (b"PK\x05\x06", "ZIP"),
+    (b"%PDF-", "PDF"),
+    (b"<?php", "PHP"),
+    (b"<script", "HTML"),
+    (b"<html", "HTML"),
+    (b"<!doctype html", "HTML"),
+    (b"<iframe", "HTML"),
+];
+
+/// セキュリティスキャンで検出された問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecurityFinding {
+    /// JPEG/PNGとして認識できないデータ
+    UnrecognizedFormat,
+    /// 構造が壊れており、途中で解析を打ち切った
+    MalformedStructure {
+        /// 問題が見つかった位置
+        offset: usize,
+        /// 詳細
+        message: String,
+    },
+    /// EOI/IENDの後に付加されたデータ
+    TrailingData {
+        /// 末尾データの開始位置
+        offset: usize,
+        /// 末尾データのバイト数
+        length: usize,
+    },
+    /// 過大な付随チャンク・APPセグメント
+    OversizedMetadata {
+        /// チャンクタイプまたはセグメント名
+        name: String,
+        /// バイト数
+        length: usize,
+    },
+    /// メタデータや末尾データに埋め込まれた他形式のシグネチャ
+    EmbeddedSignature {
+        /// 検出された形式（"ZIP"、"HTML"など）
+        kind: &'static str,
+        /// シグネチャが見つかった位置
+        offset: usize,
+    },
+    /// 重複したクリティカルチャンク・マーカー
+    DuplicateCritical {
+        /// チャンクタイプまたはマーカー名
+        name: String,
+        /// 出現回数
+        count: usize,
+    },
+    /// 分割されたメタデータの同じ連番を、内容の異なる複数のセグメントが主張している
+    ///
+    /// パーサーによって採用されるセグメントが異なるため、解釈の食い違いを招きます。
+    OverlappingSegments {
+        /// セグメント名（"ICC_PROFILE"、"JUMBF"）
+        name: String,
+        /// 重複した連番
+        sequence: u32,
+    },
+}
+
+/// セキュリティスキャンの結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityReport {
+    /// 検出された問題
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityReport {
+    /// 問題が検出されなかったか
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// アップロードされた画像をデコードせずに検査し、ポリグロットや付加データを検出します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+///
+/// # Returns
+/// * 検出された問題の一覧
+///
+/// # Details
+/// - EOI/IEND以降の末尾データ
+/// - 過大な付随チャンク・APPセグメント
+/// - メタデータ内に埋め込まれたZIP/HTML/PHP/PDFのシグネチャ
+/// - IHDR/IEND/PLTEやSOI/SOFマーカーの重複（EOIの後に連結されたJPEGは末尾データとして報告）
+/// - ICCプロファイル・JUMBFの同じ連番を主張する複数のセグメント
+pub fn scan_security(data: &[u8]) -> SecurityReport {
+    let mut report = SecurityReport::default();
+
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Png) => scan_png(data, &mut report),
+        Some(ImageFormat::Jpeg) => scan_jpeg(data, &mut report),
+        None => report.findings.push(SecurityFinding::UnrecognizedFormat),
+    }
+
+    report
+}
+
+/// 重複したクリティカルチャンク・マーカーや連番の重複があればエラーを返します
+///
+/// 軽量化関数の`reject_duplicate_critical`オプションで使用します。
+pub(crate) fn check_duplicate_critical(data: &[u8]) -> Result<(), Error> {
+    let ambiguous: Vec<String> = scan_security(data)
+        .findings
+        .iter()
+        .filter_map(|finding| match finding {
+            SecurityFinding::DuplicateCritical { name, count } => {
+                Some(format!("{name} appears {count} times"))
+            }
+            SecurityFinding::OverlappingSegments { name, sequence } => Some(format!(
+                "{name} sequence {sequence} is claimed by multiple segments"
+            )),
+            _ => None,
+        })
+        .collect();
+
+    if ambiguous.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidFormat(format!(
+            "Ambiguous structure: {}",
+            ambiguous.join("; ")
+        )))
+    }
+}
+
+/// PNGを検査します
+fn scan_png(data: &[u8], report: &mut SecurityReport) {
+    let mut counts: Vec<(&[u8; 4], usize)> = vec![(b"IHDR", 0), (b"PLTE", 0), (b"IEND", 0)];
+    let mut pos = 8;
+    let mut iend_end = None;
+
+    while pos < data.len() {
+        let chunk = match png::read_chunk(data, pos) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                // IEND以降の不正なデータは末尾データとして扱う
+                if iend_end.is_none() {
+                    report.findings.push(SecurityFinding::MalformedStructure {
+                        offset: pos,
+                        message: e.to_string(),
+                    });
+                }
+                break;
+            }
+        };
+
+        for (chunk_type, count) in counts.iter_mut() {
+            if **chunk_type == chunk.chunk_type {
+                *count += 1;
+            }
+        }
+
+        // 付随チャンク（先頭が小文字）の検査
+        if chunk.chunk_type[0].is_ascii_lowercase() {
+            if chunk.data.len() > OVERSIZED_METADATA_THRESHOLD {
+                report.findings.push(SecurityFinding::OversizedMetadata {
+                    name: chunk.type_name(),
+                    length: chunk.data.len(),
+                });
+            }
+            find_signatures(chunk.data, chunk.offset + 8, report);
+        }
+
+        pos += chunk.bytes.len();
+        if &chunk.chunk_type == b"IEND" && iend_end.is_none() {
+            iend_end = Some(pos);
+        }
+    }
+
+    for (chunk_type, count) in counts {
+        if count > 1 {
+            report.findings.push(SecurityFinding::DuplicateCritical {
+                name: String::from_utf8_lossy(chunk_type).to_string(),
+                count,
+            });
+        }
+    }
+
+    if let Some(end) = iend_end {
+        report_trailing(data, end, report);
+    }
+}
+
+/// JPEGを検査します
+fn scan_jpeg(data: &[u8], report: &mut SecurityReport) {
+    let (segments, sos_pos) = match jpeg::parse_segments(data) {
+        Ok(result) => result,
+        Err(e) => {
+            report.findings.push(SecurityFinding::MalformedStructure {
+                offset: 0,
+                message: e.to_string(),
+            });
+            return;
+        }
+    };
+
+    let mut sof_count = 0;
+    let mut soi_count = 1;
+    let mut app_totals = [0usize; 16];
+    let mut offset = 2;
+    // (セグメント名, インスタンス番号, 連番, 内容)
+    let mut sequenced: Vec<(&str, u16, u32, &[u8])> = Vec::new();
+
+    for segment in &segments {
+        let payload = segment.payload();
+        match segment.marker {
+            0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => sof_count += 1,
+            0xD8 => soi_count += 1,
+            0xE2 if payload.len() > 14 && payload.starts_with(b"ICC_PROFILE\0") => {
+                sequenced.push(("ICC_PROFILE", 0, payload[12] as u32, payload));
+            }
+            0xEB if payload.len() >= 8 && payload.starts_with(b"JP") => {
+                // インスタンス番号ごとに連番を区別する
+                let instance = u16::from_be_bytes([payload[2], payload[3]]);
+                let sequence = u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]);
+                sequenced.push(("JUMBF", instance, sequence, payload));
+            }
+            _ => {}
+        }
+        match segment.marker {
+            0xE0..=0xEF => {
+                app_totals[(segment.marker - 0xE0) as usize] += segment.payload().len();
+                find_signatures(segment.payload(), offset + 4, report);
+            }
+            0xFE => find_signatures(segment.payload(), offset + 4, report),
+            _ => {}
+        }
+        offset += segment.bytes.len();
+    }
+
+    for (index, (name, instance, sequence, payload)) in sequenced.iter().enumerate() {
+        let conflicting = sequenced[..index]
+            .iter()
+            .any(|(n, i, s, p)| n == name && i == instance && s == sequence && p != payload);
+        let reported = report.findings.iter().any(|finding| {
+            matches!(finding, SecurityFinding::OverlappingSegments { name: n, sequence: s }
+                if n == name && *s == *sequence)
+        });
+        if conflicting && !reported {
+            report.findings.push(SecurityFinding::OverlappingSegments {
+                name: name.to_string(),
+                sequence: *sequence,
+            });
+        }
+    }
+
+    for (index, total) in app_totals.iter().enumerate() {
+        if *total > OVERSIZED_METADATA_THRESHOLD {
+            report.findings.push(SecurityFinding::OversizedMetadata {
+                name: format!("APP{index}"),
+                length: *total,
+            });
+        }
+    }
+
+    let Some(sos_pos) = sos_pos else {
+        report.findings.push(SecurityFinding::MalformedStructure {
+            offset,
+            message: "SOS marker not found".to_string(),
+        });
+        return;
+    };
+
+    let walk = jpeg::walk_scans(data, sos_pos);
+
+    // スキャンの間に現れたフレームマーカーも数える
+    sof_count += walk
+        .markers
+        .iter()
+        .filter(
+            |(marker, _)| matches!(marker, 0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF),
+        )
+        .count();
+
+    if soi_count > 1 {
+        report.findings.push(SecurityFinding::DuplicateCritical {
+            name: "SOI".to_string(),
+            count: soi_count,
+        });
+    }
+    if sof_count > 1 {
+        report.findings.push(SecurityFinding::DuplicateCritical {
+            name: "SOF".to_string(),
+            count: sof_count,
+        });
+    }
+
+    // EOIの後に連結されたJPEG（MPFの副画像など）は重複ではなく末尾データとして報告する
+    match walk.eoi_end {
+        Some(end) => report_trailing(data, end, report),
+        None => report.findings.push(SecurityFinding::MalformedStructure {
+            offset: data.len(),
+            message: "EOI marker not found".to_string(),
+        }),
+    }
+}
+
+/// 末尾データを報告します
+fn report_trailing(data: &[u8], end: usize, report: &mut SecurityReport) {
+    if end < data.len() {
+        report.findings.push(SecurityFinding::TrailingData {
+            offset: end,
+            length: data.len() - end,
+        });
+        find_signatures(&data[end..], end, report);
+    }
+}
+
+/// バイト列から埋め込みシグネチャを検索します（HTML系は大文字小文字を区別しません）
+fn find_signatures(bytes: &[u8], base_offset: usize, report: &mut SecurityReport) {
+    for (signature, kind) in EMBEDDED_SIGNATURES {
+        if let Some(position) = bytes
+            .windows(signature.len())
+            .position(|window| window.eq_ignore_ascii_case(signature))
+        {
+            report.findings.push(SecurityFinding::EmbeddedSignature {
+                kind,
+                offset: base_offset + position,
+            });
+        }
+    }
+}
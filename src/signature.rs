@@ -0,0 +1,202 @@
+use crate::etag::{self, EtagOptions};
+use crate::{jpeg, png, xmp, Error, ImageFormat};
+use sha2::{Digest, Sha256};
+
+/// 署名を格納するPNGのtEXtチャンクのキーワード
+const PNG_SIGNATURE_KEYWORD: &str = "HMAC Signature";
+/// 署名を格納するXMPのプロパティ名
+const XMP_SIGNATURE_PROPERTY: &str = "Signature";
+/// 署名の形式（`hmac-sha256:<メタデータ>:<16進数のHMAC>`）の識別子
+const SCHEME: &str = "hmac-sha256";
+/// SHA-256のブロック長
+const BLOCK_SIZE: usize = 64;
+
+/// 署名に含めるメタデータ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignedMetadata {
+    /// EXIF（JPEGのAPP1、PNGのeXIf）
+    Exif,
+    /// ICCプロファイル（JPEGのAPP2、PNGのiCCPを展開したもの）
+    Icc,
+}
+
+impl SignedMetadata {
+    /// 署名の文字列に記録する名前
+    fn name(self) -> &'static str {
+        match self {
+            SignedMetadata::Exif => "exif",
+            SignedMetadata::Icc => "icc",
+        }
+    }
+
+    /// 署名の文字列に記録した名前から変換します
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "exif" => Some(SignedMetadata::Exif),
+            "icc" => Some(SignedMetadata::Icc),
+            _ => None,
+        }
+    }
+}
+
+/// 署名のオプション
+#[derive(Debug, Clone, Default)]
+pub struct SignOptions {
+    /// 画像データに加えて署名に含めるメタデータ
+    pub metadata: Vec<SignedMetadata>,
+}
+
+/// 画像データと指定したメタデータに対するHMAC-SHA256の署名を書き込みます
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+/// * `key` - HMACの鍵
+/// * `options` - 署名に含めるメタデータ
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - 署名を書き込んだ画像データ
+/// * `Err(Error)` - 対応していない形式や構造が壊れている場合など
+///
+/// # Details
+/// - 画像データは[`crate::etag_with_options`]の`pixels_only`と同じ範囲
+///   （JPEGのフレーム・テーブル・スキャン、PNGのIHDR・PLTE・tRNS・IDAT）を対象にします
+/// - JPEGではXMPの`wim:Signature`プロパティ、PNGではキーワード`HMAC Signature`の
+///   tEXtチャンクに`hmac-sha256:<メタデータ>:<HMAC>`の形式で書き込みます
+/// - 既存の署名は置き換えます
+pub fn write_signature(data: &[u8], key: &[u8], options: &SignOptions) -> Result<Vec<u8>, Error> {
+    let mut metadata = options.metadata.clone();
+    metadata.sort_by_key(|m| m.name());
+    metadata.dedup();
+
+    let mac = compute(data, key, &metadata)?;
+    let names: Vec<&str> = metadata.iter().map(|m| m.name()).collect();
+    let value = format!("{SCHEME}:{}:{}", names.join(","), hex(&mac));
+
+    match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => {
+            let packet = jpeg::read_xmp(data)?.unwrap_or_default();
+            let packet =
+                xmp::set_property(&packet, xmp::NS_WIM, XMP_SIGNATURE_PROPERTY, Some(&value))?;
+            jpeg::write_xmp(data, &packet)
+        }
+        Some(ImageFormat::Png) => {
+            let unsigned = png::remove_text_chunks(data, &[PNG_SIGNATURE_KEYWORD])?;
+            png::add_text_chunk(&unsigned, PNG_SIGNATURE_KEYWORD, &value)
+        }
+        None => Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    }
+}
+
+/// [`write_signature`]で書き込んだ署名を検証します
+///
+/// # Arguments
+/// * `data` - 画像のバイトデータ（JPEGまたはPNG）
+/// * `key` - HMACの鍵
+///
+/// # Returns
+/// * `Ok(true)` - 署名が画像データと記録されたメタデータに一致する
+/// * `Ok(false)` - 署名がない、形式が不正、または一致しない
+/// * `Err(Error)` - 対応していない形式や構造が壊れている場合
+pub fn verify_signature(data: &[u8], key: &[u8]) -> Result<bool, Error> {
+    let value = match ImageFormat::detect(data) {
+        Some(ImageFormat::Jpeg) => jpeg::read_xmp(data)?
+            .and_then(|packet| xmp::read_property(&packet, xmp::NS_WIM.0, XMP_SIGNATURE_PROPERTY)),
+        Some(ImageFormat::Png) => {
+            png::find_text_chunk(data, PNG_SIGNATURE_KEYWORD).map(|chunk| chunk.text)
+        }
+        None => return Err(Error::InvalidFormat("Unsupported image format".to_string())),
+    };
+    let Some(value) = value else {
+        return Ok(false);
+    };
+
+    let mut parts = value.splitn(3, ':');
+    let (Some(SCHEME), Some(names), Some(expected)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(false);
+    };
+    let metadata: Option<Vec<SignedMetadata>> = names
+        .split(',')
+        .filter(|name| !name.is_empty())
+        .map(SignedMetadata::from_name)
+        .collect();
+    let Some(metadata) = metadata else {
+        return Ok(false);
+    };
+
+    let mac = hex(&compute(data, key, &metadata)?);
+    Ok(constant_time_eq(mac.as_bytes(), expected.as_bytes()))
+}
+
+/// 画像データとメタデータのダイジェストに対するHMACを計算します
+fn compute(data: &[u8], key: &[u8], metadata: &[SignedMetadata]) -> Result<[u8; 32], Error> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"web-image-meta signature v1\0");
+    etag::update_content(data, &EtagOptions { pixels_only: true }, &mut hasher)?;
+
+    for &item in metadata {
+        let bytes = read_metadata(data, item)?;
+        etag::update_item(
+            &mut hasher,
+            item.name().as_bytes(),
+            &bytes.unwrap_or_default(),
+        );
+    }
+
+    Ok(hmac_sha256(key, &hasher.finalize()))
+}
+
+/// 署名に含めるメタデータを読み取ります
+fn read_metadata(data: &[u8], item: SignedMetadata) -> Result<Option<Vec<u8>>, Error> {
+    if ImageFormat::detect(data) == Some(ImageFormat::Png) {
+        let chunks = png::parse_chunks(data)?;
+        return Ok(match item {
+            SignedMetadata::Exif => chunks
+                .iter()
+                .find(|chunk| &chunk.chunk_type == b"eXIf")
+                .map(|chunk| chunk.data.to_vec()),
+            SignedMetadata::Icc => chunks
+                .iter()
+                .find(|chunk| &chunk.chunk_type == b"iCCP")
+                .and_then(|chunk| png::iccp_profile(chunk.data)),
+        });
+    }
+
+    let (segments, _) = jpeg::parse_segments(data)?;
+    Ok(match item {
+        SignedMetadata::Exif => segments
+            .iter()
+            .find(|segment| segment.app_kind() == Some(jpeg::AppKind::Exif))
+            .map(|segment| segment.payload().to_vec()),
+        SignedMetadata::Icc => jpeg::assemble_icc_profile(&segments).ok().flatten(),
+    })
+}
+
+/// HMAC-SHA256（RFC 2104）を計算します
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5C));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+/// 16進数の文字列に変換します
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 比較にかかる時間が一致する位置に依存しないよう比較します
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,217 @@
+//! 非同期ストリームのメタデータ軽量化（`tokio`フィーチャー）
+
+use crate::cancel;
+use crate::{jpeg, png, CancellationToken, Error, ImageFormat, Processor, ProgressCallback};
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::task::JoinHandle;
+
+/// 一度に読み取るバイト数
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// [`CleanReader`]のオプション
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// JPEGの軽量化のオプション
+    pub jpeg_options: jpeg::CleanOptions,
+    /// PNGの軽量化のオプション
+    pub png_options: png::CleanOptions,
+    /// 入力の最大バイト数（超えた場合は読み取りを中止してエラー）
+    pub max_input_size: usize,
+    /// 処理を中断するためのトークン
+    ///
+    /// 入力の読み取りごとに確認し、中断時は[`Error::Cancelled`]を返します。
+    /// `jpeg_options`・`png_options`にトークンがない場合はそれらにも設定し、
+    /// 軽量化の途中でも中断します。
+    pub cancellation: Option<CancellationToken>,
+    /// 軽量化の進捗を受け取るコールバック
+    ///
+    /// 入力を読み終えた後の検証・書き込みの進捗を、軽量化を実行するスレッドから通知します。
+    /// 内容は[`Processor::clean_with_progress`]と同じです。
+    pub progress: Option<ProgressCallback>,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            jpeg_options: jpeg::CleanOptions::default(),
+            png_options: png::CleanOptions::default(),
+            max_input_size: 50 * 1024 * 1024,
+            cancellation: None,
+            progress: None,
+        }
+    }
+}
+
+/// 画像の`AsyncRead`を包み、軽量化した画像を読み取る`AsyncRead`
+///
+/// オブジェクトストレージのGETのレスポンスをそのままPUTに渡すなど、
+/// ファイルに保存せずに軽量化するための構造体です。
+///
+/// # Details
+/// - 出力前の検証（デコード）に画像全体が必要なため、入力をすべてメモリに読み込んでから
+///   出力を始めます。出力も画像全体を保持するため、最大で入力と出力の両方がメモリに載ります。
+///   入力は[`max_input_size`](StreamOptions::max_input_size)までに制限されます
+/// - 先頭のバイト列がJPEG・PNGでない場合は、入力を最後まで読まずにエラーにします
+/// - 軽量化は`tokio::task::spawn_blocking`で実行し、非同期タスクをブロックしません。
+///   そのため、tokioのランタイム内で読み取る必要があります
+/// - エラーは[`io::ErrorKind::InvalidData`]の`io::Error`として返り、
+///   `get_ref`で元の[`Error`]を参照できます（入力のI/Oエラーはそのまま返ります）。
+///   エラーの後に読み取った場合も、同じ種類・メッセージのエラーを返し続けます
+#[derive(Debug)]
+pub struct CleanReader<R> {
+    inner: R,
+    state: State,
+    max_input_size: usize,
+    cancellation: Option<CancellationToken>,
+    progress: Option<ProgressCallback>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    position: usize,
+}
+
+/// [`CleanReader`]の処理の段階
+#[derive(Debug)]
+enum State {
+    /// 入力を読み取っている
+    Reading(Processor),
+    /// ブロッキングタスクで軽量化している
+    Cleaning(JoinHandle<(Processor, Result<(), Error>)>),
+    /// 軽量化した出力を返している
+    Writing,
+    /// エラーで終了した（以降の読み取りも同じ種類・内容のエラーを返す）
+    Failed(io::ErrorKind, String),
+}
+
+impl<R: AsyncRead + Unpin> CleanReader<R> {
+    /// デフォルトのオプションで作成します
+    pub fn new(inner: R) -> Self {
+        Self::with_options(inner, StreamOptions::default())
+    }
+
+    /// オプションを指定して作成します
+    pub fn with_options(inner: R, options: StreamOptions) -> Self {
+        let mut processor = Processor::with_options(options.jpeg_options, options.png_options);
+        processor.set_default_cancellation(options.cancellation.as_ref());
+        Self {
+            inner,
+            state: State::Reading(processor),
+            max_input_size: options.max_input_size,
+            cancellation: options.cancellation,
+            progress: options.progress,
+            input: Vec::new(),
+            output: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// 包んでいる`AsyncRead`を返します
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// 入力を最後まで読み取り、軽量化した出力を用意します
+    fn poll_clean(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if let State::Reading(_) = self.state {
+            ready!(self.poll_fill(cx))?;
+            let State::Reading(mut processor) = mem::replace(&mut self.state, State::Writing)
+            else {
+                unreachable!()
+            };
+            let input = mem::take(&mut self.input);
+            let progress = self.progress.clone();
+            self.state = State::Cleaning(tokio::task::spawn_blocking(move || {
+                let result = processor
+                    .clean_with_progress(&input, |value| {
+                        if let Some(progress) = &progress {
+                            progress.report(value);
+                        }
+                    })
+                    .map(|_| ());
+                (processor, result)
+            }));
+        }
+
+        if let State::Cleaning(task) = &mut self.state {
+            let (mut processor, result) =
+                ready!(Pin::new(task).poll(cx)).map_err(io::Error::other)?;
+            result.map_err(|err| match err {
+                Error::Io(err) => err,
+                err => invalid_data(err),
+            })?;
+            self.output = processor.take_buffer();
+            self.state = State::Writing;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// 入力を最後まで読み取ります
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if let Err(err) = cancel::check(self.cancellation.as_ref()) {
+                return Poll::Ready(Err(invalid_data(err)));
+            }
+
+            // 入力のバッファに直接読み取る
+            let start = self.input.len();
+            self.input.resize(start + CHUNK_SIZE, 0);
+            let mut buf = ReadBuf::new(&mut self.input[start..]);
+            let result = Pin::new(&mut self.inner).poll_read(cx, &mut buf);
+            let length = buf.filled().len();
+            self.input.truncate(start + length);
+            ready!(result)?;
+            if length == 0 {
+                return Poll::Ready(Ok(()));
+            }
+
+            if self.input.len() > self.max_input_size {
+                return Poll::Ready(Err(invalid_data(Error::LimitExceeded(format!(
+                    "Input exceeds {} bytes",
+                    self.max_input_size
+                )))));
+            }
+
+            // 形式を判定できる長さになったら、画像でない入力を早めに拒否する
+            if start < 8 && self.input.len() >= 8 && ImageFormat::detect(&self.input).is_none() {
+                return Poll::Ready(Err(invalid_data(Error::InvalidFormat(
+                    "Unsupported image format".to_string(),
+                ))));
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CleanReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let State::Failed(kind, message) = &this.state {
+            return Poll::Ready(Err(io::Error::new(*kind, message.clone())));
+        }
+        if !matches!(this.state, State::Writing) {
+            // エラーの後に読み取られても、空の出力（EOF）と誤解されないようにする
+            if let Err(err) = ready!(this.poll_clean(cx)) {
+                this.state = State::Failed(err.kind(), err.to_string());
+                return Poll::Ready(Err(err));
+            }
+        }
+
+        let remaining = &this.output[this.position..];
+        let length = remaining.len().min(buf.remaining());
+        buf.put_slice(&remaining[..length]);
+        this.position += length;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// ライブラリのエラーを`io::Error`に変換します
+fn invalid_data(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
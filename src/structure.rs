@@ -0,0 +1,37 @@
+/// 構造検証で見つかった問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructureIssue {
+    /// 問題のあるセグメント・チャンクの位置
+    pub offset: usize,
+    /// 問題の内容
+    pub message: String,
+}
+
+/// デコーダーを使わない構造検証の結果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StructureReport {
+    /// 走査したセグメント（JPEG）またはチャンク（PNG）の数
+    pub units: usize,
+    /// 終端のマーカー（EOI）またはチャンク（IEND）まで到達した
+    pub complete: bool,
+    /// 終端以降に続くデータのバイト数
+    pub trailing_bytes: usize,
+    /// 見つかった問題（出現順）
+    pub issues: Vec<StructureIssue>,
+}
+
+impl StructureReport {
+    /// 問題が見つからなかったか
+    ///
+    /// 終端以降のデータは問題として扱いません。
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub(crate) fn push(&mut self, offset: usize, message: impl Into<String>) {
+        self.issues.push(StructureIssue {
+            offset,
+            message: message.into(),
+        });
+    }
+}
@@ -0,0 +1,124 @@
+use crate::Error;
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+/// 書き込むテキスト（コメント・テキストチャンクの値）の検査・修正のオプション
+///
+/// 既定ではすべて無効で、テキストはそのまま書き込まれます。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextOptions {
+    /// 制御文字（NULなど。タブ・LF・CRを除く）を取り除く
+    pub strip_control: bool,
+    /// 改行（CRLF・CR）をLFに統一する
+    pub normalize_newlines: bool,
+    /// Unicode正規化形式C（NFC）に変換する
+    pub nfc: bool,
+    /// 修正せず、有効な項目に該当するテキストをエラーにする
+    ///
+    /// 例えば`strip_control`と併用すると、制御文字を含むテキストを
+    /// [`Error::InvalidFormat`]として拒否します。
+    pub reject: bool,
+}
+
+impl TextOptions {
+    /// テキストに有効な項目を適用します
+    ///
+    /// # Returns
+    /// * `Ok(Cow<str>)` - 修正したテキスト（変更がない場合は借用）
+    /// * `Err(Error::InvalidFormat)` - [`reject`](Self::reject)が有効で、修正が必要な場合
+    pub fn apply<'a>(&self, text: &'a str) -> Result<Cow<'a, str>, Error> {
+        let mut text = Cow::Borrowed(text);
+
+        if self.normalize_newlines && text.contains('\r') {
+            if self.reject {
+                return Err(Error::InvalidFormat(
+                    "Text contains CR line breaks".to_string(),
+                ));
+            }
+            text = Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"));
+        }
+
+        if self.strip_control {
+            if let Some(c) = text.chars().find(|&c| is_stripped_control(c)) {
+                if self.reject {
+                    return Err(Error::InvalidFormat(format!(
+                        "Text contains control character U+{:04X}",
+                        c as u32
+                    )));
+                }
+                text = Cow::Owned(text.chars().filter(|&c| !is_stripped_control(c)).collect());
+            }
+        }
+
+        if self.nfc && !is_nfc(&text) {
+            if self.reject {
+                return Err(Error::InvalidFormat(
+                    "Text is not in Unicode NFC".to_string(),
+                ));
+            }
+            text = Cow::Owned(text.nfc().collect());
+        }
+
+        Ok(text)
+    }
+}
+
+/// 取り除く制御文字か判定します（タブ・LF・CRは残します）
+fn is_stripped_control(c: char) -> bool {
+    c.is_control() && !matches!(c, '\t' | '\n' | '\r')
+}
+
+/// 読み取った文字列がUTF-8として不正な場合の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Utf8Policy {
+    /// [`Error::ParseError`]として拒否する
+    Reject,
+    /// 不正なバイト列を置換文字（U+FFFD）に変換する
+    #[default]
+    Lossy,
+    /// 値全体をISO-8859-1（Latin-1）として解釈する
+    ///
+    /// UTF-8に対応していない古いアプリケーションが書き込んだ値を、文字を失わずに読み取れます。
+    Latin1Fallback,
+}
+
+impl Utf8Policy {
+    /// バイト列をUTF-8として解釈し、不正な場合はこのポリシーで修復します
+    ///
+    /// # Arguments
+    /// * `bytes` - 文字列のバイト列
+    /// * `field` - エラーメッセージに使うフィールド名
+    ///
+    /// # Returns
+    /// * `Ok(DecodedText)` - 文字列と、適用した修復
+    /// * `Err(Error::ParseError)` - [`Reject`](Self::Reject)で、UTF-8として不正な場合
+    pub fn decode(self, bytes: &[u8], field: &str) -> Result<DecodedText, Error> {
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return Ok(DecodedText {
+                text: text.to_string(),
+                repaired: None,
+            });
+        }
+
+        let text = match self {
+            Utf8Policy::Reject => {
+                return Err(Error::ParseError(format!("{field} is not valid UTF-8")));
+            }
+            Utf8Policy::Lossy => String::from_utf8_lossy(bytes).into_owned(),
+            Utf8Policy::Latin1Fallback => bytes.iter().map(|&b| b as char).collect(),
+        };
+        Ok(DecodedText {
+            text,
+            repaired: Some(self),
+        })
+    }
+}
+
+/// [`Utf8Policy`]を適用して読み取った文字列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedText {
+    /// 文字列
+    pub text: String,
+    /// UTF-8として不正だったために適用した修復（正しいUTF-8の場合は`None`）
+    pub repaired: Option<Utf8Policy>,
+}
@@ -0,0 +1,265 @@
+use crate::Error;
+
+/// VP8Xのフラグ：ICCプロファイル
+const FLAG_ICC: u8 = 0x20;
+/// VP8Xのフラグ：アルファ
+const FLAG_ALPHA: u8 = 0x10;
+/// VP8Xのフラグ：EXIF
+const FLAG_EXIF: u8 = 0x08;
+/// VP8Xのフラグ：XMP
+const FLAG_XMP: u8 = 0x04;
+
+/// メタデータ軽量化のオプション
+///
+/// ICCプロファイル（ICCP）は表示に必要なためデフォルトで保持します。
+#[derive(Debug, Clone, Default)]
+pub struct CleanOptions {
+    /// EXIFチャンクを保持する
+    pub preserve_exif: bool,
+    /// XMPチャンクを保持する
+    pub preserve_xmp: bool,
+    /// sRGBのICCプロファイルを削除する
+    ///
+    /// WebPはICCプロファイルがない場合sRGBとして表示されるため、見た目は変わりません。
+    pub replace_srgb_icc: bool,
+}
+
+/// WebPのチャンク
+#[derive(Debug, Clone, Copy)]
+struct Chunk<'a> {
+    fourcc: [u8; 4],
+    data: &'a [u8],
+}
+
+/// WebP画像からEXIFやXMPなどのメタデータを削除します
+///
+/// ICCプロファイルとアニメーション・アルファの情報は保持します。
+pub fn clean_metadata(data: &[u8]) -> Result<Vec<u8>, Error> {
+    clean_metadata_with_options(data, &CleanOptions::default())
+}
+
+/// オプションを指定してWebP画像からメタデータを削除します
+///
+/// # Arguments
+/// * `data` - WebP画像のバイトデータ
+/// * `options` - 保持するメタデータの指定
+pub fn clean_metadata_with_options(data: &[u8], options: &CleanOptions) -> Result<Vec<u8>, Error> {
+    let chunks = parse_chunks(data)?;
+
+    let kept: Vec<Chunk> = chunks
+        .into_iter()
+        .filter(|chunk| match &chunk.fourcc {
+            b"EXIF" => options.preserve_exif,
+            b"XMP " => options.preserve_xmp,
+            b"ICCP" => !(options.replace_srgb_icc && crate::icc::is_srgb_profile(chunk.data)),
+            // 画像データと構造に関わるチャンク
+            b"VP8X" | b"VP8 " | b"VP8L" | b"ALPH" | b"ANIM" | b"ANMF" => true,
+            _ => false,
+        })
+        .collect();
+
+    build(&kept)
+}
+
+/// WebP画像からICCプロファイルを読み取ります
+///
+/// # Returns
+/// * `Ok(Some(Vec<u8>))` - ICCPチャンクの内容
+/// * `Ok(None)` - ICCプロファイルがない場合
+/// * `Err(Error)` - WebPではない場合
+pub fn read_icc_profile(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let chunks = parse_chunks(data)?;
+    Ok(chunks
+        .iter()
+        .find(|chunk| &chunk.fourcc == b"ICCP")
+        .map(|chunk| chunk.data.to_vec()))
+}
+
+/// WebP画像にICCプロファイルを書き込みます
+///
+/// 既存のICCPチャンクは置き換えます。シンプル形式（VP8/VP8Lのみ）のファイルは、
+/// 画像データから寸法を読み取りVP8Xチャンクを追加した拡張形式に変換します。
+///
+/// # Arguments
+/// * `data` - WebP画像のバイトデータ
+/// * `profile` - ICCプロファイル
+pub fn write_icc_profile(data: &[u8], profile: &[u8]) -> Result<Vec<u8>, Error> {
+    if profile.is_empty() {
+        return Err(Error::InvalidFormat("ICC profile is empty".to_string()));
+    }
+
+    let chunks = parse_chunks(data)?;
+
+    // ICCPはVP8Xの直後に置く
+    let extended;
+    let vp8x = match chunks.first() {
+        Some(chunk) if &chunk.fourcc == b"VP8X" => *chunk,
+        _ => {
+            extended = create_vp8x(&chunks)?;
+            Chunk {
+                fourcc: *b"VP8X",
+                data: &extended,
+            }
+        }
+    };
+    let mut output = vec![
+        vp8x,
+        Chunk {
+            fourcc: *b"ICCP",
+            data: profile,
+        },
+    ];
+    output.extend(
+        chunks
+            .iter()
+            .filter(|chunk| !matches!(&chunk.fourcc, b"VP8X" | b"ICCP")),
+    );
+
+    build(&output)
+}
+
+/// RIFFヘッダーを確認し、チャンクを列挙します
+fn parse_chunks(data: &[u8]) -> Result<Vec<Chunk<'_>>, Error> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return Err(Error::InvalidFormat("Not a valid WebP file".to_string()));
+    }
+
+    let riff_size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+    let end = riff_size
+        .checked_add(8)
+        .filter(|&end| end <= data.len())
+        .ok_or_else(|| Error::ParseError("RIFF size exceeds file".to_string()))?;
+
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= end {
+        let fourcc = [data[pos], data[pos + 1], data[pos + 2], data[pos + 3]];
+        let size = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]])
+            as usize;
+        let data_end = (pos + 8)
+            .checked_add(size)
+            .filter(|&data_end| data_end <= end)
+            .ok_or_else(|| Error::ParseError("Chunk extends beyond file".to_string()))?;
+
+        chunks.push(Chunk {
+            fourcc,
+            data: &data[pos + 8..data_end],
+        });
+
+        // 奇数サイズのチャンクはパディングされる
+        pos = data_end + (size & 1);
+    }
+
+    if !chunks
+        .iter()
+        .any(|chunk| matches!(&chunk.fourcc, b"VP8 " | b"VP8L" | b"ANMF"))
+    {
+        return Err(Error::InvalidFormat(
+            "WebP image data not found".to_string(),
+        ));
+    }
+    if chunks.len() > 1 && &chunks[0].fourcc != b"VP8X" {
+        return Err(Error::InvalidFormat(
+            "Extended WebP must start with VP8X".to_string(),
+        ));
+    }
+
+    Ok(chunks)
+}
+
+/// シンプル形式の画像データからVP8Xチャンクの内容を作成します
+fn create_vp8x(chunks: &[Chunk]) -> Result<[u8; 10], Error> {
+    let image = chunks
+        .first()
+        .ok_or_else(|| Error::InvalidFormat("WebP image data not found".to_string()))?;
+
+    let (width, height, alpha) = match (&image.fourcc, image.data) {
+        // フレームタグ(3) + スタートコード(3) + 幅(2) + 高さ(2)
+        (b"VP8 ", [_, _, _, 0x9D, 0x01, 0x2A, w0, w1, h0, h1, ..]) => (
+            (u16::from_le_bytes([*w0, *w1]) & 0x3FFF) as u32,
+            (u16::from_le_bytes([*h0, *h1]) & 0x3FFF) as u32,
+            false,
+        ),
+        // シグネチャ(1) + 幅-1(14ビット) + 高さ-1(14ビット) + アルファ(1ビット)
+        (b"VP8L", [0x2F, b0, b1, b2, b3, ..]) => {
+            let bits = u32::from_le_bytes([*b0, *b1, *b2, *b3]);
+            (
+                (bits & 0x3FFF) + 1,
+                ((bits >> 14) & 0x3FFF) + 1,
+                bits & (1 << 28) != 0,
+            )
+        }
+        _ => {
+            return Err(Error::InvalidFormat(
+                "Invalid WebP image header".to_string(),
+            ))
+        }
+    };
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidFormat("Invalid image dimensions".to_string()));
+    }
+
+    let mut vp8x = [0u8; 10];
+    if alpha {
+        vp8x[0] = FLAG_ALPHA;
+    }
+    vp8x[4..7].copy_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x[7..10].copy_from_slice(&(height - 1).to_le_bytes()[..3]);
+    Ok(vp8x)
+}
+
+/// チャンクからWebPファイルを組み立てます
+///
+/// VP8XのICC・EXIF・XMPフラグは実際のチャンクに合わせて更新します。
+fn build(chunks: &[Chunk]) -> Result<Vec<u8>, Error> {
+    let has = |fourcc: &[u8; 4]| chunks.iter().any(|chunk| &chunk.fourcc == fourcc);
+
+    let mut body = Vec::new();
+    for chunk in chunks {
+        if &chunk.fourcc == b"VP8X" {
+            let mut vp8x = chunk.data.to_vec();
+            if let Some(flags) = vp8x.first_mut() {
+                *flags &= !(FLAG_ICC | FLAG_EXIF | FLAG_XMP);
+                for (fourcc, flag) in [
+                    (b"ICCP", FLAG_ICC),
+                    (b"EXIF", FLAG_EXIF),
+                    (b"XMP ", FLAG_XMP),
+                ] {
+                    if has(fourcc) {
+                        *flags |= flag;
+                    }
+                }
+            }
+            write_chunk(&mut body, &chunk.fourcc, &vp8x);
+        } else {
+            write_chunk(&mut body, &chunk.fourcc, chunk.data);
+        }
+    }
+
+    let riff_size = u32::try_from(body.len() + 4).map_err(|_| Error::PayloadTooLarge {
+        limit: u32::MAX as usize - 4,
+        actual: body.len(),
+        splittable: false,
+    })?;
+
+    let mut output = Vec::with_capacity(body.len() + 12);
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&riff_size.to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    output.extend_from_slice(&body);
+
+    // 出力が有効なWebPか検証
+    parse_chunks(&output)?;
+
+    Ok(output)
+}
+
+/// チャンクを書き込みます（奇数サイズの場合はパディングを追加）
+fn write_chunk(output: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(fourcc);
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        output.push(0);
+    }
+}
@@ -0,0 +1,689 @@
+use crate::Error;
+
+/// xmp名前空間
+const NS_XMP: (&str, &str) = ("xmp", "http://ns.adobe.com/xap/1.0/");
+/// Dublin Core名前空間
+pub(crate) const NS_DC: (&str, &str) = ("dc", "http://purl.org/dc/elements/1.1/");
+/// photoshop名前空間
+const NS_PHOTOSHOP: (&str, &str) = ("photoshop", "http://ns.adobe.com/photoshop/1.0/");
+
+/// xmpNote名前空間（拡張XMPのGUIDを記録する`HasExtendedXMP`）
+pub(crate) const NS_XMP_NOTE: (&str, &str) = ("xmpNote", "http://ns.adobe.com/xmp/note/");
+/// このライブラリが書き込むプロパティ（署名・来歴）の名前空間
+pub(crate) const NS_WIM: (&str, &str) = (
+    "wim",
+    "https://github.com/ideamans/rust-web-image-meta/ns/1.0/",
+);
+
+/// 新しく作成するXMPパケットの雛形
+const EMPTY_PACKET: &str = concat!(
+    "<?xpacket begin=\"\u{FEFF}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n",
+    "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n",
+    " <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n",
+    "  <rdf:Description rdf:about=\"\"/>\n",
+    " </rdf:RDF>\n",
+    "</x:xmpmeta>\n",
+    "<?xpacket end=\"w\"?>",
+);
+
+/// XMPパケットからレーティング（`xmp:Rating`）を読み取ります
+///
+/// # Returns
+/// * `Some(i8)` - -1（却下）から5までの値
+/// * `None` - 記録されていない場合、範囲外や数値ではない場合
+pub fn read_rating(packet: &str) -> Option<i8> {
+    // 小数（"3.0"など）で記録するアプリケーションもある
+    let value: f64 = read_property(packet, NS_XMP.0, "Rating")?.parse().ok()?;
+    (value.fract() == 0.0 && (-1.0..=5.0).contains(&value)).then_some(value as i8)
+}
+
+/// XMPパケットからラベル（`xmp:Label`）を読み取ります
+///
+/// 空のラベルは`None`として扱います。
+pub fn read_label(packet: &str) -> Option<String> {
+    read_property(packet, NS_XMP.0, "Label").filter(|label| !label.is_empty())
+}
+
+/// XMPパケットから緊急度（`photoshop:Urgency`）を読み取ります
+///
+/// # Returns
+/// * `Some(u8)` - 0（なし）から8までの値
+/// * `None` - 記録されていない場合、範囲外や数値ではない場合
+pub fn read_urgency(packet: &str) -> Option<u8> {
+    read_property(packet, NS_PHOTOSHOP.0, "Urgency")?
+        .parse()
+        .ok()
+        .filter(|urgency| *urgency <= 8)
+}
+
+/// XMPパケットのレーティング（`xmp:Rating`）を書き換えます
+///
+/// # Arguments
+/// * `packet` - XMPパケット（空文字列の場合は新しいパケットを作成）
+/// * `rating` - -1（却下）から5までの値（`None`の場合は削除）
+///
+/// # Returns
+/// * `Ok(String)` - 書き換えたXMPパケット
+/// * `Err(Error)` - 値が範囲外の場合、`rdf:RDF`が見つからない場合
+pub fn set_rating(packet: &str, rating: Option<i8>) -> Result<String, Error> {
+    if let Some(rating) = rating.filter(|rating| !(-1..=5).contains(rating)) {
+        return Err(Error::InvalidFormat(format!("Invalid rating: {rating}")));
+    }
+    set_property(
+        packet,
+        NS_XMP,
+        "Rating",
+        rating.map(|r| r.to_string()).as_deref(),
+    )
+}
+
+/// XMPパケットのラベル（`xmp:Label`）を書き換えます
+///
+/// 空文字列または`None`の場合はラベルを削除します。
+pub fn set_label(packet: &str, label: Option<&str>) -> Result<String, Error> {
+    set_property(packet, NS_XMP, "Label", label.filter(|l| !l.is_empty()))
+}
+
+/// XMPパケットの緊急度（`photoshop:Urgency`）を書き換えます
+///
+/// 0から8までの値を指定します（`None`の場合は削除）。
+pub fn set_urgency(packet: &str, urgency: Option<u8>) -> Result<String, Error> {
+    if let Some(urgency) = urgency.filter(|urgency| *urgency > 8) {
+        return Err(Error::InvalidFormat(format!("Invalid urgency: {urgency}")));
+    }
+    set_property(
+        packet,
+        NS_PHOTOSHOP,
+        "Urgency",
+        urgency.map(|u| u.to_string()).as_deref(),
+    )
+}
+
+/// 単純なプロパティの値を読み取ります
+///
+/// `rdf:Description`の属性形式（`xmp:Rating="5"`）と要素形式
+/// （`<xmp:Rating>5</xmp:Rating>`）の両方に対応します。
+/// 名前空間は標準の接頭辞で記録されているものとして扱います。
+pub(crate) fn read_property(packet: &str, prefix: &str, name: &str) -> Option<String> {
+    let qualified = format!("{prefix}:{name}");
+
+    for start in find_start_tags(packet, "rdf:Description") {
+        let end = tag_end(packet, start)?;
+        if let Some(attribute) = parse_attributes(&packet[start..end])
+            .into_iter()
+            .find(|attribute| attribute.name == qualified)
+        {
+            return Some(unescape(attribute.value).trim().to_string());
+        }
+    }
+
+    let element = find_element(packet, &qualified)?;
+    Some(
+        unescape(&packet[element.content.clone()])
+            .trim()
+            .to_string(),
+    )
+}
+
+/// 言語別の代替テキスト（`rdf:Alt`）の値を読み取ります
+///
+/// `x-default`の値を優先し、ない場合は最初の値を返します。
+/// `rdf:Alt`を使わずに単純なプロパティとして記録されている場合はその値を返します。
+pub(crate) fn read_lang_alt(packet: &str, prefix: &str, name: &str) -> Option<String> {
+    let qualified = format!("{prefix}:{name}");
+    let Some(element) = find_element(packet, &qualified) else {
+        return read_property(packet, prefix, name).filter(|value| !value.is_empty());
+    };
+
+    let items = list_items(&packet[element.content.clone()]);
+    if items.is_empty() {
+        return read_property(packet, prefix, name).filter(|value| !value.is_empty());
+    }
+    items
+        .iter()
+        .find(|(lang, _)| lang.as_deref() == Some("x-default"))
+        .or_else(|| items.first())
+        .map(|(_, value)| value.clone())
+        .filter(|value| !value.is_empty())
+}
+
+/// 配列（`rdf:Bag`、`rdf:Seq`）の値をすべて読み取ります（空の値は除きます）
+pub(crate) fn read_array(packet: &str, prefix: &str, name: &str) -> Vec<String> {
+    let qualified = format!("{prefix}:{name}");
+    let Some(element) = find_element(packet, &qualified) else {
+        return Vec::new();
+    };
+
+    list_items(&packet[element.content.clone()])
+        .into_iter()
+        .map(|(_, value)| value)
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+/// `rdf:li`要素の言語（`xml:lang`）と値を列挙します
+fn list_items(content: &str) -> Vec<(Option<String>, String)> {
+    let mut items = Vec::new();
+    for start in find_start_tags(content, "rdf:li") {
+        let Some(open_end) = tag_end(content, start) else {
+            continue;
+        };
+        let lang = parse_attributes(&content[start..open_end])
+            .into_iter()
+            .find(|attribute| attribute.name == "xml:lang")
+            .map(|attribute| attribute.value.to_string());
+
+        let value = if content[..open_end].ends_with("/>") {
+            String::new()
+        } else {
+            let Some(length) = content[open_end..].find("</rdf:li>") else {
+                continue;
+            };
+            unescape(&content[open_end..open_end + length])
+                .trim()
+                .to_string()
+        };
+        items.push((lang, value));
+    }
+    items
+}
+
+/// 単純なプロパティを書き換えます
+///
+/// 既存の属性・要素をすべて削除し、値がある場合は最初の`rdf:Description`に
+/// 属性として追加します。名前空間の宣言がない場合はあわせて追加します。
+pub(crate) fn set_property(
+    packet: &str,
+    namespace: (&str, &str),
+    name: &str,
+    value: Option<&str>,
+) -> Result<String, Error> {
+    let qualified = format!("{}:{name}", namespace.0);
+    let mut packet = open_packet(packet);
+    remove_property(&mut packet, &qualified);
+
+    let Some(value) = value else {
+        return Ok(packet);
+    };
+
+    let description = first_description(&mut packet)?;
+    let end = tag_end(&packet, description)
+        .ok_or_else(|| Error::ParseError("Unterminated rdf:Description".to_string()))?;
+    let insert_at = if packet[..end].ends_with("/>") {
+        end - 2
+    } else {
+        end - 1
+    };
+
+    let mut attributes = namespace_declaration(&packet, namespace);
+    attributes.push_str(&format!("\n    {qualified}=\"{}\"", escape(value)));
+    packet.insert_str(insert_at, &attributes);
+
+    Ok(packet)
+}
+
+/// 言語別の代替テキスト（`rdf:Alt`）を`x-default`の値のみで書き換えます
+///
+/// `None`または空文字列の場合はプロパティを削除します。
+pub(crate) fn set_lang_alt(
+    packet: &str,
+    namespace: (&str, &str),
+    name: &str,
+    value: Option<&str>,
+) -> Result<String, Error> {
+    let items: Vec<String> = value
+        .filter(|value| !value.is_empty())
+        .map(|value| format!("<rdf:li xml:lang=\"x-default\">{}</rdf:li>", escape(value)))
+        .into_iter()
+        .collect();
+    set_container(packet, namespace, name, "rdf:Alt", &items)
+}
+
+/// 配列（`rdf:Bag`または`rdf:Seq`）を書き換えます
+///
+/// 空の値は除き、値がない場合はプロパティを削除します。
+pub(crate) fn set_array(
+    packet: &str,
+    namespace: (&str, &str),
+    name: &str,
+    container: &str,
+    values: &[String],
+) -> Result<String, Error> {
+    let items: Vec<String> = values
+        .iter()
+        .filter(|value| !value.is_empty())
+        .map(|value| format!("<rdf:li>{}</rdf:li>", escape(value)))
+        .collect();
+    set_container(packet, namespace, name, container, &items)
+}
+
+/// `rdf:li`要素を持つコンテナのプロパティを書き換えます
+///
+/// 既存の属性・要素をすべて削除し、最初の`rdf:Description`の子要素として追加します。
+fn set_container(
+    packet: &str,
+    namespace: (&str, &str),
+    name: &str,
+    container: &str,
+    items: &[String],
+) -> Result<String, Error> {
+    let qualified = format!("{}:{name}", namespace.0);
+    let mut packet = open_packet(packet);
+    remove_property(&mut packet, &qualified);
+    if items.is_empty() {
+        return Ok(packet);
+    }
+
+    let mut element = format!("\n   <{qualified}>\n    <{container}>");
+    for item in items {
+        element.push_str("\n     ");
+        element.push_str(item);
+    }
+    element.push_str(&format!("\n    </{container}>\n   </{qualified}>"));
+
+    let description = first_description(&mut packet)?;
+    let end = tag_end(&packet, description)
+        .ok_or_else(|| Error::ParseError("Unterminated rdf:Description".to_string()))?;
+    let declaration = namespace_declaration(&packet, namespace);
+
+    if packet[..end].ends_with("/>") {
+        // 空要素を開始タグと終了タグに分ける
+        packet.replace_range(
+            end - 2..end,
+            &format!("{declaration}>{element}\n  </rdf:Description>"),
+        );
+    } else {
+        let close = packet[end..]
+            .find("</rdf:Description>")
+            .map(|length| end + length)
+            .ok_or_else(|| Error::ParseError("Unterminated rdf:Description".to_string()))?;
+        let insert_at = packet[..close].trim_end().len().max(end);
+        packet.insert_str(insert_at, &element);
+        packet.insert_str(end - 1, &declaration);
+    }
+
+    Ok(packet)
+}
+
+/// 編集対象のパケットを返します（空の場合は新しいパケットを作成）
+fn open_packet(packet: &str) -> String {
+    if packet.trim().is_empty() {
+        EMPTY_PACKET.to_string()
+    } else {
+        packet.to_string()
+    }
+}
+
+/// プロパティの属性形式・要素形式の値をすべて削除します
+fn remove_property(packet: &mut String, qualified: &str) {
+    // 属性形式の値を削除（後ろから削除して位置をずらさない）
+    let mut removals = Vec::new();
+    for start in find_start_tags(packet, "rdf:Description") {
+        let Some(end) = tag_end(packet, start) else {
+            continue;
+        };
+        for attribute in parse_attributes(&packet[start..end]) {
+            if attribute.name == qualified {
+                removals.push((start + attribute.span.start, start + attribute.span.end));
+            }
+        }
+    }
+    remove_ranges(packet, &mut removals);
+
+    // 要素形式の値を削除
+    while let Some(element) = find_element(packet, qualified) {
+        packet.replace_range(element.whitespace_start..element.end, "");
+    }
+}
+
+/// 標準XMPに収まるように、プロパティの一部を拡張XMPに移して分割します
+///
+/// XMP仕様のPart 3に従い、`rdf:Description`の最上位のプロパティ（属性形式・要素形式）を
+/// 大きいものから順に拡張XMPへ移し、`fits`が`true`を返した時点の標準XMPと拡張XMPを返します。
+/// 拡張XMPは`xpacket`のラッパーを持たない`x:xmpmeta`で、元のパケットの名前空間の宣言を引き継ぎます。
+/// プロパティを解釈できない場合や、すべて移しても収まらない場合は`None`を返します。
+pub(crate) fn split_extended(
+    packet: &str,
+    fits: impl Fn(&str) -> bool,
+) -> Option<(String, String)> {
+    struct Property<'a> {
+        /// 直前の空白を含むパケット内の範囲
+        range: std::ops::Range<usize>,
+        /// 拡張XMPに書き込む内容
+        text: &'a str,
+        attribute: bool,
+    }
+
+    let mut properties = Vec::new();
+    let mut declarations: Vec<&str> = Vec::new();
+    for tag in ["x:xmpmeta", "rdf:RDF", "rdf:Description"] {
+        for start in find_start_tags(packet, tag) {
+            // プロパティの値の中の入れ子の`rdf:Description`は、そのプロパティとともに移す
+            if properties
+                .iter()
+                .any(|property: &Property| !property.attribute && property.range.contains(&start))
+            {
+                continue;
+            }
+            let open_end = tag_end(packet, start)?;
+            for attribute in parse_attributes(&packet[start..open_end]) {
+                let text =
+                    packet[start + attribute.span.start..start + attribute.span.end].trim_start();
+                if attribute.name.starts_with("xmlns:") {
+                    if !matches!(attribute.name, "xmlns:x" | "xmlns:rdf")
+                        && !declarations
+                            .iter()
+                            .any(|d| d.split('=').next() == Some(attribute.name))
+                    {
+                        declarations.push(text);
+                    }
+                } else if tag == "rdf:Description" && !attribute.name.starts_with("rdf:") {
+                    properties.push(Property {
+                        range: start + attribute.span.start..start + attribute.span.end,
+                        text,
+                        attribute: true,
+                    });
+                }
+            }
+
+            if tag != "rdf:Description" || packet[..open_end].ends_with("/>") {
+                continue;
+            }
+            // 子要素（要素形式のプロパティ）を列挙する
+            let mut pos = open_end;
+            loop {
+                let child = pos + (packet[pos..].len() - packet[pos..].trim_start().len());
+                let rest = &packet[child..];
+                if rest.starts_with("</") || !rest.starts_with('<') {
+                    break;
+                }
+                let name_len =
+                    rest[1..].find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')?;
+                let end = element_end(packet, child, &rest[1..1 + name_len])?;
+                properties.push(Property {
+                    range: pos..end,
+                    text: packet[child..end].trim(),
+                    attribute: false,
+                });
+                pos = end;
+            }
+        }
+    }
+
+    // 大きいプロパティから順に移す
+    let mut order: Vec<usize> = (0..properties.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(properties[index].text.len()));
+    let mut moved = Vec::new();
+    for index in order {
+        moved.push(index);
+        let mut ranges: Vec<(usize, usize)> = moved
+            .iter()
+            .map(|&index| (properties[index].range.start, properties[index].range.end))
+            .collect();
+        let mut standard = packet.to_string();
+        remove_ranges(&mut standard, &mut ranges);
+        if !fits(&standard) {
+            continue;
+        }
+
+        moved.sort_unstable();
+        let mut extended = String::from(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  <rdf:Description rdf:about=\"\"",
+        );
+        for text in declarations.iter().copied().chain(
+            moved
+                .iter()
+                .filter(|&&index| properties[index].attribute)
+                .map(|&index| properties[index].text),
+        ) {
+            extended.push_str("\n    ");
+            extended.push_str(text);
+        }
+        let elements: Vec<&str> = moved
+            .iter()
+            .filter(|&&index| !properties[index].attribute)
+            .map(|&index| properties[index].text)
+            .collect();
+        if elements.is_empty() {
+            extended.push_str("/>");
+        } else {
+            extended.push('>');
+            for text in elements {
+                extended.push_str("\n   ");
+                extended.push_str(text);
+            }
+            extended.push_str("\n  </rdf:Description>");
+        }
+        extended.push_str("\n </rdf:RDF>\n</x:xmpmeta>");
+        return Some((standard, extended));
+    }
+
+    None
+}
+
+/// 開始タグの位置から、対応する終了タグの直後の位置を返します
+fn element_end(packet: &str, start: usize, name: &str) -> Option<usize> {
+    let open_end = tag_end(packet, start)?;
+    if packet[..open_end].ends_with("/>") {
+        return Some(open_end);
+    }
+
+    // 同じ名前の要素の入れ子を数える
+    let close = format!("</{name}>");
+    let mut depth = 1;
+    let mut pos = open_end;
+    loop {
+        let next_close = pos + packet[pos..].find(&close)?;
+        let nested = find_start_tags(&packet[pos..next_close], name);
+        for &nested_start in &nested {
+            let nested_end = tag_end(packet, pos + nested_start)?;
+            if !packet[..nested_end].ends_with("/>") {
+                depth += 1;
+            }
+        }
+        depth -= 1;
+        pos = next_close + close.len();
+        if depth == 0 {
+            return Some(pos);
+        }
+    }
+}
+
+/// 最初の`rdf:Description`の位置を返します（ない場合は`rdf:RDF`の直後に追加）
+fn first_description(packet: &mut String) -> Result<usize, Error> {
+    if let Some(&start) = find_start_tags(packet, "rdf:Description").first() {
+        return Ok(start);
+    }
+
+    let rdf = find_start_tags(packet, "rdf:RDF")
+        .first()
+        .and_then(|&start| tag_end(packet, start))
+        .ok_or_else(|| Error::ParseError("rdf:RDF not found in XMP".to_string()))?;
+    packet.insert_str(rdf, "\n  <rdf:Description rdf:about=\"\"/>");
+    Ok(rdf + 3)
+}
+
+/// 名前空間が宣言されていない場合に追加する属性を返します
+fn namespace_declaration(packet: &str, (prefix, uri): (&str, &str)) -> String {
+    if packet.contains(&format!("xmlns:{prefix}=")) {
+        String::new()
+    } else {
+        format!("\n    xmlns:{prefix}=\"{uri}\"")
+    }
+}
+
+/// 属性の名前と値の位置
+struct Attribute<'a> {
+    name: &'a str,
+    value: &'a str,
+    /// 直前の空白から閉じ引用符までの範囲（タグの先頭からの相対位置）
+    span: std::ops::Range<usize>,
+}
+
+/// 開始タグ（`<name ...>`）の属性を列挙します
+fn parse_attributes(tag: &str) -> Vec<Attribute<'_>> {
+    let bytes = tag.as_bytes();
+    let mut attributes = Vec::new();
+
+    // タグ名を読み飛ばす
+    let mut pos = 1;
+    while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && !b"/>".contains(&bytes[pos]) {
+        pos += 1;
+    }
+
+    loop {
+        let span_start = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos >= bytes.len() || b"/>".contains(&bytes[pos]) {
+            break;
+        }
+
+        let name_start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'=' {
+            pos += 1;
+        }
+        let name = &tag[name_start..pos];
+        while pos < bytes.len() && (bytes[pos].is_ascii_whitespace() || bytes[pos] == b'=') {
+            pos += 1;
+        }
+
+        let Some(&quote) = bytes.get(pos).filter(|b| matches!(b, b'"' | b'\'')) else {
+            break;
+        };
+        let Some(length) = tag[pos + 1..].find(quote as char) else {
+            break;
+        };
+        let value = &tag[pos + 1..pos + 1 + length];
+        pos += length + 2;
+
+        attributes.push(Attribute {
+            name,
+            value,
+            span: span_start..pos,
+        });
+    }
+
+    attributes
+}
+
+/// 要素の位置
+struct Element {
+    /// 開始タグの直前の空白の位置
+    whitespace_start: usize,
+    /// 要素の内容の範囲（空要素の場合は空）
+    content: std::ops::Range<usize>,
+    /// 終了タグの直後の位置
+    end: usize,
+}
+
+/// 指定した名前の最初の要素を探します
+fn find_element(packet: &str, name: &str) -> Option<Element> {
+    let start = *find_start_tags(packet, name).first()?;
+    let whitespace_start = packet[..start].trim_end().len();
+    let open_end = tag_end(packet, start)?;
+
+    if packet[..open_end].ends_with("/>") {
+        return Some(Element {
+            whitespace_start,
+            content: open_end..open_end,
+            end: open_end,
+        });
+    }
+
+    let close = format!("</{name}>");
+    let content_end = open_end + packet[open_end..].find(&close)?;
+    Some(Element {
+        whitespace_start,
+        content: open_end..content_end,
+        end: content_end + close.len(),
+    })
+}
+
+/// 指定した名前の開始タグの位置を列挙します
+fn find_start_tags(packet: &str, name: &str) -> Vec<usize> {
+    let pattern = format!("<{name}");
+    packet
+        .match_indices(&pattern)
+        .map(|(index, _)| index)
+        .filter(|&index| {
+            packet[index + pattern.len()..]
+                .bytes()
+                .next()
+                .is_some_and(|b| b.is_ascii_whitespace() || b == b'>' || b == b'/')
+        })
+        .collect()
+}
+
+/// 開始タグの終端（`>`の直後）の位置を返します（引用符内の`>`は無視します）
+fn tag_end(packet: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (index, b) in packet[start..].bytes().enumerate() {
+        match (quote, b) {
+            (None, b'"' | b'\'') => quote = Some(b),
+            (Some(q), _) if q == b => quote = None,
+            (None, b'>') => return Some(start + index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 指定した範囲を後ろから削除します
+fn remove_ranges(packet: &mut String, ranges: &mut Vec<(usize, usize)>) {
+    ranges.sort_unstable();
+    ranges.dedup();
+    for (start, end) in ranges.drain(..).rev() {
+        packet.replace_range(start..end, "");
+    }
+}
+
+/// XMLの特殊文字をエスケープします
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// XMLの定義済み実体参照と文字参照を展開します
+fn unescape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(pos) = rest.find('&') {
+        output.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
@@ -0,0 +1,236 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{batch, jpeg, Error, Processor};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_process_continues_after_failure() {
+    let items = vec![
+        (
+            "basic",
+            load_test_image("jpeg/metadata/metadata_basic_exif.jpg"),
+        ),
+        ("corrupt", b"not an image".to_vec()),
+        ("png", load_test_image("png/metadata/metadata_text.png")),
+    ];
+
+    let mut processor = Processor::new();
+    let results: Vec<_> = batch::process(items, |data: Vec<u8>| {
+        processor.clean(&data).map(|cleaned| cleaned.len())
+    })
+    .collect();
+
+    let ids: Vec<_> = results.iter().map(|(id, _)| *id).collect();
+    assert_eq!(ids, ["basic", "corrupt", "png"]);
+    assert!(results[0].1.is_ok());
+    assert!(matches!(results[1].1, Err(Error::InvalidFormat(_))));
+    assert!(results[2].1.is_ok());
+
+    // 失敗だけを集める
+    let items = [
+        (0, load_test_image("jpeg/metadata/metadata_none.jpg")),
+        (1, vec![0xFF, 0xD8, 0xFF]),
+    ];
+    let failures: Vec<_> = batch::process(items, |data| jpeg::clean_metadata(&data))
+        .filter_map(|(id, result)| result.err().map(|_| id))
+        .collect();
+    assert_eq!(failures, [1]);
+}
+
+#[test]
+fn test_process_is_lazy() {
+    let mut calls = 0;
+    let mut results = batch::process((0..10).map(|i| (i, i)), |i| {
+        calls += 1;
+        Ok::<_, Error>(i * 2)
+    });
+    let (id, result) = results.next().unwrap();
+    assert_eq!((id, result.unwrap()), (0, 0));
+    drop(results);
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn test_clean_tree() {
+    use batch::{Glob, GlobSetBuilder, TreeOptions};
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    let jpeg_data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let png_data = load_test_image("png/metadata/metadata_text.png");
+    fs::write(root.join("top.jpg"), &jpeg_data).unwrap();
+    fs::write(root.join("a/b/deep.png"), &png_data).unwrap();
+    fs::write(root.join("a/broken.jpg"), b"not an image").unwrap();
+    fs::write(root.join("a/notes.txt"), b"not an image").unwrap();
+
+    let filter = GlobSetBuilder::new()
+        .add(Glob::new("**/*.{jpg,png}").unwrap())
+        .build()
+        .unwrap();
+
+    // dry_runではファイルを書き換えない
+    let options = TreeOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let report = batch::clean_tree(root, &filter, &options).unwrap();
+    assert_eq!(report.files_processed, 3);
+    assert_eq!(report.files_touched, 2);
+    assert_eq!(fs::read(root.join("top.jpg")).unwrap(), jpeg_data);
+
+    let report = batch::clean_tree(root, &filter, &TreeOptions::default()).unwrap();
+    assert_eq!(report.files_processed, 3);
+    assert_eq!(report.files_touched, 2);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].0, root.join("a/broken.jpg"));
+
+    let expected_jpeg = jpeg::clean_metadata(&jpeg_data).unwrap();
+    let expected_png = web_image_meta::png::clean_chunks(&png_data).unwrap();
+    assert_eq!(fs::read(root.join("top.jpg")).unwrap(), expected_jpeg);
+    assert_eq!(fs::read(root.join("a/b/deep.png")).unwrap(), expected_png);
+    assert_eq!(
+        report.bytes_saved,
+        (jpeg_data.len() - expected_jpeg.len() + png_data.len() - expected_png.len()) as u64
+    );
+    assert_eq!(report.bytes_grown, 0);
+
+    // 一時ファイルは残らない
+    let names: Vec<_> = fs::read_dir(root)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names.len(), 2);
+
+    // 2回目は書き換えるファイルがない
+    let report = batch::clean_tree(root, &filter, &TreeOptions::default()).unwrap();
+    assert_eq!(report.files_touched, 0);
+    assert_eq!(report.bytes_saved, 0);
+
+    assert!(batch::clean_tree(root.join("missing"), &filter, &TreeOptions::default()).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clean_tree_does_not_follow_temp_symlink() {
+    use batch::{Glob, GlobSetBuilder, TreeOptions};
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    let jpeg_data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    fs::write(root.join("top.jpg"), &jpeg_data).unwrap();
+    fs::set_permissions(root.join("top.jpg"), fs::Permissions::from_mode(0o640)).unwrap();
+    fs::write(root.join("victim.txt"), b"keep").unwrap();
+
+    // 一時ファイルの名前に置かれたシンボリックリンクは開かずに別の名前を使う
+    let temp_name = format!(".top.jpg.{}.0.tmp", std::process::id());
+    std::os::unix::fs::symlink(root.join("victim.txt"), root.join(&temp_name)).unwrap();
+
+    let filter = GlobSetBuilder::new()
+        .add(Glob::new("*.jpg").unwrap())
+        .build()
+        .unwrap();
+    let report = batch::clean_tree(root, &filter, &TreeOptions::default()).unwrap();
+    assert_eq!(report.files_touched, 1);
+    assert_eq!(fs::read(root.join("victim.txt")).unwrap(), b"keep");
+    assert_eq!(
+        fs::read(root.join("top.jpg")).unwrap(),
+        jpeg::clean_metadata(&jpeg_data).unwrap()
+    );
+    let mode = fs::metadata(root.join("top.jpg"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o640);
+}
+
+#[test]
+fn test_clean_tree_cancelled() {
+    use batch::{Glob, GlobSetBuilder, TreeOptions};
+    use web_image_meta::CancellationToken;
+
+    let dir = tempfile::tempdir().unwrap();
+    let jpeg_data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    fs::write(dir.path().join("a.jpg"), &jpeg_data).unwrap();
+    let filter = GlobSetBuilder::new()
+        .add(Glob::new("*.jpg").unwrap())
+        .build()
+        .unwrap();
+
+    let token = CancellationToken::new();
+    token.cancel();
+    let options = TreeOptions {
+        cancellation: Some(token),
+        ..Default::default()
+    };
+    assert!(matches!(
+        batch::clean_tree(dir.path(), &filter, &options),
+        Err(Error::Cancelled)
+    ));
+    // 中断前のファイルは書き換えない
+    assert_eq!(fs::read(dir.path().join("a.jpg")).unwrap(), jpeg_data);
+}
+
+#[test]
+fn test_clean_tree_progress() {
+    use batch::{Glob, GlobSetBuilder, TreeOptions};
+    use std::sync::{Arc, Mutex};
+    use web_image_meta::{ProgressCallback, ProgressStage};
+
+    let dir = tempfile::tempdir().unwrap();
+    let jpeg_data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    fs::write(dir.path().join("a.jpg"), &jpeg_data).unwrap();
+    fs::write(dir.path().join("b.jpg"), &jpeg_data).unwrap();
+    let filter = GlobSetBuilder::new()
+        .add(Glob::new("*.jpg").unwrap())
+        .build()
+        .unwrap();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let options = TreeOptions {
+        progress: Some(ProgressCallback::new(move |p| sink.lock().unwrap().push(p))),
+        ..Default::default()
+    };
+    batch::clean_tree(dir.path(), &filter, &options).unwrap();
+
+    // ファイルごとに入力の検証の開始から通知する
+    let events = events.lock().unwrap();
+    let starts = events
+        .iter()
+        .filter(|p| p.stage == ProgressStage::ValidateInput && p.processed == 0)
+        .count();
+    assert_eq!(starts, 2);
+    assert_eq!(events.last().unwrap().fraction(), 1.0);
+}
+
+#[test]
+fn test_process_with_cancellation() {
+    use web_image_meta::CancellationToken;
+
+    let token = CancellationToken::new();
+    let mut calls = 0;
+    let results: Vec<_> = batch::process_with_cancellation(
+        vec![(1, "a"), (2, "b"), (3, "c")],
+        token.clone(),
+        |input: &str| {
+            calls += 1;
+            // 最初の入力の処理中に中断する
+            token.cancel();
+            Ok(input.to_uppercase())
+        },
+    )
+    .collect();
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, 1);
+    assert_eq!(results[0].1.as_ref().unwrap(), "A");
+    assert_eq!(results[1].0, 2);
+    assert!(matches!(results[1].1, Err(Error::Cancelled)));
+    assert_eq!(calls, 1);
+}
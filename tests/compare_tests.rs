@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, metadata_equal, pixels_equal, png};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_compare_jpeg() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let with_comment = jpeg::write_comment(&data, "only the comment differs").unwrap();
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+
+    assert!(pixels_equal(&data, &data));
+    assert!(metadata_equal(&data, &data));
+
+    // メタデータのみ異なる
+    assert!(pixels_equal(&data, &with_comment));
+    assert!(!metadata_equal(&data, &with_comment));
+    assert!(pixels_equal(&data, &cleaned));
+    assert!(!metadata_equal(&data, &cleaned));
+
+    // 末尾データはどちらの比較にも含まれない
+    let mut with_trailing = data.clone();
+    with_trailing.extend_from_slice(b"garbage");
+    assert!(pixels_equal(&data, &with_trailing));
+    assert!(metadata_equal(&data, &with_trailing));
+
+    // 画像データが異なる
+    let other = load_test_image("jpeg/quality/quality_20.jpg");
+    assert!(!pixels_equal(&data, &other));
+
+    // 解析できない入力や形式の異なる入力は`false`
+    let png_data = load_test_image("png/chunk/chunk_background.png");
+    assert!(!pixels_equal(&data, b"not an image"));
+    assert!(!metadata_equal(b"not an image", b"not an image"));
+    assert!(!pixels_equal(&data, &png_data));
+}
+
+#[test]
+fn test_compare_png() {
+    let data = load_test_image("png/chunk/chunk_background.png");
+    let with_text = png::add_text_chunk(&data, "Comment", "text only").unwrap();
+    let cleaned = png::clean_chunks(&data).unwrap();
+
+    assert!(pixels_equal(&data, &with_text));
+    assert!(!metadata_equal(&data, &with_text));
+    assert!(pixels_equal(&data, &cleaned));
+    assert!(!metadata_equal(&data, &cleaned));
+
+    let other = load_test_image("png/colortype/colortype_rgb.png");
+    assert!(!pixels_equal(&data, &other));
+}
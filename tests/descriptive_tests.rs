@@ -0,0 +1,343 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::exif::{IfdKind, XpTag};
+use web_image_meta::{
+    jpeg, read_descriptive, read_descriptive_with_options, read_iptc_charset, read_keywords,
+    write_descriptive, write_descriptive_with_options, write_keywords, Descriptive,
+    DescriptiveOptions, DescriptiveWriteOptions, IptcCharset, KeywordMode,
+};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// IPTC-IIMのデータセット（レコード2）を組み立てる
+fn iim(datasets: &[(u8, &str)]) -> Vec<u8> {
+    let mut output = Vec::new();
+    for (dataset, value) in datasets {
+        output.extend_from_slice(&[0x1C, 2, *dataset]);
+        output.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        output.extend_from_slice(value.as_bytes());
+    }
+    output
+}
+
+/// IPTCと任意のダイジェストを格納したAPP13をSOIの直後に挿入する
+fn insert_iptc(data: &[u8], iim: &[u8], digest: Option<[u8; 16]>) -> Vec<u8> {
+    let mut resources = Vec::new();
+    let mut push_resource = |id: u16, content: &[u8]| {
+        resources.extend_from_slice(b"8BIM");
+        resources.extend_from_slice(&id.to_be_bytes());
+        resources.extend_from_slice(&[0, 0]);
+        resources.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        resources.extend_from_slice(content);
+        if content.len() % 2 == 1 {
+            resources.push(0);
+        }
+    };
+    push_resource(0x0404, iim);
+    if let Some(digest) = digest {
+        push_resource(0x0425, &digest);
+    }
+
+    let mut output = data[..2].to_vec();
+    output.extend_from_slice(&[0xFF, 0xED]);
+    output.extend_from_slice(&((resources.len() + 16) as u16).to_be_bytes());
+    output.extend_from_slice(b"Photoshop 3.0\0");
+    output.extend_from_slice(&resources);
+    output.extend_from_slice(&data[2..]);
+    output
+}
+
+#[test]
+fn test_read_descriptive_single_sources() {
+    let data = load_test_image("jpeg/metadata/metadata_iptc.jpg");
+    assert_eq!(
+        read_descriptive(&data).unwrap(),
+        Descriptive {
+            description: Some("Test IPTC Caption".to_string()),
+            creator: vec!["Test Photographer".to_string()],
+            copyright: Some("Test Copyright".to_string()),
+            ..Default::default()
+        }
+    );
+
+    let data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let descriptive = read_descriptive(&data).unwrap();
+    assert_eq!(descriptive.description.as_deref(), Some("Test XMP Data"));
+    assert_eq!(descriptive.creator, ["Test Suite"]);
+
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(read_descriptive(&data).unwrap(), Descriptive::default());
+    assert!(read_descriptive(b"not an image").is_err());
+}
+
+#[test]
+fn test_read_descriptive_mwg_precedence() {
+    let xmp = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let iim = iim(&[
+        (5, "IPTC Title"),
+        (25, "sunrise"),
+        (25, "mountain"),
+        (120, "IPTC Caption"),
+    ]);
+
+    // ダイジェストがない場合はXMPを優先し、XMPにない項目はIPTCから補う
+    let data = insert_iptc(&xmp, &iim, None);
+    let descriptive = read_descriptive(&data).unwrap();
+    assert_eq!(descriptive.description.as_deref(), Some("Test XMP Data"));
+    assert_eq!(descriptive.title.as_deref(), Some("IPTC Title"));
+    assert_eq!(descriptive.keywords, ["sunrise", "mountain"]);
+
+    // ダイジェストが一致しない場合はIPTCが変更されたとみなして優先する
+    let data = insert_iptc(&xmp, &iim, Some([0; 16]));
+    let descriptive = read_descriptive(&data).unwrap();
+    assert_eq!(descriptive.description.as_deref(), Some("IPTC Caption"));
+
+    // EXIFは常に優先する
+    let data = jpeg::set_exif_text(&data, IfdKind::Ifd0, 0x010E, "EXIF Description").unwrap();
+    let data = jpeg::set_exif_text(&data, IfdKind::Ifd0, 0x013B, "Alice; Bob").unwrap();
+    let descriptive = read_descriptive(&data).unwrap();
+    assert_eq!(descriptive.description.as_deref(), Some("EXIF Description"));
+    assert_eq!(descriptive.creator, ["Alice", "Bob"]);
+}
+
+#[test]
+fn test_read_descriptive_xp_tags() {
+    // エクスプローラーで付けたXP系のタグのみの画像
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let data = jpeg::set_xp_text(&data, XpTag::Title, "夕焼け").unwrap();
+    let data = jpeg::set_xp_text(&data, XpTag::Comment, "From the balcony").unwrap();
+    let data = jpeg::set_xp_text(&data, XpTag::Keywords, "sunset;sky; ").unwrap();
+    let data = jpeg::set_xp_text(&data, XpTag::Author, "Alice").unwrap();
+    assert_eq!(
+        read_descriptive(&data).unwrap(),
+        Descriptive {
+            title: Some("夕焼け".to_string()),
+            description: Some("From the balcony".to_string()),
+            keywords: vec!["sunset".to_string(), "sky".to_string()],
+            creator: vec!["Alice".to_string()],
+            copyright: None,
+        }
+    );
+
+    // 他のメタデータがある項目はそちらを優先する
+    let data = jpeg::set_exif_text(&data, IfdKind::Ifd0, 0x010E, "EXIF Description").unwrap();
+    let descriptive = read_descriptive(&data).unwrap();
+    assert_eq!(descriptive.description.as_deref(), Some("EXIF Description"));
+    assert_eq!(descriptive.title.as_deref(), Some("夕焼け"));
+
+    // 書き込み時は古いXP系のタグを削除する
+    let output = write_descriptive(&data, &Descriptive::default()).unwrap();
+    assert_eq!(read_descriptive(&output).unwrap(), Descriptive::default());
+}
+
+#[test]
+fn test_write_descriptive() {
+    let descriptive = Descriptive {
+        title: Some("朝焼けの富士山".to_string()),
+        description: Some("Sunrise over Mt. Fuji & Lake Kawaguchi".to_string()),
+        keywords: vec!["sunrise".to_string(), "富士山".to_string()],
+        creator: vec!["Alice".to_string(), "Bob".to_string()],
+        copyright: Some("(c) 2024 Example".to_string()),
+    };
+
+    // IPTCのみの画像にEXIFとXMPを作成して書き込む
+    let data = load_test_image("jpeg/metadata/metadata_iptc.jpg");
+    let output = write_descriptive(&data, &descriptive).unwrap();
+    assert_eq!(read_descriptive(&output).unwrap(), descriptive);
+
+    // 3つのブロックすべてに同じ値がある
+    assert_eq!(
+        web_image_meta::exif::read_text(&output, IfdKind::Ifd0, 0x013B).unwrap(),
+        Some("Alice; Bob".to_string())
+    );
+    let packet = jpeg::read_xmp(&output).unwrap().unwrap();
+    assert!(packet.contains("<rdf:li>富士山</rdf:li>"));
+    assert!(packet.contains("Mt. Fuji &amp; Lake"));
+    let caption = b"\x1C\x02\x78\x00\x26Sunrise over Mt. Fuji & Lake Kawaguchi";
+    assert!(output.windows(caption.len()).any(|w| w == caption));
+    assert!(output.windows(3).any(|w| w == b"\x1B%G"));
+
+    // 既存のXMPのプロパティは保持し、値を更新する
+    let data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let xmp = jpeg::read_xmp(&data).unwrap().unwrap();
+    let xmp = web_image_meta::xmp::set_rating(&xmp, Some(5)).unwrap();
+    let data = jpeg::write_xmp(&data, &xmp).unwrap();
+    let output = write_descriptive(&data, &descriptive).unwrap();
+    assert_eq!(read_descriptive(&output).unwrap(), descriptive);
+    let packet = jpeg::read_xmp(&output).unwrap().unwrap();
+    assert_eq!(web_image_meta::xmp::read_rating(&packet), Some(5));
+    assert!(!packet.contains("Test XMP Data"));
+
+    // 空の説明情報ですべて削除
+    let cleared = write_descriptive(&output, &Descriptive::default()).unwrap();
+    assert_eq!(read_descriptive(&cleared).unwrap(), Descriptive::default());
+    assert!(!cleared.windows(4).any(|w| w == b"8BIM"));
+
+    let png = load_test_image("png/metadata/metadata_none.png");
+    assert!(write_descriptive(&png, &descriptive).is_err());
+}
+
+#[test]
+fn test_read_write_keywords() {
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+
+    // XMPとIPTCでキーワードが異なる画像
+    let data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let data = write_keywords(&data, &strings(&["sea", "sky"]), KeywordMode::Replace).unwrap();
+    let data = insert_iptc(
+        &data,
+        &iim(&[(25, "sky"), (25, "boat"), (120, "Caption")]),
+        None,
+    );
+    assert_eq!(read_keywords(&data).unwrap(), ["sea", "sky", "boat"]);
+
+    // 追加すると両方のブロックが同じキーワードになる
+    let merged =
+        write_keywords(&data, &strings(&["boat", " sunset "]), KeywordMode::Merge).unwrap();
+    assert_eq!(
+        read_keywords(&merged).unwrap(),
+        ["sea", "sky", "boat", "sunset"]
+    );
+    let packet = jpeg::read_xmp(&merged).unwrap().unwrap();
+    assert!(packet.contains("<rdf:li>boat</rdf:li>"));
+    assert!(merged.windows(8).any(|w| w == b"\x1C\x02\x19\x00\x03sea"));
+
+    // 置き換えてもキーワード以外の値は保持
+    let replaced = write_keywords(&merged, &strings(&["night"]), KeywordMode::Replace).unwrap();
+    assert_eq!(read_keywords(&replaced).unwrap(), ["night"]);
+    let descriptive = read_descriptive(&replaced).unwrap();
+    assert_eq!(descriptive.description.as_deref(), Some("Test XMP Data"));
+    assert!(replaced.windows(7).any(|w| w == b"Caption"));
+
+    // キーワードのない画像
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert!(read_keywords(&data).unwrap().is_empty());
+    let output = write_keywords(&data, &strings(&["new"]), KeywordMode::Merge).unwrap();
+    assert_eq!(read_keywords(&output).unwrap(), ["new"]);
+}
+
+#[test]
+fn test_read_descriptive_iptc_charset() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let dataset = |record: u8, id: u8, value: &[u8]| {
+        let mut output = vec![0x1C, record, id];
+        output.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        output.extend_from_slice(value);
+        output
+    };
+
+    // CodedCharacterSetでISO 8859-1を宣言したIPTC
+    let latin1 = [
+        dataset(1, 90, b"\x1B.A"),
+        dataset(2, 120, b"Caf\xE9 cr\xE8me"),
+    ]
+    .concat();
+    let jpeg = insert_iptc(&data, &latin1, None);
+    assert_eq!(read_iptc_charset(&jpeg).unwrap(), Some(IptcCharset::Latin1));
+    assert_eq!(
+        read_descriptive(&jpeg).unwrap().description.as_deref(),
+        Some("Café crème")
+    );
+
+    // 宣言のないShift_JISのIPTCは、オプションで指定した文字コードで復号する
+    let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode("富士山");
+    let jpeg = insert_iptc(&data, &dataset(2, 5, &sjis), None);
+    assert_eq!(read_iptc_charset(&jpeg).unwrap(), Some(IptcCharset::Latin1));
+    let options = DescriptiveOptions {
+        iptc_charset: IptcCharset::ShiftJis,
+    };
+    let descriptive = read_descriptive_with_options(&jpeg, &options).unwrap();
+    assert_eq!(descriptive.title.as_deref(), Some("富士山"));
+    assert_ne!(
+        read_descriptive(&jpeg).unwrap().title.as_deref(),
+        Some("富士山")
+    );
+
+    // 宣言がなくてもUTF-8として正しい値はUTF-8として扱う
+    let jpeg = insert_iptc(&data, &iim(&[(5, "富士山")]), None);
+    assert_eq!(read_iptc_charset(&jpeg).unwrap(), Some(IptcCharset::Utf8));
+    let descriptive = read_descriptive_with_options(&jpeg, &options).unwrap();
+    assert_eq!(descriptive.title.as_deref(), Some("富士山"));
+
+    assert_eq!(read_iptc_charset(&data).unwrap(), None);
+}
+
+#[test]
+fn test_write_descriptive_legacy_caption() {
+    let data = load_test_image("jpeg/metadata/metadata_iptc.jpg");
+    let descriptive = Descriptive {
+        description: Some("Café terrace".to_string()),
+        ..Default::default()
+    };
+    let options = DescriptiveWriteOptions {
+        legacy_caption: true,
+    };
+
+    // 既定では旧形式のキャプションを書き込まない
+    let legacy = b"8BIM\x03\xF0\0\0\0\0\0\x0D\x0CCaf\xE9 terrace";
+    let output = write_descriptive(&data, &descriptive).unwrap();
+    assert!(!output.windows(legacy.len()).any(|w| w == legacy));
+
+    // Windows-1252のパスカル文字列として書き込み、IPTCとダイジェストも更新する
+    let output = write_descriptive_with_options(&data, &descriptive, &options).unwrap();
+    assert!(output.windows(legacy.len()).any(|w| w == legacy));
+    assert_eq!(read_descriptive(&output).unwrap(), descriptive);
+    assert!(output.windows(6).any(|w| w == b"8BIM\x04\x25"));
+
+    // 書き直しても1つだけ残り、表せない文字はUTF-8で書き込む
+    let descriptive = Descriptive {
+        description: Some("桜".to_string()),
+        ..Default::default()
+    };
+    let output = write_descriptive_with_options(&output, &descriptive, &options).unwrap();
+    let caption = b"8BIM\x03\xF0\0\0\0\0\0\x04\x03\xE6\xA1\x9C";
+    assert!(output.windows(caption.len()).any(|w| w == caption));
+    assert_eq!(
+        output.windows(6).filter(|w| w == b"8BIM\x03\xF0").count(),
+        1
+    );
+
+    // 説明を削除すると旧形式のキャプションも削除する
+    let cleared =
+        write_descriptive_with_options(&output, &Descriptive::default(), &options).unwrap();
+    assert!(!cleared.windows(6).any(|w| w == b"8BIM\x03\xF0"));
+}
+
+#[test]
+fn test_read_descriptive_ducky() {
+    // 画質と著作権表示のみのDuckyをSOIの直後に挿入する
+    let copyright: Vec<u8> = "(c) Example"
+        .encode_utf16()
+        .flat_map(u16::to_be_bytes)
+        .collect();
+    let mut payload = b"Ducky\x00\x01\x00\x04\x00\x00\x00\x46\x00\x03".to_vec();
+    payload.extend_from_slice(&((copyright.len() + 4) as u16).to_be_bytes());
+    payload.extend_from_slice(&((copyright.len() / 2) as u32).to_be_bytes());
+    payload.extend_from_slice(&copyright);
+    payload.extend_from_slice(&[0, 0]);
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let mut jpeg = data[..2].to_vec();
+    jpeg.extend_from_slice(&[0xFF, 0xEC]);
+    jpeg.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    jpeg.extend_from_slice(&payload);
+    jpeg.extend_from_slice(&data[2..]);
+
+    // 他のメタデータがない場合はDuckyの著作権表示を採用する
+    let descriptive = read_descriptive(&jpeg).unwrap();
+    assert_eq!(descriptive.copyright.as_deref(), Some("(c) Example"));
+    assert_eq!(descriptive.description, None);
+
+    // 書き込みでは古い値が読み取られないよう削除し、画質は保持する
+    let output = write_descriptive(&jpeg, &Descriptive::default()).unwrap();
+    assert_eq!(read_descriptive(&output).unwrap(), Descriptive::default());
+    assert_eq!(
+        jpeg::read_ducky(&output).unwrap(),
+        Some(jpeg::Ducky {
+            quality: Some(70),
+            ..Default::default()
+        })
+    );
+}
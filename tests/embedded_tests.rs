@@ -0,0 +1,133 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use web_image_meta::{extract_embedded, jpeg, EmbeddedAssets};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+// ヘルパー関数：SOI直後にセグメントを挿入
+fn insert_segment_after_soi(data: &[u8], marker: u8, payload: &[u8]) -> Vec<u8> {
+    let mut output = vec![0xFF, 0xD8, 0xFF, marker];
+    output.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    output.extend_from_slice(payload);
+    output.extend_from_slice(&data[2..]);
+    output
+}
+
+// ヘルパー関数：IEND直前にチャンクを挿入
+fn insert_chunk_before_iend(data: &[u8], chunk_type: &[u8; 4], chunk_data: &[u8]) -> Vec<u8> {
+    let iend = data.len() - 12;
+    let mut output = data[..iend].to_vec();
+    output.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(chunk_data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(chunk_data);
+    output.extend_from_slice(&hasher.finalize().to_be_bytes());
+    output.extend_from_slice(&data[iend..]);
+    output
+}
+
+#[test]
+fn test_extract_embedded_jpeg() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(extract_embedded(&data).unwrap(), EmbeddedAssets::default());
+
+    let data = load_test_image("jpeg/icc/icc_applep3.jpg");
+    let icc = extract_embedded(&data).unwrap().icc.expect("ICC not found");
+    let declared = u32::from_be_bytes([icc[0], icc[1], icc[2], icc[3]]) as usize;
+    assert_eq!(icc.len(), declared);
+    assert_eq!(&icc[36..40], b"acsp");
+
+    let data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let thumbnail = extract_embedded(&data).unwrap().exif_thumbnail;
+    assert!(thumbnail
+        .expect("Thumbnail not found")
+        .starts_with(&[0xFF, 0xD8]));
+
+    let data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let xmp = extract_embedded(&data).unwrap().xmp.expect("XMP not found");
+    assert_eq!(
+        Some(String::from_utf8(xmp).unwrap()),
+        jpeg::read_xmp(&data).unwrap()
+    );
+
+    let data = load_test_image("jpeg/metadata/metadata_iptc.jpg");
+    let iptc = extract_embedded(&data)
+        .unwrap()
+        .iptc
+        .expect("IPTC not found");
+    assert_eq!(iptc[0], 0x1C);
+}
+
+#[test]
+fn test_extract_embedded_mpf_images() {
+    let primary = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let secondary = load_test_image("jpeg/thumbnail/thumbnail_none.jpg");
+
+    // MPFセグメント: "MPF\0" + TIFFヘッダー + MPエントリ（代表画像と副画像）
+    let segment_len = 2 + 2 + 4 + 8 + 2 + 12 + 4 + 32;
+    let primary_len = primary.len() + segment_len;
+    let mut mpf = b"MPF\0II*\0".to_vec();
+    mpf.extend_from_slice(&8u32.to_le_bytes());
+    mpf.extend_from_slice(&1u16.to_le_bytes());
+    mpf.extend_from_slice(&0xB002u16.to_le_bytes());
+    mpf.extend_from_slice(&7u16.to_le_bytes());
+    mpf.extend_from_slice(&32u32.to_le_bytes());
+    mpf.extend_from_slice(&26u32.to_le_bytes());
+    mpf.extend_from_slice(&0u32.to_le_bytes());
+    for (attribute, size, offset) in [
+        (0x2003_0000u32, primary_len, 0),
+        (0, secondary.len(), primary_len - 10),
+    ] {
+        mpf.extend_from_slice(&attribute.to_le_bytes());
+        mpf.extend_from_slice(&(size as u32).to_le_bytes());
+        mpf.extend_from_slice(&(offset as u32).to_le_bytes());
+        mpf.extend_from_slice(&[0; 4]);
+    }
+
+    let mut data = insert_segment_after_soi(&primary, 0xE2, &mpf);
+    assert_eq!(data.len(), primary_len);
+    data.extend_from_slice(&secondary);
+
+    let assets = extract_embedded(&data).expect("Failed to extract");
+    assert_eq!(assets.mpf_images, vec![secondary]);
+}
+
+#[test]
+fn test_extract_embedded_png() {
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert_eq!(extract_embedded(&data).unwrap(), EmbeddedAssets::default());
+
+    let profile = b"fake ICC profile data".to_vec();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&profile).unwrap();
+    let mut iccp = b"test\0\0".to_vec();
+    iccp.extend_from_slice(&encoder.finish().unwrap());
+
+    let packet = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>";
+    let mut itxt = b"XML:com.adobe.xmp\0\0\0\0\0".to_vec();
+    itxt.extend_from_slice(packet.as_bytes());
+
+    let with_icc = insert_chunk_before_iend(&data, b"iCCP", &iccp);
+    let with_xmp = insert_chunk_before_iend(&with_icc, b"iTXt", &itxt);
+    let with_c2pa = insert_chunk_before_iend(&with_xmp, b"caBX", b"jumbf");
+
+    let assets = extract_embedded(&with_c2pa).expect("Failed to extract");
+    assert_eq!(assets.icc, Some(profile));
+    assert_eq!(assets.xmp, Some(packet.as_bytes().to_vec()));
+    assert_eq!(assets.c2pa, Some(b"jumbf".to_vec()));
+    assert_eq!(assets.iptc, None);
+    assert!(assets.mpf_images.is_empty());
+}
+
+#[test]
+fn test_extract_embedded_invalid() {
+    assert!(extract_embedded(b"not an image").is_err());
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{etag, etag_with_options, jpeg, png, EtagOptions};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+const PIXELS_ONLY: EtagOptions = EtagOptions { pixels_only: true };
+
+#[test]
+fn test_etag_format() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let tag = etag(&data).expect("Failed to compute ETag");
+
+    assert!(tag.starts_with("W/\"") && tag.ends_with('"'));
+    assert_eq!(tag.len(), 2 + 1 + 32 + 1);
+    assert!(tag[3..35].chars().all(|c| c.is_ascii_hexdigit()));
+
+    // 同じ入力なら常に同じ値
+    assert_eq!(tag, etag(&data).unwrap());
+}
+
+#[test]
+fn test_etag_ignores_jpeg_comment_and_trailing_data() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let with_comment = jpeg::write_comment(&data, "only the comment differs").unwrap();
+    let mut with_trailing = data.clone();
+    with_trailing.extend_from_slice(b"garbage");
+
+    let original = etag(&data).unwrap();
+    assert_eq!(original, etag(&with_comment).unwrap());
+    assert_eq!(original, etag(&with_trailing).unwrap());
+}
+
+#[test]
+fn test_etag_pixels_only_ignores_jpeg_metadata() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+
+    // 通常モードではEXIFの変更を検出する
+    assert_ne!(etag(&data).unwrap(), etag(&cleaned).unwrap());
+
+    // 画素のみのモードでは同じ値になる
+    assert_eq!(
+        etag_with_options(&data, &PIXELS_ONLY).unwrap(),
+        etag_with_options(&cleaned, &PIXELS_ONLY).unwrap()
+    );
+}
+
+#[test]
+fn test_etag_png() {
+    let data = load_test_image("png/chunk/chunk_background.png");
+    let with_text = png::add_text_chunk(&data, "Comment", "text only").unwrap();
+    let cleaned = png::clean_chunks(&data).unwrap();
+
+    assert_eq!(etag(&data).unwrap(), etag(&with_text).unwrap());
+    assert_ne!(etag(&data).unwrap(), etag(&cleaned).unwrap());
+    assert_eq!(
+        etag_with_options(&data, &PIXELS_ONLY).unwrap(),
+        etag_with_options(&cleaned, &PIXELS_ONLY).unwrap()
+    );
+}
+
+#[test]
+fn test_etag_differs_between_images() {
+    let a = load_test_image("jpeg/quality/quality_50.jpg");
+    let b = load_test_image("jpeg/quality/quality_80.jpg");
+    assert_ne!(etag(&a).unwrap(), etag(&b).unwrap());
+    assert_ne!(
+        etag_with_options(&a, &PIXELS_ONLY).unwrap(),
+        etag_with_options(&b, &PIXELS_ONLY).unwrap()
+    );
+
+    // 通常モードと画素のみのモードは区別される
+    assert_ne!(
+        etag(&a).unwrap(),
+        etag_with_options(&a, &PIXELS_ONLY).unwrap()
+    );
+    assert!(etag(b"not an image").is_err());
+}
@@ -0,0 +1,507 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::exif::{self, IfdKind, XpTag};
+use web_image_meta::{jpeg, Utf8Policy};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// JPEGのAPP1からTIFFデータの開始位置を探す
+fn find_tiff_offset(data: &[u8]) -> usize {
+    data.windows(6)
+        .position(|window| window == b"Exif\0\0")
+        .expect("EXIF not found")
+        + 6
+}
+
+#[test]
+fn test_entries_raw_offsets() {
+    let data = load_test_image("jpeg/orientation/orientation_6.jpg");
+    let entries = exif::entries_raw(&data).expect("Failed to walk EXIF");
+    assert!(!entries.is_empty());
+
+    let orientation = entries
+        .iter()
+        .find(|entry| entry.ifd == IfdKind::Ifd0 && entry.tag == 0x0112)
+        .expect("Orientation not found");
+    assert_eq!(orientation.field_type, 3);
+    assert_eq!(orientation.count, 1);
+    assert_eq!(orientation.value_offset, orientation.entry_offset + 8);
+
+    // 絶対位置から値を直接読み取れる
+    let tiff = find_tiff_offset(&data);
+    let value = if &data[tiff..tiff + 2] == b"II" {
+        u16::from_le_bytes([
+            data[orientation.value_offset],
+            data[orientation.value_offset + 1],
+        ])
+    } else {
+        u16::from_be_bytes([
+            data[orientation.value_offset],
+            data[orientation.value_offset + 1],
+        ])
+    };
+    assert_eq!(value, 6);
+
+    // エントリのタグも絶対位置から読み取れる
+    for entry in &entries {
+        let bytes = [data[entry.entry_offset], data[entry.entry_offset + 1]];
+        let tag = if &data[tiff..tiff + 2] == b"II" {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        };
+        assert_eq!(tag, entry.tag);
+    }
+}
+
+#[test]
+fn test_entries_raw_traverses_sub_ifds() {
+    let data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let entries = exif::entries_raw(&data).unwrap();
+
+    for kind in [IfdKind::Ifd0, IfdKind::Exif, IfdKind::Ifd1] {
+        assert!(
+            entries.iter().any(|entry| entry.ifd == kind),
+            "{kind:?} not traversed"
+        );
+    }
+
+    // kamadak-exifと同じ数のエントリ（IFDへのポインタを除く）
+    let reader = ::exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(&data))
+        .unwrap();
+    let pointers = [0x8769, 0x8825, 0xA005];
+    let count = entries
+        .iter()
+        .filter(|entry| !pointers.contains(&entry.tag))
+        .count();
+    assert_eq!(count, reader.fields().count());
+
+    // TIFFデータを直接渡した場合は位置がTIFF先頭からの相対位置になる
+    let tiff = find_tiff_offset(&data);
+    let from_tiff = exif::entries_raw(&data[tiff..]).unwrap();
+    assert_eq!(from_tiff.len(), entries.len());
+    assert_eq!(from_tiff[0].entry_offset + tiff, entries[0].entry_offset);
+}
+
+/// リトルエンディアンのIFDテーブル（エントリは（タグ, 型, 個数, 4バイトの値））を書き込む
+fn write_ifd(tiff: &mut Vec<u8>, entries: &[(u16, u16, u32, [u8; 4])], next: u32) {
+    tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, field_type, count, value) in entries {
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&field_type.to_le_bytes());
+        tiff.extend_from_slice(&count.to_le_bytes());
+        tiff.extend_from_slice(value);
+    }
+    tiff.extend_from_slice(&next.to_le_bytes());
+}
+
+#[test]
+fn test_entries_raw_interop_and_overlap() {
+    // IFD0(8) → Exif IFD(50) → 互換性IFD(80)
+    // GPS IFDはIFD0のテーブルの途中を指し、IFD1はIFD0を指す（循環）
+    let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+    write_ifd(
+        &mut tiff,
+        &[
+            (0x0112, 3, 1, [6, 0, 0, 0]),
+            (0x8769, 4, 1, 50u32.to_le_bytes()),
+            (0x8825, 4, 1, 24u32.to_le_bytes()),
+        ],
+        8,
+    );
+    write_ifd(
+        &mut tiff,
+        &[
+            (0x9000, 7, 4, *b"0230"),
+            (0xA005, 4, 1, 80u32.to_le_bytes()),
+        ],
+        0,
+    );
+    write_ifd(&mut tiff, &[(0x0001, 2, 4, *b"R98\0")], 0);
+
+    let entries = exif::entries_raw(&tiff).unwrap();
+    let tags: Vec<(IfdKind, u16)> = entries.iter().map(|entry| (entry.ifd, entry.tag)).collect();
+    assert_eq!(
+        tags,
+        [
+            (IfdKind::Ifd0, 0x0112),
+            (IfdKind::Ifd0, 0x8769),
+            (IfdKind::Ifd0, 0x8825),
+            (IfdKind::Exif, 0x9000),
+            (IfdKind::Exif, 0xA005),
+            (IfdKind::Interop, 0x0001),
+        ]
+    );
+
+    // 互換性IFDのタグ名はGPS IFDのタグと区別する
+    assert_eq!(entries[5].tag_name(), Some("InteropIndex"));
+    assert_eq!(entries[0].tag_name(), Some("Orientation"));
+
+    // 互換性IFDの値はIFDを指定して読み取れる
+    assert_eq!(
+        exif::read_text(&tiff, IfdKind::Interop, 0x0001).unwrap(),
+        Some("R98".to_string())
+    );
+}
+
+#[test]
+fn test_entries_raw_without_exif() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert!(exif::entries_raw(&data).unwrap().is_empty());
+
+    let data = load_test_image("png/metadata/metadata_none.png");
+    assert!(exif::entries_raw(&data).unwrap().is_empty());
+
+    assert!(exif::entries_raw(b"not an image").is_err());
+
+    // 軽量化後はオリエンテーションのみ
+    let data = load_test_image("jpeg/orientation/orientation_3.jpg");
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+    let entries = exif::entries_raw(&cleaned).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].tag, 0x0112);
+}
+
+#[test]
+fn test_tag_name_registry() {
+    assert_eq!(exif::tag_name(0x0112), Some("Orientation"));
+    assert_eq!(exif::tag_name(0x9003), Some("DateTimeOriginal"));
+    assert_eq!(exif::tag_name(0xA434), Some("LensModel"));
+    assert_eq!(exif::tag_name(0x0000), Some("GPSVersionID"));
+    assert_eq!(exif::tag_name(0x0002), Some("GPSLatitude"));
+    assert_eq!(exif::tag_name(0x1234), None);
+
+    assert_eq!(exif::tag_id("Orientation"), Some(0x0112));
+    assert_eq!(exif::tag_id("gpslongitude"), Some(0x0004));
+    assert_eq!(exif::tag_id("NoSuchTag"), None);
+
+    // サンプル画像のIFD0・Exif IFD・GPS IFDのタグはすべて名前が引ける
+    let data = load_test_image("jpeg/metadata/metadata_gps.jpg");
+    let entries = exif::entries_raw(&data).unwrap();
+    assert!(entries.iter().any(|entry| entry.ifd == IfdKind::Gps));
+    for entry in entries
+        .iter()
+        .filter(|entry| matches!(entry.ifd, IfdKind::Ifd0 | IfdKind::Exif | IfdKind::Gps))
+    {
+        let name = exif::tag_name(entry.tag)
+            .unwrap_or_else(|| panic!("no name for tag 0x{:04X}", entry.tag));
+        assert_eq!(exif::tag_id(name), Some(entry.tag));
+    }
+}
+
+/// GPS IFDだけを持つビッグエンディアンのTIFFデータを組み立てる
+///
+/// エントリは（タグ, 型, 個数, 値）で、4バイトを超える値はIFDの後ろに置く
+fn build_gps_tiff(entries: &[(u16, u16, u32, Vec<u8>)]) -> Vec<u8> {
+    let gps_offset = 8 + 2 + 12 + 4;
+    let mut tiff = b"MM\0*\0\0\0\x08".to_vec();
+    tiff.extend_from_slice(&[0, 1, 0x88, 0x25, 0, 4, 0, 0, 0, 1]);
+    tiff.extend_from_slice(&(gps_offset as u32).to_be_bytes());
+    tiff.extend_from_slice(&[0; 4]);
+
+    let mut value_offset = gps_offset + 2 + entries.len() * 12 + 4;
+    let mut values = Vec::new();
+    tiff.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+    for (tag, field_type, count, value) in entries {
+        tiff.extend_from_slice(&tag.to_be_bytes());
+        tiff.extend_from_slice(&field_type.to_be_bytes());
+        tiff.extend_from_slice(&count.to_be_bytes());
+        if value.len() <= 4 {
+            let mut inline = value.clone();
+            inline.resize(4, 0);
+            tiff.extend_from_slice(&inline);
+        } else {
+            tiff.extend_from_slice(&(value_offset as u32).to_be_bytes());
+            value_offset += value.len();
+            values.extend_from_slice(value);
+        }
+    }
+    tiff.extend_from_slice(&[0; 4]);
+    tiff.extend_from_slice(&values);
+    tiff
+}
+
+/// 有理数の列をビッグエンディアンのバイト列にする
+fn rationals(values: &[(u32, u32)]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|(n, d)| [n.to_be_bytes(), d.to_be_bytes()].concat())
+        .collect()
+}
+
+#[test]
+fn test_read_gps_coordinates() {
+    let data = load_test_image("jpeg/metadata/metadata_gps.jpg");
+    let gps = exif::read_gps_coordinates(&data).unwrap().unwrap();
+    assert!((gps.lat - 35.6762).abs() < 1e-6);
+    assert!((gps.lon - 139.6503).abs() < 1e-6);
+    assert_eq!(gps.alt, None);
+
+    // 南緯・西経・海面下
+    let tiff = build_gps_tiff(&[
+        (0x0001, 2, 2, b"S\0".to_vec()),
+        (0x0002, 5, 3, rationals(&[(33, 1), (52, 1), (1800, 100)])),
+        (0x0003, 2, 2, b"W\0".to_vec()),
+        (0x0004, 5, 3, rationals(&[(151, 1), (12, 1), (36, 1)])),
+        (0x0005, 1, 1, vec![1]),
+        (0x0006, 5, 1, rationals(&[(125, 10)])),
+    ]);
+    let gps = exif::read_gps_coordinates(&tiff).unwrap().unwrap();
+    assert!((gps.lat + (33.0 + 52.0 / 60.0 + 18.0 / 3600.0)).abs() < 1e-9);
+    assert!((gps.lon + (151.0 + 12.0 / 60.0 + 36.0 / 3600.0)).abs() < 1e-9);
+    assert_eq!(gps.alt, Some(-12.5));
+    assert_eq!(gps.img_direction, None);
+    assert_eq!(gps.speed, None);
+
+    // 速度はkm/hに換算し、撮影方向は記録された値を返す
+    let tiff = build_gps_tiff(&[
+        (0x0002, 5, 3, rationals(&[(35, 1), (0, 1), (0, 1)])),
+        (0x0004, 5, 3, rationals(&[(139, 1), (0, 1), (0, 1)])),
+        (0x000C, 2, 2, b"N\0".to_vec()),
+        (0x000D, 5, 1, rationals(&[(10, 1)])),
+        (0x0010, 2, 2, b"M\0".to_vec()),
+        (0x0011, 5, 1, rationals(&[(9050, 100)])),
+    ]);
+    let gps = exif::read_gps_coordinates(&tiff).unwrap().unwrap();
+    assert!((gps.speed.unwrap() - 18.52).abs() < 1e-9);
+    assert_eq!(gps.img_direction, Some(90.5));
+
+    // 経度がない、分母が0
+    let tiff = build_gps_tiff(&[(0x0002, 5, 3, rationals(&[(33, 1), (0, 1), (0, 1)]))]);
+    assert_eq!(exif::read_gps_coordinates(&tiff).unwrap(), None);
+    let tiff = build_gps_tiff(&[
+        (0x0002, 5, 3, rationals(&[(33, 0), (0, 1), (0, 1)])),
+        (0x0004, 5, 3, rationals(&[(151, 1), (0, 1), (0, 1)])),
+    ]);
+    assert_eq!(exif::read_gps_coordinates(&tiff).unwrap(), None);
+
+    let none = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(exif::read_gps_coordinates(&none).unwrap(), None);
+}
+
+#[test]
+fn test_date_times_with_offset() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let date_times = exif::read_date_times(&data).unwrap().unwrap();
+    let original = date_times.original.clone().unwrap();
+    assert_eq!(original.value, "2024:01:01 12:00:00");
+    assert_eq!(original.offset, None);
+    assert_eq!(original.unix_timestamp(), None);
+
+    // 撮影日時に時差を追加し、変更日時はそのまま
+    let updated = jpeg::set_date_times(
+        &data,
+        &exif::DateTimes {
+            original: Some(exif::DateTime::new("2024:01:01 12:00:00", Some("+09:00"))),
+            digitized: Some(exif::DateTime::new("2023:12:31 22:30:00", Some("-05:30"))),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let read = exif::read_date_times(&updated).unwrap().unwrap();
+    assert_eq!(read.modified, date_times.modified);
+    let original = read.original.unwrap();
+    assert_eq!(original.offset.as_deref(), Some("+09:00"));
+    assert_eq!(
+        original.to_rfc3339().as_deref(),
+        Some("2024-01-01T12:00:00+09:00")
+    );
+    assert_eq!(original.unix_timestamp(), Some(1_704_078_000));
+    // 時差を考慮すると撮影日時より後
+    let digitized = read.digitized.unwrap();
+    assert_eq!(digitized.offset_minutes(), Some(-330));
+    assert_eq!(digitized.unix_timestamp(), Some(1_704_081_600));
+
+    // 時差を指定しないと既存の時差を削除
+    let updated = jpeg::set_date_times(
+        &updated,
+        &exif::DateTimes {
+            original: Some(exif::DateTime::new("2024:01:01 12:00:00", None)),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let read = exif::read_date_times(&updated).unwrap().unwrap();
+    assert_eq!(read.original.unwrap().offset, None);
+    assert!(read.digitized.unwrap().offset.is_some());
+
+    // EXIFのない画像、不正な形式
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(exif::read_date_times(&data).unwrap(), None);
+    let date_times = exif::DateTimes {
+        modified: Some(exif::DateTime::new("2024-01-01 12:00:00", None)),
+        ..Default::default()
+    };
+    assert!(jpeg::set_date_times(&data, &date_times).is_err());
+    let date_times = exif::DateTimes {
+        modified: Some(exif::DateTime::new("2024:01:01 12:00:00", Some("JST"))),
+        ..Default::default()
+    };
+    assert!(jpeg::set_date_times(&data, &date_times).is_err());
+}
+
+#[test]
+fn test_utf8_text_type() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+
+    // 非ASCIIの文字列はEXIF 3.0のUTF-8型（129）で記録
+    let output = jpeg::set_exif_text(&data, IfdKind::Ifd0, 0x010E, "富士山の夜明け").unwrap();
+    let entries = exif::entries_raw(&output).unwrap();
+    let description = entries
+        .iter()
+        .find(|entry| entry.ifd == IfdKind::Ifd0 && entry.tag == 0x010E)
+        .unwrap();
+    assert_eq!(description.field_type, 129);
+    assert_eq!(description.count as usize, "富士山の夜明け".len() + 1);
+
+    // 別のタグを書き換えて組み立て直してもUTF-8の値は失われない
+    let output = jpeg::set_exif_text(&output, IfdKind::Ifd0, 0x013B, "Taro Yamada").unwrap();
+    assert_eq!(
+        exif::read_text(&output, IfdKind::Ifd0, 0x010E).unwrap(),
+        Some("富士山の夜明け".to_string())
+    );
+    let artist = exif::entries_raw(&output)
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.tag == 0x013B)
+        .unwrap();
+    assert_eq!(artist.field_type, 2);
+    assert_eq!(
+        exif::read_text(&output, IfdKind::Ifd0, 0x013B).unwrap(),
+        Some("Taro Yamada".to_string())
+    );
+
+    // 空文字列で削除、ポインタタグやNULは書き込めない
+    let output = jpeg::set_exif_text(&output, IfdKind::Ifd0, 0x010E, "").unwrap();
+    assert_eq!(
+        exif::read_text(&output, IfdKind::Ifd0, 0x010E).unwrap(),
+        None
+    );
+    assert!(jpeg::set_exif_text(&data, IfdKind::Ifd0, 0x8769, "x").is_err());
+    assert!(jpeg::set_exif_text(&data, IfdKind::Ifd0, 0x013B, "a\0b").is_err());
+}
+
+#[test]
+fn test_xp_text() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    assert_eq!(exif::read_xp_text(&data, XpTag::Title).unwrap(), None);
+
+    // IFD0にUCS-2（リトルエンディアン）のBYTE配列として記録
+    let output = jpeg::set_xp_text(&data, XpTag::Title, "富士山").unwrap();
+    let entry = exif::entries_raw(&output)
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.ifd == IfdKind::Ifd0 && entry.tag == 0x9C9B)
+        .unwrap();
+    assert_eq!(entry.field_type, 1);
+    assert_eq!(
+        &output[entry.value_offset..entry.value_offset + entry.value_len],
+        &[0xCC, 0x5B, 0xEB, 0x58, 0x71, 0x5C, 0, 0]
+    );
+    assert_eq!(
+        exif::read_xp_text(&output, XpTag::Title).unwrap(),
+        Some("富士山".to_string())
+    );
+
+    // 他のタグを追加しても値は保持される
+    let output = jpeg::set_xp_text(&output, XpTag::Keywords, "sunrise; mountain").unwrap();
+    assert_eq!(
+        exif::read_xp_text(&output, XpTag::Keywords).unwrap(),
+        Some("sunrise; mountain".to_string())
+    );
+    assert_eq!(
+        exif::read_xp_text(&output, XpTag::Title).unwrap(),
+        Some("富士山".to_string())
+    );
+
+    // 空文字列で削除、NULは書き込めない
+    let output = jpeg::set_xp_text(&output, XpTag::Title, "").unwrap();
+    assert_eq!(exif::read_xp_text(&output, XpTag::Title).unwrap(), None);
+    assert!(jpeg::set_xp_text(&data, XpTag::Comment, "a\0b").is_err());
+
+    // EXIFがない画像には最小限のEXIFを追加
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let output = jpeg::set_xp_text(&data, XpTag::Subject, "Landscape").unwrap();
+    assert_eq!(
+        exif::read_xp_text(&output, XpTag::Subject).unwrap(),
+        Some("Landscape".to_string())
+    );
+}
+
+#[test]
+fn test_read_text_with_policy() {
+    // ASCII型のArtistにLatin-1のバイト列を記録したTIFF（リトルエンディアン）
+    let value = b"Ren\xE9 \0";
+    let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+    tiff.extend_from_slice(&1u16.to_le_bytes());
+    tiff.extend_from_slice(&0x013Bu16.to_le_bytes());
+    tiff.extend_from_slice(&2u16.to_le_bytes());
+    tiff.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    tiff.extend_from_slice(&26u32.to_le_bytes());
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend_from_slice(value);
+
+    let decoded =
+        exif::read_text_with_policy(&tiff, IfdKind::Ifd0, 0x013B, Utf8Policy::Latin1Fallback)
+            .unwrap()
+            .unwrap();
+    assert_eq!(decoded.text, "René");
+    assert_eq!(decoded.repaired, Some(Utf8Policy::Latin1Fallback));
+
+    let decoded = exif::read_text_with_policy(&tiff, IfdKind::Ifd0, 0x013B, Utf8Policy::Lossy)
+        .unwrap()
+        .unwrap();
+    assert_eq!(decoded.text, "Ren\u{FFFD}");
+    assert!(exif::read_text_with_policy(&tiff, IfdKind::Ifd0, 0x013B, Utf8Policy::Reject).is_err());
+    assert_eq!(
+        exif::read_text(&tiff, IfdKind::Ifd0, 0x013B).unwrap(),
+        Some("Ren\u{FFFD}".to_string())
+    );
+
+    // 正しいUTF-8は修復されない
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let output = jpeg::set_exif_text(&data, IfdKind::Ifd0, 0x010E, "富士山").unwrap();
+    let decoded = exif::read_text_with_policy(&output, IfdKind::Ifd0, 0x010E, Utf8Policy::Reject)
+        .unwrap()
+        .unwrap();
+    assert_eq!(decoded.text, "富士山");
+    assert_eq!(decoded.repaired, None);
+}
+
+#[test]
+fn test_set_thumbnail() {
+    let source = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let thumbnail = web_image_meta::extract_embedded(&source)
+        .unwrap()
+        .exif_thumbnail
+        .unwrap();
+
+    // 既存のサムネイルを差し替える（COMセグメントを加えて内容を変える）
+    let mut replacement = thumbnail[..2].to_vec();
+    replacement.extend_from_slice(&[0xFF, 0xFE, 0x00, 0x07]);
+    replacement.extend_from_slice(b"fresh");
+    replacement.extend_from_slice(&thumbnail[2..]);
+    let output = jpeg::set_thumbnail(&source, &replacement).unwrap();
+    let embedded = web_image_meta::extract_embedded(&output).unwrap();
+    assert_eq!(embedded.exif_thumbnail.as_deref(), Some(&replacement[..]));
+    let entries = exif::entries_raw(&output).unwrap();
+    let compression = entries
+        .iter()
+        .find(|entry| entry.ifd == IfdKind::Ifd1 && entry.tag == 0x0103)
+        .unwrap();
+    assert_eq!(compression.tag_name(), Some("Compression"));
+
+    // EXIFのない画像には最小限のEXIFとともに追加される
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let output = jpeg::set_thumbnail(&data, &thumbnail).unwrap();
+    let embedded = web_image_meta::extract_embedded(&output).unwrap();
+    assert_eq!(embedded.exif_thumbnail.as_deref(), Some(&thumbnail[..]));
+
+    // JPEGでないサムネイルや本体より大きいサムネイルは拒否する
+    assert!(jpeg::set_thumbnail(&data, b"not a jpeg").is_err());
+    assert!(jpeg::set_thumbnail(&thumbnail, &data).is_err());
+}
@@ -0,0 +1,108 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, png, read_info, sniff, sniff_mime, ImageFormat, PixelFormat};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_sniff_mime() {
+    let jpeg = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let png = load_test_image("png/metadata/metadata_none.png");
+
+    assert_eq!(sniff_mime(&jpeg), Some("image/jpeg"));
+    assert_eq!(sniff_mime(&png), Some("image/png"));
+
+    // 先頭の数バイトだけでも判定できる
+    assert_eq!(sniff_mime(&jpeg[..3]), Some("image/jpeg"));
+    assert_eq!(sniff_mime(&png[..8]), Some("image/png"));
+}
+
+#[test]
+fn test_sniff_mime_unsupported() {
+    assert_eq!(sniff_mime(b""), None);
+    assert_eq!(sniff_mime(b"GIF89a"), None);
+    assert_eq!(sniff_mime(&[0xFF, 0xD8]), None);
+    assert_eq!(sniff_mime(&[137, 80, 78, 71, 13, 10, 26]), None);
+}
+
+#[test]
+fn test_image_format_detect() {
+    let jpeg = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(ImageFormat::detect(&jpeg), Some(ImageFormat::Jpeg));
+    assert_eq!(ImageFormat::Jpeg.mime_type(), "image/jpeg");
+    assert_eq!(ImageFormat::Png.mime_type(), "image/png");
+}
+
+#[test]
+fn test_sniff() {
+    // 定数式として評価できる
+    const PNG_HEADER: [u8; 16] = [137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82];
+    const FORMAT: Option<ImageFormat> = sniff(&PNG_HEADER);
+    assert_eq!(FORMAT, Some(ImageFormat::Png));
+
+    let jpeg = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let header: &[u8; 16] = jpeg[..16].try_into().unwrap();
+    assert_eq!(sniff(header), Some(ImageFormat::Jpeg));
+
+    assert_eq!(sniff(b"GIF89a\0\0\0\0\0\0\0\0\0\0"), None);
+    assert_eq!(sniff(&[0; 16]), None);
+}
+
+#[test]
+fn test_read_info() {
+    let jpeg_data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let info = read_info(&jpeg_data).unwrap();
+    assert_eq!(info, jpeg::read_info(&jpeg_data).unwrap());
+    assert_eq!(info.format, ImageFormat::Jpeg);
+    assert_eq!((info.width, info.height), (640, 480));
+    assert_eq!(
+        info.pixel_format,
+        PixelFormat {
+            channels: 3,
+            bits_per_channel: 8,
+            has_alpha: false,
+            palette: false,
+        }
+    );
+    let cmyk = load_test_image("jpeg/colorspace/colorspace_cmyk.jpg");
+    assert_eq!(read_info(&cmyk).unwrap().pixel_format.channels, 4);
+
+    let cases = [
+        (
+            "png/colortype/colortype_grayscale_alpha.png",
+            2,
+            8,
+            true,
+            false,
+        ),
+        ("png/colortype/colortype_rgb.png", 3, 8, false, false),
+        ("png/colortype/colortype_rgba.png", 4, 8, true, false),
+        ("png/colortype/colortype_palette.png", 1, 8, false, true),
+        ("png/depth/depth_16bit.png", 4, 16, true, false),
+    ];
+    for (path, channels, bits_per_channel, has_alpha, palette) in cases {
+        let data = load_test_image(path);
+        let info = png::read_info(&data).unwrap();
+        assert_eq!(info, read_info(&data).unwrap());
+        assert_eq!(info.format, ImageFormat::Png);
+        assert_eq!(
+            (info.width, info.height),
+            png::read_dimensions(&data).unwrap()
+        );
+        assert_eq!(
+            info.pixel_format,
+            PixelFormat {
+                channels,
+                bits_per_channel,
+                has_alpha,
+                palette,
+            },
+            "{path}"
+        );
+    }
+
+    assert!(read_info(b"GIF89a").is_err());
+}
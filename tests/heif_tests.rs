@@ -0,0 +1,322 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::heif;
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// ボックスを組み立てる
+fn make_box(box_type: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut output = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+    output.extend_from_slice(box_type);
+    output.extend_from_slice(content);
+    output
+}
+
+/// バージョン0・フラグ0のFullBoxを組み立てる
+fn make_full_box(box_type: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut full = vec![0, 0, 0, 0];
+    full.extend_from_slice(content);
+    make_box(box_type, &full)
+}
+
+/// プロパティと関連付け（アイテムID, プロパティ番号）からAVIFを組み立てる
+fn build_avif(properties: &[Vec<u8>], associations: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut ipma = (associations.len() as u32).to_be_bytes().to_vec();
+    for (item_id, indices) in associations {
+        ipma.extend_from_slice(&item_id.to_be_bytes());
+        ipma.push(indices.len() as u8);
+        ipma.extend(indices.iter().map(|index| index | 0x80));
+    }
+
+    let ipco = make_box(b"ipco", &properties.concat());
+    let iprp = make_box(b"iprp", &[ipco, make_full_box(b"ipma", &ipma)].concat());
+    let meta = make_full_box(
+        b"meta",
+        &[make_full_box(b"pitm", &1u16.to_be_bytes()), iprp].concat(),
+    );
+
+    [
+        make_box(b"ftyp", b"avif\0\0\0\0mif1miaf"),
+        meta,
+        make_box(b"mdat", &[0; 16]),
+    ]
+    .concat()
+}
+
+fn colr_prof(profile: &[u8]) -> Vec<u8> {
+    make_box(b"colr", &[b"prof".as_slice(), profile].concat())
+}
+
+fn colr_nclx(primaries: u16, transfer: u16, matrix: u16, full_range: bool) -> Vec<u8> {
+    let mut content = b"nclx".to_vec();
+    content.extend_from_slice(&primaries.to_be_bytes());
+    content.extend_from_slice(&transfer.to_be_bytes());
+    content.extend_from_slice(&matrix.to_be_bytes());
+    content.push(if full_range { 0x80 } else { 0 });
+    make_box(b"colr", &content)
+}
+
+/// JPEGのAPP2からICCプロファイルを取り出す
+fn extract_jpeg_icc(data: &[u8]) -> Vec<u8> {
+    let mut profile = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos + 1] != 0xDA {
+        let size = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let payload = &data[pos + 4..pos + 2 + size];
+        if data[pos + 1] == 0xE2 && payload.starts_with(b"ICC_PROFILE\0") {
+            profile.extend_from_slice(&payload[14..]);
+        }
+        pos += 2 + size;
+    }
+    profile
+}
+
+#[test]
+fn test_read_icc_profile_of_primary_item() {
+    let p3 = extract_jpeg_icc(&load_test_image("jpeg/icc/icc_applep3.jpg"));
+    let srgb = extract_jpeg_icc(&load_test_image("jpeg/icc/icc_srgb.jpg"));
+
+    // サムネイル（アイテム2）のプロファイルが先に並んでいても、プライマリアイテムのものを返す
+    let data = build_avif(
+        &[colr_prof(&srgb), colr_prof(&p3)],
+        &[(2, vec![1]), (1, vec![2])],
+    );
+    assert_eq!(heif::read_icc_profile(&data).unwrap(), Some(p3));
+    assert_eq!(heif::read_nclx(&data).unwrap(), None);
+}
+
+#[test]
+fn test_read_nclx() {
+    // Display P3（色域12）、sRGB伝達特性（13）、BT.601行列（6）
+    let data = build_avif(&[colr_nclx(12, 13, 6, true)], &[(1, vec![1])]);
+    assert_eq!(
+        heif::read_nclx(&data).unwrap(),
+        Some(heif::Nclx {
+            colour_primaries: 12,
+            transfer_characteristics: 13,
+            matrix_coefficients: 6,
+            full_range: true,
+        })
+    );
+    assert_eq!(heif::read_icc_profile(&data).unwrap(), None);
+}
+
+#[test]
+fn test_invalid_heif() {
+    assert!(heif::read_icc_profile(b"not a heif file").is_err());
+
+    let png = load_test_image("png/metadata/metadata_none.png");
+    assert!(heif::read_icc_profile(&png).is_err());
+
+    // ブランドがHEIF系ではない
+    let mp4 = make_box(b"ftyp", b"isom\0\0\0\0mp41");
+    assert!(heif::read_icc_profile(&mp4).is_err());
+
+    // ボックスのサイズがデータを超える
+    let mut data = build_avif(&[colr_nclx(1, 1, 1, false)], &[(1, vec![1])]);
+    data.truncate(data.len() - 4);
+    assert!(heif::read_nclx(&data).is_err());
+}
+
+/// 画像アイテム（ID 1）とEXIFアイテム（ID 2）を持つAVIFを組み立てる
+///
+/// mdatにはEXIF、画像の順にデータを格納する。
+fn build_avif_with_exif(colr: Vec<u8>, image: &[u8], exif: &[u8]) -> Vec<u8> {
+    let infe = |id: u16, item_type: &[u8; 4]| {
+        let mut content = vec![2, 0, 0, 0];
+        content.extend_from_slice(&id.to_be_bytes());
+        content.extend_from_slice(&[0, 0]);
+        content.extend_from_slice(item_type);
+        content.push(0);
+        make_box(b"infe", &content)
+    };
+    let iinf = make_full_box(
+        b"iinf",
+        &[
+            2u16.to_be_bytes().to_vec(),
+            infe(1, b"av01"),
+            infe(2, b"Exif"),
+        ]
+        .concat(),
+    );
+    // EXIFアイテムから画像アイテムへのcdsc参照
+    let iref = make_full_box(b"iref", &make_box(b"cdsc", &[0, 2, 0, 1, 0, 1]));
+    let ipco = make_box(b"ipco", &colr);
+    let ipma = make_full_box(b"ipma", &[0, 0, 0, 1, 0, 1, 1, 0x81]);
+    let iprp = make_box(b"iprp", &[ipco, ipma].concat());
+
+    // ilocの大きさは位置の値に依存しないため、metaの長さから位置を求める
+    let meta = |mdat_data: u32| {
+        let mut iloc = vec![0x44, 0x00, 0, 2];
+        for (id, offset, length) in [
+            (1u16, mdat_data + exif.len() as u32, image.len() as u32),
+            (2u16, mdat_data, exif.len() as u32),
+        ] {
+            iloc.extend_from_slice(&id.to_be_bytes());
+            iloc.extend_from_slice(&[0, 0, 0, 1]);
+            iloc.extend_from_slice(&offset.to_be_bytes());
+            iloc.extend_from_slice(&length.to_be_bytes());
+        }
+        make_full_box(
+            b"meta",
+            &[
+                make_full_box(b"pitm", &1u16.to_be_bytes()),
+                iinf.clone(),
+                make_full_box(b"iloc", &iloc),
+                iref.clone(),
+                iprp.clone(),
+            ]
+            .concat(),
+        )
+    };
+    let ftyp = make_box(b"ftyp", b"avif\0\0\0\0mif1miaf");
+    let mdat_data = (ftyp.len() + meta(0).len() + 8) as u32;
+
+    [
+        ftyp,
+        meta(mdat_data),
+        make_box(b"mdat", &[exif, image].concat()),
+    ]
+    .concat()
+}
+
+/// ilocから画像アイテム（ID 1）のデータを取り出す
+fn image_item_data(data: &[u8]) -> &[u8] {
+    let pos = data.windows(4).position(|w| w == b"iloc").unwrap() + 4;
+    let count = u16::from_be_bytes([data[pos + 6], data[pos + 7]]) as usize;
+    for index in 0..count {
+        let item = &data[pos + 8 + index * 14..];
+        if u16::from_be_bytes([item[0], item[1]]) == 1 {
+            let offset = u32::from_be_bytes(item[6..10].try_into().unwrap()) as usize;
+            let length = u32::from_be_bytes(item[10..14].try_into().unwrap()) as usize;
+            return &data[offset..offset + length];
+        }
+    }
+    panic!("image item not found");
+}
+
+/// ilocを8バイトの位置・長さ・ベースオフセットを持つものに置き換える
+///
+/// アイテムは（ID, ベースオフセット, エクステントのオフセット, 長さ）で指定する。
+fn replace_iloc(data: &[u8], items: &[(u16, u64, u64, u64)]) -> Vec<u8> {
+    let mut iloc = vec![0x88, 0x80];
+    iloc.extend_from_slice(&(items.len() as u16).to_be_bytes());
+    for &(id, base_offset, offset, length) in items {
+        iloc.extend_from_slice(&id.to_be_bytes());
+        iloc.extend_from_slice(&[0, 0]);
+        iloc.extend_from_slice(&base_offset.to_be_bytes());
+        iloc.extend_from_slice(&1u16.to_be_bytes());
+        iloc.extend_from_slice(&offset.to_be_bytes());
+        iloc.extend_from_slice(&length.to_be_bytes());
+    }
+    let iloc = make_full_box(b"iloc", &iloc);
+
+    let start = data.windows(4).position(|w| w == b"iloc").unwrap() - 4;
+    let end = start + u32::from_be_bytes(data[start..start + 4].try_into().unwrap()) as usize;
+    let meta = data.windows(4).position(|w| w == b"meta").unwrap() - 4;
+    let meta_size = u32::from_be_bytes(data[meta..meta + 4].try_into().unwrap());
+
+    let mut output = [&data[..start], &iloc, &data[end..]].concat();
+    let meta_size = meta_size as usize + iloc.len() - (end - start);
+    output[meta..meta + 4].copy_from_slice(&(meta_size as u32).to_be_bytes());
+    output
+}
+
+#[test]
+fn test_clean_metadata_rejects_overflowing_iloc() {
+    let image = b"AV1 IMAGE PAYLOAD";
+    let exif = b"\0\0\0\x06Exif\0\0MM\0\x2a\0\0\0\x08";
+    let data = build_avif_with_exif(colr_nclx(1, 1, 1, false), image, exif);
+
+    // 置き換えてもilocの大きさは変わらないため、mdatのデータの位置は同じ
+    let placeholder = replace_iloc(&data, &[(1, 0, 0, 1), (2, 0, 0, 1)]);
+    let mdat_data = (placeholder.len() - image.len() - exif.len()) as u64;
+    let exif_len = exif.len() as u64;
+    let image_len = image.len() as u64;
+
+    // 削除するアイテムのベースオフセットとオフセットの和が溢れる
+    let removed = replace_iloc(
+        &data,
+        &[
+            (1, 0, mdat_data + exif_len, image_len),
+            (2, u64::MAX, 16, exif_len),
+        ],
+    );
+    assert!(matches!(
+        heif::clean_metadata(&removed),
+        Err(web_image_meta::Error::InvalidFormat(_))
+    ));
+
+    // 残すアイテムの位置を書き換える際に溢れる
+    let kept = replace_iloc(
+        &data,
+        &[
+            (1, mdat_data + exif_len, u64::MAX, image_len),
+            (2, 0, mdat_data, exif_len),
+        ],
+    );
+    assert!(matches!(
+        heif::clean_metadata(&kept),
+        Err(web_image_meta::Error::InvalidFormat(_))
+    ));
+
+    // 正しい位置であれば削除できる
+    let valid = replace_iloc(
+        &data,
+        &[
+            (1, 0, mdat_data + exif_len, image_len),
+            (2, 0, mdat_data, exif_len),
+        ],
+    );
+    let cleaned = heif::clean_metadata(&valid).unwrap();
+    assert!(!cleaned.windows(4).any(|w| w == b"Exif"));
+}
+
+#[test]
+fn test_clean_metadata_preserves_colr() {
+    let image = b"AV1 IMAGE PAYLOAD";
+    let exif = b"\0\0\0\x06Exif\0\0MM\0\x2a\0\0\0\x08\0\0SECRET-GPS";
+    let p3 = extract_jpeg_icc(&load_test_image("jpeg/icc/icc_applep3.jpg"));
+
+    // Display P3のICCプロファイル、BT.2020のnclx
+    for colr in [colr_prof(&p3), colr_nclx(9, 16, 9, false)] {
+        let data = build_avif_with_exif(colr, image, exif);
+        assert_eq!(image_item_data(&data), image);
+
+        let cleaned = heif::clean_metadata(&data).unwrap();
+        assert_eq!(
+            heif::read_icc_profile(&cleaned).unwrap(),
+            heif::read_icc_profile(&data).unwrap()
+        );
+        assert_eq!(
+            heif::read_nclx(&cleaned).unwrap(),
+            heif::read_nclx(&data).unwrap()
+        );
+        assert!(!cleaned.windows(10).any(|w| w == b"SECRET-GPS"));
+        assert!(!cleaned.windows(4).any(|w| w == b"Exif"));
+        assert!(!cleaned.windows(4).any(|w| w == b"cdsc"));
+        assert_eq!(image_item_data(&cleaned), image);
+        assert_eq!(cleaned.len(), data.len() - exif.len() - 14 - 21 - 14);
+
+        // EXIFを保持する場合は変更しない
+        let options = heif::CleanOptions {
+            preserve_exif: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            heif::clean_metadata_with_options(&data, &options).unwrap(),
+            data
+        );
+    }
+
+    assert_eq!(
+        heif::read_icc_profile(
+            &heif::clean_metadata(&build_avif_with_exif(colr_prof(&p3), image, exif)).unwrap()
+        )
+        .unwrap(),
+        Some(p3)
+    );
+}
@@ -0,0 +1,135 @@
+use web_image_meta::jp2::{self, BoxKind};
+
+const SIGNATURE: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x0C, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+];
+const UUID_XMP: [u8; 16] = [
+    0xBE, 0x7A, 0xCF, 0xCB, 0x97, 0xA9, 0x42, 0xE8, 0x9C, 0x71, 0x99, 0x94, 0x91, 0xE3, 0xAF, 0xAC,
+];
+const UUID_GEOJP2: [u8; 16] = [
+    0xB1, 0x4B, 0xF8, 0xBD, 0x08, 0x3D, 0x4B, 0x43, 0xA5, 0xAE, 0x8C, 0xD7, 0xD5, 0xA6, 0xCE, 0x03,
+];
+
+/// ボックスを組み立てる
+fn make_box(box_type: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut output = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+    output.extend_from_slice(box_type);
+    output.extend_from_slice(content);
+    output
+}
+
+/// トップレベルのボックスのタイプを列挙
+fn list_boxes(data: &[u8]) -> Vec<[u8; 4]> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let size = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        boxes.push(data[pos + 4..pos + 8].try_into().unwrap());
+        pos += size;
+    }
+    boxes
+}
+
+fn jp2h(with_resolution: bool) -> Vec<u8> {
+    let mut content = make_box(b"ihdr", &[0, 0, 0, 8, 0, 0, 0, 8, 0, 3, 7, 7, 0, 0]);
+    content.extend(make_box(b"colr", &[1, 0, 0, 0, 0, 0, 16]));
+    if with_resolution {
+        content.extend(make_box(
+            b"res ",
+            &make_box(b"resc", &[0, 72, 0, 1, 0, 72, 0, 1, 0, 0]),
+        ));
+    }
+    make_box(b"jp2h", &content)
+}
+
+/// メタデータを多く含むJP2（コードストリームはダミー）
+fn build_jp2() -> Vec<u8> {
+    [
+        SIGNATURE.to_vec(),
+        make_box(b"ftyp", b"jp2 \0\0\0\0jp2 "),
+        jp2h(true),
+        make_box(b"xml ", b"<dc:title>Archive</dc:title>"),
+        make_box(b"uuid", &[UUID_XMP.as_slice(), b"<x:xmpmeta/>"].concat()),
+        make_box(
+            b"uuid",
+            &[b"JpgTiffExif->JP2".as_slice(), b"II*\0"].concat(),
+        ),
+        make_box(b"uuid", &[UUID_GEOJP2.as_slice(), b"II*\0geo"].concat()),
+        make_box(b"jp2c", &[0xFF, 0x4F, 0xFF, 0x51, 0xFF, 0xD9]),
+    ]
+    .concat()
+}
+
+#[test]
+fn test_read_metadata_boxes() {
+    let data = build_jp2();
+    let boxes = jp2::read_metadata_boxes(&data).unwrap();
+
+    let kinds: Vec<BoxKind> = boxes.iter().map(|b| b.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            BoxKind::Resolution,
+            BoxKind::Xml,
+            BoxKind::Xmp,
+            BoxKind::Exif,
+            BoxKind::GeoJp2
+        ]
+    );
+
+    // 位置はボックスの先頭を指し、UUIDは内容に含まない
+    for b in &boxes {
+        let box_type = &data[b.offset + 4..b.offset + 8];
+        assert!(matches!(box_type, b"res " | b"xml " | b"uuid"));
+    }
+    assert_eq!(boxes[1].data, b"<dc:title>Archive</dc:title>");
+    assert_eq!(boxes[2].data, b"<x:xmpmeta/>");
+}
+
+#[test]
+fn test_clean_metadata() {
+    let data = build_jp2();
+
+    let cleaned = jp2::clean_metadata(&data).unwrap();
+    assert_eq!(
+        list_boxes(&cleaned),
+        [*b"jP  ", *b"ftyp", *b"jp2h", *b"jp2c"]
+    );
+    let boxes = jp2::read_metadata_boxes(&cleaned).unwrap();
+    assert_eq!(boxes.len(), 1);
+    assert_eq!(boxes[0].kind, BoxKind::Resolution);
+
+    // 解像度ボックスも削除し、GeoJP2は保持
+    let options = jp2::CleanOptions {
+        preserve_geojp2: true,
+        preserve_resolution: false,
+        ..Default::default()
+    };
+    let cleaned = jp2::clean_metadata_with_options(&data, &options).unwrap();
+    let kinds: Vec<BoxKind> = jp2::read_metadata_boxes(&cleaned)
+        .unwrap()
+        .iter()
+        .map(|b| b.kind)
+        .collect();
+    assert_eq!(kinds, [BoxKind::GeoJp2]);
+    assert!(cleaned.windows(jp2h(false).len()).any(|w| w == jp2h(false)));
+}
+
+#[test]
+fn test_invalid_jp2() {
+    assert!(jp2::read_metadata_boxes(b"not a jp2 file").is_err());
+
+    // コードストリームがない
+    let data = [
+        SIGNATURE.to_vec(),
+        make_box(b"ftyp", b"jp2 \0\0\0\0jp2 "),
+        jp2h(false),
+    ]
+    .concat();
+    assert!(jp2::clean_metadata(&data).is_err());
+
+    // ボックスのサイズがデータを超える
+    let mut data = build_jp2();
+    data.truncate(data.len() - 2);
+    assert!(jp2::clean_metadata(&data).is_err());
+}
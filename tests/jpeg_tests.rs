@@ -1,7 +1,7 @@
 use std::fs;
 use std::path::Path;
 use web_image_meta::jpeg;
-use web_image_meta::Error;
+use web_image_meta::{Error, TextOptions, Utf8Policy};
 
 fn load_test_image(path: &str) -> Vec<u8> {
     let full_path = Path::new("tests/test_data").join(path);
@@ -430,6 +430,28 @@ fn test_cmyk_colorspace() {
     assert_eq!(&cleaned[0..2], &[0xFF, 0xD8]);
 }
 
+#[test]
+fn test_component_count() {
+    for (path, components) in [
+        ("jpeg/colorspace/colorspace_grayscale.jpg", 1),
+        ("jpeg/colorspace/colorspace_rgb.jpg", 3),
+        ("jpeg/colorspace/colorspace_cmyk.jpg", 4),
+        ("jpeg/encoding/encoding_progressive.jpg", 3),
+    ] {
+        let data = load_test_image(path);
+        assert_eq!(jpeg::component_count(&data).unwrap(), components, "{path}");
+        assert_eq!(
+            jpeg::is_grayscale(&data).unwrap(),
+            components == 1,
+            "{path}"
+        );
+    }
+
+    // SOFがない場合はエラー
+    assert!(jpeg::component_count(&[0xFF, 0xD8, 0xFF, 0xD9]).is_err());
+    assert!(jpeg::is_grayscale(b"not a jpeg").is_err());
+}
+
 #[test]
 fn test_app14_adobe_preservation() {
     // CMYK JPEGにはAPP14 Adobeセグメントが含まれている
@@ -664,6 +686,59 @@ fn test_dpi_metadata_handling() {
     }
 }
 
+#[test]
+fn test_clean_metadata_preserve_comments() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let data = jpeg::write_comment(&data, "Photo: Example Agency").unwrap();
+
+    // デフォルトではコメントを削除
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+    assert_eq!(jpeg::read_comment(&cleaned).unwrap(), None);
+
+    let options = jpeg::CleanOptions {
+        preserve_comments: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(
+        jpeg::read_comment(&cleaned).unwrap(),
+        Some("Photo: Example Agency".to_string())
+    );
+}
+
+#[test]
+fn test_clean_metadata_preserve_resolution() {
+    use web_image_meta::exif::{self, IfdKind};
+
+    let data = load_test_image("jpeg/dpi/dpi_exif_200dpi.jpg");
+    let options = jpeg::CleanOptions {
+        preserve_resolution: true,
+        preserve_color_tags: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert!(has_marker(&cleaned, 0xE1));
+
+    let tags = |data: &[u8]| -> Vec<(IfdKind, u16)> {
+        exif::entries_raw(data)
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.ifd, entry.tag))
+            .collect()
+    };
+    let original = tags(&data);
+    let kept = tags(&cleaned);
+    for tag in [0x011A, 0x011B, 0x0128] {
+        assert_eq!(
+            kept.contains(&(IfdKind::Ifd0, tag)),
+            original.contains(&(IfdKind::Ifd0, tag)),
+            "tag 0x{tag:04X}"
+        );
+    }
+    assert!(kept.contains(&(IfdKind::Ifd0, 0x011A)));
+    assert!(!kept.contains(&(IfdKind::Ifd0, 0x010F)));
+}
+
 #[test]
 fn test_metadata_types() {
     // Test various metadata types
@@ -1096,3 +1171,1659 @@ fn extract_orientation_from_exif(exif_data: &[u8]) -> Option<u16> {
 
     None
 }
+
+#[test]
+fn test_comment_raw_bytes_round_trip() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let (sjis, _, _) = encoding_rs::SHIFT_JIS.encode("日本語のコメント");
+
+    let written = jpeg::write_comment_raw(&data, &sjis, jpeg::CommentEncoding::ShiftJis)
+        .expect("Failed to write raw comment");
+
+    // バイト列がそのまま保存されていること
+    let raw = jpeg::read_comment_raw(&written).expect("Failed to read raw comment");
+    assert_eq!(raw.as_deref(), Some(sjis.as_ref()));
+
+    // エンコーディングを指定すれば正しくデコードできること
+    let decoded = jpeg::read_comment_with_encoding(&written, jpeg::CommentEncoding::ShiftJis)
+        .expect("Failed to decode comment");
+    assert_eq!(decoded, Some("日本語のコメント".to_string()));
+
+    // UTF-8として読むと文字化けすること
+    let lossy = jpeg::read_comment(&written).expect("Failed to read comment");
+    assert_ne!(lossy, Some("日本語のコメント".to_string()));
+
+    // UTF-8として厳密にデコードするとエラーになること
+    assert!(jpeg::read_comment_with_encoding(&written, jpeg::CommentEncoding::Utf8).is_err());
+}
+
+#[test]
+fn test_comment_latin1_encoding() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let encoding = jpeg::CommentEncoding::Latin1;
+
+    let bytes = encoding.encode("Café ©").expect("Failed to encode Latin-1");
+    assert_eq!(bytes, vec![b'C', b'a', b'f', 0xE9, b' ', 0xA9]);
+
+    let written = jpeg::write_comment_raw(&data, &bytes, encoding).expect("Failed to write");
+    let decoded = jpeg::read_comment_with_encoding(&written, encoding).expect("Failed to read");
+    assert_eq!(decoded, Some("Café ©".to_string()));
+
+    // Latin-1で表現できない文字はエラー
+    assert!(encoding.encode("日本語").is_err());
+}
+
+#[test]
+fn test_write_comment_raw_rejects_mismatched_encoding() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let invalid_utf8 = [0x82, 0xA0, 0xFF];
+
+    let result = jpeg::write_comment_raw(&data, &invalid_utf8, jpeg::CommentEncoding::Utf8);
+    assert!(result.is_err());
+}
+
+// ヘルパー関数：SOSまでのマーカーを出現順に列挙
+fn list_markers(data: &[u8]) -> Vec<u8> {
+    let mut markers = Vec::new();
+    let mut pos = 2;
+    while pos + 1 < data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        markers.push(marker);
+        pos += 2;
+
+        if marker == 0xDA {
+            break;
+        }
+        if (0xD0..=0xD9).contains(&marker) {
+            continue;
+        }
+        if pos + 2 > data.len() {
+            break;
+        }
+        let size = ((data[pos] as u16) << 8) | (data[pos + 1] as u16);
+        pos += size as usize;
+    }
+    markers
+}
+
+#[test]
+fn test_write_comment_placement() {
+    let data = load_test_image("jpeg/critical/critical_xmp_complex.jpg");
+
+    let write = |placement: jpeg::CommentPlacement| {
+        let options = jpeg::CommentOptions {
+            placement,
+            ..Default::default()
+        };
+        jpeg::write_comment_with_options(&data, "placement", &options)
+            .expect("Failed to write comment")
+    };
+
+    // SOIの直後
+    let markers = list_markers(&write(jpeg::CommentPlacement::AfterSoi));
+    assert_eq!(markers[0], 0xFE);
+
+    // 最後のAPPセグメントの直後
+    let markers = list_markers(&write(jpeg::CommentPlacement::AfterAppSegments));
+    let com_index = markers.iter().position(|&m| m == 0xFE).unwrap();
+    assert!((0xE0..=0xEF).contains(&markers[com_index - 1]));
+    assert!(markers[com_index + 1..]
+        .iter()
+        .all(|m| !(0xE0..=0xEF).contains(m)));
+
+    // SOSの直前
+    let markers = list_markers(&write(jpeg::CommentPlacement::BeforeSos));
+    let com_index = markers.iter().position(|&m| m == 0xFE).unwrap();
+    assert_eq!(markers[com_index + 1], 0xDA);
+
+    // 既定はDQTの直前
+    let markers = list_markers(&write(jpeg::CommentPlacement::BeforeTables));
+    let com_index = markers.iter().position(|&m| m == 0xFE).unwrap();
+    assert_eq!(markers[com_index + 1], 0xDB);
+
+    // いずれの位置でもコメントを読み取れる
+    let written = write(jpeg::CommentPlacement::AfterSoi);
+    assert_eq!(
+        jpeg::read_comment(&written).unwrap(),
+        Some("placement".to_string())
+    );
+}
+
+#[test]
+fn test_write_comment_placement_is_stable() {
+    let data = load_test_image("jpeg/critical/critical_xmp_complex.jpg");
+
+    for placement in [
+        jpeg::CommentPlacement::BeforeTables,
+        jpeg::CommentPlacement::AfterSoi,
+        jpeg::CommentPlacement::AfterAppSegments,
+        jpeg::CommentPlacement::BeforeSos,
+    ] {
+        let options = jpeg::CommentOptions {
+            placement,
+            ..Default::default()
+        };
+        let once = jpeg::write_comment_with_options(&data, "stable", &options).unwrap();
+        let twice = jpeg::write_comment_with_options(&once, "stable", &options).unwrap();
+        assert_eq!(once, twice, "{:?} should be stable", placement);
+        assert_eq!(count_markers(&twice, 0xFE), 1);
+    }
+}
+
+#[test]
+fn test_write_comment_split_long() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    // 分割位置が複数バイトの文字に重なる長さ
+    let comment = format!("{{\"a\":\"{}\"}}", "x".repeat(65525) + &"あ".repeat(100));
+
+    // 分割しない場合は分割可能であることを示すエラー
+    assert!(matches!(
+        jpeg::write_comment_with_options(&data, &comment, &jpeg::CommentOptions::default()),
+        Err(Error::PayloadTooLarge {
+            limit: 65533,
+            splittable: true,
+            ..
+        })
+    ));
+    assert!(matches!(
+        jpeg::write_comment_raw(&data, comment.as_bytes(), jpeg::CommentEncoding::Utf8),
+        Err(Error::PayloadTooLarge {
+            splittable: true,
+            ..
+        })
+    ));
+
+    // write_commentは自動的に分割する
+    let output = jpeg::write_comment(&data, &comment).unwrap();
+    assert_eq!(count_markers(&output, 0xFE), 2);
+
+    let options = jpeg::CommentOptions {
+        split_long: true,
+        ..Default::default()
+    };
+    let output = jpeg::write_comment_with_options(&data, &comment, &options).unwrap();
+    assert_eq!(count_markers(&output, 0xFE), 2);
+
+    // 各セグメントは単独でも正しいUTF-8
+    let separate = jpeg::read_comments(&output, &jpeg::ReadCommentOptions::default()).unwrap();
+    assert_eq!(separate.len(), 2);
+    assert_eq!(separate.concat(), comment);
+
+    let joined = jpeg::read_comments(
+        &output,
+        &jpeg::ReadCommentOptions {
+            join_segments: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(joined, vec![comment]);
+
+    // 短いコメントは1つのセグメント
+    let output = jpeg::write_comment_with_options(&data, "short", &options).unwrap();
+    assert_eq!(count_markers(&output, 0xFE), 1);
+    assert_eq!(
+        jpeg::read_comments(&output, &jpeg::ReadCommentOptions::default()).unwrap(),
+        vec!["short".to_string()]
+    );
+}
+
+#[test]
+fn test_write_comment_sanitize_text() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    // 結合文字の「か」+「゙」はNFCで「が」になる
+    let comment = "line1\r\nline2\0\rか\u{3099}";
+
+    let options = jpeg::CommentOptions {
+        text: TextOptions {
+            strip_control: true,
+            normalize_newlines: true,
+            nfc: true,
+            reject: false,
+        },
+        ..Default::default()
+    };
+    let output = jpeg::write_comment_with_options(&data, comment, &options).unwrap();
+    assert_eq!(
+        jpeg::read_comment(&output).unwrap(),
+        Some("line1\nline2\nが".to_string())
+    );
+
+    // 修正せずに拒否する
+    let reject = jpeg::CommentOptions {
+        text: TextOptions {
+            strip_control: true,
+            reject: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(matches!(
+        jpeg::write_comment_with_options(&data, comment, &reject),
+        Err(Error::InvalidFormat(_))
+    ));
+    assert!(jpeg::write_comment_with_options(&data, "clean\r\ntext", &reject).is_ok());
+}
+
+// ヘルパー関数：SOIの直後にセグメントを挿入
+fn insert_segment_after_soi(data: &[u8], marker: u8, payload: &[u8]) -> Vec<u8> {
+    let mut output = vec![0xFF, 0xD8, 0xFF, marker];
+    output.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    output.extend_from_slice(payload);
+    output.extend_from_slice(&data[2..]);
+    output
+}
+
+// ヘルパー関数：C2PAのJUMBFスーパーボックスを作成
+fn build_c2pa_jumbf(content: &[u8]) -> Vec<u8> {
+    let mut jumd = Vec::new();
+    jumd.extend_from_slice(b"jumd");
+    jumd.extend_from_slice(&[
+        0x63, 0x32, 0x70, 0x61, 0x00, 0x11, 0x00, 0x10, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B,
+        0x71,
+    ]);
+    jumd.push(0x03);
+    jumd.extend_from_slice(b"c2pa\0");
+
+    let mut body = Vec::new();
+    body.extend_from_slice(&((jumd.len() + 4) as u32).to_be_bytes());
+    body.extend_from_slice(&jumd);
+    body.extend_from_slice(&((content.len() + 8) as u32).to_be_bytes());
+    body.extend_from_slice(b"json");
+    body.extend_from_slice(content);
+
+    let mut jumbf = Vec::new();
+    jumbf.extend_from_slice(&((body.len() + 8) as u32).to_be_bytes());
+    jumbf.extend_from_slice(b"jumb");
+    jumbf.extend_from_slice(&body);
+    jumbf
+}
+
+// ヘルパー関数：JUMBFを2つのAPP11パケットに分割してJPEGに埋め込む
+fn embed_c2pa(data: &[u8], jumbf: &[u8]) -> Vec<u8> {
+    let split = jumbf.len() / 2;
+
+    let mut second = b"JP".to_vec();
+    second.extend_from_slice(&1u16.to_be_bytes());
+    second.extend_from_slice(&2u32.to_be_bytes());
+    second.extend_from_slice(&jumbf[0..8]); // 繰り返されるLBox/TBox
+    second.extend_from_slice(&jumbf[split..]);
+
+    let mut first = b"JP".to_vec();
+    first.extend_from_slice(&1u16.to_be_bytes());
+    first.extend_from_slice(&1u32.to_be_bytes());
+    first.extend_from_slice(&jumbf[..split]);
+
+    let with_second = insert_segment_after_soi(data, 0xEB, &second);
+    insert_segment_after_soi(&with_second, 0xEB, &first)
+}
+
+#[test]
+fn test_read_c2pa_raw() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    assert_eq!(jpeg::read_c2pa_raw(&data).unwrap(), None);
+
+    let jumbf = build_c2pa_jumbf(br#"{"claim":"test manifest"}"#);
+    let with_c2pa = embed_c2pa(&data, &jumbf);
+
+    let extracted = jpeg::read_c2pa_raw(&with_c2pa).expect("Failed to read C2PA");
+    assert_eq!(extracted, Some(jumbf));
+}
+
+#[test]
+fn test_clean_metadata_c2pa_handling() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let jumbf = build_c2pa_jumbf(br#"{"claim":"test manifest"}"#);
+    let with_c2pa = embed_c2pa(&data, &jumbf);
+
+    // 既定ではC2PAは削除され、マニフェストの無効化が報告される
+    let (cleaned, report) =
+        jpeg::clean_metadata_with_report(&with_c2pa, &jpeg::CleanOptions::default())
+            .expect("Failed to clean metadata");
+    assert!(!has_marker(&cleaned, 0xEB));
+    assert!(report.c2pa_present);
+    assert!(report.c2pa_invalidated);
+
+    // preserve_c2paを指定すると保持される
+    let options = jpeg::CleanOptions {
+        preserve_c2pa: true,
+        ..Default::default()
+    };
+    let (cleaned, report) =
+        jpeg::clean_metadata_with_report(&with_c2pa, &options).expect("Failed to clean metadata");
+    assert_eq!(count_markers(&cleaned, 0xEB), 2);
+    assert_eq!(jpeg::read_c2pa_raw(&cleaned).unwrap(), Some(jumbf));
+    // 他のメタデータが削除されたためハッシュは一致しなくなる
+    assert!(report.c2pa_invalidated);
+
+    // C2PAがない場合
+    let (_, report) = jpeg::clean_metadata_with_report(&data, &options).unwrap();
+    assert_eq!(report, jpeg::CleanReport::default());
+}
+
+/// kamadak-exifで主画像のタグを読み取る
+fn read_exif_field(data: &[u8], tag: exif::Tag) -> Option<String> {
+    let reader = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(data))
+        .ok()?;
+    reader
+        .get_field(tag, exif::In::PRIMARY)
+        .map(|field| field.display_value().to_string())
+}
+
+#[test]
+fn test_clean_metadata_preserve_color_tags() {
+    let data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let color_space = read_exif_field(&data, exif::Tag::ColorSpace);
+    assert!(color_space.is_some());
+
+    // デフォルトでは色に関するタグは削除される
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::ColorSpace), None);
+
+    let options = jpeg::CleanOptions {
+        preserve_color_tags: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(
+        read_exif_field(&cleaned, exif::Tag::ColorSpace),
+        color_space
+    );
+    assert_eq!(
+        read_exif_field(&cleaned, exif::Tag::Orientation),
+        read_exif_field(&data, exif::Tag::Orientation)
+    );
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::Make), None);
+
+    // オリエンテーションを保持しない場合も色に関するタグのみのEXIFが作成される
+    let options = jpeg::CleanOptions {
+        preserve_orientation: false,
+        preserve_color_tags: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(
+        read_exif_field(&cleaned, exif::Tag::ColorSpace),
+        color_space
+    );
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::Orientation), None);
+}
+
+#[test]
+fn test_clean_metadata_preserve_exif_tags() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let date = read_exif_field(&data, exif::Tag::DateTimeOriginal);
+    let make = read_exif_field(&data, exif::Tag::Make);
+    assert!(date.is_some() && make.is_some());
+
+    let options = jpeg::CleanOptions {
+        preserve_exif_tags: vec![0x9003, 0x010F],
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::DateTimeOriginal), date);
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::Make), make);
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::Model), None);
+
+    // 指定したタグのみなので元のEXIFより小さい
+    let exif_size = |data: &[u8]| {
+        exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(data))
+            .unwrap()
+            .buf()
+            .len()
+    };
+    assert!(exif_size(&cleaned) < exif_size(&data));
+
+    // GPSタグも指定できる
+    let data = load_test_image("jpeg/metadata/metadata_gps.jpg");
+    let latitude = read_exif_field(&data, exif::Tag::GPSLatitude);
+    assert!(latitude.is_some());
+    let options = jpeg::CleanOptions {
+        preserve_exif_tags: vec![0x0001, 0x0002],
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::GPSLatitude), latitude);
+    assert_eq!(
+        read_exif_field(&cleaned, exif::Tag::GPSLatitudeRef),
+        read_exif_field(&data, exif::Tag::GPSLatitudeRef)
+    );
+    assert_eq!(read_exif_field(&cleaned, exif::Tag::GPSLongitude), None);
+}
+
+#[test]
+fn test_clean_metadata_exif_byte_order() {
+    use web_image_meta::exif::ByteOrder;
+
+    let tiff_order = |data: &[u8]| {
+        let pos = data
+            .windows(6)
+            .position(|window| window == b"Exif\0\0")
+            .expect("EXIF not found")
+            + 6;
+        data[pos..pos + 2].to_vec()
+    };
+    let fields = |data: &[u8]| -> Vec<String> {
+        let reader = exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(data))
+            .unwrap();
+        reader
+            .fields()
+            .map(|f| format!("{:?} {} {}", f.ifd_num, f.tag, f.display_value()))
+            .collect()
+    };
+
+    let data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let (target, signature) = if tiff_order(&data) == b"II" {
+        (ByteOrder::BigEndian, b"MM")
+    } else {
+        (ByteOrder::LittleEndian, b"II")
+    };
+
+    // 保持するEXIFをサムネイルごと変換
+    let options = jpeg::CleanOptions {
+        preserve_exif: true,
+        exif_byte_order: Some(target),
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(tiff_order(&cleaned), signature);
+    assert_eq!(fields(&cleaned), fields(&data));
+
+    let thumbnail = |data: &[u8]| {
+        let reader = exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(data))
+            .unwrap();
+        let offset = reader
+            .get_field(exif::Tag::JPEGInterchangeFormat, exif::In::THUMBNAIL)
+            .and_then(|f| f.value.get_uint(0))
+            .unwrap() as usize;
+        let length = reader
+            .get_field(exif::Tag::JPEGInterchangeFormatLength, exif::In::THUMBNAIL)
+            .and_then(|f| f.value.get_uint(0))
+            .unwrap() as usize;
+        reader.buf()[offset..offset + length].to_vec()
+    };
+    assert_eq!(thumbnail(&cleaned), thumbnail(&data));
+
+    // 最小限のEXIFも指定したバイトオーダーで作成
+    let data = load_test_image("jpeg/orientation/orientation_6.jpg");
+    let options = jpeg::CleanOptions {
+        exif_byte_order: Some(ByteOrder::BigEndian),
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(tiff_order(&cleaned), b"MM");
+    assert_eq!(
+        read_exif_field(&cleaned, exif::Tag::Orientation),
+        read_exif_field(&data, exif::Tag::Orientation)
+    );
+}
+
+#[test]
+fn test_check_integrity_and_repair_truncated() {
+    let data = load_test_image("jpeg/orientation/orientation_6.jpg");
+
+    let report = jpeg::check_integrity(&data).unwrap();
+    assert!(report.is_complete());
+    assert_eq!(report.trailing_bytes, 0);
+    assert_eq!(jpeg::repair_truncated(&data).unwrap(), data);
+
+    // 画像データの途中で途切れたファイル
+    let truncated = &data[..data.len() * 2 / 3];
+    let report = jpeg::check_integrity(truncated).unwrap();
+    assert!(report.has_sos);
+    assert!(!report.has_eoi);
+    assert!(report.truncated);
+
+    let repaired = jpeg::repair_truncated(truncated).unwrap();
+    assert!(repaired.ends_with(&[0xFF, 0xD9]));
+    assert!(jpeg::check_integrity(&repaired).unwrap().is_complete());
+    let mut decoder = jpeg_decoder::Decoder::new(std::io::Cursor::new(&repaired));
+    assert!(decoder.decode().is_ok());
+
+    // SOSより前で途切れたファイルは修復できない
+    let header_only = &data[..20];
+    assert!(!jpeg::check_integrity(header_only).unwrap().has_sos);
+    assert!(jpeg::repair_truncated(header_only).is_err());
+}
+
+#[test]
+fn test_estimate_icc_profile() {
+    // 1セグメントに収まるプロファイルは実際のAPP2セグメントの合計と一致
+    let data = load_test_image("jpeg/icc/icc_srgb.jpg");
+    let mut profile_len = 0;
+    let mut segments_len = 0;
+    let mut pos = 2;
+    while data[pos + 1] != 0xDA {
+        let size = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if data[pos + 1] == 0xE2 && data[pos + 4..].starts_with(b"ICC_PROFILE\0") {
+            profile_len += size - 16;
+            segments_len += size + 2;
+        }
+        pos += 2 + size;
+    }
+    assert_eq!(jpeg::estimate_icc_profile(profile_len), Some(segments_len));
+
+    // 複数セグメントへの分割
+    assert_eq!(jpeg::estimate_icc_profile(0), Some(0));
+    assert_eq!(jpeg::estimate_icc_profile(65519), Some(65519 + 18));
+    assert_eq!(jpeg::estimate_icc_profile(65520), Some(65520 + 36));
+    assert_eq!(jpeg::estimate_icc_profile(65519 * 3), Some(65519 * 3 + 54));
+
+    // 255セグメントを超えるプロファイルは埋め込めない
+    assert_eq!(
+        jpeg::estimate_icc_profile(65519 * 255),
+        Some(65519 * 255 + 18 * 255)
+    );
+    assert_eq!(jpeg::estimate_icc_profile(65519 * 255 + 1), None);
+}
+
+#[test]
+fn test_predict_clean_size() {
+    let option_sets = [
+        jpeg::CleanOptions::default(),
+        jpeg::CleanOptions {
+            preserve_exif: true,
+            preserve_xmp: true,
+            remove_thumbnail: true,
+            ..Default::default()
+        },
+        jpeg::CleanOptions {
+            preserve_orientation: false,
+            replace_srgb_icc: true,
+            remove_duplicates: true,
+            ..Default::default()
+        },
+    ];
+
+    for path in [
+        "jpeg/orientation/orientation_6.jpg",
+        "jpeg/thumbnail/thumbnail_embedded.jpg",
+        "jpeg/icc/icc_srgb.jpg",
+        "jpeg/critical/critical_xmp_complex.jpg",
+        "jpeg/metadata/metadata_full_exif.jpg",
+        "jpeg/metadata/metadata_none.jpg",
+    ] {
+        let data = load_test_image(path);
+        for options in &option_sets {
+            let cleaned = jpeg::clean_metadata_with_options(&data, options).unwrap();
+            assert_eq!(
+                jpeg::predict_clean_size(&data, options).unwrap(),
+                cleaned.len(),
+                "{path}: {options:?}"
+            );
+        }
+    }
+
+    assert!(jpeg::predict_clean_size(b"not a jpeg", &Default::default()).is_err());
+}
+
+#[test]
+fn test_strict_conformance() {
+    let data = load_test_image("jpeg/critical/critical_xmp_complex.jpg");
+    assert!(jpeg::check_conformance(&data).is_ok());
+
+    let strict = jpeg::CleanOptions {
+        strict: true,
+        ..Default::default()
+    };
+    assert!(jpeg::clean_metadata_with_options(&data, &strict).is_ok());
+
+    // 識別子が未知のAPPセグメント
+    let private = insert_segment_after_soi(&data, 0xE7, b"PrivateVendor\0data");
+    let err = jpeg::clean_metadata_with_options(&private, &strict).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("APP7 segment with unknown signature at offset 2"));
+    // 通常は削除される
+    assert!(jpeg::clean_metadata(&private).is_ok());
+
+    // EOI以降のデータ
+    let mut trailing = data.clone();
+    trailing.extend_from_slice(b"appended");
+    let err = jpeg::check_conformance(&trailing).unwrap_err();
+    assert!(err.to_string().contains("8 bytes after EOI"));
+}
+
+// ヘルパー関数：ICCセグメントを（シーケンス番号, 総数, データ長）の列として取得
+fn list_icc_segments(data: &[u8]) -> Vec<(u8, u8, usize)> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos + 1] != 0xDA {
+        let size = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let payload = &data[pos + 4..pos + 2 + size];
+        if data[pos + 1] == 0xE2 && payload.starts_with(b"ICC_PROFILE\0") {
+            segments.push((payload[12], payload[13], payload.len() - 14));
+        }
+        pos += 2 + size;
+    }
+    segments
+}
+
+#[test]
+fn test_normalize_icc_segments() {
+    let data = load_test_image("jpeg/icc/icc_applep3.jpg");
+    let original = list_icc_segments(&data);
+    assert_eq!(original.len(), 1);
+    let icc_pos = find_marker_position(&data, 0xE2).unwrap();
+    let size = u16::from_be_bytes([data[icc_pos + 2], data[icc_pos + 3]]) as usize;
+    let profile = data[icc_pos + 18..icc_pos + 2 + size].to_vec();
+
+    // 同じプロファイルが二重に出力されている
+    let doubled = insert_segment_after_soi(&data, 0xE2, &data[icc_pos + 4..icc_pos + 2 + size]);
+    assert_eq!(list_icc_segments(&doubled).len(), 2);
+    let normalized = jpeg::normalize_icc_segments(&doubled).unwrap();
+    assert_eq!(list_icc_segments(&normalized), original);
+    assert_eq!(normalized.len(), data.len());
+
+    // プロファイルを2つに分割し、シーケンス番号を誤って0にする
+    let (first, second) = profile.split_at(profile.len() / 2);
+    let mut broken = data[..icc_pos].to_vec();
+    for chunk in [first, second] {
+        broken.extend_from_slice(&[0xFF, 0xE2]);
+        broken.extend_from_slice(&((chunk.len() + 16) as u16).to_be_bytes());
+        broken.extend_from_slice(b"ICC_PROFILE\0\0\0");
+        broken.extend_from_slice(chunk);
+    }
+    broken.extend_from_slice(&data[icc_pos + 2 + size..]);
+
+    let options = jpeg::CleanOptions {
+        normalize_icc: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&broken, &options).unwrap();
+    assert_eq!(list_icc_segments(&cleaned), original);
+    let cleaned = jpeg::clean_metadata_with_options(&doubled, &options).unwrap();
+    assert_eq!(list_icc_segments(&cleaned), original);
+    assert_eq!(cleaned.len(), jpeg::clean_metadata(&data).unwrap().len());
+    // デフォルトではセグメントをそのまま残す
+    assert_eq!(
+        list_icc_segments(&jpeg::clean_metadata(&broken).unwrap()).len(),
+        2
+    );
+
+    // 細かく断片化されたプロファイルを1つのセグメントにまとめる
+    let fragments: Vec<&[u8]> = profile.chunks(profile.len().div_ceil(20)).collect();
+    let mut fragmented = data[..icc_pos].to_vec();
+    for (index, chunk) in fragments.iter().enumerate() {
+        fragmented.extend_from_slice(&[0xFF, 0xE2]);
+        fragmented.extend_from_slice(&((chunk.len() + 16) as u16).to_be_bytes());
+        fragmented.extend_from_slice(b"ICC_PROFILE\0");
+        fragmented.extend_from_slice(&[index as u8 + 1, fragments.len() as u8]);
+        fragmented.extend_from_slice(chunk);
+    }
+    fragmented.extend_from_slice(&data[icc_pos + 2 + size..]);
+    assert_eq!(list_icc_segments(&fragmented).len(), fragments.len());
+    let merged = jpeg::normalize_icc_segments(&fragmented).unwrap();
+    assert_eq!(list_icc_segments(&merged), original);
+    assert_eq!(merged, data);
+
+    // ICCプロファイルのない画像は変更しない
+    let none = load_test_image("jpeg/icc/icc_none.jpg");
+    assert_eq!(jpeg::normalize_icc_segments(&none).unwrap(), none);
+}
+
+#[test]
+fn test_read_scan_info() {
+    let baseline = load_test_image("jpeg/encoding/encoding_baseline.jpg");
+    let info = jpeg::read_scan_info(&baseline).unwrap();
+    assert_eq!(info.restart_interval, None);
+    assert_eq!(info.scan_count, 1);
+    assert!(!info.has_restart_markers());
+    assert!(!info.progressive);
+
+    let progressive = load_test_image("jpeg/encoding/encoding_progressive.jpg");
+    let info = jpeg::read_scan_info(&progressive).unwrap();
+    assert_eq!(info.scan_count, 10);
+    assert!(info.progressive);
+
+    // DRIセグメントを追加し、EOIの直前にリスタートマーカーを置く
+    let mut with_restart = insert_segment_after_soi(&baseline, 0xDD, &[0x00, 0x04]);
+    let eoi = with_restart.len() - 2;
+    with_restart.splice(eoi..eoi, [0xFF, 0xD0, 0xFF, 0xD1]);
+    let info = jpeg::read_scan_info(&with_restart).unwrap();
+    assert_eq!(info.restart_interval, Some(4));
+    assert_eq!(info.restart_markers, 2);
+    assert!(info.has_restart_markers());
+
+    assert!(jpeg::read_scan_info(b"not a jpeg").is_err());
+}
+
+#[test]
+fn test_validate_structure() {
+    for dir in ["critical", "encoding", "icc", "metadata", "thumbnail"] {
+        for entry in fs::read_dir(Path::new("tests/test_data/jpeg").join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            let report = jpeg::validate_structure(&fs::read(&path).unwrap()).unwrap();
+            assert!(report.is_valid(), "{}: {:?}", path.display(), report.issues);
+            assert!(report.complete);
+        }
+    }
+
+    let data = load_test_image("jpeg/encoding/encoding_progressive.jpg");
+    let report = jpeg::validate_structure(&data).unwrap();
+    assert_eq!(report.trailing_bytes, 0);
+    assert!(report.units > 10);
+
+    // SOFの成分数を壊す
+    let sof = find_marker_position(&data, 0xC2).unwrap();
+    let mut corrupted = data.clone();
+    corrupted[sof + 9] = 4;
+    let report = jpeg::validate_structure(&corrupted).unwrap();
+    assert_eq!(report.issues.len(), 1);
+    assert_eq!(report.issues[0].offset, sof);
+    assert!(report.issues[0].message.contains("invalid SOF length"));
+
+    // EOIのないデータと、末尾データ
+    let report = jpeg::validate_structure(&data[..data.len() - 2]).unwrap();
+    assert!(!report.complete);
+    assert!(report.issues[0].message.contains("EOI marker not found"));
+    let mut trailing = data.clone();
+    trailing.extend_from_slice(b"trailer");
+    let report = jpeg::validate_structure(&trailing).unwrap();
+    assert!(report.is_valid());
+    assert_eq!(report.trailing_bytes, 7);
+
+    // SOSより前で途切れたデータ
+    let report = jpeg::validate_structure(&data[..sof + 6]).unwrap();
+    assert!(report.issues[0].message.contains("truncated"));
+}
+
+#[test]
+fn test_verify_passthrough() {
+    let options = jpeg::CleanOptions {
+        verify_passthrough: true,
+        ..Default::default()
+    };
+    for dir in ["critical", "encoding", "icc", "metadata", "orientation"] {
+        for entry in fs::read_dir(Path::new("tests/test_data/jpeg").join(dir)).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+            assert!(jpeg::verify_passthrough(&data, &cleaned).is_ok());
+        }
+    }
+
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+
+    // 画像データの1バイトを変更
+    let mut tampered = cleaned.clone();
+    let index = tampered.len() - 100;
+    tampered[index] ^= 0x01;
+    let err = jpeg::verify_passthrough(&data, &tampered).unwrap_err();
+    assert!(err.to_string().contains("Image data differs"));
+
+    // 量子化テーブルを変更
+    let mut tampered = cleaned.clone();
+    let dqt = find_marker_position(&tampered, 0xDB).unwrap();
+    tampered[dqt + 10] ^= 0x01;
+    let err = jpeg::verify_passthrough(&data, &tampered).unwrap_err();
+    assert!(err.to_string().contains("Segment 0xDB"));
+}
+
+#[test]
+fn test_verify_pixels() {
+    let options = jpeg::CleanOptions {
+        verify_pixels: true,
+        ..Default::default()
+    };
+    for dir in ["colorspace", "icc", "metadata"] {
+        for entry in fs::read_dir(Path::new("tests/test_data/jpeg").join(dir)).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+            assert!(jpeg::verify_pixels(&data, &cleaned).is_ok());
+        }
+    }
+
+    // 量子化テーブルを変更すると画素が変わる
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let mut tampered = jpeg::clean_metadata(&data).unwrap();
+    let dqt = find_marker_position(&tampered, 0xDB).unwrap();
+    tampered[dqt + 10] += 1;
+    let err = jpeg::verify_pixels(&data, &tampered).unwrap_err();
+    assert!(matches!(err, Error::PixelMismatch(_)));
+
+    // 寸法が異なる画像
+    let other = load_test_image("jpeg/quality/quality_80.jpg");
+    assert!(matches!(
+        jpeg::verify_pixels(&data, &other),
+        Err(Error::PixelMismatch(_))
+    ));
+}
+
+#[test]
+fn test_read_gps() {
+    let data = load_test_image("jpeg/metadata/metadata_gps.jpg");
+    let (lat, lon) = jpeg::read_gps(&data).unwrap();
+    assert!((lat - 35.6762).abs() < 1e-6);
+    assert!((lon - 139.6503).abs() < 1e-6);
+
+    // 軽量化でGPSを削除したEXIF、EXIFのない画像
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+    assert_eq!(jpeg::read_gps(&cleaned), None);
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(jpeg::read_gps(&data), None);
+
+    assert_eq!(jpeg::read_gps(b"not a jpeg"), None);
+}
+
+#[test]
+fn test_set_gps() {
+    // EXIFのない画像には最小限のEXIFを追加
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let output = jpeg::set_gps(&data, -33.8568, 151.2153, Some(-12.5)).unwrap();
+    let gps = web_image_meta::exif::read_gps_coordinates(&output)
+        .unwrap()
+        .unwrap();
+    assert!((gps.lat + 33.8568).abs() < 1e-6);
+    assert!((gps.lon - 151.2153).abs() < 1e-6);
+    assert_eq!(gps.alt, Some(-12.5));
+
+    // 既存の位置情報を置き換え、高度を省略すると削除する
+    let output = jpeg::set_gps(&output, 35.6762, -0.1278, None).unwrap();
+    let gps = web_image_meta::exif::read_gps_coordinates(&output)
+        .unwrap()
+        .unwrap();
+    assert!((gps.lat - 35.6762).abs() < 1e-6);
+    assert!((gps.lon + 0.1278).abs() < 1e-6);
+    assert_eq!(gps.alt, None);
+
+    // EXIFのその他のタグは保持
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let output = jpeg::set_gps(&data, 10.0, 20.0, None).unwrap();
+    assert_eq!(jpeg::read_gps(&output), Some((10.0, 20.0)));
+    assert_eq!(
+        jpeg::read_orientation(&output).unwrap(),
+        jpeg::read_orientation(&data).unwrap()
+    );
+
+    assert!(jpeg::set_gps(&data, 91.0, 0.0, None).is_err());
+    assert!(jpeg::set_gps(&data, 0.0, f64::NAN, None).is_err());
+}
+
+#[test]
+fn test_set_gps_coordinates() {
+    use web_image_meta::exif::{self, GpsCoordinates};
+
+    let data = load_test_image("jpeg/metadata/metadata_gps.jpg");
+    let gps = GpsCoordinates::new(35.6762, 139.6503)
+        .with_alt(Some(120.25))
+        .with_img_direction(Some(271.5))
+        .with_speed(Some(36.125));
+    let output = jpeg::set_gps_coordinates(&data, &gps).unwrap();
+    let read = exif::read_gps_coordinates(&output).unwrap().unwrap();
+    assert!((read.lat - gps.lat).abs() < 1e-6);
+    assert!((read.lon - gps.lon).abs() < 1e-6);
+    assert_eq!(read.alt, Some(120.25));
+    assert_eq!(read.img_direction, Some(271.5));
+    assert_eq!(read.speed, Some(36.125));
+    assert_eq!(
+        exif::read_text(&output, exif::IfdKind::Gps, 0x000C).unwrap(),
+        Some("K".to_string())
+    );
+    assert_eq!(
+        exif::read_text(&output, exif::IfdKind::Gps, 0x0010).unwrap(),
+        Some("T".to_string())
+    );
+
+    // set_gpsは速度と撮影方向を残す
+    let output = jpeg::set_gps(&output, 10.0, 20.0, Some(5.0)).unwrap();
+    let read = exif::read_gps_coordinates(&output).unwrap().unwrap();
+    assert_eq!(read.alt, Some(5.0));
+    assert_eq!(read.img_direction, Some(271.5));
+    assert_eq!(read.speed, Some(36.125));
+
+    // 指定した項目のみ置き換え、高度は指定しなければ削除する
+    let output = jpeg::set_gps_coordinates(
+        &output,
+        &GpsCoordinates::new(1.0, 2.0).with_speed(Some(3.0)),
+    )
+    .unwrap();
+    let read = exif::read_gps_coordinates(&output).unwrap().unwrap();
+    assert_eq!(read.alt, None);
+    assert_eq!(read.img_direction, Some(271.5));
+    assert_eq!(read.speed, Some(3.0));
+
+    // EXIFのない画像
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let output = jpeg::set_gps_coordinates(&data, &gps).unwrap();
+    assert_eq!(
+        exif::read_gps_coordinates(&output).unwrap().unwrap().speed,
+        Some(36.125)
+    );
+
+    for invalid in [
+        gps.with_img_direction(Some(360.0)),
+        gps.with_img_direction(Some(-1.0)),
+        gps.with_speed(Some(-0.5)),
+        gps.with_speed(Some(f64::INFINITY)),
+    ] {
+        assert!(jpeg::set_gps_coordinates(&data, &invalid).is_err());
+    }
+}
+
+#[test]
+fn test_has_alpha() {
+    let data = load_test_image("jpeg/encoding/encoding_baseline.jpg");
+    assert!(!jpeg::has_alpha(&data).unwrap());
+    assert!(jpeg::has_alpha(b"not a jpeg").is_err());
+}
+
+#[test]
+fn test_check_color_segments() {
+    use jpeg::ColorConflict;
+
+    for file in ["colorspace_grayscale.jpg", "colorspace_rgb.jpg"] {
+        let data = load_test_image(&format!("jpeg/colorspace/{file}"));
+        assert!(jpeg::check_color_segments(&data).unwrap().is_empty());
+    }
+    let data = load_test_image("jpeg/icc/icc_srgb.jpg");
+    assert!(jpeg::check_color_segments(&data).unwrap().is_empty());
+
+    // CMYK画像にJFIFがある
+    let cmyk = load_test_image("jpeg/colorspace/colorspace_cmyk.jpg");
+    assert_eq!(
+        jpeg::check_color_segments(&cmyk).unwrap(),
+        [ColorConflict::JfifComponentMismatch { components: 4 }]
+    );
+
+    // RGBのプロファイルを持つグレースケール画像
+    let gray = load_test_image("jpeg/colorspace/colorspace_grayscale.jpg");
+    let mut profile = vec![0u8; 128];
+    profile[16..20].copy_from_slice(b"RGB ");
+    let icc = [b"ICC_PROFILE\0\x01\x01".as_slice(), &profile].concat();
+    assert_eq!(
+        jpeg::check_color_segments(&insert_segment_after_soi(&gray, 0xE2, &icc)).unwrap(),
+        [ColorConflict::IccColorSpaceMismatch {
+            components: 1,
+            color_space: *b"RGB "
+        }]
+    );
+
+    // Adobeセグメントの変換方式
+    let rgb = load_test_image("jpeg/colorspace/colorspace_rgb.jpg");
+    let adobe = |transform: u8| [b"Adobe\0\x64\0\0\0\0".as_slice(), &[transform]].concat();
+    let with_rgb = insert_segment_after_soi(&rgb, 0xEE, &adobe(0));
+    assert_eq!(
+        jpeg::check_color_segments(&with_rgb).unwrap(),
+        [ColorConflict::JfifWithAdobeRgb]
+    );
+    let with_ycck = insert_segment_after_soi(&rgb, 0xEE, &adobe(2));
+    assert_eq!(
+        jpeg::check_color_segments(&with_ycck).unwrap(),
+        [ColorConflict::AdobeTransformMismatch {
+            components: 3,
+            transform: 2
+        }]
+    );
+
+    assert!(jpeg::check_color_segments(b"not a jpeg").is_err());
+}
+
+#[test]
+fn test_lint() {
+    use web_image_meta::LintRule;
+
+    let rules = |data: &[u8]| {
+        jpeg::lint(data)
+            .iter()
+            .map(|finding| finding.rule)
+            .collect::<Vec<_>>()
+    };
+
+    let data = load_test_image("jpeg/colorspace/colorspace_rgb.jpg");
+    assert!(jpeg::lint(&data).is_empty());
+
+    // JFIFの重複と空のAPPセグメント
+    let jfif = insert_segment_after_soi(&data, 0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+    let empty = insert_segment_after_soi(&jfif, 0xE3, b"");
+    let findings = jpeg::lint(&empty);
+    assert_eq!(findings[0].rule, LintRule::EmptySegment);
+    assert_eq!(findings[0].offset, Some(2));
+    assert_eq!(findings[1].rule, LintRule::DuplicateSegment);
+
+    // EOIの前にEXIFを置き、EOIを取り除く
+    let mut after_scan = data[..data.len() - 2].to_vec();
+    after_scan.extend_from_slice(&[0xFF, 0xE1, 0x00, 0x08]);
+    after_scan.extend_from_slice(b"Exif\0\0");
+    let findings = jpeg::lint(&after_scan);
+    assert_eq!(
+        findings.iter().map(|f| f.rule).collect::<Vec<_>>(),
+        [LintRule::SegmentAfterScan, LintRule::MissingEoi]
+    );
+    assert!(findings[0].message.contains("EXIF"));
+
+    // サムネイルの上限サイズ
+    let thumbnail = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    assert!(!rules(&thumbnail).contains(&LintRule::OversizedThumbnail));
+    let options = jpeg::LintOptions {
+        max_thumbnail_size: 16,
+    };
+    let findings = jpeg::lint_with_options(&thumbnail, &options);
+    assert!(findings
+        .iter()
+        .any(|f| f.rule == LintRule::OversizedThumbnail));
+
+    assert_eq!(rules(b"not a jpeg"), [LintRule::MalformedStructure]);
+}
+
+#[test]
+fn test_clean_metadata_with_progress() {
+    use web_image_meta::ProgressStage;
+
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let options = jpeg::CleanOptions::default();
+    let mut events = Vec::new();
+    let cleaned = jpeg::clean_metadata_with_progress(&data, &options, |p| events.push(p)).unwrap();
+    assert_eq!(
+        cleaned,
+        jpeg::clean_metadata_with_options(&data, &options).unwrap()
+    );
+
+    // 段階は順に進み、書き込みの進捗は単調に増加して出力サイズで終わる
+    let stages: Vec<ProgressStage> = events.iter().map(|p| p.stage).collect();
+    assert_eq!(stages.first(), Some(&ProgressStage::ValidateInput));
+    assert_eq!(stages.last(), Some(&ProgressStage::ValidateOutput));
+    assert!(stages.windows(2).all(|w| w[0] as u8 <= w[1] as u8));
+    let writes: Vec<usize> = events
+        .iter()
+        .filter(|p| p.stage == ProgressStage::Write)
+        .map(|p| p.processed)
+        .collect();
+    assert!(writes.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!(writes.last(), Some(&cleaned.len()));
+    assert_eq!(events.last().unwrap().fraction(), 1.0);
+
+    // 画素の比較を含む出力の検証は、デコードの途中の位置を単調に通知する
+    let options = jpeg::CleanOptions {
+        verify_pixels: true,
+        ..Default::default()
+    };
+    let mut events = Vec::new();
+    jpeg::clean_metadata_with_progress(&data, &options, |p| events.push(p)).unwrap();
+    let validated: Vec<usize> = events
+        .iter()
+        .filter(|p| p.stage == ProgressStage::ValidateOutput)
+        .map(|p| p.processed)
+        .collect();
+    assert!(validated.windows(2).all(|w| w[0] <= w[1]));
+    assert!(validated.iter().any(|&p| p > 0 && p < cleaned.len()));
+    assert_eq!(validated.last(), Some(&cleaned.len()));
+}
+
+#[test]
+fn test_clean_metadata_cancellation() {
+    use web_image_meta::{CancellationToken, ProgressStage};
+
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let token = CancellationToken::new();
+    let options = jpeg::CleanOptions {
+        cancellation: Some(token.clone()),
+        ..Default::default()
+    };
+
+    // 中断されていなければ結果は変わらない
+    assert_eq!(
+        jpeg::clean_metadata_with_options(&data, &options).unwrap(),
+        jpeg::clean_metadata(&data).unwrap()
+    );
+
+    // 書き込みの途中で中断する
+    let result = jpeg::clean_metadata_with_progress(&data, &options, |p| {
+        if p.stage == ProgressStage::Write {
+            token.cancel();
+        }
+    });
+    assert!(matches!(result, Err(Error::Cancelled)));
+
+    // 中断済みのトークンでは処理を始めない
+    assert!(token.is_cancelled());
+    assert!(matches!(
+        jpeg::clean_metadata_with_options(&data, &options),
+        Err(Error::Cancelled)
+    ));
+
+    // 出力の検証（画素の比較のためのデコード）の途中で中断する
+    let token = CancellationToken::new();
+    let options = jpeg::CleanOptions {
+        verify_pixels: true,
+        cancellation: Some(token.clone()),
+        ..Default::default()
+    };
+    let result = jpeg::clean_metadata_with_progress(&data, &options, |p| {
+        if p.stage == ProgressStage::ValidateOutput {
+            token.cancel();
+        }
+    });
+    assert!(matches!(result, Err(Error::Cancelled)));
+}
+
+#[test]
+fn test_segments_iterator() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let mut iter = jpeg::segments(&data).unwrap();
+
+    // 最初のEXIFセグメントで打ち切れる
+    let exif = iter
+        .by_ref()
+        .map(Result::unwrap)
+        .find(|segment| segment.marker == 0xE1 && segment.payload().starts_with(b"Exif\0\0"))
+        .unwrap();
+    assert_eq!(&data[exif.offset..exif.offset + 2], &[0xFF, 0xE1]);
+    // セグメントは入力を借用している
+    assert_eq!(
+        exif.bytes.as_ptr(),
+        data[exif.offset..].as_ptr(),
+        "segment should borrow the input"
+    );
+    assert_eq!(iter.sos_position(), None);
+
+    // 最後まで読むとセグメントは隙間なく並び、SOSの位置が分かる
+    let mut iter = jpeg::segments(&data).unwrap();
+    let mut pos = 2;
+    for segment in iter.by_ref() {
+        let segment = segment.unwrap();
+        assert_eq!(segment.offset, pos);
+        pos += segment.bytes.len();
+    }
+    assert_eq!(iter.sos_position(), Some(pos));
+    assert_eq!(&data[pos..pos + 2], &[0xFF, 0xDA]);
+
+    // 途切れたデータではエラーを1度だけ返す
+    let results: Vec<_> = jpeg::segments(&data[..exif.offset + 10]).unwrap().collect();
+    assert!(results.last().unwrap().is_err());
+    assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+
+    assert!(jpeg::segments(b"not a jpeg").is_err());
+}
+
+#[test]
+fn test_classify_app_segment() {
+    use jpeg::AppKind;
+
+    let cases: [(u8, &[u8], AppKind); 12] = [
+        (0xE0, b"JFIF\0\x01\x02", AppKind::Jfif),
+        (0xE0, b"JFXX\0\x10", AppKind::Jfxx),
+        (0xE1, b"Exif\0\0II*\0", AppKind::Exif),
+        (0xE1, b"http://ns.adobe.com/xap/1.0/\0<x/>", AppKind::Xmp),
+        (
+            0xE1,
+            b"http://ns.adobe.com/xmp/extension/\0",
+            AppKind::ExtendedXmp,
+        ),
+        (0xE2, b"ICC_PROFILE\0\x01\x01", AppKind::Icc),
+        (0xE2, b"MPF\0MM\0*", AppKind::Mpf),
+        (0xEB, b"JP\0\x01", AppKind::Jumbf),
+        (0xEC, b"Ducky\0\x01", AppKind::Ducky),
+        (0xED, b"Photoshop 3.0\08BIM", AppKind::Photoshop),
+        (0xEE, b"Adobe\0\x64", AppKind::Adobe),
+        (0xE3, b"Meta\0\x01", AppKind::Unknown(b"Meta".to_vec())),
+    ];
+    for (marker, payload, kind) in cases {
+        assert_eq!(jpeg::classify_app_segment(marker, payload), kind);
+    }
+
+    // 識別子とマーカーの組み合わせが一致しない場合
+    assert_eq!(
+        jpeg::classify_app_segment(0xE2, b"Exif\0\0"),
+        AppKind::Unknown(b"Exif".to_vec())
+    );
+    // null終端がない識別子は最大32バイト
+    let long = [b'A'; 40];
+    assert_eq!(
+        jpeg::classify_app_segment(0xEF, &long),
+        AppKind::Unknown(vec![b'A'; 32])
+    );
+
+    // セグメントのイテレータから判定できる
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let kinds: Vec<_> = jpeg::segments(&data)
+        .unwrap()
+        .map(Result::unwrap)
+        .filter_map(|segment| segment.app_kind())
+        .collect();
+    assert!(kinds.contains(&AppKind::Exif));
+    let dqt = jpeg::segments(&data)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|segment| segment.marker == 0xDB)
+        .unwrap();
+    assert_eq!(dqt.app_kind(), None);
+}
+
+#[test]
+fn test_clean_metadata_deterministic() {
+    use web_image_meta::exif::{DateTime, DateTimes};
+
+    let data = jpeg::set_date_times(
+        &load_test_image("jpeg/metadata/metadata_full_exif.jpg"),
+        &DateTimes {
+            original: Some(DateTime::new("2024:01:01 12:00:00", None)),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    // 同じ入力とオプションからは同じ出力
+    for options in [
+        jpeg::CleanOptions::default(),
+        jpeg::CleanOptions {
+            preserve_exif_tags: vec![0x9003],
+            ..Default::default()
+        },
+        jpeg::CleanOptions {
+            preserve_exif: true,
+            ..Default::default()
+        },
+    ] {
+        let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+        assert_eq!(
+            cleaned,
+            jpeg::clean_metadata_with_options(&data, &options).unwrap()
+        );
+        assert_eq!(
+            jpeg::write_comment(&cleaned, "note").unwrap(),
+            jpeg::write_comment(&cleaned, "note").unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_clean_metadata_exif_placement() {
+    let data = load_test_image("jpeg/critical/critical_jfif_exif_dpi.jpg");
+    let app_markers = |options: &jpeg::CleanOptions| -> Vec<u8> {
+        let cleaned = jpeg::clean_metadata_with_options(&data, options).unwrap();
+        jpeg::segments(&cleaned)
+            .unwrap()
+            .map(|segment| segment.unwrap().marker)
+            .filter(|marker| matches!(marker, 0xE0 | 0xE1))
+            .collect()
+    };
+
+    let mut options = jpeg::CleanOptions {
+        preserve_exif: true,
+        ..Default::default()
+    };
+    assert_eq!(app_markers(&options), vec![0xE0, 0xE1]);
+
+    options.exif_placement = jpeg::ExifPlacement::First;
+    assert_eq!(app_markers(&options), vec![0xE1, 0xE0]);
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(&cleaned[2..4], &[0xFF, 0xE1]);
+    assert_eq!(
+        jpeg::predict_clean_size(&data, &options).unwrap(),
+        cleaned.len()
+    );
+}
+
+#[test]
+fn test_clean_metadata_preserve_byte_order() {
+    // 元のEXIFはビッグエンディアン（MM）
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let tiff_header = |data: &[u8]| {
+        let pos = data.windows(6).position(|w| w == b"Exif\0\0").unwrap() + 6;
+        data[pos..pos + 2].to_vec()
+    };
+    assert_eq!(tiff_header(&data), b"MM");
+
+    let mut options = jpeg::CleanOptions {
+        preserve_exif_tags: vec![0x010F],
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(tiff_header(&cleaned), b"II");
+
+    options.preserve_byte_order = true;
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(tiff_header(&cleaned), b"MM");
+    assert_eq!(
+        jpeg::read_orientation(&cleaned).unwrap(),
+        jpeg::read_orientation(&data).unwrap()
+    );
+
+    // 明示したバイトオーダーが優先される
+    options.exif_byte_order = Some(web_image_meta::exif::ByteOrder::LittleEndian);
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(tiff_header(&cleaned), b"II");
+}
+
+#[test]
+fn test_write_xmp_extended() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let description = "x".repeat(150_000);
+    let history = "h".repeat(30_000);
+    let packet = format!(
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:xmp="http://ns.adobe.com/xap/1.0/" dc:source="{description}" xmp:Rating="4"><dc:title><rdf:Alt><rdf:li xml:lang="x-default">Title</rdf:li></rdf:Alt></dc:title><dc:description>{history}</dc:description></rdf:Description></rdf:RDF></x:xmpmeta>"#
+    );
+
+    // 収まらない大きなプロパティだけを拡張XMPに移し、残りは標準XMPに残す
+    let output = jpeg::write_xmp(&data, &packet).unwrap();
+    let standard = jpeg::read_xmp(&output).unwrap().unwrap();
+    assert!(standard.contains("xmpNote:HasExtendedXMP"));
+    assert!(!standard.contains(&description));
+    assert!(standard.contains(&history));
+    assert!(standard.contains("xmp:Rating=\"4\""));
+    assert!(standard.contains("<rdf:li xml:lang=\"x-default\">Title</rdf:li>"));
+    let extended = jpeg::read_extended_xmp(&output).unwrap().unwrap();
+    assert!(extended.contains(&format!("dc:source=\"{description}\"")));
+    assert!(extended.contains("xmlns:dc=\"http://purl.org/dc/elements/1.1/\""));
+    assert!(!extended.contains("HasExtendedXMP"));
+    assert!(!extended.contains(&history));
+    assert!(standard.len() + 29 <= 0xFFFF - 2);
+
+    // 全体の長さ・オフセットのフィールド（識別子35バイトとGUIDの後）の位置
+    let fields: Vec<usize> = jpeg::segments(&output)
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|segment| segment.app_kind() == Some(jpeg::AppKind::ExtendedXmp))
+        .map(|segment| segment.offset + 4 + 35 + 32)
+        .collect();
+    assert!(fields.len() > 1);
+
+    // セグメントのデータで満たせない全体の長さは確保せずにエラー
+    let mut oversized = output.clone();
+    for &field in &fields {
+        oversized[field..field + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    }
+    assert!(jpeg::read_extended_xmp(&oversized).is_err());
+
+    // 長さの合計が一致しても、重複したオフセットで隙間が残る場合はエラー
+    let mut overlapping = output.clone();
+    let last = fields[fields.len() - 1];
+    overlapping[last + 4..last + 8].copy_from_slice(&0u32.to_be_bytes());
+    assert!(jpeg::read_extended_xmp(&overlapping).is_err());
+
+    // 要素形式のプロパティも移す
+    let packet = packet.replace(&history, &"h".repeat(70_000));
+    let output = jpeg::write_xmp(&data, &packet).unwrap();
+    let standard = jpeg::read_xmp(&output).unwrap().unwrap();
+    assert!(!standard.contains("<dc:description>"));
+    assert!(standard.contains("Title"));
+    let extended = jpeg::read_extended_xmp(&output).unwrap().unwrap();
+    assert!(extended.contains(&format!(
+        "<dc:description>{}</dc:description>",
+        "h".repeat(70_000)
+    )));
+    let extended_count = |data: &[u8]| {
+        jpeg::segments(data)
+            .unwrap()
+            .filter(|segment| {
+                segment.as_ref().unwrap().app_kind() == Some(jpeg::AppKind::ExtendedXmp)
+            })
+            .count()
+    };
+    assert_eq!(extended_count(&output), 4);
+
+    // 書き直すと古い拡張XMPは置き換えられる
+    let rewritten = jpeg::write_xmp(&output, &packet.replace('x', "y")).unwrap();
+    assert_eq!(extended_count(&rewritten), 4);
+    assert!(jpeg::read_extended_xmp(&rewritten)
+        .unwrap()
+        .unwrap()
+        .contains(&"y".repeat(150_000)));
+
+    // 拡張XMPがない場合
+    assert_eq!(jpeg::read_extended_xmp(&data).unwrap(), None);
+}
+
+#[test]
+fn test_write_icc_profile() {
+    let source = load_test_image("jpeg/icc/icc_applep3.jpg");
+    let profile = web_image_meta::extract_embedded(&source)
+        .unwrap()
+        .icc
+        .unwrap();
+    let data = load_test_image("jpeg/icc/icc_none.jpg");
+
+    let output = jpeg::write_icc_profile(&data, &profile).unwrap();
+    assert_eq!(list_icc_segments(&output), list_icc_segments(&source));
+    let embedded = web_image_meta::extract_embedded(&output).unwrap();
+    assert_eq!(embedded.icc.as_deref(), Some(profile.as_slice()));
+
+    // 大きなプロファイルは連番付きで分割する
+    let large: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+    let output = jpeg::write_icc_profile(&output, &large).unwrap();
+    let segments = list_icc_segments(&output);
+    assert_eq!(segments.len(), 4);
+    assert!(segments
+        .iter()
+        .enumerate()
+        .all(|(index, &(sequence, count, _))| sequence as usize == index + 1 && count == 4));
+    let embedded = web_image_meta::extract_embedded(&output).unwrap();
+    assert_eq!(embedded.icc, Some(large));
+
+    // 255セグメントを超える場合は分割できない
+    let huge = vec![0u8; 255 * 65519 + 1];
+    assert!(matches!(
+        jpeg::write_icc_profile(&data, &huge),
+        Err(Error::PayloadTooLarge {
+            splittable: false,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_optimize_huffman() {
+    let mut optimized_count = 0;
+    for dir in [
+        "colorspace",
+        "encoding",
+        "quality",
+        "subsampling",
+        "metadata",
+    ] {
+        for entry in fs::read_dir(Path::new("tests/test_data/jpeg").join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+            let optimized = jpeg::optimize_huffman(&data).unwrap();
+            assert!(optimized.len() <= data.len(), "{path:?}");
+            assert!(jpeg::verify_pixels(&data, &optimized).is_ok(), "{path:?}");
+            if optimized != data {
+                optimized_count += 1;
+            }
+        }
+    }
+    assert!(optimized_count > 0);
+
+    // プログレッシブJPEGは対象外
+    let data = load_test_image("jpeg/encoding/encoding_progressive.jpg");
+    assert_eq!(jpeg::optimize_huffman(&data).unwrap(), data);
+
+    // リスタートマーカーを含む画像
+    let pixels: Vec<u8> = (0..64 * 48 * 3).map(|i| (i * 7 % 251) as u8).collect();
+    let mut data = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut data, 90);
+    encoder.set_restart_interval(4);
+    encoder
+        .encode(&pixels, 64, 48, jpeg_encoder::ColorType::Rgb)
+        .unwrap();
+    let optimized = jpeg::optimize_huffman(&data).unwrap();
+    assert!(optimized.len() < data.len());
+    assert_eq!(
+        jpeg::read_scan_info(&optimized).unwrap().restart_markers,
+        jpeg::read_scan_info(&data).unwrap().restart_markers
+    );
+    assert!(jpeg::verify_pixels(&data, &optimized).is_ok());
+
+    // メタデータの軽量化と同時に行う
+    let data = load_test_image("jpeg/orientation/orientation_6.jpg");
+    let options = jpeg::CleanOptions {
+        optimize_huffman: true,
+        verify_passthrough: true,
+        verify_pixels: true,
+        ..Default::default()
+    };
+    let (cleaned, report) = jpeg::clean_metadata_with_report(&data, &options).unwrap();
+    assert!(report.huffman_optimized);
+    assert!(cleaned.len() < jpeg::clean_metadata(&data).unwrap().len());
+    assert_eq!(
+        jpeg::read_orientation(&cleaned).unwrap(),
+        jpeg::read_orientation(&data).unwrap()
+    );
+}
+
+#[test]
+fn test_read_comment_with_policy() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let latin1 = [b'C', b'a', b'f', 0xE9];
+    let written = jpeg::write_comment_raw(&data, &latin1, jpeg::CommentEncoding::Latin1)
+        .expect("Failed to write raw comment");
+
+    // 修復した場合は適用したポリシーが報告される
+    let decoded = jpeg::read_comment_with_policy(&written, Utf8Policy::Latin1Fallback)
+        .unwrap()
+        .unwrap();
+    assert_eq!(decoded.text, "Café");
+    assert_eq!(decoded.repaired, Some(Utf8Policy::Latin1Fallback));
+
+    let decoded = jpeg::read_comment_with_policy(&written, Utf8Policy::Lossy)
+        .unwrap()
+        .unwrap();
+    assert_eq!(decoded.text, "Caf\u{FFFD}");
+    assert_eq!(decoded.repaired, Some(Utf8Policy::Lossy));
+
+    assert!(matches!(
+        jpeg::read_comment_with_policy(&written, Utf8Policy::Reject),
+        Err(Error::ParseError(_))
+    ));
+
+    // 正しいUTF-8は修復されない
+    let written = jpeg::write_comment(&data, "Café").expect("Failed to write comment");
+    let decoded = jpeg::read_comment_with_policy(&written, Utf8Policy::Reject)
+        .unwrap()
+        .unwrap();
+    assert_eq!(decoded.text, "Café");
+    assert_eq!(decoded.repaired, None);
+
+    assert_eq!(
+        jpeg::read_comment_with_policy(&data, Utf8Policy::Reject).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_normalize() {
+    let data = load_test_image("jpeg/icc/icc_applep3.jpg");
+    let data = jpeg::write_xmp(&data, "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>").unwrap();
+    let data = jpeg::write_comment(&data, "normalize").unwrap();
+    let normalized = jpeg::normalize(&data).expect("Failed to normalize");
+
+    // APP・COMを逆順にしてテーブルの後ろに移し、空のAPP15を加えた画像
+    let mut iter = jpeg::segments(&data).unwrap();
+    let segments: Vec<_> = iter.by_ref().map(Result::unwrap).collect();
+    let sos = iter.sos_position().unwrap();
+    let is_header = |marker: u8| (0xE0..=0xEF).contains(&marker) || marker == 0xFE;
+    let mut shuffled = vec![0xFF, 0xD8];
+    for segment in segments.iter().filter(|s| !is_header(s.marker)) {
+        shuffled.extend_from_slice(segment.bytes);
+    }
+    shuffled.extend_from_slice(&[0xFF, 0xEF, 0x00, 0x02]);
+    for segment in segments.iter().rev().filter(|s| is_header(s.marker)) {
+        shuffled.extend_from_slice(segment.bytes);
+    }
+    shuffled.extend_from_slice(&data[sos..]);
+    assert_ne!(shuffled, data);
+
+    // 並びが異なっても同じバイト列になり、再度の正規化で変わらない
+    assert_eq!(jpeg::normalize(&shuffled).unwrap(), normalized);
+    assert_eq!(jpeg::normalize(&normalized).unwrap(), normalized);
+
+    // 正規の順序になり、DQTは1つにまとまる
+    let markers: Vec<_> = jpeg::segments(&normalized)
+        .unwrap()
+        .map(|segment| segment.unwrap())
+        .map(|segment| (segment.marker, segment.app_kind()))
+        .collect();
+    let position = |marker: u8| markers.iter().position(|(m, _)| *m == marker).unwrap();
+    assert_eq!(markers[0], (0xE0, Some(jpeg::AppKind::Jfif)));
+    assert!(position(0xE1) < position(0xE2));
+    assert!(position(0xE2) < position(0xFE));
+    assert!(position(0xFE) < position(0xDB));
+    assert_eq!(markers.iter().filter(|(m, _)| *m == 0xDB).count(), 1);
+    assert!(!markers.iter().any(|(m, _)| *m == 0xEF));
+
+    // エントロピー符号化データと画素は変わらない
+    assert!(normalized.ends_with(&data[sos..]));
+    jpeg::verify_pixels(&data, &normalized).expect("Pixels changed");
+    assert!(jpeg::normalize(b"not a jpeg").is_err());
+
+    // デコードできない入力（SOFがない）は拒否する
+    let mut without_sof = vec![0xFF, 0xD8];
+    for segment in segments.iter().filter(|s| !matches!(s.marker, 0xC0..=0xC3)) {
+        without_sof.extend_from_slice(segment.bytes);
+    }
+    without_sof.extend_from_slice(&data[sos..]);
+    assert!(matches!(
+        jpeg::normalize(&without_sof),
+        Err(Error::InvalidFormat(_))
+    ));
+}
+
+/// Duckyセグメントのペイロードを組み立てる
+fn ducky_payload(quality: u32, comment: &str, copyright: &str) -> Vec<u8> {
+    let mut payload = b"Ducky".to_vec();
+    payload.extend_from_slice(&[0, 1, 0, 4]);
+    payload.extend_from_slice(&quality.to_be_bytes());
+    for (tag, text) in [(2u16, comment), (3, copyright)] {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        payload.extend_from_slice(&tag.to_be_bytes());
+        payload.extend_from_slice(&((4 + units.len() * 2) as u16).to_be_bytes());
+        payload.extend_from_slice(&(units.len() as u32).to_be_bytes());
+        for unit in units {
+            payload.extend_from_slice(&unit.to_be_bytes());
+        }
+    }
+    payload.extend_from_slice(&[0, 0]);
+    payload
+}
+
+#[test]
+fn test_read_ducky() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(jpeg::read_ducky(&data).unwrap(), None);
+
+    let with_ducky = insert_segment_after_soi(
+        &data,
+        0xEC,
+        &ducky_payload(60, "Saved for Web", "© 2009 Example"),
+    );
+    assert_eq!(
+        jpeg::read_ducky(&with_ducky).unwrap(),
+        Some(jpeg::Ducky {
+            quality: Some(60),
+            comment: Some("Saved for Web".to_string()),
+            copyright: Some("© 2009 Example".to_string()),
+        })
+    );
+
+    // 既定では削除し、オプションで保持する
+    let cleaned = jpeg::clean_metadata(&with_ducky).unwrap();
+    assert_eq!(jpeg::read_ducky(&cleaned).unwrap(), None);
+    let options = jpeg::CleanOptions {
+        preserve_ducky: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&with_ducky, &options).unwrap();
+    assert_eq!(
+        jpeg::read_ducky(&cleaned).unwrap().unwrap().quality,
+        Some(60)
+    );
+
+    // 途中で途切れたDuckyはそれまでの値を返す
+    let truncated = insert_segment_after_soi(
+        &data,
+        0xEC,
+        b"Ducky\x00\x01\x00\x04\x00\x00\x00\x50\x00\x02\x00\x40",
+    );
+    assert_eq!(
+        jpeg::read_ducky(&truncated).unwrap(),
+        Some(jpeg::Ducky {
+            quality: Some(80),
+            ..Default::default()
+        })
+    );
+}
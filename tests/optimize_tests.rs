@@ -0,0 +1,144 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, optimize, png, Error, ImageFormat, OptimizeOptions, Trim};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_optimize_jpeg() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let (output, report) = optimize(&data, &OptimizeOptions::default()).unwrap();
+
+    assert_eq!(report.format, ImageFormat::Jpeg);
+    assert_eq!(report.input_size, data.len());
+    assert_eq!(report.output_size, output.len());
+    assert_eq!(report.recompress_saved, 0);
+    assert_eq!(report.bytes_saved(), report.clean_saved);
+    assert!(output.len() < data.len());
+    jpeg::verify_pixels(&data, &output).unwrap();
+
+    // sRGBのICCプロファイルは削除する
+    let data = load_test_image("jpeg/icc/icc_srgb.jpg");
+    let has_icc = |data: &[u8]| data.windows(12).any(|w| w == b"ICC_PROFILE\0");
+    assert!(has_icc(&data));
+    let (output, _) = optimize(&data, &OptimizeOptions::default()).unwrap();
+    assert!(!has_icc(&output));
+
+    // sRGB以外のプロファイルは保持する
+    let data = load_test_image("jpeg/icc/icc_applep3.jpg");
+    let (output, _) = optimize(&data, &OptimizeOptions::default()).unwrap();
+    assert!(has_icc(&output));
+}
+
+#[test]
+fn test_optimize_png() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let (cleaned, report) = optimize(&data, &OptimizeOptions::default()).unwrap();
+    assert_eq!(report.format, ImageFormat::Png);
+    assert_eq!(report.recompress_saved, 0);
+    assert!(report.clean_saved > 0);
+
+    let options = OptimizeOptions {
+        png_recompress_level: Some(9),
+        ..Default::default()
+    };
+    let (output, report) = optimize(&data, &options).unwrap();
+    assert_eq!(report.output_size, cleaned.len() - report.recompress_saved);
+    assert_eq!(report.bytes_saved(), data.len() - output.len());
+    png::verify_pixels(&data, &output).unwrap();
+
+    let options = OptimizeOptions {
+        png_recompress_level: Some(10),
+        ..Default::default()
+    };
+    assert!(optimize(&data, &options).is_err());
+    assert!(optimize(b"not an image", &OptimizeOptions::default()).is_err());
+}
+
+#[test]
+fn test_optimize_max_output_size() {
+    let data = load_test_image("jpeg/icc/icc_applep3.jpg");
+    let data = jpeg::write_xmp(&data, "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"/>").unwrap();
+    let data = jpeg::write_comment(&data, "Sent from my camera").unwrap();
+    let mut options = OptimizeOptions::default();
+    options.jpeg_options.preserve_xmp = true;
+    options.jpeg_options.preserve_comments = true;
+    let has_icc = |data: &[u8]| data.windows(12).any(|w| w == b"ICC_PROFILE\0");
+
+    let (full, report) = optimize(&data, &options).unwrap();
+    assert!(report.trimmed.is_empty());
+
+    // 収まっている場合は何も削除しない
+    options.max_output_size = Some(full.len());
+    let (output, report) = optimize(&data, &options).unwrap();
+    assert_eq!(output, full);
+    assert!(report.trimmed.is_empty());
+
+    // サムネイルがないため、XMPから削除する
+    options.max_output_size = Some(full.len() - 1);
+    let (without_xmp, report) = optimize(&data, &options).unwrap();
+    assert_eq!(report.trimmed, [Trim::Xmp]);
+    assert_eq!(report.output_size, without_xmp.len());
+    assert!(jpeg::read_xmp(&without_xmp).unwrap().is_none());
+    assert!(has_icc(&without_xmp));
+
+    options.max_output_size = Some(without_xmp.len() - 1);
+    let (output, report) = optimize(&data, &options).unwrap();
+    assert_eq!(report.trimmed, [Trim::Xmp, Trim::IccToSrgb]);
+    assert!(!has_icc(&output));
+    assert!(jpeg::read_comment(&output).unwrap().is_some());
+    jpeg::verify_pixels(&data, &output).unwrap();
+
+    // すべて削除しても収まらない場合はエラー
+    options.max_output_size = Some(100);
+    assert!(matches!(
+        optimize(&data, &options),
+        Err(Error::LimitExceeded(_))
+    ));
+
+    // EXIFを保持する場合はサムネイルから削除する
+    let data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let mut options = OptimizeOptions::default();
+    options.jpeg_options.preserve_exif = true;
+    let (full, _) = optimize(&data, &options).unwrap();
+    options.max_output_size = Some(full.len() - 1);
+    let (output, report) = optimize(&data, &options).unwrap();
+    assert_eq!(report.trimmed, [Trim::Thumbnail]);
+    assert!(output.len() + 1000 < full.len());
+
+    // PNGではテキストチャンクをコメントとして削除する
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let mut options = OptimizeOptions::default();
+    options.png_options.preserve_text = true;
+    let (full, _) = optimize(&data, &options).unwrap();
+    options.max_output_size = Some(full.len() - 1);
+    let (output, report) = optimize(&data, &options).unwrap();
+    assert_eq!(report.trimmed, [Trim::Comments]);
+    assert!(png::read_text_chunks(&output).unwrap().is_empty());
+
+    // PNGでもeXIfチャンクのサムネイルから削除する
+    let jpeg_data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let app1 = jpeg_data.windows(6).position(|w| w == b"Exif\0\0").unwrap();
+    let length = u16::from_be_bytes([jpeg_data[app1 - 2], jpeg_data[app1 - 1]]) as usize;
+    let tiff = &jpeg_data[app1 + 6..app1 - 2 + length];
+    let mut chunk = (tiff.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(b"eXIf");
+    chunk.extend_from_slice(tiff);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&chunk[4..]);
+    chunk.extend_from_slice(&hasher.finalize().to_be_bytes());
+    let mut data = load_test_image("png/metadata/metadata_none.png");
+    data.splice(33..33, chunk);
+
+    let mut options = OptimizeOptions::default();
+    options.png_options.preserve_exif = true;
+    let (full, _) = optimize(&data, &options).unwrap();
+    options.max_output_size = Some(full.len() - 1);
+    let (output, report) = optimize(&data, &options).unwrap();
+    assert_eq!(report.trimmed, [Trim::Thumbnail]);
+    assert!(output.len() + 1000 < full.len());
+    assert!(output.windows(4).any(|w| w == b"eXIf"));
+}
@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, Orientation};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_orientation_exif_round_trip() {
+    for value in 1..=8u16 {
+        let orientation = Orientation::from_exif(value).expect("Valid orientation");
+        assert_eq!(orientation.to_exif(), value);
+        assert_eq!(u16::from(orientation), value);
+        assert_eq!(Orientation::try_from(value).unwrap(), orientation);
+    }
+
+    assert_eq!(Orientation::from_exif(0), None);
+    assert_eq!(Orientation::from_exif(9), None);
+    assert!(Orientation::try_from(9).is_err());
+    assert_eq!(Orientation::default(), Orientation::Normal);
+}
+
+#[test]
+fn test_orientation_helpers() {
+    assert!(!Orientation::Normal.is_transposed());
+    assert!(!Orientation::Rotate180.is_transposed());
+    assert!(Orientation::Rotate90.is_transposed());
+    assert!(Orientation::Transverse.is_transposed());
+
+    assert!(Orientation::FlipHorizontal.is_mirrored());
+    assert!(Orientation::Transpose.is_mirrored());
+    assert!(!Orientation::Rotate270.is_mirrored());
+
+    assert_eq!(
+        Orientation::Rotate90.display_dimensions(640, 480),
+        (480, 640)
+    );
+    assert_eq!(
+        Orientation::FlipVertical.display_dimensions(640, 480),
+        (640, 480)
+    );
+}
+
+#[test]
+fn test_read_orientation() {
+    let cases = [
+        (
+            "jpeg/orientation/orientation_1.jpg",
+            Some(Orientation::Normal),
+        ),
+        (
+            "jpeg/orientation/orientation_3.jpg",
+            Some(Orientation::Rotate180),
+        ),
+        (
+            "jpeg/orientation/orientation_6.jpg",
+            Some(Orientation::Rotate90),
+        ),
+        (
+            "jpeg/orientation/orientation_8.jpg",
+            Some(Orientation::Rotate270),
+        ),
+        ("jpeg/metadata/metadata_none.jpg", None),
+    ];
+
+    for (path, expected) in cases {
+        let data = load_test_image(path);
+        assert_eq!(jpeg::read_orientation(&data).unwrap(), expected, "{path}");
+
+        // 軽量化後も同じ値が読み取れる
+        let cleaned = jpeg::clean_metadata(&data).unwrap();
+        assert_eq!(
+            jpeg::read_orientation(&cleaned).unwrap(),
+            expected,
+            "{path}"
+        );
+    }
+
+    assert!(jpeg::read_orientation(b"not a jpeg").is_err());
+}
+
+/// オリエンテーションの値を直接書き換えたJPEGを作成
+fn with_raw_orientation(data: &[u8], value: u16) -> Vec<u8> {
+    let entry = web_image_meta::exif::entries_raw(data)
+        .unwrap()
+        .into_iter()
+        .find(|entry| entry.tag == 0x0112)
+        .expect("Orientation not found");
+    let tiff = data
+        .windows(6)
+        .position(|window| window == b"Exif\0\0")
+        .unwrap()
+        + 6;
+    let bytes = if &data[tiff..tiff + 2] == b"II" {
+        value.to_le_bytes()
+    } else {
+        value.to_be_bytes()
+    };
+
+    let mut output = data.to_vec();
+    output[entry.value_offset..entry.value_offset + 2].copy_from_slice(&bytes);
+    output
+}
+
+#[test]
+fn test_repair_invalid_orientation() {
+    let data = with_raw_orientation(&load_test_image("jpeg/orientation/orientation_6.jpg"), 0);
+    assert_eq!(jpeg::read_orientation(&data).unwrap(), None);
+
+    // デフォルトでは無効な値は削除される
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+    assert!(web_image_meta::exif::entries_raw(&cleaned)
+        .unwrap()
+        .is_empty());
+
+    // 修復オプションでは1に正規化される
+    let options = jpeg::CleanOptions {
+        repair_orientation: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(
+        jpeg::read_orientation(&cleaned).unwrap(),
+        Some(Orientation::Normal)
+    );
+
+    // 保持するEXIFも修復される
+    let options = jpeg::CleanOptions {
+        repair_orientation: true,
+        preserve_exif: true,
+        ..Default::default()
+    };
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(
+        jpeg::read_orientation(&cleaned).unwrap(),
+        Some(Orientation::Normal)
+    );
+}
+
+#[test]
+fn test_set_orientation() {
+    // 既存の値（無効な値を含む）を上書き
+    let data = load_test_image("jpeg/orientation/orientation_6.jpg");
+    for source in [data.clone(), with_raw_orientation(&data, 9)] {
+        let updated = jpeg::set_orientation(&source, Orientation::Rotate180).unwrap();
+        assert_eq!(updated.len(), source.len());
+        assert_eq!(
+            jpeg::read_orientation(&updated).unwrap(),
+            Some(Orientation::Rotate180)
+        );
+    }
+
+    // EXIFがない場合は追加
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let updated = jpeg::set_orientation(&data, Orientation::Rotate270).unwrap();
+    assert_eq!(
+        jpeg::read_orientation(&updated).unwrap(),
+        Some(Orientation::Rotate270)
+    );
+
+    // オリエンテーションのないEXIFにはタグを追加し、他のタグは維持
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    assert_eq!(jpeg::read_orientation(&data).unwrap(), None);
+    let before = web_image_meta::exif::entries_raw(&data).unwrap().len();
+    let updated = jpeg::set_orientation(&data, Orientation::Rotate90).unwrap();
+    assert_eq!(
+        jpeg::read_orientation(&updated).unwrap(),
+        Some(Orientation::Rotate90)
+    );
+    assert_eq!(
+        web_image_meta::exif::entries_raw(&updated).unwrap().len(),
+        before + 1
+    );
+}
@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{
+    jpeg, read_info, Error, ImageFormat, PeekOptions, PeekResult, PeekStep, PeekTargets,
+    RangePlanner,
+};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// 計画に従ってデータから範囲を取り出し、結果と取得したバイト数を返す
+fn run(mut planner: RangePlanner, data: &[u8]) -> Result<(PeekResult, u64), Error> {
+    let mut fetched = 0;
+    loop {
+        match planner.next_step()? {
+            PeekStep::Fetch(range) => {
+                let start = (range.start as usize).min(data.len());
+                let end = (range.end as usize).min(data.len());
+                fetched += (end - start) as u64;
+                planner.feed(range.start, &data[start..end]);
+            }
+            PeekStep::Done(result) => return Ok((result, fetched)),
+        }
+    }
+}
+
+#[test]
+fn test_range_planner_matches_full_read() {
+    let small = PeekOptions {
+        initial_fetch_size: 32,
+        min_fetch_size: 16,
+    };
+    for (file, has_icc) in [
+        ("jpeg/orientation/orientation_6.jpg", false),
+        ("jpeg/metadata/metadata_full_exif.jpg", false),
+        ("jpeg/thumbnail/thumbnail_embedded.jpg", false),
+        ("jpeg/icc/icc_srgb.jpg", true),
+        ("jpeg/icc/icc_none.jpg", false),
+        ("png/metadata/metadata_text.png", false),
+    ] {
+        let data = load_test_image(file);
+        let info = read_info(&data).unwrap();
+        let orientation = match info.format {
+            ImageFormat::Jpeg => jpeg::read_orientation(&data).unwrap(),
+            ImageFormat::Png => None,
+        };
+
+        for options in [PeekOptions::default(), small] {
+            let planner = RangePlanner::with_options(PeekTargets::default(), options);
+            let (result, _) = run(planner, &data).unwrap();
+            assert_eq!(result.format, info.format, "{file}");
+            assert_eq!(result.width, Some(info.width), "{file}");
+            assert_eq!(result.height, Some(info.height), "{file}");
+            assert_eq!(result.orientation, orientation, "{file}");
+            assert_eq!(result.has_icc, has_icc, "{file}");
+        }
+    }
+}
+
+#[test]
+fn test_range_planner_fetches_little() {
+    // サムネイルを含むEXIFも全体は取得しない
+    let data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let options = PeekOptions {
+        initial_fetch_size: 64,
+        min_fetch_size: 64,
+    };
+    let targets = PeekTargets {
+        dimensions: false,
+        orientation: true,
+        icc: false,
+    };
+    let (result, fetched) = run(RangePlanner::with_options(targets, options), &data).unwrap();
+    assert_eq!(result.width, None);
+    assert!(fetched < 1024, "fetched {fetched} bytes");
+
+    // 最初の取得は先頭から
+    let mut planner = RangePlanner::new(PeekTargets::default());
+    assert_eq!(planner.next_step().unwrap(), PeekStep::Fetch(0..64 * 1024));
+    planner.set_len(100);
+    assert_eq!(planner.next_step().unwrap(), PeekStep::Fetch(0..100));
+}
+
+#[test]
+fn test_range_planner_errors() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let sos = data.windows(2).position(|w| w == [0xFF, 0xDA]).unwrap();
+    let truncated = &data[..sos - 10];
+    let options = PeekOptions {
+        initial_fetch_size: 64,
+        min_fetch_size: 64,
+    };
+    let planner = RangePlanner::with_options(PeekTargets::default(), options);
+    assert!(run(planner, truncated).is_err());
+
+    let planner = RangePlanner::new(PeekTargets::default());
+    assert!(matches!(
+        run(planner, b"not an image at all"),
+        Err(Error::InvalidFormat(_))
+    ));
+}
@@ -2,7 +2,7 @@ use std::fs;
 use std::io::Write;
 use std::path::Path;
 use web_image_meta::png;
-use web_image_meta::Error;
+use web_image_meta::{CleanPreset, Error, TextOptions, Utf8Policy};
 
 fn load_test_image(path: &str) -> Vec<u8> {
     let full_path = Path::new("tests/test_data").join(path);
@@ -162,6 +162,37 @@ fn test_add_multiple_text_chunks() {
         .any(|c| c.keyword == "Description" && c.text == "Test Description"));
 }
 
+#[test]
+fn test_add_text_chunk_sanitize_text() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+    let text = "Caf\u{65}\u{301}\0 menu\r\n";
+
+    let options = png::WriteOptions {
+        text: TextOptions {
+            strip_control: true,
+            normalize_newlines: true,
+            nfc: true,
+            reject: false,
+        },
+        ..Default::default()
+    };
+    let output = png::add_text_chunk_with_options(&data, "Comment", text, &options).unwrap();
+    let chunk = png::find_text_chunk(&output, "Comment").unwrap();
+    assert_eq!(chunk.text, "Caf\u{e9} menu\n");
+
+    // NFCでないテキストを拒否する
+    let reject = png::WriteOptions {
+        text: TextOptions {
+            nfc: true,
+            reject: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(png::add_text_chunk_with_options(&data, "Comment", text, &reject).is_err());
+    assert!(png::add_text_chunk_with_options(&data, "Comment", "Caf\u{e9}", &reject).is_ok());
+}
+
 #[test]
 fn test_estimate_text_chunk() {
     // 空のテキストチャンク
@@ -1187,3 +1218,1227 @@ fn test_itxt_chunk_compressed() {
     assert_eq!(chunks[0].keyword, "Description");
     assert_eq!(chunks[0].text, text);
 }
+
+#[test]
+fn test_inflate_validation() {
+    let options = png::CleanOptions {
+        validation: png::Validation::Inflate,
+        ..Default::default()
+    };
+
+    // インターレース・16ビット・パレットを含む全サンプルが検証を通る
+    for path in [
+        "png/interlace/interlace_adam7.png",
+        "png/critical/critical_interlace_highres.png",
+        "png/critical/critical_16bit_palette.png",
+        "png/depth/depth_1bit.png",
+        "png/depth/depth_16bit.png",
+        "png/colortype/colortype_grayscale_alpha.png",
+        "png/metadata/metadata_text.png",
+    ] {
+        let data = load_test_image(path);
+        png::clean_chunks_with_options(&data, &options).unwrap_or_else(|e| panic!("{path}: {e:?}"));
+    }
+
+    // IDATの圧縮データを壊し、CRCは再計算する
+    let mut data = load_test_image("png/interlace/interlace_none.png");
+    let idat = find_chunk_position(&data, b"IDAT").unwrap();
+    let length = u32::from_be_bytes(data[idat..idat + 4].try_into().unwrap()) as usize;
+    let data_end = idat + 8 + length;
+    data[data_end - 8] ^= 0xFF;
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&data[idat + 4..data_end]);
+    data[data_end..data_end + 4].copy_from_slice(&hasher.finalize().to_be_bytes());
+
+    // ヘッダーのみの検証では検出できない
+    assert!(png::clean_chunks(&data).is_ok());
+    assert!(png::clean_chunks_with_options(&data, &options).is_err());
+
+    let options = png::CleanOptions {
+        validation: png::Validation::None,
+        ..Default::default()
+    };
+    assert!(png::clean_chunks_with_options(&data, &options).is_ok());
+}
+
+#[test]
+fn test_add_text_chunk_with_corrupted_crc() {
+    let original = load_test_image("png/chunk/chunk_gamma.png");
+    let bkgd = find_chunk_position(&original, b"bKGD").unwrap();
+    let length = u32::from_be_bytes(original[bkgd..bkgd + 4].try_into().unwrap()) as usize;
+    let crc_pos = bkgd + 8 + length;
+    let mut data = original.clone();
+    data[crc_pos] ^= 0xFF;
+
+    // 壊れた付随チャンクはそのまま出力される
+    let written = png::add_text_chunk(&data, "Title", "test").unwrap();
+    assert_eq!(written[crc_pos], data[crc_pos]);
+
+    // CRCを修正して書き込む
+    let fix = png::WriteOptions {
+        fix_crc: true,
+        ..Default::default()
+    };
+    let written = png::add_text_chunk_with_options(&data, "Title", "test", &fix).unwrap();
+    assert_eq!(written[crc_pos], original[crc_pos]);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&written[bkgd + 4..crc_pos]);
+    assert_eq!(
+        written[crc_pos..crc_pos + 4],
+        hasher.finalize().to_be_bytes()
+    );
+
+    // 重要なチャンクのCRC不一致は許容しない
+    let mut data = original.clone();
+    data[8 + 8 + 13] ^= 0xFF;
+    assert!(png::add_text_chunk_with_options(&data, "Title", "test", &fix).is_err());
+}
+
+#[test]
+fn test_estimate_itxt_and_ztxt_chunk() {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    // 言語タグと翻訳キーワードが空の非圧縮iTXt
+    let keyword = "Title";
+    let text = "日本語のタイトル 🎯";
+    let mut itxt = keyword.as_bytes().to_vec();
+    itxt.extend_from_slice(&[0, 0, 0, 0, 0]);
+    itxt.extend_from_slice(text.as_bytes());
+    assert_eq!(png::estimate_itxt_chunk(keyword, text), 12 + itxt.len());
+
+    // zTXtは圧縮レベルやデータによらず上限を超えない
+    let repetitive = "metadata ".repeat(5000);
+    let mut state = 0x12345678u32;
+    let noisy: String = (0..70000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            char::from(b' ' + (state % 95) as u8)
+        })
+        .collect();
+
+    for text in ["", "short", repetitive.as_str(), noisy.as_str()] {
+        let estimated = png::estimate_ztxt_chunk("Comment", text);
+        for level in [0, 1, 6, 9] {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+            encoder.write_all(text.as_bytes()).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let actual = 12 + "Comment".len() + 2 + compressed.len();
+            assert!(
+                actual <= estimated,
+                "level {level}, {} bytes: {actual} > {estimated}",
+                text.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_predict_clean_size() {
+    let option_sets = [
+        png::CleanOptions::default(),
+        png::CleanOptions {
+            preserve_text: true,
+            remove_duplicates: true,
+            ..Default::default()
+        },
+    ];
+
+    for path in [
+        "png/metadata/metadata_text.png",
+        "png/metadata/metadata_compressed.png",
+        "png/metadata/metadata_none.png",
+        "png/chunk/chunk_transparency.png",
+        "png/interlace/interlace_adam7.png",
+    ] {
+        let data = load_test_image(path);
+        for options in &option_sets {
+            let cleaned = png::clean_chunks_with_options(&data, options).unwrap();
+            assert_eq!(
+                png::predict_clean_size(&data, options).unwrap(),
+                cleaned.len(),
+                "{path}: {options:?}"
+            );
+        }
+    }
+
+    assert!(png::predict_clean_size(b"not a png", &Default::default()).is_err());
+}
+
+/// 指定位置にチャンクを挿入
+fn insert_chunk(data: &[u8], pos: usize, chunk_type: &[u8; 4], chunk_data: &[u8]) -> Vec<u8> {
+    let mut output = data[..pos].to_vec();
+    output.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(chunk_data);
+    output.extend_from_slice(
+        &crc32fast::hash(&[chunk_type.as_slice(), chunk_data].concat()).to_be_bytes(),
+    );
+    output.extend_from_slice(&data[pos..]);
+    output
+}
+
+#[test]
+fn test_strict_conformance() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+    assert!(png::check_conformance(&data).is_ok());
+
+    let strict = png::CleanOptions {
+        strict: true,
+        ..Default::default()
+    };
+    assert!(png::clean_chunks_with_options(&data, &strict).is_ok());
+
+    // 仕様にない私的チャンク
+    let idat = find_chunk_position(&data, b"IDAT").unwrap();
+    let private = insert_chunk(&data, idat, b"prVt", b"secret");
+    let err = png::clean_chunks_with_options(&private, &strict).unwrap_err();
+    assert!(err.to_string().contains(&format!("prVt at offset {idat}")));
+    // 通常は削除される
+    assert!(!check_chunk_exists(
+        &png::clean_chunks(&private).unwrap(),
+        b"prVt"
+    ));
+
+    // IDATより後のgAMA
+    let iend = find_chunk_position(&data, b"IEND").unwrap();
+    let misplaced = insert_chunk(&data, iend, b"gAMA", &45455u32.to_be_bytes());
+    let err = png::check_conformance(&misplaced).unwrap_err();
+    assert!(err.to_string().contains("gAMA"));
+    assert!(err.to_string().contains("must precede PLTE and IDAT"));
+}
+
+#[test]
+fn test_reject_duplicate_critical() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+
+    // IENDの後に別のPNG本体を付加する
+    let mut doubled = data.clone();
+    doubled.extend_from_slice(&data[8..]);
+
+    // デフォルトでは最初のIENDまでを出力する
+    assert_eq!(
+        png::clean_chunks(&doubled).unwrap(),
+        png::clean_chunks(&data).unwrap()
+    );
+
+    let options = png::CleanOptions {
+        reject_duplicate_critical: true,
+        ..Default::default()
+    };
+    assert!(png::clean_chunks_with_options(&data, &options).is_ok());
+    let err = png::clean_chunks_with_options(&doubled, &options).unwrap_err();
+    assert!(err.to_string().contains("IHDR appears 2 times"));
+    assert!(err.to_string().contains("IEND appears 2 times"));
+}
+
+#[test]
+fn test_validate_structure() {
+    for dir in ["colortype", "critical", "interlace", "metadata"] {
+        for entry in fs::read_dir(Path::new("tests/test_data/png").join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            let report = png::validate_structure(&fs::read(&path).unwrap()).unwrap();
+            assert!(report.is_valid(), "{}: {:?}", path.display(), report.issues);
+            assert!(report.complete);
+        }
+    }
+
+    let data = load_test_image("png/colortype/colortype_palette.png");
+    let report = png::validate_structure(&data).unwrap();
+    assert_eq!(report.trailing_bytes, 0);
+
+    // IDATのCRCを壊す
+    let idat = find_chunk_position(&data, b"IDAT").unwrap();
+    let length = u32::from_be_bytes(data[idat..idat + 4].try_into().unwrap()) as usize;
+    let mut corrupted = data.clone();
+    corrupted[idat + 8 + length] ^= 0xFF;
+    let report = png::validate_structure(&corrupted).unwrap();
+    assert_eq!(report.issues.len(), 1);
+    assert_eq!(report.issues[0].offset, idat);
+    assert!(report.issues[0].message.contains("CRC mismatch in IDAT"));
+
+    // パレット画像からPLTEを取り除く
+    let plte = find_chunk_position(&data, b"PLTE").unwrap();
+    let plte_length = u32::from_be_bytes(data[plte..plte + 4].try_into().unwrap()) as usize;
+    let mut without_plte = data[..plte].to_vec();
+    without_plte.extend_from_slice(&data[plte + 12 + plte_length..]);
+    let report = png::validate_structure(&without_plte).unwrap();
+    assert!(report.issues[0]
+        .message
+        .contains("precedes the required PLTE"));
+
+    // 途中で途切れたデータ
+    let report = png::validate_structure(&data[..idat + 20]).unwrap();
+    assert!(!report.complete);
+    assert_eq!(report.issues[0].offset, idat);
+    assert!(report.issues[0].message.contains("truncated"));
+
+    assert!(png::validate_structure(b"not a png").is_err());
+}
+
+#[test]
+fn test_verify_passthrough() {
+    let options = png::CleanOptions {
+        replace_srgb_icc: true,
+        verify_passthrough: true,
+        ..Default::default()
+    };
+    for dir in ["chunk", "colortype", "critical", "metadata"] {
+        for entry in fs::read_dir(Path::new("tests/test_data/png").join(dir)).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            let cleaned = png::clean_chunks_with_options(&data, &options).unwrap();
+            assert!(png::verify_passthrough(&data, &cleaned).is_ok());
+        }
+    }
+
+    let data = load_test_image("png/colortype/colortype_palette.png");
+    let cleaned = png::clean_chunks(&data).unwrap();
+
+    // パレットの色を変更
+    let mut tampered = cleaned.clone();
+    let plte = find_chunk_position(&tampered, b"PLTE").unwrap();
+    tampered[plte + 8] ^= 0x01;
+    let err = png::verify_passthrough(&data, &tampered).unwrap_err();
+    assert!(err.to_string().contains("PLTE"));
+
+    // IDATを取り除く
+    let idat = find_chunk_position(&cleaned, b"IDAT").unwrap();
+    let length = u32::from_be_bytes(cleaned[idat..idat + 4].try_into().unwrap()) as usize;
+    let mut truncated = cleaned[..idat].to_vec();
+    truncated.extend_from_slice(&cleaned[idat + 12 + length..]);
+    assert!(png::verify_passthrough(&data, &truncated).is_err());
+}
+
+#[test]
+fn test_verify_pixels() {
+    let options = png::CleanOptions {
+        verify_pixels: true,
+        ..Default::default()
+    };
+    for dir in ["alpha", "colortype", "metadata"] {
+        for entry in fs::read_dir(Path::new("tests/test_data/png").join(dir)).unwrap() {
+            let data = fs::read(entry.unwrap().path()).unwrap();
+            let cleaned = png::clean_chunks_with_options(&data, &options).unwrap();
+            assert!(png::verify_pixels(&data, &cleaned).is_ok());
+        }
+    }
+
+    // パレットの色を変更（CRCは再計算）
+    let data = load_test_image("png/colortype/colortype_palette.png");
+    let mut tampered = png::clean_chunks(&data).unwrap();
+    let plte = find_chunk_position(&tampered, b"PLTE").unwrap();
+    let length = u32::from_be_bytes(tampered[plte..plte + 4].try_into().unwrap()) as usize;
+    tampered[plte + 8] ^= 0x01;
+    let crc = crc32fast::hash(&tampered[plte + 4..plte + 8 + length]);
+    tampered[plte + 8 + length..plte + 12 + length].copy_from_slice(&crc.to_be_bytes());
+    assert!(png::verify_passthrough(&data, &tampered).is_err());
+    let err = png::verify_pixels(&data, &tampered).unwrap_err();
+    assert!(matches!(err, Error::PixelMismatch(_)));
+
+    // 1ビットの画像でも異なる画素の座標を求める
+    let (width, height) = (20u32, 4);
+    let original = bilevel_png(width, height, None);
+    let changed = bilevel_png(width, height, Some((10, 2)));
+    assert!(png::verify_pixels(&original, &original).is_ok());
+    let err = png::verify_pixels(&original, &changed).unwrap_err();
+    assert!(err.to_string().contains("(10, 2)"), "{err}");
+}
+
+/// 1ビットのグレースケール画像を作成する（`set`の画素のみ白）
+fn bilevel_png(width: u32, height: u32, set: Option<(usize, usize)>) -> Vec<u8> {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    let row_bytes = (width as usize).div_ceil(8);
+    let mut raw = vec![0u8; (row_bytes + 1) * height as usize];
+    if let Some((x, y)) = set {
+        raw[y * (row_bytes + 1) + 1 + x / 8] |= 0x80 >> (x % 8);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&raw).unwrap();
+
+    let mut ihdr = width.to_be_bytes().to_vec();
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[1, 0, 0, 0, 0]);
+    let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (chunk_type, chunk_data) in [
+        (b"IHDR", ihdr),
+        (b"IDAT", encoder.finish().unwrap()),
+        (b"IEND", Vec::new()),
+    ] {
+        data.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+        data.extend_from_slice(chunk_type);
+        data.extend_from_slice(&chunk_data);
+        data.extend_from_slice(
+            &crc32fast::hash(&[chunk_type.as_slice(), &chunk_data].concat()).to_be_bytes(),
+        );
+    }
+    data
+}
+
+#[test]
+fn test_read_dimensions() {
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert_eq!(png::read_dimensions(&data).unwrap(), (480, 480));
+
+    // IDATが壊れていてもIHDRだけで読み取れる
+    let truncated = &data[..33 + 20];
+    assert_eq!(png::read_dimensions(truncated).unwrap(), (480, 480));
+
+    // シグネチャやIHDRが不正
+    assert!(png::read_dimensions(b"not a png").is_err());
+    assert!(png::read_dimensions(&data[..20]).is_err());
+    let mut zero = data.clone();
+    zero[16..20].copy_from_slice(&[0, 0, 0, 0]);
+    assert!(png::read_dimensions(&zero).is_err());
+}
+
+/// 最初のIDATの前にチャンクを挿入する
+fn insert_before_idat(data: &[u8], chunk_type: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let pos = find_chunk_position(data, b"IDAT").unwrap();
+    let mut chunk = (content.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(chunk_type);
+    chunk.extend_from_slice(content);
+    chunk.extend_from_slice(
+        &crc32fast::hash(&[chunk_type.as_slice(), content].concat()).to_be_bytes(),
+    );
+    [&data[..pos], &chunk, &data[pos..]].concat()
+}
+
+#[test]
+fn test_has_transparency() {
+    assert!(png::has_transparency(&load_test_image("png/colortype/colortype_rgba.png")).unwrap());
+    assert!(!png::has_transparency(&load_test_image("png/colortype/colortype_rgb.png")).unwrap());
+
+    // パレットのtRNSは不透明でないエントリがある場合のみ
+    let palette = load_test_image("png/colortype/colortype_palette.png");
+    assert!(!png::has_transparency(&palette).unwrap());
+    let opaque = insert_before_idat(&palette, b"tRNS", &[255, 255]);
+    assert!(!png::has_transparency(&opaque).unwrap());
+    let transparent = insert_before_idat(&palette, b"tRNS", &[255, 0]);
+    assert!(png::has_transparency(&transparent).unwrap());
+
+    // RGBのtRNSは透明色の指定
+    let rgb = load_test_image("png/colortype/colortype_rgb.png");
+    let keyed = insert_before_idat(&rgb, b"tRNS", &[0, 0, 0, 0, 0, 0]);
+    assert!(png::has_transparency(&keyed).unwrap());
+
+    assert!(png::has_transparency(b"not a png").is_err());
+}
+
+#[test]
+fn test_check_color_chunks() {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use png::ColorConflict;
+
+    // colortype_rgb.pngのcHRMはsRGBの色度
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert!(png::check_color_chunks(&data).unwrap().is_empty());
+    let srgb = insert_before_idat(&data, b"sRGB", &[0]);
+    assert!(png::check_color_chunks(&srgb).unwrap().is_empty());
+
+    // sRGBと異なるgAMA
+    let gamma = insert_before_idat(&srgb, b"gAMA", &100000u32.to_be_bytes());
+    assert_eq!(
+        png::check_color_chunks(&gamma).unwrap(),
+        [ColorConflict::GammaMismatch { gamma: 100000 }]
+    );
+    let options = png::CleanOptions {
+        resolve_color_conflicts: true,
+        ..Default::default()
+    };
+    let resolved = png::clean_chunks_with_options(&gamma, &options).unwrap();
+    assert!(png::check_color_chunks(&resolved).unwrap().is_empty());
+    assert!(check_chunk_exists(&resolved, b"sRGB"));
+    assert!(!check_chunk_exists(&resolved, b"gAMA"));
+    // オプションなしでは保持
+    let cleaned = png::clean_chunks(&gamma).unwrap();
+    assert!(check_chunk_exists(&cleaned, b"gAMA"));
+
+    // sRGB以外のICCプロファイルとsRGBが両方ある
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&[0u8; 200]).unwrap();
+    let iccp = [b"Custom\0\0".as_slice(), &encoder.finish().unwrap()].concat();
+    let both = insert_before_idat(&gamma, b"iCCP", &iccp);
+    let conflicts = png::check_color_chunks(&both).unwrap();
+    assert!(conflicts.contains(&ColorConflict::IccWithSrgb));
+
+    // ICCプロファイルを残し、gAMAは代替情報として保持
+    let resolved = png::clean_chunks_with_options(&both, &options).unwrap();
+    assert!(png::check_color_chunks(&resolved).unwrap().is_empty());
+    assert!(check_chunk_exists(&resolved, b"iCCP"));
+    assert!(!check_chunk_exists(&resolved, b"sRGB"));
+    assert!(check_chunk_exists(&resolved, b"gAMA"));
+
+    assert!(png::check_color_chunks(b"not a png").is_err());
+}
+
+#[test]
+fn test_lint() {
+    use web_image_meta::LintRule;
+
+    let rules = |data: &[u8]| {
+        png::lint(data)
+            .iter()
+            .map(|finding| finding.rule)
+            .collect::<Vec<_>>()
+    };
+
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert_eq!(rules(&data), [LintRule::MissingColorInfo]);
+    let srgb = insert_before_idat(&data, b"sRGB", &[0]);
+    assert!(png::lint(&srgb).is_empty());
+
+    // 重複したsRGBと不正なキーワード
+    let duplicate = insert_before_idat(&srgb, b"sRGB", &[0]);
+    let findings = png::lint(&duplicate);
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].rule, LintRule::DuplicateChunk);
+    assert_eq!(
+        findings[0].offset,
+        find_chunk_position(&duplicate, b"IDAT").map(|pos| pos - 13)
+    );
+    let keyword = insert_before_idat(&srgb, b"tEXt", b" Bad  key\0text");
+    assert_eq!(rules(&keyword), [LintRule::InvalidKeyword]);
+
+    // 過大なテキスト
+    let text = png::add_text_chunk(&data, "Comment", &"a".repeat(100)).unwrap();
+    let options = png::LintOptions {
+        max_text_size: 64,
+        require_color_info: false,
+    };
+    let findings = png::lint_with_options(&text, &options);
+    assert_eq!(findings[0].rule, LintRule::OversizedText);
+
+    assert_eq!(rules(b"not a png"), [LintRule::MalformedStructure]);
+    assert_eq!(
+        rules(&srgb[..srgb.len() - 6]),
+        [LintRule::MalformedStructure, LintRule::MalformedStructure]
+    );
+}
+
+#[test]
+fn test_clean_chunks_with_progress() {
+    use web_image_meta::ProgressStage;
+
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let options = png::CleanOptions::default();
+    let mut events = Vec::new();
+    let cleaned = png::clean_chunks_with_progress(&data, &options, |p| events.push(p)).unwrap();
+    assert_eq!(
+        cleaned,
+        png::clean_chunks_with_options(&data, &options).unwrap()
+    );
+
+    let last_write = events
+        .iter()
+        .rev()
+        .find(|p| p.stage == ProgressStage::Write)
+        .unwrap();
+    assert_eq!(last_write.processed, cleaned.len());
+    assert_eq!(last_write.total, cleaned.len());
+    assert_eq!(events[0].stage, ProgressStage::ValidateInput);
+    assert_eq!(events[0].total, data.len());
+
+    // 展開・デコードによる検証では途中の位置も通知する
+    let data = load_test_image("png/compression/compression_0.png");
+    for validation in [png::Validation::Inflate, png::Validation::Full] {
+        let options = png::CleanOptions {
+            validation,
+            ..Default::default()
+        };
+        let mut events = Vec::new();
+        png::clean_chunks_with_progress(&data, &options, |p| events.push(p)).unwrap();
+        let validated: Vec<usize> = events
+            .iter()
+            .filter(|p| p.stage == ProgressStage::ValidateInput)
+            .map(|p| p.processed)
+            .collect();
+        assert!(
+            validated.iter().any(|&p| p > 0 && p < data.len()),
+            "{validation:?}"
+        );
+        assert!(validated.windows(2).all(|w| w[0] <= w[1]), "{validation:?}");
+    }
+
+    // エラー時は途中までの進捗のみ
+    let mut events = Vec::new();
+    assert!(png::clean_chunks_with_progress(b"not a png", &options, |p| events.push(p)).is_err());
+    assert!(events.is_empty());
+}
+
+#[test]
+fn test_clean_chunks_cancellation() {
+    use web_image_meta::CancellationToken;
+
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let token = CancellationToken::new();
+    for validation in [
+        png::Validation::Header,
+        png::Validation::Inflate,
+        png::Validation::Full,
+    ] {
+        let options = png::CleanOptions {
+            validation,
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            png::clean_chunks_with_options(&data, &options).unwrap(),
+            png::clean_chunks(&data).unwrap()
+        );
+    }
+
+    // クローンしたトークンで中断すると、どの検証レベルでもエラーになる
+    token.clone().cancel();
+    for validation in [png::Validation::None, png::Validation::Full] {
+        let options = png::CleanOptions {
+            validation,
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            png::clean_chunks_with_options(&data, &options),
+            Err(Error::Cancelled)
+        ));
+    }
+}
+
+#[test]
+fn test_chunks_iterator() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+
+    let chunks: Vec<png::Chunk> = png::chunks(&data)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(&chunks.first().unwrap().chunk_type, b"IHDR");
+    assert_eq!(&chunks.last().unwrap().chunk_type, b"IEND");
+    assert!(chunks.iter().any(|chunk| chunk.type_name() == "tEXt"));
+
+    // チャンクは入力を借用し、シグネチャの直後から隙間なく並ぶ
+    let mut pos = 8;
+    for chunk in &chunks {
+        assert_eq!(chunk.offset, pos);
+        assert_eq!(chunk.bytes.as_ptr(), data[pos..].as_ptr());
+        assert_eq!(chunk.data.len() + 12, chunk.bytes.len());
+        pos += chunk.bytes.len();
+    }
+
+    // 途切れたデータではエラーを1度だけ返す
+    let results: Vec<_> = png::chunks(&data[..data.len() - 6]).unwrap().collect();
+    assert!(results.last().unwrap().is_err());
+    assert_eq!(results.iter().filter(|r| r.is_err()).count(), 1);
+
+    assert!(png::chunks(b"not a png").is_err());
+}
+
+#[test]
+fn test_find_text_chunk() {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"compressed").unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    let data = insert_before_idat(&data, b"tEXt", b"Source\0first");
+    let data = insert_before_idat(&data, b"tEXt", b"Source\0second");
+    let data = insert_before_idat(
+        &data,
+        b"zTXt",
+        &[b"Provenance\0\0".as_slice(), &compressed].concat(),
+    );
+    // 展開できない圧縮iTXtは読み飛ばす
+    let data = insert_before_idat(&data, b"iTXt", b"Broken\0\x01\0\0\0garbage");
+
+    let found = png::find_text_chunk(&data, "Source").unwrap();
+    assert_eq!(found.text, "first");
+    let found = png::find_text_chunk(&data, "Provenance").unwrap();
+    assert_eq!(found.keyword, "Provenance");
+    assert_eq!(found.text, "compressed");
+    assert_eq!(png::find_text_chunk(&data, "Broken"), None);
+    assert_eq!(png::find_text_chunk(&data, "Missing"), None);
+    assert_eq!(png::find_text_chunk(b"not a png", "Source"), None);
+
+    // 一覧でも壊れたiTXtは除外される
+    let all = png::read_text_chunks(&data).unwrap();
+    assert_eq!(all.iter().filter(|c| c.keyword == "Source").count(), 2);
+    assert!(all.iter().all(|c| c.keyword != "Broken"));
+}
+
+#[test]
+fn test_clean_chunks_deterministic() {
+    use web_image_meta::jpeg;
+
+    // JPEGのEXIFをeXIfチャンクとして追加する
+    let jpeg_data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let segment = jpeg::segments(&jpeg_data)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|segment| segment.payload().starts_with(b"Exif\0\0"))
+        .unwrap();
+    let base = load_test_image("png/colortype/colortype_rgb.png");
+    let data = insert_before_idat(&base, b"eXIf", &segment.payload()[6..]);
+
+    // 同じ入力とオプションからは同じ出力
+    for options in [
+        png::CleanOptions::default(),
+        png::CleanOptions {
+            preserve_exif: true,
+            ..Default::default()
+        },
+    ] {
+        let cleaned = png::clean_chunks_with_options(&data, &options).unwrap();
+        assert_eq!(
+            cleaned,
+            png::clean_chunks_with_options(&data, &options).unwrap()
+        );
+        assert_eq!(
+            png::add_text_chunk(&cleaned, "Title", "note").unwrap(),
+            png::add_text_chunk(&cleaned, "Title", "note").unwrap()
+        );
+        assert!(png::check_conformance(&cleaned).is_ok());
+    }
+}
+
+#[test]
+fn test_read_write_xmp() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+    assert_eq!(png::read_xmp(&data).unwrap(), None);
+
+    let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF/></x:xmpmeta>"#;
+    let with_xmp = png::write_xmp(&data, packet).unwrap();
+    assert_eq!(png::read_xmp(&with_xmp).unwrap().as_deref(), Some(packet));
+
+    // 非圧縮・言語タグなしのiTXtとしてIDATの前に書き込まれる
+    let itxt = find_chunk_position(&with_xmp, b"iTXt").unwrap();
+    assert!(itxt < find_chunk_position(&with_xmp, b"IDAT").unwrap());
+    assert_eq!(
+        &with_xmp[itxt + 8..itxt + 8 + 24],
+        b"XML:com.adobe.xmp\0\0\0\0\0<x"
+    );
+
+    // 置き換えてもXMPチャンクは1つ
+    let replaced = png::write_xmp(&with_xmp, "<x:xmpmeta/>").unwrap();
+    assert_eq!(
+        png::read_xmp(&replaced).unwrap().as_deref(),
+        Some("<x:xmpmeta/>")
+    );
+    assert_eq!(replaced.len(), with_xmp.len() - packet.len() + 12);
+
+    // 空文字列で削除
+    let removed = png::write_xmp(&replaced, "").unwrap();
+    assert_eq!(removed, data);
+
+    // preserve_xmpでは他のテキストチャンクを削除してXMPのみ残す
+    let options = png::CleanOptions {
+        preserve_xmp: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&with_xmp, &options).unwrap();
+    assert_eq!(png::read_xmp(&cleaned).unwrap().as_deref(), Some(packet));
+    assert_eq!(png::read_text_chunks(&cleaned).unwrap().len(), 1);
+    let cleaned = png::clean_chunks(&with_xmp).unwrap();
+    assert_eq!(png::read_xmp(&cleaned).unwrap(), None);
+}
+
+#[test]
+fn test_clean_chunks_keep_text_keywords() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+    let data = png::add_text_chunk(&data, "Copyright", "Example Agency").unwrap();
+    let data = png::add_text_chunk(&data, "Comment", "internal note").unwrap();
+    // 言語タグ付きのiTXt（キーワード + null + 非圧縮 + 言語タグ + null + 翻訳キーワード + null + テキスト）
+    let itxt = b"AssetID\0\0\0ja\0\xe8\xb3\x87\xe7\x94\xa3ID\0A-123";
+    let data = insert_before_idat(&data, b"iTXt", itxt);
+
+    let options = png::CleanOptions {
+        keep_text_keywords: vec!["Copyright".to_string(), "AssetID".to_string()],
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&data, &options).unwrap();
+
+    let chunks = png::read_text_chunks(&cleaned).unwrap();
+    let keywords: Vec<&str> = chunks.iter().map(|c| c.keyword.as_str()).collect();
+    assert_eq!(keywords, vec!["AssetID", "Copyright"]);
+    // iTXtは言語タグを含めて元のまま残る
+    let itxt_pos = find_chunk_position(&cleaned, b"iTXt").unwrap();
+    assert_eq!(&cleaned[itxt_pos + 8..itxt_pos + 8 + itxt.len()], itxt);
+}
+
+#[test]
+fn test_clean_chunks_compress_text() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+    let json = format!("[{}]", vec![r#"{"step":"resize","ok":true}"#; 50].join(","));
+    let data = png::add_text_chunk(&data, "Provenance", &json).unwrap();
+    let data = png::add_text_chunk(&data, "Title", "short").unwrap();
+
+    let options = png::CleanOptions {
+        preserve_text: true,
+        compress_text: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&data, &options).unwrap();
+    assert!(cleaned.len() < data.len() - json.len() / 2);
+    // 小さくならない短いテキストはtEXtのまま
+    assert!(check_chunk_exists(&cleaned, b"zTXt"));
+    assert!(check_chunk_exists(&cleaned, b"tEXt"));
+
+    let chunks = png::read_text_chunks(&cleaned).unwrap();
+    assert_eq!(
+        png::find_text_chunk(&cleaned, "Provenance").unwrap().text,
+        json
+    );
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(
+        png::predict_clean_size(&data, &options).unwrap(),
+        cleaned.len()
+    );
+}
+
+/// IDATを指定した大きさのチャンクに分割する
+fn split_idat(data: &[u8], size: usize) -> Vec<u8> {
+    let pos = find_chunk_position(data, b"IDAT").unwrap();
+    let length = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+    let mut output = data[..pos].to_vec();
+    for part in data[pos + 8..pos + 8 + length].chunks(size) {
+        output.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        output.extend_from_slice(b"IDAT");
+        output.extend_from_slice(part);
+        output.extend_from_slice(
+            &crc32fast::hash(&[b"IDAT".as_slice(), part].concat()).to_be_bytes(),
+        );
+    }
+    output.extend_from_slice(&data[pos + 12 + length..]);
+    output
+}
+
+#[test]
+fn test_merge_idat() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let split = split_idat(&data, 64);
+    let count = |data: &[u8]| {
+        png::chunks(data)
+            .unwrap()
+            .filter(|chunk| &chunk.as_ref().unwrap().chunk_type == b"IDAT")
+            .count()
+    };
+    assert!(count(&split) > 1);
+
+    let merged = png::merge_idat(&split).unwrap();
+    assert_eq!(count(&merged), 1);
+    assert_eq!(merged, png::merge_idat(&data).unwrap());
+    png::verify_pixels(&split, &merged).unwrap();
+
+    // clean_chunksのオプション
+    let options = png::CleanOptions {
+        merge_idat: true,
+        verify_passthrough: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&split, &options).unwrap();
+    assert_eq!(count(&cleaned), 1);
+    assert_eq!(
+        cleaned,
+        png::clean_chunks_with_options(&data, &options).unwrap()
+    );
+    assert_eq!(
+        png::predict_clean_size(&split, &options).unwrap(),
+        cleaned.len()
+    );
+    // 区切りが変わるためチャンク単位の比較では一致しない
+    assert!(png::verify_passthrough(&split, &cleaned).is_err());
+}
+
+#[test]
+fn test_recompress() {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    // 圧縮していない64x64のRGBのグラデーション
+    let (width, height) = (64u32, 64u32);
+    let mut raw = Vec::new();
+    for y in 0..height {
+        raw.push(0); // フィルタなし
+        for x in 0..width {
+            raw.extend_from_slice(&[(x * 4) as u8, (y * 4) as u8, 128]);
+        }
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::none());
+    encoder.write_all(&raw).unwrap();
+    let idat = encoder.finish().unwrap();
+
+    let mut ihdr = width.to_be_bytes().to_vec();
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+    let mut data = vec![137, 80, 78, 71, 13, 10, 26, 10];
+    for (chunk_type, content) in [
+        (b"IHDR", &ihdr[..]),
+        (b"IDAT", &idat[..]),
+        (b"IEND", &[][..]),
+    ] {
+        data.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        data.extend_from_slice(chunk_type);
+        data.extend_from_slice(content);
+        data.extend_from_slice(
+            &crc32fast::hash(&[chunk_type.as_slice(), content].concat()).to_be_bytes(),
+        );
+    }
+    let data = split_idat(&data, 4096);
+
+    let recompressed = png::recompress(&data, 9).unwrap();
+    assert!(recompressed.len() < data.len());
+    png::verify_pixels(&data, &recompressed).unwrap();
+
+    // 小さくならない場合は入力のまま
+    assert_eq!(png::recompress(&recompressed, 0).unwrap(), recompressed);
+    assert!(png::recompress(&data, 10).is_err());
+}
+
+#[test]
+fn test_read_text_chunks_with_policy() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+    let with_text = png::add_text_chunk(&data, "Title", "Café").expect("Failed to add text chunk");
+
+    // Latin-1のtEXtチャンクをIHDRの直後に挿入する
+    let chunk_data = b"Author\0Ren\xE9".to_vec();
+    let mut chunk = (chunk_data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(b"tEXt");
+    chunk.extend_from_slice(&chunk_data);
+    chunk.extend_from_slice(
+        &crc32fast::hash(&[b"tEXt".as_slice(), &chunk_data].concat()).to_be_bytes(),
+    );
+    let png_data = [&with_text[..33], &chunk, &with_text[33..]].concat();
+
+    let chunks = png::read_text_chunks_with_policy(&png_data, Utf8Policy::Latin1Fallback)
+        .expect("Failed to read text chunks");
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].keyword, "Author");
+    assert_eq!(chunks[0].text.text, "René");
+    assert_eq!(chunks[0].text.repaired, Some(Utf8Policy::Latin1Fallback));
+    assert_eq!(chunks[1].text.text, "Café");
+    assert_eq!(chunks[1].text.repaired, None);
+
+    let chunks = png::read_text_chunks_with_policy(&png_data, Utf8Policy::Lossy).unwrap();
+    assert_eq!(chunks[0].text.repaired, Some(Utf8Policy::Lossy));
+    assert!(matches!(
+        png::read_text_chunks_with_policy(&png_data, Utf8Policy::Reject),
+        Err(Error::ParseError(_))
+    ));
+    assert!(png::read_text_chunks_with_policy(&with_text, Utf8Policy::Reject).is_ok());
+}
+
+#[test]
+fn test_clean_chunks_hist_splt() {
+    let data = load_test_image("png/colortype/colortype_palette.png");
+    let plte = png::chunks(&data)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|chunk| &chunk.chunk_type == b"PLTE")
+        .unwrap();
+    let after_plte = plte.offset + plte.bytes.len();
+    let entries = plte.data.len() / 3;
+
+    // PLTEの直後にhISTとsPLTを挿入する
+    let hist = vec![0u8; entries * 2];
+    let splt = [b"quant\0\x08".as_slice(), &[0, 0, 0, 255, 0, 1]].concat();
+    let with_chunks = insert_chunk(&data, after_plte, b"sPLT", &splt);
+    let with_chunks = insert_chunk(&with_chunks, after_plte, b"hIST", &hist);
+
+    // 既定では削除する
+    let cleaned = png::clean_chunks(&with_chunks).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"hIST"));
+    assert!(!check_chunk_exists(&cleaned, b"sPLT"));
+
+    let options = png::CleanOptions {
+        preserve_hist: true,
+        preserve_splt: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&with_chunks, &options).unwrap();
+    assert!(check_chunk_exists(&cleaned, b"hIST"));
+    assert!(check_chunk_exists(&cleaned, b"sPLT"));
+    png::verify_pixels(&data, &cleaned).unwrap();
+
+    // 項目数がPLTEと一致しないhISTや、構造が壊れたsPLTは保持しない
+    let broken = insert_chunk(&data, after_plte, b"sPLT", b"quant\0\x08\0\0");
+    let broken = insert_chunk(&broken, after_plte, b"hIST", &hist[2..]);
+    let cleaned = png::clean_chunks_with_options(&broken, &options).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"hIST"));
+    assert!(!check_chunk_exists(&cleaned, b"sPLT"));
+
+    // PLTEより前のhISTは保持しない
+    let before_plte = insert_chunk(&data, plte.offset, b"hIST", &hist);
+    let cleaned = png::clean_chunks_with_options(&before_plte, &options).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"hIST"));
+
+    // パレットの項目数以内のtRNSは残し、超えるtRNSは削除する
+    assert!(!check_chunk_exists(&data, b"tRNS"));
+    let trns = insert_chunk(&data, after_plte, b"tRNS", &vec![128; entries]);
+    let cleaned = png::clean_chunks(&trns).unwrap();
+    assert!(check_chunk_exists(&cleaned, b"tRNS"));
+    let oversized = insert_chunk(&data, after_plte, b"tRNS", &vec![128; entries + 1]);
+    let cleaned = png::clean_chunks(&oversized).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"tRNS"));
+}
+
+#[test]
+fn test_offset_scale_stereo() {
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert_eq!(png::read_offset(&data).unwrap(), None);
+    assert_eq!(png::read_scale(&data).unwrap(), None);
+    assert_eq!(png::read_stereo(&data).unwrap(), None);
+
+    let offset = png::ImageOffset {
+        x: -120,
+        y: 3400,
+        unit: png::OffsetUnit::Micrometer,
+    };
+    let scale = png::PhysicalScale {
+        width: 1.5e-6,
+        height: 0.25,
+        unit: png::ScaleUnit::Meter,
+    };
+    let written = png::write_offset(&data, &offset).unwrap();
+    let written = png::write_scale(&written, &scale).unwrap();
+    let written = png::write_stereo(&written, png::StereoMode::DivergingFuse).unwrap();
+    assert_eq!(png::read_offset(&written).unwrap(), Some(offset));
+    assert_eq!(png::read_scale(&written).unwrap(), Some(scale));
+    assert_eq!(
+        png::read_stereo(&written).unwrap(),
+        Some(png::StereoMode::DivergingFuse)
+    );
+    png::check_conformance(&written).unwrap();
+
+    // 既存のチャンクは置き換える
+    let rewritten = png::write_stereo(&written, png::StereoMode::CrossFuse).unwrap();
+    assert_eq!(rewritten.len(), written.len());
+    assert_eq!(
+        png::read_stereo(&rewritten).unwrap(),
+        Some(png::StereoMode::CrossFuse)
+    );
+
+    // 正の値でない寸法は書き込まない
+    let invalid = png::PhysicalScale {
+        width: 0.0,
+        ..scale
+    };
+    assert!(matches!(
+        png::write_scale(&data, &invalid),
+        Err(Error::InvalidFormat(_))
+    ));
+
+    // 既定では削除し、オプションで保持する
+    let cleaned = png::clean_chunks(&written).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"oFFs"));
+    assert!(!check_chunk_exists(&cleaned, b"sCAL"));
+    assert!(!check_chunk_exists(&cleaned, b"sTER"));
+
+    let options = png::CleanOptions {
+        preserve_scal: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&written, &options).unwrap();
+    assert_eq!(png::read_scale(&cleaned).unwrap(), Some(scale));
+    assert!(!check_chunk_exists(&cleaned, b"oFFs"));
+
+    let cleaned =
+        png::clean_chunks_with_options(&written, &CleanPreset::Archival.png_options()).unwrap();
+    assert_eq!(png::read_offset(&cleaned).unwrap(), Some(offset));
+    assert_eq!(png::read_scale(&cleaned).unwrap(), Some(scale));
+
+    // 構造が壊れたsCALは読み取らず、保持もしない
+    let idat = png::chunks(&data)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|chunk| &chunk.chunk_type == b"IDAT")
+        .unwrap();
+    let broken = insert_chunk(&data, idat.offset, b"sCAL", b"\x01abc\x001.0");
+    assert_eq!(png::read_scale(&broken).unwrap(), None);
+    let cleaned = png::clean_chunks_with_options(&broken, &options).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"sCAL"));
+}
+
+/// ICCプロファイルのタグの内容を返す
+fn icc_tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let read_u32 = |pos: usize| u32::from_be_bytes(profile[pos..pos + 4].try_into().unwrap());
+    (0..read_u32(128) as usize).find_map(|index| {
+        let entry = 132 + index * 12;
+        (&profile[entry..entry + 4] == signature).then(|| {
+            let offset = read_u32(entry + 4) as usize;
+            &profile[offset..offset + read_u32(entry + 8) as usize]
+        })
+    })
+}
+
+#[test]
+fn test_synthesize_icc_profile() {
+    // cHRMはsRGBの原色、gAMAは1/2.2
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert_eq!(png::synthesize_icc_profile(&data).unwrap(), None);
+    assert_eq!(png::embed_synthesized_icc(&data).unwrap(), data);
+
+    let data = insert_before_idat(&data, b"gAMA", &45455u32.to_be_bytes());
+    let profile = png::synthesize_icc_profile(&data).unwrap().unwrap();
+    assert_eq!(
+        u32::from_be_bytes(profile[0..4].try_into().unwrap()) as usize,
+        profile.len()
+    );
+    assert_eq!(&profile[12..20], b"mntrRGB ");
+    assert_eq!(&profile[36..40], b"acsp");
+    let description = icc_tag(&profile, b"desc").unwrap();
+    assert!(description[12..].starts_with(b"sRGB primaries, gamma 2.20\0"));
+
+    // 赤の原色はD50に順応したsRGBの値
+    let red = icc_tag(&profile, b"rXYZ").unwrap();
+    let red: Vec<f64> = red[8..20]
+        .chunks_exact(4)
+        .map(|value| f64::from(i32::from_be_bytes(value.try_into().unwrap())) / 65536.0)
+        .collect();
+    for (value, expected) in red.iter().zip([0.4361, 0.2225, 0.0139]) {
+        assert!((value - expected).abs() < 0.001, "{red:?}");
+    }
+    assert_eq!(
+        &icc_tag(&profile, b"rTRC").unwrap()[8..14],
+        &[0, 0, 0, 1, 2, 51]
+    );
+
+    // iCCPとして埋め込むと、以降は作成しない
+    let embedded = png::embed_synthesized_icc(&data).unwrap();
+    let icc = web_image_meta::extract_embedded(&embedded).unwrap().icc;
+    assert_eq!(icc.as_deref(), Some(&profile[..]));
+    assert!(check_chunk_exists(&embedded, b"gAMA"));
+    assert_eq!(png::synthesize_icc_profile(&embedded).unwrap(), None);
+
+    // グレースケールの画像はトーンカーブのみのプロファイル
+    let gray = load_test_image("png/colortype/colortype_grayscale.png");
+    let gray = insert_before_idat(&gray, b"gAMA", &100000u32.to_be_bytes());
+    let profile = png::synthesize_icc_profile(&gray).unwrap().unwrap();
+    assert_eq!(&profile[16..20], b"GRAY");
+    assert!(icc_tag(&profile, b"kTRC").is_some());
+    assert!(icc_tag(&profile, b"rXYZ").is_none());
+    assert!(png::embed_synthesized_icc(&gray).is_ok());
+}
+
+#[test]
+fn test_read_orientation_and_preserve() {
+    // JPEGのAPP1からEXIFのTIFFデータを取り出してeXIfとして埋め込む
+    let jpeg = load_test_image("jpeg/orientation/orientation_6.jpg");
+    let start = jpeg.windows(6).position(|w| w == b"Exif\0\0").unwrap();
+    let length = u16::from_be_bytes([jpeg[start - 2], jpeg[start - 1]]) as usize;
+    let tiff = &jpeg[start + 6..start - 2 + length];
+
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert_eq!(png::read_orientation(&data).unwrap(), None);
+    let data = insert_before_idat(&data, b"eXIf", tiff);
+    assert_eq!(
+        png::read_orientation(&data).unwrap(),
+        Some(web_image_meta::Orientation::Rotate90)
+    );
+    assert!(png::read_orientation(b"not a png").is_err());
+
+    // デフォルトではeXIfを削除する
+    let cleaned = png::clean_chunks(&data).unwrap();
+    assert_eq!(png::read_orientation(&cleaned).unwrap(), None);
+
+    // オリエンテーションのみの最小限のeXIfを作り直す
+    let options = png::CleanOptions {
+        preserve_orientation: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&data, &options).unwrap();
+    assert_eq!(
+        png::read_orientation(&cleaned).unwrap(),
+        Some(web_image_meta::Orientation::Rotate90)
+    );
+    let exif = png::chunks(&cleaned)
+        .unwrap()
+        .map(Result::unwrap)
+        .find(|chunk| &chunk.chunk_type == b"eXIf")
+        .unwrap();
+    assert!(exif.data.len() < tiff.len());
+    assert_eq!(
+        png::predict_clean_size(&data, &options).unwrap(),
+        cleaned.len()
+    );
+    assert!(CleanPreset::WebSafe.png_options().preserve_orientation);
+
+    // eXIfが複数ある場合は最初のもののみから作り直す
+    let jpeg = load_test_image("jpeg/orientation/orientation_3.jpg");
+    let start = jpeg.windows(6).position(|w| w == b"Exif\0\0").unwrap();
+    let length = u16::from_be_bytes([jpeg[start - 2], jpeg[start - 1]]) as usize;
+    let doubled = insert_before_idat(&data, b"eXIf", &jpeg[start + 6..start - 2 + length]);
+    let cleaned = png::clean_chunks_with_options(&doubled, &options).unwrap();
+    let exif_count = png::chunks(&cleaned)
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|chunk| &chunk.chunk_type == b"eXIf")
+        .count();
+    assert_eq!(exif_count, 1);
+    assert_eq!(
+        png::read_orientation(&cleaned).unwrap(),
+        Some(web_image_meta::Orientation::Rotate90)
+    );
+
+    // preserve_exifが優先され、元のeXIfをそのまま残す
+    let options = png::CleanOptions {
+        preserve_exif: true,
+        preserve_orientation: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&data, &options).unwrap();
+    assert!(cleaned.windows(tiff.len()).any(|window| window == tiff));
+}
+
+#[test]
+fn test_dsig_policy() {
+    let data = load_test_image("png/colortype/colortype_rgb.png");
+    assert!(png::find_dsig_pair(&data).unwrap().is_none());
+
+    // IHDRの直後とIENDの直前にdSIGを置く（内容は同じでも組として扱う）
+    let start_only = insert_chunk(&data, 33, b"dSIG", b"signature");
+    let iend = find_chunk_position(&start_only, b"IEND").unwrap();
+    let signed = insert_chunk(&start_only, iend, b"dSIG", b"signature");
+
+    let pair = png::find_dsig_pair(&signed).unwrap().unwrap();
+    assert_eq!(pair[0].offset, 33);
+    assert_eq!(pair[1].data, b"signature");
+    let count = png::chunks(&signed)
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|chunk| chunk.is_dsig())
+        .count();
+    assert_eq!(count, 2);
+
+    // デフォルトでは削除する
+    let cleaned = png::clean_chunks(&signed).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"dSIG"));
+
+    // 組を保持する（重複の削除の対象外）
+    let options = png::CleanOptions {
+        dsig: png::DsigPolicy::PreservePair,
+        remove_duplicates: true,
+        ..Default::default()
+    };
+    let cleaned = png::clean_chunks_with_options(&signed, &options).unwrap();
+    let pair = png::find_dsig_pair(&cleaned).unwrap().unwrap();
+    assert_eq!(pair[0].offset, 33);
+    assert_eq!(
+        png::predict_clean_size(&signed, &options).unwrap(),
+        cleaned.len()
+    );
+
+    // 組になっていないdSIGは保持しない
+    assert!(png::find_dsig_pair(&start_only).unwrap().is_none());
+    let cleaned = png::clean_chunks_with_options(&start_only, &options).unwrap();
+    assert!(!check_chunk_exists(&cleaned, b"dSIG"));
+
+    assert_eq!(
+        CleanPreset::Archival.png_options().dsig,
+        png::DsigPolicy::PreservePair
+    );
+}
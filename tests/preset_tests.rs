@@ -0,0 +1,232 @@
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use web_image_meta::{jpeg, png, CleanPreset};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// SOSより前のセグメントを(マーカー, ペイロード)として列挙
+fn list_segments(data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF && data[pos + 1] != 0xDA {
+        let size = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        segments.push((data[pos + 1], data[pos + 4..pos + 2 + size].to_vec()));
+        pos += 2 + size;
+    }
+    segments
+}
+
+/// PNGのチャンクタイプを列挙
+fn list_chunks(data: &[u8]) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        chunks.push(String::from_utf8_lossy(&data[pos + 4..pos + 8]).to_string());
+        pos += 12 + length;
+    }
+    chunks
+}
+
+/// IHDRの直後にチャンクを挿入
+fn insert_chunk_after_ihdr(data: &[u8], chunk_type: &[u8; 4], chunk_data: &[u8]) -> Vec<u8> {
+    let mut output = data[..33].to_vec();
+    output.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(chunk_data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(chunk_data);
+    output.extend_from_slice(&hasher.finalize().to_be_bytes());
+    output.extend_from_slice(&data[33..]);
+    output
+}
+
+/// JPEGのAPP2からICCプロファイルを取り出す
+fn extract_icc(data: &[u8]) -> Vec<u8> {
+    list_segments(data)
+        .into_iter()
+        .filter(|(marker, payload)| *marker == 0xE2 && payload.starts_with(b"ICC_PROFILE\0"))
+        .flat_map(|(_, payload)| payload[14..].to_vec())
+        .collect()
+}
+
+#[test]
+fn test_privacy_preset_removes_orientation() {
+    let data = load_test_image("jpeg/orientation/orientation_6.jpg");
+
+    let cleaned =
+        jpeg::clean_metadata_with_options(&data, &CleanPreset::Privacy.jpeg_options()).unwrap();
+    assert!(list_segments(&cleaned)
+        .iter()
+        .all(|(marker, _)| *marker != 0xE1 && *marker != 0xFE));
+
+    // デフォルトではオリエンテーションが保持される
+    let cleaned = jpeg::clean_metadata(&data).unwrap();
+    assert!(list_segments(&cleaned)
+        .iter()
+        .any(|(marker, _)| *marker == 0xE1));
+}
+
+/// C2PAのJUMBFを1つのAPP11セグメントとしてSOIの直後に挿入
+fn embed_c2pa(data: &[u8]) -> Vec<u8> {
+    let mut jumd = b"jumd".to_vec();
+    jumd.extend_from_slice(b"c2pa\x00\x11\x00\x10\x80\x00\x00\xAA\x00\x38\x9B\x71\x03c2pa\0");
+    let mut jumbf = ((jumd.len() + 12) as u32).to_be_bytes().to_vec();
+    jumbf.extend_from_slice(b"jumb");
+    jumbf.extend_from_slice(&((jumd.len() + 4) as u32).to_be_bytes());
+    jumbf.extend_from_slice(&jumd);
+
+    let mut payload = b"JP\0\x01\0\0\0\x01".to_vec();
+    payload.extend_from_slice(&jumbf);
+    let mut output = data[..2].to_vec();
+    output.extend_from_slice(&[0xFF, 0xEB]);
+    output.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    output.extend_from_slice(&payload);
+    output.extend_from_slice(&data[2..]);
+    output
+}
+
+#[test]
+fn test_privacy_with_provenance_preset_keeps_c2pa() {
+    let data = embed_c2pa(&load_test_image("jpeg/metadata/metadata_full_exif.jpg"));
+    let options = CleanPreset::PrivacyWithProvenance.jpeg_options();
+    let (cleaned, report) = jpeg::clean_metadata_with_report(&data, &options).unwrap();
+
+    let segments = list_segments(&cleaned);
+    assert!(segments.iter().all(|(marker, _)| *marker != 0xE1));
+    assert_eq!(
+        jpeg::read_c2pa_raw(&cleaned).unwrap(),
+        jpeg::read_c2pa_raw(&data).unwrap()
+    );
+    assert!(jpeg::read_c2pa_raw(&cleaned).unwrap().is_some());
+
+    // 他のメタデータを削除したため無効化が報告される
+    assert!(report.c2pa_present);
+    assert!(report.c2pa_invalidated);
+
+    // Privacyプリセットではマニフェストも削除される
+    let cleaned =
+        jpeg::clean_metadata_with_options(&data, &CleanPreset::Privacy.jpeg_options()).unwrap();
+    assert_eq!(jpeg::read_c2pa_raw(&cleaned).unwrap(), None);
+}
+
+#[test]
+fn test_websafe_preset_removes_srgb_icc_only() {
+    let options = CleanPreset::WebSafe.jpeg_options();
+
+    let srgb = load_test_image("jpeg/icc/icc_srgb.jpg");
+    let cleaned = jpeg::clean_metadata_with_options(&srgb, &options).unwrap();
+    assert!(extract_icc(&cleaned).is_empty());
+
+    // sRGB以外のプロファイルは保持
+    let p3 = load_test_image("jpeg/icc/icc_applep3.jpg");
+    let cleaned = jpeg::clean_metadata_with_options(&p3, &options).unwrap();
+    assert_eq!(extract_icc(&cleaned), extract_icc(&p3));
+}
+
+#[test]
+fn test_websafe_preset_replaces_png_iccp() {
+    let profile = extract_icc(&load_test_image("jpeg/icc/icc_srgb.jpg"));
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&profile).unwrap();
+    let mut iccp = b"sRGB IEC61966-2.1\0\0".to_vec();
+    iccp.extend_from_slice(&encoder.finish().unwrap());
+
+    let data = insert_chunk_after_ihdr(
+        &load_test_image("png/metadata/metadata_none.png"),
+        b"iCCP",
+        &iccp,
+    );
+
+    let cleaned = png::clean_chunks_with_options(&data, &CleanPreset::WebSafe.png_options())
+        .expect("Failed to clean PNG");
+    let chunks = list_chunks(&cleaned);
+    assert!(chunks.contains(&"sRGB".to_string()));
+    assert!(!chunks.contains(&"iCCP".to_string()));
+
+    // デフォルトではiCCPのまま保持
+    let cleaned = png::clean_chunks(&data).unwrap();
+    assert!(list_chunks(&cleaned).contains(&"iCCP".to_string()));
+}
+
+#[test]
+fn test_archival_preset_keeps_exif_and_xmp() {
+    let options = CleanPreset::Archival.jpeg_options();
+
+    let data = load_test_image("jpeg/critical/critical_xmp_complex.jpg");
+    let cleaned = jpeg::clean_metadata_with_options(&data, &options).unwrap();
+    let original = list_segments(&data);
+    let segments = list_segments(&cleaned);
+
+    // EXIFとXMPはそのまま、IPTC（APP13）とコメントは削除
+    let app1 = |segments: &[(u8, Vec<u8>)]| -> Vec<Vec<u8>> {
+        segments
+            .iter()
+            .filter(|(marker, _)| *marker == 0xE1)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    };
+    assert_eq!(app1(&segments), app1(&original));
+    assert!(segments
+        .iter()
+        .all(|(marker, _)| *marker != 0xED && *marker != 0xFE));
+}
+
+#[test]
+fn test_archival_preset_removes_thumbnail() {
+    let data = load_test_image("jpeg/thumbnail/thumbnail_embedded.jpg");
+    let cleaned =
+        jpeg::clean_metadata_with_options(&data, &CleanPreset::Archival.jpeg_options()).unwrap();
+    assert!(cleaned.len() < data.len());
+
+    let original = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(&data))
+        .unwrap();
+    let reduced = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(&cleaned))
+        .unwrap();
+
+    assert!(original.fields().any(|f| f.ifd_num == exif::In::THUMBNAIL));
+    assert!(reduced.fields().all(|f| f.ifd_num == exif::In::PRIMARY));
+    assert_eq!(
+        reduced.fields().count(),
+        original
+            .fields()
+            .filter(|f| f.ifd_num == exif::In::PRIMARY)
+            .count()
+    );
+}
+
+#[test]
+fn test_archival_preset_png() {
+    let data = load_test_image("png/metadata/metadata_compressed.png");
+    let cleaned = png::clean_chunks_with_options(&data, &CleanPreset::Archival.png_options())
+        .expect("Failed to clean PNG");
+
+    assert_eq!(
+        png::read_text_chunks(&cleaned).unwrap(),
+        png::read_text_chunks(&data).unwrap()
+    );
+    assert!(!list_chunks(&cleaned).contains(&"bKGD".to_string()));
+
+    // 重複した付随チャンクは1つにまとめられる
+    let text = {
+        let with_text = png::add_text_chunk(&data, "Dup", "same").unwrap();
+        png::add_text_chunk(&with_text, "Dup", "same").unwrap()
+    };
+    let cleaned = png::clean_chunks_with_options(&text, &CleanPreset::Archival.png_options())
+        .expect("Failed to clean PNG");
+    let dups = png::read_text_chunks(&cleaned)
+        .unwrap()
+        .into_iter()
+        .filter(|chunk| chunk.keyword == "Dup")
+        .count();
+    assert_eq!(dups, 1);
+}
@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, png, Processor};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_processor_matches_functions() {
+    let jpeg_data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let png_data = load_test_image("png/metadata/metadata_text.png");
+
+    let mut processor = Processor::new();
+    processor.jpeg_options.preserve_xmp = true;
+    processor.png_options.preserve_text = true;
+
+    let expected = jpeg::clean_metadata_with_options(&jpeg_data, &processor.jpeg_options).unwrap();
+    assert_eq!(processor.clean(&jpeg_data).unwrap(), expected.as_slice());
+    assert_eq!(
+        processor.clean_jpeg(&jpeg_data).unwrap(),
+        expected.as_slice()
+    );
+
+    let expected = png::clean_chunks_with_options(&png_data, &processor.png_options).unwrap();
+    assert_eq!(processor.clean(&png_data).unwrap(), expected.as_slice());
+
+    let (_, report) = processor.clean_jpeg_with_report(&jpeg_data).unwrap();
+    assert!(!report.c2pa_present);
+
+    assert!(processor.clean(b"not an image").is_err());
+    assert!(processor.clean_png(&jpeg_data).is_err());
+}
+
+#[test]
+fn test_processor_reuses_buffer() {
+    let data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let mut processor = Processor::new();
+
+    let first = processor.clean(&data).unwrap().as_ptr();
+    let second = processor.clean(&data).unwrap().as_ptr();
+    assert_eq!(first, second);
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, png, read_provenance, stamp_provenance, ProvenanceInfo};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+fn sample() -> ProvenanceInfo {
+    ProvenanceInfo {
+        source_system: "acme-cms".to_string(),
+        asset_id: "asset-12345".to_string(),
+        pipeline_version: "2.4.1".to_string(),
+        timestamp: "2024-05-01T12:00:00Z".to_string(),
+    }
+}
+
+#[test]
+fn test_stamp_provenance_jpeg() {
+    let data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    assert_eq!(read_provenance(&data).unwrap(), None);
+
+    let stamped = stamp_provenance(&data, &sample()).unwrap();
+    assert_eq!(read_provenance(&stamped).unwrap(), Some(sample()));
+    let packet = jpeg::read_xmp(&stamped).unwrap().unwrap();
+    assert!(packet.contains("wim:AssetID=\"asset-12345\""));
+    // 既存のXMPプロパティは保持される
+    let original = jpeg::read_xmp(&data).unwrap().unwrap();
+    assert!(packet.len() > original.len());
+
+    // 置き換えと空の項目の削除
+    let updated = ProvenanceInfo {
+        pipeline_version: "2.5.0".to_string(),
+        timestamp: String::new(),
+        ..sample()
+    };
+    let restamped = stamp_provenance(&stamped, &updated).unwrap();
+    assert_eq!(read_provenance(&restamped).unwrap(), Some(updated));
+    assert!(!jpeg::read_xmp(&restamped)
+        .unwrap()
+        .unwrap()
+        .contains("wim:Timestamp"));
+}
+
+#[test]
+fn test_stamp_provenance_png() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+    assert_eq!(read_provenance(&data).unwrap(), None);
+    let text_count = png::read_text_chunks(&data).unwrap().len();
+
+    let stamped = stamp_provenance(&data, &sample()).unwrap();
+    assert_eq!(read_provenance(&stamped).unwrap(), Some(sample()));
+    assert_eq!(
+        png::read_text_chunks(&stamped).unwrap().len(),
+        text_count + 4
+    );
+
+    // 書き直しても重複しない
+    let restamped = stamp_provenance(&stamped, &sample()).unwrap();
+    assert_eq!(restamped, stamped);
+
+    // すべて空にすると削除される
+    let cleared = stamp_provenance(&stamped, &ProvenanceInfo::default()).unwrap();
+    assert_eq!(cleared, data);
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{map_regions, size_breakdown, RegionKind};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// 範囲が先頭から隙間なく並び、末尾まで覆っているか確認する
+fn assert_contiguous(data: &[u8], regions: &[web_image_meta::Region]) {
+    let mut pos = 0;
+    for region in regions {
+        assert_eq!(region.range.start, pos);
+        pos = region.range.end;
+    }
+    assert_eq!(pos, data.len());
+}
+
+#[test]
+fn test_map_regions_jpeg() {
+    let mut data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    data.extend_from_slice(b"trailer");
+
+    let regions = map_regions(&data).unwrap();
+    assert_contiguous(&data, &regions);
+    assert_eq!(regions[0].kind, RegionKind::Signature);
+    assert_eq!(regions[0].range, 0..2);
+
+    // EXIF（APP1）はメタデータ
+    let exif = regions
+        .iter()
+        .find(|region| data[region.range.start + 1] == 0xE1)
+        .unwrap();
+    assert_eq!(exif.kind, RegionKind::Metadata);
+
+    // 最後のスキャンデータはEOIで終わり、その後は末尾データ
+    let last = &regions[regions.len() - 1];
+    assert_eq!(last.kind, RegionKind::Trailing);
+    assert_eq!(&data[last.range.clone()], b"trailer");
+    let scan = &regions[regions.len() - 2];
+    assert_eq!(scan.kind, RegionKind::ImageData);
+    assert_eq!(&data[scan.range.end - 2..scan.range.end], [0xFF, 0xD9]);
+
+    // SOSより前のフィルバイトはどの範囲にも属さないためエラー
+    let mut filled = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    filled.splice(2..2, [0xFF, 0xFF, 0xFF]);
+    assert!(map_regions(&filled).is_err());
+}
+
+#[test]
+fn test_map_regions_png() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+
+    let regions = map_regions(&data).unwrap();
+    assert_contiguous(&data, &regions);
+    assert_eq!(regions[0].kind, RegionKind::Signature);
+    for region in &regions[1..] {
+        let chunk_type = &data[region.range.start + 4..region.range.start + 8];
+        let expected = match chunk_type {
+            b"IHDR" | b"PLTE" | b"tRNS" | b"IDAT" | b"IEND" => RegionKind::ImageData,
+            _ => RegionKind::Metadata,
+        };
+        assert_eq!(region.kind, expected);
+    }
+    assert!(regions.iter().any(|r| r.kind == RegionKind::Metadata));
+}
+
+#[test]
+fn test_map_regions_invalid() {
+    assert!(map_regions(b"GIF89a").is_err());
+    let data = load_test_image("png/metadata/metadata_text.png");
+    assert!(map_regions(&data[..data.len() - 20]).is_err());
+}
+
+#[test]
+fn test_size_breakdown() {
+    let data = load_test_image("jpeg/metadata/metadata_basic_exif.jpg");
+    let breakdown = size_breakdown(&data).unwrap();
+
+    // 大きい順に並び、合計はファイルサイズと一致する
+    assert!(breakdown
+        .items
+        .windows(2)
+        .all(|pair| pair[0].range.len() >= pair[1].range.len()));
+    let sum: usize = breakdown.items.iter().map(|item| item.range.len()).sum();
+    assert_eq!(sum, data.len());
+    assert_eq!(
+        breakdown.total(RegionKind::Signature)
+            + breakdown.total(RegionKind::Metadata)
+            + breakdown.total(RegionKind::ImageData),
+        data.len()
+    );
+
+    // セグメントごとの名前を持つ
+    assert_eq!(breakdown.items[0].name, "SOS");
+    let exif = breakdown
+        .items
+        .iter()
+        .find(|item| item.name == "APP1 (EXIF)")
+        .unwrap();
+    assert_eq!(exif.kind, RegionKind::Metadata);
+    assert!(breakdown.items.iter().any(|item| item.name == "DQT"));
+
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let breakdown = size_breakdown(&data).unwrap();
+    assert_eq!(breakdown.items.len(), map_regions(&data).unwrap().len());
+    assert!(breakdown.items.iter().any(|item| item.name == "tEXt"));
+    assert_eq!(breakdown.items.last().unwrap().name, "Signature");
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{
+    jpeg, read_resolution, set_dpi, transfer_resolution, write_resolution, Resolution,
+    ResolutionUnit,
+};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// DPIに換算した値を小数点以下を丸めて返す
+fn rounded_dpi(resolution: &Resolution) -> (f64, f64) {
+    let (x, y) = resolution.to_dpi().unwrap();
+    (x.round(), y.round())
+}
+
+#[test]
+fn test_resolution_conversions() {
+    // 72 DPIは2835ピクセル/メートル
+    assert_eq!(Resolution::from_dpi(72.0, 72.0).to_phys(), (2835, 2835, 1));
+    let phys = Resolution::from_phys(2835, 2835, 1).unwrap();
+    assert_eq!(phys.unit, ResolutionUnit::Meter);
+    assert_eq!(rounded_dpi(&phys), (72.0, 72.0));
+    // 整数のDPIに換算できるメートル単位はJFIFではインチ、それ以外はセンチメートルにする
+    assert_eq!(phys.to_jfif(), (1, 72, 72));
+    assert_eq!(
+        Resolution::from_phys(2800, 2800, 1).unwrap().to_jfif(),
+        (2, 28, 28)
+    );
+    assert_eq!(
+        Resolution::from_jfif(2, 28, 28).unwrap().to_phys(),
+        (2800, 2800, 1)
+    );
+
+    // 単位のない縦横比はそのまま
+    let aspect = Resolution::from_phys(2, 1, 0).unwrap();
+    assert_eq!(aspect.to_dpi(), None);
+    assert_eq!(aspect.to_jfif(), (0, 2, 1));
+
+    assert_eq!(Resolution::from_jfif(3, 72, 72), None);
+    assert_eq!(Resolution::from_phys(0, 72, 1), None);
+}
+
+#[test]
+fn test_read_resolution() {
+    let jfif = read_resolution(&load_test_image("jpeg/dpi/dpi_jfif_200dpi.jpg"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(jfif, Resolution::from_dpi(200.0, 200.0));
+
+    let png_data = load_test_image("png/colortype/colortype_rgb.png");
+    assert!(read_resolution(&png_data).is_ok());
+    assert!(read_resolution(b"not an image").is_err());
+}
+
+#[test]
+fn test_transfer_resolution_between_formats() {
+    let jpeg_data = load_test_image("jpeg/dpi/dpi_jfif_200dpi.jpg");
+    let png_data = load_test_image("png/colortype/colortype_rgb.png");
+
+    // JPEGのJFIFからPNGのpHYsへ
+    let png_out = transfer_resolution(&jpeg_data, &png_data).unwrap();
+    let phys = read_resolution(&png_out).unwrap().unwrap();
+    assert_eq!(phys.unit, ResolutionUnit::Meter);
+    assert_eq!((phys.x, phys.y), (7874.0, 7874.0));
+    assert_eq!(rounded_dpi(&phys), (200.0, 200.0));
+    assert!(web_image_meta::png::check_conformance(&png_out).is_ok());
+
+    // 書き込みを繰り返してもpHYsは1つだけ
+    let rewritten = write_resolution(&png_out, &Resolution::from_dpi(96.0, 96.0)).unwrap();
+    assert_eq!(rewritten.len(), png_out.len());
+    assert_eq!(
+        rounded_dpi(&read_resolution(&rewritten).unwrap().unwrap()),
+        (96.0, 96.0)
+    );
+
+    // PNGのpHYsからJPEGのJFIFへ
+    let jpeg_out = transfer_resolution(&rewritten, &jpeg_data).unwrap();
+    let jfif = read_resolution(&jpeg_out).unwrap().unwrap();
+    assert_eq!(jfif, Resolution::from_dpi(96.0, 96.0));
+    assert_eq!(jpeg_out.len(), jpeg_data.len());
+}
+
+#[test]
+fn test_write_resolution_updates_exif() {
+    use web_image_meta::exif::{self, IfdKind};
+
+    // JFIFとEXIFの両方を書き換える
+    let data = load_test_image("jpeg/dpi/dpi_exif_72dpi.jpg");
+    let output = write_resolution(&data, &Resolution::from_dpi(300.0, 300.0)).unwrap();
+    assert_eq!(
+        read_resolution(&output).unwrap(),
+        Some(Resolution::from_dpi(300.0, 300.0))
+    );
+    let resolution_tags: Vec<_> = exif::entries_raw(&output)
+        .unwrap()
+        .into_iter()
+        .filter(|entry| entry.ifd == IfdKind::Ifd0 && matches!(entry.tag, 0x011A | 0x011B | 0x0128))
+        .collect();
+    assert_eq!(resolution_tags.len(), 3);
+    let x = resolution_tags.iter().find(|e| e.tag == 0x011A).unwrap();
+    let value = &output[x.value_offset..x.value_offset + 8];
+    let read_u32 = |bytes: &[u8]| {
+        if output[..x.value_offset].windows(2).any(|w| w == b"MM") {
+            u32::from_be_bytes(bytes.try_into().unwrap())
+        } else {
+            u32::from_le_bytes(bytes.try_into().unwrap())
+        }
+    };
+    assert_eq!(read_u32(&value[0..4]) / read_u32(&value[4..8]), 300);
+
+    // JFIFもEXIFもない画像にはEXIFを追加する
+    let cleaned = jpeg::clean_metadata_with_options(
+        &data,
+        &jpeg::CleanOptions {
+            preserve_orientation: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(write_resolution(&cleaned, &Resolution::from_dpi(150.0, 150.0)).is_ok());
+
+    assert!(write_resolution(&data, &Resolution::from_dpi(0.0, 72.0)).is_err());
+}
+
+#[test]
+fn test_set_dpi() {
+    // PNGはメートル単位、JPEGはインチ単位で書き込む
+    let png_out = set_dpi(&load_test_image("png/colortype/colortype_rgb.png"), 72.0).unwrap();
+    assert_eq!(
+        read_resolution(&png_out).unwrap(),
+        Resolution::from_phys(2835, 2835, 1)
+    );
+
+    let jpeg_data = load_test_image("jpeg/dpi/dpi_exif_200dpi.jpg");
+    let jpeg_out = set_dpi(&jpeg_data, 144.0).unwrap();
+    assert_eq!(
+        read_resolution(&jpeg_out).unwrap(),
+        Some(Resolution::from_dpi(144.0, 144.0))
+    );
+
+    assert!(set_dpi(&jpeg_data, f64::NAN).is_err());
+    assert!(set_dpi(b"not an image", 72.0).is_err());
+}
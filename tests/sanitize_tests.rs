@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, png, sanitize, Error, SanitizeOptions};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_sanitize_jpeg_truncates_and_cleans() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let mut polyglot = data.clone();
+    polyglot.extend_from_slice(b"PK\x03\x04hidden archive");
+
+    let (output, report) =
+        sanitize(&polyglot, &SanitizeOptions::default()).expect("Failed to sanitize");
+
+    assert_eq!(output, jpeg::clean_metadata(&data).unwrap());
+    assert_eq!(report.mime_type, "image/jpeg");
+    assert_eq!(report.trailing_bytes_removed, polyglot.len() - data.len());
+    assert_eq!(report.input_size, polyglot.len());
+    assert_eq!(report.output_size, output.len());
+    assert!(report.width > 0 && report.height > 0);
+    assert!(!report.findings.is_empty());
+}
+
+#[test]
+fn test_sanitize_png_cleans_text_chunks() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+
+    let (output, report) = sanitize(&data, &SanitizeOptions::default()).unwrap();
+
+    assert_eq!(report.mime_type, "image/png");
+    assert_eq!(report.trailing_bytes_removed, 0);
+    assert!(png::read_text_chunks(&output).unwrap().is_empty());
+}
+
+#[test]
+fn test_sanitize_jpeg_with_concatenated_image() {
+    // MPFのプレビューや深度マップのように、EOIの後に別のJPEGが連結された画像
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let mut concatenated = data.clone();
+    concatenated.extend_from_slice(&load_test_image("jpeg/quality/quality_20.jpg"));
+
+    let (output, report) =
+        sanitize(&concatenated, &SanitizeOptions::default()).expect("Failed to sanitize");
+    assert_eq!(output, jpeg::clean_metadata(&data).unwrap());
+    assert_eq!(
+        report.trailing_bytes_removed,
+        concatenated.len() - data.len()
+    );
+}
+
+#[test]
+fn test_sanitize_rejects_trailing_data_when_not_truncating() {
+    let mut data = load_test_image("png/metadata/metadata_none.png");
+    data.extend_from_slice(b"<html>payload</html>");
+
+    let options = SanitizeOptions {
+        truncate_trailing_data: false,
+        ..Default::default()
+    };
+    assert!(matches!(
+        sanitize(&data, &options),
+        Err(Error::InvalidFormat(_))
+    ));
+}
+
+#[test]
+fn test_sanitize_resource_limits() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+
+    let options = SanitizeOptions {
+        max_file_size: 100,
+        ..Default::default()
+    };
+    assert!(matches!(
+        sanitize(&data, &options),
+        Err(Error::LimitExceeded(_))
+    ));
+
+    let options = SanitizeOptions {
+        max_dimension: 8,
+        ..Default::default()
+    };
+    assert!(matches!(
+        sanitize(&data, &options),
+        Err(Error::LimitExceeded(_))
+    ));
+
+    let options = SanitizeOptions {
+        max_pixels: 64,
+        ..Default::default()
+    };
+    assert!(matches!(
+        sanitize(&data, &options),
+        Err(Error::LimitExceeded(_))
+    ));
+}
+
+#[test]
+fn test_sanitize_rejects_duplicate_critical_chunks() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+    let mut doubled = data.clone();
+    doubled.extend_from_slice(&data[8..]);
+
+    assert!(matches!(
+        sanitize(&doubled, &SanitizeOptions::default()),
+        Err(Error::InvalidFormat(_))
+    ));
+    assert!(sanitize(b"not an image", &SanitizeOptions::default()).is_err());
+}
+
+#[test]
+fn test_sanitize_without_cleaning_keeps_metadata() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let options = SanitizeOptions {
+        clean_metadata: false,
+        ..Default::default()
+    };
+
+    let (output, _) = sanitize(&data, &options).unwrap();
+    assert_eq!(output, data);
+}
+
+#[test]
+fn test_sanitize_strips_kept_payloads_with_signatures() {
+    let data = load_test_image("jpeg/icc/icc_srgb.jpg");
+    let icc = data
+        .windows(12)
+        .position(|window| window == b"ICC_PROFILE\0")
+        .unwrap();
+    let segment_length = u16::from_be_bytes([data[icc - 2], data[icc - 1]]) as usize;
+    let segment_end = icc - 2 + segment_length;
+
+    // ZIPのシグネチャを含むICCプロファイルはセグメントごと削除する
+    let mut patched = data.clone();
+    patched[segment_end - 8..segment_end - 4].copy_from_slice(b"PK\x03\x04");
+
+    let (output, _) = sanitize(&patched, &SanitizeOptions::default()).unwrap();
+    assert!(!output.windows(4).any(|window| window == b"PK\x03\x04"));
+    assert!(!output.windows(12).any(|window| window == b"ICC_PROFILE\0"));
+
+    // シグネチャを含まないICCプロファイルは残す
+    let (output, _) = sanitize(&data, &SanitizeOptions::default()).unwrap();
+    assert!(output.windows(12).any(|window| window == b"ICC_PROFILE\0"));
+
+    // 残したセグメント以外のシグネチャは引き続き拒否する
+    let mut commented = data[..2].to_vec();
+    commented.extend_from_slice(&[0xFF, 0xFE, 0x00, 0x07]);
+    commented.extend_from_slice(b"<?php");
+    commented.extend_from_slice(&data[2..]);
+    let options = SanitizeOptions {
+        clean_metadata: false,
+        ..Default::default()
+    };
+    assert!(matches!(
+        sanitize(&commented, &options),
+        Err(Error::InvalidFormat(_))
+    ));
+}
@@ -0,0 +1,247 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use web_image_meta::{scan_security, SecurityFinding};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+// ヘルパー関数：ディレクトリ以下のファイルを再帰的に列挙
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(list_files(&path));
+        } else {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+// ヘルパー関数：IENDの直前にチャンクを挿入
+fn insert_chunk_before_iend(data: &[u8], chunk_type: &[u8; 4], chunk_data: &[u8]) -> Vec<u8> {
+    let iend_pos = data.len() - 12;
+    assert_eq!(&data[iend_pos + 4..iend_pos + 8], b"IEND");
+
+    let mut output = data[..iend_pos].to_vec();
+    output.extend_from_slice(&(chunk_data.len() as u32).to_be_bytes());
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(chunk_data);
+    let crc = crc32fast::hash(&[chunk_type.as_slice(), chunk_data].concat());
+    output.extend_from_slice(&crc.to_be_bytes());
+    output.extend_from_slice(&data[iend_pos..]);
+    output
+}
+
+#[test]
+fn test_scan_security_sample_files_are_clean() {
+    for dir in ["jpeg", "png"] {
+        for path in list_files(&Path::new("tests/test_data").join(dir)) {
+            let data = fs::read(&path).unwrap();
+            let report = scan_security(&data);
+            assert!(
+                report.is_clean(),
+                "{} should be clean: {:?}",
+                path.display(),
+                report.findings
+            );
+        }
+    }
+}
+
+#[test]
+fn test_scan_security_jpeg_appended_zip() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let mut polyglot = data.clone();
+    polyglot.extend_from_slice(b"PK\x03\x04\x14\x00\x00\x00payload.php");
+
+    let report = scan_security(&polyglot);
+    assert!(report.findings.contains(&SecurityFinding::TrailingData {
+        offset: data.len(),
+        length: polyglot.len() - data.len(),
+    }));
+    assert!(report
+        .findings
+        .contains(&SecurityFinding::EmbeddedSignature {
+            kind: "ZIP",
+            offset: data.len(),
+        }));
+}
+
+#[test]
+fn test_scan_security_jpeg_html_in_comment() {
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let with_comment =
+        web_image_meta::jpeg::write_comment(&data, "<SCRIPT>alert(1)</SCRIPT>").unwrap();
+
+    let report = scan_security(&with_comment);
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| matches!(f, SecurityFinding::EmbeddedSignature { kind: "HTML", .. })));
+}
+
+#[test]
+fn test_scan_security_png_duplicate_critical_chunks() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+
+    // IHDRとIENDを含むPNG本体をもう一度付加する
+    let mut doubled = data.clone();
+    doubled.extend_from_slice(&data[8..]);
+
+    let report = scan_security(&doubled);
+    assert!(report
+        .findings
+        .contains(&SecurityFinding::DuplicateCritical {
+            name: "IHDR".to_string(),
+            count: 2,
+        }));
+    assert!(report
+        .findings
+        .contains(&SecurityFinding::DuplicateCritical {
+            name: "IEND".to_string(),
+            count: 2,
+        }));
+    assert!(report.findings.contains(&SecurityFinding::TrailingData {
+        offset: data.len(),
+        length: data.len() - 8,
+    }));
+}
+
+#[test]
+fn test_scan_security_png_html_in_text_chunk() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+    let tampered = insert_chunk_before_iend(
+        &data,
+        b"tEXt",
+        b"Comment\0<!DOCTYPE html><html><body>hi</body></html>",
+    );
+
+    let report = scan_security(&tampered);
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| matches!(f, SecurityFinding::EmbeddedSignature { kind: "HTML", .. })));
+    assert!(!report
+        .findings
+        .iter()
+        .any(|f| matches!(f, SecurityFinding::TrailingData { .. })));
+}
+
+#[test]
+fn test_scan_security_png_oversized_chunk() {
+    let data = load_test_image("png/metadata/metadata_none.png");
+    let large = vec![b'a'; web_image_meta::OVERSIZED_METADATA_THRESHOLD + 1];
+    let tampered = insert_chunk_before_iend(&data, b"zzZz", &large);
+
+    let report = scan_security(&tampered);
+    assert!(report
+        .findings
+        .contains(&SecurityFinding::OversizedMetadata {
+            name: "zzZz".to_string(),
+            length: large.len(),
+        }));
+}
+
+#[test]
+fn test_scan_security_unrecognized_and_truncated() {
+    let report = scan_security(b"GIF89a not supported");
+    assert_eq!(report.findings, vec![SecurityFinding::UnrecognizedFormat]);
+
+    // EOIのないJPEG
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let truncated = &data[..data.len() - 2];
+    let report = scan_security(truncated);
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| matches!(f, SecurityFinding::MalformedStructure { .. })));
+}
+
+#[test]
+fn test_scan_security_jpeg_duplicate_soi_and_overlapping_icc() {
+    // EOIの後に連結されたJPEG（MPFの副画像など）は重複ではなく末尾データ
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let mut concatenated = data.clone();
+    concatenated.extend_from_slice(&data);
+
+    let report = scan_security(&concatenated);
+    assert!(report.findings.contains(&SecurityFinding::TrailingData {
+        offset: data.len(),
+        length: data.len(),
+    }));
+    assert!(!report
+        .findings
+        .iter()
+        .any(|f| matches!(f, SecurityFinding::DuplicateCritical { .. })));
+
+    // 1番目のICCセグメントを内容を変えて複製する
+    let data = load_test_image("jpeg/icc/icc_srgb.jpg");
+    let icc_pos = data
+        .windows(16)
+        .position(|w| w[..2] == [0xFF, 0xE2] && &w[4..] == b"ICC_PROFILE\0")
+        .unwrap();
+    let size = u16::from_be_bytes([data[icc_pos + 2], data[icc_pos + 3]]) as usize;
+    let mut segment = data[icc_pos..icc_pos + 2 + size].to_vec();
+    let last = segment.len() - 1;
+    segment[last] ^= 0xFF;
+
+    let mut tampered = data[..icc_pos].to_vec();
+    tampered.extend_from_slice(&segment);
+    tampered.extend_from_slice(&data[icc_pos..]);
+
+    let report = scan_security(&tampered);
+    assert!(report
+        .findings
+        .contains(&SecurityFinding::OverlappingSegments {
+            name: "ICC_PROFILE".to_string(),
+            sequence: segment[16] as u32,
+        }));
+
+    // 同じ内容の複製は曖昧ではない
+    let mut duplicated = data[..icc_pos].to_vec();
+    duplicated.extend_from_slice(&data[icc_pos..icc_pos + 2 + size]);
+    duplicated.extend_from_slice(&data[icc_pos..]);
+    assert!(!scan_security(&duplicated)
+        .findings
+        .iter()
+        .any(|f| matches!(f, SecurityFinding::OverlappingSegments { .. })));
+}
+
+#[test]
+fn test_scan_security_jumbf_sequence_uses_full_u32() {
+    // 下位16ビットが同じで上位が異なる連番は別のセグメント
+    let jumbf = |sequence: u32, content: &[u8]| {
+        let mut payload = b"JP".to_vec();
+        payload.extend_from_slice(&1u16.to_be_bytes());
+        payload.extend_from_slice(&sequence.to_be_bytes());
+        payload.extend_from_slice(content);
+        let mut segment = vec![0xFF, 0xEB];
+        segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        segment.extend_from_slice(&payload);
+        segment
+    };
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    let build = |second: u32| {
+        [
+            &data[..2],
+            &jumbf(1, b"first")[..],
+            &jumbf(second, b"second")[..],
+            &data[2..],
+        ]
+        .concat()
+    };
+
+    let overlapping = |data: &[u8]| {
+        scan_security(data)
+            .findings
+            .iter()
+            .any(|f| matches!(f, SecurityFinding::OverlappingSegments { .. }))
+    };
+    assert!(!overlapping(&build(0x0001_0001)));
+    assert!(overlapping(&build(1)));
+}
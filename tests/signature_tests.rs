@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, png, verify_signature, write_signature, SignOptions, SignedMetadata};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+const KEY: &[u8] = b"secret key";
+
+#[test]
+fn test_signature_jpeg() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    assert!(!verify_signature(&data, KEY).unwrap());
+
+    let signed = write_signature(&data, KEY, &SignOptions::default()).unwrap();
+    assert!(verify_signature(&signed, KEY).unwrap());
+    assert!(!verify_signature(&signed, b"other key").unwrap());
+    let packet = jpeg::read_xmp(&signed).unwrap().unwrap();
+    assert!(packet.contains("wim:Signature=\"hmac-sha256::"));
+
+    // メタデータの変更は署名に影響しない
+    let commented = jpeg::write_comment(&signed, "edited").unwrap();
+    assert!(verify_signature(&commented, KEY).unwrap());
+
+    // 画像データを改ざんすると一致しない
+    let mut tampered = signed.clone();
+    let index = tampered.len() - 100;
+    tampered[index] ^= 0x01;
+    assert!(!verify_signature(&tampered, KEY).unwrap());
+}
+
+#[test]
+fn test_signature_jpeg_with_metadata() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let options = SignOptions {
+        metadata: vec![SignedMetadata::Exif, SignedMetadata::Icc],
+    };
+    let signed = write_signature(&data, KEY, &options).unwrap();
+    assert!(verify_signature(&signed, KEY).unwrap());
+    let packet = jpeg::read_xmp(&signed).unwrap().unwrap();
+    assert!(packet.contains("hmac-sha256:exif,icc:"));
+
+    // 署名に含めたEXIFを変更すると一致しない
+    let edited = jpeg::set_gps(&signed, 35.0, 139.0, None).unwrap();
+    assert!(!verify_signature(&edited, KEY).unwrap());
+
+    // EXIFを含めない署名では一致する
+    let signed = write_signature(&data, KEY, &SignOptions::default()).unwrap();
+    let edited = jpeg::set_gps(&signed, 35.0, 139.0, None).unwrap();
+    assert!(verify_signature(&edited, KEY).unwrap());
+}
+
+#[test]
+fn test_signature_png() {
+    let data = load_test_image("png/metadata/metadata_text.png");
+    assert!(!verify_signature(&data, KEY).unwrap());
+
+    let signed = write_signature(&data, KEY, &SignOptions::default()).unwrap();
+    assert!(verify_signature(&signed, KEY).unwrap());
+    assert!(!verify_signature(&signed, b"other key").unwrap());
+
+    // 署名し直すと既存の署名を置き換える
+    let resigned = write_signature(&signed, b"new key", &SignOptions::default()).unwrap();
+    assert_eq!(resigned.len(), signed.len());
+    assert!(verify_signature(&resigned, b"new key").unwrap());
+    assert!(!verify_signature(&resigned, KEY).unwrap());
+
+    // テキストチャンクの追加は署名に影響しない
+    let with_text = png::add_text_chunk(&signed, "Comment", "edited").unwrap();
+    assert!(verify_signature(&with_text, KEY).unwrap());
+}
@@ -0,0 +1,121 @@
+#![cfg(feature = "tokio")]
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+use web_image_meta::stream::{CleanReader, StreamOptions};
+use web_image_meta::{jpeg, png, Error};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+async fn read_all(mut reader: CleanReader<&[u8]>) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    reader.read_to_end(&mut output).await?;
+    Ok(output)
+}
+
+#[tokio::test]
+async fn test_clean_reader_matches_functions() {
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let output = read_all(CleanReader::new(data.as_slice())).await.unwrap();
+    assert_eq!(output, jpeg::clean_metadata(&data).unwrap());
+
+    let data = load_test_image("png/metadata/metadata_text.png");
+    let options = StreamOptions {
+        png_options: png::CleanOptions {
+            preserve_text: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let output = read_all(CleanReader::with_options(data.as_slice(), options.clone()))
+        .await
+        .unwrap();
+    assert_eq!(
+        output,
+        png::clean_chunks_with_options(&data, &options.png_options).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_clean_reader_errors() {
+    // 入力の上限
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let options = StreamOptions {
+        max_input_size: data.len() - 1,
+        ..Default::default()
+    };
+    let err = read_all(CleanReader::with_options(data.as_slice(), options))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let inner = err.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+    assert!(matches!(inner, Error::LimitExceeded(_)));
+
+    // 画像ではない入力
+    let err = read_all(CleanReader::new(b"not an image at all".as_slice()))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    // 軽量化に失敗した後も、空の出力（EOF）ではなくエラーを返し続ける
+    let mut reader = CleanReader::new(&data[..20]);
+    let mut buf = [0; 16];
+    let err = reader.read(&mut buf).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let again = reader.read(&mut buf).await.unwrap_err();
+    assert_eq!(again.kind(), err.kind());
+    assert_eq!(again.to_string(), err.to_string());
+}
+
+#[tokio::test]
+async fn test_clean_reader_progress() {
+    use std::sync::{Arc, Mutex};
+    use web_image_meta::{ProgressCallback, ProgressStage};
+
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let options = StreamOptions {
+        progress: Some(ProgressCallback::new(move |p| sink.lock().unwrap().push(p))),
+        ..Default::default()
+    };
+    let output = read_all(CleanReader::with_options(data.as_slice(), options))
+        .await
+        .unwrap();
+
+    let events = events.lock().unwrap();
+    assert_eq!(events[0].stage, ProgressStage::ValidateInput);
+    assert!(events.iter().any(|p| p.stage == ProgressStage::Write));
+    let last = events.last().unwrap();
+    assert_eq!(last.stage, ProgressStage::ValidateOutput);
+    assert_eq!(last.processed, output.len());
+}
+
+#[tokio::test]
+async fn test_clean_reader_cancelled() {
+    use web_image_meta::CancellationToken;
+
+    let data = load_test_image("jpeg/metadata/metadata_full_exif.jpg");
+    let token = CancellationToken::new();
+    let options = StreamOptions {
+        cancellation: Some(token.clone()),
+        ..Default::default()
+    };
+    let output = read_all(CleanReader::with_options(data.as_slice(), options.clone()))
+        .await
+        .unwrap();
+    assert_eq!(output, jpeg::clean_metadata(&data).unwrap());
+
+    token.cancel();
+    let err = read_all(CleanReader::with_options(data.as_slice(), options))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let inner = err.get_ref().unwrap().downcast_ref::<Error>().unwrap();
+    assert!(matches!(inner, Error::Cancelled));
+}
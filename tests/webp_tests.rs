@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::webp;
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+/// チャンクからWebPファイルを組み立てる
+fn build_webp(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (fourcc, data) in chunks {
+        body.extend_from_slice(*fourcc);
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            body.push(0);
+        }
+    }
+    let mut output = b"RIFF".to_vec();
+    output.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    output.extend_from_slice(&body);
+    output
+}
+
+/// 幅と高さを指定したVP8Lチャンクの内容（画素データはダミー）
+fn vp8l(width: u32, height: u32, alpha: bool) -> Vec<u8> {
+    let bits = (width - 1) | ((height - 1) << 14) | ((alpha as u32) << 28);
+    let mut data = vec![0x2F];
+    data.extend_from_slice(&bits.to_le_bytes());
+    data.extend_from_slice(&[0; 7]);
+    data
+}
+
+/// チャンクのFourCCを列挙
+fn list_chunks(data: &[u8]) -> Vec<[u8; 4]> {
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        chunks.push(data[pos..pos + 4].try_into().unwrap());
+        pos += 8 + size + (size & 1);
+    }
+    chunks
+}
+
+/// JPEGのAPP2からICCプロファイルを取り出す
+fn extract_jpeg_icc(data: &[u8]) -> Vec<u8> {
+    let mut profile = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos + 1] != 0xDA {
+        let size = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let payload = &data[pos + 4..pos + 2 + size];
+        if data[pos + 1] == 0xE2 && payload.starts_with(b"ICC_PROFILE\0") {
+            profile.extend_from_slice(&payload[14..]);
+        }
+        pos += 2 + size;
+    }
+    profile
+}
+
+#[test]
+fn test_write_and_read_icc_profile() {
+    let p3 = extract_jpeg_icc(&load_test_image("jpeg/icc/icc_applep3.jpg"));
+    let data = build_webp(&[(b"VP8L", &vp8l(300, 200, true))]);
+    assert_eq!(webp::read_icc_profile(&data).unwrap(), None);
+
+    // シンプル形式はVP8Xを追加して拡張形式に変換される
+    let written = webp::write_icc_profile(&data, &p3).unwrap();
+    assert_eq!(list_chunks(&written), [*b"VP8X", *b"ICCP", *b"VP8L"]);
+    assert_eq!(webp::read_icc_profile(&written).unwrap(), Some(p3.clone()));
+
+    let vp8x = &written[20..30];
+    assert_eq!(vp8x[0], 0x20 | 0x10);
+    assert_eq!(u32::from_le_bytes([vp8x[4], vp8x[5], vp8x[6], 0]) + 1, 300);
+    assert_eq!(u32::from_le_bytes([vp8x[7], vp8x[8], vp8x[9], 0]) + 1, 200);
+
+    // 既存のプロファイルは置き換えられる
+    let srgb = extract_jpeg_icc(&load_test_image("jpeg/icc/icc_srgb.jpg"));
+    let replaced = webp::write_icc_profile(&written, &srgb).unwrap();
+    assert_eq!(list_chunks(&replaced), [*b"VP8X", *b"ICCP", *b"VP8L"]);
+    assert_eq!(webp::read_icc_profile(&replaced).unwrap(), Some(srgb));
+}
+
+#[test]
+fn test_clean_metadata_preserves_wide_gamut_icc() {
+    let p3 = extract_jpeg_icc(&load_test_image("jpeg/icc/icc_applep3.jpg"));
+    let vp8x = [0x20 | 0x08 | 0x04, 0, 0, 0, 9, 0, 0, 9, 0, 0];
+    let data = build_webp(&[
+        (b"VP8X", &vp8x),
+        (b"ICCP", &p3),
+        (b"VP8L", &vp8l(10, 10, false)),
+        (b"EXIF", b"Exif\0\0MM"),
+        (b"XMP ", b"<x:xmpmeta/>"),
+    ]);
+
+    let options = webp::CleanOptions {
+        replace_srgb_icc: true,
+        ..Default::default()
+    };
+    let cleaned = webp::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(list_chunks(&cleaned), [*b"VP8X", *b"ICCP", *b"VP8L"]);
+    assert_eq!(cleaned[20], 0x20);
+    assert_eq!(webp::read_icc_profile(&cleaned).unwrap(), Some(p3));
+}
+
+#[test]
+fn test_clean_metadata_replaces_srgb_icc() {
+    let srgb = extract_jpeg_icc(&load_test_image("jpeg/icc/icc_srgb.jpg"));
+    let data =
+        webp::write_icc_profile(&build_webp(&[(b"VP8L", &vp8l(8, 8, false))]), &srgb).unwrap();
+
+    // デフォルトではsRGBのプロファイルも保持
+    let cleaned = webp::clean_metadata(&data).unwrap();
+    assert_eq!(webp::read_icc_profile(&cleaned).unwrap(), Some(srgb));
+
+    let options = webp::CleanOptions {
+        replace_srgb_icc: true,
+        ..Default::default()
+    };
+    let cleaned = webp::clean_metadata_with_options(&data, &options).unwrap();
+    assert_eq!(webp::read_icc_profile(&cleaned).unwrap(), None);
+    assert_eq!(cleaned[20] & 0x20, 0);
+}
+
+#[test]
+fn test_invalid_webp() {
+    assert!(webp::read_icc_profile(b"not a webp file").is_err());
+    assert!(webp::read_icc_profile(&build_webp(&[(b"EXIF", b"Exif")])).is_err());
+
+    // 宣言されたサイズがファイルを超える
+    let mut data = build_webp(&[(b"VP8L", &vp8l(8, 8, false))]);
+    data.truncate(data.len() - 4);
+    assert!(webp::clean_metadata(&data).is_err());
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::Path;
+use web_image_meta::{jpeg, xmp};
+
+fn load_test_image(path: &str) -> Vec<u8> {
+    let full_path = Path::new("tests/test_data").join(path);
+    fs::read(full_path).unwrap_or_else(|_| panic!("Failed to read test image: {}", path))
+}
+
+#[test]
+fn test_rating_label_urgency() {
+    let data = load_test_image("jpeg/metadata/metadata_xmp.jpg");
+    let packet = jpeg::read_xmp(&data).unwrap().unwrap();
+    assert_eq!(xmp::read_rating(&packet), None);
+
+    let packet = xmp::set_rating(&packet, Some(4)).unwrap();
+    let packet = xmp::set_label(&packet, Some("Red & Blue")).unwrap();
+    let packet = xmp::set_urgency(&packet, Some(2)).unwrap();
+    assert!(packet.contains("xmlns:xmp="));
+    assert!(packet.contains("xmlns:photoshop="));
+
+    // JPEGに書き戻しても既存のプロパティは保持
+    let output = jpeg::write_xmp(&data, &packet).unwrap();
+    let packet = jpeg::read_xmp(&output).unwrap().unwrap();
+    assert_eq!(xmp::read_rating(&packet), Some(4));
+    assert_eq!(xmp::read_label(&packet), Some("Red & Blue".to_string()));
+    assert_eq!(xmp::read_urgency(&packet), Some(2));
+    assert!(packet.contains("Test XMP Data"));
+
+    // 上書きと削除
+    let packet = xmp::set_rating(&packet, Some(-1)).unwrap();
+    assert_eq!(xmp::read_rating(&packet), Some(-1));
+    assert_eq!(packet.matches("xmp:Rating=").count(), 1);
+    let packet = xmp::set_label(&packet, None).unwrap();
+    assert_eq!(xmp::read_label(&packet), None);
+    assert!(!packet.contains("xmp:Label"));
+
+    assert!(xmp::set_rating(&packet, Some(6)).is_err());
+    assert!(xmp::set_urgency(&packet, Some(9)).is_err());
+}
+
+#[test]
+fn test_element_form_and_new_packet() {
+    // 要素形式で記録されたプロパティ
+    let packet = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+ <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+  <xmp:Rating>3.0</xmp:Rating>
+  <xmp:Label>Select&#x21;</xmp:Label>
+ </rdf:Description>
+</rdf:RDF></x:xmpmeta>"#;
+    assert_eq!(xmp::read_rating(packet), Some(3));
+    assert_eq!(xmp::read_label(packet), Some("Select!".to_string()));
+
+    let updated = xmp::set_rating(packet, Some(5)).unwrap();
+    assert!(!updated.contains("<xmp:Rating>"));
+    assert_eq!(xmp::read_rating(&updated), Some(5));
+    assert_eq!(xmp::read_label(&updated), Some("Select!".to_string()));
+
+    // XMPのない画像には新しいパケットを作成して挿入
+    let data = load_test_image("jpeg/metadata/metadata_none.jpg");
+    assert_eq!(jpeg::read_xmp(&data).unwrap(), None);
+    let packet = xmp::set_rating("", Some(1)).unwrap();
+    let output = jpeg::write_xmp(&data, &packet).unwrap();
+    let read = jpeg::read_xmp(&output).unwrap().unwrap();
+    assert_eq!(xmp::read_rating(&read), Some(1));
+
+    // 空のパケットでXMPを削除
+    let output = jpeg::write_xmp(&output, "").unwrap();
+    assert_eq!(jpeg::read_xmp(&output).unwrap(), None);
+}